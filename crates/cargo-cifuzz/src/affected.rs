@@ -0,0 +1,136 @@
+//! Selecting the fuzz tests which are affected by the changes since a
+//! git revision, e.g. the ones of a pull request.
+//!
+//! A fuzz test is affected if its coverage report, which
+//! `cargo cifuzz coverage` stores in `.cifuzz-coverage/<fuzz_test>`,
+//! covers a line of a changed file. Fuzz tests without a coverage
+//! report are always affected, so are all fuzz tests by changes to the
+//! files which determine how they are built, e.g. the Cargo.lock.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::lcov;
+
+/// The files whose changes affect all fuzz tests.
+const BUILD_FILES: &[&str] = &[
+    "Cargo.toml",
+    "Cargo.lock",
+    "build.rs",
+    "cifuzz.yaml",
+    "rust-toolchain",
+    "rust-toolchain.toml",
+];
+
+/// Why a fuzz test is affected by the changes.
+#[derive(Debug, PartialEq)]
+pub enum Reason {
+    NoCoverageReport,
+    BuildFile(PathBuf),
+    Covered(PathBuf),
+}
+
+impl std::fmt::Display for Reason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Reason::NoCoverageReport => write!(f, "has no coverage report"),
+            Reason::BuildFile(path) => write!(f, "is built with the changed {}", path.display()),
+            Reason::Covered(path) => write!(f, "covers the changed {}", path.display()),
+        }
+    }
+}
+
+/// Returns the files in the project directory which changed between
+/// the merge base of the revision and HEAD, relative to the project
+/// directory.
+pub fn changed_files(project_dir: &Path, rev: &str) -> Result<Vec<PathBuf>> {
+    let output = Command::new("git")
+        .args(["diff", "--name-only", "--relative"])
+        .arg(format!("{rev}..."))
+        .current_dir(project_dir)
+        .output()
+        .context("failed to execute git")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to list the changes since {rev}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Reads the coverage report of the fuzz test, with the paths relative
+/// to the project directory, or returns `None` if it doesn't exist.
+pub fn coverage_report(project_dir: &Path, fuzz_test: &str) -> Result<Option<lcov::Report>> {
+    let path = project_dir
+        .join(".cifuzz-coverage")
+        .join(fuzz_test)
+        .join("coverage.lcov");
+    let tracefile = match std::fs::read_to_string(&path) {
+        Ok(tracefile) => tracefile,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", path.display())),
+    };
+    let report =
+        lcov::parse(&tracefile).with_context(|| format!("failed to parse {}", path.display()))?;
+    Ok(Some(report.strip_prefix(project_dir)))
+}
+
+/// Returns why a fuzz test with the coverage report is affected by the
+/// changed files, or `None` if it isn't.
+pub fn affected(changed: &[PathBuf], report: Option<&lcov::Report>) -> Option<Reason> {
+    if let Some(path) = changed.iter().find(|path| {
+        path.file_name()
+            .is_some_and(|name| BUILD_FILES.iter().any(|f| name == *f))
+    }) {
+        return Some(Reason::BuildFile(path.clone()));
+    }
+    let Some(report) = report else {
+        return Some(Reason::NoCoverageReport);
+    };
+    changed
+        .iter()
+        .find(|path| {
+            report
+                .files
+                .get(path.to_string_lossy().as_ref())
+                .is_some_and(|file| file.lines.values().any(|&count| count > 0))
+        })
+        .map(|path| Reason::Covered(path.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn affected_by_changes() {
+        let report = lcov::parse(
+            "SF:/p/src/parser.rs\nDA:1,3\nend_of_record\nSF:/p/src/unused.rs\nDA:1,0\nend_of_record\n",
+        )
+        .unwrap()
+        .strip_prefix(Path::new("/p"));
+        let changed = |paths: &[&str]| paths.iter().map(PathBuf::from).collect::<Vec<_>>();
+
+        assert_eq!(
+            affected(&changed(&["README.md", "src/parser.rs"]), Some(&report)),
+            Some(Reason::Covered("src/parser.rs".into()))
+        );
+        assert_eq!(affected(&changed(&["src/unused.rs"]), Some(&report)), None);
+        assert_eq!(affected(&changed(&["src/other.rs"]), Some(&report)), None);
+        assert_eq!(
+            affected(&changed(&["crates/foo/Cargo.toml"]), Some(&report)),
+            Some(Reason::BuildFile("crates/foo/Cargo.toml".into()))
+        );
+        assert_eq!(
+            affected(&changed(&["src/other.rs"]), None),
+            Some(Reason::NoCoverageReport)
+        );
+    }
+}
@@ -0,0 +1,1390 @@
+//! Building fuzz tests with cargo.
+//!
+//! Fuzz tests are libtest tests generated by the `#[fuzz_test]` macro,
+//! so they are built via `cargo test --no-run`. When built with
+//! `--cfg fuzzing`, the test of a fuzz test `foo` is called `foo::fuzz`
+//! and runs `foo` under libFuzzer.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::build_cache;
+use crate::config::{self, ProjectConfig, PROJECT_CONFIG_FILE};
+use crate::corpus;
+use crate::exit_code::Failure;
+use crate::instrument;
+use crate::log;
+use crate::native;
+
+/// The rustc flags needed to build fuzz tests for libFuzzer: The
+/// `fuzzing` cfg selects the libFuzzer harness and the SanitizerCoverage
+/// flags provide the coverage feedback libFuzzer needs. Tracing the
+/// operands of comparisons, divisions and array indices is what the
+/// value profile of libFuzzer (`--use-value-profile`) is based on.
+/// Debug assertions and overflow checks are enabled even in release
+/// builds, because silently wrapping arithmetic hides bugs the fuzzer
+/// would find. The flags of RUSTFLAGS take precedence over the ones of
+/// the cargo profile.
+const FUZZING_RUSTFLAGS: &[&str] = &[
+    "--cfg",
+    "fuzzing",
+    "-Cpasses=sancov-module",
+    "-Cllvm-args=-sanitizer-coverage-level=4",
+    "-Cllvm-args=-sanitizer-coverage-inline-8bit-counters",
+    "-Cllvm-args=-sanitizer-coverage-pc-table",
+    "-Cllvm-args=-sanitizer-coverage-trace-compares",
+    "-Cllvm-args=-sanitizer-coverage-trace-divs",
+    "-Cllvm-args=-sanitizer-coverage-trace-geps",
+    // Debug info is needed for meaningful stack traces
+    "-Cdebuginfo=1",
+    "-Cforce-frame-pointers=yes",
+    "-Cdebug-assertions=on",
+    "-Coverflow-checks=on",
+];
+
+/// The clang flags for the native code of the fuzz tests built for
+/// libFuzzer, see [`native`], which correspond to the SanitizerCoverage
+/// flags of the Rust code. The frame pointers and the debug info make
+/// the native frames appear symbolized in the stack traces.
+const FUZZING_CFLAGS: &[&str] = &["-fsanitize=fuzzer-no-link", "-g", "-fno-omit-frame-pointer"];
+
+/// The rustc flags which make the libFuzzer harness define the symbols
+/// of custom mutators, which replace the built-in mutations of libFuzzer
+/// and are therefore only defined for fuzz tests which set a mutator.
+/// They aren't part of [`Builder::rustflags`], because they don't affect
+/// the execution of an input.
+const CUSTOM_MUTATOR_RUSTFLAGS: &[&str] = &["--cfg", "cifuzz_custom_mutator"];
+
+/// The rustc flags needed to build fuzz tests for coverage reports. The
+/// libFuzzer harness is used to replay the corpus, but without the
+/// SanitizerCoverage instrumentation, which isn't needed for that.
+const COVERAGE_RUSTFLAGS: &[&str] = &["--cfg", "fuzzing", "-Cinstrument-coverage"];
+
+/// The rustc flags needed to build fuzz tests for AFL++: The
+/// `cifuzz_afl` cfg selects the AFL++ harness instead of the libFuzzer
+/// one, and the SanitizerCoverage flags are those which afl.rs uses to
+/// provide the edge coverage of AFL++. The runtime of AFL++ is linked
+/// in addition, see [`afl_runtime`].
+const AFL_RUSTFLAGS: &[&str] = &[
+    "--cfg",
+    "fuzzing",
+    "--cfg",
+    "cifuzz_afl",
+    "-Cpasses=sancov-module",
+    "-Cllvm-args=-sanitizer-coverage-level=3",
+    "-Cllvm-args=-sanitizer-coverage-trace-pc-guard",
+    "-Cllvm-args=-sanitizer-coverage-prune-blocks=0",
+    "-Cllvm-args=-sanitizer-coverage-trace-compares",
+    "-Cdebuginfo=1",
+    "-Cforce-frame-pointers=yes",
+    "-Cdebug-assertions=on",
+    "-Coverflow-checks=on",
+];
+
+/// The clang flags for the native code of the fuzz tests built for
+/// AFL++, whose runtime provides the callbacks of these
+/// SanitizerCoverage flags as well.
+const AFL_CFLAGS: &[&str] = &[
+    "-fsanitize-coverage=trace-pc-guard,trace-cmp",
+    "-g",
+    "-fno-omit-frame-pointer",
+];
+
+/// The directories in which AFL++ installs its runtime by default.
+const AFL_RUNTIME_DIRS: &[&str] = &["/usr/local/lib/afl", "/usr/lib/afl"];
+
+/// The rustc flags needed to build fuzz tests for honggfuzz: The
+/// `cifuzz_honggfuzz` cfg selects the honggfuzz harness, and the
+/// SanitizerCoverage flags are those which honggfuzz-rs uses. The
+/// runtime of honggfuzz is linked in addition, see
+/// [`honggfuzz_runtime`].
+const HONGGFUZZ_RUSTFLAGS: &[&str] = &[
+    "--cfg",
+    "fuzzing",
+    "--cfg",
+    "cifuzz_honggfuzz",
+    "-Cpasses=sancov-module",
+    "-Cllvm-args=-sanitizer-coverage-level=4",
+    "-Cllvm-args=-sanitizer-coverage-trace-pc-guard",
+    "-Cllvm-args=-sanitizer-coverage-trace-divs",
+    "-Cllvm-args=-sanitizer-coverage-trace-compares",
+    "-Cdebuginfo=1",
+    "-Cforce-frame-pointers=yes",
+    "-Cdebug-assertions=on",
+    "-Coverflow-checks=on",
+];
+
+/// The clang flags for the native code of the fuzz tests built for
+/// honggfuzz.
+const HONGGFUZZ_CFLAGS: &[&str] = &[
+    "-fsanitize-coverage=trace-pc-guard,trace-cmp,trace-div",
+    "-g",
+    "-fno-omit-frame-pointer",
+];
+
+/// The static libraries of the honggfuzz runtime, in link order.
+const HONGGFUZZ_LIBRARIES: &[&str] = &["libhfuzz.a", "libhfcommon.a"];
+
+/// The directories in which honggfuzz installs its runtime by default.
+const HONGGFUZZ_RUNTIME_DIRS: &[&str] = &["/usr/local/lib/honggfuzz", "/usr/lib/honggfuzz"];
+
+/// The rustc flags needed to build fuzz tests for wasm32-wasip1, which
+/// run under libFuzzer in cifuzz-wasm-driver: The `cifuzz_wasm` cfg
+/// selects the wasm harness, which exports the functions the driver
+/// calls. The driver copies the inline 8-bit counters of the module to
+/// libFuzzer after every input, the other SanitizerCoverage callbacks
+/// aren't available in the module.
+const WASM_RUSTFLAGS: &[&str] = &[
+    "--cfg",
+    "fuzzing",
+    "--cfg",
+    "cifuzz_wasm",
+    "-Cpasses=sancov-module",
+    "-Cllvm-args=-sanitizer-coverage-level=3",
+    "-Cllvm-args=-sanitizer-coverage-inline-8bit-counters",
+    "-Clink-arg=--export=cifuzz_input_buffer",
+    "-Clink-arg=--export=cifuzz_test_one_input",
+    "-Cdebuginfo=1",
+    "-Cdebug-assertions=on",
+    "-Coverflow-checks=on",
+];
+
+/// The target of the fuzz tests run by cifuzz-wasm-driver.
+const WASM_TARGET: &str = "wasm32-wasip1";
+
+/// The environment variable with the path of cifuzz-wasm-driver, which
+/// is otherwise searched next to cargo-cifuzz and in the PATH.
+const WASM_DRIVER_ENV: &str = "CIFUZZ_WASM_DRIVER";
+
+/// The rustc flags which instrument the code for AddressSanitizer, which
+/// detects memory errors like out-of-bounds accesses and
+/// use-after-free in unsafe code. Only the crates are instrumented, not
+/// the standard library, which would require rebuilding it.
+const ADDRESS_SANITIZER_RUSTFLAGS: &[&str] = &["-Zsanitizer=address"];
+
+/// The rustc flags for ThreadSanitizer, which detects data races.
+const THREAD_SANITIZER_RUSTFLAGS: &[&str] = &["-Zsanitizer=thread"];
+
+/// The rustc flags for MemorySanitizer, which detects reads of
+/// uninitialized memory, e.g. of buffers filled by C code. Tracking the
+/// origins makes the reports point to the allocation of the memory.
+const MEMORY_SANITIZER_RUSTFLAGS: &[&str] =
+    &["-Zsanitizer=memory", "-Zsanitizer-memory-track-origins"];
+
+/// The rustc flags for LeakSanitizer, which detects memory which is
+/// never freed, without the overhead of AddressSanitizer.
+const LEAK_SANITIZER_RUSTFLAGS: &[&str] = &["-Zsanitizer=leak"];
+
+/// The name of the test which the `#[fuzz_test]` macro generates in the
+/// module of the fuzz test when building with `--cfg fuzzing`.
+const FUZZ_TEST_HARNESS_NAME: &str = "fuzz";
+
+/// The environment variable with the file in which the harnesses of the
+/// fuzz tests register them instead of fuzzing, see
+/// [`Builder::discover`].
+// Must be kept in sync with crates/cifuzz/src/registry.rs
+const REGISTRY_FILE_ENV: &str = "CIFUZZ_REGISTRY_FILE";
+
+/// What the fuzz tests are built for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildMode {
+    /// Fuzzing with libFuzzer
+    Fuzzing,
+    /// Fuzzing with AFL++
+    Afl,
+    /// Fuzzing with honggfuzz
+    Honggfuzz,
+    /// Fuzzing with libFuzzer in cifuzz-wasm-driver
+    Wasm,
+    /// Collecting the coverage of the inputs in the corpus
+    Coverage,
+}
+
+/// A sanitizer which detects bugs which don't cause a panic. Sanitizers
+/// require a nightly toolchain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sanitizer {
+    Address,
+    Thread,
+    Memory,
+    Leak,
+}
+
+impl Sanitizer {
+    /// The name of the sanitizer as used by rustc and in directory names.
+    pub fn name(self) -> &'static str {
+        match self {
+            Sanitizer::Address => "address",
+            Sanitizer::Thread => "thread",
+            Sanitizer::Memory => "memory",
+            Sanitizer::Leak => "leak",
+        }
+    }
+
+    fn rustflags(self) -> &'static [&'static str] {
+        match self {
+            Sanitizer::Address => ADDRESS_SANITIZER_RUSTFLAGS,
+            Sanitizer::Thread => THREAD_SANITIZER_RUSTFLAGS,
+            Sanitizer::Memory => MEMORY_SANITIZER_RUSTFLAGS,
+            Sanitizer::Leak => LEAK_SANITIZER_RUSTFLAGS,
+        }
+    }
+
+    /// The clang flags which instrument native code for the sanitizer,
+    /// using the same runtime as the Rust code.
+    fn cflags(self) -> &'static [&'static str] {
+        match self {
+            Sanitizer::Address => &["-fsanitize=address"],
+            Sanitizer::Thread => &["-fsanitize=thread"],
+            Sanitizer::Memory => &["-fsanitize=memory", "-fsanitize-memory-track-origins"],
+            Sanitizer::Leak => &["-fsanitize=leak"],
+        }
+    }
+
+    /// Whether the standard library must be instrumented as well, which
+    /// requires rebuilding it from the rust-src component. Without it,
+    /// MemorySanitizer reports all memory initialized by the standard
+    /// library and ThreadSanitizer misses its synchronization.
+    fn requires_build_std(self) -> bool {
+        match self {
+            Sanitizer::Address | Sanitizer::Leak => false,
+            Sanitizer::Thread | Sanitizer::Memory => true,
+        }
+    }
+
+    /// Whether the sanitizer includes LeakSanitizer, which libFuzzer
+    /// needs to detect leaks.
+    pub fn detects_leaks(self) -> bool {
+        match self {
+            Sanitizer::Address | Sanitizer::Leak => true,
+            Sanitizer::Thread | Sanitizer::Memory => false,
+        }
+    }
+
+    /// The environment variable with the runtime options of the
+    /// sanitizer.
+    pub fn options_env(self) -> &'static str {
+        match self {
+            Sanitizer::Address => "ASAN_OPTIONS",
+            Sanitizer::Thread => "TSAN_OPTIONS",
+            Sanitizer::Memory => "MSAN_OPTIONS",
+            Sanitizer::Leak => "LSAN_OPTIONS",
+        }
+    }
+
+    /// The runtime options of the sanitizer. Leaks are only reported if
+    /// `detect_leaks` is set.
+    pub fn options(self, detect_leaks: bool) -> &'static [&'static str] {
+        match self {
+            // Data races are only reported, not treated as crashes, by
+            // default
+            Sanitizer::Thread => &["halt_on_error=1"],
+            // AddressSanitizer checks for leaks at exit by default, which
+            // can't be attributed to an input
+            Sanitizer::Address if !detect_leaks => &["detect_leaks=0"],
+            Sanitizer::Address | Sanitizer::Memory | Sanitizer::Leak => &[],
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BuilderOptions {
+    pub project_dir: PathBuf,
+    pub mode: BuildMode,
+    /// The sanitizer to build the fuzz tests with, only used for fuzzing
+    pub sanitizer: Option<Sanitizer>,
+    /// Additional arguments to pass to `cargo test`
+    pub args: Vec<String>,
+}
+
+/// The result of building a fuzz test.
+#[derive(Debug, Clone)]
+pub struct BuildResult {
+    /// The name of the fuzz test function
+    pub name: String,
+    /// The full path of the libtest test which runs the fuzz test
+    pub test_name: String,
+    /// The test executable containing the fuzz test
+    pub executable: PathBuf,
+    /// The directory of the package containing the fuzz test. cargo
+    /// runs tests in this directory, so we do the same.
+    pub package_dir: PathBuf,
+    /// The directory in which libFuzzer stores the inputs it generates
+    pub generated_corpus: PathBuf,
+    /// The seed corpus directories of the fuzz test which exist
+    pub seed_corpus_dirs: Vec<PathBuf>,
+    /// The `<fuzz_test>.dict` files of the fuzz test
+    pub dictionaries: Vec<PathBuf>,
+}
+
+/// A fuzz test found by [`Builder::discover`].
+#[derive(Debug)]
+pub struct DiscoveredFuzzTest {
+    /// The path of the fuzz test function, starting with the name of
+    /// the crate, e.g. "foo::parser::my_fuzz_test"
+    pub path: String,
+    /// The source file containing the fuzz test
+    pub source_file: PathBuf,
+    /// The line of the `#[fuzz_test]` attribute
+    pub line: u32,
+    pub build: BuildResult,
+}
+
+/// A test executable built by cargo.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestExecutable {
+    pub path: PathBuf,
+    pub package_dir: PathBuf,
+}
+
+pub struct Builder {
+    opts: BuilderOptions,
+    /// The RUSTFLAGS which are used instead of the ones of the
+    /// environment
+    env_rustflags: Option<Vec<String>>,
+}
+
+impl Builder {
+    pub fn new(opts: BuilderOptions) -> Self {
+        Builder {
+            opts,
+            env_rustflags: None,
+        }
+    }
+
+    /// Builds with the flags instead of the RUSTFLAGS of the
+    /// environment, e.g. with the ones recorded with a finding.
+    pub fn set_env_rustflags(&mut self, rustflags: Vec<String>) {
+        self.env_rustflags = Some(rustflags);
+    }
+
+    /// The directory for the files of the commands using the build, e.g.
+    /// the working directories of the fuzzers. The build itself is
+    /// cached in .cifuzz/build-cache, see [`build_cache`].
+    pub fn build_dir(&self) -> PathBuf {
+        let build_dir = self.opts.project_dir.join(".cifuzz-build");
+        match self.opts.mode {
+            BuildMode::Fuzzing => build_dir
+                .join("libfuzzer")
+                .join(self.sanitizer().map_or("none", Sanitizer::name)),
+            BuildMode::Afl => build_dir.join("afl"),
+            BuildMode::Honggfuzz => build_dir.join("honggfuzz"),
+            BuildMode::Wasm => build_dir.join("wasm"),
+            BuildMode::Coverage => build_dir.join("coverage"),
+        }
+    }
+
+    /// The config of the project, which may not have a cifuzz.yaml when
+    /// its directory is passed explicitly.
+    fn project_config(&self) -> Result<ProjectConfig> {
+        if !self.opts.project_dir.join(PROJECT_CONFIG_FILE).is_file() {
+            return Ok(ProjectConfig::default());
+        }
+        config::parse_project_config(&self.opts.project_dir)
+    }
+
+    /// The name of the build mode in the build cache.
+    fn cache_name(&self) -> String {
+        match self.opts.mode {
+            BuildMode::Fuzzing => format!(
+                "libfuzzer-{}",
+                self.sanitizer().map_or("none", Sanitizer::name)
+            ),
+            BuildMode::Afl => "afl".to_string(),
+            BuildMode::Honggfuzz => "honggfuzz".to_string(),
+            BuildMode::Wasm => "wasm".to_string(),
+            BuildMode::Coverage => "coverage".to_string(),
+        }
+    }
+
+    /// The RUSTFLAGS with which the fuzz tests are built, the flags
+    /// from the environment followed by the flags of the build mode.
+    pub fn rustflags(&self) -> Vec<String> {
+        let mut rustflags: Vec<String> = match &self.env_rustflags {
+            Some(rustflags) => rustflags.clone(),
+            None => std::env::var("RUSTFLAGS")
+                .map(|f| f.split_whitespace().map(String::from).collect())
+                .unwrap_or_default(),
+        };
+        let mode_flags = match self.opts.mode {
+            BuildMode::Fuzzing => FUZZING_RUSTFLAGS,
+            BuildMode::Afl => AFL_RUSTFLAGS,
+            BuildMode::Honggfuzz => HONGGFUZZ_RUSTFLAGS,
+            BuildMode::Wasm => WASM_RUSTFLAGS,
+            BuildMode::Coverage => COVERAGE_RUSTFLAGS,
+        };
+        rustflags.extend(mode_flags.iter().map(|f| f.to_string()));
+        if self.opts.mode == BuildMode::Afl {
+            if let Some(runtime) = afl_runtime() {
+                rustflags.push(format!("-Clink-arg={}", runtime.display()));
+            }
+        }
+        if self.opts.mode == BuildMode::Honggfuzz {
+            for library in honggfuzz_runtime().unwrap_or_default() {
+                rustflags.push(format!("-Clink-arg={}", library.display()));
+            }
+        }
+        if let Some(sanitizer) = self.sanitizer() {
+            rustflags.extend(sanitizer.rustflags().iter().map(|f| f.to_string()));
+        }
+        rustflags
+    }
+
+    /// The clang flags with which the native code of the fuzz tests is
+    /// built, see [`native`]. Native code isn't instrumented for
+    /// coverage reports, whose runtime must match the one of rustc, and
+    /// for wasm, which requires a C toolchain for wasm32-wasip1.
+    pub fn cflags(&self) -> Vec<String> {
+        let mode_flags: &[&str] = match self.opts.mode {
+            BuildMode::Fuzzing => FUZZING_CFLAGS,
+            BuildMode::Afl => AFL_CFLAGS,
+            BuildMode::Honggfuzz => HONGGFUZZ_CFLAGS,
+            BuildMode::Wasm | BuildMode::Coverage => return Vec::new(),
+        };
+        let mut cflags: Vec<String> = mode_flags.iter().map(|f| f.to_string()).collect();
+        if let Some(sanitizer) = self.sanitizer() {
+            cflags.extend(sanitizer.cflags().iter().map(|f| f.to_string()));
+        }
+        cflags
+    }
+
+    /// Additional flags of `cargo test` required by the build.
+    fn cargo_flags(&self) -> Vec<&'static str> {
+        match self.sanitizer() {
+            // Building the standard library requires --target, which
+            // is always passed
+            Some(sanitizer) if sanitizer.requires_build_std() => vec!["-Zbuild-std"],
+            _ => Vec::new(),
+        }
+    }
+
+    fn sanitizer(&self) -> Option<Sanitizer> {
+        match self.opts.mode {
+            BuildMode::Fuzzing => self.opts.sanitizer,
+            BuildMode::Afl | BuildMode::Honggfuzz | BuildMode::Wasm | BuildMode::Coverage => None,
+        }
+    }
+
+    /// The target triple the fuzz tests are built for, the host unless
+    /// they're built for wasm.
+    pub fn target(&self) -> Result<String> {
+        match self.opts.mode {
+            BuildMode::Wasm => Ok(WASM_TARGET.to_string()),
+            _ => host_target(),
+        }
+    }
+
+    /// Builds the test executables and returns the result for the
+    /// specified fuzz test.
+    pub fn build_for_run(&self, fuzz_test: &str) -> Result<BuildResult> {
+        let name = fuzz_test.rsplit("::").next().unwrap_or(fuzz_test);
+        let custom_mutator = self.opts.mode == BuildMode::Fuzzing
+            && corpus::sets_custom_mutator(&self.opts.project_dir, name)?;
+        let executables = self.build(custom_mutator)?;
+
+        let mut matches = Vec::new();
+        let mut all_fuzz_tests = Vec::new();
+        for executable in &executables {
+            for test in list_fuzz_tests(&executable.path)? {
+                if matches_fuzz_test(&test, fuzz_test) {
+                    matches.push((executable, test.clone()));
+                }
+                all_fuzz_tests.push(test);
+            }
+        }
+
+        let (executable, test_name) = match matches.len() {
+            1 => matches.pop().unwrap(),
+            0 if all_fuzz_tests.is_empty() => {
+                bail!("No fuzz tests found, fuzz tests are functions annotated with #[fuzz_test]")
+            }
+            0 => bail!(
+                "Fuzz test {:?} not found, available fuzz tests:\n  {}",
+                fuzz_test,
+                all_fuzz_tests
+                    .iter()
+                    .map(|t| t.strip_suffix("::fuzz").unwrap_or(t))
+                    .collect::<Vec<_>>()
+                    .join("\n  ")
+            ),
+            _ => bail!(
+                "Fuzz test {:?} is ambiguous, specify it by its full module path:\n  {}",
+                fuzz_test,
+                matches
+                    .iter()
+                    .map(|(_, t)| t.strip_suffix("::fuzz").unwrap_or(t))
+                    .collect::<Vec<_>>()
+                    .join("\n  ")
+            ),
+        };
+
+        self.build_result(executable, test_name)
+    }
+
+    /// Builds the test executables and returns all fuzz tests in them,
+    /// in the order of their paths. Unlike the tests listed by libtest,
+    /// the fuzz tests are only those whose harnesses register them.
+    pub fn discover(&self) -> Result<Vec<DiscoveredFuzzTest>> {
+        let executables = self.build(false)?;
+        let registry_dir = self.build_dir().join("registry");
+        if registry_dir.exists() {
+            std::fs::remove_dir_all(&registry_dir)
+                .with_context(|| format!("failed to remove {}", registry_dir.display()))?;
+        }
+        std::fs::create_dir_all(&registry_dir)
+            .with_context(|| format!("failed to create {}", registry_dir.display()))?;
+
+        let mut fuzz_tests = Vec::new();
+        for (i, executable) in executables.iter().enumerate() {
+            let harnesses = list_fuzz_tests(&executable.path)?;
+            if harnesses.is_empty() {
+                continue;
+            }
+            let registry = registry_dir.join(i.to_string());
+            for entry in register_fuzz_tests(&executable.path, &harnesses, &registry)? {
+                let Some(test_name) = harnesses.iter().find(|t| entry.is_harness(t)) else {
+                    continue;
+                };
+                let source_file = executable
+                    .package_dir
+                    .ancestors()
+                    .map(|dir| dir.join(&entry.file))
+                    .find(|path| path.is_file())
+                    .unwrap_or_else(|| PathBuf::from(&entry.file));
+                fuzz_tests.push(DiscoveredFuzzTest {
+                    path: entry.path,
+                    source_file,
+                    line: entry.line,
+                    build: self.build_result(executable, test_name.clone())?,
+                });
+            }
+        }
+        fuzz_tests.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(fuzz_tests)
+    }
+
+    fn build_result(&self, executable: &TestExecutable, test_name: String) -> Result<BuildResult> {
+        let name = fuzz_test_name(&test_name)
+            .expect("fuzz test harnesses have a parent module")
+            .to_string();
+        let generated_corpus = self.opts.project_dir.join(".cifuzz-corpus").join(&name);
+        let seed_corpus_dirs = seed_corpus_dirs(&executable.package_dir, &name)?;
+        let dictionaries = dictionary_files(&executable.package_dir, &name)?;
+
+        Ok(BuildResult {
+            name,
+            test_name,
+            executable: executable.path.clone(),
+            package_dir: executable.package_dir.clone(),
+            generated_corpus,
+            seed_corpus_dirs,
+            dictionaries,
+        })
+    }
+
+    /// Builds the test executables, with the custom mutator of the fuzz
+    /// tests if `custom_mutator` is set, see [`CUSTOM_MUTATOR_RUSTFLAGS`].
+    fn build(&self, custom_mutator: bool) -> Result<Vec<TestExecutable>> {
+        if let Some(sanitizer) = self.sanitizer() {
+            if !is_nightly()? {
+                bail!(
+                    "The {} sanitizer requires a nightly toolchain, e.g. `cargo +nightly cifuzz ...`",
+                    sanitizer.name()
+                );
+            }
+        }
+        if self.opts.mode == BuildMode::Afl && afl_runtime().is_none() {
+            bail!(
+                "The runtime of AFL++ (afl-compiler-rt.o) wasn't found, install AFL++ or set \
+                 AFL_PATH to the directory containing it"
+            );
+        }
+        if self.opts.mode == BuildMode::Honggfuzz && honggfuzz_runtime().is_none() {
+            bail!(
+                "The runtime of honggfuzz (libhfuzz.a and libhfcommon.a) wasn't found, install \
+                 honggfuzz or set HONGGFUZZ_PATH to the directory it was built in"
+            );
+        }
+        if self.opts.mode == BuildMode::Wasm && !is_target_installed(WASM_TARGET)? {
+            bail!(
+                "The {WASM_TARGET} target isn't installed, install it with \
+                 `rustup target add {WASM_TARGET}`"
+            );
+        }
+        let wasm_driver = match self.opts.mode {
+            BuildMode::Wasm => Some(wasm_driver()?),
+            _ => None,
+        };
+        let target = self.target()?;
+        let mut rustflags = self.rustflags();
+        if custom_mutator {
+            rustflags.extend(CUSTOM_MUTATOR_RUSTFLAGS.iter().map(|f| f.to_string()));
+        }
+        // With the instrument setting, the instrumentation flags are only
+        // applied to the selected crates by the rustc wrapper
+        let filter = match self.project_config()?.instrument {
+            Some(instrument) => {
+                let (flags, others) = instrument::split_flags(rustflags);
+                rustflags = others;
+                let filter = instrument::Filter::new(&instrument, &self.opts.project_dir, flags)?;
+                log::debug!("Instrumentation: {:?}", filter);
+                Some(filter)
+            }
+            None => None,
+        };
+        let compilers = native::Compilers::new(self.cflags());
+        if compilers.is_none() && !self.cflags().is_empty() {
+            log::debug!("clang wasn't found, native code isn't instrumented");
+        }
+
+        // We don't use the target directory of the project, to avoid
+        // that the instrumented build invalidates the regular build and
+        // vice versa
+        let cache = build_cache::Entry::open(
+            &self.opts.project_dir,
+            &self.cache_name(),
+            build_cache::Key {
+                toolchain: rustc_version()?,
+                target: target.clone(),
+                rustflags: rustflags.clone(),
+                cargo_flags: self.cargo_flags().iter().map(|f| f.to_string()).collect(),
+                instrument: filter.clone(),
+                native: compilers.clone(),
+            },
+        )?;
+        if let Some(executables) = cache.lookup(&self.opts.args)? {
+            log::debug!(
+                "Sources unchanged, using the build in {}",
+                cache.dir().display()
+            );
+            return match &wasm_driver {
+                Some(driver) => wrap_wasm_modules(executables, driver),
+                None => Ok(executables),
+            };
+        }
+
+        let start = SystemTime::now();
+        let mut cmd = Command::new(cargo());
+        cmd.args(["test", "--no-run"])
+            .args(["--message-format", "json-render-diagnostics"])
+            // Passing the target explicitly causes the RUSTFLAGS to
+            // not be applied to build scripts and proc macros, which
+            // can't be instrumented
+            .args(["--target", &target])
+            .args(self.cargo_flags())
+            .args(&self.opts.args)
+            .env("RUSTFLAGS", rustflags.join(" "))
+            .env("CARGO_TARGET_DIR", cache.target_dir())
+            .current_dir(&self.opts.project_dir)
+            .stdout(Stdio::piped());
+        if let Some(filter) = &filter {
+            let wrapper = std::env::current_exe().context("failed to find cargo-cifuzz")?;
+            cmd.env("RUSTC_WRAPPER", wrapper)
+                .env(instrument::WRAPPER_ENV, serde_json::to_string(filter)?);
+        }
+        if let Some(compilers) = &compilers {
+            cmd.envs(compilers.env(cache.dir())?);
+        }
+        log::debug!("Command: {:?}", cmd);
+
+        let output = cmd
+            .spawn()
+            .and_then(|child| child.wait_with_output())
+            .context("failed to execute cargo")?;
+        if !output.status.success() {
+            return Err(Failure::Build.error(anyhow!(
+                "Failed to build fuzz tests: cargo exited with {}",
+                output.status
+            )));
+        }
+
+        let messages = String::from_utf8_lossy(&output.stdout);
+        let executables = parse_test_executables(&messages)?;
+        cache.store(
+            &self.opts.args,
+            &executables,
+            parse_local_packages(&messages)?,
+            start,
+        )?;
+        build_cache::prune(&self.opts.project_dir, build_cache::MAX_ENTRIES)?;
+        match &wasm_driver {
+            Some(driver) => wrap_wasm_modules(executables, driver),
+            None => Ok(executables),
+        }
+    }
+}
+
+/// Returns the path of cifuzz-wasm-driver, from `$CIFUZZ_WASM_DRIVER`,
+/// next to cargo-cifuzz or in the PATH.
+fn wasm_driver() -> Result<PathBuf> {
+    if let Some(driver) = std::env::var_os(WASM_DRIVER_ENV) {
+        return Ok(PathBuf::from(driver));
+    }
+    let name = format!("cifuzz-wasm-driver{}", std::env::consts::EXE_SUFFIX);
+    let next_to_us = std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join(&name)));
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    next_to_us
+        .into_iter()
+        .chain(std::env::split_paths(&path).map(|dir| dir.join(&name)))
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            anyhow!(
+                "cifuzz-wasm-driver wasn't found, install it with `cargo install --git {} \
+                 cifuzz-wasm-driver` or set {WASM_DRIVER_ENV} to its path",
+                crate::workspace::CIFUZZ_GIT_URL
+            )
+        })
+}
+
+/// Replaces the wasm modules by scripts which execute them with
+/// cifuzz-wasm-driver, so that they can be executed like native test
+/// executables. The scripts are written next to the modules.
+fn wrap_wasm_modules(
+    executables: Vec<TestExecutable>,
+    driver: &Path,
+) -> Result<Vec<TestExecutable>> {
+    executables
+        .into_iter()
+        .map(|executable| {
+            let script = executable.path.with_extension("");
+            std::fs::write(&script, wasm_wrapper_script(driver, &executable.path))
+                .with_context(|| format!("failed to write {}", script.display()))?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))
+                    .with_context(|| format!("failed to make {} executable", script.display()))?;
+            }
+            Ok(TestExecutable {
+                path: script,
+                package_dir: executable.package_dir,
+            })
+        })
+        .collect()
+}
+
+/// The shell script which executes the wasm module with the driver,
+/// passing on its arguments.
+fn wasm_wrapper_script(driver: &Path, module: &Path) -> String {
+    let quote = |path: &Path| format!("'{}'", path.display().to_string().replace('\'', r"'\''"));
+    format!(
+        "#!/bin/sh\nexec {} {} \"$@\"\n",
+        quote(driver),
+        quote(module)
+    )
+}
+
+/// Returns the cargo executable which invoked us, so that the same
+/// toolchain is used.
+pub fn cargo() -> String {
+    std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string())
+}
+
+/// Returns the path of `afl-compiler-rt.o`, the runtime of AFL++ which
+/// implements the SanitizerCoverage callbacks, the fork server and the
+/// persistent mode. It's searched in `$AFL_PATH` and the directories
+/// AFL++ is installed to by default.
+pub fn afl_runtime() -> Option<PathBuf> {
+    let afl_path = std::env::var_os("AFL_PATH").map(PathBuf::from);
+    afl_path
+        .into_iter()
+        .chain(AFL_RUNTIME_DIRS.iter().map(PathBuf::from))
+        .map(|dir| dir.join("afl-compiler-rt.o"))
+        .find(|path| path.is_file())
+}
+
+/// Returns the paths of the static libraries of the honggfuzz runtime,
+/// which implement the SanitizerCoverage callbacks and the persistent
+/// mode. They're searched in `$HONGGFUZZ_PATH`, which may be the
+/// directory honggfuzz was built in, and the directories honggfuzz is
+/// installed to by default.
+pub fn honggfuzz_runtime() -> Option<Vec<PathBuf>> {
+    let honggfuzz_path = std::env::var_os("HONGGFUZZ_PATH").map(PathBuf::from);
+    let dirs: Vec<PathBuf> = honggfuzz_path
+        .into_iter()
+        .chain(HONGGFUZZ_RUNTIME_DIRS.iter().map(PathBuf::from))
+        .collect();
+    HONGGFUZZ_LIBRARIES
+        .iter()
+        .map(|library| {
+            // In the build directory, every library is in a directory
+            // of its name
+            let build_dir = library.trim_end_matches(".a");
+            dirs.iter()
+                .flat_map(|dir| [dir.join(library), dir.join(build_dir).join(library)])
+                .find(|path| path.is_file())
+        })
+        .collect()
+}
+
+/// Returns the target triple of the host, as reported by rustc.
+pub fn host_target() -> Result<String> {
+    rustc_version_info("host")?.context("failed to determine the host target from `rustc -vV`")
+}
+
+/// Returns the version of rustc as printed by `rustc -V`, e.g.
+/// "1.86.0-nightly (a580b5c37 2025-02-04)".
+pub fn rustc_release() -> Result<String> {
+    let version = rustc_version()?;
+    version
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("rustc "))
+        .map(|release| release.trim().to_string())
+        .context("failed to determine the version of rustc from `rustc -vV`")
+}
+
+/// Checks whether the standard library of the target is installed,
+/// which rustup doesn't do for other targets than the host.
+fn is_target_installed(target: &str) -> Result<bool> {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let output = Command::new(&rustc)
+        .args(["--print", "target-libdir", "--target", target])
+        .output()
+        .with_context(|| format!("failed to execute {rustc}"))?;
+    let libdir = String::from_utf8_lossy(&output.stdout);
+    Ok(output.status.success() && Path::new(libdir.trim()).is_dir())
+}
+
+/// Checks whether rustc accepts unstable flags like `-Zsanitizer`.
+fn is_nightly() -> Result<bool> {
+    if std::env::var_os("RUSTC_BOOTSTRAP").is_some_and(|v| v == "1") {
+        return Ok(true);
+    }
+    let release = rustc_version_info("release")?.unwrap_or_default();
+    Ok(release.contains("-nightly") || release.contains("-dev"))
+}
+
+/// Returns the value of a line like "host: x86_64-unknown-linux-gnu"
+/// printed by `rustc -vV`.
+fn rustc_version_info(key: &str) -> Result<Option<String>> {
+    let prefix = format!("{key}: ");
+    Ok(rustc_version()?
+        .lines()
+        .find_map(|l| l.strip_prefix(&prefix))
+        .map(|value| value.trim().to_string()))
+}
+
+/// Returns the output of `rustc -vV`, which identifies the toolchain.
+fn rustc_version() -> Result<String> {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let output = Command::new(&rustc)
+        .arg("-vV")
+        .output()
+        .with_context(|| format!("failed to execute {rustc}"))?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[derive(Deserialize)]
+struct CargoMessage {
+    reason: String,
+    #[serde(default)]
+    package_id: Option<String>,
+    #[serde(default)]
+    manifest_path: Option<PathBuf>,
+    #[serde(default)]
+    executable: Option<PathBuf>,
+    #[serde(default)]
+    profile: Option<CargoProfile>,
+}
+
+#[derive(Deserialize)]
+struct CargoProfile {
+    test: bool,
+}
+
+/// Parses the test executables from the JSON messages printed by cargo.
+fn parse_test_executables(messages: &str) -> Result<Vec<TestExecutable>> {
+    let mut executables = Vec::new();
+    for line in messages.lines().filter(|l| l.starts_with('{')) {
+        let msg: CargoMessage = serde_json::from_str(line)
+            .with_context(|| format!("failed to parse cargo message {line}"))?;
+        if msg.reason != "compiler-artifact" || !msg.profile.is_some_and(|p| p.test) {
+            continue;
+        }
+        let (Some(path), Some(manifest_path)) = (msg.executable, msg.manifest_path) else {
+            continue;
+        };
+        let package_dir = manifest_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        executables.push(TestExecutable { path, package_dir });
+    }
+    Ok(executables)
+}
+
+/// Parses the directories of the local packages, i.e. those which are
+/// not downloaded by cargo, from the JSON messages printed by cargo.
+fn parse_local_packages(messages: &str) -> Result<Vec<PathBuf>> {
+    let mut package_dirs = Vec::new();
+    for line in messages.lines().filter(|l| l.starts_with('{')) {
+        let msg: CargoMessage = serde_json::from_str(line)
+            .with_context(|| format!("failed to parse cargo message {line}"))?;
+        if msg.reason != "compiler-artifact"
+            || !msg.package_id.is_some_and(|id| id.contains("path+file://"))
+        {
+            continue;
+        }
+        if let Some(dir) = msg.manifest_path.as_deref().and_then(Path::parent) {
+            package_dirs.push(dir.to_path_buf());
+        }
+    }
+    package_dirs.sort();
+    package_dirs.dedup();
+    Ok(package_dirs)
+}
+
+/// Returns the fuzz test harnesses contained in the test executable.
+fn list_fuzz_tests(executable: &Path) -> Result<Vec<String>> {
+    let output = Command::new(executable)
+        .args(["--list", "--format", "terse"])
+        .output()
+        .with_context(|| format!("failed to list the tests of {}", executable.display()))?;
+    if !output.status.success() {
+        bail!(
+            "Failed to list the tests of {}: {}",
+            executable.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(parse_test_list(&String::from_utf8_lossy(&output.stdout))
+        .into_iter()
+        .filter(|t| fuzz_test_name(t).is_some())
+        .collect())
+}
+
+/// A fuzz test registered by its harness.
+#[derive(Debug, PartialEq)]
+struct RegistryEntry {
+    path: String,
+    line: u32,
+    file: String,
+}
+
+impl RegistryEntry {
+    /// Whether the libtest test is the harness of the fuzz test, whose
+    /// name is the path of the fuzz test without the crate.
+    fn is_harness(&self, test: &str) -> bool {
+        let module = self.path.split_once("::").map_or("", |(_, m)| m);
+        test.strip_suffix(FUZZ_TEST_HARNESS_NAME)
+            .and_then(|t| t.strip_suffix("::"))
+            == Some(module)
+    }
+}
+
+/// Runs the harnesses of the test executable with the registry file
+/// set and returns the fuzz tests they register.
+fn register_fuzz_tests(
+    executable: &Path,
+    harnesses: &[String],
+    registry: &Path,
+) -> Result<Vec<RegistryEntry>> {
+    let output = Command::new(executable)
+        .arg("--exact")
+        .args(harnesses)
+        .env(REGISTRY_FILE_ENV, registry)
+        .output()
+        .with_context(|| format!("failed to execute {}", executable.display()))?;
+    if !output.status.success() {
+        bail!(
+            "Failed to list the fuzz tests of {}: {}",
+            executable.display(),
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+    if !registry.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(registry)
+        .with_context(|| format!("failed to read {}", registry.display()))?;
+    Ok(parse_registry(&content))
+}
+
+/// Parses the lines `<path>\t<line>\t<file>` of the registry file.
+// Must be kept in sync with crates/cifuzz/src/registry.rs
+fn parse_registry(content: &str) -> Vec<RegistryEntry> {
+    content
+        .lines()
+        .filter_map(|l| {
+            let mut fields = l.splitn(3, '\t');
+            Some(RegistryEntry {
+                path: fields.next()?.to_string(),
+                line: fields.next()?.parse().ok()?,
+                file: fields.next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Returns the `cargo test` arguments which build all members of the
+/// workspace, unless they select packages themselves.
+pub fn workspace_args(args: &[String]) -> Vec<String> {
+    let selects_packages = args.iter().any(|a| {
+        ["-p", "--package", "--workspace", "--all"]
+            .iter()
+            .any(|flag| a == flag || a.starts_with(&format!("{flag}=")))
+            || (a.starts_with("-p") && a.len() > 2)
+    });
+    let mut workspace_args = args.to_vec();
+    if !selects_packages {
+        workspace_args.insert(0, "--workspace".to_string());
+    }
+    workspace_args
+}
+
+/// Parses the output of `<test executable> --list --format terse`.
+fn parse_test_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|l| l.strip_suffix(": test"))
+        .map(String::from)
+        .collect()
+}
+
+/// Returns the name of the fuzz test function if the test is a fuzz
+/// test harness, i.e. for "a::my_fuzz_test::fuzz" returns "my_fuzz_test".
+fn fuzz_test_name(test: &str) -> Option<&str> {
+    let module = test
+        .strip_suffix(FUZZ_TEST_HARNESS_NAME)?
+        .strip_suffix("::")?;
+    Some(module.rsplit("::").next().unwrap_or(module))
+}
+
+/// Returns the seed corpus directories of the fuzz test in the package,
+/// i.e. the `<fuzz_test>_inputs` directories and `corpus/<fuzz_test>`.
+/// The regression test only uses the `<fuzz_test>_inputs` directory
+/// next to the source file of the fuzz test, which we don't know, so
+/// all directories of that name in the package are used.
+// Must be kept in sync with crates/cifuzz/src/regression.rs
+pub fn seed_corpus_dirs(package_dir: &Path, fuzz_test: &str) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    find_paths(package_dir, &format!("{fuzz_test}_inputs"), &mut dirs)?;
+    dirs.retain(|p| p.is_dir());
+    let corpus_dir = package_dir.join("corpus").join(fuzz_test);
+    if corpus_dir.is_dir() {
+        dirs.push(corpus_dir);
+    }
+    Ok(dirs)
+}
+
+/// Returns the `<fuzz_test>.dict` dictionary files in the package.
+pub fn dictionary_files(package_dir: &Path, fuzz_test: &str) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    find_paths(package_dir, &format!("{fuzz_test}.dict"), &mut files)?;
+    files.retain(|p| p.is_file());
+    Ok(files)
+}
+
+/// Finds the files and directories with the name below `dir`, skipping
+/// hidden directories like .cifuzz-build and cargo target directories.
+fn find_paths(dir: &Path, name: &str, paths: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = std::fs::read_dir(dir)
+        .with_context(|| format!("failed to read {}", dir.display()))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        if file_name == name {
+            paths.push(path);
+        } else if path.is_dir() && !file_name.starts_with('.') && file_name != "target" {
+            find_paths(&path, name, paths)?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks whether the test is the harness of the fuzz test specified
+/// by the user, either by its name or by a suffix of its module path
+/// like "tests::my_fuzz_test".
+fn matches_fuzz_test(test: &str, fuzz_test: &str) -> bool {
+    let Some(module) = test
+        .strip_suffix(FUZZ_TEST_HARNESS_NAME)
+        .and_then(|m| m.strip_suffix("::"))
+    else {
+        return false;
+    };
+    module == fuzz_test || module.ends_with(&format!("::{fuzz_test}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitizer_build() {
+        let builder = Builder::new(BuilderOptions {
+            project_dir: PathBuf::from("/p"),
+            mode: BuildMode::Fuzzing,
+            sanitizer: Some(Sanitizer::Address),
+            args: Vec::new(),
+        });
+        assert_eq!(
+            builder.build_dir(),
+            Path::new("/p/.cifuzz-build/libfuzzer/address")
+        );
+        assert!(builder
+            .rustflags()
+            .ends_with(&["-Zsanitizer=address".to_string()]));
+        assert!(builder.cargo_flags().is_empty());
+        assert_eq!(
+            builder.cflags(),
+            [
+                "-fsanitize=fuzzer-no-link",
+                "-g",
+                "-fno-omit-frame-pointer",
+                "-fsanitize=address"
+            ]
+        );
+
+        let builder = Builder::new(BuilderOptions {
+            sanitizer: Some(Sanitizer::Memory),
+            ..builder.opts
+        });
+        assert_eq!(
+            builder.build_dir(),
+            Path::new("/p/.cifuzz-build/libfuzzer/memory")
+        );
+        assert!(builder
+            .rustflags()
+            .contains(&"-Zsanitizer=memory".to_string()));
+        assert_eq!(builder.cargo_flags(), ["-Zbuild-std"]);
+
+        let builder = Builder::new(BuilderOptions {
+            sanitizer: Some(Sanitizer::Leak),
+            ..builder.opts
+        });
+        assert!(builder
+            .rustflags()
+            .ends_with(&["-Zsanitizer=leak".to_string()]));
+        assert!(builder.cargo_flags().is_empty());
+
+        // Coverage builds are never sanitized
+        let builder = Builder::new(BuilderOptions {
+            mode: BuildMode::Coverage,
+            ..builder.opts
+        });
+        assert!(!builder.rustflags().iter().any(|f| f.contains("sanitizer=")));
+        assert!(builder.cflags().is_empty());
+    }
+
+    #[test]
+    fn recorded_rustflags() {
+        let mut builder = Builder::new(BuilderOptions {
+            project_dir: PathBuf::from("/p"),
+            mode: BuildMode::Fuzzing,
+            sanitizer: None,
+            args: Vec::new(),
+        });
+        builder.set_env_rustflags(vec!["-Copt-level=1".to_string()]);
+        let rustflags = builder.rustflags();
+        assert_eq!(rustflags[0], "-Copt-level=1");
+        assert_eq!(rustflags[1..], *FUZZING_RUSTFLAGS);
+        assert!(rustflags.contains(&"-Coverflow-checks=on".to_string()));
+    }
+
+    #[test]
+    fn afl_build() {
+        let builder = Builder::new(BuilderOptions {
+            project_dir: PathBuf::from("/p"),
+            mode: BuildMode::Afl,
+            sanitizer: Some(Sanitizer::Address),
+            args: Vec::new(),
+        });
+        assert_eq!(builder.build_dir(), Path::new("/p/.cifuzz-build/afl"));
+        let rustflags = builder.rustflags();
+        assert!(rustflags.windows(2).any(|f| f == ["--cfg", "cifuzz_afl"]));
+        assert!(rustflags.contains(&"-Cllvm-args=-sanitizer-coverage-trace-pc-guard".to_string()));
+        assert!(!rustflags.iter().any(|f| f.contains("sanitizer=")));
+    }
+
+    #[test]
+    fn honggfuzz_build() {
+        let builder = Builder::new(BuilderOptions {
+            project_dir: PathBuf::from("/p"),
+            mode: BuildMode::Honggfuzz,
+            sanitizer: None,
+            args: Vec::new(),
+        });
+        assert_eq!(builder.build_dir(), Path::new("/p/.cifuzz-build/honggfuzz"));
+        let rustflags = builder.rustflags();
+        assert!(rustflags
+            .windows(2)
+            .any(|f| f == ["--cfg", "cifuzz_honggfuzz"]));
+        assert!(!rustflags.windows(2).any(|f| f == ["--cfg", "cifuzz_afl"]));
+    }
+
+    #[test]
+    fn wasm_build() {
+        let builder = Builder::new(BuilderOptions {
+            project_dir: PathBuf::from("/p"),
+            mode: BuildMode::Wasm,
+            sanitizer: Some(Sanitizer::Address),
+            args: Vec::new(),
+        });
+        assert_eq!(builder.build_dir(), Path::new("/p/.cifuzz-build/wasm"));
+        let rustflags = builder.rustflags();
+        assert!(rustflags.windows(2).any(|f| f == ["--cfg", "cifuzz_wasm"]));
+        assert!(!rustflags.iter().any(|f| f.starts_with("-Zsanitizer")));
+    }
+
+    #[test]
+    fn wasm_wrapper_scripts() {
+        let script = wasm_wrapper_script(
+            Path::new("/bin/cifuzz-wasm-driver"),
+            Path::new("/p/it's.wasm"),
+        );
+        assert_eq!(
+            script,
+            "#!/bin/sh\nexec '/bin/cifuzz-wasm-driver' '/p/it'\\''s.wasm' \"$@\"\n"
+        );
+    }
+
+    #[test]
+    fn parse_list_output() {
+        let output = "explore_me::tests::test_explore_me: test\n\
+                      my_fuzz_test::my_fuzz_test::fuzz: test\n\
+                      some_bench: bench\n";
+        assert_eq!(
+            parse_test_list(output),
+            vec![
+                "explore_me::tests::test_explore_me",
+                "my_fuzz_test::my_fuzz_test::fuzz"
+            ]
+        );
+    }
+
+    #[test]
+    fn fuzz_test_names() {
+        assert_eq!(
+            fuzz_test_name("my_fuzz_test::my_fuzz_test::fuzz"),
+            Some("my_fuzz_test")
+        );
+        assert_eq!(fuzz_test_name("t::fuzz"), Some("t"));
+        assert_eq!(fuzz_test_name("fuzz"), None);
+        assert_eq!(fuzz_test_name("tests::no_fuzz"), None);
+        assert_eq!(fuzz_test_name("tests::regression"), None);
+    }
+
+    #[test]
+    fn parse_registry_entries() {
+        let entries = parse_registry(
+            "foo::my_fuzz_test\t3\tsrc/my_fuzz_test.rs\n\
+             foo::parser::tests::parse\t12\tsrc/parser.rs\n\
+             invalid\n",
+        );
+        assert_eq!(
+            entries,
+            vec![
+                RegistryEntry {
+                    path: "foo::my_fuzz_test".to_string(),
+                    line: 3,
+                    file: "src/my_fuzz_test.rs".to_string(),
+                },
+                RegistryEntry {
+                    path: "foo::parser::tests::parse".to_string(),
+                    line: 12,
+                    file: "src/parser.rs".to_string(),
+                },
+            ]
+        );
+        assert!(entries[0].is_harness("my_fuzz_test::fuzz"));
+        assert!(entries[1].is_harness("parser::tests::parse::fuzz"));
+        assert!(!entries[1].is_harness("tests::parse::fuzz"));
+        assert!(!entries[1].is_harness("parser::tests::parse"));
+    }
+
+    #[test]
+    fn add_workspace_arg() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            workspace_args(&args(&["--features", "foo"])),
+            args(&["--workspace", "--features", "foo"])
+        );
+        for selected in [
+            args(&["-p", "foo"]),
+            args(&["-pfoo"]),
+            args(&["--package=foo"]),
+            args(&["--workspace"]),
+        ] {
+            assert_eq!(workspace_args(&selected), selected);
+        }
+    }
+
+    #[test]
+    fn match_fuzz_test() {
+        let test = "parser::tests::parse_fuzz_test::fuzz";
+        assert!(matches_fuzz_test(test, "parse_fuzz_test"));
+        assert!(matches_fuzz_test(test, "tests::parse_fuzz_test"));
+        assert!(matches_fuzz_test(test, "parser::tests::parse_fuzz_test"));
+        assert!(!matches_fuzz_test(test, "fuzz_test"));
+        assert!(!matches_fuzz_test(test, "parser"));
+        assert!(!matches_fuzz_test("parser::tests::parse", "parse"));
+    }
+
+    #[test]
+    fn parse_cargo_messages() {
+        let messages = r#"{"reason":"compiler-artifact","manifest_path":"/p/foo/Cargo.toml","profile":{"test":false},"executable":null}
+{"reason":"compiler-artifact","manifest_path":"/p/foo/Cargo.toml","profile":{"test":true},"executable":"/p/target/debug/deps/foo-123"}
+{"reason":"compiler-artifact","manifest_path":"/p/foo/Cargo.toml","profile":{"test":false},"executable":"/p/target/debug/foo"}
+{"reason":"build-finished","success":true}"#;
+        let executables = parse_test_executables(messages).unwrap();
+        assert_eq!(executables.len(), 1);
+        assert_eq!(
+            executables[0].path,
+            Path::new("/p/target/debug/deps/foo-123")
+        );
+        assert_eq!(executables[0].package_dir, Path::new("/p/foo"));
+    }
+
+    #[test]
+    fn parse_local_package_dirs() {
+        let messages = r#"{"reason":"compiler-artifact","package_id":"registry+https://github.com/rust-lang/crates.io-index#libc@0.2.150","manifest_path":"/home/.cargo/registry/src/libc-0.2.150/Cargo.toml"}
+{"reason":"compiler-artifact","package_id":"path+file:///p/bar#0.1.0","manifest_path":"/p/bar/Cargo.toml"}
+{"reason":"compiler-artifact","package_id":"foo 0.1.0 (path+file:///p/foo)","manifest_path":"/p/foo/Cargo.toml"}
+{"reason":"compiler-artifact","package_id":"path+file:///p/foo#0.1.0","manifest_path":"/p/foo/Cargo.toml"}
+{"reason":"build-finished","success":true}"#;
+        assert_eq!(
+            parse_local_packages(messages).unwrap(),
+            [Path::new("/p/bar"), Path::new("/p/foo")]
+        );
+    }
+
+    #[test]
+    fn find_seed_corpus_and_dictionaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let package = dir.path();
+        for d in [
+            "src/my_fuzz_test_inputs/nested",
+            "tests/my_fuzz_test_inputs",
+            "target/my_fuzz_test_inputs",
+            ".cifuzz-build/my_fuzz_test_inputs",
+            "src/other_inputs",
+            "corpus/my_fuzz_test",
+        ] {
+            std::fs::create_dir_all(package.join(d)).unwrap();
+        }
+        std::fs::write(package.join("src").join("my_fuzz_test.dict"), "").unwrap();
+        std::fs::write(package.join("target").join("my_fuzz_test.dict"), "").unwrap();
+        assert_eq!(
+            seed_corpus_dirs(package, "my_fuzz_test").unwrap(),
+            vec![
+                package.join("src").join("my_fuzz_test_inputs"),
+                package.join("tests").join("my_fuzz_test_inputs"),
+                package.join("corpus").join("my_fuzz_test"),
+            ]
+        );
+        assert_eq!(
+            dictionary_files(package, "my_fuzz_test").unwrap(),
+            vec![package.join("src").join("my_fuzz_test.dict")]
+        );
+    }
+}
@@ -0,0 +1,377 @@
+//! The cache of the instrumented builds in .cifuzz/build-cache.
+//!
+//! The builds with sanitizers and coverage instrumentation can't share
+//! any artifacts with each other, so every combination of toolchain,
+//! build mode and RUSTFLAGS is built in a cargo target directory of its
+//! own. Switching between them, e.g. between sanitizers or from stable
+//! to nightly, reuses the earlier build of the combination instead of
+//! rebuilding the whole workspace.
+//!
+//! After a build, the entry of the combination records the test
+//! executables and a fingerprint of the sources of the local packages
+//! they were built from. As long as neither the fingerprint nor the
+//! arguments of `cargo test` change, the executables are reused without
+//! running cargo. Only the most recently used entries are kept.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::build::TestExecutable;
+use crate::instrument::Filter;
+use crate::native::Compilers;
+
+/// The number of entries which are kept, each of them is a full build
+/// of the workspace.
+pub const MAX_ENTRIES: usize = 4;
+
+/// The files of the project root which affect the build besides the
+/// sources of the packages.
+const BUILD_CONFIG_FILES: &[&str] = &[
+    "Cargo.toml",
+    "Cargo.lock",
+    ".cargo/config.toml",
+    ".cargo/config",
+    "rust-toolchain.toml",
+    "rust-toolchain",
+];
+
+/// What the artifacts of a build depend on, besides the sources and the
+/// arguments of `cargo test`, which cargo already distinguishes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Key {
+    /// The output of `rustc -vV`
+    pub toolchain: String,
+    pub target: String,
+    pub rustflags: Vec<String>,
+    pub cargo_flags: Vec<String>,
+    /// The crates which are instrumented, all if unset
+    pub instrument: Option<Filter>,
+    /// The compilers and flags for native code, if it's instrumented
+    pub native: Option<Compilers>,
+}
+
+impl Key {
+    fn hash(&self) -> String {
+        let json = serde_json::to_string(self).expect("keys are serializable");
+        sha1_smol::Sha1::from(json).digest().to_string()
+    }
+}
+
+/// What an entry records about its last build.
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    key: Key,
+    cargo_args: Vec<String>,
+    /// The directories of the local packages the executables were built
+    /// from
+    package_dirs: Vec<PathBuf>,
+    fingerprint: String,
+    executables: Vec<TestExecutable>,
+}
+
+/// The entry of the cache for a combination of toolchain, build mode
+/// and RUSTFLAGS.
+#[derive(Debug)]
+pub struct Entry {
+    dir: PathBuf,
+    project_dir: PathBuf,
+    key: Key,
+}
+
+impl Entry {
+    /// Opens the entry for the key, `name` describes the build mode,
+    /// e.g. "libfuzzer-address".
+    pub fn open(project_dir: &Path, name: &str, key: Key) -> Result<Entry> {
+        let cache_dir = cache_dir(project_dir);
+        let dir = cache_dir.join(format!("{name}-{}", &key.hash()[..16]));
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        // The cache must not end up in the repository, unlike the
+        // findings in .cifuzz
+        let gitignore = cache_dir.join(".gitignore");
+        if !gitignore.exists() {
+            fs::write(&gitignore, "*\n")
+                .with_context(|| format!("failed to write {}", gitignore.display()))?;
+        }
+        Ok(Entry {
+            dir,
+            project_dir: project_dir.to_path_buf(),
+            key,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The cargo target directory of the entry.
+    pub fn target_dir(&self) -> PathBuf {
+        self.dir.join("target")
+    }
+
+    fn manifest_path(&self) -> PathBuf {
+        self.dir.join("manifest.json")
+    }
+
+    /// Returns the test executables of the last build, if neither the
+    /// sources nor the arguments of `cargo test` changed since then.
+    pub fn lookup(&self, cargo_args: &[String]) -> Result<Option<Vec<TestExecutable>>> {
+        let path = self.manifest_path();
+        let Ok(json) = fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+        // A manifest of an older version is rebuilt like a missing one
+        let Ok(manifest) = serde_json::from_str::<Manifest>(&json) else {
+            return Ok(None);
+        };
+        if manifest.key != self.key
+            || manifest.cargo_args != cargo_args
+            || !manifest.executables.iter().all(|e| e.path.is_file())
+        {
+            return Ok(None);
+        }
+        let (fingerprint, _) = fingerprint(&self.project_dir, &manifest.package_dirs)?;
+        if fingerprint != manifest.fingerprint {
+            return Ok(None);
+        }
+        // The modification time of the manifest is the last use of the
+        // entry, see prune
+        File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+            .with_context(|| format!("failed to update {}", path.display()))?;
+        Ok(Some(manifest.executables))
+    }
+
+    /// Records the test executables of a build which started at
+    /// `start`. If sources were modified during the build, the
+    /// executables may be outdated and are not recorded.
+    pub fn store(
+        &self,
+        cargo_args: &[String],
+        executables: &[TestExecutable],
+        mut package_dirs: Vec<PathBuf>,
+        start: SystemTime,
+    ) -> Result<()> {
+        let path = self.manifest_path();
+        package_dirs.sort();
+        package_dirs.dedup();
+        let (fingerprint, modified) = fingerprint(&self.project_dir, &package_dirs)?;
+        if modified.is_some_and(|modified| modified >= start) {
+            if path.exists() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("failed to remove {}", path.display()))?;
+            }
+            return Ok(());
+        }
+        let manifest = Manifest {
+            key: self.key.clone(),
+            cargo_args: cargo_args.to_vec(),
+            package_dirs,
+            fingerprint,
+            executables: executables.to_vec(),
+        };
+        fs::write(&path, serde_json::to_string_pretty(&manifest)?)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+fn cache_dir(project_dir: &Path) -> PathBuf {
+    project_dir.join(".cifuzz").join("build-cache")
+}
+
+/// Removes the least recently used entries of the cache beyond
+/// `max_entries`.
+pub fn prune(project_dir: &Path, max_entries: usize) -> Result<()> {
+    let cache_dir = cache_dir(project_dir);
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(&cache_dir)
+        .with_context(|| format!("failed to read {}", cache_dir.display()))?
+    {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        // Entries without a manifest are in use by a build
+        let Ok(used) = path
+            .join("manifest.json")
+            .metadata()
+            .and_then(|m| m.modified())
+        else {
+            continue;
+        };
+        entries.push((used, path));
+    }
+    entries.sort_by_key(|(used, _)| std::cmp::Reverse(*used));
+    for (_, path) in entries.into_iter().skip(max_entries) {
+        fs::remove_dir_all(&path)
+            .with_context(|| format!("failed to remove {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Computes the fingerprint of the sources in the package directories
+/// and the build configuration of the project, from the paths, sizes and
+/// modification times of the files. Also returns the latest
+/// modification time.
+fn fingerprint(
+    project_dir: &Path,
+    package_dirs: &[PathBuf],
+) -> Result<(String, Option<SystemTime>)> {
+    let mut files = Vec::new();
+    for dir in package_dirs {
+        // Nested packages are part of the files of the outer package
+        if package_dirs.iter().any(|d| d != dir && dir.starts_with(d)) {
+            continue;
+        }
+        find_files(dir, &mut files)?;
+    }
+    files.extend(
+        BUILD_CONFIG_FILES
+            .iter()
+            .map(|file| project_dir.join(file))
+            .filter(|path| path.is_file()),
+    );
+    files.sort();
+    files.dedup();
+
+    let mut hasher = sha1_smol::Sha1::new();
+    let mut latest = None;
+    for file in files {
+        let metadata = file
+            .metadata()
+            .with_context(|| format!("failed to read {}", file.display()))?;
+        let modified = metadata.modified()?;
+        latest = latest.max(Some(modified));
+        let nanos = modified
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        hasher.update(format!("{}\0{}\0{nanos}\n", file.display(), metadata.len()).as_bytes());
+    }
+    Ok((hasher.digest().to_string(), latest))
+}
+
+/// Finds the files below `dir`, skipping hidden directories like
+/// .cifuzz and cargo target directories.
+fn find_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() {
+            if !name.starts_with('.') && name != "target" {
+                find_files(&path, files)?;
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(rustflags: &[&str]) -> Key {
+        Key {
+            toolchain: "rustc 1.80.0".to_string(),
+            target: "x86_64-unknown-linux-gnu".to_string(),
+            rustflags: rustflags.iter().map(|f| f.to_string()).collect(),
+            cargo_flags: Vec::new(),
+            instrument: None,
+            native: None,
+        }
+    }
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn reuses_unchanged_builds() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path();
+        let package = project.join("foo");
+        write(&project.join("Cargo.toml"), "[workspace]");
+        write(&package.join("src").join("lib.rs"), "");
+        let executable = project.join("foo-123");
+        write(&executable, "");
+        let executables = vec![TestExecutable {
+            path: executable,
+            package_dir: package.clone(),
+        }];
+        let args = vec!["--features".to_string(), "foo".to_string()];
+        let start = SystemTime::now() + std::time::Duration::from_secs(60);
+
+        let entry = Entry::open(project, "libfuzzer-none", key(&["--cfg", "fuzzing"])).unwrap();
+        assert!(entry.lookup(&args).unwrap().is_none());
+        entry
+            .store(&args, &executables, vec![package.clone()], start)
+            .unwrap();
+        assert_eq!(entry.lookup(&args).unwrap().unwrap().len(), 1);
+        assert!(entry.lookup(&[]).unwrap().is_none());
+
+        // Other flags are built in another entry
+        let other = Entry::open(project, "libfuzzer-none", key(&["-Cdebuginfo=2"])).unwrap();
+        assert_ne!(other.dir(), entry.dir());
+        assert!(other.lookup(&args).unwrap().is_none());
+
+        // Modified sources invalidate the build
+        write(&package.join("src").join("lib.rs"), "fn f() {}");
+        assert!(entry.lookup(&args).unwrap().is_none());
+        entry
+            .store(&args, &executables, vec![package.clone()], start)
+            .unwrap();
+        assert!(entry.lookup(&args).unwrap().is_some());
+        write(&package.join("src").join("new.rs"), "");
+        assert!(entry.lookup(&args).unwrap().is_none());
+        entry
+            .store(&args, &executables, vec![package.clone()], start)
+            .unwrap();
+        write(&project.join("Cargo.lock"), "");
+        assert!(entry.lookup(&args).unwrap().is_none());
+
+        // Sources modified during the build aren't recorded as built
+        entry
+            .store(&args, &executables, vec![package], SystemTime::UNIX_EPOCH)
+            .unwrap();
+        assert!(entry.lookup(&args).unwrap().is_none());
+        assert_eq!(
+            fs::read_to_string(cache_dir(project).join(".gitignore")).unwrap(),
+            "*\n"
+        );
+    }
+
+    #[test]
+    fn prunes_least_recently_used_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path();
+        let mut entries = Vec::new();
+        for i in 0..3 {
+            let entry = Entry::open(project, "afl", key(&[&i.to_string()])).unwrap();
+            let manifest = entry.manifest_path();
+            write(&manifest, "{}");
+            File::options()
+                .write(true)
+                .open(&manifest)
+                .unwrap()
+                .set_modified(UNIX_EPOCH + std::time::Duration::from_secs(i))
+                .unwrap();
+            entries.push(entry);
+        }
+        // An entry which is being built
+        let building = Entry::open(project, "afl", key(&["3"])).unwrap();
+
+        prune(project, 2).unwrap();
+        assert!(!entries[0].dir().exists());
+        assert!(entries[1].dir().exists());
+        assert!(entries[2].dir().exists());
+        assert!(building.dir().exists());
+    }
+}
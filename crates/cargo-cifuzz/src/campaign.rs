@@ -0,0 +1,229 @@
+//! The state of the fuzzing campaign of a fuzz test, which
+//! `cargo cifuzz run --resume` continues from.
+//!
+//! The corpus generated by libFuzzer is kept in the generated corpus
+//! directory anyway. The rest of the state, i.e. the seed of libFuzzer's
+//! random number generator, the fuzzing time spent of the --timeout and
+//! the statistics, is stored in `.cifuzz/campaigns/<fuzz_test>.json`
+//! while the fuzz test runs. Like the events, the state is kept in a
+//! global, so that the runner can update it with the progress it
+//! parses, and it's written periodically, so that the state of a run
+//! which is killed is lost for no more than [`SAVE_INTERVAL`].
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::log;
+use crate::parser::Stats;
+
+/// How often the state is written while the fuzz test runs.
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+static CAMPAIGN: Mutex<Option<Campaign>> = Mutex::new(None);
+
+/// The state of a campaign, as stored in its file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    pub fuzz_test: String,
+    /// The seed of the first run of libFuzzer, which the resumed runs
+    /// use as well
+    pub seed: Option<u64>,
+    /// The total fuzzing time of the campaign
+    pub timeout_secs: Option<u64>,
+    /// The fuzzing time spent so far
+    pub elapsed_secs: u64,
+    /// The executed inputs over all runs
+    pub execs: u64,
+    /// The latest coverage and corpus size
+    pub edges: u64,
+    pub corpus_size: u64,
+    /// The number of times the campaign was started or resumed
+    pub sessions: u32,
+    /// Whether the campaign ran until its end, after which it can't be
+    /// resumed
+    pub finished: bool,
+}
+
+impl State {
+    pub fn new(fuzz_test: &str, timeout: Option<Duration>) -> State {
+        State {
+            fuzz_test: fuzz_test.to_string(),
+            seed: None,
+            timeout_secs: timeout.map(|t| t.as_secs()),
+            elapsed_secs: 0,
+            execs: 0,
+            edges: 0,
+            corpus_size: 0,
+            sessions: 0,
+            finished: false,
+        }
+    }
+
+    /// The fuzzing time left of the timeout, or `None` if the campaign
+    /// runs indefinitely.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.timeout_secs
+            .map(|timeout| Duration::from_secs(timeout.saturating_sub(self.elapsed_secs)))
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_secs(self.elapsed_secs)
+    }
+}
+
+struct Campaign {
+    file: PathBuf,
+    state: State,
+    /// The state at the start of the session, which the progress of the
+    /// session is added to
+    elapsed_before: Duration,
+    execs_before: u64,
+    start: Instant,
+    last_save: Instant,
+}
+
+impl Campaign {
+    fn update(&mut self) {
+        self.state.elapsed_secs = (self.elapsed_before + self.start.elapsed()).as_secs();
+    }
+
+    fn save(&mut self) {
+        self.update();
+        self.last_save = Instant::now();
+        if let Err(err) = save(&self.file, &self.state) {
+            log::debug!("{err:#}");
+        }
+    }
+}
+
+/// Returns the file with the state of the campaign of the fuzz test.
+pub fn file(project_dir: &Path, fuzz_test: &str) -> PathBuf {
+    project_dir
+        .join(".cifuzz")
+        .join("campaigns")
+        .join(format!("{fuzz_test}.json"))
+}
+
+/// Loads the state of a campaign, or returns `None` if the file doesn't
+/// exist.
+pub fn load(file: &Path) -> Result<Option<State>> {
+    let content = match std::fs::read(file) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", file.display())),
+    };
+    serde_json::from_slice(&content)
+        .map(Some)
+        .with_context(|| format!("failed to parse {}", file.display()))
+}
+
+/// Writes the state to a temporary file which replaces the file, so
+/// that a run killed while writing it doesn't leave a truncated file.
+fn save(file: &Path, state: &State) -> Result<()> {
+    let dir = file
+        .parent()
+        .expect("the file is in the campaigns directory");
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let tmp = file.with_extension("json.tmp");
+    let json = serde_json::to_string_pretty(state)?;
+    std::fs::write(&tmp, json + "\n")
+        .with_context(|| format!("failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, file).with_context(|| format!("failed to write {}", file.display()))
+}
+
+/// Starts a session of the campaign, which stores its state in the file
+/// until [`finish`] is called.
+pub fn start(file: PathBuf, mut state: State) -> Result<()> {
+    state.sessions += 1;
+    state.finished = false;
+    save(&file, &state)?;
+    *CAMPAIGN.lock().unwrap() = Some(Campaign {
+        file,
+        elapsed_before: state.elapsed(),
+        execs_before: state.execs,
+        state,
+        start: Instant::now(),
+        last_save: Instant::now(),
+    });
+    Ok(())
+}
+
+/// Records the seed which libFuzzer printed, if the campaign has none
+/// yet.
+pub fn seed(seed: u64) {
+    if let Some(campaign) = CAMPAIGN.lock().unwrap().as_mut() {
+        if campaign.state.seed.is_none() {
+            campaign.state.seed = Some(seed);
+            campaign.save();
+        }
+    }
+}
+
+/// Records the progress of the running fuzz test.
+pub fn progress(stats: &Stats) {
+    let mut campaign = CAMPAIGN.lock().unwrap();
+    let Some(campaign) = campaign.as_mut() else {
+        return;
+    };
+    campaign.state.execs = campaign.execs_before + stats.execs;
+    campaign.state.edges = stats.cov;
+    campaign.state.corpus_size = stats.corpus;
+    if campaign.last_save.elapsed() >= SAVE_INTERVAL {
+        campaign.save();
+    }
+}
+
+/// Records that the fuzz test exited, after which the executed inputs
+/// of a restarted instance count from zero.
+pub fn exited() {
+    if let Some(campaign) = CAMPAIGN.lock().unwrap().as_mut() {
+        campaign.execs_before = campaign.state.execs;
+        campaign.save();
+    }
+}
+
+/// Ends the session of the campaign, and the campaign itself if it
+/// `finished`, and returns its state. Returns `None` if no campaign was
+/// started.
+pub fn finish(finished: bool) -> Option<State> {
+    let mut campaign = CAMPAIGN.lock().unwrap().take()?;
+    campaign.state.finished = finished;
+    campaign.save();
+    Some(campaign.state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load() {
+        let project = tempfile::tempdir().unwrap();
+        let file = file(project.path(), "my_fuzz_test");
+        assert_eq!(load(&file).unwrap(), None);
+
+        let state = State {
+            seed: Some(3639972260),
+            elapsed_secs: 600,
+            execs: 1234567,
+            sessions: 1,
+            ..State::new("my_fuzz_test", Some(Duration::from_secs(3600)))
+        };
+        save(&file, &state).unwrap();
+        let loaded = load(&file).unwrap().unwrap();
+        assert_eq!(loaded, state);
+        assert_eq!(loaded.remaining(), Some(Duration::from_secs(3000)));
+        assert!(!file.with_extension("json.tmp").exists());
+
+        let state = State {
+            elapsed_secs: 4000,
+            ..state
+        };
+        assert_eq!(state.remaining(), Some(Duration::ZERO));
+        assert_eq!(State::new("my_fuzz_test", None).remaining(), None);
+    }
+}
@@ -0,0 +1,529 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use serde::Serialize;
+
+use crate::build::{self, host_target, BuildMode, Builder, BuilderOptions, Sanitizer};
+use crate::config;
+use crate::corpus;
+use crate::dictionary;
+use crate::finding;
+use crate::log;
+use crate::runner::libfuzzer::LIBFUZZER_ARGS_ENV;
+
+/// The file with the metadata of the bundle.
+const METADATA_FILE: &str = "bundle.json";
+
+/// The script which runs the fuzz tests of the bundle.
+const RUNNER_SCRIPT: &str = "run.sh";
+
+/// The directory in $OUT with the test executables of the OSS-Fuzz
+/// layout.
+const OSS_FUZZ_BIN_DIR: &str = "cifuzz-bin";
+
+/// The environment variable with the output directory of OSS-Fuzz
+/// builds.
+const OSS_FUZZ_OUT_ENV: &str = "OUT";
+
+/// Bundle fuzz tests for running them without the Rust toolchain
+///
+/// This command builds the fuzz tests like `cargo cifuzz run` and packs
+/// the test executables, the seed corpora and the dictionaries of the
+/// fuzz tests into a self-contained archive, together with a script
+/// which runs them with libFuzzer. The archive can be executed on
+/// machines without the Rust toolchain and the sources, e.g. by a fleet
+/// of fuzzing machines or in containers:
+///
+///     cargo cifuzz bundle -o bundle.tar.gz
+///     tar -xzf bundle.tar.gz && ./run.sh my_fuzz_test -max_total_time=3600
+///
+/// Without `<FUZZ_TEST>` arguments, all fuzz tests of the workspace are bundled,
+/// the ones `cargo cifuzz list` lists. The bundle contains:
+///
+///     bundle.json                   the metadata, e.g. the fuzz tests,
+///                                   the commit and the build flags
+///     run.sh                        runs a fuzz test, passing the other
+///                                   arguments to libFuzzer
+///     bin/<executable>              the test executables
+///     <fuzz_test>/seeds/            the seed corpus
+///     <fuzz_test>/<fuzz_test>.dict  the dictionary, including the
+///                                   tokens extracted from the sources
+///
+/// run.sh stores the generated corpus in `corpus/<fuzz_test>` and the
+/// crashing inputs in `artifacts/<fuzz_test>` next to the script. The
+/// generated corpus can be pulled into the project with
+/// `cargo cifuzz corpus sync <FUZZ_TEST> --pull --remote <BUNDLE>/corpus`.
+/// The engine-args of the cifuzz.yaml are passed to libFuzzer as well,
+/// before the arguments of the script.
+///
+/// The executables are linked dynamically against the C library of the
+/// build machine, which the machines running them need as well.
+///
+/// With --oss-fuzz, the fuzz tests are installed into the output
+/// directory in the layout OSS-Fuzz expects in $OUT instead, which the
+/// build.sh generated by `cargo cifuzz integrate oss-fuzz` does: a
+/// `<fuzz_test>` script per fuzz test, which runs the test executable in
+/// cifuzz-bin/ with the libFuzzer flags it's passed, and the
+/// `<fuzz_test>_seed_corpus.zip`, `<fuzz_test>.dict` and `<fuzz_test>.options`
+/// files. The engine-args of the cifuzz.yaml become the options.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+pub struct BundleArgs {
+    /// The fuzz tests to bundle, by their name or (a suffix of) their
+    /// path. Defaults to all fuzz tests of the workspace.
+    fuzz_tests: Vec<String>,
+
+    /// The archive to write, or with --oss-fuzz the directory to install
+    /// the fuzz tests into. Defaults to fuzz-tests.tar.gz, or with
+    /// --oss-fuzz to $OUT.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Install the fuzz tests in the layout of OSS-Fuzz instead of
+    /// creating an archive
+    #[arg(long)]
+    oss_fuzz: bool,
+
+    /// Build the fuzz tests with a sanitizer. Requires a nightly
+    /// toolchain.
+    #[arg(long, value_enum)]
+    sanitizer: Option<Sanitizer>,
+
+    /// Don't add the tokens extracted from the sources to the
+    /// dictionaries
+    #[arg(long)]
+    no_auto_dict: bool,
+
+    /// Additional arguments to pass to `cargo test`
+    #[arg(last = true)]
+    cargo_args: Vec<String>,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+/// The metadata of the bundle in bundle.json.
+#[derive(Debug, Serialize)]
+struct Metadata {
+    /// The version of cargo-cifuzz which created the bundle
+    cifuzz_version: &'static str,
+    created_at: String,
+    commit: Option<String>,
+    target: String,
+    sanitizer: Option<Sanitizer>,
+    build_flags: Vec<String>,
+    cargo_args: Vec<String>,
+    fuzz_tests: Vec<BundledFuzzTest>,
+}
+
+#[derive(Debug, Serialize)]
+struct BundledFuzzTest {
+    name: String,
+    path: String,
+    /// The source file relative to the project directory
+    source_file: PathBuf,
+    line: u32,
+    /// The executable relative to the bundle
+    executable: PathBuf,
+    /// The libtest test running the fuzz test
+    test_name: String,
+    seeds: usize,
+    dictionary: Option<PathBuf>,
+    engine_args: Vec<String>,
+}
+
+pub fn run(args: BundleArgs) -> Result<()> {
+    let output = match (&args.output, args.oss_fuzz) {
+        (Some(output), _) => output.clone(),
+        (None, false) => PathBuf::from("fuzz-tests.tar.gz"),
+        (None, true) => std::env::var_os(OSS_FUZZ_OUT_ENV)
+            .map(PathBuf::from)
+            .context("--oss-fuzz requires --output or $OUT")?,
+    };
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let project_config = config::parse_project_config(&project_dir)?;
+
+    log::info!("Building the fuzz tests");
+    let builder = Builder::new(BuilderOptions {
+        project_dir: project_dir.clone(),
+        sanitizer: args.sanitizer,
+        mode: BuildMode::Fuzzing,
+        args: build::workspace_args(&args.cargo_args),
+    });
+    let mut fuzz_tests = builder.discover()?;
+    if !args.fuzz_tests.is_empty() {
+        for selected in &args.fuzz_tests {
+            if !fuzz_tests.iter().any(|t| matches(&t.path, selected)) {
+                bail!("Fuzz test {selected:?} not found, see `cargo cifuzz list`");
+            }
+        }
+        fuzz_tests.retain(|t| args.fuzz_tests.iter().any(|s| matches(&t.path, s)));
+    }
+    if fuzz_tests.is_empty() {
+        bail!("No fuzz tests found, fuzz tests are functions annotated with #[fuzz_test]");
+    }
+    let mut names = BTreeMap::new();
+    for fuzz_test in &fuzz_tests {
+        if let Some(other) = names.insert(&fuzz_test.build.name, &fuzz_test.path) {
+            bail!(
+                "The fuzz tests {other} and {} have the same name, bundle them separately",
+                fuzz_test.path
+            );
+        }
+    }
+
+    let bundle_dir = builder.build_dir().join("bundle");
+    if bundle_dir.exists() {
+        std::fs::remove_dir_all(&bundle_dir)
+            .with_context(|| format!("failed to remove {}", bundle_dir.display()))?;
+    }
+    let bin_dir = bundle_dir.join("bin");
+    std::fs::create_dir_all(&bin_dir)
+        .with_context(|| format!("failed to create {}", bin_dir.display()))?;
+
+    let mut bundled = Vec::new();
+    for fuzz_test in fuzz_tests {
+        let build = fuzz_test.build;
+        let name = build.name.clone();
+        let file_name = build.executable.file_name().unwrap_or_default();
+        let executable = Path::new("bin").join(file_name);
+        if !bundle_dir.join(&executable).exists() {
+            std::fs::copy(&build.executable, bundle_dir.join(&executable))
+                .with_context(|| format!("failed to copy {}", build.executable.display()))?;
+        }
+
+        let test_dir = bundle_dir.join(&name);
+        let seeds = corpus::import(
+            &corpus::list_inputs(&build.seed_corpus_dirs)?,
+            &test_dir.join("seeds"),
+        )?;
+
+        let mut dictionaries = build.dictionaries.clone();
+        if let Some(dict) = &project_config.dict {
+            dictionaries.push(project_dir.join(dict));
+        }
+        if !args.no_auto_dict {
+            let auto_dict = builder
+                .build_dir()
+                .join("dictionaries")
+                .join(format!("{name}.auto.dict"));
+            if dictionary::generate(&build.package_dir, &auto_dict)? > 0 {
+                dictionaries.push(auto_dict);
+            }
+        }
+        let dict_file = Path::new(&name).join(format!("{name}.dict"));
+        let dictionary = match dictionary::merge(&dictionaries, &bundle_dir.join(&dict_file))? {
+            Some(merged) => {
+                if merged != bundle_dir.join(&dict_file) {
+                    std::fs::copy(&merged, bundle_dir.join(&dict_file))
+                        .with_context(|| format!("failed to copy {}", merged.display()))?;
+                }
+                Some(dict_file)
+            }
+            None => None,
+        };
+        log::info!("Bundled {name} with {seeds} seeds");
+        bundled.push(BundledFuzzTest {
+            name: name.clone(),
+            path: fuzz_test.path,
+            source_file: fuzz_test
+                .source_file
+                .strip_prefix(&project_dir)
+                .unwrap_or(&fuzz_test.source_file)
+                .to_path_buf(),
+            line: fuzz_test.line,
+            executable,
+            test_name: build.test_name,
+            seeds,
+            dictionary,
+            engine_args: project_config.engine_args.for_fuzz_test(&name).to_vec(),
+        });
+    }
+
+    let metadata = Metadata {
+        cifuzz_version: env!("CARGO_PKG_VERSION"),
+        created_at: humantime::format_rfc3339_seconds(std::time::SystemTime::now()).to_string(),
+        commit: finding::git_commit(&project_dir),
+        target: host_target()?,
+        sanitizer: args.sanitizer,
+        build_flags: builder.rustflags(),
+        cargo_args: args.cargo_args,
+        fuzz_tests: bundled,
+    };
+    let json = serde_json::to_string_pretty(&metadata)?;
+    std::fs::write(bundle_dir.join(METADATA_FILE), json + "\n")
+        .context("failed to write the metadata")?;
+    let script = bundle_dir.join(RUNNER_SCRIPT);
+    std::fs::write(&script, runner_script(&metadata)).context("failed to write the script")?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))
+            .context("failed to make the script executable")?;
+    }
+
+    if args.oss_fuzz {
+        install_oss_fuzz(&bundle_dir, &metadata, &output)?;
+    } else {
+        archive(&bundle_dir, &output)?;
+    }
+    log::success!(
+        "Bundled {} fuzz {} into {}",
+        metadata.fuzz_tests.len(),
+        if metadata.fuzz_tests.len() == 1 {
+            "test"
+        } else {
+            "tests"
+        },
+        output.display()
+    );
+    Ok(())
+}
+
+/// Whether the fuzz test with the path is selected by its name or a
+/// suffix of its path.
+fn matches(path: &str, selected: &str) -> bool {
+    path == selected || path.ends_with(&format!("::{selected}"))
+}
+
+/// Packs the directory into a gzipped tar archive.
+fn archive(dir: &Path, output: &Path) -> Result<()> {
+    let output = std::path::absolute(output)
+        .with_context(|| format!("invalid output {}", output.display()))?;
+    let mut cmd = Command::new("tar");
+    cmd.arg("-czf").arg(&output).arg("-C").arg(dir).arg(".");
+    log::debug!("Command: {:?}", cmd);
+    let status = cmd.status().context("failed to execute tar")?;
+    if !status.success() {
+        bail!("tar failed to create {}", output.display());
+    }
+    Ok(())
+}
+
+/// Installs the fuzz tests of the bundle into the output directory of
+/// an OSS-Fuzz build, in which every executable file is a fuzz target.
+fn install_oss_fuzz(bundle_dir: &Path, metadata: &Metadata, out_dir: &Path) -> Result<()> {
+    let bin_dir = out_dir.join(OSS_FUZZ_BIN_DIR);
+    std::fs::create_dir_all(&bin_dir)
+        .with_context(|| format!("failed to create {}", bin_dir.display()))?;
+    for fuzz_test in &metadata.fuzz_tests {
+        let name = &fuzz_test.name;
+        let executable =
+            Path::new(OSS_FUZZ_BIN_DIR).join(fuzz_test.executable.file_name().unwrap());
+        std::fs::copy(
+            bundle_dir.join(&fuzz_test.executable),
+            out_dir.join(&executable),
+        )
+        .with_context(|| format!("failed to copy {}", fuzz_test.executable.display()))?;
+
+        let script = out_dir.join(name);
+        std::fs::write(&script, oss_fuzz_script(metadata, fuzz_test, &executable))
+            .with_context(|| format!("failed to write {}", script.display()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))
+                .with_context(|| format!("failed to make {} executable", script.display()))?;
+        }
+
+        if let Some(dictionary) = &fuzz_test.dictionary {
+            std::fs::copy(
+                bundle_dir.join(dictionary),
+                out_dir.join(format!("{name}.dict")),
+            )
+            .with_context(|| format!("failed to copy the dictionary of {name}"))?;
+        }
+        if fuzz_test.seeds > 0 {
+            let zip = std::path::absolute(out_dir.join(format!("{name}_seed_corpus.zip")))?;
+            let mut cmd = Command::new("zip");
+            cmd.arg("-q")
+                .arg("-r")
+                .arg(&zip)
+                .arg(".")
+                .current_dir(bundle_dir.join(name).join("seeds"));
+            log::debug!("Command: {:?}", cmd);
+            let status = cmd.status().context("failed to execute zip")?;
+            if !status.success() {
+                bail!("zip failed to create {}", zip.display());
+            }
+        }
+        if let Some(options) = oss_fuzz_options(name, &fuzz_test.engine_args) {
+            std::fs::write(out_dir.join(format!("{name}.options")), options)
+                .with_context(|| format!("failed to write the options of {name}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the script which OSS-Fuzz runs as the fuzz target of the
+/// fuzz test. The test executable takes the libFuzzer flags from the
+/// environment, because its arguments are the ones of libtest.
+fn oss_fuzz_script(metadata: &Metadata, fuzz_test: &BundledFuzzTest, executable: &Path) -> String {
+    format!(
+        "#!/bin/sh\n\
+         # Runs the fuzz test {} with libFuzzer, created by cargo-cifuzz {}.\n\
+         # OSS-Fuzz detects fuzz targets by the name LLVMFuzzerTestOneInput.\n\
+         set -eu\n\
+         this_dir=\"$(dirname \"$0\")\"\n\
+         args=\n\
+         for arg in \"$@\"; do\n\
+         \x20   args=\"$args\n$arg\"\n\
+         done\n\
+         # Panics must print a stack trace\n\
+         export RUST_BACKTRACE=\"${{RUST_BACKTRACE:-1}}\"\n\
+         export {LIBFUZZER_ARGS_ENV}=\"$args\"\n\
+         exec \"$this_dir/\"{} --exact {} --nocapture --test-threads 1\n",
+        fuzz_test.path,
+        metadata.cifuzz_version,
+        shell_quote(&executable.display().to_string()),
+        shell_quote(&fuzz_test.test_name),
+    )
+}
+
+/// Translates the engine arguments of a fuzz test into the .options
+/// file of OSS-Fuzz, which only supports flags with values. Returns
+/// `None` if there are none.
+fn oss_fuzz_options(fuzz_test: &str, engine_args: &[String]) -> Option<String> {
+    let mut options = String::new();
+    for arg in engine_args {
+        match arg.strip_prefix('-').and_then(|flag| flag.split_once('=')) {
+            Some((flag, value)) if !flag.is_empty() => {
+                options.push_str(&format!("{flag} = {value}\n"));
+            }
+            _ => log::info!(
+                "Skipping the engine argument {arg:?} of {fuzz_test}, which OSS-Fuzz doesn't support"
+            ),
+        }
+    }
+    (!options.is_empty()).then(|| format!("[libfuzzer]\n{options}"))
+}
+
+/// Returns the script which runs a fuzz test of the bundle with
+/// libFuzzer, like the libFuzzer runner of `cargo cifuzz run`.
+fn runner_script(metadata: &Metadata) -> String {
+    let mut script = format!(
+        "#!/bin/sh\n\
+         # Runs a fuzz test of the bundle with libFuzzer, created by cargo-cifuzz {}.\n\
+         #\n\
+         # Usage: ./{RUNNER_SCRIPT} <fuzz_test> [libFuzzer flags...]\n\
+         set -eu\n\
+         cd \"$(dirname \"$0\")\"\n\
+         bundle=\"$(pwd)\"\n\
+         \n\
+         if [ $# -eq 0 ]; then\n\
+         \x20   echo \"Usage: $0 <fuzz_test> [libFuzzer flags...]\" >&2\n\
+         \x20   echo \"Fuzz tests:\" >&2\n",
+        metadata.cifuzz_version
+    );
+    for fuzz_test in &metadata.fuzz_tests {
+        script.push_str(&format!(
+            "    echo {} >&2\n",
+            shell_quote(&format!("  {}", fuzz_test.name))
+        ));
+    }
+    script.push_str(
+        "    exit 1\n\
+         fi\n\
+         fuzz_test=\"$1\"\n\
+         shift\n\
+         \n\
+         # libFuzzer uses the last value of a flag, so the flags passed to the\n\
+         # script override the defaults\n\
+         case \"$fuzz_test\" in\n",
+    );
+    for fuzz_test in &metadata.fuzz_tests {
+        let mut args = vec![format!(
+            "-artifact_prefix=$bundle/artifacts/{}/",
+            fuzz_test.name
+        )];
+        if let Some(dictionary) = &fuzz_test.dictionary {
+            args.push(format!("-dict=$bundle/{}", dictionary.display()));
+        }
+        args.extend(fuzz_test.engine_args.iter().cloned());
+        script.push_str(&format!(
+            "    {})\n\
+             \x20       executable={}\n\
+             \x20       test_name={}\n\
+             \x20       args={}\n\
+             \x20       ;;\n",
+            shell_quote(&fuzz_test.name),
+            shell_quote(&fuzz_test.executable.display().to_string()),
+            shell_quote(&fuzz_test.test_name),
+            // $bundle is expanded, the rest is quoted
+            shell_quote(&args.join("\n")).replace("$bundle", "'\"$bundle\"'")
+        ));
+    }
+    script.push_str(
+        "    *)\n\
+         \x20       echo \"Unknown fuzz test $fuzz_test\" >&2\n\
+         \x20       exit 1\n\
+         \x20       ;;\n\
+         esac\n\
+         \n\
+         for arg in \"$@\"; do\n\
+         \x20   args=\"$args\n$arg\"\n\
+         done\n\
+         mkdir -p \"$bundle/corpus/$fuzz_test\" \"$bundle/artifacts/$fuzz_test\"\n\
+         args=\"$args\n$bundle/corpus/$fuzz_test\"\n\
+         if [ -d \"$bundle/$fuzz_test/seeds\" ]; then\n\
+         \x20   args=\"$args\n$bundle/$fuzz_test/seeds\"\n\
+         fi\n\
+         \n\
+         # Panics must print a stack trace\n\
+         export RUST_BACKTRACE=\"${RUST_BACKTRACE:-1}\"\n",
+    );
+    if let Some(sanitizer) = metadata.sanitizer {
+        let env = sanitizer.options_env();
+        let options = sanitizer.options(false);
+        if !options.is_empty() {
+            // The options of the environment take precedence
+            script.push_str(&format!(
+                "export {env}=\"{}${{{env}:+:${env}}}\"\n",
+                options.join(":")
+            ));
+        }
+    }
+    script.push_str(&format!(
+        "export {LIBFUZZER_ARGS_ENV}=\"$args\"\n\
+         exec \"$bundle/$executable\" --exact \"$test_name\" --nocapture --test-threads 1\n"
+    ));
+    script
+}
+
+/// Quotes the string for the shell.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_for_shell() {
+        assert_eq!(shell_quote("my_fuzz_test"), "'my_fuzz_test'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn translate_engine_args_to_options() {
+        let args = ["-max_len=4096", "-use_value_profile=1", "-close_fd_mask"].map(String::from);
+        assert_eq!(
+            oss_fuzz_options("my_fuzz_test", &args).unwrap(),
+            "[libfuzzer]\nmax_len = 4096\nuse_value_profile = 1\n"
+        );
+        assert_eq!(oss_fuzz_options("my_fuzz_test", &[]), None);
+    }
+
+    #[test]
+    fn select_fuzz_tests() {
+        assert!(matches("foo::parser::my_fuzz_test", "my_fuzz_test"));
+        assert!(matches("foo::parser::my_fuzz_test", "parser::my_fuzz_test"));
+        assert!(!matches("foo::parser::my_fuzz_test", "fuzz_test"));
+    }
+}
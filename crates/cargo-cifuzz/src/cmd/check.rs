@@ -0,0 +1,333 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+
+use crate::build::{self, BuildMode, BuildResult, Builder, BuilderOptions};
+use crate::config::{self, parse_duration};
+use crate::log;
+use crate::parser;
+use crate::runner::libfuzzer::{Runner, RunnerOptions};
+
+/// Check that the fuzz tests work, as a fast smoke test
+///
+/// This command builds the fuzz tests of all members of the workspace
+/// like `cargo cifuzz run` and runs each of them on the first --runs
+/// inputs libFuzzer generates, starting from an empty corpus. It's
+/// meant to run on every pull request, to notice fuzz tests which can't
+/// find bugs anymore long before they're fuzzed for hours. A fuzz test
+/// fails the check if
+///
+///   * it crashes, `cargo cifuzz run` stores the crash as a finding,
+///   * it covers no code at all, e.g. because the instrument setting of
+///     the cifuzz.yaml excludes the code under test, or
+///   * none of the inputs covers more code than the empty input, i.e.
+///     the inputs don't reach the code under test, e.g. because decoding
+///     the parameters of the fuzz test already exhausts them or because
+///     they're rejected as invalid.
+///
+/// The results are printed as a table, and the command fails if any
+/// fuzz test failed the check:
+///
+///     FUZZ TEST     RESULT  EDGES  INPUTS
+///     my_fuzz_test  ok      64     1000
+///     parse_config  failed  3      1000
+///
+/// The seed corpus and the generated corpus aren't used, so that the
+/// result doesn't depend on them and the check is fast. Additional
+/// arguments for `cargo test` can be passed after a "--", e.g.
+/// "-- -p my_crate" to only check the fuzz tests of a package.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+pub struct CheckArgs {
+    /// Only check these fuzz tests, given by their names or (suffixes
+    /// of) their paths
+    fuzz_tests: Vec<String>,
+
+    /// The number of inputs to run each fuzz test on
+    #[arg(long, default_value_t = 1000, value_parser = clap::value_parser!(u64).range(1..))]
+    runs: u64,
+
+    /// Maximum time to run each fuzz test, e.g. "30s", after which it's
+    /// checked with the inputs it ran on so far
+    #[arg(long, default_value = "1m", value_parser = parse_duration)]
+    timeout: Duration,
+
+    /// Additional arguments to pass to `cargo test`
+    #[arg(last = true)]
+    cargo_args: Vec<String>,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+/// The outcome of the check of a fuzz test.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Passed,
+    /// The fuzz test crashed, with the panic message or the error
+    Crashed(String),
+    /// The fuzz test covered no edges
+    NoCoverage,
+    /// No input covered more edges than the empty input
+    NoNewCoverage,
+}
+
+/// The result of the check of a fuzz test.
+#[derive(Debug, PartialEq)]
+struct Check {
+    outcome: Outcome,
+    /// The edges covered by the inputs
+    edges: u64,
+    /// The number of inputs the fuzz test ran on
+    inputs: u64,
+}
+
+pub fn run(args: CheckArgs) -> Result<()> {
+    if args.timeout < Duration::from_secs(1) {
+        bail!("invalid argument for \"--timeout\" flag: timeout can't be less than a second");
+    }
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+
+    log::info!("Building the fuzz tests");
+    let builder = Builder::new(BuilderOptions {
+        project_dir: project_dir.clone(),
+        sanitizer: None,
+        mode: BuildMode::Fuzzing,
+        args: build::workspace_args(&args.cargo_args),
+    });
+    let mut fuzz_tests = builder.discover()?;
+    for name in &args.fuzz_tests {
+        if !fuzz_tests.iter().any(|t| matches(&t.path, name)) {
+            bail!("No fuzz test {name} found, `cargo cifuzz list` lists the fuzz tests");
+        }
+    }
+    if !args.fuzz_tests.is_empty() {
+        fuzz_tests.retain(|t| args.fuzz_tests.iter().any(|name| matches(&t.path, name)));
+    }
+    if fuzz_tests.is_empty() {
+        bail!("No fuzz tests found, fuzz tests are functions annotated with #[fuzz_test]");
+    }
+
+    let mut rows = Vec::new();
+    let mut failed = Vec::new();
+    for fuzz_test in &fuzz_tests {
+        let name = &fuzz_test.build.name;
+        log::info!("Checking {name}");
+        let work_dir = builder.build_dir().join("check").join(name);
+        let check = check(&fuzz_test.build, &work_dir, args.runs, args.timeout)?;
+        match &check.outcome {
+            Outcome::Passed => {}
+            Outcome::Crashed(error) => log::error!(
+                "{name} crashed: {error}, run `cargo cifuzz run {name}` to store the finding"
+            ),
+            Outcome::NoCoverage => log::error!(
+                "{name} covered no code, check that the code under test is instrumented"
+            ),
+            Outcome::NoNewCoverage => log::error!(
+                "None of the inputs of {name} covered more code than the empty input, check that \
+                 decoding the parameters doesn't exhaust the inputs and that they aren't rejected"
+            ),
+        }
+        let passed = check.outcome == Outcome::Passed;
+        if !passed {
+            failed.push(name.clone());
+        }
+        rows.push([
+            name.clone(),
+            if passed { "ok" } else { "failed" }.to_string(),
+            check.edges.to_string(),
+            check.inputs.to_string(),
+        ]);
+    }
+
+    let header = ["FUZZ TEST", "RESULT", "EDGES", "INPUTS"];
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let format_row = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    println!("{}", format_row(header.to_vec()));
+    for row in &rows {
+        println!("{}", format_row(row.iter().map(String::as_str).collect()));
+    }
+
+    if !failed.is_empty() {
+        bail!(
+            "{} of {} fuzz tests failed the check: {}",
+            failed.len(),
+            fuzz_tests.len(),
+            failed.join(", ")
+        );
+    }
+    log::success!("All {} fuzz tests passed the check", fuzz_tests.len());
+    Ok(())
+}
+
+/// Whether the path of a fuzz test matches a name or (a suffix of) a
+/// path passed by the user.
+pub fn matches(path: &str, name: &str) -> bool {
+    path == name || path.ends_with(&format!("::{name}"))
+}
+
+/// Runs the fuzz test on `runs` inputs generated from an empty corpus in
+/// the work directory.
+fn check(
+    build_result: &BuildResult,
+    work_dir: &Path,
+    runs: u64,
+    timeout: Duration,
+) -> Result<Check> {
+    if work_dir.exists() {
+        std::fs::remove_dir_all(work_dir)
+            .with_context(|| format!("failed to remove {}", work_dir.display()))?;
+    }
+    let corpus_dir = work_dir.join("corpus");
+    let artifact_dir = work_dir.join("artifacts");
+    for dir in [&corpus_dir, &artifact_dir] {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    let runner = Runner::new(RunnerOptions {
+        executable: build_result.executable.clone(),
+        test_name: build_result.test_name.clone(),
+        working_dir: build_result.package_dir.clone(),
+        generated_corpus_dir: corpus_dir,
+        seed_corpus_dirs: Vec::new(),
+        dictionary: None,
+        artifact_dir,
+        timeout: Some(timeout),
+        input_timeout: Some(timeout),
+        rss_limit_mb: None,
+        seed: None,
+        use_value_profile: false,
+        sanitizer: None,
+        detect_leaks: false,
+        engine_args: vec![format!("-runs={runs}")],
+        capture_output: true,
+        slow_inputs_dir: None,
+        slow_inputs: 0,
+        exit_on_plateau: None,
+    });
+    let mut cmd = runner.command();
+    cmd.stdout(Stdio::null()).stderr(Stdio::piped());
+    log::debug!("Command: {:?}", cmd);
+    let output = cmd
+        .output()
+        .with_context(|| format!("failed to execute {}", build_result.executable.display()))?;
+    let lines: Vec<String> = String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .map(String::from)
+        .collect();
+    Ok(evaluate(output.status.success(), &lines))
+}
+
+/// Evaluates the output of libFuzzer, which exited successfully or not.
+fn evaluate(success: bool, output: &[String]) -> Check {
+    let stats: Vec<(&str, parser::Stats)> = output
+        .iter()
+        .filter_map(|line| Some((line.as_str(), parser::parse_stats(line)?)))
+        .collect();
+    let initial = stats
+        .iter()
+        .find(|(line, _)| line.split_whitespace().nth(1) == Some("INITED"))
+        .map(|(_, stats)| stats);
+    let last = stats.last().map(|(_, stats)| *stats).unwrap_or_default();
+    let outcome = if !success {
+        let report = parser::parse_crash(output);
+        Outcome::Crashed(
+            report
+                .and_then(|report| report.panic_message.or(report.error))
+                .unwrap_or_else(|| "the fuzz test exited unexpectedly".to_string()),
+        )
+    } else if last.cov == 0 {
+        Outcome::NoCoverage
+    } else if initial.is_none_or(|initial| last.cov <= initial.cov) {
+        Outcome::NoNewCoverage
+    } else {
+        Outcome::Passed
+    };
+    Check {
+        outcome,
+        edges: last.cov,
+        inputs: last.execs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(output: &str) -> Vec<String> {
+        output.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn evaluate_output() {
+        let output = lines(
+            "INFO: A corpus is not provided, starting from an empty corpus\n\
+             #2\tINITED cov: 12 ft: 12 corp: 1/1b exec/s: 0 rss: 30Mb\n\
+             #3\tNEW    cov: 15 ft: 16 corp: 2/5b lim: 4 exec/s: 0 rss: 30Mb\n\
+             #1000\tDONE   cov: 64 ft: 80 corp: 21/1434b lim: 14 exec/s: 0 rss: 31Mb\n",
+        );
+        assert_eq!(
+            evaluate(true, &output),
+            Check {
+                outcome: Outcome::Passed,
+                edges: 64,
+                inputs: 1000,
+            }
+        );
+
+        let output = lines(
+            "#2\tINITED cov: 12 ft: 12 corp: 1/1b exec/s: 0 rss: 30Mb\n\
+             #1000\tDONE   cov: 12 ft: 12 corp: 1/1b lim: 4 exec/s: 0 rss: 31Mb\n",
+        );
+        assert_eq!(evaluate(true, &output).outcome, Outcome::NoNewCoverage);
+
+        let output = lines("#1000\tDONE   cov: 0 ft: 0 corp: 1/1b exec/s: 0 rss: 31Mb\n");
+        assert_eq!(evaluate(true, &output).outcome, Outcome::NoCoverage);
+
+        let output = lines(
+            "#2\tINITED cov: 12 ft: 12 corp: 1/1b exec/s: 0 rss: 30Mb\n\
+             thread '<unnamed>' panicked at src/lib.rs:3:5:\n\
+             index out of bounds: the len is 0 but the index is 0\n\
+             ==1== ERROR: libFuzzer: deadly signal\n",
+        );
+        let check = evaluate(false, &output);
+        assert!(
+            matches!(&check.outcome, Outcome::Crashed(error) if error.contains("index out of bounds")),
+            "{check:?}"
+        );
+        assert_eq!(check.edges, 12);
+    }
+
+    #[test]
+    fn match_fuzz_tests() {
+        assert!(matches("my_crate::parser::my_fuzz_test", "my_fuzz_test"));
+        assert!(matches(
+            "my_crate::parser::my_fuzz_test",
+            "parser::my_fuzz_test"
+        ));
+        assert!(!matches(
+            "my_crate::parser::other_fuzz_test",
+            "my_fuzz_test"
+        ));
+        assert!(!matches("my_crate::parser::my_fuzz_test", "fuzz_test"));
+    }
+}
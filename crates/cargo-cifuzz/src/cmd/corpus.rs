@@ -0,0 +1,710 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Subcommand};
+
+use crate::build::{self, cargo, BuildMode, BuildResult, Builder, BuilderOptions};
+use crate::cmd::check::matches;
+use crate::config;
+use crate::corpus;
+use crate::log;
+use crate::merge::{self, Summary};
+use crate::runner::libfuzzer::{Runner, RunnerOptions};
+use crate::sync::{self, Remote};
+use crate::workspace::Workspace;
+
+/// Manage the corpus of a fuzz test
+#[derive(Debug, Args)]
+pub struct CorpusArgs {
+    #[command(subcommand)]
+    command: CorpusCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum CorpusCommand {
+    CrossPollinate(CrossPollinateArgs),
+    Export(ExportArgs),
+    Import(ImportArgs),
+    Prune(PruneArgs),
+    Record(RecordArgs),
+    Sync(SyncArgs),
+}
+
+/// The environment variable with the directory which cifuzz::seed!
+/// records the inputs into.
+// Must be kept in sync with crates/cifuzz/src/seed.rs
+const RECORD_DIR_ENV: &str = "CIFUZZ_SEED_RECORD_DIR";
+
+/// Remove inputs from the generated corpus which don't add coverage
+///
+/// This command builds the fuzz test like 'cargo cifuzz run', executes
+/// it with the inputs of its seed corpus and of its generated corpus in
+/// `.cifuzz-corpus/<FUZZ_TEST>` and removes the generated inputs whose
+/// coverage is already reached by other inputs. Smaller inputs are
+/// preferred, so that the corpus stays fast to execute.
+///
+/// The seed corpus is never modified. Inputs on which the fuzz test
+/// crashes are kept. With --dry-run, the corpus is only analyzed, e.g.
+/// to check in CI how much it would shrink.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+struct PruneArgs {
+    /// The fuzz test whose corpus is pruned
+    fuzz_test: String,
+
+    /// Only show how the corpus would change, don't remove any inputs
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Additional arguments to pass to `cargo test`
+    #[arg(last = true)]
+    cargo_args: Vec<String>,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+/// Add the inputs of the other fuzz tests which add coverage to the corpus
+///
+/// This command builds all fuzz tests of the workspace like 'cargo
+/// cifuzz check' and executes every fuzz test with the inputs of the
+/// seed corpora and the generated corpora of all the other ones. The
+/// inputs which cover code its own corpus doesn't cover are added to
+/// its generated corpus in `.cifuzz-corpus/<FUZZ_TEST>`, so that fuzz
+/// tests of the same library start from each other's discoveries.
+///
+/// With fuzz test names, only the corpora of these fuzz tests are
+/// exchanged. The corpora are read before any input is added, so the
+/// order of the fuzz tests doesn't matter. Inputs on which a fuzz test
+/// crashes are skipped, run the fuzz test on them to store the finding.
+/// With --dry-run, the inputs are only counted.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+struct CrossPollinateArgs {
+    /// The fuzz tests whose corpora are exchanged. Defaults to all fuzz
+    /// tests of the workspace.
+    fuzz_tests: Vec<String>,
+
+    /// Only show how many inputs would be added, don't add any inputs
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Additional arguments to pass to `cargo test`
+    #[arg(last = true)]
+    cargo_args: Vec<String>,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+/// Record the inputs of unit tests into the seed corpus
+///
+/// This command executes the tests of the project with 'cargo test' and
+/// stores the inputs which the unit tests record with cifuzz::seed! in
+/// the `<FUZZ_TEST>_inputs` directories of the fuzz tests, so that the
+/// fuzzer starts with the coverage the unit tests reach. cifuzz::seed!
+/// runs a fuzz test with the given values, e.g. a unit test calling
+///
+///     cifuzz::seed!(my_fuzz_test, 397652_i64, 3082562284_i64, "FUZZ");
+///
+/// records the input from which my_fuzz_test consumes these values.
+/// Inputs which are already in the seed corpus are skipped. Tests which
+/// fail don't record inputs.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+struct RecordArgs {
+    /// Only store the inputs of this fuzz test
+    fuzz_test: Option<String>,
+
+    /// Additional arguments to pass to `cargo test`
+    #[arg(last = true)]
+    cargo_args: Vec<String>,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+/// Synchronize the generated corpus of a fuzz test with a remote corpus
+///
+/// This command downloads the inputs of the remote corpus of the fuzz
+/// test into its generated corpus in `.cifuzz-corpus/<FUZZ_TEST>`, and
+/// uploads the inputs of the generated corpus which the remote corpus
+/// doesn't contain yet. `cargo cifuzz run` does the same before and
+/// after fuzzing if the corpus-remote of the cifuzz.yaml is set.
+///
+/// The remote corpus of the fuzz test is the `<FUZZ_TEST>` directory of
+/// the --remote, which defaults to the corpus-remote, e.g.
+///
+///     s3://my-bucket/corpus       synced with the AWS CLI
+///     gs://my-bucket/corpus       synced with the Google Cloud CLI
+///     https://example.com/corpus  listed and downloaded with GET,
+///                                 uploaded with PUT by curl
+///     /mnt/shared/corpus          a directory
+///
+/// Inputs are named after the SHA-1 of their content, so that the
+/// inputs uploaded by different runs never conflict. Inputs are never
+/// removed from the remote corpus.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+struct SyncArgs {
+    /// The fuzz test whose corpus is synchronized
+    fuzz_test: String,
+
+    /// The remote corpus. Defaults to the corpus-remote of the
+    /// cifuzz.yaml.
+    #[arg(long, value_name = "URL")]
+    remote: Option<String>,
+
+    /// Only download the inputs of the remote corpus
+    #[arg(long, conflicts_with = "push")]
+    pull: bool,
+
+    /// Only upload the inputs of the generated corpus
+    #[arg(long)]
+    push: bool,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+/// Import the inputs of an AFL++ queue into the seed corpus
+///
+/// This command copies the inputs of the queue directories of AFL++ in
+/// `<AFL_DIR>` into the `<FUZZ_TEST>_inputs` directory of the fuzz test,
+/// named like libFuzzer names the inputs it generates. `<AFL_DIR>` is
+/// either the output directory of afl-fuzz, which may contain multiple
+/// fuzzer instances, the directory of one instance or a queue
+/// directory.
+///
+/// The inputs are imported into the existing `<FUZZ_TEST>_inputs`
+/// directory of the fuzz test, or a new one next to the source file
+/// defining it. Inputs which are already in the directory are skipped.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+struct ImportArgs {
+    /// The fuzz test to import the inputs for
+    fuzz_test: String,
+
+    /// The AFL++ output or queue directory
+    afl_dir: PathBuf,
+
+    /// The directory to import the inputs into
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+/// Export the corpus of a fuzz test as AFL++ seeds
+///
+/// This command copies the inputs of the seed corpus and of the
+/// generated corpus in `.cifuzz-corpus/<FUZZ_TEST>` into `<OUTPUT>`, named
+/// like AFL++ names the seeds in its queue, so that the directory can
+/// be passed to afl-fuzz with -i. Inputs with the same content are only
+/// exported once.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+struct ExportArgs {
+    /// The fuzz test whose corpus is exported
+    fuzz_test: String,
+
+    /// The directory to export the inputs into, which must not contain
+    /// inputs yet
+    output: PathBuf,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+pub fn run(args: CorpusArgs) -> Result<()> {
+    match args.command {
+        CorpusCommand::CrossPollinate(args) => cross_pollinate(args),
+        CorpusCommand::Export(args) => export(args),
+        CorpusCommand::Import(args) => import(args),
+        CorpusCommand::Prune(args) => prune(args),
+        CorpusCommand::Record(args) => record(args),
+        CorpusCommand::Sync(args) => sync(args),
+    }
+}
+
+fn sync(args: SyncArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let Some(url) = args
+        .remote
+        .or(config::parse_project_config(&project_dir)?.corpus_remote)
+    else {
+        bail!("No remote corpus, pass --remote or set the corpus-remote in the cifuzz.yaml");
+    };
+    let remote = Remote::parse(&url, &project_dir)?;
+    let name = fuzz_test_name(&args.fuzz_test);
+    let dir = project_dir.join(".cifuzz-corpus").join(name);
+    if !args.push {
+        let pulled = remote.pull(name, &dir)?;
+        log::success!("Pulled {pulled} new inputs of {name} from {url}");
+    }
+    if !args.pull {
+        let pushed = remote.push(name, &dir, &sync::staging_dir(&project_dir, name))?;
+        log::success!("Pushed {pushed} new inputs of {name} to {url}");
+    }
+    Ok(())
+}
+
+fn record(args: RecordArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let record_dir = project_dir.join(".cifuzz-build").join("seeds");
+    if record_dir.exists() {
+        std::fs::remove_dir_all(&record_dir)
+            .with_context(|| format!("failed to remove {}", record_dir.display()))?;
+    }
+
+    log::info!("Running the tests to record seeds");
+    let mut cmd = Command::new(cargo());
+    cmd.arg("test")
+        .args(&args.cargo_args)
+        .env(RECORD_DIR_ENV, &record_dir)
+        .current_dir(&project_dir);
+    log::debug!("Command: {:?}", cmd);
+    let status = cmd.status().context("failed to execute cargo")?;
+    if !status.success() {
+        log::info!("Some tests failed, their inputs are not recorded");
+    }
+
+    let mut fuzz_tests = Vec::new();
+    if record_dir.is_dir() {
+        for entry in std::fs::read_dir(&record_dir)
+            .with_context(|| format!("failed to read {}", record_dir.display()))?
+        {
+            let entry = entry?;
+            fuzz_tests.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    fuzz_tests.sort();
+    if let Some(fuzz_test) = &args.fuzz_test {
+        let name = fuzz_test_name(fuzz_test);
+        fuzz_tests.retain(|recorded| recorded == name);
+    }
+    if fuzz_tests.is_empty() {
+        log::info!("No seeds were recorded, see 'cargo cifuzz corpus record --help'");
+        return Ok(());
+    }
+
+    for fuzz_test in fuzz_tests {
+        let inputs = corpus::list_inputs(&[record_dir.join(&fuzz_test)])?;
+        let output = inputs_dir(&project_dir, &fuzz_test)?;
+        let imported = corpus::import(&inputs, &output)?;
+        log::success!(
+            "Recorded {} new of {} inputs of {} into {}",
+            imported,
+            inputs.len(),
+            fuzz_test,
+            output.display()
+        );
+    }
+    Ok(())
+}
+
+fn import(args: ImportArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let name = fuzz_test_name(&args.fuzz_test);
+    let output = match args.output {
+        Some(output) => output,
+        None => inputs_dir(&project_dir, name)?,
+    };
+
+    let mut inputs = Vec::new();
+    for queue in corpus::afl_queue_dirs(&args.afl_dir)? {
+        let queue_inputs = corpus::afl_queue_inputs(&queue)?;
+        log::debug!("Found {} inputs in {}", queue_inputs.len(), queue.display());
+        inputs.extend(queue_inputs);
+    }
+    let imported = corpus::import(&inputs, &output)?;
+    log::success!(
+        "Imported {} of {} inputs into {}",
+        imported,
+        inputs.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+fn export(args: ExportArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let name = fuzz_test_name(&args.fuzz_test);
+    let mut dirs = Vec::new();
+    for package_dir in package_dirs(&project_dir)? {
+        dirs.extend(build::seed_corpus_dirs(&package_dir, name)?);
+    }
+    dirs.push(project_dir.join(".cifuzz-corpus").join(name));
+
+    let inputs = corpus::list_inputs(&dirs)?;
+    if inputs.is_empty() {
+        bail!("The fuzz test {name} has no inputs");
+    }
+    let exported = corpus::export(&inputs, &args.output)?;
+    log::success!(
+        "Exported {} inputs into {}",
+        exported,
+        args.output.display()
+    );
+    Ok(())
+}
+
+/// Returns the name of the fuzz test function, which may be specified
+/// with its module path.
+fn fuzz_test_name(fuzz_test: &str) -> &str {
+    fuzz_test.rsplit("::").next().unwrap_or(fuzz_test)
+}
+
+/// Returns the directories of the packages of the project.
+fn package_dirs(project_dir: &Path) -> Result<Vec<PathBuf>> {
+    let workspace = Workspace::load(project_dir)?;
+    Ok(workspace
+        .members
+        .iter()
+        .filter_map(|member| member.manifest_path.parent().map(Path::to_path_buf))
+        .collect())
+}
+
+/// Returns the `<fuzz_test>_inputs` directory of the fuzz test, which is
+/// the one that exists or the one next to the source file defining it.
+fn inputs_dir(project_dir: &Path, fuzz_test: &str) -> Result<PathBuf> {
+    let dir_name = format!("{fuzz_test}_inputs");
+    let mut dirs = Vec::new();
+    let mut sources = Vec::new();
+    for package_dir in package_dirs(project_dir)? {
+        dirs.extend(
+            build::seed_corpus_dirs(&package_dir, fuzz_test)?
+                .into_iter()
+                .filter(|dir| dir.ends_with(&dir_name)),
+        );
+        sources.extend(corpus::find_fuzz_test_sources(&package_dir, fuzz_test)?);
+    }
+    if dirs.is_empty() && sources.len() == 1 {
+        dirs.push(sources[0].with_file_name(&dir_name));
+    }
+    match dirs.len() {
+        1 => Ok(dirs.pop().unwrap()),
+        0 if sources.is_empty() => bail!("Fuzz test {fuzz_test:?} not found"),
+        _ => bail!(
+            "The directory of the seed corpus of {fuzz_test} is ambiguous, specify it with --output"
+        ),
+    }
+}
+
+fn prune(args: PruneArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+
+    log::info!("Building {}", args.fuzz_test);
+    let builder = Builder::new(BuilderOptions {
+        project_dir: project_dir.clone(),
+        sanitizer: None,
+        mode: BuildMode::Fuzzing,
+        args: args.cargo_args,
+    });
+    let build_result = builder.build_for_run(&args.fuzz_test)?;
+    log::success!("Built fuzz test {}", build_result.name);
+
+    let corpus_dir = &build_result.generated_corpus;
+    let generated = corpus::list_inputs(std::slice::from_ref(corpus_dir))?;
+    if generated.is_empty() {
+        log::info!("The corpus {} is empty", corpus_dir.display());
+        return Ok(());
+    }
+    let seeds = corpus::list_inputs(&build_result.seed_corpus_dirs)?;
+
+    let work_dir = builder.build_dir().join("corpus");
+    std::fs::create_dir_all(&work_dir)
+        .with_context(|| format!("failed to create {}", work_dir.display()))?;
+    let control_file = work_dir.join(format!("{}.merge", build_result.name));
+    let runner = merge_runner(&project_dir, &build_result);
+
+    log::info!(
+        "Executing {} with {} inputs",
+        build_result.name,
+        seeds.len() + generated.len()
+    );
+    let inputs: Vec<PathBuf> = seeds.iter().chain(&generated).cloned().collect();
+    let coverage = merge::execute(&runner, &control_file, &inputs, seeds.len())?;
+    let before = merge::summarize(&coverage);
+
+    // The inputs which crash can't be compared, so they are kept
+    let mut keep = merge::select(&coverage, seeds.len());
+    keep.extend((seeds.len()..inputs.len()).filter(|&i| coverage[i].crashed));
+    keep.sort_unstable();
+    let (kept, removed): (Vec<_>, Vec<_>) = (seeds.len()..inputs.len())
+        .map(|i| (i, &inputs[i]))
+        .partition(|(i, _)| keep.binary_search(i).is_ok());
+
+    // The edges of the inputs are only recorded by the first input
+    // covering them, so the pruned corpus is executed again to count
+    // them
+    log::info!("Executing {} with the pruned corpus", build_result.name);
+    let pruned: Vec<PathBuf> = seeds
+        .iter()
+        .chain(kept.iter().map(|(_, input)| *input))
+        .cloned()
+        .collect();
+    let after = merge::summarize(&merge::execute(
+        &runner,
+        &work_dir.join(format!("{}.pruned.merge", build_result.name)),
+        &pruned,
+        seeds.len(),
+    )?);
+    log::info!("\n{}", format_summary(&before, &after));
+
+    if args.dry_run {
+        log::success!(
+            "{} of {} inputs in {} are redundant",
+            removed.len(),
+            generated.len(),
+            corpus_dir.display()
+        );
+        return Ok(());
+    }
+    for (_, input) in &removed {
+        std::fs::remove_file(input)
+            .with_context(|| format!("failed to remove {}", input.display()))?;
+    }
+    log::success!(
+        "Removed {} of {} inputs from {}",
+        removed.len(),
+        generated.len(),
+        corpus_dir.display()
+    );
+    Ok(())
+}
+
+/// Returns a runner executing the fuzz test for a merge.
+fn merge_runner(project_dir: &Path, build_result: &BuildResult) -> Runner {
+    Runner::new(RunnerOptions {
+        executable: build_result.executable.clone(),
+        test_name: build_result.test_name.clone(),
+        working_dir: build_result.package_dir.clone(),
+        generated_corpus_dir: build_result.generated_corpus.clone(),
+        seed_corpus_dirs: Vec::new(),
+        dictionary: None,
+        artifact_dir: project_dir
+            .join(".cifuzz-artifacts")
+            .join(&build_result.name),
+        timeout: None,
+        input_timeout: None,
+        rss_limit_mb: None,
+        seed: None,
+        use_value_profile: false,
+        sanitizer: None,
+        detect_leaks: false,
+        engine_args: Vec::new(),
+        capture_output: false,
+        slow_inputs_dir: None,
+        slow_inputs: 0,
+        exit_on_plateau: None,
+    })
+}
+
+fn cross_pollinate(args: CrossPollinateArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+
+    log::info!("Building the fuzz tests");
+    let builder = Builder::new(BuilderOptions {
+        project_dir: project_dir.clone(),
+        sanitizer: None,
+        mode: BuildMode::Fuzzing,
+        args: build::workspace_args(&args.cargo_args),
+    });
+    let mut fuzz_tests = builder.discover()?;
+    for name in &args.fuzz_tests {
+        if !fuzz_tests.iter().any(|t| matches(&t.path, name)) {
+            bail!("No fuzz test {name} found, `cargo cifuzz list` lists the fuzz tests");
+        }
+    }
+    if !args.fuzz_tests.is_empty() {
+        fuzz_tests.retain(|t| args.fuzz_tests.iter().any(|name| matches(&t.path, name)));
+    }
+    if fuzz_tests.len() < 2 {
+        bail!(
+            "Cross-pollinating needs at least two fuzz tests, found {}",
+            fuzz_tests.len()
+        );
+    }
+
+    let mut corpora = Vec::new();
+    for fuzz_test in &fuzz_tests {
+        let build = &fuzz_test.build;
+        let mut dirs = build.seed_corpus_dirs.clone();
+        dirs.push(build.generated_corpus.clone());
+        let mut inputs = Vec::new();
+        for input in corpus::list_inputs(&dirs)? {
+            inputs.push((corpus::digest(&input)?, input));
+        }
+        corpora.push(inputs);
+    }
+
+    let work_dir = builder.build_dir().join("corpus");
+    std::fs::create_dir_all(&work_dir)
+        .with_context(|| format!("failed to create {}", work_dir.display()))?;
+    let mut rows = Vec::new();
+    for (i, fuzz_test) in fuzz_tests.iter().enumerate() {
+        let build = &fuzz_test.build;
+        let own = &corpora[i];
+        let others = foreign_inputs(&corpora, i);
+        if others.is_empty() {
+            rows.push((build.name.clone(), 0, 0));
+            continue;
+        }
+        log::info!(
+            "Executing {} with {} inputs of the other fuzz tests",
+            build.name,
+            others.len()
+        );
+        let runner = merge_runner(&project_dir, build);
+        let inputs: Vec<PathBuf> = own
+            .iter()
+            .map(|(_, input)| input)
+            .chain(&others)
+            .cloned()
+            .collect();
+        let control_file = work_dir.join(format!("{}.cross-pollinate.merge", build.name));
+        let coverage = merge::execute(&runner, &control_file, &inputs, own.len())?;
+        let selected: Vec<PathBuf> = merge::select(&coverage, own.len())
+            .into_iter()
+            .map(|i| inputs[i].clone())
+            .collect();
+        let added = if args.dry_run {
+            selected.len()
+        } else {
+            corpus::import(&selected, &build.generated_corpus)?
+        };
+        rows.push((build.name.clone(), others.len(), added));
+    }
+    log::info!("\n{}", format_cross_pollination(&rows));
+
+    let added: usize = rows.iter().map(|(_, _, added)| added).sum();
+    if args.dry_run {
+        log::success!("{added} inputs would add coverage to the fuzz tests");
+    } else {
+        log::success!("Added {added} inputs to the corpora of the fuzz tests");
+    }
+    Ok(())
+}
+
+/// Returns the inputs of all corpora but the one at `index` which it
+/// doesn't contain, given with the digests of their content. Inputs with
+/// the same content are only returned once.
+fn foreign_inputs(corpora: &[Vec<(String, PathBuf)>], index: usize) -> Vec<PathBuf> {
+    let mut seen: HashSet<&str> = corpora[index]
+        .iter()
+        .map(|(digest, _)| digest.as_str())
+        .collect();
+    corpora
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != index)
+        .flat_map(|(_, inputs)| inputs)
+        .filter(|(digest, _)| seen.insert(digest))
+        .map(|(_, input)| input.clone())
+        .collect()
+}
+
+/// Formats the inputs each fuzz test was executed with and the number of
+/// them which added coverage as a table.
+fn format_cross_pollination(rows: &[(String, usize, usize)]) -> String {
+    let width = rows
+        .iter()
+        .map(|(name, _, _)| name.len())
+        .chain(["Fuzz test".len()])
+        .max()
+        .unwrap_or_default();
+    let mut table = format!(
+        "{:width$} | {:>10} | {:>10}\n",
+        "Fuzz test", "Inputs", "Added"
+    );
+    for (name, inputs, added) in rows {
+        table.push_str(&format!("{name:width$} | {inputs:>10} | {added:>10}\n"));
+    }
+    table
+}
+
+/// Formats the coverage of the corpus before and after pruning as a
+/// table. Both include the seed corpus.
+fn format_summary(before: &Summary, after: &Summary) -> String {
+    let rows = [
+        ("Inputs", before.inputs as u64, after.inputs as u64),
+        ("Bytes", before.bytes, after.bytes),
+        ("Features", before.features as u64, after.features as u64),
+        ("Edges", before.edges as u64, after.edges as u64),
+    ];
+    let mut table = format!("{:8} | {:>10} | {:>10}\n", "", "Before", "After");
+    for (label, before, after) in rows {
+        table.push_str(&format!("{label:8} | {before:>10} | {after:>10}\n"));
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_table() {
+        let before = Summary {
+            inputs: 120,
+            bytes: 4096,
+            features: 300,
+            edges: 95,
+        };
+        let after = Summary {
+            inputs: 14,
+            bytes: 230,
+            ..before
+        };
+        let table = format_summary(&before, &after);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "         |     Before |      After");
+        assert_eq!(lines[1], "Inputs   |        120 |         14");
+        assert_eq!(lines[4], "Edges    |         95 |         95");
+    }
+
+    #[test]
+    fn foreign_inputs_of_other_corpora() {
+        let corpus = |inputs: &[(&str, &str)]| -> Vec<(String, PathBuf)> {
+            inputs
+                .iter()
+                .map(|(digest, path)| (digest.to_string(), PathBuf::from(path)))
+                .collect()
+        };
+        let corpora = vec![
+            corpus(&[("1", "a/1"), ("2", "a/2")]),
+            corpus(&[("2", "b/2"), ("3", "b/3")]),
+            corpus(&[("3", "c/3"), ("4", "c/4")]),
+        ];
+        assert_eq!(
+            foreign_inputs(&corpora, 0),
+            [PathBuf::from("b/3"), PathBuf::from("c/4")]
+        );
+        assert_eq!(
+            foreign_inputs(&corpora, 2),
+            [PathBuf::from("a/1"), PathBuf::from("a/2")]
+        );
+
+        let table = format_cross_pollination(&[("parse_fuzz_test".to_string(), 12, 3)]);
+        assert_eq!(
+            table.lines().nth(1).unwrap(),
+            "parse_fuzz_test |         12 |          3"
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Args;
+
+use crate::build::{BuildMode, Builder, BuilderOptions};
+use crate::config;
+use crate::coverage;
+use crate::lcov;
+use crate::log;
+use crate::runner::libfuzzer::{Runner, RunnerOptions};
+
+/// Generate a coverage report for a fuzz test
+///
+/// This command builds the fuzz test with coverage instrumentation,
+/// executes it with all inputs of its corpus in
+/// `.cifuzz-corpus/<FUZZ_TEST>` and of its seed corpus and creates an
+/// LCOV tracefile and an HTML report showing which lines and branches
+/// were reached.
+///
+/// The reports are stored in `.cifuzz-coverage/<FUZZ_TEST>` in the
+/// project directory:
+///
+///     coverage.lcov     the LCOV tracefile
+///     html/index.html   the HTML report
+///
+/// With --diff, the coverage is compared to an earlier LCOV tracefile,
+/// e.g. the coverage.lcov of a run before changing the corpus or the
+/// fuzz test, and the lines and branches which are newly covered or no
+/// longer covered are listed.
+///
+/// The llvm-profdata and llvm-cov tools of the llvm-tools rustup
+/// component are required:
+///
+///     rustup component add llvm-tools
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+pub struct CoverageArgs {
+    /// The fuzz test to generate the coverage report for
+    fuzz_test: String,
+
+    /// The directory in which the reports are stored
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// An LCOV tracefile to compare the coverage to
+    #[arg(long, value_name = "BASELINE")]
+    diff: Option<PathBuf>,
+
+    /// Additional arguments to pass to `cargo test`
+    #[arg(last = true)]
+    cargo_args: Vec<String>,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+pub fn run(args: CoverageArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    // The baseline is read first, because it may be the tracefile which
+    // is overwritten by this run
+    let baseline = match &args.diff {
+        Some(path) => {
+            let tracefile = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let report = lcov::parse(&tracefile)
+                .with_context(|| format!("failed to parse {}", path.display()))?;
+            Some((path, report.strip_prefix(&project_dir)))
+        }
+        None => None,
+    };
+
+    log::info!("Building {} with coverage instrumentation", args.fuzz_test);
+    let builder = Builder::new(BuilderOptions {
+        project_dir: project_dir.clone(),
+        sanitizer: None,
+        mode: BuildMode::Coverage,
+        args: args.cargo_args,
+    });
+    let build_result = builder.build_for_run(&args.fuzz_test)?;
+    log::success!("Built fuzz test {}", build_result.name);
+
+    let output_dir = args.output.unwrap_or_else(|| {
+        project_dir
+            .join(".cifuzz-coverage")
+            .join(&build_result.name)
+    });
+    std::fs::create_dir_all(&output_dir)
+        .with_context(|| format!("failed to create {}", output_dir.display()))?;
+
+    // Profiles of earlier runs must not end up in the report
+    let profile_dir = builder
+        .build_dir()
+        .join("profiles")
+        .join(&build_result.name);
+    if profile_dir.exists() {
+        std::fs::remove_dir_all(&profile_dir)
+            .with_context(|| format!("failed to remove {}", profile_dir.display()))?;
+    }
+    std::fs::create_dir_all(&profile_dir)?;
+
+    let corpus_dirs: Vec<PathBuf> = [build_result.generated_corpus.clone()]
+        .into_iter()
+        .filter(|dir| dir.is_dir())
+        .chain(build_result.seed_corpus_dirs.iter().cloned())
+        .collect();
+    let runner = Runner::new(RunnerOptions {
+        executable: build_result.executable.clone(),
+        test_name: build_result.test_name.clone(),
+        working_dir: build_result.package_dir.clone(),
+        generated_corpus_dir: build_result.generated_corpus.clone(),
+        seed_corpus_dirs: Vec::new(),
+        dictionary: None,
+        artifact_dir: project_dir
+            .join(".cifuzz-artifacts")
+            .join(&build_result.name),
+        timeout: None,
+        input_timeout: None,
+        rss_limit_mb: None,
+        seed: None,
+        use_value_profile: false,
+        sanitizer: None,
+        detect_leaks: false,
+        engine_args: Vec::new(),
+        capture_output: false,
+        slow_inputs_dir: None,
+        slow_inputs: 0,
+        exit_on_plateau: None,
+    });
+    log::info!("Running {} on its corpus", build_result.name);
+    let result = runner.replay_corpus(&corpus_dirs, &profile_dir.join("%p.profraw"))?;
+    if !result.status.success() {
+        // The profile of a crashing process is not written
+        log::error!(
+            "The fuzz test {} crashed while replaying the corpus:\n{}",
+            build_result.name,
+            result.output.join("\n")
+        );
+    }
+
+    let profile = profile_dir.join("merged.profdata");
+    coverage::merge_profiles(&profile_dir, &profile)?;
+
+    let lcov_file = output_dir.join("coverage.lcov");
+    let tracefile = coverage::export_lcov(&build_result.executable, &profile)?;
+    std::fs::write(&lcov_file, &tracefile)
+        .with_context(|| format!("failed to write {}", lcov_file.display()))?;
+    let html_dir = output_dir.join("html");
+    coverage::generate_html(&build_result.executable, &profile, &html_dir)?;
+
+    let report = lcov::parse(&tracefile)?;
+    log::info!("\n{}", format_summary(&report, &project_dir));
+    if let Some((path, baseline)) = baseline {
+        let diff = lcov::diff(&baseline, &report.strip_prefix(&project_dir));
+        if diff.is_empty() {
+            log::info!("No coverage changes compared to {}", path.display());
+        } else {
+            log::info!(
+                "Coverage changes compared to {}:\n{}",
+                path.display(),
+                format_diff(&diff)
+            );
+        }
+    }
+    log::success!("Created coverage reports in {}", output_dir.display());
+    log::info!(
+        "Open {} to view the HTML report",
+        html_dir.join("index.html").display()
+    );
+    Ok(())
+}
+
+/// Formats the coverage of every file and the total as a table.
+fn format_summary(report: &lcov::Report, project_dir: &Path) -> String {
+    let mut rows: Vec<(String, lcov::Summary)> = report
+        .files
+        .iter()
+        .map(|(path, file)| {
+            let path = Path::new(path);
+            let path = path.strip_prefix(project_dir).unwrap_or(path);
+            (path.display().to_string(), file.summary())
+        })
+        .collect();
+    rows.push(("Total".to_string(), report.summary()));
+
+    let width = rows.iter().map(|(path, _)| path.len()).max().unwrap_or(0);
+    let mut table = format!(
+        "{:width$} | {:>18} | {:>18} | {:>18}\n",
+        "File", "Functions", "Lines", "Branches"
+    );
+    for (path, summary) in rows {
+        table.push_str(&format!(
+            "{:width$} | {:>18} | {:>18} | {:>18}\n",
+            path,
+            summary.functions.to_string(),
+            summary.lines.to_string(),
+            summary.branches.to_string()
+        ));
+    }
+    table
+}
+
+/// Formats the changed lines and branches of every file.
+fn format_diff(diff: &BTreeMap<String, lcov::FileDiff>) -> String {
+    let mut text = String::new();
+    for (path, file) in diff {
+        text.push_str(&format!("{path}\n"));
+        let changes = [
+            ("Newly covered lines", &file.newly_covered_lines),
+            ("Newly uncovered lines", &file.newly_uncovered_lines),
+            ("Newly covered branches", &file.newly_covered_branches),
+            ("Newly uncovered branches", &file.newly_uncovered_branches),
+        ];
+        for (label, lines) in changes {
+            if !lines.is_empty() {
+                text.push_str(&format!("  {label}: {}\n", lcov::format_lines(lines)));
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_table() {
+        let report =
+            lcov::parse("SF:/p/src/a.rs\nFNDA:1,f\nDA:1,1\nDA:2,0\nBRDA:1,0,0,1\nend_of_record\n")
+                .unwrap();
+        let table = format_summary(&report, Path::new("/p"));
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("src/a.rs |"), "{table}");
+        assert!(lines[1].contains("1/2 (50.0%)"), "{table}");
+        assert!(lines[2].starts_with("Total    |"), "{table}");
+    }
+
+    #[test]
+    fn diff_listing() {
+        let baseline = lcov::parse("SF:src/a.rs\nDA:1,1\nDA:2,0\nDA:3,0\nend_of_record\n").unwrap();
+        let current = lcov::parse("SF:src/a.rs\nDA:1,0\nDA:2,1\nDA:3,1\nend_of_record\n").unwrap();
+        assert_eq!(
+            format_diff(&lcov::diff(&baseline, &current)),
+            "src/a.rs\n  Newly covered lines: 2-3\n  Newly uncovered lines: 1\n"
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Args;
+
+use crate::log;
+use crate::stubs;
+
+/// Create a new fuzz test
+///
+/// This command creates a new templated fuzz test source file in the
+/// current directory and declares it as a test module in the parent
+/// module (mod.rs, lib.rs or main.rs in the same directory).
+///
+/// After running this command, you should edit the created file in
+/// order to make it call the functions you want to fuzz. You can then
+/// execute the fuzz test via 'cargo cifuzz run'.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+pub struct CreateArgs {
+    /// File path of new fuzz test. The file name is used as the name of
+    /// the fuzz test.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub fn run(args: CreateArgs) -> Result<()> {
+    let path = match args.output {
+        Some(path) => path,
+        None => stubs::fuzz_test_filename(&std::env::current_dir()?),
+    };
+    log::debug!("Output path: {}", path.display());
+    let name = stubs::module_name(&path)?;
+
+    stubs::create(&path)
+        .with_context(|| format!("Failed to create fuzz test stub {}", path.display()))?;
+    log::success!("Created fuzz test stub {}", path.display());
+
+    // Files in the tests directory are integration tests, which are
+    // crates of their own
+    let is_integration_test = path
+        .parent()
+        .and_then(|dir| dir.file_name())
+        .is_some_and(|dir| dir == "tests");
+    match stubs::parent_module(&path) {
+        Some(parent) => {
+            if stubs::register_module(&parent, name)? {
+                log::info!("Declared the module {} in {}", name, parent.display());
+            }
+        }
+        None if is_integration_test => {}
+        None => log::info!(
+            "\nDeclare the fuzz test as a test module in its parent module:\n\n    \
+             #[cfg(test)]\n    mod {name};"
+        ),
+    }
+
+    log::info!(
+        "\nNote: Fuzz tests can be put anywhere in your repository, but it makes sense\n\
+         to keep them close to the tested code - just like regular unit tests."
+    );
+    Ok(())
+}
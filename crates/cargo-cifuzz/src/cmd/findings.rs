@@ -0,0 +1,447 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+
+use crate::cmd::gc;
+use crate::config::{self, SeverityRule};
+use crate::finding::{self, Finding, Status, CRASHING_INPUT_FILE};
+use crate::log;
+use crate::sarif;
+use crate::severity;
+
+/// Manage the findings of the fuzz tests
+#[derive(Debug, Args)]
+pub struct FindingsArgs {
+    #[command(subcommand)]
+    command: FindingsCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum FindingsCommand {
+    Export(ExportArgs),
+    Gc(gc::GcArgs),
+    List(ListArgs),
+    Mark(MarkArgs),
+    Show(ShowArgs),
+}
+
+/// List the findings of the fuzz tests
+///
+/// This command lists the findings in .cifuzz/findings, newest first,
+/// with their type, severity, triage status, age and dedup token, e.g.
+///
+///     NAME          FUZZ TEST     TYPE   SEVERITY  STATUS  AGE  DEDUP TOKEN
+///     5ed1b771cc61  my_fuzz_test  crash  high      open    2h   0d09a0cb426d
+///
+/// Findings with the same dedup token are the same bug.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+struct ListArgs {
+    /// Only list the findings of this fuzz test
+    fuzz_test: Option<String>,
+
+    /// Only list the findings with this status
+    #[arg(long, value_enum)]
+    status: Option<Status>,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+/// Show the details of a finding
+///
+/// This command prints everything recorded about a finding: the panic
+/// message, the stack trace, the output of the fuzz test and how it was
+/// built and run.
+///
+/// `<FINDING>` is either the directory of a finding or its name, e.g.
+/// "c33f2bd8" for `.cifuzz/findings/<FUZZ_TEST>/c33f2bd8`. A unique prefix
+/// of the name is enough.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+struct ShowArgs {
+    /// The directory or the name of the finding
+    finding: String,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+/// Set the triage status of findings
+///
+/// This command marks findings as fixed or ignored, or as open again.
+/// The status is stored in the finding.json of the finding:
+///
+///     cargo cifuzz findings mark fixed c33f2bd8
+///
+/// A fixed finding is reopened when a fuzz test finds the same bug
+/// again. Ignored findings are skipped by the regression tests, e.g.
+/// for accepted bugs in dependencies. `cargo cifuzz gc` removes the
+/// fixed findings.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+struct MarkArgs {
+    /// The status to set
+    #[arg(value_enum)]
+    status: Status,
+
+    /// The directories or the names of the findings
+    #[arg(required = true)]
+    findings: Vec<String>,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Format {
+    /// SARIF 2.1.0, e.g. for GitHub code scanning
+    Sarif,
+    /// A JSON array of the findings with their kind and severity
+    Json,
+}
+
+/// Export the findings in a format for other tools
+///
+/// This command converts the findings in .cifuzz/findings, i.e. their
+/// type, the location of the panic, the stack trace and the path of the
+/// crashing input, into a report for other tools. The report is written
+/// to stdout, or to the --output file.
+///
+/// With --format sarif, the report is a SARIF log, which GitHub code
+/// scanning shows as alerts at the panic location of every finding:
+///
+///     cargo cifuzz findings export --format sarif -o cifuzz.sarif
+///
+/// With --format json, the report is an array of the findings with the
+/// kind of bug and the severity they're classified with, for scripts
+/// which triage them.
+///
+/// The severity of the results is the one of the severity rules of the
+/// cifuzz.yaml, the one reported by the fuzz test or the default of the
+/// kind of bug, e.g. high for a heap-buffer-overflow and low for a
+/// panic.
+///
+/// The paths in the report are relative to the project directory, which
+/// is expected to be the root of the repository.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+struct ExportArgs {
+    /// Only export the findings of this fuzz test
+    fuzz_test: Option<String>,
+
+    /// The format of the report
+    #[arg(long, value_enum)]
+    format: Format,
+
+    /// The file to write the report to
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+pub fn run(args: FindingsArgs) -> Result<()> {
+    match args.command {
+        FindingsCommand::Export(args) => export(args),
+        FindingsCommand::Gc(args) => gc::run(args),
+        FindingsCommand::List(args) => list(args),
+        FindingsCommand::Mark(args) => mark(args),
+        FindingsCommand::Show(args) => show(args),
+    }
+}
+
+fn list(args: ListArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let project_config = config::parse_project_config(&project_dir)?;
+    let mut findings = load_findings(&project_dir, args.fuzz_test.as_deref())?;
+    findings.retain(|finding| args.status.is_none_or(|status| finding.status == status));
+    if findings.is_empty() {
+        log::info!("No findings");
+        return Ok(());
+    }
+    findings.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+
+    let header = [
+        "NAME",
+        "FUZZ TEST",
+        "TYPE",
+        "SEVERITY",
+        "STATUS",
+        "AGE",
+        "DEDUP TOKEN",
+    ];
+    let rows: Vec<[String; 7]> = findings
+        .iter()
+        .map(|finding| {
+            [
+                short(&finding.name),
+                finding.metadata.fuzz_test.clone(),
+                finding.error_type.description().to_string(),
+                finding
+                    .severity(&project_config.severity)
+                    .name()
+                    .to_string(),
+                finding.status.name().to_string(),
+                age(&finding.created_at),
+                short(&finding.dedup_token),
+            ]
+        })
+        .collect();
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let format_row = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    println!("{}", format_row(header.to_vec()));
+    for row in &rows {
+        println!("{}", format_row(row.iter().map(String::as_str).collect()));
+    }
+    Ok(())
+}
+
+fn show(args: ShowArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let dir = finding::find(&project_dir, &args.finding)?;
+    let finding = Finding::load(&dir)?;
+    let metadata = &finding.metadata;
+    let project_config = config::parse_project_config(&project_dir)?;
+
+    println!("Finding:      {}", finding.name);
+    println!("Fuzz test:    {}", metadata.fuzz_test);
+    println!(
+        "Type:         {} ({} severity)",
+        finding.error_type.description(),
+        finding.severity(&project_config.severity).name()
+    );
+    println!("Status:       {}", finding.status.name());
+    println!(
+        "Found:        {} ({} ago)",
+        finding.created_at,
+        age(&finding.created_at)
+    );
+    if let Some(commit) = &metadata.commit {
+        println!("Commit:       {commit}");
+    }
+    println!("Engine:       {}", metadata.engine.name());
+    if let Some(sanitizer) = metadata.sanitizer {
+        println!("Sanitizer:    {}", sanitizer.name());
+    }
+    if let Some(rustc) = &metadata.rustc {
+        println!("Rustc:        {rustc}");
+    }
+    if let Some(target) = &metadata.target {
+        println!("Target:       {target}");
+    }
+    if !metadata.cargo_args.is_empty() {
+        println!("Cargo args:   {}", metadata.cargo_args.join(" "));
+    }
+    println!("Dedup token:  {}", finding.dedup_token);
+    println!("Input:        {}", dir.join(CRASHING_INPUT_FILE).display());
+    println!("\n{}", finding.details);
+    if !finding.stack_trace.is_empty() {
+        println!("\nStack trace:");
+        for frame in &finding.stack_trace {
+            if frame.source_file.is_empty() {
+                println!("  #{} {}", frame.frame_number, frame.function);
+            } else {
+                println!(
+                    "  #{} {}\n       at {}:{}:{}",
+                    frame.frame_number, frame.function, frame.source_file, frame.line, frame.column
+                );
+            }
+        }
+    }
+    if !finding.operations.is_empty() {
+        println!("\nOperations:");
+        for (i, operation) in finding.operations.iter().enumerate() {
+            println!("  {i}: {operation}");
+        }
+    }
+    if !finding.output.is_empty() {
+        println!("\nOutput of the fuzz test:");
+        for line in &finding.output {
+            println!("  {line}");
+        }
+    }
+    if !finding.logs.is_empty() {
+        println!("\nOutput:");
+        for line in &finding.logs {
+            println!("  {line}");
+        }
+    }
+    Ok(())
+}
+
+fn mark(args: MarkArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    // All findings are looked up first, so that a typo doesn't leave
+    // the findings half marked
+    let dirs = args
+        .findings
+        .iter()
+        .map(|name| finding::find(&project_dir, name))
+        .collect::<Result<Vec<_>>>()?;
+    for dir in dirs {
+        let mut finding = Finding::load(&dir)?;
+        finding.status = args.status;
+        finding.save(&project_dir)?;
+        log::success!(
+            "Marked the finding {} as {}",
+            finding.name,
+            args.status.name()
+        );
+    }
+    Ok(())
+}
+
+/// Abbreviates a hash like git does.
+fn short(hash: &str) -> String {
+    hash.chars().take(12).collect()
+}
+
+/// How long ago the RFC 3339 timestamp was, in its largest unit, e.g.
+/// "3d".
+fn age(created_at: &str) -> String {
+    let Ok(created_at) = humantime::parse_rfc3339_weak(created_at) else {
+        return "?".to_string();
+    };
+    let age = SystemTime::now()
+        .duration_since(created_at)
+        .unwrap_or_default();
+    format_age(age)
+}
+
+pub fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+fn export(args: ExportArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let project_config = config::parse_project_config(&project_dir)?;
+    let findings = load_findings(&project_dir, args.fuzz_test.as_deref())?;
+    let report = match args.format {
+        Format::Sarif => {
+            serde_json::to_string_pretty(&sarif::report(&findings, &project_config.severity))?
+                + "\n"
+        }
+        Format::Json => {
+            serde_json::to_string_pretty(&json_report(&findings, &project_config.severity))? + "\n"
+        }
+    };
+    match &args.output {
+        Some(output) => {
+            std::fs::write(output, report)
+                .with_context(|| format!("failed to write {}", output.display()))?;
+            log::success!(
+                "Exported {} findings to {}",
+                findings.len(),
+                output.display()
+            );
+        }
+        None => print!("{report}"),
+    }
+    Ok(())
+}
+
+/// Returns the findings as a JSON array, with the kind and the severity
+/// they're classified with.
+fn json_report(findings: &[Finding], rules: &[SeverityRule]) -> serde_json::Value {
+    let findings: Vec<serde_json::Value> = findings
+        .iter()
+        .map(|finding| {
+            serde_json::json!({
+                "name": finding.name,
+                "fuzz_test": finding.metadata.fuzz_test,
+                "type": finding.error_type,
+                "kind": severity::kind(finding),
+                "severity": finding.severity(rules),
+                "status": finding.status,
+                "details": finding.details,
+                "dedup_token": finding.dedup_token,
+                "created_at": finding.created_at,
+                "input_file": finding.input_file,
+                "stack_trace": finding.stack_trace,
+                "commit": finding.metadata.commit,
+            })
+        })
+        .collect();
+    serde_json::Value::Array(findings)
+}
+
+/// Loads the findings of all fuzz tests, or of the fuzz test, which may
+/// be specified with its module path.
+pub fn load_findings(project_dir: &Path, fuzz_test: Option<&str>) -> Result<Vec<Finding>> {
+    let fuzz_test = fuzz_test.map(|f| f.rsplit("::").next().unwrap_or(f));
+    let mut findings = Vec::new();
+    for dir in finding::dirs(project_dir)? {
+        match Finding::load(&dir) {
+            Ok(finding) => {
+                if fuzz_test.is_none_or(|f| finding.metadata.fuzz_test == f) {
+                    findings.push(finding);
+                }
+            }
+            // Findings added by hand may not have a valid finding.json
+            Err(err) => log::info!("Skipping the finding in {}: {:#}", dir.display(), err),
+        }
+    }
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::finding::test_finding;
+
+    #[test]
+    fn export_json() {
+        let project = tempfile::tempdir().unwrap();
+        test_finding("e6c1a2d3").save(project.path()).unwrap();
+        let findings = load_findings(project.path(), None).unwrap();
+        let rule: SeverityRule = serde_yaml::from_str("kind: panic\nseverity: medium").unwrap();
+
+        let report = json_report(&findings, &[]);
+        assert_eq!(report[0]["name"], "e6c1a2d3");
+        assert_eq!(report[0]["kind"], "panic");
+        assert_eq!(report[0]["severity"], "low");
+        assert_eq!(report[0]["status"], "open");
+        assert_eq!(json_report(&findings, &[rule])[0]["severity"], "medium");
+    }
+
+    #[test]
+    fn ages() {
+        assert_eq!(format_age(Duration::from_secs(59)), "59s");
+        assert_eq!(format_age(Duration::from_secs(3599)), "59m");
+        assert_eq!(format_age(Duration::from_secs(7200)), "2h");
+        assert_eq!(format_age(Duration::from_secs(3 * 86400 + 5)), "3d");
+        assert_eq!(age("invalid"), "?");
+    }
+}
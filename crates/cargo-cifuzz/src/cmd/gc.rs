@@ -0,0 +1,298 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use clap::Args;
+
+use crate::cmd::status::format_size;
+use crate::config::{self, Retention};
+use crate::finding::{self, Finding, Status};
+use crate::log;
+
+/// The age after which artifacts are removed if neither --max-age nor
+/// the cifuzz.yaml set one.
+const DEFAULT_MAX_ARTIFACT_AGE: Duration = Duration::from_secs(30 * 86400);
+
+/// Remove fixed and duplicate findings and stale artifacts
+///
+/// This command applies the retention policy of the cifuzz.yaml to the
+/// findings in .cifuzz/findings and the artifacts which libFuzzer wrote
+/// to .cifuzz-artifacts, which grow quickly when fuzzing continuously:
+///
+///     retention:
+///       max-findings-per-token: 3
+///       max-artifact-age: 7d
+///       max-artifacts-size: 1GiB
+///
+/// It removes
+///   - the findings marked as fixed,
+///   - the findings with a dedup token of which max-findings-per-token
+///     older findings are kept, over all fuzz tests,
+///   - the artifacts whose input is stored in a finding,
+///   - the artifacts older than max-artifact-age, 30 days by default,
+///   - the oldest artifacts while all of them are larger than
+///     max-artifacts-size.
+///
+/// The flags take precedence over the cifuzz.yaml. The corpus isn't
+/// touched, `cargo cifuzz corpus prune` removes its redundant inputs.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+pub struct GcArgs {
+    /// Remove artifacts older than this, e.g. "7d"
+    #[arg(long, value_parser = config::parse_duration)]
+    max_age: Option<Duration>,
+
+    /// Remove the oldest artifacts while all of them are larger than
+    /// this, e.g. "1GiB"
+    #[arg(long, value_parser = config::parse_size)]
+    max_size: Option<u64>,
+
+    /// Keep at most this many findings with the same dedup token
+    #[arg(long)]
+    max_findings_per_token: Option<usize>,
+
+    /// Only print what would be removed
+    #[arg(long)]
+    dry_run: bool,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+pub fn run(args: GcArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let retention = config::parse_project_config(&project_dir)?.retention;
+    let retention = Retention {
+        max_findings_per_token: args
+            .max_findings_per_token
+            .or(retention.max_findings_per_token),
+        max_artifact_age: args.max_age.or(retention.max_artifact_age),
+        max_artifacts_size: args.max_size.or(retention.max_artifacts_size),
+    };
+    let mut removed = 0;
+    let mut freed = 0;
+    let mut remove = |path: &Path, reason: &str| -> Result<()> {
+        removed += 1;
+        freed += disk_usage(path);
+        if args.dry_run {
+            log::info!("Would remove {} ({reason})", path.display());
+            return Ok(());
+        }
+        log::debug!("Removing {} ({reason})", path.display());
+        if path.is_dir() {
+            std::fs::remove_dir_all(path)
+        } else {
+            std::fs::remove_file(path)
+        }
+        .with_context(|| format!("failed to remove {}", path.display()))
+    };
+
+    // The findings by dedup token, the oldest first
+    let mut by_token: BTreeMap<String, Vec<(Finding, PathBuf)>> = BTreeMap::new();
+    for dir in finding::dirs(&project_dir)? {
+        let Ok(finding) = Finding::load(&dir) else {
+            continue;
+        };
+        if finding.status == Status::Fixed {
+            remove(&dir, "fixed")?;
+        } else {
+            by_token
+                .entry(finding.dedup_token.clone())
+                .or_default()
+                .push((finding, dir));
+        }
+    }
+    let mut stored = Vec::new();
+    for findings in by_token.values_mut() {
+        findings.sort_by(|(a, _), (b, _)| (&a.created_at, &a.name).cmp(&(&b.created_at, &b.name)));
+        let keep = retention.max_findings_per_token.unwrap_or(usize::MAX);
+        for (i, (finding, dir)) in findings.iter().enumerate() {
+            if i < keep {
+                stored.push((finding.metadata.fuzz_test.clone(), finding.name.clone()));
+            } else {
+                remove(dir, &format!("duplicate of {}", findings[0].0.name))?;
+            }
+        }
+    }
+
+    let artifacts_root = project_dir.join(".cifuzz-artifacts");
+    let max_age = retention
+        .max_artifact_age
+        .unwrap_or(DEFAULT_MAX_ARTIFACT_AGE);
+    let now = SystemTime::now();
+    let mut kept = Vec::new();
+    for fuzz_test_dir in read_dir(&artifacts_root)? {
+        let fuzz_test = fuzz_test_dir
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        for artifact in read_dir(&fuzz_test_dir)? {
+            let name = artifact.file_name().unwrap_or_default().to_string_lossy();
+            // libFuzzer names the artifacts "<kind>-<hash>", the name of
+            // the finding is the hash
+            let hash = name.split_once('-').map_or(&*name, |(_, hash)| hash);
+            let modified = artifact.metadata().and_then(|m| m.modified()).ok();
+            if stored.iter().any(|(f, n)| *f == fuzz_test && n == hash) {
+                remove(&artifact, "stored in a finding")?;
+            } else if modified
+                .and_then(|modified| now.duration_since(modified).ok())
+                .is_some_and(|age| age > max_age)
+            {
+                remove(&artifact, "expired")?;
+            } else {
+                let size = disk_usage(&artifact);
+                kept.push((modified.unwrap_or(now), size, artifact));
+            }
+        }
+    }
+    if let Some(max_size) = retention.max_artifacts_size {
+        let mut size: u64 = kept.iter().map(|(_, size, _)| size).sum();
+        kept.sort();
+        for (_, artifact_size, artifact) in &kept {
+            if size <= max_size {
+                break;
+            }
+            remove(artifact, &format!("over {}", format_size(max_size)))?;
+            size -= artifact_size;
+        }
+    }
+
+    if args.dry_run {
+        log::info!(
+            "Would remove {removed} findings and artifacts ({})",
+            format_size(freed)
+        );
+    } else {
+        log::success!(
+            "Removed {removed} findings and artifacts ({})",
+            format_size(freed)
+        );
+    }
+    Ok(())
+}
+
+/// Returns the entries of the directory, or none if it doesn't exist.
+fn read_dir(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut paths = Vec::new();
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?
+    {
+        paths.push(entry?.path());
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Returns the size of the file, or of the files in the directory.
+fn disk_usage(path: &Path) -> u64 {
+    if path.is_dir() {
+        read_dir(path)
+            .unwrap_or_default()
+            .iter()
+            .map(|path| disk_usage(path))
+            .sum()
+    } else {
+        path.metadata().map_or(0, |metadata| metadata.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::finding::test_finding;
+
+    fn write_finding(project_dir: &Path, name: &str, status: Status, dedup_token: &str, day: u32) {
+        let finding = Finding {
+            status,
+            dedup_token: dedup_token.to_string(),
+            created_at: format!("2024-01-{day:02}T00:00:00Z"),
+            ..test_finding(name)
+        };
+        finding.save(project_dir).unwrap();
+    }
+
+    fn args(project_dir: &Path, dry_run: bool) -> GcArgs {
+        GcArgs {
+            max_age: Some(Duration::from_secs(3600)),
+            max_size: None,
+            max_findings_per_token: None,
+            dry_run,
+            project_dir: Some(project_dir.to_path_buf()),
+        }
+    }
+
+    #[test]
+    fn gc_fixed_findings_and_artifacts() {
+        let project = tempfile::tempdir().unwrap();
+        write_finding(project.path(), "e6c1a2d3", Status::Fixed, "a", 1);
+        write_finding(project.path(), "0123abcd", Status::Open, "b", 1);
+        let artifacts = project
+            .path()
+            .join(".cifuzz-artifacts")
+            .join("my_fuzz_test");
+        std::fs::create_dir_all(&artifacts).unwrap();
+        std::fs::write(artifacts.join("crash-0123abcd"), "stored").unwrap();
+        std::fs::write(artifacts.join("crash-4567"), "not stored").unwrap();
+        std::fs::write(project.path().join(config::PROJECT_CONFIG_FILE), "").unwrap();
+
+        run(args(project.path(), true)).unwrap();
+        assert_eq!(finding::dirs(project.path()).unwrap().len(), 2);
+        run(args(project.path(), false)).unwrap();
+        let dirs = finding::dirs(project.path()).unwrap();
+        assert_eq!(dirs.len(), 1);
+        assert!(dirs[0].ends_with("0123abcd"));
+        assert_eq!(
+            read_dir(&artifacts).unwrap(),
+            [artifacts.join("crash-4567")]
+        );
+    }
+
+    #[test]
+    fn gc_with_retention() {
+        let project = tempfile::tempdir().unwrap();
+        // The two oldest findings of the same bug are kept
+        write_finding(project.path(), "c", Status::Open, "0d09a0cb", 3);
+        write_finding(project.path(), "a", Status::Ignored, "0d09a0cb", 2);
+        write_finding(project.path(), "b", Status::Open, "0d09a0cb", 1);
+        write_finding(project.path(), "d", Status::Open, "5ed1b771", 4);
+        std::fs::write(
+            project.path().join(config::PROJECT_CONFIG_FILE),
+            "retention:\n  max-findings-per-token: 2\n  max-artifacts-size: 10B\n",
+        )
+        .unwrap();
+        let artifacts = project
+            .path()
+            .join(".cifuzz-artifacts")
+            .join("my_fuzz_test");
+        std::fs::create_dir_all(&artifacts).unwrap();
+        let old = artifacts.join("crash-1111");
+        std::fs::write(&old, "123456").unwrap();
+        let file = std::fs::File::options().write(true).open(&old).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(60))
+            .unwrap();
+        std::fs::write(artifacts.join("crash-2222"), "123456").unwrap();
+
+        run(GcArgs {
+            max_age: None,
+            ..args(project.path(), false)
+        })
+        .unwrap();
+        let names: Vec<_> = finding::dirs(project.path())
+            .unwrap()
+            .iter()
+            .map(|dir| dir.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["a", "b", "d"]);
+        assert_eq!(
+            read_dir(&artifacts).unwrap(),
+            [artifacts.join("crash-2222")]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+
+use crate::generate::{self, Candidate};
+use crate::log;
+use crate::stubs;
+use crate::workspace::{Bin, Workspace};
+
+/// Generate fuzz tests for the functions of a crate
+///
+/// This command scans the public functions of the package in the
+/// current directory for functions whose parameters can all be decoded
+/// from the input of the fuzzer: integers, strings, byte slices,
+/// collections and tuples of them and types deriving
+/// arbitrary::Arbitrary. For each of them, it creates a fuzz test which
+/// calls the function with the decoded parameters next to the root of
+/// the crate (src/lib.rs or src/main.rs) and declares it as a test
+/// module there.
+///
+/// The generated fuzz tests are skeletons: Review them and add
+/// assertions on the results of the calls. Existing fuzz test files are
+/// not overwritten.
+///
+/// With --bin, it creates a fuzz test of the command line of a binary
+/// of the workspace instead, `<bin>_cli_fuzz_test`, which executes a copy
+/// of its main function with the arguments and stdin decoded from the
+/// input. In the copy, the calls of std::env::args, std::io::stdin,
+/// std::process::exit and the parsers of clap are replaced by those of
+/// cifuzz::cli, so a tool is fuzzed end to end:
+///
+///     cargo cifuzz generate --bin my-tool
+///     cargo cifuzz run my_tool_cli_fuzz_test
+///
+/// Only the calls in main itself are replaced. Delete the fuzz test and
+/// generate it again when main changes.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+pub struct GenerateArgs {
+    /// Only generate fuzz tests for the functions with the given paths,
+    /// which can omit leading modules, e.g. parse or parser::parse
+    functions: Vec<String>,
+
+    /// Only list the functions for which fuzz tests can be generated
+    #[arg(long)]
+    list: bool,
+
+    /// Generate the fuzz test of the binary with the given name, which
+    /// can be omitted if the package in the current directory has only
+    /// one
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["functions", "list"])]
+    bin: Option<Option<String>>,
+}
+
+pub fn run(args: GenerateArgs) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    if let Some(bin) = &args.bin {
+        return generate_cli_fuzz_test(&cwd, bin.as_deref());
+    }
+    let Some(package_dir) = cwd.ancestors().find(|dir| dir.join("Cargo.toml").is_file()) else {
+        bail!("Failed to find a cargo package in {}", cwd.display());
+    };
+    let Some(crate_root) = generate::crate_root(package_dir) else {
+        bail!(
+            "Failed to find src/lib.rs or src/main.rs in {}",
+            package_dir.display()
+        );
+    };
+    log::debug!("Crate root: {}", crate_root.display());
+
+    let candidates = generate::find_candidates(&crate_root)?;
+    let names = generate::fuzz_test_names(&candidates);
+    let selected: Vec<(&Candidate, &String)> = candidates
+        .iter()
+        .zip(&names)
+        .filter(|(c, _)| args.functions.is_empty() || args.functions.iter().any(|f| c.matches(f)))
+        .collect();
+    for function in &args.functions {
+        if !candidates.iter().any(|c| c.matches(function)) {
+            bail!(
+                "No fuzz test can be generated for {function}: It must be a public function \
+                 whose parameters can all be decoded from the fuzzer input"
+            );
+        }
+    }
+    if selected.is_empty() {
+        log::info!("No functions found for which fuzz tests can be generated");
+        return Ok(());
+    }
+
+    if args.list {
+        for (candidate, name) in selected {
+            println!("{} ({name})", candidate.path);
+        }
+        return Ok(());
+    }
+
+    let dir = crate_root.parent().unwrap();
+    for (candidate, name) in selected {
+        let path = dir.join(format!("{name}.rs"));
+        if path.exists() {
+            log::info!("Skipping {}, {} exists", candidate.path, path.display());
+            continue;
+        }
+        std::fs::write(&path, generate::fuzz_test_source(candidate, name))
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        if stubs::register_module(&crate_root, name)? {
+            log::debug!("Declared the module {} in {}", name, crate_root.display());
+        }
+        log::success!(
+            "Created fuzz test {} for {}",
+            path.display(),
+            candidate.path
+        );
+    }
+    log::info!(
+        "\nReview the generated fuzz tests, add assertions on the results of the calls\n\
+         and execute them via 'cargo cifuzz run'."
+    );
+    Ok(())
+}
+
+fn generate_cli_fuzz_test(cwd: &Path, name: Option<&str>) -> Result<()> {
+    let workspace = Workspace::load(cwd)?;
+    let bins: Vec<&Bin> = match name {
+        Some(name) => workspace
+            .members
+            .iter()
+            .flat_map(|member| &member.bins)
+            .filter(|bin| bin.name == name)
+            .collect(),
+        // The binaries of the innermost package containing the directory
+        None => workspace
+            .members
+            .iter()
+            .filter(|member| cwd.starts_with(member.manifest_path.parent().unwrap()))
+            .max_by_key(|member| member.manifest_path.components().count())
+            .map(|member| member.bins.iter().collect())
+            .unwrap_or_default(),
+    };
+    let bin = match (bins.as_slice(), name) {
+        ([bin], _) => bin,
+        ([], Some(name)) => bail!("No binary {name} found in the workspace"),
+        ([], None) => bail!("Failed to find a binary in {}", cwd.display()),
+        (_, _) => {
+            let names: Vec<&str> = bins.iter().map(|bin| bin.name.as_str()).collect();
+            bail!(
+                "Found several binaries, select one with --bin <NAME>: {}",
+                names.join(", ")
+            );
+        }
+    };
+
+    let root = &bin.src_path;
+    let source = std::fs::read_to_string(root)
+        .with_context(|| format!("failed to read {}", root.display()))?;
+    let relative_root = root.strip_prefix(&workspace.root).unwrap_or(root);
+    let name = generate::cli_fuzz_test_name(&bin.name);
+    let fuzz_test = generate::cli_fuzz_test_source(
+        &bin.name,
+        &relative_root.display().to_string(),
+        &source,
+        &name,
+    )?;
+
+    // The modules of a root file other than main.rs, like src/bin/tool.rs,
+    // would be next to it in src/bin, where cargo takes them for
+    // binaries of their own
+    let dir = root.parent().unwrap();
+    let stem = root.file_stem().unwrap_or_default().to_string_lossy();
+    let (path, module_path) = if stem == "main" {
+        (dir.join(format!("{name}.rs")), None)
+    } else {
+        (
+            dir.join(&*stem).join(format!("{name}.rs")),
+            Some(format!("{stem}/{name}.rs")),
+        )
+    };
+    if path.exists() {
+        log::info!(
+            "Skipping the binary {}, {} exists",
+            bin.name,
+            path.display()
+        );
+        return Ok(());
+    }
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(&path, fuzz_test)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    if stubs::register_module_at(root, &name, module_path.as_deref())? {
+        log::debug!("Declared the module {} in {}", name, root.display());
+    }
+    log::success!(
+        "Created fuzz test {} for the binary {}",
+        path.display(),
+        bin.name
+    );
+    log::info!("\nExecute it via 'cargo cifuzz run {name}'.");
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Args;
+
+use crate::config;
+use crate::log;
+use crate::workspace::{CifuzzSource, Workspace, CIFUZZ_GIT_URL};
+
+/// Set up a project for use with cargo cifuzz
+///
+/// This command detects the cargo workspace containing the current
+/// directory, adds the cifuzz crate as a dev-dependency to each member
+/// of the workspace and creates a cifuzz.yaml configuration file in the
+/// workspace root.
+///
+/// The cifuzz crate re-exports the `#[fuzz_test]` macro, so no other
+/// dependency is needed. If the root manifest declares cifuzz in
+/// [workspace.dependencies], the members inherit that declaration.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+pub struct InitArgs {
+    /// Use the cifuzz crate at the given path instead of the one from
+    /// the cifuzz git repository
+    #[arg(long)]
+    cifuzz_path: Option<PathBuf>,
+}
+
+pub fn run(args: InitArgs) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    let workspace = Workspace::load(&cwd)?;
+    log::debug!("Workspace root: {}", workspace.root.display());
+
+    let source = match args.cifuzz_path {
+        Some(path) => CifuzzSource::Path(
+            std::path::absolute(&path)
+                .with_context(|| format!("invalid path {}", path.display()))?,
+        ),
+        None => CifuzzSource::Git(CIFUZZ_GIT_URL.to_string()),
+    };
+
+    // Create the config first, so that the manifests are not modified
+    // if the project was already initialized
+    let config_path = config::create_project_config(&workspace.root)?;
+
+    for name in workspace.add_cifuzz_dev_dependency(&source)? {
+        log::info!("Added the cifuzz dev-dependency to {}", name);
+    }
+
+    log::success!("Configuration saved in {}", config_path.display());
+    log::info!("\nUse 'cargo cifuzz create' to create your first fuzz test.");
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+
+use crate::config;
+use crate::github_actions::{self, Workflow};
+use crate::log;
+use crate::oss_fuzz::{self, Project};
+
+/// Integrate the fuzz tests with CI systems and fuzzing services
+#[derive(Debug, Args)]
+pub struct IntegrateArgs {
+    #[command(subcommand)]
+    command: IntegrateCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum IntegrateCommand {
+    GithubActions(GithubActionsArgs),
+    OssFuzz(OssFuzzArgs),
+}
+
+/// Generate a GitHub Actions workflow which fuzzes the fuzz tests
+///
+/// This command creates a workflow which runs all fuzz tests of the
+/// workspace on pushes to the default branch, and on pull requests only
+/// the ones affected by their changes, so that fuzzing a pull request
+/// takes time in proportion to the change:
+///
+///     cargo cifuzz run --all --timeout 5m --changed-since origin/main
+///
+/// The pushes store the generated corpus and the coverage reports of
+/// the fuzz tests in the cache of GitHub Actions, from which the pull
+/// requests start. A fuzz test is affected if its coverage report
+/// covers a changed file, see `cargo cifuzz run --help`. Until the
+/// first push created the coverage reports, the pull requests run all
+/// fuzz tests. The findings are uploaded as an artifact of failed runs.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+struct GithubActionsArgs {
+    /// The time every fuzz test runs for
+    #[arg(long, default_value = "5m")]
+    timeout: String,
+
+    /// The default branch. Defaults to the HEAD of the origin remote,
+    /// or "main".
+    #[arg(long)]
+    branch: Option<String>,
+
+    /// The workflow file to write. Defaults to
+    /// .github/workflows/cifuzz.yml in the project directory.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Overwrite an existing workflow
+    #[arg(long)]
+    force: bool,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+/// Generate the files to run the fuzz tests on OSS-Fuzz
+///
+/// This command creates the Dockerfile, build.sh and project.yaml of an
+/// OSS-Fuzz project for the crate, which belong into the
+/// `projects/<NAME>` directory of the OSS-Fuzz repository. The Dockerfile
+/// installs cargo-cifuzz and clones the main repository, and build.sh
+/// builds the fuzz tests and installs them into $OUT with
+///
+///     cargo cifuzz bundle --oss-fuzz --output "$OUT" --sanitizer "$SANITIZER"
+///
+/// OSS-Fuzz then runs every fuzz test of the workspace, the ones
+/// `cargo cifuzz list` lists, as a fuzz target with its seed corpus,
+/// dictionary and engine-args. The project can be tested locally with
+/// the helper of the OSS-Fuzz repository:
+///
+///     python3 infra/helper.py build_fuzzers <NAME>
+///     python3 infra/helper.py run_fuzzer <NAME> my_fuzz_test
+///
+/// The fuzz tests are built for libFuzzer with AddressSanitizer, which
+/// requires the nightly toolchain of the OSS-Fuzz image. Coverage
+/// builds of OSS-Fuzz aren't supported, `cargo cifuzz coverage` creates
+/// the coverage reports.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+struct OssFuzzArgs {
+    /// The name of the OSS-Fuzz project. Defaults to the name of the
+    /// project directory.
+    #[arg(long)]
+    name: Option<String>,
+
+    /// The git repository which OSS-Fuzz clones. Defaults to the URL of
+    /// the origin remote.
+    #[arg(long, value_name = "URL")]
+    main_repo: Option<String>,
+
+    /// The homepage of the project. Defaults to the main repository.
+    #[arg(long, value_name = "URL")]
+    homepage: Option<String>,
+
+    /// The e-mail address which OSS-Fuzz reports the findings to.
+    /// Defaults to the one of the git config.
+    #[arg(long, value_name = "EMAIL")]
+    primary_contact: Option<String>,
+
+    /// The directory to write the files to
+    #[arg(short, long, default_value = "oss-fuzz")]
+    output: PathBuf,
+
+    /// Overwrite existing files
+    #[arg(long)]
+    force: bool,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+pub fn run(args: IntegrateArgs) -> Result<()> {
+    match args.command {
+        IntegrateCommand::GithubActions(args) => github_actions(args),
+        IntegrateCommand::OssFuzz(args) => oss_fuzz(args),
+    }
+}
+
+fn github_actions(args: GithubActionsArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    humantime::parse_duration(&args.timeout)
+        .with_context(|| format!("invalid --timeout {}", args.timeout))?;
+    let branch = args.branch.unwrap_or_else(|| {
+        git(
+            &project_dir,
+            &["symbolic-ref", "--short", "refs/remotes/origin/HEAD"],
+        )
+        .and_then(|head| head.strip_prefix("origin/").map(String::from))
+        .unwrap_or_else(|| "main".to_string())
+    });
+    let path = args.output.unwrap_or_else(|| {
+        project_dir
+            .join(".github")
+            .join("workflows")
+            .join("cifuzz.yml")
+    });
+    let workflow = Workflow {
+        branch,
+        timeout: args.timeout,
+    };
+    github_actions::write(&workflow, &path, args.force)?;
+    log::success!("Created the workflow {}", path.display());
+    log::info!(
+        "Pull requests fuzz the fuzz tests affected by their changes once a push to {} \
+         created the coverage reports",
+        workflow.branch
+    );
+    Ok(())
+}
+
+fn oss_fuzz(args: OssFuzzArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let name = match args.name {
+        Some(name) => name,
+        None => oss_fuzz::project_name(
+            &project_dir
+                .file_name()
+                .context("the project directory has no name, specify --name")?
+                .to_string_lossy(),
+        ),
+    };
+    let main_repo = match args.main_repo {
+        Some(url) => url,
+        None => git(&project_dir, &["remote", "get-url", "origin"])
+            .context("The project has no origin remote, specify --main-repo")?,
+    };
+    let primary_contact = match args.primary_contact {
+        Some(email) => email,
+        None => git(&project_dir, &["config", "user.email"])
+            .context("No e-mail address is configured for git, specify --primary-contact")?,
+    };
+    let homepage = args
+        .homepage
+        .unwrap_or_else(|| main_repo.trim_end_matches(".git").to_string());
+    let project = Project {
+        name,
+        main_repo,
+        homepage,
+        primary_contact,
+    };
+
+    for path in oss_fuzz::write(&project, &args.output, args.force)? {
+        log::info!("Created {}", path.display());
+    }
+    log::success!(
+        "Created the OSS-Fuzz project {}, copy {} to projects/{} of the OSS-Fuzz repository",
+        project.name,
+        args.output.display(),
+        project.name
+    );
+    Ok(())
+}
+
+/// Returns the output of the git command, or `None` if it fails.
+fn git(project_dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(project_dir)
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !stdout.is_empty()).then_some(stdout)
+}
@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Args;
+
+use crate::build::{self, BuildMode, Builder, BuilderOptions};
+use crate::config;
+use crate::log;
+
+/// List the fuzz tests of the workspace
+///
+/// This command builds the fuzz tests of all members of the workspace,
+/// like `cargo cifuzz run`, and lists every function annotated with
+/// `#[fuzz_test]` by its path and the location of its source:
+///
+///     my_crate::parser::tests::parse_fuzz_test  src/parser.rs:42
+///
+/// The fuzz tests are found in the built test executables, so there is
+/// no list of them to maintain. Any of them can be passed to the other
+/// commands by its name, or by (a suffix of) its path without the crate
+/// if the name is ambiguous. `cargo cifuzz run --all` runs them all.
+///
+/// Additional arguments for `cargo test` can be passed after a "--",
+/// e.g. "-- -p my_crate" to only list the fuzz tests of a package.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+pub struct ListArgs {
+    /// Additional arguments to pass to `cargo test`
+    #[arg(last = true)]
+    cargo_args: Vec<String>,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+pub fn run(args: ListArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+
+    log::info!("Building the fuzz tests");
+    let builder = Builder::new(BuilderOptions {
+        project_dir: project_dir.clone(),
+        sanitizer: None,
+        mode: BuildMode::Fuzzing,
+        args: build::workspace_args(&args.cargo_args),
+    });
+    let fuzz_tests = builder.discover()?;
+    if fuzz_tests.is_empty() {
+        log::info!("No fuzz tests found, fuzz tests are functions annotated with #[fuzz_test]");
+        return Ok(());
+    }
+
+    let width = fuzz_tests.iter().map(|t| t.path.len()).max().unwrap_or(0);
+    for fuzz_test in fuzz_tests {
+        let source_file = fuzz_test
+            .source_file
+            .strip_prefix(&project_dir)
+            .unwrap_or(&fuzz_test.source_file);
+        println!(
+            "{:width$}  {}:{}",
+            fuzz_test.path,
+            source_file.display(),
+            fuzz_test.line
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+
+use crate::build::{BuildMode, BuildResult, Builder, BuilderOptions, Sanitizer};
+use crate::config;
+use crate::finding::{self, CRASHING_INPUT_FILE};
+use crate::log;
+use crate::minimize::{Minimizer, Outcome};
+use crate::parser;
+use crate::runner::libfuzzer::{Runner, RunnerOptions};
+use crate::trace;
+
+/// Minimize a crashing input of a fuzz test
+///
+/// This command builds the fuzz test like 'cargo cifuzz run' and
+/// shrinks the crashing input as long as it still causes the same
+/// crash, i.e. a crash with the same top stack frames.
+///
+/// `<INPUT>` is either the path of a crashing input or the name of a
+/// finding of the fuzz test. The minimized input is stored next to it
+/// with a "-minimized" suffix, e.g. in
+/// `.cifuzz/findings/<FUZZ_TEST>/<HASH>/crashing-input-minimized`.
+///
+/// Fuzz tests using the FuzzedDataProvider are shrunk along the values
+/// they consume before single bytes are removed: strings are removed or
+/// shortened, and integers are moved towards zero, or towards the
+/// minimum of their range if it doesn't contain zero, which also
+/// shortens vectors. `cargo cifuzz run --trace` shows the values the
+/// minimized input decodes to.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+pub struct MinimizeArgs {
+    /// The fuzz test which crashes with the input
+    fuzz_test: String,
+
+    /// The crashing input or the name of a finding
+    input: String,
+
+    /// Where to store the minimized input
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Maximum number of executions of the fuzz test
+    #[arg(long, default_value_t = 10_000)]
+    max_runs: usize,
+
+    /// Build the fuzz test with a sanitizer to detect bugs which don't
+    /// cause a panic, e.g. memory errors in unsafe code. Requires a
+    /// nightly toolchain.
+    #[arg(long, value_enum)]
+    sanitizer: Option<Sanitizer>,
+
+    /// Additional arguments to pass to `cargo test`
+    #[arg(last = true)]
+    cargo_args: Vec<String>,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+pub fn run(args: MinimizeArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let input_file = resolve_input(&project_dir, &args.fuzz_test, &args.input)?;
+    let input = std::fs::read(&input_file)
+        .with_context(|| format!("failed to read {}", input_file.display()))?;
+    let output = args.output.unwrap_or_else(|| {
+        let mut name = input_file.file_name().unwrap_or_default().to_owned();
+        name.push("-minimized");
+        input_file.with_file_name(name)
+    });
+
+    log::info!("Building {}", args.fuzz_test);
+    let builder = Builder::new(BuilderOptions {
+        project_dir: project_dir.clone(),
+        sanitizer: args.sanitizer,
+        mode: BuildMode::Fuzzing,
+        args: args.cargo_args,
+    });
+    let build_result = builder.build_for_run(&args.fuzz_test)?;
+    log::success!("Built fuzz test {}", build_result.name);
+
+    // The candidates are written to the build directory, which is
+    // ignored like the other .cifuzz-* directories
+    let work_dir = project_dir.join(".cifuzz-build").join("minimize");
+    std::fs::create_dir_all(&work_dir)
+        .with_context(|| format!("failed to create {}", work_dir.display()))?;
+    let runner = runner(&project_dir, &build_result, args.sanitizer);
+    let execute_input = |data: &[u8]| execute(&runner, &work_dir, data);
+    let dedup_token = |report: &parser::CrashReport| {
+        finding::crash_dedup_token(report, &project_dir, &build_result.package_dir)
+    };
+
+    // The crash of the original input is the one to preserve
+    let Some(report) = execute_input(&input)?.1 else {
+        bail!(
+            "The fuzz test {} doesn't crash with {}",
+            build_result.name,
+            input_file.display()
+        );
+    };
+    let token = dedup_token(&report);
+
+    log::info!(
+        "Minimizing {} ({} bytes)",
+        input_file.display(),
+        input.len()
+    );
+    let mut minimizer = Minimizer::new(args.max_runs, |data: &[u8]| {
+        let (calls, report) = execute_input(data)?;
+        Ok(match report {
+            Some(report) if dedup_token(&report) == token => Outcome::Reproduces(calls),
+            _ => Outcome::DoesNotReproduce,
+        })
+    });
+    let minimized = minimizer.minimize(input)?;
+    log::debug!("Executed the fuzz test {} times", minimizer.runs());
+
+    std::fs::write(&output, &minimized)
+        .with_context(|| format!("failed to write {}", output.display()))?;
+    log::success!(
+        "Minimized input of {} bytes saved in {}",
+        minimized.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// Returns the path of the input, which is either a file or the name of
+/// a finding of the fuzz test.
+fn resolve_input(project_dir: &Path, fuzz_test: &str, input: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(input);
+    if path.is_file() {
+        return Ok(path);
+    }
+    let finding = finding::findings_dir(project_dir, fuzz_test)
+        .join(input)
+        .join(CRASHING_INPUT_FILE);
+    if finding.is_file() {
+        return Ok(finding);
+    }
+    bail!("{input} is neither a file nor a finding of the fuzz test {fuzz_test}")
+}
+
+fn runner(project_dir: &Path, build_result: &BuildResult, sanitizer: Option<Sanitizer>) -> Runner {
+    Runner::new(RunnerOptions {
+        executable: build_result.executable.clone(),
+        test_name: build_result.test_name.clone(),
+        working_dir: build_result.package_dir.clone(),
+        generated_corpus_dir: build_result.generated_corpus.clone(),
+        seed_corpus_dirs: Vec::new(),
+        dictionary: None,
+        artifact_dir: project_dir
+            .join(".cifuzz-artifacts")
+            .join(&build_result.name),
+        timeout: None,
+        input_timeout: None,
+        rss_limit_mb: None,
+        seed: None,
+        use_value_profile: false,
+        sanitizer,
+        // Minimizing a leak requires detecting it
+        detect_leaks: sanitizer.is_some_and(Sanitizer::detects_leaks),
+        engine_args: Vec::new(),
+        capture_output: false,
+        slow_inputs_dir: None,
+        slow_inputs: 0,
+        exit_on_plateau: None,
+    })
+}
+
+/// Executes the fuzz test with the data and returns the calls of the
+/// provider it made and the crash, if any.
+fn execute(
+    runner: &Runner,
+    work_dir: &Path,
+    data: &[u8],
+) -> Result<(Vec<trace::Call>, Option<parser::CrashReport>)> {
+    let input = work_dir.join("input");
+    let calls_file = work_dir.join("calls");
+    std::fs::write(&input, data)?;
+    // Crashes which are not panics, e.g. stack overflows, kill the
+    // process before the calls are written
+    let _ = std::fs::remove_file(&calls_file);
+
+    let result = runner.run_input(&input, Some(&calls_file))?;
+    let calls = match std::fs::read_to_string(&calls_file) {
+        Ok(calls) => trace::parse_calls(&calls)?,
+        Err(_) => Vec::new(),
+    };
+    let report = if result.status.success() {
+        None
+    } else {
+        parser::parse_crash(&result.output)
+    };
+    Ok((calls, report))
+}
@@ -0,0 +1,19 @@
+//! The subcommands of `cargo cifuzz`.
+
+pub mod bundle;
+pub mod check;
+pub mod corpus;
+pub mod coverage;
+pub mod create;
+pub mod findings;
+pub mod gc;
+pub mod generate;
+pub mod init;
+pub mod integrate;
+pub mod list;
+pub mod minimize;
+pub mod replay;
+pub mod report;
+pub mod reproduce;
+pub mod run;
+pub mod status;
@@ -0,0 +1,443 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Args;
+
+use crate::build::{BuildMode, BuildResult, Builder, BuilderOptions, Sanitizer};
+use crate::config;
+use crate::corpus;
+use crate::exit_code::Failure;
+use crate::log;
+use crate::merge;
+use crate::parser;
+use crate::runner::libfuzzer::{Runner, RunnerOptions};
+
+/// Execute every input of the corpus once and report the failing ones
+///
+/// This command builds the fuzz test like 'cargo cifuzz run' and
+/// executes it with every input of its seed corpus and of its generated
+/// corpus in `.cifuzz-corpus/<FUZZ_TEST>`, without fuzzing. The inputs on
+/// which it crashes or exceeds the --input-timeout are reported with
+/// the panic message or the error, e.g. to check a corpus against a new
+/// version of the code. Unlike a fuzzing run, the replay doesn't stop at
+/// the first failing input. With --input, only the given inputs are
+/// executed.
+///
+/// With --bisect, the command finds the first commit between the --good
+/// commit and HEAD at which the first failing input fails, like `git
+/// bisect`:
+///
+///     cargo cifuzz replay my_fuzz_test --input crash-5ed1 --bisect --good v1.2.0
+///
+/// The commits are checked out in a git worktree in .cifuzz-build, so
+/// the working directory stays untouched, and only the commits on the
+/// first-parent history are tested. Commits at which the fuzz test
+/// doesn't build are skipped.
+///
+/// The command exits with the exit code of a finding if an input fails.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+pub struct ReplayArgs {
+    /// The fuzz test to execute
+    fuzz_test: String,
+
+    /// Execute this input instead of the corpus, can be repeated
+    #[arg(long = "input", value_name = "INPUT")]
+    inputs: Vec<PathBuf>,
+
+    /// Build the fuzz test with a sanitizer, see 'cargo cifuzz run'
+    #[arg(long, value_enum)]
+    sanitizer: Option<Sanitizer>,
+
+    /// Maximum time the fuzz test may run on a single input, e.g. "10s",
+    /// before the input is reported as a timeout
+    #[arg(long, default_value = "1m", value_parser = config::parse_duration)]
+    input_timeout: Duration,
+
+    /// Find the first commit at which the first failing input fails
+    #[arg(long, requires = "good")]
+    bisect: bool,
+
+    /// A commit at which the input doesn't fail, where --bisect starts
+    #[arg(long, value_name = "REV", requires = "bisect")]
+    good: Option<String>,
+
+    /// Additional arguments to pass to `cargo test`
+    #[arg(last = true)]
+    cargo_args: Vec<String>,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+pub fn run(args: ReplayArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let project_config = config::parse_project_config(&project_dir)?;
+    let sanitizer = args
+        .sanitizer
+        .or(project_config.fuzz_test(&args.fuzz_test).sanitizer);
+
+    log::info!("Building {}", args.fuzz_test);
+    let builder = builder(&project_dir, sanitizer, &args.cargo_args);
+    let build_result = builder.build_for_run(&args.fuzz_test)?;
+    log::success!("Built fuzz test {}", build_result.name);
+
+    let inputs = if args.inputs.is_empty() {
+        let mut dirs = build_result.seed_corpus_dirs.clone();
+        dirs.push(build_result.generated_corpus.clone());
+        dirs.retain(|dir| dir.is_dir());
+        corpus::list_inputs(&dirs)?
+    } else {
+        args.inputs
+            .iter()
+            .map(|input| std::path::absolute(input).context("invalid input path"))
+            .collect::<Result<_>>()?
+    };
+    if inputs.is_empty() {
+        log::info!("The corpus of {} is empty", build_result.name);
+        return Ok(());
+    }
+
+    let work_dir = builder.build_dir().join("replay");
+    std::fs::create_dir_all(&work_dir)
+        .with_context(|| format!("failed to create {}", work_dir.display()))?;
+    let runner = runner(&build_result, &work_dir, sanitizer, args.input_timeout);
+    log::info!(
+        "Executing {} with {} inputs",
+        build_result.name,
+        inputs.len()
+    );
+    let failures = replay(&runner, &work_dir, &inputs)?;
+    if failures.is_empty() {
+        log::success!(
+            "{} executed all {} inputs without failing",
+            build_result.name,
+            inputs.len()
+        );
+        return Ok(());
+    }
+    for (input, details) in &failures {
+        log::error!("{}: {details}", input.display());
+    }
+
+    if args.bisect {
+        let good = args.good.as_deref().unwrap_or_default();
+        let (input, _) = &failures[0];
+        // The input is copied, it may be a file of the checked out tree
+        let bisect_input = work_dir.join("bisect-input");
+        std::fs::copy(input, &bisect_input)
+            .with_context(|| format!("failed to copy {}", input.display()))?;
+        log::info!(
+            "Bisecting the commits between {good} and HEAD with {}",
+            input.display()
+        );
+        bisect(&args, &project_dir, sanitizer, &bisect_input, good)?;
+    }
+
+    Err(Failure::Finding.error(anyhow!(
+        "{} of {} inputs failed",
+        failures.len(),
+        inputs.len()
+    )))
+}
+
+fn builder(project_dir: &Path, sanitizer: Option<Sanitizer>, cargo_args: &[String]) -> Builder {
+    Builder::new(BuilderOptions {
+        project_dir: project_dir.to_path_buf(),
+        sanitizer,
+        mode: BuildMode::Fuzzing,
+        args: cargo_args.to_vec(),
+    })
+}
+
+/// Returns a runner executing the fuzz test with single inputs under
+/// the input timeout.
+fn runner(
+    build_result: &BuildResult,
+    work_dir: &Path,
+    sanitizer: Option<Sanitizer>,
+    input_timeout: Duration,
+) -> Runner {
+    Runner::new(RunnerOptions {
+        executable: build_result.executable.clone(),
+        test_name: build_result.test_name.clone(),
+        working_dir: build_result.package_dir.clone(),
+        generated_corpus_dir: build_result.generated_corpus.clone(),
+        seed_corpus_dirs: Vec::new(),
+        dictionary: None,
+        artifact_dir: work_dir.join("artifacts"),
+        timeout: None,
+        input_timeout: Some(input_timeout),
+        rss_limit_mb: None,
+        seed: None,
+        use_value_profile: false,
+        sanitizer,
+        detect_leaks: false,
+        engine_args: Vec::new(),
+        capture_output: false,
+        slow_inputs_dir: None,
+        slow_inputs: 0,
+        exit_on_plateau: None,
+    })
+}
+
+/// Executes the inputs and returns the ones on which the fuzz test
+/// failed, with the details of the failure. The inputs are executed in
+/// one process like in a merge, which is restarted after a failure,
+/// and the failing inputs are executed again on their own to tell why
+/// they failed.
+fn replay(runner: &Runner, work_dir: &Path, inputs: &[PathBuf]) -> Result<Vec<(PathBuf, String)>> {
+    let control_file = work_dir.join("replay.merge");
+    let coverage = merge::execute(runner, &control_file, inputs, inputs.len())?;
+    let mut failures = Vec::new();
+    for (input, _) in inputs.iter().zip(&coverage).filter(|(_, c)| c.crashed) {
+        let result = runner.reproduce(input)?;
+        let details = match parser::parse_crash(&result.output) {
+            Some(report) => report.details(),
+            None if result.status.success() => {
+                "only fails after the inputs executed before it".to_string()
+            }
+            None => format!("exited with {}", result.status),
+        };
+        failures.push((input.clone(), details));
+    }
+    Ok(failures)
+}
+
+/// Finds the first commit between `good` and HEAD at which the fuzz
+/// test fails on the input, by building it in a worktree.
+fn bisect(
+    args: &ReplayArgs,
+    project_dir: &Path,
+    sanitizer: Option<Sanitizer>,
+    input: &Path,
+    good: &str,
+) -> Result<()> {
+    let toplevel = PathBuf::from(git(project_dir, &["rev-parse", "--show-toplevel"])?);
+    let relative = project_dir
+        .canonicalize()?
+        .strip_prefix(toplevel.canonicalize()?)
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let range = format!("{good}..HEAD");
+    let commits: Vec<String> = git(
+        project_dir,
+        &["rev-list", "--first-parent", "--reverse", &range],
+    )?
+    .lines()
+    .map(str::to_string)
+    .collect();
+    if commits.is_empty() {
+        bail!("There are no commits between {good} and HEAD");
+    }
+    let good_commit = git(project_dir, &["rev-parse", &format!("{good}^{{commit}}")])?;
+
+    let worktree = project_dir.join(".cifuzz-build").join("bisect");
+    if worktree.exists() {
+        // Left behind by an interrupted bisection
+        let _ = git(
+            project_dir,
+            &["worktree", "remove", "--force", &worktree.to_string_lossy()],
+        );
+        let _ = std::fs::remove_dir_all(&worktree);
+    }
+    git(
+        project_dir,
+        &[
+            "worktree",
+            "add",
+            "--detach",
+            &worktree.to_string_lossy(),
+            &good_commit,
+        ],
+    )?;
+    let result = bisect_in(
+        args,
+        &worktree,
+        &relative,
+        sanitizer,
+        input,
+        &good_commit,
+        &commits,
+    );
+    if let Err(err) = git(
+        project_dir,
+        &["worktree", "remove", "--force", &worktree.to_string_lossy()],
+    ) {
+        log::info!(
+            "Failed to remove the worktree {}: {err:#}",
+            worktree.display()
+        );
+    }
+    let (first_bad, skipped) = result?;
+
+    log::success!(
+        "The first commit at which the input fails is\n  {}",
+        git(project_dir, &["log", "-1", "--format=%h %s", &first_bad])?
+    );
+    if !skipped.is_empty() {
+        log::info!(
+            "The commits before it which failed to build may have introduced the failure as well:"
+        );
+        for commit in &skipped {
+            log::info!(
+                "  {}",
+                git(project_dir, &["log", "-1", "--format=%h %s", commit])?
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Tests the good commit, HEAD and then the commits in between in the
+/// worktree, and returns the first commit at which the input fails and
+/// the commits before it which were skipped.
+fn bisect_in(
+    args: &ReplayArgs,
+    worktree: &Path,
+    relative: &Path,
+    sanitizer: Option<Sanitizer>,
+    input: &Path,
+    good: &str,
+    commits: &[String],
+) -> Result<(String, Vec<String>)> {
+    let mut test = |commit: &str| -> Result<Option<bool>> {
+        let described = git(worktree, &["log", "-1", "--format=%h %s", commit])?;
+        git(
+            worktree,
+            &["checkout", "--quiet", "--force", "--detach", commit],
+        )?;
+        let project_dir = worktree.join(relative);
+        let builder = builder(&project_dir, sanitizer, &args.cargo_args);
+        let build_result = match builder.build_for_run(&args.fuzz_test) {
+            Ok(build_result) => build_result,
+            Err(err) => {
+                log::info!("{described}: skipped, the fuzz test failed to build: {err:#}");
+                return Ok(None);
+            }
+        };
+        let work_dir = builder.build_dir().join("replay");
+        let result =
+            runner(&build_result, &work_dir, sanitizer, args.input_timeout).reproduce(input)?;
+        let fails = !result.status.success();
+        log::info!("{described}: {}", if fails { "fails" } else { "passes" });
+        Ok(Some(fails))
+    };
+
+    match test(good)? {
+        Some(false) => {}
+        Some(true) => bail!("The input already fails at the good commit {good}"),
+        None => bail!("The fuzz test doesn't build at the good commit {good}"),
+    }
+    let head = commits.last().expect("commits is not empty");
+    match test(head)? {
+        Some(true) => {}
+        Some(false) => bail!(
+            "The input doesn't fail at HEAD in a clean checkout, the failure may be caused by \
+             uncommitted changes"
+        ),
+        None => bail!("The fuzz test doesn't build at HEAD in a clean checkout"),
+    }
+    let (first_bad, skipped) = first_bad(&commits[..commits.len() - 1], &mut test)?;
+    Ok((
+        commits[first_bad.unwrap_or(commits.len() - 1)].clone(),
+        skipped.into_iter().map(|i| commits[i].clone()).collect(),
+    ))
+}
+
+/// Binary searches the commits, which are followed by a bad commit, for
+/// the first bad one. `test` returns whether the input fails at a
+/// commit, or none if the commit can't be tested. Returns the index of
+/// the first bad commit, none if it's the one after them, and the
+/// indices of the skipped commits which may be the first bad one as
+/// well.
+fn first_bad(
+    commits: &[String],
+    test: &mut impl FnMut(&str) -> Result<Option<bool>>,
+) -> Result<(Option<usize>, Vec<usize>)> {
+    // The indices of the commits which weren't skipped
+    let mut candidates: Vec<usize> = (0..commits.len()).collect();
+    let mut skipped = Vec::new();
+    // The bad commit is candidates[hi], or the one after them
+    let (mut lo, mut hi) = (0, candidates.len());
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        match test(&commits[candidates[mid]])? {
+            Some(true) => hi = mid,
+            Some(false) => lo = mid + 1,
+            None => {
+                skipped.push(candidates.remove(mid));
+                hi -= 1;
+            }
+        }
+    }
+    let first_bad = candidates.get(hi).copied();
+    let last_good = hi.checked_sub(1).map(|i| candidates[i]);
+    skipped
+        .retain(|&i| last_good.is_none_or(|good| i > good) && first_bad.is_none_or(|bad| i < bad));
+    skipped.sort_unstable();
+    Ok((first_bad, skipped))
+}
+
+/// Executes git in the directory and returns its trimmed output.
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .context("failed to execute git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bisects ten commits of which the one at `bad` is the first bad
+    /// one, and those in `broken` don't build.
+    fn bisect_commits(bad: usize, broken: &[usize]) -> (Option<usize>, Vec<usize>, usize) {
+        let commits: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+        let mut tests = 0;
+        let (first_bad, skipped) = first_bad(&commits, &mut |commit| {
+            tests += 1;
+            let i: usize = commit.parse().unwrap();
+            Ok((!broken.contains(&i)).then_some(i >= bad))
+        })
+        .unwrap();
+        (first_bad, skipped, tests)
+    }
+
+    #[test]
+    fn bisect_first_bad_commit() {
+        for bad in 0..10 {
+            let (first_bad, skipped, tests) = bisect_commits(bad, &[]);
+            assert_eq!(first_bad, Some(bad));
+            assert!(skipped.is_empty());
+            assert!(tests <= 4, "{tests} tests");
+        }
+        // Only the commit after them is bad
+        assert_eq!(bisect_commits(10, &[]).0, None);
+    }
+
+    #[test]
+    fn bisect_with_skipped_commits() {
+        // The commits which don't build right before the first bad one
+        // may be the first bad one
+        assert_eq!(bisect_commits(6, &[4, 5]), (Some(6), vec![4, 5], 5));
+        // Those between good commits may not
+        let (first_bad, skipped, _) = bisect_commits(8, &[2, 4]);
+        assert_eq!((first_bad, skipped), (Some(8), vec![]));
+        assert_eq!(bisect_commits(10, &[9]).1, [9]);
+    }
+}
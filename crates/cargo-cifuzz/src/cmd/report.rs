@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Args;
+
+use crate::affected;
+use crate::cmd::findings::load_findings;
+use crate::config;
+use crate::html_report;
+use crate::log;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Format {
+    /// A standalone HTML page
+    Html,
+}
+
+/// Write a report of the findings and the coverage of the fuzz tests
+///
+/// This command writes a report of the findings in .cifuzz/findings and
+/// of the coverage reports in .cifuzz-coverage, e.g. to attach to the
+/// ticket of a security review:
+///
+///     cargo cifuzz report --format html -o cifuzz-report.html
+///
+/// The findings are grouped by their dedup token, so that every bug is
+/// shown once, open and severe bugs first. Every bug is shown with the
+/// stack trace of its newest finding, with the source around the frames
+/// in the project, the operations and the output of the fuzz test and
+/// the beginning of the crashing input. The coverage is the one of the
+/// last `cargo cifuzz coverage` of every fuzz test.
+///
+/// The HTML report has no external resources. It's written to stdout,
+/// or to the --output file.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+pub struct ReportArgs {
+    /// Only report the findings and the coverage of this fuzz test
+    fuzz_test: Option<String>,
+
+    /// The format of the report
+    #[arg(long, value_enum, default_value = "html")]
+    format: Format,
+
+    /// The file to write the report to
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+pub fn run(args: ReportArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let findings = load_findings(&project_dir, args.fuzz_test.as_deref())?;
+    let fuzz_test = args
+        .fuzz_test
+        .as_deref()
+        .map(|f| f.rsplit("::").next().unwrap_or(f));
+
+    let mut coverage = Vec::new();
+    let coverage_dir = project_dir.join(".cifuzz-coverage");
+    if coverage_dir.is_dir() {
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(&coverage_dir)
+            .with_context(|| format!("failed to read {}", coverage_dir.display()))?
+        {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if fuzz_test.is_none_or(|f| f == name) {
+                names.push(name);
+            }
+        }
+        names.sort();
+        for name in names {
+            if let Some(report) = affected::coverage_report(&project_dir, &name)? {
+                coverage.push((name, report.summary()));
+            }
+        }
+    }
+
+    let report = match args.format {
+        Format::Html => html_report::report(
+            &project_dir,
+            &findings,
+            &coverage,
+            &config::parse_project_config(&project_dir)?.severity,
+        ),
+    };
+    match &args.output {
+        Some(output) => {
+            std::fs::write(output, report)
+                .with_context(|| format!("failed to write {}", output.display()))?;
+            log::success!(
+                "Wrote the report of {} findings to {}",
+                findings.len(),
+                output.display()
+            );
+        }
+        None => print!("{report}"),
+    }
+    Ok(())
+}
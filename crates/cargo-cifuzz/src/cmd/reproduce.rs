@@ -0,0 +1,279 @@
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+
+use crate::build::{self, BuildMode, Builder, BuilderOptions};
+use crate::config;
+use crate::finding::{self, Finding, CRASHING_INPUT_FILE};
+use crate::log;
+use crate::parser;
+use crate::runner::libfuzzer::{Runner, RunnerOptions};
+use crate::runner::{self, miri, Engine};
+
+/// Reproduce a finding with the configuration it was found with
+///
+/// This command rebuilds the fuzz test of the finding with the
+/// configuration recorded in its finding.json, i.e. with the same
+/// sanitizer, RUSTFLAGS and arguments of `cargo test`, and executes it
+/// only with the crashing input, with the seed and the limits of the
+/// run which found it. The output of the fuzz test, e.g. the panic
+/// message or the report of the sanitizer, is printed.
+///
+/// `<FINDING>` is either the directory of a finding or its name, e.g.
+/// "c33f2bd8" for `.cifuzz/findings/<FUZZ_TEST>/c33f2bd8`. A unique prefix
+/// of the name is enough.
+///
+/// The command fails if the fuzz test doesn't crash with the input, so
+/// it can also be used to check that a bug has been fixed.
+///
+/// The finding.json also records the commit of the project, the version
+/// of rustc and the target the fuzz test was built for. If they differ
+/// from the current ones, the finding may not reproduce, which the
+/// command warns about. With --switch-toolchain, the finding is
+/// reproduced with the rustup toolchain of the recorded rustc, e.g.
+/// "1.84.0" or "nightly-2025-02-05".
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+pub struct ReproduceArgs {
+    /// The directory or the name of the finding
+    finding: String,
+
+    /// Reproduce the finding with the rustup toolchain it was found
+    /// with, if the current rustc is a different version
+    #[arg(long)]
+    switch_toolchain: bool,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+pub fn run(args: ReproduceArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let dir = finding::find(&project_dir, &args.finding)?;
+    let finding = Finding::load(&dir)?;
+    let metadata = &finding.metadata;
+
+    let commit = finding::git_commit(&project_dir);
+    if let (Some(recorded), Some(commit)) = (&metadata.commit, &commit) {
+        if recorded != commit {
+            log::error!(
+                "The finding was found at commit {}, the project is at commit {}",
+                recorded,
+                commit
+            );
+        }
+    }
+    let rustc = build::rustc_release()?;
+    if let Some(recorded) = metadata.rustc.as_ref().filter(|r| **r != rustc) {
+        let toolchain = rustup_toolchain(recorded);
+        match &toolchain {
+            Some(toolchain)
+                if args.switch_toolchain && std::env::var_os(SWITCHED_TOOLCHAIN_ENV).is_none() =>
+            {
+                return reproduce_with(toolchain);
+            }
+            Some(toolchain) => log::error!(
+                "The finding was found with rustc {recorded}, the current rustc is {rustc}. \
+                 Pass --switch-toolchain to reproduce it with the toolchain {toolchain}"
+            ),
+            None => log::error!(
+                "The finding was found with rustc {recorded}, the current rustc is {rustc}"
+            ),
+        }
+    }
+
+    log::info!("Building {}", metadata.fuzz_test);
+    let builder_options = || BuilderOptions {
+        project_dir: project_dir.clone(),
+        sanitizer: metadata.sanitizer,
+        mode: match metadata.engine {
+            Engine::Libfuzzer => BuildMode::Fuzzing,
+            Engine::Afl => BuildMode::Afl,
+            Engine::Honggfuzz => BuildMode::Honggfuzz,
+            Engine::Wasm => BuildMode::Wasm,
+            Engine::Miri => BuildMode::Fuzzing,
+        },
+        args: metadata.cargo_args.clone(),
+    };
+    // The recorded flags are the RUSTFLAGS of the environment followed
+    // by the flags of the build mode
+    let mut mode_builder = Builder::new(builder_options());
+    mode_builder.set_env_rustflags(Vec::new());
+    let mut builder = Builder::new(builder_options());
+    match metadata
+        .build_flags
+        .strip_suffix(mode_builder.rustflags().as_slice())
+    {
+        Some(env_rustflags) => builder.set_env_rustflags(env_rustflags.to_vec()),
+        None => log::info!(
+            "The finding was built with flags which can't be restored, building with {:?}",
+            builder.rustflags().join(" ")
+        ),
+    }
+    let target = builder.target()?;
+    if let Some(recorded) = metadata.target.as_ref().filter(|t| **t != target) {
+        log::error!(
+            "The finding was found on {recorded}, the fuzz test is built for {target}, \
+             platform-specific bugs may not reproduce"
+        );
+    }
+    let build_result = builder.build_for_run(&metadata.fuzz_test)?;
+    log::success!("Built fuzz test {}", build_result.name);
+
+    let input = dir.join(CRASHING_INPUT_FILE);
+    log::info!("Running {} with {}", build_result.name, input.display());
+    let result = match metadata.engine {
+        Engine::Libfuzzer | Engine::Wasm => {
+            let mut libfuzzer_args = metadata.libfuzzer_args.clone();
+            if let Some(seed) = metadata.seed {
+                libfuzzer_args.push(format!("-seed={seed}"));
+            }
+            let runner = Runner::new(RunnerOptions {
+                executable: build_result.executable.clone(),
+                test_name: build_result.test_name.clone(),
+                working_dir: build_result.package_dir.clone(),
+                generated_corpus_dir: build_result.generated_corpus.clone(),
+                seed_corpus_dirs: Vec::new(),
+                dictionary: None,
+                artifact_dir: builder.build_dir().join("reproduce"),
+                timeout: None,
+                input_timeout: None,
+                rss_limit_mb: None,
+                seed: metadata.rng_seed,
+                use_value_profile: false,
+                sanitizer: metadata.sanitizer,
+                detect_leaks: libfuzzer_args.iter().any(|arg| arg == "-detect_leaks=1"),
+                engine_args: libfuzzer_args,
+                capture_output: false,
+                slow_inputs_dir: None,
+                slow_inputs: 0,
+                exit_on_plateau: None,
+            });
+            runner.reproduce(&input)?
+        }
+        // AFL++ and honggfuzz findings are reproduced outside of the
+        // fuzzer, like they were when they were found
+        Engine::Afl | Engine::Honggfuzz => runner::run_input_from_stdin(
+            &build_result.executable,
+            &build_result.test_name,
+            &build_result.package_dir,
+            &input,
+        )?,
+        Engine::Miri => {
+            miri::check_installed()?;
+            miri::Runner::new(miri::RunnerOptions {
+                test_name: build_result.test_name.clone(),
+                working_dir: build_result.package_dir.clone(),
+                inputs: Vec::new(),
+                build_dir: project_dir.join(".cifuzz-build").join("miri"),
+                cargo_args: metadata.cargo_args.clone(),
+                engine_args: Vec::new(),
+            })
+            .run_input(&input)?
+        }
+    };
+    for line in &result.output {
+        eprintln!("{line}");
+    }
+
+    if result.status.success() {
+        bail!(
+            "The finding {} doesn't reproduce, the fuzz test {} doesn't crash with its input",
+            finding.name,
+            build_result.name
+        );
+    }
+    let Some(report) = parser::parse_crash(&result.output) else {
+        bail!(
+            "The fuzz test {} exited with {}",
+            build_result.name,
+            result.status
+        );
+    };
+    let token = finding::crash_dedup_token(&report, &project_dir, &build_result.package_dir);
+    if token != finding.dedup_token {
+        bail!(
+            "The fuzz test {} crashed differently than in the finding {}: {}",
+            build_result.name,
+            finding.name,
+            report.details()
+        );
+    }
+    log::success!(
+        "Reproduced the {} of the finding {}: {}",
+        finding.error_type.description(),
+        finding.name,
+        finding.details
+    );
+    Ok(())
+}
+
+/// Set for the reproduction with the toolchain of the finding, which
+/// doesn't switch toolchains again if the version still differs.
+const SWITCHED_TOOLCHAIN_ENV: &str = "CIFUZZ_SWITCHED_TOOLCHAIN";
+
+/// Executes this command again with the rustup toolchain.
+fn reproduce_with(toolchain: &str) -> Result<()> {
+    log::info!("Reproducing the finding with the toolchain {toolchain}");
+    let exe = std::env::current_exe().context("failed to determine the path of cargo-cifuzz")?;
+    let mut cmd = Command::new("rustup");
+    cmd.args(["run", toolchain])
+        .arg(exe)
+        .args(std::env::args_os().skip(1))
+        .env(SWITCHED_TOOLCHAIN_ENV, toolchain);
+    log::debug!("Command: {:?}", cmd);
+    let status = cmd.status().context("failed to execute rustup")?;
+    if !status.success() {
+        bail!("Reproducing the finding with the toolchain {toolchain} failed");
+    }
+    Ok(())
+}
+
+/// Returns the name of the rustup toolchain of a version of rustc like
+/// "1.86.0-nightly (a580b5c37 2025-02-04)". Nightly and beta toolchains
+/// are named after the day they were released, which is the day after
+/// the date of their last commit.
+fn rustup_toolchain(rustc: &str) -> Option<String> {
+    let (release, rest) = rustc.split_once(' ').unwrap_or((rustc, ""));
+    let channel = match release.split_once('-') {
+        None => return Some(release.to_string()),
+        Some((_, pre)) if pre.starts_with("nightly") => "nightly",
+        Some((_, pre)) if pre.starts_with("beta") => "beta",
+        Some(_) => return None,
+    };
+    let date = rest
+        .trim_matches(|c| c == '(' || c == ')')
+        .split(' ')
+        .nth(1)?;
+    let commit_date = humantime::parse_rfc3339(&format!("{date}T00:00:00Z")).ok()?;
+    let release_date = humantime::format_rfc3339(commit_date + Duration::from_secs(86400));
+    Some(format!("{channel}-{}", &release_date.to_string()[..10]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rustup_toolchains() {
+        assert_eq!(
+            rustup_toolchain("1.84.0 (9fc6b4312 2025-01-07)").as_deref(),
+            Some("1.84.0")
+        );
+        assert_eq!(
+            rustup_toolchain("1.86.0-nightly (a580b5c37 2025-02-28)").as_deref(),
+            Some("nightly-2025-03-01")
+        );
+        assert_eq!(
+            rustup_toolchain("1.85.0-beta.3 (4b8b87c2d 2025-01-31)").as_deref(),
+            Some("beta-2025-02-01")
+        );
+        assert_eq!(rustup_toolchain("1.87.0-dev"), None);
+        assert_eq!(rustup_toolchain("1.86.0-nightly"), None);
+    }
+}
@@ -0,0 +1,1845 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Args;
+
+use crate::affected;
+use crate::build::{self, BuildMode, BuildResult, Builder, BuilderOptions, Sanitizer};
+use crate::campaign;
+use crate::config::{self, parse_duration, FeatureSet, ProjectConfig, SeverityRule};
+use crate::corpus;
+use crate::dashboard;
+use crate::dictionary;
+use crate::events;
+use crate::exit_code::{self, Failure};
+use crate::finding::{self, Finding, Metadata, Status};
+use crate::focus;
+use crate::junit::{self, Outcome, TestCase, TestSuite};
+use crate::log;
+use crate::metrics;
+use crate::notify;
+use crate::parser::{self, CrashReport};
+use crate::regression_test;
+use crate::runner::libfuzzer::{Runner, RunnerOptions};
+use crate::runner::{afl, flags, honggfuzz, miri, Engine, FuzzingResult, RunResult};
+use crate::slow_inputs;
+use crate::symbolize;
+use crate::sync::{self, Remote};
+use crate::trace;
+
+/// Build and run a fuzz test
+///
+/// This command builds the fuzz tests of the project with coverage
+/// instrumentation for libFuzzer and executes the specified fuzz test.
+///
+/// `<FUZZ_TEST>` is the name of a function annotated with `#[fuzz_test]`.
+/// If multiple fuzz tests have the same name, specify it with (a suffix
+/// of) its module path, e.g. "parser::tests::my_fuzz_test".
+///
+/// Additional arguments for `cargo test` can be passed after a "--".
+/// For example:
+///
+///     cargo cifuzz run my_fuzz_test -- --release
+///
+/// The fuzzer starts from the inputs in the seed corpus directories of
+/// the fuzz test, i.e. the `<FUZZ_TEST>_inputs` directory next to its
+/// source file and the `corpus/<FUZZ_TEST>` directory of its package.
+/// Tokens from the `<FUZZ_TEST>.dict` files in the package of the fuzz
+/// test, the --dict file and the dictionary! invocations in the fuzz
+/// test are inserted into the generated inputs, as well as the string
+/// literals which the sources of the package compare to other values,
+/// e.g. "FUZZING" in `c == "FUZZING"`.
+///
+/// The inputs generated by the fuzzer are stored in
+/// `.cifuzz-corpus/<FUZZ_TEST>` in the project directory. Crashes are
+/// stored as findings in `.cifuzz/findings/<FUZZ_TEST>/<HASH>`, which
+/// contain the crashing input, the panic message and the stack trace.
+/// Crashes with the same top stack frames as an existing finding are
+/// not stored again.
+///
+/// The sanitizers require a nightly toolchain. ThreadSanitizer and
+/// MemorySanitizer also rebuild the standard library with it, which
+/// requires the rust-src rustup component:
+///
+///     rustup +nightly component add rust-src
+///     cargo +nightly cifuzz run my_fuzz_test --sanitizer memory
+///
+/// C and C++ code compiled by build scripts, e.g. with the cc crate, is
+/// built with the coverage and sanitizer flags of clang as well, so that
+/// bugs in native code called via FFI are detected. clang is used unless
+/// CC and CXX are set, the native code isn't instrumented without it.
+///
+/// An input on which the fuzz test runs for longer than the
+/// --input-timeout is stored as a finding as well, with the stack trace
+/// of the fuzz test at the time it was interrupted. So is an input on
+/// which the fuzz test exceeds the --rss-limit-mb, before the OS kills
+/// the fuzz test.
+///
+/// With --jobs, multiple instances of the fuzz test run in parallel and
+/// share the inputs they generate. Their combined progress is printed
+/// instead of the output of libFuzzer.
+///
+/// With --tui, a dashboard in the terminal shows the executions per
+/// second, the coverage and the corpus size of every job, the coverage
+/// and the corpus size over time, the findings of the run, e.g. with
+/// --keep-going, and the slowest inputs libFuzzer reported. The output
+/// of the fuzz test isn't shown, the messages of cargo-cifuzz are
+/// printed when the dashboard closes.
+///
+/// With --metrics-endpoint, the progress of a long-running campaign is
+/// exported for monitoring: the executions, the executions per second,
+/// the coverage, the corpus size, the memory, the findings, the uptime
+/// and the seconds since the last new coverage of the fuzz test. They
+/// are served for Prometheus to scrape on /metrics of an http:// URL,
+/// or pushed to StatsD every 10 seconds for a statsd:// URL:
+///
+///     cargo cifuzz run my_fuzz_test --timeout 24h --metrics-endpoint http://0.0.0.0:9464
+///
+/// The fuzz test runs in a child process of cargo-cifuzz, which stores
+/// the finding when it crashes or exceeds a limit. With --keep-going,
+/// the fuzz test is restarted afterwards and continues from the inputs
+/// generated so far, until the --timeout is reached or it stops without
+/// a finding. The command fails at the end if there were findings.
+///
+/// If the corpus-remote of the cifuzz.yaml is set, the inputs of the
+/// remote corpus of the fuzz test are pulled into the generated corpus
+/// before fuzzing, and the new inputs are pushed to it afterwards, see
+/// `cargo cifuzz corpus sync --help`. --no-sync skips both.
+///
+/// The state of the run is stored in `.cifuzz/campaigns/<FUZZ_TEST>.json`
+/// while the fuzz test runs. If the run is killed, or stopped by a
+/// finding or an error, --resume continues it instead of starting over:
+/// from the generated corpus, with the seed of libFuzzer's random number
+/// generator and for the rest of the --timeout, unless another --timeout
+/// is passed as the new total fuzzing time:
+///
+///     cargo cifuzz run my_fuzz_test --timeout 8h
+///     cargo cifuzz run my_fuzz_test --resume
+///
+/// With --seed, a run is reproducible: the seed fixes the mutations of
+/// libFuzzer and the order in which it loads the corpus, and it seeds
+/// `cifuzz::rng()`. Parallel jobs use consecutive seeds for libFuzzer.
+/// Findings record the seed, which `cargo cifuzz reproduce` passes
+/// again.
+///
+/// With --slow-inputs, the runtime measures how long the fuzz test
+/// takes on every input, and the N slowest inputs of the run are
+/// reported at the end, with the time they took and whether they are in
+/// the corpus. They are stored in `.cifuzz-artifacts/<FUZZ_TEST>/slow-inputs`.
+/// With --flamegraph, the fuzz test is then profiled on the slowest
+/// input with perf, and the profile is rendered as a flamegraph.svg in
+/// the same directory if inferno is installed (`cargo install inferno`):
+///
+///     cargo cifuzz run my_fuzz_test --timeout 10m --slow-inputs 5 --flamegraph
+///
+/// With --detect-leaks, libFuzzer checks for memory leaks after every
+/// input which allocates more memory than it frees. Leaks are stored as
+/// findings with the stack trace of the allocation.
+///
+/// With --engine afl or --engine honggfuzz, the fuzz test is built for
+/// AFL++ or honggfuzz instead and executed by afl-fuzz or honggfuzz,
+/// which must be installed. The corpus is shared with libFuzzer: The
+/// fuzzer starts from the seed corpus and the generated corpus, and the
+/// inputs it generates are added to the generated corpus. Crashes are
+/// reproduced without the fuzzer to store them as findings. Sanitizers,
+/// --detect-leaks and --use-value-profile are only supported by
+/// libFuzzer, --jobs by libFuzzer and honggfuzz.
+///
+/// With --engine wasm, the fuzz test is built for wasm32-wasip1 and
+/// runs under libFuzzer in cifuzz-wasm-driver, which executes the module
+/// with wasmtime and passes the coverage of its SanitizerCoverage
+/// counters to libFuzzer. Panics abort on wasm, so ignore_panics and
+/// custom mutators aren't supported.
+///
+/// With --engine miri, the fuzz test isn't fuzzed, but the generated
+/// corpus, the seed corpus and the crashing inputs of the findings are
+/// replayed under Miri, which detects undefined behavior the sanitizers
+/// miss, like out-of-bounds pointers, misaligned accesses, violations of
+/// the Stacked Borrows aliasing rules and data races. Every undefined
+/// behavior is stored as a finding. Miri requires a nightly toolchain
+/// with the miri component, and fuzz tests which call foreign functions
+/// can't be executed. The --engine-arg flags are passed to Miri:
+///
+///     rustup +nightly component add miri
+///     cargo +nightly cifuzz run my_fuzz_test --engine miri --engine-arg=-Zmiri-tree-borrows
+///
+/// Options of the fuzzing engine which have no flag of their own can be
+/// passed with --engine-arg, or set in the engine-args of the
+/// cifuzz.yaml for all fuzz tests or per fuzz test. They take precedence
+/// over the values set by cargo-cifuzz. libFuzzer flags are checked
+/// against the flags libFuzzer knows:
+///
+///     cargo cifuzz run my_fuzz_test --engine-arg=-max_len=4096
+///
+/// With the feature-matrix of the cifuzz.yaml, the fuzz test is built
+/// and run once per combination of cargo features, one after the other,
+/// each for the --timeout and with the --jobs, since code behind feature
+/// gates is easily left unfuzzed. The fuzz-tests map can have a
+/// feature-matrix of its own. --features and --no-default-features run
+/// the fuzz test with those features instead:
+///
+///     feature-matrix:
+///       - []
+///       - [json, xml]
+///       - features: [xml]
+///         no-default-features: true
+///
+/// The fuzz-tests map of the cifuzz.yaml overrides the timeout, the
+/// dictionary and the engine arguments per fuzz test, and sets the
+/// maximum input length and the sanitizer of libFuzzer, e.g. for a
+/// parser which needs larger inputs than a protocol handler. The flags
+/// take precedence over both:
+///
+///     fuzz-tests:
+///       parse_fuzz_test:
+///         max-len: 65536
+///         sanitizer: address
+///
+/// Findings which match the ignore list of the cifuzz.yaml, by their
+/// dedup token or their panic message, and findings marked as ignored
+/// with `cargo cifuzz findings mark` don't fail the run. The fuzz test
+/// is restarted after them, like with --keep-going.
+///
+/// With --exit-on-plateau, the fuzz test is stopped early when it
+/// covered no new edges for the given duration, even if the --timeout
+/// isn't reached yet, which saves CI time on fuzz tests whose coverage
+/// is saturated. The run then fails with the exit code 5 instead of 0,
+/// unless the fuzz test found a bug:
+///
+///     cargo cifuzz run my_fuzz_test --timeout 1h --exit-on-plateau 10m
+///
+/// To gate merges on a short fuzzing run in CI, limit the run with
+/// --max-fuzzing-duration (an alias of --timeout) and check the exit
+/// code of the command:
+///
+///     0  the fuzz test ran for the whole duration without a finding,
+///        or only with ignored findings
+///     3  the fuzz test found a bug, which is stored as a finding
+///     4  the fuzz tests failed to build
+///     5  the fuzz test was stopped early by --exit-on-plateau
+///     1  any other error, e.g. an invalid cifuzz.yaml
+///
+/// With --output json, the progress of the fuzzer, the findings and a
+/// summary of the run are written to stdout as JSON lines, one event per
+/// line, for dashboards and CI wrappers. The log and the output of the
+/// fuzz test are written to stderr. With --output-file, the events are
+/// written to the file instead:
+///
+///     {"event":"progress","execs":65536,"execs_per_sec":16384,
+///      "corpus_size":21,"edges":64,"new_edges":3,"features":80,
+///      "rss_mb":42,"fuzz_test":"my_fuzz_test","elapsed_secs":4.2}
+///
+/// The events are "start", "progress", "finding" and "summary", which
+/// contains the exit code of the command.
+///
+/// With --report, a JUnit XML report is written at the end of the run,
+/// in which the fuzz test is a test case that fails with its findings,
+/// so that CI systems show it alongside the unit tests:
+///
+///     cargo cifuzz run my_fuzz_test --max-fuzzing-duration 5m --report junit.xml
+///
+/// The regression tests of fuzz tests write JUnit reports as well, with
+/// a test case for every input they execute, if the CIFUZZ_JUNIT_DIR
+/// environment variable is set:
+///
+///     CIFUZZ_JUNIT_DIR=target/junit cargo test
+///
+/// With --focus-function, libFuzzer prefers to mutate the inputs which
+/// reach the function, to explore it more thoroughly than the rest of
+/// the code. The function is given by (a suffix of) its path. libFuzzer
+/// finds it with the help of a sanitizer, so the fuzz test is built with
+/// AddressSanitizer unless another --sanitizer is selected, which takes
+/// a nightly toolchain and llvm-symbolizer. At the end of the run, it's
+/// reported how many inputs of the corpus reach the function, or that
+/// the fuzzer never reached it:
+///
+///     cargo +nightly cifuzz run my_fuzz_test --focus-function explore_me::explore_me
+///
+/// With --all instead of a fuzz test, all fuzz tests of the members of
+/// the workspace are built and run one after the other, each for the
+/// --timeout, which is required. The fuzz tests are those listed by
+/// `cargo cifuzz list`. A finding doesn't stop the run, the command
+/// fails at the end if any fuzz test found a bug, and the report
+/// contains a test case per fuzz test:
+///
+///     cargo cifuzz run --all --timeout 10m --report junit.xml
+///
+/// With --changed-since, --all only runs the fuzz tests affected by the
+/// changes since the merge base with a git revision, e.g. the target
+/// branch of a pull request, so that fuzzing in CI takes time in
+/// proportion to the change. A fuzz test is affected if its coverage
+/// report in `.cifuzz-coverage/<FUZZ_TEST>`, created by
+/// `cargo cifuzz coverage`, covers a changed file, if it has no
+/// coverage report, or if a Cargo.toml, the Cargo.lock or the
+/// cifuzz.yaml changed:
+///
+///     cargo cifuzz run --all --timeout 5m --changed-since origin/main
+///
+/// With --trace, the fuzz test isn't fuzzed but executed once with the
+/// given input, e.g. a file of the corpus or the crashing input of a
+/// finding, and every value the FuzzedDataProvider decodes from it is
+/// printed with the bytes of the input it was decoded from. Integers are
+/// decoded from the end of the input, bytes and strings from the front:
+///
+///     cargo cifuzz run my_fuzz_test --trace .cifuzz-corpus/my_fuzz_test/<HASH>
+///
+///     OFFSET  BYTES                       CALL                  VALUE
+///     11..15  00 94 35 77                 consume_int::<u32>()  2000000000
+///     0..9    46 55 5a 5a 49 4e 47 5c ..  consume_string(100)   "FUZZING"
+#[derive(Debug, Clone, Args)]
+#[command(verbatim_doc_comment)]
+pub struct RunArgs {
+    /// The fuzz test to run
+    #[arg(required_unless_present = "all", conflicts_with = "all")]
+    fuzz_test: Option<String>,
+
+    /// Run all fuzz tests of the workspace one after the other, each
+    /// for the --timeout
+    #[arg(long)]
+    all: bool,
+
+    /// With --all, only run the fuzz tests affected by the changes since
+    /// the merge base with the git revision
+    #[arg(long, value_name = "REV", requires = "all")]
+    changed_since: Option<String>,
+
+    /// Build the fuzz test with a sanitizer to detect bugs which don't
+    /// cause a panic, e.g. memory errors in unsafe code. Requires a
+    /// nightly toolchain.
+    #[arg(long, value_enum)]
+    sanitizer: Option<Sanitizer>,
+
+    /// Report memory leaks as findings, with the input which caused them.
+    /// Uses LeakSanitizer if no other sanitizer which detects leaks is
+    /// selected.
+    #[arg(long)]
+    detect_leaks: bool,
+
+    /// Focus the fuzzer on the inputs which reach the function, given by
+    /// (a suffix of) its path, e.g. "explore_me::explore_me". Requires a
+    /// sanitizer, AddressSanitizer is used if none is selected.
+    #[arg(long, value_name = "FUNCTION", conflicts_with = "all")]
+    focus_function: Option<String>,
+
+    /// The fuzzing engine which runs the fuzz test
+    #[arg(long, value_enum, default_value_t)]
+    engine: Engine,
+
+    /// An additional argument for the fuzzing engine, e.g.
+    /// "-max_len=4096" for libFuzzer. Can be passed multiple times.
+    #[arg(long = "engine-arg", value_name = "ARG", allow_hyphen_values = true)]
+    engine_args: Vec<String>,
+
+    /// The cargo features to build the fuzz test with, comma separated,
+    /// instead of the feature-matrix of the cifuzz.yaml
+    #[arg(long, value_name = "FEATURES")]
+    features: Vec<String>,
+
+    /// Build the fuzz test without the default features
+    #[arg(long)]
+    no_default_features: bool,
+
+    /// The features of the run, from the flags or the feature-matrix
+    #[arg(skip)]
+    feature_set: Option<FeatureSet>,
+
+    /// Additional arguments to pass to `cargo test`
+    #[arg(last = true)]
+    cargo_args: Vec<String>,
+
+    /// Only build the fuzz test, don't run it
+    #[arg(long)]
+    build_only: bool,
+
+    /// Execute the fuzz test once with the input and print the values
+    /// the FuzzedDataProvider decodes from it instead of fuzzing
+    #[arg(long, value_name = "INPUT", conflicts_with_all = ["all", "build_only"])]
+    trace: Option<PathBuf>,
+
+    /// Maximum time to run the fuzz test, e.g. "30m", "1h". The default
+    /// is to run indefinitely.
+    #[arg(long, visible_alias = "max-fuzzing-duration", value_parser = parse_duration)]
+    timeout: Option<Duration>,
+
+    /// Maximum time the fuzz test may run on a single input, e.g. "10s",
+    /// before the input is reported as a timeout. Defaults to --timeout,
+    /// or 20 minutes if that is unset.
+    #[arg(long, value_parser = parse_duration)]
+    input_timeout: Option<Duration>,
+
+    /// Maximum memory the fuzz test may use in MB, before the input it's
+    /// executing is reported as out-of-memory. Defaults to 2048. 0
+    /// disables the limit.
+    #[arg(long, value_name = "MB")]
+    rss_limit_mb: Option<u64>,
+
+    /// Stop the fuzz test early when it covered no new edges for this
+    /// long, e.g. "10m", and exit with 5
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    exit_on_plateau: Option<Duration>,
+
+    /// Don't stop at the first finding, but store it and restart the
+    /// fuzz test, which continues from the generated corpus
+    #[arg(long)]
+    keep_going: bool,
+
+    /// Show a dashboard with the progress of the jobs, the coverage over
+    /// time, the findings and the slowest inputs instead of the output
+    /// of libFuzzer
+    #[arg(long)]
+    tui: bool,
+
+    /// Don't pull from or push to the corpus-remote of the cifuzz.yaml
+    #[arg(long)]
+    no_sync: bool,
+
+    /// Print the output of the fuzz test on every input. By default,
+    /// libFuzzer runs it with stdout and stderr captured, which makes
+    /// printing fuzz tests a lot faster, and only the output of a
+    /// crashing input is printed and stored with the finding.
+    #[arg(long)]
+    no_capture: bool,
+
+    /// Continue the interrupted run of the fuzz test, with its seed and
+    /// for the rest of its --timeout
+    #[arg(long, conflicts_with = "all")]
+    resume: bool,
+
+    /// The seed of the fuzzer's random number generator and of
+    /// `cifuzz::rng()`, between 1 and 4294967295, which makes the run
+    /// reproducible. Parallel jobs use consecutive seeds for the fuzzer.
+    #[arg(long, conflicts_with = "resume", value_parser = clap::value_parser!(u64).range(1..=u64::from(u32::MAX)))]
+    seed: Option<u64>,
+
+    /// Report the N slowest inputs at the end of the run, between 1 and
+    /// 100
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..=100))]
+    slow_inputs: Option<u16>,
+
+    /// Profile the fuzz test on the slowest input with perf and render a
+    /// flamegraph with inferno
+    #[arg(long, requires = "slow_inputs")]
+    flamegraph: bool,
+
+    /// The number of fuzz test instances to run in parallel, which share
+    /// the generated corpus
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: u16,
+
+    /// A dictionary file with tokens the fuzzer inserts into the inputs
+    #[arg(long, value_name = "FILE")]
+    dict: Option<PathBuf>,
+
+    /// Use the values of comparisons as feedback for the fuzzer, which
+    /// helps to solve comparisons of integers
+    #[arg(long)]
+    use_value_profile: bool,
+
+    /// Don't generate a dictionary from the string literals in the
+    /// sources of the package
+    #[arg(long)]
+    no_auto_dict: bool,
+
+    /// The format of the output on stdout: "text" for none besides the
+    /// output of the fuzz test, "json" for JSON lines with the progress
+    /// and the findings
+    #[arg(long, value_enum, default_value_t)]
+    output: Output,
+
+    /// Write the JSON lines of --output json to the file instead of
+    /// stdout
+    #[arg(long, value_name = "FILE")]
+    output_file: Option<PathBuf>,
+
+    /// Write a JUnit XML report of the run to the file
+    #[arg(long, value_name = "FILE")]
+    report: Option<PathBuf>,
+
+    /// Export the metrics of the run, served for Prometheus with
+    /// `http://<ADDRESS>:<PORT>` or pushed to StatsD with
+    /// `statsd://<HOST>:<PORT>`
+    #[arg(long, value_name = "URL")]
+    metrics_endpoint: Option<String>,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum Output {
+    #[default]
+    Text,
+    Json,
+}
+
+pub fn run(mut args: RunArgs) -> Result<()> {
+    if args.output_file.is_some() && args.output != Output::Json {
+        bail!("--output-file can only be used with --output json");
+    }
+    if args.tui && !std::io::stderr().is_terminal() {
+        bail!("--tui requires a terminal");
+    }
+    if args.engine == Engine::Miri && !args.build_only {
+        miri::check_installed()?;
+    }
+    if args.trace.is_some() && args.engine != Engine::Libfuzzer {
+        bail!("--trace requires the libfuzzer engine");
+    }
+    if let Some(endpoint) = &args.metrics_endpoint {
+        metrics::start(&metrics::Endpoint::parse(endpoint)?)?;
+    }
+    if args.all {
+        return run_all(args);
+    }
+    let fuzz_test = args
+        .fuzz_test
+        .clone()
+        .expect("the fuzz test is required without --all");
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let project_config = config::parse_project_config(&project_dir)?;
+    let feature_sets = feature_sets(&args, project_config.feature_matrix(&fuzz_test));
+    if feature_sets.len() > 1 {
+        let runs = feature_sets
+            .into_iter()
+            .map(|feature_set| Run {
+                label: format!("{fuzz_test} with {feature_set}"),
+                args: RunArgs {
+                    feature_set: Some(feature_set),
+                    ..args.clone()
+                },
+                target: Target::Name(&fuzz_test),
+            })
+            .collect();
+        return run_sequence(&args, runs, "runs");
+    }
+    args.feature_set = feature_sets.into_iter().next();
+
+    start_events(&args, &fuzz_test)?;
+    metrics::start_fuzz_test(&fuzz_test);
+    let start = Instant::now();
+    let findings = RefCell::new(Vec::new());
+    let result = fuzz(&args, Target::Name(&fuzz_test), &findings);
+    metrics::stop_fuzz_test();
+    finish_campaign(&result);
+    events::summary(result.as_ref().map_or_else(exit_code::of, |()| 0));
+    if let Some(report) = &args.report {
+        let test_case = test_case(
+            &fuzz_test,
+            start.elapsed(),
+            &result,
+            &findings.into_inner(),
+            &project_config.severity,
+        );
+        write_report(report, vec![test_case])?;
+    }
+    result
+}
+
+/// Returns the feature combinations the fuzz test is run with: the ones
+/// of the flags if they're passed, otherwise the ones of the
+/// feature-matrix, none meaning the features of the cargo arguments.
+fn feature_sets(args: &RunArgs, feature_matrix: &[FeatureSet]) -> Vec<FeatureSet> {
+    if !args.features.is_empty() || args.no_default_features {
+        let features = args
+            .features
+            .iter()
+            .flat_map(|features| features.split([',', ' ']))
+            .filter(|feature| !feature.is_empty())
+            .map(String::from)
+            .collect();
+        return vec![FeatureSet {
+            features,
+            no_default_features: args.no_default_features,
+        }];
+    }
+    feature_matrix.to_vec()
+}
+
+/// The arguments of `cargo test` for the run, including the ones which
+/// select its features.
+fn cargo_args(args: &RunArgs) -> Vec<String> {
+    let mut cargo_args = args.cargo_args.clone();
+    if let Some(feature_set) = &args.feature_set {
+        cargo_args.extend(feature_set.cargo_args());
+    }
+    cargo_args
+}
+
+/// Runs all fuzz tests of the workspace one after the other, each with
+/// the feature combinations of its feature-matrix.
+fn run_all(mut args: RunArgs) -> Result<()> {
+    args.cargo_args = build::workspace_args(&args.cargo_args);
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let project_config = config::parse_project_config(&project_dir)?;
+    // Miri stops when it replayed all inputs
+    if !args.build_only
+        && args.engine != Engine::Miri
+        && args.timeout.or(project_config.timeout).is_none()
+    {
+        bail!("--all requires a --timeout, which every fuzz test runs for");
+    }
+
+    let changed = match &args.changed_since {
+        Some(rev) => Some(affected::changed_files(&project_dir, rev)?),
+        None => None,
+    };
+
+    // The features of the flags apply to all fuzz tests instead of their
+    // feature-matrix, whose features are built when the fuzz tests run
+    let flag_features = feature_sets(&args, &[]).pop();
+    if let Some(feature_set) = &flag_features {
+        args.cargo_args.extend(feature_set.cargo_args());
+    }
+    log::info!("Building the fuzz tests");
+    let mut fuzz_tests = builder(&args, project_dir.clone(), sanitizer(&args, None)?).discover()?;
+    if fuzz_tests.is_empty() {
+        bail!("No fuzz tests found, fuzz tests are functions annotated with #[fuzz_test]");
+    }
+    log::success!(
+        "Built {} fuzz {}",
+        fuzz_tests.len(),
+        if fuzz_tests.len() == 1 {
+            "test"
+        } else {
+            "tests"
+        }
+    );
+    if args.build_only {
+        return Ok(());
+    }
+    if let Some(changed) = changed {
+        let mut skipped = Vec::new();
+        for fuzz_test in std::mem::take(&mut fuzz_tests) {
+            let report = affected::coverage_report(&project_dir, &fuzz_test.build.name)?;
+            match affected::affected(&changed, report.as_ref()) {
+                Some(reason) => {
+                    log::info!("{} {reason}", fuzz_test.build.name);
+                    fuzz_tests.push(fuzz_test);
+                }
+                None => skipped.push(fuzz_test.build.name),
+            }
+        }
+        if !skipped.is_empty() {
+            log::info!(
+                "Skipping the fuzz tests which the changes don't affect: {}",
+                skipped.join(", ")
+            );
+        }
+        if fuzz_tests.is_empty() {
+            if let Some(report) = &args.report {
+                write_report(report, Vec::new())?;
+            }
+            log::success!("No fuzz test is affected by the changes");
+            return Ok(());
+        }
+    }
+
+    let mut runs = Vec::new();
+    let mut matrix = false;
+    for fuzz_test in fuzz_tests {
+        let name = fuzz_test.build.name.clone();
+        let feature_sets = match flag_features {
+            Some(_) => Vec::new(),
+            None => project_config.feature_matrix(&name).to_vec(),
+        };
+        if feature_sets.len() <= 1 {
+            runs.push(Run {
+                label: name,
+                args: RunArgs {
+                    feature_set: feature_sets.into_iter().next(),
+                    ..args.clone()
+                },
+                target: Target::Discovered(fuzz_test.build),
+            });
+            continue;
+        }
+        matrix = true;
+        for feature_set in feature_sets {
+            runs.push(Run {
+                label: format!("{name} with {feature_set}"),
+                args: RunArgs {
+                    feature_set: Some(feature_set),
+                    ..args.clone()
+                },
+                target: Target::Discovered(fuzz_test.build.clone()),
+            });
+        }
+    }
+    run_sequence(&args, runs, if matrix { "runs" } else { "fuzz tests" })
+}
+
+/// A run of a fuzz test by [`run_sequence`].
+struct Run<'a> {
+    /// The fuzz test and its features, for the log and the report
+    label: String,
+    args: RunArgs,
+    target: Target<'a>,
+}
+
+/// Executes the runs one after the other. Findings don't stop them,
+/// other errors do. The runs are counted as `noun` in the summary.
+fn run_sequence(args: &RunArgs, runs: Vec<Run>, noun: &str) -> Result<()> {
+    let total = runs.len();
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let severity_rules = config::parse_project_config(&project_dir)?.severity;
+    let mut test_cases = Vec::new();
+    let mut failed = Vec::new();
+    let mut plateaued = Vec::new();
+    let mut error = None;
+    for (i, run) in runs.into_iter().enumerate() {
+        let name = match &run.target {
+            Target::Name(fuzz_test) => fuzz_test.to_string(),
+            Target::Discovered(build_result) => build_result.name.clone(),
+        };
+        if i == 0 {
+            start_events(args, &name)?;
+        } else {
+            events::restart(&name, args.engine, args.jobs.into());
+        }
+        if run.label != name {
+            log::info!("Running {}", run.label);
+        }
+        metrics::start_fuzz_test(&name);
+        let start = Instant::now();
+        let findings = RefCell::new(Vec::new());
+        let result = fuzz(&run.args, run.target, &findings);
+        metrics::stop_fuzz_test();
+        finish_campaign(&result);
+        events::summary(result.as_ref().map_or_else(exit_code::of, |()| 0));
+        let mut test_case = test_case(
+            &name,
+            start.elapsed(),
+            &result,
+            &findings.into_inner(),
+            &severity_rules,
+        );
+        if run.label != name {
+            test_case.name = run.label.clone();
+        }
+        test_cases.push(test_case);
+        match result {
+            Ok(()) => {}
+            Err(err) if exit_code::of(&err) == Failure::Finding.code() => {
+                log::error!("{err:#}");
+                failed.push(run.label);
+            }
+            Err(err) if exit_code::of(&err) == Failure::Plateau.code() => {
+                log::info!("{err:#}");
+                plateaued.push(run.label);
+            }
+            Err(err) => {
+                error = Some(err);
+                break;
+            }
+        }
+    }
+
+    if let Some(report) = &args.report {
+        write_report(report, test_cases)?;
+    }
+    if let Some(err) = error {
+        return Err(err);
+    }
+    if !failed.is_empty() {
+        return Err(Failure::Finding.error(anyhow!(
+            "{} of {total} {noun} found bugs: {}",
+            failed.len(),
+            failed.join(", ")
+        )));
+    }
+    if !plateaued.is_empty() {
+        return Err(Failure::Plateau.error(anyhow!(
+            "{} of {total} {noun} were stopped early, they covered no new edges: {}",
+            plateaued.len(),
+            plateaued.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Starts emitting the events of the run with --output json.
+fn start_events(args: &RunArgs, fuzz_test: &str) -> Result<()> {
+    if args.output != Output::Json {
+        return Ok(());
+    }
+    let jobs = args.jobs.into();
+    match &args.output_file {
+        Some(file) => {
+            let file = std::fs::File::create(file)
+                .with_context(|| format!("failed to create {}", file.display()))?;
+            events::start(Box::new(file), false, fuzz_test, args.engine, jobs);
+        }
+        None => events::start(
+            Box::new(std::io::stdout()),
+            true,
+            fuzz_test,
+            args.engine,
+            jobs,
+        ),
+    }
+    Ok(())
+}
+
+/// Returns the test case of the run of the fuzz test for the JUnit XML
+/// report, which fails with the findings of the run.
+fn test_case(
+    fuzz_test: &str,
+    time: Duration,
+    result: &Result<()>,
+    findings: &[Finding],
+    rules: &[SeverityRule],
+) -> TestCase {
+    let mut unique: Vec<&Finding> = Vec::new();
+    for finding in findings {
+        if !unique.iter().any(|f| f.dedup_token == finding.dedup_token) {
+            unique.push(finding);
+        }
+    }
+    let outcome = match result {
+        Ok(()) => Outcome::Passed,
+        Err(err) if exit_code::of(err) == Failure::Plateau.code() => Outcome::Passed,
+        Err(err) if exit_code::of(err) == Failure::Finding.code() => Outcome::Failure {
+            kind: match unique.as_slice() {
+                [finding] => finding.error_type.description().to_string(),
+                _ => "finding".to_string(),
+            },
+            message: format!("{err:#}"),
+            text: unique
+                .iter()
+                .map(|finding| finding_text(finding, rules))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        },
+        Err(err) => Outcome::Error {
+            message: format!("{err:#}"),
+        },
+    };
+    TestCase {
+        name: unique
+            .first()
+            .map_or(fuzz_test, |finding| &finding.metadata.fuzz_test)
+            .to_string(),
+        classname: "fuzzing".to_string(),
+        time,
+        outcome,
+    }
+}
+
+/// Writes the JUnit XML report of the run with the test cases of the
+/// fuzz tests.
+fn write_report(report: &Path, test_cases: Vec<TestCase>) -> Result<()> {
+    let suite = TestSuite {
+        name: "cargo cifuzz run".to_string(),
+        test_cases,
+    };
+    std::fs::write(report, junit::report(&[suite]))
+        .with_context(|| format!("failed to write {}", report.display()))
+}
+
+/// Describes the finding with its stack trace, for the report.
+fn finding_text(finding: &Finding, rules: &[SeverityRule]) -> String {
+    let mut text = format!(
+        "{} ({} severity): {}\n",
+        finding.error_type.description(),
+        finding.severity(rules).name(),
+        finding.details
+    );
+    for frame in &finding.stack_trace {
+        if frame.source_file.is_empty() {
+            text.push_str(&format!("    at {}\n", frame.function));
+        } else {
+            text.push_str(&format!(
+                "    at {} ({}:{}:{})\n",
+                frame.function, frame.source_file, frame.line, frame.column
+            ));
+        }
+    }
+    if !finding.operations.is_empty() {
+        text.push_str("Operations:\n");
+        for (i, operation) in finding.operations.iter().enumerate() {
+            text.push_str(&format!("  {i}: {operation}\n"));
+        }
+    }
+    text.push_str(&format!(
+        "The crashing input is stored in {}\n",
+        finding.input_file.display()
+    ));
+    text
+}
+
+/// The fuzz test which [`fuzz`] runs.
+enum Target<'a> {
+    /// The fuzz test specified by the user, which is built first
+    Name(&'a str),
+    /// A fuzz test built and discovered by `--all`
+    Discovered(BuildResult),
+}
+
+fn fuzz(args: &RunArgs, target: Target, findings: &RefCell<Vec<Finding>>) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let project_config = config::parse_project_config(&project_dir)?;
+    let fuzz_test_config = project_config.fuzz_test(match &target {
+        Target::Name(fuzz_test) => fuzz_test,
+        Target::Discovered(build_result) => &build_result.name,
+    });
+
+    // Flags take precedence over the settings of the fuzz test in the
+    // cifuzz.yaml, which take precedence over the ones for all fuzz tests
+    let timeout = args
+        .timeout
+        .or(fuzz_test_config.timeout)
+        .or(project_config.timeout);
+    let dict = args.dict.clone().or_else(|| {
+        fuzz_test_config
+            .dict
+            .as_ref()
+            .or(project_config.dict.as_ref())
+            .map(|dict| project_dir.join(dict))
+    });
+    if let Some(dict) = dict.as_ref().filter(|dict| !dict.is_file()) {
+        bail!("Dictionary {} doesn't exist", dict.display());
+    }
+    if timeout.is_some_and(|t| t < Duration::from_secs(1)) {
+        bail!("invalid argument for \"--timeout\" flag: timeout can't be less than a second");
+    }
+    // A single input must not stall the fuzzer for longer than the whole
+    // run may take
+    let input_timeout = args.input_timeout.or(timeout);
+    if input_timeout.is_some_and(|t| t < Duration::from_secs(1)) {
+        bail!("invalid argument for \"--input-timeout\" flag: timeout can't be less than a second");
+    }
+    if args
+        .exit_on_plateau
+        .is_some_and(|t| t < Duration::from_secs(1))
+    {
+        bail!(
+            "invalid argument for \"--exit-on-plateau\" flag: duration can't be less than a second"
+        );
+    }
+    let sanitizer = sanitizer(args, fuzz_test_config.sanitizer)?;
+    let detect_leaks = args.detect_leaks || sanitizer == Some(Sanitizer::Leak);
+
+    let builder = builder(args, project_dir.clone(), sanitizer);
+    let build_result = match target {
+        Target::Name(fuzz_test) => {
+            log::info!("Building {fuzz_test}");
+            let build_result = builder.build_for_run(fuzz_test)?;
+            log::success!("Built fuzz test {}", build_result.name);
+            build_result
+        }
+        // --all built the fuzz tests without the sanitizers and the
+        // feature-matrix of the cifuzz.yaml
+        Target::Discovered(build_result)
+            if sanitizer != self::sanitizer(args, None)? || args.feature_set.is_some() =>
+        {
+            let module = build_result
+                .test_name
+                .strip_suffix("::fuzz")
+                .unwrap_or(&build_result.test_name);
+            log::info!(
+                "Building {} with the settings of the cifuzz.yaml",
+                build_result.name
+            );
+            builder.build_for_run(module)?
+        }
+        Target::Discovered(build_result) => build_result,
+    };
+    log::debug!("Executable: {}", build_result.executable.display());
+
+    if args.build_only {
+        return Ok(());
+    }
+    if let Some(input) = &args.trace {
+        let work_dir = builder.build_dir().join("trace");
+        return trace_input(&build_result, sanitizer, input, &work_dir);
+    }
+    // Fail before fuzzing instead of at the end
+    if args.flamegraph {
+        slow_inputs::perf()?;
+    }
+
+    let focus_function = match &args.focus_function {
+        Some(function) if args.engine == Engine::Libfuzzer => {
+            if symbolize::llvm_symbolizer().is_none() {
+                bail!(
+                    "--focus-function requires llvm-symbolizer, install it with \
+                     `rustup component add llvm-tools`"
+                );
+            }
+            let name = focus::resolve(&build_result.executable, function)?;
+            log::info!("Focusing on the function {name}");
+            Some(name)
+        }
+        _ => None,
+    };
+    // The flags take precedence over the settings in the cifuzz.yaml
+    let engine_args: Vec<String> = focus_function
+        .iter()
+        .map(|name| format!("-focus_function={name}"))
+        .chain(
+            project_config
+                .engine_args
+                .for_fuzz_test(&build_result.name)
+                .iter()
+                .cloned(),
+        )
+        .chain(
+            fuzz_test_config
+                .max_len
+                .filter(|_| matches!(args.engine, Engine::Libfuzzer | Engine::Wasm))
+                .map(|max_len| format!("-max_len={max_len}")),
+        )
+        .chain(fuzz_test_config.engine_args.iter().cloned())
+        .chain(args.engine_args.iter().cloned())
+        .collect();
+    if matches!(args.engine, Engine::Libfuzzer | Engine::Wasm) {
+        flags::validate_libfuzzer_args(&engine_args)?;
+    }
+
+    for dir in &build_result.seed_corpus_dirs {
+        log::info!("Using seed corpus {}", dir.display());
+    }
+    let dictionary_dir = builder.build_dir().join("dictionaries");
+    let mut dictionaries: Vec<PathBuf> = dict
+        .into_iter()
+        .chain(build_result.dictionaries.iter().cloned())
+        .collect();
+    if !args.no_auto_dict {
+        let auto_dict = dictionary_dir.join(format!("{}.auto.dict", build_result.name));
+        let tokens = dictionary::generate(&build_result.package_dir, &auto_dict)?;
+        log::debug!("Extracted {tokens} tokens from the sources");
+        if tokens > 0 {
+            dictionaries.push(auto_dict);
+        }
+    }
+    let dictionary = dictionary::merge(
+        &dictionaries,
+        &dictionary_dir.join(format!("{}.dict", build_result.name)),
+    )?;
+
+    let artifact_dir = project_dir
+        .join(".cifuzz-artifacts")
+        .join(&build_result.name);
+    let work_dir = builder.build_dir().join("work").join(&build_result.name);
+    let metadata = Metadata {
+        fuzz_test: build_result.name.clone(),
+        commit: finding::git_commit(&project_dir),
+        build_flags: builder.rustflags(),
+        cargo_args: cargo_args(args),
+        engine: args.engine,
+        sanitizer,
+        seed: None,
+        rng_seed: args.seed,
+        libfuzzer_args: Vec::new(),
+        rustc: build::rustc_release().ok(),
+        target: builder.target().ok(),
+    };
+    let save = |report: &CrashReport| {
+        save_finding(
+            report,
+            &project_dir,
+            &build_result.package_dir,
+            metadata.clone(),
+            &project_config,
+            findings,
+        )
+    };
+    // The remote corpus, which the fuzzer starts from as well and which
+    // the generated inputs are pushed to however the fuzzing ends
+    let remote = match &project_config.corpus_remote {
+        Some(url) if !args.no_sync => Some(Remote::parse(url, &project_dir)?),
+        _ => None,
+    };
+    let name = build_result.name.clone();
+    let generated_corpus = build_result.generated_corpus.clone();
+    if let Some(remote) = &remote {
+        log::info!("Pulling the remote corpus of {name}");
+        match remote.pull(&name, &generated_corpus) {
+            Ok(pulled) => log::info!("Pulled {pulled} new inputs"),
+            Err(err) => log::error!("Failed to pull the remote corpus: {err:#}"),
+        }
+    }
+    let result = (|| -> Result<()> {
+        match args.engine {
+            Engine::Libfuzzer | Engine::Wasm => {}
+            Engine::Miri => {
+                let mut corpus_dirs = vec![build_result.generated_corpus.clone()];
+                corpus_dirs.extend(build_result.seed_corpus_dirs.iter().cloned());
+                corpus_dirs.retain(|dir| dir.is_dir());
+                let mut inputs = corpus::list_inputs(&corpus_dirs)?;
+                let findings_dir = finding::findings_dir(&project_dir, &build_result.name);
+                inputs.extend(
+                    finding::dirs(&project_dir)?
+                        .into_iter()
+                        .filter(|dir| dir.starts_with(&findings_dir))
+                        .map(|dir| dir.join(finding::CRASHING_INPUT_FILE))
+                        .filter(|input| input.is_file()),
+                );
+                log::info!(
+                    "Replaying {} inputs of {} under Miri",
+                    inputs.len(),
+                    build_result.name
+                );
+                let runner = miri::Runner::new(miri::RunnerOptions {
+                    test_name: build_result.test_name.clone(),
+                    working_dir: build_result.package_dir.clone(),
+                    inputs,
+                    build_dir: project_dir.join(".cifuzz-build").join("miri"),
+                    cargo_args: cargo_args(args),
+                    engine_args: engine_args.clone(),
+                });
+                let result = runner.run()?;
+                if result.crashes.is_empty() && result.status.success() {
+                    log::success!("Miri detected no undefined behavior");
+                }
+                return report_findings(
+                    args.engine,
+                    result,
+                    &artifact_dir,
+                    |input| runner.run_input(input),
+                    save,
+                );
+            }
+            Engine::Afl => {
+                let runner = afl::Runner::new(afl::RunnerOptions {
+                    executable: build_result.executable.clone(),
+                    test_name: build_result.test_name.clone(),
+                    working_dir: build_result.package_dir.clone(),
+                    generated_corpus_dir: build_result.generated_corpus.clone(),
+                    seed_corpus_dirs: build_result.seed_corpus_dirs.clone(),
+                    dictionary,
+                    work_dir,
+                    timeout,
+                    input_timeout,
+                    rss_limit_mb: args.rss_limit_mb,
+                    keep_going: args.keep_going,
+                    engine_args: engine_args.clone(),
+                });
+                log::info!("Running {} with AFL++", build_result.name);
+                let result = runner.run()?;
+                return report_findings(
+                    args.engine,
+                    result,
+                    &artifact_dir,
+                    |input| runner.run_input(input),
+                    save,
+                );
+            }
+            Engine::Honggfuzz => {
+                let runner = honggfuzz::Runner::new(honggfuzz::RunnerOptions {
+                    executable: build_result.executable.clone(),
+                    test_name: build_result.test_name.clone(),
+                    working_dir: build_result.package_dir.clone(),
+                    generated_corpus_dir: build_result.generated_corpus.clone(),
+                    seed_corpus_dirs: build_result.seed_corpus_dirs.clone(),
+                    dictionary,
+                    work_dir,
+                    timeout,
+                    input_timeout,
+                    rss_limit_mb: args.rss_limit_mb,
+                    jobs: args.jobs.into(),
+                    keep_going: args.keep_going,
+                    engine_args: engine_args.clone(),
+                });
+                log::info!("Running {} with honggfuzz", build_result.name);
+                let result = runner.run()?;
+                return report_findings(
+                    args.engine,
+                    result,
+                    &artifact_dir,
+                    |input| runner.run_input(input),
+                    save,
+                );
+            }
+        }
+        // The campaign of the fuzz test, whose state --resume continues from
+        let campaign_file = campaign::file(&project_dir, &build_result.name);
+        let mut engine_args = engine_args;
+        let campaign = if args.resume {
+            let Some(state) = campaign::load(&campaign_file)? else {
+                bail!(
+                    "The fuzz test {} has no run to resume, run it without --resume first",
+                    build_result.name
+                );
+            };
+            if state.finished {
+                bail!(
+                    "The last run of {} finished, run it without --resume to start a new one",
+                    build_result.name
+                );
+            }
+            // A --timeout sets a new total fuzzing time
+            let state = campaign::State {
+                timeout_secs: args
+                    .timeout
+                    .map_or(state.timeout_secs, |t| Some(t.as_secs())),
+                ..state
+            };
+            if state
+                .remaining()
+                .is_some_and(|t| t < Duration::from_secs(1))
+            {
+                bail!(
+                    "The run of {} already used its whole timeout of {}",
+                    build_result.name,
+                    humantime::format_duration(state.elapsed())
+                );
+            }
+            log::info!(
+                "Resuming the run of {} after {} of fuzzing",
+                build_result.name,
+                humantime::format_duration(state.elapsed())
+            );
+            // Parallel jobs must not use the same seed
+            if let Some(seed) = state.seed.filter(|_| args.jobs == 1) {
+                // The flags of the user take precedence
+                engine_args.insert(0, format!("-seed={seed}"));
+            }
+            state
+        } else {
+            if campaign::load(&campaign_file)
+                .ok()
+                .flatten()
+                .is_some_and(|state| !state.finished)
+            {
+                log::info!(
+                    "Starting over, the interrupted run of {} can be continued with --resume instead",
+                    build_result.name
+                );
+            }
+            campaign::State::new(&build_result.name, timeout)
+        };
+        let timeout = campaign.remaining();
+        let slow_inputs_dir = match args.slow_inputs {
+            Some(_) => {
+                let dir = artifact_dir.join("slow-inputs");
+                slow_inputs::prepare(&dir)?;
+                Some(dir)
+            }
+            None => None,
+        };
+
+        let mut runner = Runner::new(RunnerOptions {
+            executable: build_result.executable,
+            test_name: build_result.test_name,
+            working_dir: build_result.package_dir.clone(),
+            generated_corpus_dir: build_result.generated_corpus,
+            seed_corpus_dirs: build_result.seed_corpus_dirs,
+            dictionary,
+            artifact_dir: artifact_dir.clone(),
+            timeout,
+            input_timeout,
+            rss_limit_mb: args.rss_limit_mb,
+            seed: args.seed,
+            use_value_profile: args.use_value_profile,
+            sanitizer,
+            detect_leaks,
+            engine_args,
+            capture_output: !args.no_capture,
+            slow_inputs_dir: slow_inputs_dir.clone(),
+            slow_inputs: args.slow_inputs.unwrap_or_default().into(),
+            exit_on_plateau: args.exit_on_plateau,
+        });
+
+        // With --keep-going, the fuzz test is restarted after every finding
+        // and continues from the corpus generated so far
+        let start = Instant::now();
+        // The dedup tokens of the findings, the fuzz test will likely find
+        // the same bug again after a restart. Ignored findings don't fail
+        // the run, the fuzz test is restarted after them even without
+        // --keep-going.
+        let mut found = HashSet::new();
+        let mut ignored = HashSet::new();
+        // The number of inputs reaching the focus function, over all runs
+        let mut focus_inputs = None;
+        let mut plateaued = false;
+        campaign::start(campaign_file, campaign)?;
+        // Closes the dashboard when the fuzz test stops
+        let _dashboard = if args.tui {
+            Some(dashboard::start(&build_result.name, args.jobs.into())?)
+        } else {
+            None
+        };
+        // The slow inputs are reported however the fuzzing ends
+        let fuzzing = (|| -> Result<()> {
+            loop {
+                let result = if args.jobs > 1 {
+                    log::info!("Running {} with {} jobs", build_result.name, args.jobs);
+                    runner.run_jobs(args.jobs.into())?
+                } else {
+                    log::info!("Running {}", build_result.name);
+                    runner.run()?
+                };
+                campaign::exited();
+                focus_inputs = focus_inputs.max(parser::focus_inputs(&result.output));
+                if result.plateaued {
+                    plateaued = true;
+                    break;
+                }
+                if result.status.success() {
+                    break;
+                }
+
+                let Some(report) =
+                    parser::parse_crash(&result.output).filter(|r| r.input_file.is_some())
+                else {
+                    return Err(Failure::Finding.error(anyhow!(
+                        "The fuzz test {} exited with {}, crashing inputs are stored in {}",
+                        build_result.name,
+                        result.status,
+                        artifact_dir.display()
+                    )));
+                };
+                // The seed and the limits are recorded to reproduce the finding
+                let finding = save_finding(
+                    &report,
+                    &project_dir,
+                    &build_result.package_dir,
+                    Metadata {
+                        seed: parser::seed(&result.output),
+                        libfuzzer_args: runner.input_args(),
+                        ..metadata.clone()
+                    },
+                    &project_config,
+                    findings,
+                )?;
+                let is_ignored = finding.status == Status::Ignored;
+                if is_ignored {
+                    ignored.insert(finding.dedup_token.clone());
+                } else if !args.keep_going {
+                    report_focus(focus_function.as_deref(), focus_inputs);
+                    return Err(Failure::Finding.error(anyhow!(
+                        "The fuzz test {} found a {}: {}",
+                        build_result.name,
+                        finding.error_type.description(),
+                        finding.details
+                    )));
+                } else {
+                    log::error!(
+                        "The fuzz test {} found a {}: {}",
+                        build_result.name,
+                        finding.error_type.description(),
+                        finding.details
+                    );
+                    found.insert(finding.dedup_token.clone());
+                }
+
+                // The fuzz test would crash again on every restart
+                if !parser::corpus_loaded(&result.output) {
+                    if is_ignored {
+                        log::info!(
+                            "The fuzz test {} crashed on an input of its corpus, it can't be restarted",
+                            build_result.name
+                        );
+                        break;
+                    }
+                    return Err(Failure::Finding.error(anyhow!(
+                        "The fuzz test {} crashed on an input of its corpus",
+                        build_result.name
+                    )));
+                }
+                if let Some(timeout) = timeout {
+                    let remaining = timeout.saturating_sub(start.elapsed());
+                    if remaining < Duration::from_secs(1) {
+                        break;
+                    }
+                    runner.set_timeout(Some(remaining));
+                }
+                log::info!("Restarting {}", build_result.name);
+            }
+            report_focus(focus_function.as_deref(), focus_inputs);
+
+            if !ignored.is_empty() {
+                log::info!(
+                    "The fuzz test {} found {} ignored {}",
+                    build_result.name,
+                    ignored.len(),
+                    if ignored.len() == 1 { "bug" } else { "bugs" }
+                );
+            }
+            if !found.is_empty() {
+                return Err(Failure::Finding.error(anyhow!(
+                    "The fuzz test {} found {} {}",
+                    build_result.name,
+                    found.len(),
+                    if found.len() == 1 { "bug" } else { "bugs" }
+                )));
+            }
+            if plateaued {
+                return Err(Failure::Plateau.error(anyhow!(
+                    "The fuzz test {} covered no new edges for {}, stopped it after {}",
+                    build_result.name,
+                    humantime::format_duration(args.exit_on_plateau.unwrap_or_default()),
+                    humantime::format_duration(Duration::from_secs(start.elapsed().as_secs()))
+                )));
+            }
+            Ok(())
+        })();
+        if let Some(dir) = &slow_inputs_dir {
+            report_slow_inputs(args, &runner, &build_result.name, dir, &generated_corpus);
+        }
+        fuzzing
+    })();
+    if let Some(remote) = &remote {
+        log::info!("Pushing the new inputs of {name} to the remote corpus");
+        let staging_dir = sync::staging_dir(&project_dir, &name);
+        match remote.push(&name, &generated_corpus, &staging_dir) {
+            Ok(pushed) => log::info!("Pushed {pushed} new inputs"),
+            Err(err) => log::error!("Failed to push the corpus: {err:#}"),
+        }
+    }
+    result
+}
+
+/// Executes the fuzz test with the input and prints the calls of the
+/// `FuzzedDataProvider` it made, for --trace.
+fn trace_input(
+    build_result: &BuildResult,
+    sanitizer: Option<Sanitizer>,
+    input: &Path,
+    work_dir: &Path,
+) -> Result<()> {
+    let data =
+        std::fs::read(input).with_context(|| format!("failed to read {}", input.display()))?;
+    std::fs::create_dir_all(work_dir)
+        .with_context(|| format!("failed to create {}", work_dir.display()))?;
+    let calls_file = work_dir.join(format!("{}.calls", build_result.name));
+    // Crashes which are not panics, e.g. stack overflows, kill the
+    // process before the calls are written
+    let _ = std::fs::remove_file(&calls_file);
+
+    let runner = Runner::new(RunnerOptions {
+        executable: build_result.executable.clone(),
+        test_name: build_result.test_name.clone(),
+        working_dir: build_result.package_dir.clone(),
+        generated_corpus_dir: build_result.generated_corpus.clone(),
+        seed_corpus_dirs: Vec::new(),
+        dictionary: None,
+        artifact_dir: work_dir.to_path_buf(),
+        timeout: None,
+        input_timeout: None,
+        rss_limit_mb: None,
+        seed: None,
+        use_value_profile: false,
+        sanitizer,
+        detect_leaks: false,
+        engine_args: Vec::new(),
+        capture_output: false,
+        slow_inputs_dir: None,
+        slow_inputs: 0,
+        exit_on_plateau: None,
+    });
+    log::info!("Tracing {} with {}", build_result.name, input.display());
+    let result = runner.run_input(input, Some(&calls_file))?;
+    let calls = match std::fs::read_to_string(&calls_file) {
+        Ok(calls) => trace::parse_calls(&calls)?,
+        Err(_) => Vec::new(),
+    };
+    if calls.is_empty() {
+        log::info!("The fuzz test didn't use the FuzzedDataProvider");
+    } else {
+        print!("{}", trace::format(&calls, &data));
+    }
+    let unconsumed = trace::unconsumed_bytes(&calls, &data);
+    if unconsumed > 0 && !calls.is_empty() {
+        log::info!(
+            "{unconsumed} of the {} bytes of the input weren't consumed",
+            data.len()
+        );
+    }
+    if !result.status.success() {
+        let error = parser::parse_crash(&result.output)
+            .and_then(|report| report.panic_message.or(report.error))
+            .unwrap_or_else(|| result.status.to_string());
+        bail!(
+            "The fuzz test {} crashed with the input: {error}",
+            build_result.name
+        );
+    }
+    Ok(())
+}
+
+/// Ends the session of the campaign of the fuzz test, which finishes the
+/// campaign unless the run was stopped by a finding or an error.
+fn finish_campaign(result: &Result<()>) {
+    let finished = match result {
+        Ok(()) => true,
+        Err(err) => exit_code::of(err) == Failure::Plateau.code(),
+    };
+    let Some(state) = campaign::finish(finished) else {
+        return;
+    };
+    if state.sessions > 1 {
+        log::info!(
+            "The fuzz test {} ran for {} in {} runs and executed {} inputs",
+            state.fuzz_test,
+            humantime::format_duration(state.elapsed()),
+            state.sessions,
+            state.execs
+        );
+    }
+}
+
+/// Reports the slowest inputs the runtime wrote to the directory and
+/// profiles the fuzz test on the slowest one with --flamegraph. Errors
+/// are logged, they don't fail the run.
+fn report_slow_inputs(
+    args: &RunArgs,
+    runner: &Runner,
+    fuzz_test: &str,
+    dir: &Path,
+    corpus_dir: &Path,
+) {
+    let count = args.slow_inputs.unwrap_or_default().into();
+    let inputs = match slow_inputs::read(dir, count, corpus_dir) {
+        Ok(inputs) => inputs,
+        Err(err) => {
+            log::error!("Failed to read the slow inputs: {err:#}");
+            return;
+        }
+    };
+    slow_inputs::report(fuzz_test, &inputs);
+    let Some(slowest) = inputs.first().filter(|_| args.flamegraph) else {
+        return;
+    };
+    log::info!("Profiling {fuzz_test} on {}", slowest.path.display());
+    match slow_inputs::flamegraph(runner, slowest, dir) {
+        Ok(profile) => log::success!("The profile is stored in {}", profile.display()),
+        Err(err) => log::error!("Failed to profile the fuzz test: {err:#}"),
+    }
+}
+
+/// Reports whether the fuzzer reached the focus function, by the number
+/// of inputs in the corpus which reach it.
+fn report_focus(function: Option<&str>, inputs: Option<u64>) {
+    let Some(function) = function else {
+        return;
+    };
+    match inputs {
+        Some(0) | None => log::error!("The fuzzer never reached the focus function {function}"),
+        Some(inputs) => log::success!(
+            "{inputs} {} of the corpus reach the focus function {function}",
+            if inputs == 1 { "input" } else { "inputs" }
+        ),
+    }
+}
+
+/// Stores the crash as a finding, unless it's a duplicate of an
+/// existing one, and adds it to the findings of the run. The status of
+/// the returned finding is the one of the existing finding, or ignored
+/// if it matches an ignore rule of the cifuzz.yaml. New findings which
+/// aren't ignored are announced to the webhook of the cifuzz.yaml.
+fn save_finding(
+    report: &CrashReport,
+    project_dir: &Path,
+    package_dir: &Path,
+    metadata: Metadata,
+    project_config: &ProjectConfig,
+    findings: &RefCell<Vec<Finding>>,
+) -> Result<Finding> {
+    let mut finding = Finding::new(report, project_dir, package_dir, metadata)?;
+    let rule = project_config
+        .ignore
+        .iter()
+        .find(|rule| rule.matches(&finding));
+    let (dir, duplicate) = match finding.find_duplicate(project_dir)? {
+        Some(dir) => {
+            log::info!(
+                "The {} is a duplicate of the existing finding in {}",
+                finding.error_type.description(),
+                dir.display()
+            );
+            let mut existing = Finding::load(&dir)?;
+            if existing.status == Status::Fixed {
+                log::error!(
+                    "The finding {} was marked as fixed, but the bug is back. Reopening it",
+                    existing.name
+                );
+                existing.status = Status::Open;
+                existing.save(project_dir)?;
+            }
+            finding.status = existing.status;
+            (dir, true)
+        }
+        None => {
+            // Ignored findings are stored as well, but the regression
+            // tests skip them
+            if rule.is_some() {
+                finding.status = Status::Ignored;
+            }
+            let dir = finding.save(project_dir)?;
+            log::info!("Finding saved in {}", dir.display());
+            if rule.is_none() {
+                add_regression_test(&finding, package_dir)?;
+            }
+            (dir, false)
+        }
+    };
+    if let Some(rule) = rule {
+        finding.status = Status::Ignored;
+        log::info!(
+            "The {} is ignored by the ignore list of the cifuzz.yaml{}",
+            finding.error_type.description(),
+            rule.reason
+                .as_ref()
+                .map(|reason| format!(": {reason}"))
+                .unwrap_or_default()
+        );
+    } else if finding.status == Status::Ignored {
+        log::info!("The finding {} is marked as ignored", finding.name);
+    }
+    events::finding(&finding, &dir, duplicate);
+    metrics::finding();
+    if !duplicate && finding.status != Status::Ignored {
+        notify::new_finding(
+            &project_config.notify,
+            &finding,
+            finding.severity(&project_config.severity),
+            &dir,
+        );
+    }
+    dashboard::finding(&format!(
+        "{}{}: {}",
+        if finding.status == Status::Ignored {
+            "(ignored) "
+        } else {
+            ""
+        },
+        finding.error_type.description(),
+        finding.details
+    ));
+    findings.borrow_mut().push(finding.clone());
+    Ok(finding)
+}
+
+/// Generates a regression test with the input of the finding next to the
+/// source file defining the fuzz test, see [`regression_test`].
+fn add_regression_test(finding: &Finding, package_dir: &Path) -> Result<()> {
+    let fuzz_test = &finding.metadata.fuzz_test;
+    let sources = corpus::find_fuzz_test_sources(package_dir, fuzz_test)?;
+    let [source] = sources.as_slice() else {
+        log::debug!("The source file of {fuzz_test} is ambiguous, not adding a regression test");
+        return Ok(());
+    };
+    match regression_test::add(finding, source)? {
+        Some((file, true)) => log::info!(
+            "Regression test saved in {}, include it next to the fuzz test with\n{}",
+            file.display(),
+            regression_test::module_declaration(fuzz_test)
+        ),
+        Some((file, false)) => log::info!("Regression test added to {}", file.display()),
+        None => {}
+    }
+    Ok(())
+}
+
+/// Returns the sanitizer to build with, checking that the flags are
+/// supported by the engine. The --sanitizer flag takes precedence over
+/// the `configured` sanitizer of the fuzz test in the cifuzz.yaml, which
+/// is only used with libFuzzer.
+fn sanitizer(args: &RunArgs, configured: Option<Sanitizer>) -> Result<Option<Sanitizer>> {
+    let selected = args
+        .sanitizer
+        .or(configured.filter(|_| args.engine == Engine::Libfuzzer));
+    let sanitizer = match selected {
+        None if args.detect_leaks => Some(Sanitizer::Leak),
+        Some(sanitizer) if args.detect_leaks && !sanitizer.detects_leaks() => bail!(
+            "--detect-leaks can't be used with the {} sanitizer",
+            sanitizer.name()
+        ),
+        // libFuzzer finds the focus function with the symbolizer of the
+        // sanitizer runtime
+        None if args.focus_function.is_some() && args.engine == Engine::Libfuzzer => {
+            Some(Sanitizer::Address)
+        }
+        sanitizer => sanitizer,
+    };
+    if args.engine != Engine::Libfuzzer {
+        let unsupported = [
+            (args.sanitizer.is_some(), "--sanitizer"),
+            (args.detect_leaks, "--detect-leaks"),
+            (args.focus_function.is_some(), "--focus-function"),
+            (args.resume && args.engine != Engine::Wasm, "--resume"),
+            (args.tui && args.engine != Engine::Wasm, "--tui"),
+            (
+                args.jobs > 1 && matches!(args.engine, Engine::Afl | Engine::Miri),
+                "--jobs",
+            ),
+            (args.use_value_profile, "--use-value-profile"),
+            (args.seed.is_some() && args.engine != Engine::Wasm, "--seed"),
+            (args.slow_inputs.is_some(), "--slow-inputs"),
+            (args.exit_on_plateau.is_some(), "--exit-on-plateau"),
+            (
+                args.timeout.is_some() && args.engine == Engine::Miri,
+                "--timeout",
+            ),
+            (
+                args.input_timeout.is_some() && args.engine == Engine::Miri,
+                "--input-timeout",
+            ),
+            (
+                args.rss_limit_mb.is_some() && args.engine == Engine::Miri,
+                "--rss-limit-mb",
+            ),
+        ];
+        if let Some((_, flag)) = unsupported.iter().find(|(used, _)| *used) {
+            bail!(
+                "{flag} can't be used with the {} engine",
+                args.engine.name()
+            );
+        }
+    }
+    Ok(sanitizer)
+}
+
+fn builder(args: &RunArgs, project_dir: PathBuf, sanitizer: Option<Sanitizer>) -> Builder {
+    Builder::new(BuilderOptions {
+        project_dir,
+        sanitizer,
+        mode: match args.engine {
+            Engine::Libfuzzer => BuildMode::Fuzzing,
+            Engine::Afl => BuildMode::Afl,
+            Engine::Honggfuzz => BuildMode::Honggfuzz,
+            Engine::Wasm => BuildMode::Wasm,
+            // The fuzz test is found in the build for libFuzzer, Miri
+            // builds it on its own
+            Engine::Miri => BuildMode::Fuzzing,
+        },
+        args: cargo_args(args),
+    })
+}
+
+/// Reproduces the crashes found by AFL++ or honggfuzz without the
+/// fuzzer, to get their panic messages and stack traces, and stores them
+/// as findings. The crashing inputs are copied to the artifact directory
+/// with libFuzzer's names. The undefined behavior found by Miri is
+/// reproduced with Miri to get its report.
+fn report_findings(
+    engine: Engine,
+    result: FuzzingResult,
+    artifact_dir: &Path,
+    reproduce: impl Fn(&Path) -> Result<RunResult>,
+    save: impl Fn(&CrashReport) -> Result<Finding>,
+) -> Result<()> {
+    if !result.hangs.is_empty() {
+        for hang in &result.hangs {
+            copy_artifact(hang, artifact_dir, "timeout")?;
+        }
+        log::error!(
+            "{} found {} inputs on which the fuzz test hangs, they are stored in {}",
+            engine.name(),
+            result.hangs.len(),
+            artifact_dir.display()
+        );
+    }
+    if result.crashes.is_empty() {
+        if !result.status.success() {
+            bail!("{} exited with {}", engine.name(), result.status);
+        }
+        return Ok(());
+    }
+
+    let mut findings = Vec::new();
+    for crash in &result.crashes {
+        let kind = if engine == Engine::Miri {
+            "ub"
+        } else {
+            "crash"
+        };
+        let artifact = copy_artifact(crash, artifact_dir, kind)?;
+        let output = reproduce(&artifact)?;
+        let report = match parser::parse_crash(&output.output) {
+            Some(report) if !output.status.success() => report,
+            _ if engine == Engine::Miri => {
+                log::info!(
+                    "The undefined behavior on {} doesn't reproduce",
+                    artifact.display()
+                );
+                continue;
+            }
+            _ => {
+                log::info!(
+                    "The crash on {} doesn't reproduce without {}",
+                    artifact.display(),
+                    engine.name()
+                );
+                continue;
+            }
+        };
+        let report = CrashReport {
+            input_file: Some(artifact),
+            ..report
+        };
+        let finding = save(&report)?;
+        // The fuzzer may store multiple crashes of the same bug
+        if !findings
+            .iter()
+            .any(|f: &Finding| f.dedup_token == finding.dedup_token)
+        {
+            findings.push(finding);
+        }
+    }
+    let ignored = findings
+        .iter()
+        .filter(|f| f.status == Status::Ignored)
+        .count();
+    findings.retain(|f| f.status != Status::Ignored);
+    if ignored > 0 {
+        log::info!(
+            "{} found {} ignored {}",
+            engine.name(),
+            ignored,
+            if ignored == 1 { "bug" } else { "bugs" }
+        );
+        if findings.is_empty() {
+            return Ok(());
+        }
+    }
+    match findings.as_slice() {
+        [] => Err(Failure::Finding.error(anyhow!(
+            "{} found {} crashing inputs which don't reproduce, they are stored in {}",
+            engine.name(),
+            result.crashes.len(),
+            artifact_dir.display()
+        ))),
+        [finding] => Err(Failure::Finding.error(anyhow!(
+            "The fuzz test {} found a {}: {}",
+            finding.metadata.fuzz_test,
+            finding.error_type.description(),
+            finding.details
+        ))),
+        findings => {
+            for finding in findings {
+                log::error!(
+                    "The fuzz test {} found a {}: {}",
+                    finding.metadata.fuzz_test,
+                    finding.error_type.description(),
+                    finding.details
+                );
+            }
+            Err(Failure::Finding.error(anyhow!(
+                "The fuzz test {} found {} bugs",
+                findings[0].metadata.fuzz_test,
+                findings.len()
+            )))
+        }
+    }
+}
+
+/// Copies an input found by AFL++ or honggfuzz into the artifact directory, named
+/// like libFuzzer names its artifacts.
+fn copy_artifact(input: &Path, artifact_dir: &Path, kind: &str) -> Result<PathBuf> {
+    let data =
+        std::fs::read(input).with_context(|| format!("failed to read {}", input.display()))?;
+    std::fs::create_dir_all(artifact_dir)
+        .with_context(|| format!("failed to create {}", artifact_dir.display()))?;
+    let path = artifact_dir.join(format!("{kind}-{}", sha1_smol::Sha1::from(&data).digest()));
+    std::fs::write(&path, &data).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
@@ -0,0 +1,299 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use clap::Args;
+
+use crate::affected;
+use crate::campaign;
+use crate::cmd::findings::{format_age, load_findings};
+use crate::config;
+use crate::corpus;
+use crate::finding::{Finding, Status};
+use crate::lcov::Counter;
+use crate::log;
+
+/// Show the state of the fuzz tests of the project
+///
+/// This command prints an overview of every fuzz test which was fuzzed
+/// or configured in the project, from what the other commands stored in
+/// the project directory, without building anything:
+///
+///     FUZZ TEST     INPUTS  SIZE    EDGES  LINES          OPEN FINDINGS  LAST RUN
+///     my_fuzz_test  21      1.4KiB  64     73/80 (91.2%)  1              2h ago
+///
+/// INPUTS and SIZE are those of the generated corpus in
+/// `.cifuzz-corpus/<FUZZ_TEST>`. EDGES is the coverage libFuzzer reported
+/// at the end of the last `cargo cifuzz run`, LINES the line coverage of
+/// the last `cargo cifuzz coverage`. OPEN FINDINGS are the findings which
+/// aren't marked as fixed or ignored, see `cargo cifuzz findings list`.
+/// LAST RUN is when the last run of the fuzz test ended, with
+/// "(interrupted)" if it can be continued with `cargo cifuzz run
+/// --resume`.
+///
+/// Fuzz tests which were never run aren't shown, unless they have
+/// settings in the fuzz-tests map of the cifuzz.yaml. `cargo cifuzz
+/// list` lists all fuzz tests of the workspace.
+#[derive(Debug, Args)]
+#[command(verbatim_doc_comment)]
+pub struct StatusArgs {
+    /// Only show the state of this fuzz test
+    fuzz_test: Option<String>,
+
+    /// The project root which is the parent for all the project
+    /// sources. Defaults to the directory containing the cifuzz.yaml.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
+
+/// The state of a fuzz test.
+#[derive(Debug, PartialEq)]
+struct FuzzTestStatus {
+    name: String,
+    /// The number of inputs in the generated corpus and their total size
+    inputs: usize,
+    size: u64,
+    /// The edges covered at the end of the last run
+    edges: Option<u64>,
+    /// The lines covered in the last coverage report
+    lines: Option<Counter>,
+    open_findings: usize,
+    /// How long ago the last run ended, and whether it was interrupted
+    last_run: Option<(Duration, bool)>,
+}
+
+pub fn run(args: StatusArgs) -> Result<()> {
+    let project_dir = config::project_dir(args.project_dir.as_deref())?;
+    let project_config = config::parse_project_config(&project_dir)?;
+    let mut names = fuzz_test_names(&project_dir)?;
+    names.extend(project_config.fuzz_tests.keys().cloned());
+    if let Some(fuzz_test) = &args.fuzz_test {
+        let fuzz_test = fuzz_test.rsplit("::").next().unwrap_or(fuzz_test);
+        names.retain(|name| name == fuzz_test);
+    }
+    if names.is_empty() {
+        log::info!("No fuzz test was run yet, `cargo cifuzz list` lists the fuzz tests");
+        return Ok(());
+    }
+
+    let findings = load_findings(&project_dir, None)?;
+    let mut rows = Vec::new();
+    for name in names {
+        let status = status(&project_dir, &name, &findings)?;
+        rows.push([
+            status.name,
+            status.inputs.to_string(),
+            format_size(status.size),
+            status
+                .edges
+                .map_or("-".to_string(), |edges| edges.to_string()),
+            status
+                .lines
+                .map_or("-".to_string(), |lines| lines.to_string()),
+            status.open_findings.to_string(),
+            match status.last_run {
+                None => "never".to_string(),
+                Some((age, false)) => format!("{} ago", format_age(age)),
+                Some((age, true)) => format!("{} ago (interrupted)", format_age(age)),
+            },
+        ]);
+    }
+
+    let header = [
+        "FUZZ TEST",
+        "INPUTS",
+        "SIZE",
+        "EDGES",
+        "LINES",
+        "OPEN FINDINGS",
+        "LAST RUN",
+    ];
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let format_row = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    println!("{}", format_row(header.to_vec()));
+    for row in &rows {
+        println!("{}", format_row(row.iter().map(String::as_str).collect()));
+    }
+    Ok(())
+}
+
+/// Returns the names of the fuzz tests which have a generated corpus,
+/// findings, a coverage report or a campaign.
+fn fuzz_test_names(project_dir: &Path) -> Result<BTreeSet<String>> {
+    let mut names = BTreeSet::new();
+    let dirs = [
+        project_dir.join(".cifuzz-corpus"),
+        project_dir.join(".cifuzz").join("findings"),
+        project_dir.join(".cifuzz-coverage"),
+    ];
+    for dir in &dirs {
+        for path in entries(dir)? {
+            if path.is_dir() {
+                names.extend(file_name(&path));
+            }
+        }
+    }
+    let campaigns = project_dir.join(".cifuzz").join("campaigns");
+    for path in entries(&campaigns)? {
+        if path.extension().is_some_and(|ext| ext == "json") {
+            names.extend(
+                path.file_stem()
+                    .map(|name| name.to_string_lossy().into_owned()),
+            );
+        }
+    }
+    Ok(names)
+}
+
+/// Returns the entries of the directory, none if it doesn't exist.
+fn entries(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    std::fs::read_dir(dir)
+        .with_context(|| format!("failed to read {}", dir.display()))?
+        .map(|entry| Ok(entry?.path()))
+        .collect()
+}
+
+fn file_name(path: &Path) -> Option<String> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+fn status(project_dir: &Path, name: &str, findings: &[Finding]) -> Result<FuzzTestStatus> {
+    let corpus_dir = project_dir.join(".cifuzz-corpus").join(name);
+    let inputs = if corpus_dir.is_dir() {
+        corpus::list_inputs(&[corpus_dir])?
+    } else {
+        Vec::new()
+    };
+    let size = inputs
+        .iter()
+        .map(|input| input.metadata().map_or(0, |m| m.len()))
+        .sum();
+
+    let campaign_file = campaign::file(project_dir, name);
+    let campaign = campaign::load(&campaign_file)?;
+    // The campaign file is saved when the run ends
+    let last_run = campaign.as_ref().and_then(|state| {
+        let modified = campaign_file.metadata().ok()?.modified().ok()?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        Some((age, !state.finished))
+    });
+
+    let lines = affected::coverage_report(project_dir, name)?.map(|report| report.summary().lines);
+    let open_findings = findings
+        .iter()
+        .filter(|finding| finding.metadata.fuzz_test == name && finding.status == Status::Open)
+        .count();
+
+    Ok(FuzzTestStatus {
+        name: name.to_string(),
+        inputs: inputs.len(),
+        size,
+        edges: campaign.map(|state| state.edges),
+        lines,
+        open_findings,
+        last_run,
+    })
+}
+
+/// Formats a size in bytes with a binary unit, e.g. "1.4KiB".
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes}B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1}{}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzz_test_status() {
+        let project = tempfile::tempdir().unwrap();
+        let project_dir = project.path();
+        let corpus_dir = project_dir.join(".cifuzz-corpus").join("my_fuzz_test");
+        std::fs::create_dir_all(&corpus_dir).unwrap();
+        std::fs::write(corpus_dir.join("a"), "abc").unwrap();
+        std::fs::write(corpus_dir.join("b"), "12345").unwrap();
+        let coverage_dir = project_dir.join(".cifuzz-coverage").join("my_fuzz_test");
+        std::fs::create_dir_all(&coverage_dir).unwrap();
+        std::fs::write(
+            coverage_dir.join("coverage.lcov"),
+            "SF:src/lib.rs\nDA:1,3\nDA:2,0\nDA:3,1\nend_of_record\n",
+        )
+        .unwrap();
+        let campaigns_dir = project_dir.join(".cifuzz").join("campaigns");
+        std::fs::create_dir_all(&campaigns_dir).unwrap();
+        let state = campaign::State {
+            edges: 64,
+            finished: true,
+            ..campaign::State::new("other_fuzz_test", None)
+        };
+        std::fs::write(
+            campaigns_dir.join("other_fuzz_test.json"),
+            serde_json::to_string(&state).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            fuzz_test_names(project_dir).unwrap(),
+            BTreeSet::from(["my_fuzz_test".to_string(), "other_fuzz_test".to_string()])
+        );
+        assert_eq!(
+            status(project_dir, "my_fuzz_test", &[]).unwrap(),
+            FuzzTestStatus {
+                name: "my_fuzz_test".to_string(),
+                inputs: 2,
+                size: 8,
+                edges: None,
+                lines: Some(Counter {
+                    covered: 2,
+                    total: 3
+                }),
+                open_findings: 0,
+                last_run: None,
+            }
+        );
+        let other = status(project_dir, "other_fuzz_test", &[]).unwrap();
+        assert_eq!(other.edges, Some(64));
+        assert!(other
+            .last_run
+            .is_some_and(|(age, interrupted)| { age < Duration::from_secs(60) && !interrupted }));
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(format_size(0), "0B");
+        assert_eq!(format_size(1023), "1023B");
+        assert_eq!(format_size(1434), "1.4KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0MiB");
+    }
+}
@@ -0,0 +1,816 @@
+//! The project configuration, which is read from the same cifuzz.yaml
+//! file as the one used by the cifuzz CLI.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+use crate::build::Sanitizer;
+use crate::finding::{Finding, Severity};
+
+pub const PROJECT_CONFIG_FILE: &str = "cifuzz.yaml";
+
+const PROJECT_CONFIG_TEMPLATE: &str = include_str!("cifuzz.yaml.tmpl");
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ProjectConfig {
+    /// Maximum time to run fuzz tests
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub timeout: Option<Duration>,
+    /// Dictionary file passed to the fuzzer, relative to the project
+    /// directory
+    #[serde(default)]
+    pub dict: Option<PathBuf>,
+    /// Additional arguments for the fuzzing engine
+    #[serde(default, alias = "engine_args")]
+    pub engine_args: EngineArgs,
+    /// Known findings which don't fail the fuzzing runs
+    #[serde(default)]
+    pub ignore: Vec<IgnoreRule>,
+    /// Rules which override the severity of findings, see
+    /// [`crate::severity`]
+    #[serde(default)]
+    pub severity: Vec<SeverityRule>,
+    /// The crates the coverage instrumentation is applied to, all crates
+    /// if unset
+    #[serde(default)]
+    pub instrument: Option<Instrument>,
+    /// The remote corpus which the runs pull inputs from and push the
+    /// inputs they generate to, see [`crate::sync`]
+    #[serde(default)]
+    pub corpus_remote: Option<String>,
+    /// Where new findings are announced, see [`crate::notify`]
+    #[serde(default)]
+    pub notify: Notify,
+    /// How many findings and artifacts `cargo cifuzz gc` keeps
+    #[serde(default)]
+    pub retention: Retention,
+    /// The feature combinations the fuzz tests are run with
+    #[serde(default, alias = "feature_matrix")]
+    pub feature_matrix: Vec<FeatureSet>,
+    /// Settings of single fuzz tests, by their name, which take
+    /// precedence over the ones above
+    #[serde(default, alias = "fuzz_tests")]
+    pub fuzz_tests: BTreeMap<String, FuzzTestConfig>,
+}
+
+impl ProjectConfig {
+    /// Returns the settings of the fuzz test, given by its name or (a
+    /// suffix of) its module path.
+    pub fn fuzz_test(&self, fuzz_test: &str) -> &FuzzTestConfig {
+        static DEFAULT: FuzzTestConfig = FuzzTestConfig {
+            timeout: None,
+            max_len: None,
+            dict: None,
+            engine_args: Vec::new(),
+            sanitizer: None,
+            feature_matrix: None,
+        };
+        let name = fuzz_test.rsplit("::").next().unwrap_or(fuzz_test);
+        self.fuzz_tests.get(name).unwrap_or(&DEFAULT)
+    }
+
+    /// Returns the feature combinations the fuzz test is run with, which
+    /// are the ones of its settings if it has any.
+    pub fn feature_matrix(&self, fuzz_test: &str) -> &[FeatureSet] {
+        self.fuzz_test(fuzz_test)
+            .feature_matrix
+            .as_deref()
+            .unwrap_or(&self.feature_matrix)
+    }
+}
+
+/// The settings of a fuzz test in the `fuzz-tests` map of the project
+/// config:
+///
+/// ```yaml
+/// fuzz-tests:
+///   parse_fuzz_test:
+///     max-len: 65536
+///     dict: fuzz/parser.dict
+///   handshake_fuzz_test:
+///     timeout: 2h
+///     sanitizer: address
+///     engine-args: [-use_value_profile=1]
+/// ```
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct FuzzTestConfig {
+    /// Maximum time to run the fuzz test
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub timeout: Option<Duration>,
+    /// Maximum length of the inputs libFuzzer generates
+    #[serde(default, alias = "max_len")]
+    pub max_len: Option<u64>,
+    /// Dictionary file passed to the fuzzer instead of the one of the
+    /// project, relative to the project directory
+    #[serde(default)]
+    pub dict: Option<PathBuf>,
+    /// Additional arguments for the fuzzing engine, after the
+    /// `engine-args` of the project
+    #[serde(default, alias = "engine_args")]
+    pub engine_args: Vec<String>,
+    /// The sanitizer the fuzz test is built with for libFuzzer
+    #[serde(default)]
+    pub sanitizer: Option<Sanitizer>,
+    /// The feature combinations the fuzz test is run with instead of
+    /// the ones of the project
+    #[serde(default, alias = "feature_matrix")]
+    pub feature_matrix: Option<Vec<FeatureSet>>,
+}
+
+/// An entry of the `feature-matrix` of the project config, the cargo
+/// features of one build of the fuzz tests, either as a list of features
+/// on top of the default ones or with the default features disabled:
+///
+/// ```yaml
+/// feature-matrix:
+///   - []
+///   - [json, xml]
+///   - features: [xml]
+///     no-default-features: true
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(from = "RawFeatureSet")]
+pub struct FeatureSet {
+    pub features: Vec<String>,
+    pub no_default_features: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawFeatureSet {
+    Features(Vec<String>),
+    Set {
+        #[serde(default)]
+        features: Vec<String>,
+        #[serde(default, rename = "no-default-features", alias = "no_default_features")]
+        no_default_features: bool,
+    },
+}
+
+impl From<RawFeatureSet> for FeatureSet {
+    fn from(raw: RawFeatureSet) -> Self {
+        match raw {
+            RawFeatureSet::Features(features) => FeatureSet {
+                features,
+                no_default_features: false,
+            },
+            RawFeatureSet::Set {
+                features,
+                no_default_features,
+            } => FeatureSet {
+                features,
+                no_default_features,
+            },
+        }
+    }
+}
+
+impl FeatureSet {
+    /// The arguments of `cargo test` which select the features.
+    pub fn cargo_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if !self.features.is_empty() {
+            args.push("--features".to_string());
+            args.push(self.features.join(","));
+        }
+        if self.no_default_features {
+            args.push("--no-default-features".to_string());
+        }
+        args
+    }
+}
+
+impl std::fmt::Display for FeatureSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match (self.features.as_slice(), self.no_default_features) {
+            ([], false) => write!(f, "the default features"),
+            ([], true) => write!(f, "no features"),
+            (features, false) => write!(f, "the features {}", features.join(", ")),
+            (features, true) => write!(
+                f,
+                "the features {} without the default ones",
+                features.join(", ")
+            ),
+        }
+    }
+}
+
+/// The `notify` setting of the project config:
+///
+/// ```yaml
+/// notify:
+///   webhook: https://hooks.slack.com/services/T000/B000/XXXX
+/// ```
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Notify {
+    /// The URL to which a JSON payload is posted for every new finding
+    #[serde(default)]
+    pub webhook: Option<String>,
+}
+
+/// The `retention` setting of the project config, which limits the
+/// findings and artifacts `cargo cifuzz gc` keeps:
+///
+/// ```yaml
+/// retention:
+///   max-findings-per-token: 3
+///   max-artifact-age: 7d
+///   max-artifacts-size: 1GiB
+/// ```
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Retention {
+    /// The number of findings with the same dedup token which are kept,
+    /// the oldest ones, over all fuzz tests
+    #[serde(default)]
+    pub max_findings_per_token: Option<usize>,
+    /// The age after which the artifacts of libFuzzer are removed
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub max_artifact_age: Option<Duration>,
+    /// The size of all artifacts above which the oldest ones are removed
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub max_artifacts_size: Option<u64>,
+}
+
+/// An entry of the `ignore` list of the project config, which matches
+/// findings by their dedup token or by their panic message:
+///
+/// ```yaml
+/// ignore:
+///   - dedup-token: 0d09a0cb426d
+///     reason: fixed in the next release of the dependency
+///   - message: "^capacity overflow"
+///     fuzz-test: my_fuzz_test
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct IgnoreRule {
+    /// The dedup token of the findings, or a prefix of it
+    #[serde(default)]
+    pub dedup_token: Option<String>,
+    /// A regular expression which matches the details of the findings,
+    /// e.g. the panic message
+    #[serde(default, deserialize_with = "deserialize_regex")]
+    pub message: Option<Regex>,
+    /// Only ignore the findings of this fuzz test
+    #[serde(default)]
+    pub fuzz_test: Option<String>,
+    /// Why the findings are ignored, which is printed when they're found
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+impl IgnoreRule {
+    pub fn matches(&self, finding: &Finding) -> bool {
+        self.fuzz_test
+            .as_ref()
+            .is_none_or(|fuzz_test| *fuzz_test == finding.metadata.fuzz_test)
+            && self
+                .dedup_token
+                .as_ref()
+                .is_none_or(|token| finding.dedup_token.starts_with(token.as_str()))
+            && self
+                .message
+                .as_ref()
+                .is_none_or(|message| message.is_match(&finding.details))
+    }
+}
+
+/// An entry of the `severity` rules of the project config, which sets
+/// the severity of the findings it matches. The first matching rule
+/// applies:
+///
+/// ```yaml
+/// severity:
+///   - kind: assertion
+///     severity: medium
+///   - message: "^invariant violated"
+///     severity: high
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct SeverityRule {
+    /// The kind of bug of the findings, e.g. `heap-buffer-overflow` or
+    /// `panic`, see [`crate::severity::kind`]
+    #[serde(default)]
+    pub kind: Option<String>,
+    /// A regular expression which matches the details of the findings,
+    /// e.g. the panic message
+    #[serde(default, deserialize_with = "deserialize_regex")]
+    pub message: Option<Regex>,
+    /// Only apply to the findings of this fuzz test
+    #[serde(default)]
+    pub fuzz_test: Option<String>,
+    pub severity: Severity,
+}
+
+impl SeverityRule {
+    /// Whether the rule applies to the finding of the given kind.
+    pub fn matches(&self, finding: &Finding, kind: &str) -> bool {
+        self.fuzz_test
+            .as_ref()
+            .is_none_or(|fuzz_test| *fuzz_test == finding.metadata.fuzz_test)
+            && self.kind.as_ref().is_none_or(|k| k == kind)
+            && self
+                .message
+                .as_ref()
+                .is_none_or(|message| message.is_match(&finding.details))
+    }
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> Result<Option<Regex>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(s) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    Regex::new(&s)
+        .map(Some)
+        .map_err(|err| serde::de::Error::custom(format!("invalid regular expression: {err}")))
+}
+
+/// The `engine-args` of the project config, either a list of arguments
+/// for all fuzz tests or lists of arguments per fuzz test:
+///
+/// ```yaml
+/// engine-args:
+///   my_fuzz_test:
+///     - -max_len=4096
+/// ```
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum EngineArgs {
+    All(Vec<String>),
+    PerFuzzTest(BTreeMap<String, Vec<String>>),
+}
+
+impl Default for EngineArgs {
+    fn default() -> Self {
+        EngineArgs::All(Vec::new())
+    }
+}
+
+impl EngineArgs {
+    /// Returns the arguments for the fuzz test with the given name.
+    pub fn for_fuzz_test(&self, fuzz_test: &str) -> &[String] {
+        match self {
+            EngineArgs::All(args) => args,
+            EngineArgs::PerFuzzTest(args) => args.get(fuzz_test).map_or(&[], Vec::as_slice),
+        }
+    }
+}
+
+/// The `instrument` setting of the project config, which selects the
+/// crates the coverage instrumentation is applied to, either as a list
+/// of crates to instrument or with crates to exclude. "workspace" stands
+/// for the members of the workspace:
+///
+/// ```yaml
+/// instrument: [workspace]
+///
+/// instrument:
+///   include: [workspace, my_dependency]
+///   exclude: [generated_bindings]
+/// ```
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Instrument {
+    Include(Vec<String>),
+    Filter {
+        /// All crates if unset
+        #[serde(default)]
+        include: Option<Vec<String>>,
+        #[serde(default)]
+        exclude: Vec<String>,
+    },
+}
+
+/// Parses a duration like "30m" or "1h30m". Like in the cifuzz CLI, a
+/// unit is required.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    humantime::parse_duration(s).with_context(|| format!("invalid duration {s:?}"))
+}
+
+fn deserialize_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    // Accept numbers as well, to report them as durations without a
+    // unit instead of as values of the wrong type
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawDuration {
+        String(String),
+        Number(u64),
+    }
+
+    let s = match Option::<RawDuration>::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(RawDuration::String(s)) => s,
+        Some(RawDuration::Number(n)) => n.to_string(),
+    };
+    parse_duration(&s)
+        .map(Some)
+        .map_err(|err| serde::de::Error::custom(format!("{err:#}")))
+}
+
+/// Parses a size like "500MB" or "2GiB". Like for durations, a unit is
+/// required.
+pub fn parse_size(s: &str) -> Result<u64> {
+    const UNITS: [(&str, u64); 9] = [
+        ("B", 1),
+        ("KB", 1000),
+        ("MB", 1000 * 1000),
+        ("GB", 1000 * 1000 * 1000),
+        ("TB", 1000 * 1000 * 1000 * 1000),
+        ("KiB", 1 << 10),
+        ("MiB", 1 << 20),
+        ("GiB", 1 << 30),
+        ("TiB", 1 << 40),
+    ];
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let multiplier = UNITS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(unit.trim()))
+        .map(|(_, multiplier)| *multiplier);
+    match (number.parse::<u64>(), multiplier) {
+        (Ok(number), Some(multiplier)) => number
+            .checked_mul(multiplier)
+            .with_context(|| format!("size {s:?} is too large")),
+        _ => bail!("invalid size {s:?}, expected a number and a unit like 500MB or 2GiB"),
+    }
+}
+
+fn deserialize_size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    // Like durations, numbers are reported as sizes without a unit
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawSize {
+        String(String),
+        Number(u64),
+    }
+
+    let s = match Option::<RawSize>::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(RawSize::String(s)) => s,
+        Some(RawSize::Number(n)) => n.to_string(),
+    };
+    parse_size(&s)
+        .map(Some)
+        .map_err(|err| serde::de::Error::custom(format!("{err:#}")))
+}
+
+/// Returns the first directory containing a cifuzz.yaml, starting at
+/// `dir` and walking up to the root directory.
+pub fn find_config_dir(dir: &Path) -> Result<PathBuf> {
+    for dir in dir.ancestors() {
+        if dir.join(PROJECT_CONFIG_FILE).is_file() {
+            return Ok(dir.to_path_buf());
+        }
+    }
+    bail!(
+        "not a cifuzz project (or any of the parent directories): {}",
+        PROJECT_CONFIG_FILE
+    )
+}
+
+pub fn parse_project_config(config_dir: &Path) -> Result<ProjectConfig> {
+    let path = config_dir.join(PROJECT_CONFIG_FILE);
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    // A config which only contains comments is an empty document,
+    // which is parsed as None
+    let config: Option<ProjectConfig> = serde_yaml::from_str(&content)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    let config = config.unwrap_or_default();
+    // A rule without a dedup token or a message would ignore all findings
+    if let Some(i) = config
+        .ignore
+        .iter()
+        .position(|rule| rule.dedup_token.is_none() && rule.message.is_none())
+    {
+        bail!(
+            "invalid ignore rule {} in {}: it needs a dedup-token or a message",
+            i + 1,
+            path.display()
+        );
+    }
+    Ok(config)
+}
+
+/// Creates a cifuzz.yaml from the template in the given directory and
+/// returns its path. Fails if the config already exists.
+pub fn create_project_config(config_dir: &Path) -> Result<PathBuf> {
+    let path = config_dir.join(PROJECT_CONFIG_FILE);
+    let last_updated = humantime::format_rfc3339(std::time::SystemTime::now()).to_string();
+    let content = PROJECT_CONFIG_TEMPLATE.replace("{last_updated}", &last_updated[..10]);
+
+    let mut file = match std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+    {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+            bail!("Config already exists in {}", path.display())
+        }
+        Err(err) => {
+            return Err(err).with_context(|| format!("failed to create {}", path.display()))
+        }
+    };
+    file.write_all(content.as_bytes())
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Determines the project directory: The given directory if set,
+/// otherwise the directory containing the cifuzz.yaml.
+pub fn project_dir(project_dir: Option<&Path>) -> Result<PathBuf> {
+    let dir = match project_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => find_config_dir(&std::env::current_dir()?)?,
+    };
+    // Make the path absolute, it's passed to processes which run in
+    // other working directories
+    std::path::absolute(&dir)
+        .with_context(|| format!("invalid project directory {}", dir.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::finding::test_finding;
+
+    #[test]
+    fn parse_commented_config() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(PROJECT_CONFIG_FILE),
+            "## comment\n#timeout: 30m\n",
+        )
+        .unwrap();
+        let config = parse_project_config(dir.path()).unwrap();
+        assert_eq!(config.timeout, None);
+    }
+
+    #[test]
+    fn parse_notify() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(PROJECT_CONFIG_FILE),
+            "notify:\n  webhook: https://hooks.example.com/cifuzz\n",
+        )
+        .unwrap();
+        let config = parse_project_config(dir.path()).unwrap();
+        assert_eq!(
+            config.notify.webhook.as_deref(),
+            Some("https://hooks.example.com/cifuzz")
+        );
+    }
+
+    #[test]
+    fn parse_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "timeout: 1h30m\ndict: fuzz.dict\nunknown-key: true\n",
+        )
+        .unwrap();
+        let config = parse_project_config(dir.path()).unwrap();
+        assert_eq!(config.timeout, Some(Duration::from_secs(5400)));
+        assert_eq!(config.dict, Some(PathBuf::from("fuzz.dict")));
+
+        // The unit is required
+        std::fs::write(&path, "timeout: 30\n").unwrap();
+        let err = parse_project_config(dir.path()).unwrap_err();
+        assert!(format!("{err:#}").contains("invalid duration"), "{err:#}");
+    }
+
+    #[test]
+    fn parse_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "retention:\n  max-findings-per-token: 3\n  max-artifact-age: 7d\n  \
+             max-artifacts-size: 2GiB\n",
+        )
+        .unwrap();
+        let config = parse_project_config(dir.path()).unwrap();
+        assert_eq!(
+            config.retention,
+            Retention {
+                max_findings_per_token: Some(3),
+                max_artifact_age: Some(Duration::from_secs(7 * 86400)),
+                max_artifacts_size: Some(2 << 30),
+            }
+        );
+
+        assert_eq!(parse_size("500MB").unwrap(), 500_000_000);
+        assert_eq!(parse_size("1 kib").unwrap(), 1024);
+        // The unit is required
+        std::fs::write(&path, "retention:\n  max-artifacts-size: 1024\n").unwrap();
+        let err = parse_project_config(dir.path()).unwrap_err();
+        assert!(format!("{err:#}").contains("invalid size"), "{err:#}");
+    }
+
+    #[test]
+    fn parse_engine_args() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG_FILE);
+        std::fs::write(&path, "engine-args:\n  - -seed=1\n").unwrap();
+        let config = parse_project_config(dir.path()).unwrap();
+        assert_eq!(config.engine_args.for_fuzz_test("any"), ["-seed=1"]);
+
+        std::fs::write(
+            &path,
+            "engine-args:\n  my_fuzz_test:\n    - -max_len=4096\n    - -seed=1\n",
+        )
+        .unwrap();
+        let config = parse_project_config(dir.path()).unwrap();
+        assert_eq!(
+            config.engine_args.for_fuzz_test("my_fuzz_test"),
+            ["-max_len=4096", "-seed=1"]
+        );
+        assert!(config.engine_args.for_fuzz_test("other").is_empty());
+    }
+
+    #[test]
+    fn parse_fuzz_tests() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "timeout: 10m\nfuzz-tests:\n  parse_fuzz_test:\n    max-len: 65536\n    \
+             dict: parser.dict\n  handshake_fuzz_test:\n    timeout: 2h\n    \
+             sanitizer: address\n    engine-args: [-use_value_profile=1]\n",
+        )
+        .unwrap();
+        let config = parse_project_config(dir.path()).unwrap();
+        assert_eq!(
+            *config.fuzz_test("parse_fuzz_test"),
+            FuzzTestConfig {
+                max_len: Some(65536),
+                dict: Some(PathBuf::from("parser.dict")),
+                ..FuzzTestConfig::default()
+            }
+        );
+        let handshake = config.fuzz_test("net::tests::handshake_fuzz_test");
+        assert_eq!(handshake.timeout, Some(Duration::from_secs(7200)));
+        assert_eq!(handshake.sanitizer, Some(Sanitizer::Address));
+        assert_eq!(handshake.engine_args, ["-use_value_profile=1"]);
+        assert_eq!(*config.fuzz_test("other"), FuzzTestConfig::default());
+
+        // Typos in the settings of a fuzz test are reported
+        std::fs::write(&path, "fuzz-tests:\n  my_fuzz_test:\n    timeot: 1h\n").unwrap();
+        let err = parse_project_config(dir.path()).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("unknown field `timeot`"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn parse_feature_matrix() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "feature-matrix:\n  - []\n  - [json, xml]\n  - features: [xml]\n    \
+             no-default-features: true\nfuzz-tests:\n  json_fuzz_test:\n    \
+             feature-matrix: [[json]]\n",
+        )
+        .unwrap();
+        let config = parse_project_config(dir.path()).unwrap();
+        let matrix = config.feature_matrix("my_fuzz_test");
+        assert_eq!(matrix.len(), 3);
+        assert_eq!(matrix[0], FeatureSet::default());
+        assert_eq!(matrix[1].cargo_args(), ["--features", "json,xml"]);
+        assert_eq!(
+            matrix[2].cargo_args(),
+            ["--features", "xml", "--no-default-features"]
+        );
+        assert_eq!(
+            matrix[2].to_string(),
+            "the features xml without the default ones"
+        );
+        assert_eq!(
+            config.feature_matrix("json_fuzz_test"),
+            [FeatureSet {
+                features: vec!["json".to_string()],
+                no_default_features: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_instrument() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG_FILE);
+        std::fs::write(&path, "instrument: [workspace]\n").unwrap();
+        let config = parse_project_config(dir.path()).unwrap();
+        assert_eq!(
+            config.instrument,
+            Some(Instrument::Include(vec!["workspace".to_string()]))
+        );
+
+        std::fs::write(&path, "instrument:\n  exclude: [bindings]\n").unwrap();
+        let config = parse_project_config(dir.path()).unwrap();
+        assert_eq!(
+            config.instrument,
+            Some(Instrument::Filter {
+                include: None,
+                exclude: vec!["bindings".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn parse_ignore_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "ignore:\n  - dedup-token: 0d09a0cb\n    reason: known\n  \
+             - message: \"^capacity overflow\"\n    fuzz-test: my_fuzz_test\n",
+        )
+        .unwrap();
+        let config = parse_project_config(dir.path()).unwrap();
+        assert_eq!(config.ignore.len(), 2);
+        assert_eq!(config.ignore[0].dedup_token.as_deref(), Some("0d09a0cb"));
+        assert_eq!(config.ignore[0].reason.as_deref(), Some("known"));
+        assert!(config.ignore[1].message.is_some());
+
+        std::fs::write(&path, "ignore:\n  - message: \"(\"\n").unwrap();
+        let err = parse_project_config(dir.path()).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("invalid regular expression"),
+            "{err:#}"
+        );
+
+        std::fs::write(&path, "ignore:\n  - reason: everything\n").unwrap();
+        let err = parse_project_config(dir.path()).unwrap_err();
+        assert!(
+            err.to_string().contains("needs a dedup-token or a message"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn match_ignore_rules() {
+        let finding = Finding {
+            details: "capacity overflow".to_string(),
+            dedup_token: "0d09a0cb426d9115".to_string(),
+            ..test_finding("5ed1b771")
+        };
+        let rule = |yaml: &str| serde_yaml::from_str::<IgnoreRule>(yaml).unwrap();
+        assert!(rule("dedup-token: 0d09a0cb").matches(&finding));
+        assert!(!rule("dedup-token: 0d09a0cc").matches(&finding));
+        assert!(rule("message: ^capacity").matches(&finding));
+        assert!(!rule("message: overflow$\nfuzz-test: other").matches(&finding));
+        assert!(!rule("dedup-token: 0d09\nmessage: index").matches(&finding));
+    }
+
+    #[test]
+    fn create_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = create_project_config(dir.path()).unwrap();
+        assert_eq!(path, dir.path().join(PROJECT_CONFIG_FILE));
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("{last_updated}"), "{content}");
+        assert_eq!(parse_project_config(dir.path()).unwrap().timeout, None);
+
+        let err = create_project_config(dir.path()).unwrap_err();
+        assert!(
+            err.to_string().starts_with("Config already exists"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn find_config_dir_in_parent() {
+        let dir = tempfile::tempdir().unwrap();
+        let sub_dir = dir.path().join("src").join("foo");
+        std::fs::create_dir_all(&sub_dir).unwrap();
+        assert!(find_config_dir(&sub_dir).is_err());
+
+        std::fs::write(dir.path().join(PROJECT_CONFIG_FILE), "").unwrap();
+        assert_eq!(find_config_dir(&sub_dir).unwrap(), dir.path());
+    }
+}
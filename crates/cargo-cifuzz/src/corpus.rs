@@ -0,0 +1,381 @@
+//! Corpus directories and their conversion from and to the layout of
+//! AFL++.
+//!
+//! libFuzzer names the inputs it stores after the SHA-1 of their
+//! content. AFL++ stores the inputs of a fuzzer instance in
+//! `<output>/<instance>/queue` with names like
+//! `id:000042,src:000003,time:1234,execs:5678,op:havoc,rep:4,+cov`,
+//! next to its bookkeeping files in hidden directories like `.state`.
+//! Inputs are imported with libFuzzer's names and exported with the
+//! names AFL++ gives the seeds it imports, `id:000000,orig:<name>`, so
+//! that both fuzzers can use the other's inputs as they are.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use syn::punctuated::Punctuated;
+use syn::visit::{self, Visit};
+use syn::{Attribute, ItemFn, Meta, Token};
+
+use crate::log;
+
+/// Returns the files in the directories and their subdirectories,
+/// sorted by size and path like libFuzzer executes them.
+pub fn list_inputs(dirs: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    fn walk(dir: &Path, files: &mut Vec<(u64, PathBuf)>) -> Result<()> {
+        for entry in
+            std::fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?
+        {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                walk(&entry.path(), files)?;
+            } else {
+                files.push((metadata.len(), entry.path()));
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    for dir in dirs.iter().filter(|dir| dir.is_dir()) {
+        walk(dir, &mut files)?;
+    }
+    files.sort();
+    Ok(files.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Returns the queue directories of an AFL++ output directory, which is
+/// either the `-o` directory of afl-fuzz containing the directories of
+/// one or multiple fuzzer instances, the directory of a single instance
+/// or a queue directory itself.
+pub fn afl_queue_dirs(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        bail!("{} is not a directory", dir.display());
+    }
+    if dir.join("queue").is_dir() {
+        return Ok(vec![dir.join("queue")]);
+    }
+    let mut queues: Vec<PathBuf> = read_dir_sorted(dir)?
+        .into_iter()
+        .map(|instance| instance.join("queue"))
+        .filter(|queue| queue.is_dir())
+        .collect();
+    if queues.is_empty() {
+        queues.push(dir.to_path_buf());
+    }
+    Ok(queues)
+}
+
+/// Returns the inputs in an AFL++ queue directory, skipping hidden files
+/// and directories like `.state`.
+pub fn afl_queue_inputs(queue: &Path) -> Result<Vec<PathBuf>> {
+    Ok(read_dir_sorted(queue)?
+        .into_iter()
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            !name.starts_with('.') && path.is_file()
+        })
+        .collect())
+}
+
+/// Returns the SHA-1 of the content of the input, which libFuzzer names
+/// the inputs it generates after.
+pub fn digest(input: &Path) -> Result<String> {
+    let data =
+        std::fs::read(input).with_context(|| format!("failed to read {}", input.display()))?;
+    Ok(sha1_smol::Sha1::from(&data).digest().to_string())
+}
+
+/// Copies the inputs into the directory with libFuzzer's names, skipping
+/// inputs which are already in it. Returns the number of copied inputs.
+pub fn import(inputs: &[PathBuf], dir: &Path) -> Result<usize> {
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let mut imported = 0;
+    for input in inputs {
+        let data =
+            std::fs::read(input).with_context(|| format!("failed to read {}", input.display()))?;
+        let path = dir.join(sha1_smol::Sha1::from(&data).digest().to_string());
+        if path.exists() {
+            continue;
+        }
+        std::fs::write(&path, &data)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        imported += 1;
+    }
+    Ok(imported)
+}
+
+/// Copies the inputs into the directory with the names AFL++ gives to
+/// the seeds it imports, skipping inputs with the same content. The
+/// directory must not contain inputs yet, because AFL++ requires unique
+/// ids. Returns the number of copied inputs.
+pub fn export(inputs: &[PathBuf], dir: &Path) -> Result<usize> {
+    if dir.is_dir() && !afl_queue_inputs(dir)?.is_empty() {
+        bail!("{} already contains inputs", dir.display());
+    }
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let mut hashes = HashSet::new();
+    for input in inputs {
+        let data =
+            std::fs::read(input).with_context(|| format!("failed to read {}", input.display()))?;
+        if !hashes.insert(sha1_smol::Sha1::from(&data).digest().bytes()) {
+            continue;
+        }
+        let orig = input.file_name().unwrap_or_default().to_string_lossy();
+        let path = dir.join(afl_seed_name(hashes.len() - 1, &orig));
+        std::fs::write(&path, &data)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    Ok(hashes.len())
+}
+
+/// The name AFL++ gives to a seed it imports into its queue.
+fn afl_seed_name(id: usize, orig: &str) -> String {
+    // The fields of the name are separated by commas
+    format!("id:{id:06},orig:{}", orig.replace([',', '/'], "_"))
+}
+
+/// Returns the source files in the package which define the fuzz test,
+/// i.e. which contain a function of that name annotated with
+/// `#[fuzz_test]`.
+pub fn find_fuzz_test_sources(package_dir: &Path, fuzz_test: &str) -> Result<Vec<PathBuf>> {
+    fn walk(dir: &Path, fuzz_test: &str, sources: &mut Vec<PathBuf>) -> Result<()> {
+        for path in read_dir_sorted(dir)? {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            if path.is_dir() {
+                if !file_name.starts_with('.') && file_name != "target" {
+                    walk(&path, fuzz_test, sources)?;
+                }
+            } else if file_name.ends_with(".rs") {
+                let source = std::fs::read_to_string(&path)
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+                if defines_fuzz_test(&source, fuzz_test) {
+                    sources.push(path);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    let mut sources = Vec::new();
+    walk(package_dir, fuzz_test, &mut sources)?;
+    Ok(sources)
+}
+
+/// Checks whether a source file in the package sets a custom mutator
+/// for the fuzz test, with `#[fuzz_test(mutator = ...)]`.
+pub fn sets_custom_mutator(package_dir: &Path, fuzz_test: &str) -> Result<bool> {
+    for path in find_fuzz_test_sources(package_dir, fuzz_test)? {
+        let source = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        if has_custom_mutator(&source, fuzz_test) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Checks whether the source contains a function of the name which is
+/// annotated with `#[fuzz_test]`, with or without arguments.
+fn defines_fuzz_test(source: &str, fuzz_test: &str) -> bool {
+    !fuzz_test_attributes(source, fuzz_test).is_empty()
+}
+
+/// Checks whether the `#[fuzz_test]` attribute of the function of the
+/// name has a `mutator` argument, parsed like `cifuzz-macros` does.
+fn has_custom_mutator(source: &str, fuzz_test: &str) -> bool {
+    fuzz_test_attributes(source, fuzz_test).iter().any(|attr| {
+        attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+            .is_ok_and(|args| args.iter().any(|arg| arg.path().is_ident("mutator")))
+    })
+}
+
+/// Returns the `#[fuzz_test]` attributes of the functions of the name,
+/// which may also be in inline modules.
+fn fuzz_test_attributes(source: &str, fuzz_test: &str) -> Vec<Attribute> {
+    struct Finder<'a> {
+        fuzz_test: &'a str,
+        attributes: Vec<Attribute>,
+    }
+
+    impl<'ast> Visit<'ast> for Finder<'_> {
+        fn visit_item_fn(&mut self, function: &'ast ItemFn) {
+            if function.sig.ident == self.fuzz_test {
+                let attributes = function.attrs.iter().filter(|attr| {
+                    attr.path()
+                        .segments
+                        .last()
+                        .is_some_and(|segment| segment.ident == "fuzz_test")
+                });
+                self.attributes.extend(attributes.cloned());
+            }
+            visit::visit_item_fn(self, function);
+        }
+    }
+
+    let file = match syn::parse_file(source) {
+        Ok(file) => file,
+        // The compiler reports the errors, if the file is compiled at
+        // all
+        Err(err) => {
+            log::debug!("Failed to parse source: {err}");
+            return Vec::new();
+        }
+    };
+    let mut finder = Finder {
+        fuzz_test,
+        attributes: Vec::new(),
+    };
+    finder.visit_file(&file);
+    finder.attributes
+}
+
+fn read_dir_sorted(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = std::fs::read_dir(dir)
+        .with_context(|| format!("failed to read {}", dir.display()))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, content: &[u8]) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn inputs_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        write(&dir.path().join("large"), b"abc");
+        write(&dir.path().join("sub").join("small"), b"a");
+        write(&dir.path().join("b"), b"ab");
+        let missing = dir.path().join("missing");
+
+        let inputs = list_inputs(&[dir.path().to_path_buf(), missing]).unwrap();
+        assert_eq!(
+            inputs,
+            [
+                dir.path().join("sub").join("small"),
+                dir.path().join("b"),
+                dir.path().join("large"),
+            ]
+        );
+    }
+
+    #[test]
+    fn afl_output_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        write(
+            &out.join("main").join("queue").join("id:000000,time:0"),
+            b"a",
+        );
+        write(
+            &out.join("main").join("queue").join(".state").join("x"),
+            b"",
+        );
+        write(&out.join("secondary").join("queue").join("id:000000"), b"b");
+        write(
+            &out.join("secondary").join("crashes").join("id:000000"),
+            b"c",
+        );
+
+        let queues = afl_queue_dirs(&out).unwrap();
+        assert_eq!(
+            queues,
+            [
+                out.join("main").join("queue"),
+                out.join("secondary").join("queue")
+            ]
+        );
+        assert_eq!(
+            afl_queue_dirs(&out.join("main")).unwrap(),
+            [out.join("main").join("queue")]
+        );
+        assert_eq!(afl_queue_dirs(&queues[0]).unwrap(), [queues[0].clone()]);
+        assert_eq!(
+            afl_queue_inputs(&queues[0]).unwrap(),
+            [queues[0].join("id:000000,time:0")]
+        );
+    }
+
+    #[test]
+    fn import_and_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = dir.path().join("queue");
+        write(&queue.join("id:000000,orig:seed"), b"FUZZ");
+        write(&queue.join("id:000001,src:000000,op:havoc"), b"FUZZING");
+        write(&queue.join("id:000002,src:000001,op:flip1"), b"FUZZ");
+        let inputs = afl_queue_inputs(&queue).unwrap();
+
+        let corpus = dir.path().join("my_fuzz_test_inputs");
+        assert_eq!(import(&inputs, &corpus).unwrap(), 2);
+        assert_eq!(import(&inputs, &corpus).unwrap(), 0);
+        let sha1 = sha1_smol::Sha1::from("FUZZ").digest().to_string();
+        assert_eq!(std::fs::read(corpus.join(&sha1)).unwrap(), b"FUZZ");
+
+        let exported = dir.path().join("in");
+        let corpus_inputs = list_inputs(&[corpus]).unwrap();
+        assert_eq!(export(&corpus_inputs, &exported).unwrap(), 2);
+        assert_eq!(
+            std::fs::read(exported.join(format!("id:000000,orig:{sha1}"))).unwrap(),
+            b"FUZZ"
+        );
+        assert!(export(&corpus_inputs, &exported).is_err());
+    }
+
+    #[test]
+    fn seed_names() {
+        assert_eq!(afl_seed_name(7, "a,b"), "id:000007,orig:a_b");
+    }
+
+    #[test]
+    fn fuzz_test_definitions() {
+        let source = "use cifuzz::fuzz_test;\n\n\
+                      /// Docs\n#[fuzz_test]\nfn my_fuzz_test(data: &[u8]) {}\n\n\
+                      fn helper() {}\n";
+        assert!(defines_fuzz_test(source, "my_fuzz_test"));
+        assert!(defines_fuzz_test(
+            "#[cifuzz::fuzz_test]\npub fn other(data: &[u8]) {}",
+            "other"
+        ));
+        assert!(defines_fuzz_test(
+            "#[fuzz_test(ignore_panics = \"invalid input\")]\nfn parse(data: &[u8]) {}",
+            "parse"
+        ));
+        assert!(!defines_fuzz_test(source, "helper"));
+        assert!(!defines_fuzz_test(source, "fuzz_test"));
+
+        assert!(has_custom_mutator(
+            "#[fuzz_test(mutator = JsonMutator)]\nfn json(data: &[u8]) {}",
+            "json"
+        ));
+        assert!(!has_custom_mutator(
+            "/// Without a custom mutator\n#[fuzz_test(init = setup)]\nfn json(data: &[u8]) {}",
+            "json"
+        ));
+        assert!(!has_custom_mutator(source, "my_fuzz_test"));
+        assert!(has_custom_mutator(
+            "mod tests {\n\
+             #[fuzz_test(init = || { setup(); }, mutator = json::JsonMutator)]\n\
+             fn json(data: &[u8]) {}\n\
+             }",
+            "json"
+        ));
+        assert!(!has_custom_mutator(
+            "#[fuzz_test(ignore_panics = \"mutator failed\")]\nfn json(data: &[u8]) {}",
+            "json"
+        ));
+        assert!(!has_custom_mutator(
+            "#[fuzz_test(init = init_mutator)]\nfn json(data: &[u8]) {}",
+            "json"
+        ));
+    }
+}
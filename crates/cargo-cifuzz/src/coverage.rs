@@ -0,0 +1,141 @@
+//! Generating coverage reports with the LLVM tools.
+//!
+//! The fuzz test is built with `-Cinstrument-coverage` and replays its
+//! corpus, writing raw profiles which `llvm-profdata` merges. The
+//! reports are then created by `llvm-cov` from the merged profile and
+//! the test executable.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::build::host_target;
+use crate::log;
+
+/// Source files which are not part of the project, i.e. the standard
+/// library and the dependencies.
+const IGNORED_SOURCES: &[&str] = &[
+    r"/rustc/",
+    r"/\.cargo/registry/",
+    r"/\.cargo/git/",
+    r"/\.rustup/",
+    r"/\.cifuzz-build/",
+    r"/\.cifuzz/build-cache/",
+];
+
+/// Returns the path of an LLVM tool. The tools of the llvm-tools rustup
+/// component are preferred, because they match the LLVM version of
+/// rustc, which determines the format of the profiles.
+pub fn llvm_tool(name: &str) -> Result<PathBuf> {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let sysroot = Command::new(&rustc)
+        .args(["--print", "sysroot"])
+        .output()
+        .with_context(|| format!("failed to execute {rustc}"))?;
+    let sysroot = PathBuf::from(String::from_utf8_lossy(&sysroot.stdout).trim());
+    let tool = sysroot
+        .join("lib")
+        .join("rustlib")
+        .join(host_target()?)
+        .join("bin")
+        .join(name);
+    if tool.is_file() {
+        return Ok(tool);
+    }
+
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    if let Some(tool) = std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|tool| tool.is_file())
+    {
+        return Ok(tool);
+    }
+    bail!("{name} not found, install it with `rustup component add llvm-tools`")
+}
+
+/// Merges the raw profiles in the directory into a single profile.
+pub fn merge_profiles(profile_dir: &Path, output: &Path) -> Result<()> {
+    let profiles: Vec<PathBuf> = std::fs::read_dir(profile_dir)
+        .with_context(|| format!("failed to read {}", profile_dir.display()))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|p| p.extension().is_some_and(|ext| ext == "profraw"))
+        .collect();
+    if profiles.is_empty() {
+        bail!("The fuzz test didn't write any coverage profiles");
+    }
+
+    let mut cmd = Command::new(llvm_tool("llvm-profdata")?);
+    cmd.args(["merge", "-sparse"])
+        .args(&profiles)
+        .arg("-o")
+        .arg(output);
+    run(cmd).context(
+        "failed to merge the coverage profiles, llvm-profdata must have the LLVM version of rustc",
+    )?;
+    Ok(())
+}
+
+/// The arguments of `llvm-cov show` and `llvm-cov export` which select
+/// the profile and the sources of the report.
+fn report_args(executable: &Path, profile: &Path) -> Vec<String> {
+    let mut args = vec![
+        format!("-instr-profile={}", profile.display()),
+        executable.display().to_string(),
+    ];
+    args.extend(
+        IGNORED_SOURCES
+            .iter()
+            .map(|regex| format!("-ignore-filename-regex={regex}")),
+    );
+    args
+}
+
+/// Exports the coverage as an LCOV tracefile.
+pub fn export_lcov(executable: &Path, profile: &Path) -> Result<String> {
+    let mut cmd = Command::new(llvm_tool("llvm-cov")?);
+    cmd.args(["export", "-format=lcov"])
+        .args(report_args(executable, profile));
+    run(cmd)
+}
+
+/// Creates an HTML report in the output directory, which shows the
+/// execution counts of all lines and branches.
+pub fn generate_html(executable: &Path, profile: &Path, output_dir: &Path) -> Result<()> {
+    let mut cmd = Command::new(llvm_tool("llvm-cov")?);
+    cmd.args(["show", "-format=html", "-show-branches=count"])
+        .arg(format!("-output-dir={}", output_dir.display()))
+        .args(report_args(executable, profile));
+    run(cmd)?;
+    Ok(())
+}
+
+fn run(mut cmd: Command) -> Result<String> {
+    log::debug!("Command: {:?}", cmd);
+    let output = cmd
+        .output()
+        .with_context(|| format!("failed to execute {:?}", cmd.get_program()))?;
+    if !output.status.success() {
+        bail!(
+            "{} failed: {}",
+            cmd.get_program().to_string_lossy(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_arguments() {
+        let args = report_args(Path::new("/build/foo-123"), Path::new("/cov/foo.profdata"));
+        assert_eq!(args[0], "-instr-profile=/cov/foo.profdata");
+        assert_eq!(args[1], "/build/foo-123");
+        assert!(args.contains(&r"-ignore-filename-regex=/\.cargo/registry/".to_string()));
+    }
+}
@@ -0,0 +1,460 @@
+//! The terminal dashboard of `cargo cifuzz run --tui`, which shows the
+//! progress of the jobs, the coverage over time, the findings and the
+//! slowest inputs instead of the output of libFuzzer.
+//!
+//! Like the events, the state of the dashboard is kept in a global, so
+//! that the runner can feed it the output of the jobs and the log can
+//! show its messages in it instead of writing them between the frames.
+//! A thread draws the dashboard periodically. The log messages are
+//! printed again when the dashboard is closed, so that they remain in
+//! the scrollback of the terminal.
+
+use std::collections::VecDeque;
+use std::io::Stderr;
+use std::sync::{Mutex, Once};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::{cursor, execute, terminal};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Cell, List, ListItem, Paragraph, Row, Sparkline, Table};
+use ratatui::{Frame, Terminal};
+
+use crate::parser::{self, Stats};
+use crate::runner::libfuzzer::combine_stats;
+
+/// How often the dashboard is redrawn.
+const FRAME_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the coverage and the corpus size are sampled for the
+/// sparklines.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The number of samples, log messages, findings and slow inputs kept.
+const MAX_SAMPLES: usize = 500;
+const MAX_MESSAGES: usize = 1000;
+const MAX_FINDINGS: usize = 100;
+const MAX_SLOW_INPUTS: usize = 10;
+
+static DASHBOARD: Mutex<Option<Dashboard>> = Mutex::new(None);
+
+static CTRLC_HANDLER: Once = Once::new();
+
+struct Dashboard {
+    terminal: Terminal<CrosstermBackend<Stderr>>,
+    state: State,
+}
+
+/// What the dashboard shows.
+struct State {
+    fuzz_test: String,
+    start: Instant,
+    /// The latest progress of every job
+    jobs: Vec<Stats>,
+    /// The combined coverage and corpus size over time
+    coverage: VecDeque<u64>,
+    corpus: VecDeque<u64>,
+    last_sample: Instant,
+    messages: Vec<String>,
+    findings: VecDeque<(Duration, String)>,
+    /// The slowest inputs by their execution time in seconds, with the
+    /// job and the file libFuzzer stored them in
+    slow_inputs: Vec<SlowInput>,
+}
+
+struct SlowInput {
+    secs: u64,
+    job: usize,
+    file: Option<String>,
+}
+
+/// Closes the dashboard when it's dropped.
+pub struct Guard(());
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        stop();
+    }
+}
+
+/// Opens the dashboard for the fuzz test run with the number of jobs on
+/// the alternate screen of the terminal.
+pub fn start(fuzz_test: &str, jobs: usize) -> Result<Guard> {
+    let mut stderr = std::io::stderr();
+    execute!(stderr, terminal::EnterAlternateScreen, cursor::Hide)
+        .context("failed to set up the terminal")?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stderr))?;
+    terminal.clear()?;
+    // Ctrl+C kills cargo-cifuzz as before, but the terminal must be
+    // restored first
+    CTRLC_HANDLER.call_once(|| {
+        let _ = ctrlc::set_handler(|| {
+            stop();
+            std::process::exit(130);
+        });
+    });
+    *DASHBOARD.lock().unwrap() = Some(Dashboard {
+        terminal,
+        state: State {
+            fuzz_test: fuzz_test.to_string(),
+            start: Instant::now(),
+            jobs: vec![Stats::default(); jobs],
+            coverage: VecDeque::new(),
+            corpus: VecDeque::new(),
+            last_sample: Instant::now(),
+            messages: Vec::new(),
+            findings: VecDeque::new(),
+            slow_inputs: Vec::new(),
+        },
+    });
+    std::thread::spawn(|| loop {
+        std::thread::sleep(FRAME_INTERVAL);
+        let mut dashboard = DASHBOARD.lock().unwrap();
+        let Some(Dashboard { terminal, state }) = dashboard.as_mut() else {
+            return;
+        };
+        state.sample();
+        let _ = terminal.draw(|frame| state.render(frame));
+    });
+    Ok(Guard(()))
+}
+
+/// Closes the dashboard and prints the messages logged while it was
+/// open.
+fn stop() {
+    let Some(mut dashboard) = DASHBOARD.lock().unwrap().take() else {
+        return;
+    };
+    let _ = execute!(
+        dashboard.terminal.backend_mut(),
+        terminal::LeaveAlternateScreen,
+        cursor::Show
+    );
+    for message in &dashboard.state.messages {
+        eprintln!("{message}");
+    }
+}
+
+/// Whether the dashboard is open, in which case the output of the fuzz
+/// test must not be written to the terminal.
+pub fn is_active() -> bool {
+    DASHBOARD.lock().unwrap().is_some()
+}
+
+/// Shows the log message in the dashboard. Returns false if it isn't
+/// open.
+pub fn log(message: &str) -> bool {
+    with_state(|state| {
+        if state.messages.len() == MAX_MESSAGES {
+            state.messages.remove(0);
+        }
+        state
+            .messages
+            .push(message.trim_end_matches('\n').to_string());
+    })
+}
+
+/// Processes a line of the output of a job.
+pub fn line(job: usize, line: &str) {
+    with_state(|state| state.update_from_line(job, line));
+}
+
+/// Adds a finding to the dashboard.
+pub fn finding(description: &str) {
+    with_state(|state| state.add_finding(description));
+}
+
+/// Updates the state of the dashboard. Returns false if it isn't open.
+fn with_state(f: impl FnOnce(&mut State)) -> bool {
+    let mut dashboard = DASHBOARD.lock().unwrap();
+    let Some(dashboard) = dashboard.as_mut() else {
+        return false;
+    };
+    f(&mut dashboard.state);
+    true
+}
+
+/// Parses the line "Slowest unit: 12 s:" which libFuzzer prints when an
+/// input ran for longer than -report_slow_units and all earlier ones.
+fn parse_slowest_unit(line: &str) -> Option<u64> {
+    let rest = line.strip_prefix("Slowest unit: ")?;
+    rest.strip_suffix(" s:")?.parse().ok()
+}
+
+/// Parses the file of a slow input from the line `artifact_prefix='...';
+/// Test unit written to .../slow-unit-<sha1>`.
+fn parse_slow_unit_file(line: &str) -> Option<&str> {
+    let (_, file) = line.split_once("Test unit written to ")?;
+    let name = file.rsplit('/').next()?;
+    name.starts_with("slow-unit-").then_some(file)
+}
+
+impl State {
+    fn update_from_line(&mut self, job: usize, line: &str) {
+        if let Some(stats) = parser::parse_stats(line) {
+            if let Some(job_stats) = self.jobs.get_mut(job) {
+                *job_stats = stats;
+            }
+        } else if let Some(secs) = parse_slowest_unit(line) {
+            self.slow_inputs.push(SlowInput {
+                secs,
+                job,
+                file: None,
+            });
+            self.slow_inputs
+                .sort_by_key(|input| std::cmp::Reverse(input.secs));
+            self.slow_inputs.truncate(MAX_SLOW_INPUTS);
+        } else if let Some(file) = parse_slow_unit_file(line) {
+            // The file is written after the execution time is reported
+            if let Some(input) = self
+                .slow_inputs
+                .iter_mut()
+                .find(|input| input.job == job && input.file.is_none())
+            {
+                input.file = Some(file.to_string());
+            }
+        }
+    }
+
+    fn add_finding(&mut self, description: &str) {
+        if self.findings.len() == MAX_FINDINGS {
+            self.findings.pop_back();
+        }
+        self.findings
+            .push_front((self.start.elapsed(), description.to_string()));
+    }
+
+    fn sample(&mut self) {
+        if self.last_sample.elapsed() < SAMPLE_INTERVAL {
+            return;
+        }
+        self.last_sample = Instant::now();
+        let combined = combine_stats(&self.jobs);
+        for (samples, value) in [
+            (&mut self.coverage, combined.cov),
+            (&mut self.corpus, combined.corpus),
+        ] {
+            if samples.len() == MAX_SAMPLES {
+                samples.pop_front();
+            }
+            samples.push_back(value);
+        }
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let combined = combine_stats(&self.jobs);
+        let [header, jobs, charts, bottom, log] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(self.jobs.len().min(8) as u16 + 3),
+            Constraint::Length(6),
+            Constraint::Min(6),
+            Constraint::Length(6),
+        ])
+        .areas(frame.area());
+
+        frame.render_widget(
+            Paragraph::new(Line::from(format!(
+                " {}  {}  execs: {}  exec/s: {}  cov: {}  corp: {}  findings: {}",
+                self.fuzz_test,
+                humantime::format_duration(Duration::from_secs(self.start.elapsed().as_secs())),
+                combined.execs,
+                combined.execs_per_sec,
+                combined.cov,
+                combined.corpus,
+                self.findings.len()
+            )))
+            .style(Style::new().add_modifier(Modifier::BOLD)),
+            header,
+        );
+        self.render_jobs(frame, jobs);
+
+        let [coverage, corpus] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(charts);
+        for (area, title, samples) in [
+            (coverage, "Edge coverage", &self.coverage),
+            (corpus, "Corpus size", &self.corpus),
+        ] {
+            // The most recent samples which fit
+            let width = area.width.saturating_sub(2) as usize;
+            let data: Vec<u64> = samples
+                .iter()
+                .skip(samples.len().saturating_sub(width))
+                .copied()
+                .collect();
+            // The baseline is the first sample, so that growth is visible
+            let min = data.first().copied().unwrap_or(0);
+            let data: Vec<u64> = data.iter().map(|v| v - min.min(*v) + 1).collect();
+            frame.render_widget(
+                Sparkline::default()
+                    .block(Block::bordered().title(format!(
+                        " {title}: {} ",
+                        samples.back().copied().unwrap_or(0)
+                    )))
+                    .data(&data)
+                    .style(Style::new().fg(Color::Green)),
+                area,
+            );
+        }
+
+        let [findings, slow_inputs] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(bottom);
+        let items: Vec<ListItem> = self
+            .findings
+            .iter()
+            .map(|(elapsed, description)| {
+                ListItem::new(format!(
+                    "{:>8}  {description}",
+                    humantime::format_duration(Duration::from_secs(elapsed.as_secs())).to_string()
+                ))
+                .style(Style::new().fg(Color::Red))
+            })
+            .collect();
+        frame.render_widget(
+            List::new(items).block(Block::bordered().title(" Recent findings ")),
+            findings,
+        );
+        let items: Vec<ListItem> = self
+            .slow_inputs
+            .iter()
+            .map(|input| {
+                ListItem::new(format!(
+                    "{:>4} s  job {}  {}",
+                    input.secs,
+                    input.job,
+                    input.file.as_deref().unwrap_or("")
+                ))
+            })
+            .collect();
+        frame.render_widget(
+            List::new(items).block(Block::bordered().title(" Slowest inputs ")),
+            slow_inputs,
+        );
+
+        // The most recent messages which fit
+        let height = log.height.saturating_sub(2) as usize;
+        let items: Vec<ListItem> = self
+            .messages
+            .iter()
+            .flat_map(|message| message.lines())
+            .rev()
+            .take(height)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .map(ListItem::new)
+            .collect();
+        frame.render_widget(
+            List::new(items).block(Block::bordered().title(" Log ")),
+            log,
+        );
+    }
+
+    fn render_jobs(&self, frame: &mut Frame, area: Rect) {
+        let rows = self.jobs.iter().enumerate().map(|(job, stats)| {
+            Row::new([
+                Cell::from(job.to_string()),
+                Cell::from(stats.execs.to_string()),
+                Cell::from(stats.execs_per_sec.to_string()),
+                Cell::from(stats.cov.to_string()),
+                Cell::from(stats.corpus.to_string()),
+                Cell::from(format!("{}Mb", stats.rss_mb)),
+            ])
+        });
+        frame.render_widget(
+            Table::new(rows, [Constraint::Length(10); 6])
+                .header(
+                    Row::new(["job", "execs", "exec/s", "cov", "corp", "rss"])
+                        .style(Style::new().add_modifier(Modifier::BOLD)),
+                )
+                .block(Block::bordered().title(" Jobs ")),
+            area,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let mut state = State {
+            fuzz_test: "my_fuzz_test".to_string(),
+            start: Instant::now(),
+            jobs: vec![Stats::default(); 2],
+            coverage: VecDeque::from([10, 12, 20]),
+            corpus: VecDeque::from([3, 4, 7]),
+            last_sample: Instant::now(),
+            messages: vec!["Running my_fuzz_test with 2 jobs".to_string()],
+            findings: VecDeque::new(),
+            slow_inputs: Vec::new(),
+        };
+        for (job, line) in [
+            (
+                0,
+                "#4096\tpulse  cov: 20 ft: 31 corp: 7/40b lim: 4 exec/s: 2048 rss: 30Mb",
+            ),
+            (
+                1,
+                "#2048\tpulse  cov: 18 ft: 29 corp: 6/34b lim: 4 exec/s: 1024 rss: 29Mb",
+            ),
+            (1, "Slowest unit: 12 s:"),
+            (
+                1,
+                "artifact_prefix='a/'; Test unit written to a/slow-unit-a9993e36",
+            ),
+        ] {
+            state.update_from_line(job, line);
+        }
+        state.add_finding("crash: branch 4 has been reached");
+
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(100, 30)).unwrap();
+        terminal.draw(|frame| state.render(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .chunks(100)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>() + "\n")
+            .collect();
+        assert!(
+            screen.contains("my_fuzz_test  0s  execs: 6144  exec/s: 3072  cov: 20  corp: 7"),
+            "{screen}"
+        );
+        assert!(screen.contains("Edge coverage: 20"), "{screen}");
+        assert!(
+            screen.contains("crash: branch 4 has been reached"),
+            "{screen}"
+        );
+        assert!(
+            screen.contains("12 s  job 1  a/slow-unit-a9993e36"),
+            "{screen}"
+        );
+        assert!(
+            screen.contains("Running my_fuzz_test with 2 jobs"),
+            "{screen}"
+        );
+    }
+
+    #[test]
+    fn slow_inputs() {
+        assert_eq!(parse_slowest_unit("Slowest unit: 12 s:"), Some(12));
+        assert_eq!(parse_slowest_unit("Slowest unit: 12 ms:"), None);
+        assert_eq!(
+            parse_slow_unit_file(
+                "artifact_prefix='/p/.cifuzz-artifacts/t/'; Test unit written to \
+                 /p/.cifuzz-artifacts/t/slow-unit-a9993e364706816aba3e25717850c26c9cd0d89d"
+            ),
+            Some("/p/.cifuzz-artifacts/t/slow-unit-a9993e364706816aba3e25717850c26c9cd0d89d")
+        );
+        assert_eq!(
+            parse_slow_unit_file("artifact_prefix='./'; Test unit written to ./crash-a9993e36"),
+            None
+        );
+    }
+}
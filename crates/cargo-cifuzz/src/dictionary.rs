@@ -0,0 +1,274 @@
+//! Dictionaries for libFuzzer, i.e. files with tokens which libFuzzer
+//! inserts into the inputs it generates.
+//!
+//! Besides the dictionaries written by the user, a dictionary is
+//! generated from the string literals in the sources of the package
+//! which are compared to other values, e.g. `c == "FUZZING"` or
+//! `s.starts_with("GET ")`. Such comparisons are almost impossible to
+//! solve byte by byte.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use syn::visit::{self, Visit};
+use syn::{BinOp, Expr, ExprBinary, ExprMethodCall, Lit, Pat};
+
+use crate::log;
+
+/// The maximum length of a token, longer ones are rejected by libFuzzer.
+const MAX_TOKEN_LEN: usize = 64;
+
+/// Methods of strings and slices whose arguments are compared to the
+/// receiver.
+const COMPARING_METHODS: &[&str] = &[
+    "contains",
+    "ends_with",
+    "eq",
+    "eq_ignore_ascii_case",
+    "find",
+    "ne",
+    "rfind",
+    "split_once",
+    "starts_with",
+    "strip_prefix",
+    "strip_suffix",
+];
+
+/// Extracts the tokens of the Rust sources in the package directory and
+/// writes them to `output` in the dictionary format. Returns the number
+/// of tokens.
+pub fn generate(package_dir: &Path, output: &Path) -> Result<usize> {
+    let mut files = Vec::new();
+    source_files(package_dir, &mut files)?;
+    let mut tokens = BTreeSet::new();
+    for file in files {
+        let source = std::fs::read_to_string(&file)
+            .with_context(|| format!("failed to read {}", file.display()))?;
+        match extract_tokens(&source) {
+            Ok(file_tokens) => tokens.extend(file_tokens),
+            // The compiler reports the errors, if the file is compiled
+            // at all
+            Err(err) => log::debug!("Failed to parse {}: {err}", file.display()),
+        }
+    }
+
+    if let Some(dir) = output.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    let tokens: Vec<Vec<u8>> = tokens.into_iter().collect();
+    std::fs::write(output, format(&tokens))
+        .with_context(|| format!("failed to write {}", output.display()))?;
+    Ok(tokens.len())
+}
+
+/// Returns the string and byte string literals in the source which are
+/// compared to other values.
+pub fn extract_tokens(source: &str) -> syn::Result<BTreeSet<Vec<u8>>> {
+    #[derive(Default)]
+    struct Visitor {
+        tokens: BTreeSet<Vec<u8>>,
+    }
+
+    impl Visitor {
+        fn add_expr(&mut self, expr: &Expr) {
+            match expr {
+                Expr::Lit(lit) => self.add_lit(&lit.lit),
+                Expr::Reference(reference) => self.add_expr(&reference.expr),
+                Expr::Paren(paren) => self.add_expr(&paren.expr),
+                _ => {}
+            }
+        }
+
+        fn add_lit(&mut self, lit: &Lit) {
+            let token = match lit {
+                Lit::Str(s) => s.value().into_bytes(),
+                Lit::ByteStr(b) => b.value(),
+                _ => return,
+            };
+            if !token.is_empty() && token.len() <= MAX_TOKEN_LEN {
+                self.tokens.insert(token);
+            }
+        }
+    }
+
+    impl Visit<'_> for Visitor {
+        fn visit_expr_binary(&mut self, expr: &ExprBinary) {
+            if matches!(expr.op, BinOp::Eq(_) | BinOp::Ne(_)) {
+                self.add_expr(&expr.left);
+                self.add_expr(&expr.right);
+            }
+            visit::visit_expr_binary(self, expr);
+        }
+
+        fn visit_expr_method_call(&mut self, call: &ExprMethodCall) {
+            if COMPARING_METHODS.iter().any(|m| call.method == m) {
+                for arg in &call.args {
+                    self.add_expr(arg);
+                }
+            }
+            visit::visit_expr_method_call(self, call);
+        }
+
+        fn visit_pat(&mut self, pat: &Pat) {
+            // Patterns of match arms and if let
+            if let Pat::Lit(lit) = pat {
+                self.add_lit(&lit.lit);
+            }
+            visit::visit_pat(self, pat);
+        }
+    }
+
+    let file = syn::parse_file(source)?;
+    let mut visitor = Visitor::default();
+    visitor.visit_file(&file);
+    Ok(visitor.tokens)
+}
+
+/// Formats the tokens in the dictionary format of libFuzzer, one quoted
+/// token per line.
+// Must be kept in sync with crates/cifuzz/src/dictionary.rs
+fn format(tokens: &[Vec<u8>]) -> String {
+    let mut dict = String::new();
+    for token in tokens {
+        dict.push('"');
+        for &byte in token {
+            match byte {
+                b'"' | b'\\' => {
+                    dict.push('\\');
+                    dict.push(byte as char);
+                }
+                b' '..=b'~' => dict.push(byte as char),
+                _ => dict.push_str(&format!("\\x{byte:02X}")),
+            }
+        }
+        dict.push_str("\"\n");
+    }
+    dict
+}
+
+/// Adds the Rust source files in the directory and its subdirectories
+/// to `files`, skipping hidden directories and cargo target directories.
+fn source_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = std::fs::read_dir(dir)
+        .with_context(|| format!("failed to read {}", dir.display()))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() {
+            if !file_name.starts_with('.') && file_name != "target" {
+                source_files(&path, files)?;
+            }
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the dictionary to pass to libFuzzer, which only accepts a
+/// single one, so multiple dictionaries are concatenated into `merged`.
+pub fn merge(dictionaries: &[PathBuf], merged: &Path) -> Result<Option<PathBuf>> {
+    match dictionaries {
+        [] => return Ok(None),
+        [dictionary] => return Ok(Some(dictionary.clone())),
+        _ => {}
+    }
+    let mut content = String::new();
+    for dictionary in dictionaries {
+        log::debug!("Using dictionary {}", dictionary.display());
+        let tokens = std::fs::read_to_string(dictionary)
+            .with_context(|| format!("failed to read {}", dictionary.display()))?;
+        content.push_str(&tokens);
+        if !content.ends_with('\n') {
+            content.push('\n');
+        }
+    }
+    if let Some(dir) = merged.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    std::fs::write(merged, content)
+        .with_context(|| format!("failed to write {}", merged.display()))?;
+    Ok(Some(merged.to_path_buf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_compared_literals() {
+        let source = r#"
+            pub fn explore_me(a: i64, c: &str, data: &[u8]) {
+                println!("not compared: {}", a);
+                if c == "FUZZING" && data.starts_with(b"\x7fELF") {
+                    match c {
+                        "GET" | "PUT" => {}
+                        _ => {}
+                    }
+                }
+                let _ = c.strip_prefix(("prefix")) != None;
+                assert_ne!(c, "in a macro");
+                let _ = c == "";
+            }
+        "#;
+        let tokens: Vec<Vec<u8>> = extract_tokens(source).unwrap().into_iter().collect();
+        assert_eq!(
+            tokens,
+            [
+                b"FUZZING".to_vec(),
+                b"GET".to_vec(),
+                b"PUT".to_vec(),
+                b"prefix".to_vec(),
+                b"\x7fELF".to_vec(),
+            ]
+        );
+        assert!(extract_tokens("fn (").is_err());
+    }
+
+    #[test]
+    fn generate_dictionary() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::write(
+            src.join("lib.rs"),
+            "fn f(s: &str) -> bool { s == \"a\\\"b\" }",
+        )
+        .unwrap();
+        std::fs::write(src.join("broken.rs"), "fn (").unwrap();
+        let target = dir.path().join("target");
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::write(
+            target.join("gen.rs"),
+            "fn f(s: &str) -> bool { s == \"x\" }",
+        )
+        .unwrap();
+
+        let output = dir.path().join("out").join("auto.dict");
+        assert_eq!(generate(dir.path(), &output).unwrap(), 1);
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "\"a\\\"b\"\n");
+    }
+
+    #[test]
+    fn merge_multiple_dictionaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.dict");
+        let b = dir.path().join("b.dict");
+        std::fs::write(&a, "\"a\"").unwrap();
+        std::fs::write(&b, "\"b\"\n").unwrap();
+        let merged = dir.path().join("build").join("merged.dict");
+
+        assert_eq!(merge(&[], &merged).unwrap(), None);
+        assert_eq!(
+            merge(std::slice::from_ref(&a), &merged).unwrap(),
+            Some(a.clone())
+        );
+        assert_eq!(merge(&[a, b], &merged).unwrap(), Some(merged.clone()));
+        assert_eq!(std::fs::read_to_string(&merged).unwrap(), "\"a\"\n\"b\"\n");
+    }
+}
@@ -0,0 +1,280 @@
+//! The events of a fuzzing run as JSON lines, for `--output json`.
+//!
+//! Every line is an object with the kind of the event, its fields, the
+//! fuzz test and the seconds since the start of the run, e.g.
+//!
+//! ```text
+//! {"event":"progress","execs":65536,...,"fuzz_test":"my_fuzz_test","elapsed_secs":2.1}
+//! ```
+//!
+//! Like the log, the events are written to a global sink, so that the
+//! runners can emit the progress they parse from the output of the
+//! fuzzing engine. Without a sink, no events are emitted.
+
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::finding::{ErrorType, Finding, Status};
+use crate::parser::Stats;
+use crate::runner::Engine;
+
+static SINK: Mutex<Option<Sink>> = Mutex::new(None);
+
+struct Sink {
+    writer: Box<dyn Write + Send>,
+    /// Whether the events are written to stdout, which the fuzz test
+    /// must not write to
+    stdout: bool,
+    fuzz_test: String,
+    start: Instant,
+    /// The latest progress, which the summary reports
+    stats: Stats,
+    findings: usize,
+    ignored_findings: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event<'a> {
+    Start {
+        engine: Engine,
+        jobs: usize,
+    },
+    Progress {
+        execs: u64,
+        execs_per_sec: u64,
+        corpus_size: u64,
+        edges: u64,
+        /// The edges covered since the previous progress event
+        new_edges: u64,
+        features: u64,
+        rss_mb: u64,
+    },
+    Finding {
+        name: &'a str,
+        #[serde(rename = "type")]
+        error_type: ErrorType,
+        details: &'a str,
+        dir: &'a Path,
+        /// Whether the finding is a duplicate of an existing one
+        duplicate: bool,
+        /// Whether the finding is ignored, so that it doesn't fail the
+        /// run
+        ignored: bool,
+    },
+    Summary {
+        execs: u64,
+        corpus_size: u64,
+        edges: u64,
+        /// The findings, including the ignored ones
+        findings: usize,
+        ignored_findings: usize,
+        /// The exit code of `cargo cifuzz`, see [`crate::exit_code`]
+        exit_code: u8,
+    },
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+    fuzz_test: &'a str,
+    elapsed_secs: f64,
+}
+
+/// Emits the events of the run of the fuzz test to the writer, starting
+/// with a start event.
+pub fn start(
+    writer: Box<dyn Write + Send>,
+    stdout: bool,
+    fuzz_test: &str,
+    engine: Engine,
+    jobs: usize,
+) {
+    *SINK.lock().unwrap() = Some(Sink {
+        writer,
+        stdout,
+        fuzz_test: fuzz_test.to_string(),
+        start: Instant::now(),
+        stats: Stats::default(),
+        findings: 0,
+        ignored_findings: 0,
+    });
+    emit(&Event::Start { engine, jobs });
+}
+
+/// Emits the events of the run of the next fuzz test to the same writer,
+/// starting with a start event, when running multiple fuzz tests.
+pub fn restart(fuzz_test: &str, engine: Engine, jobs: usize) {
+    {
+        let mut sink = SINK.lock().unwrap();
+        let Some(sink) = sink.as_mut() else {
+            return;
+        };
+        sink.fuzz_test = fuzz_test.to_string();
+        sink.start = Instant::now();
+        sink.stats = Stats::default();
+        sink.findings = 0;
+        sink.ignored_findings = 0;
+    }
+    emit(&Event::Start { engine, jobs });
+}
+
+/// Whether the events are written to stdout, in which case the output
+/// of the fuzz test must be redirected.
+pub fn uses_stdout() -> bool {
+    SINK.lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|sink| sink.stdout)
+}
+
+/// Emits the progress of the fuzzing engine.
+pub fn progress(stats: &Stats) {
+    let new_edges = {
+        let mut sink = SINK.lock().unwrap();
+        let Some(sink) = sink.as_mut() else {
+            return;
+        };
+        let new_edges = stats.cov.saturating_sub(sink.stats.cov);
+        sink.stats = *stats;
+        new_edges
+    };
+    emit(&Event::Progress {
+        execs: stats.execs,
+        execs_per_sec: stats.execs_per_sec,
+        corpus_size: stats.corpus,
+        edges: stats.cov,
+        new_edges,
+        features: stats.features,
+        rss_mb: stats.rss_mb,
+    });
+}
+
+/// Emits a finding stored in `dir`, or a duplicate of the finding in
+/// `dir`.
+pub fn finding(finding: &Finding, dir: &Path, duplicate: bool) {
+    let ignored = finding.status == Status::Ignored;
+    if let Some(sink) = SINK.lock().unwrap().as_mut() {
+        sink.findings += 1;
+        sink.ignored_findings += usize::from(ignored);
+    }
+    emit(&Event::Finding {
+        name: &finding.name,
+        error_type: finding.error_type,
+        details: &finding.details,
+        dir,
+        duplicate,
+        ignored,
+    });
+}
+
+/// Emits the summary of the run, which ended with the exit code.
+pub fn summary(exit_code: u8) {
+    let event = {
+        let sink = SINK.lock().unwrap();
+        let Some(sink) = sink.as_ref() else {
+            return;
+        };
+        Event::Summary {
+            execs: sink.stats.execs,
+            corpus_size: sink.stats.corpus,
+            edges: sink.stats.cov,
+            findings: sink.findings,
+            ignored_findings: sink.ignored_findings,
+            exit_code,
+        }
+    };
+    emit(&event);
+}
+
+fn emit(event: &Event) {
+    let mut sink = SINK.lock().unwrap();
+    let Some(sink) = sink.as_mut() else {
+        return;
+    };
+    let line = Line {
+        event,
+        fuzz_test: &sink.fuzz_test,
+        elapsed_secs: (sink.start.elapsed().as_secs_f64() * 10.0).round() / 10.0,
+    };
+    let Ok(json) = serde_json::to_string(&line) else {
+        return;
+    };
+    // A consumer which went away must not stop the fuzzing run
+    let _ = writeln!(sink.writer, "{json}").and_then(|()| sink.writer.flush());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// A writer whose content can be read after it was moved into the
+    /// sink.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn event_lines() {
+        let buffer = Buffer::default();
+        start(
+            Box::new(buffer.clone()),
+            false,
+            "my_fuzz_test",
+            Engine::Libfuzzer,
+            2,
+        );
+        let stats = Stats {
+            execs: 1000,
+            cov: 60,
+            features: 80,
+            corpus: 12,
+            execs_per_sec: 500,
+            rss_mb: 30,
+        };
+        progress(&stats);
+        progress(&Stats { cov: 64, ..stats });
+        summary(0);
+        restart("other_fuzz_test", Engine::Libfuzzer, 1);
+        summary(0);
+        *SINK.lock().unwrap() = None;
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let events: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 6);
+        assert_eq!(events[0]["event"], "start");
+        assert_eq!(events[0]["engine"], "libfuzzer");
+        assert_eq!(events[0]["fuzz_test"], "my_fuzz_test");
+        assert_eq!(events[1]["event"], "progress");
+        assert_eq!(events[1]["new_edges"], 60);
+        assert_eq!(events[2]["edges"], 64);
+        assert_eq!(events[2]["new_edges"], 4);
+        assert_eq!(events[3]["event"], "summary");
+        assert_eq!(events[3]["edges"], 64);
+        assert_eq!(events[3]["findings"], 0);
+        assert!(events[3]["elapsed_secs"].is_number());
+        assert_eq!(events[4]["event"], "start");
+        assert_eq!(events[4]["fuzz_test"], "other_fuzz_test");
+        assert_eq!(events[5]["event"], "summary");
+        assert_eq!(events[5]["edges"], 0);
+    }
+}
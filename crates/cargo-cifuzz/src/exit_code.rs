@@ -0,0 +1,92 @@
+//! The exit codes of `cargo cifuzz`, which let CI pipelines tell bugs
+//! found by a fuzz test apart from other failures:
+//!
+//! * 0: Success, e.g. the fuzz test ran until the maximum fuzzing
+//!   duration without a finding.
+//! * 1: Any other error, e.g. an invalid cifuzz.yaml.
+//! * 2: Invalid command-line arguments (the exit code of clap).
+//! * 3: The fuzz test found a bug.
+//! * 4: The fuzz tests failed to build.
+//! * 5: The fuzz test was stopped early because its coverage stopped
+//!   growing, see `cargo cifuzz run --exit-on-plateau`.
+
+use std::fmt;
+
+/// The failures with an exit code of their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// The fuzz test found a bug, e.g. a crash or a timeout
+    Finding,
+    /// cargo failed to build the fuzz tests
+    Build,
+    /// The fuzz test was stopped because it covered no new edges for
+    /// the `--exit-on-plateau` window
+    Plateau,
+}
+
+impl Failure {
+    pub fn code(self) -> u8 {
+        match self {
+            Failure::Finding => 3,
+            Failure::Build => 4,
+            Failure::Plateau => 5,
+        }
+    }
+
+    /// Attaches the failure to the error, so that `cargo cifuzz` exits
+    /// with its code.
+    pub fn error(self, error: impl Into<anyhow::Error>) -> anyhow::Error {
+        anyhow::Error::new(FailureError {
+            failure: self,
+            error: error.into(),
+        })
+    }
+}
+
+/// The exit code of `cargo cifuzz` for the error.
+pub fn of(error: &anyhow::Error) -> u8 {
+    error
+        .downcast_ref::<FailureError>()
+        .map_or(1, |e| e.failure.code())
+}
+
+#[derive(Debug)]
+struct FailureError {
+    failure: Failure,
+    error: anyhow::Error,
+}
+
+impl fmt::Display for FailureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The error is printed with its causes, because they are not
+        // the source of the failure
+        write!(f, "{:#}", self.error)
+    }
+}
+
+impl std::error::Error for FailureError {}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(of(&anyhow!("invalid cifuzz.yaml")), 1);
+        let error = Failure::Finding.error(anyhow!("found a crash"));
+        assert_eq!(of(&error), 3);
+        assert_eq!(error.to_string(), "found a crash");
+
+        let error = Failure::Build
+            .error(anyhow!("could not compile").context("failed to build"))
+            .context("Building my_fuzz_test");
+        assert_eq!(of(&error), 4);
+        assert_eq!(of(&Failure::Plateau.error(anyhow!("no new edges"))), 5);
+        assert_eq!(
+            format!("{error:#}"),
+            "Building my_fuzz_test: failed to build: could not compile"
+        );
+    }
+}
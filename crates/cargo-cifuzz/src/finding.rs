@@ -0,0 +1,1054 @@
+//! Findings are the crashes found by fuzz tests, stored in
+//! `.cifuzz/findings/<fuzz_test>/<hash>/` in the project directory.
+//!
+//! The directory of a finding contains the crashing input and a
+//! `finding.json` with the panic message, the stack trace and metadata
+//! about the build. The crashing inputs are replayed by the regression
+//! tests of the fuzz test (see crates/cifuzz/src/regression.rs).
+//!
+//! Crashes are deduplicated by a token computed from the top frames of
+//! their stack trace, so that hitting the same panic with different
+//! inputs doesn't produce a new finding every time.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::build::Sanitizer;
+use crate::config::SeverityRule;
+use crate::parser::CrashReport;
+use crate::runner::Engine;
+use crate::{log, severity};
+
+/// The file name of the crashing input in the directory of a finding,
+/// must be kept in sync with crates/cifuzz/src/regression.rs
+pub const CRASHING_INPUT_FILE: &str = "crashing-input";
+pub const FINDING_FILE: &str = "finding.json";
+
+/// The prefix of the panic message of a finding reported with
+/// `cifuzz::report_finding!`, must be kept in sync with
+/// crates/cifuzz/src/oracle.rs
+const REPORTED_FINDING_PREFIX: &str = "cifuzz finding (";
+
+/// The number of stack frames from which the dedup token of a finding
+/// is computed.
+const DEDUP_FRAMES: usize = 3;
+
+/// Returns the directory containing the findings of the fuzz test.
+pub fn findings_dir(project_dir: &Path, fuzz_test: &str) -> PathBuf {
+    project_dir.join(".cifuzz").join("findings").join(fuzz_test)
+}
+
+/// A finding, stored as a finding.json compatible with the findings of
+/// the cifuzz CLI.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "FindingJson", into = "FindingJson")]
+pub struct Finding {
+    pub name: String,
+    pub error_type: ErrorType,
+    pub input_data: Vec<u8>,
+    pub logs: Vec<String>,
+    pub details: String,
+    pub created_at: String,
+    /// The path of the crashing input relative to the project directory
+    pub input_file: PathBuf,
+    pub stack_trace: Vec<StackFrame>,
+    /// The operations which `cifuzz::ops!` executed until the crash, in
+    /// the order of their execution
+    pub operations: Vec<String>,
+    /// The output of the fuzz test on the crashing input, which is
+    /// captured during fuzzing unless `--no-capture` is passed
+    pub output: Vec<String>,
+    /// The severity given to `cifuzz::report_finding!`, otherwise the
+    /// severity is determined by the kind of bug, see [`Finding::severity`]
+    pub severity: Option<Severity>,
+    /// Findings with the same dedup token are considered the same bug
+    pub dedup_token: String,
+    /// Whether the bug is still open, set with `cargo cifuzz findings
+    /// mark`
+    pub status: Status,
+    pub metadata: Metadata,
+}
+
+/// The finding.json of a [`Finding`]. Its `type` is one of the error
+/// types of the cifuzz CLI, the error type of the finding is the ID of
+/// the `more_details`.
+#[derive(Serialize, Deserialize)]
+struct FindingJson {
+    name: String,
+    #[serde(rename = "type")]
+    error_type: GoErrorType,
+    #[serde(with = "base64_bytes")]
+    input_data: Vec<u8>,
+    logs: Vec<String>,
+    details: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    more_details: Option<ErrorDetails>,
+    created_at: String,
+    input_file: PathBuf,
+    stack_trace: Vec<StackFrame>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    operations: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    output: Vec<String>,
+    #[serde(default)]
+    dedup_token: String,
+    #[serde(default, skip_serializing_if = "Status::is_open")]
+    status: Status,
+    metadata: Metadata,
+}
+
+/// The error types of the cifuzz CLI, which must have these exact
+/// values.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum GoErrorType {
+    UnknownError,
+    CompilationError,
+    Crash,
+    Warning,
+    RuntimeError,
+}
+
+/// The `more_details` of a finding of the cifuzz CLI.
+#[derive(Debug, Serialize, Deserialize)]
+struct ErrorDetails {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    severity: Option<SeverityDetails>,
+}
+
+/// The severity in the `more_details`, with the score the cifuzz CLI
+/// gives to its levels.
+#[derive(Debug, Serialize, Deserialize)]
+struct SeverityDetails {
+    #[serde(rename = "description")]
+    level: SeverityLevel,
+    #[serde(default)]
+    score: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum SeverityLevel {
+    Critical,
+    High,
+    Medium,
+    Low,
+}
+
+impl From<Severity> for SeverityDetails {
+    fn from(severity: Severity) -> SeverityDetails {
+        let (level, score) = match severity {
+            Severity::Low => (SeverityLevel::Low, 1.0),
+            Severity::Medium => (SeverityLevel::Medium, 5.0),
+            Severity::High => (SeverityLevel::High, 7.0),
+        };
+        SeverityDetails { level, score }
+    }
+}
+
+impl From<SeverityDetails> for Severity {
+    fn from(details: SeverityDetails) -> Severity {
+        match details.level {
+            SeverityLevel::Low => Severity::Low,
+            SeverityLevel::Medium => Severity::Medium,
+            SeverityLevel::Critical | SeverityLevel::High => Severity::High,
+        }
+    }
+}
+
+impl From<FindingJson> for Finding {
+    fn from(json: FindingJson) -> Finding {
+        let (error_type, severity) = match json.more_details {
+            Some(details) => (
+                ErrorType::from_id(&details.id),
+                details.severity.map(Severity::from),
+            ),
+            None => (None, None),
+        };
+        // Findings of the cifuzz CLI may have no more details or IDs
+        // which aren't error types
+        let error_type = error_type.unwrap_or(ErrorType::Crash);
+        Finding {
+            name: json.name,
+            error_type,
+            input_data: json.input_data,
+            logs: json.logs,
+            details: json.details,
+            created_at: json.created_at,
+            input_file: json.input_file,
+            stack_trace: json.stack_trace,
+            operations: json.operations,
+            output: json.output,
+            severity,
+            dedup_token: json.dedup_token,
+            status: json.status,
+            metadata: json.metadata,
+        }
+    }
+}
+
+impl From<Finding> for FindingJson {
+    fn from(finding: Finding) -> FindingJson {
+        FindingJson {
+            name: finding.name,
+            error_type: finding.error_type.go_type(),
+            input_data: finding.input_data,
+            logs: finding.logs,
+            details: finding.details,
+            more_details: Some(ErrorDetails {
+                id: finding.error_type.id().to_string(),
+                name: finding.error_type.name().to_string(),
+                severity: finding.severity.map(SeverityDetails::from),
+            }),
+            created_at: finding.created_at,
+            input_file: finding.input_file,
+            stack_trace: finding.stack_trace,
+            operations: finding.operations,
+            output: finding.output,
+            dedup_token: finding.dedup_token,
+            status: finding.status,
+            metadata: finding.metadata,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorType {
+    Crash,
+    /// Memory which an input allocated and never freed
+    Leak,
+    /// An input on which the fuzz test ran longer than the input timeout
+    Timeout,
+    /// An input on which the fuzz test exceeded the memory limit
+    OutOfMemory,
+    /// A panic of an overflow check, e.g. "attempt to add with overflow"
+    IntegerOverflow,
+    /// Undefined behavior detected by Miri, e.g. an out-of-bounds pointer
+    /// or a violation of the aliasing rules
+    UndefinedBehavior,
+}
+
+pub const ERROR_TYPES: [ErrorType; 6] = [
+    ErrorType::Crash,
+    ErrorType::Leak,
+    ErrorType::Timeout,
+    ErrorType::OutOfMemory,
+    ErrorType::IntegerOverflow,
+    ErrorType::UndefinedBehavior,
+];
+
+impl ErrorType {
+    /// How the type is called in messages, e.g. "found a crash".
+    pub fn description(self) -> &'static str {
+        match self {
+            ErrorType::Crash => "crash",
+            ErrorType::Leak => "memory leak",
+            ErrorType::Timeout => "timeout",
+            ErrorType::OutOfMemory => "memory limit violation",
+            ErrorType::IntegerOverflow => "failed overflow check",
+            ErrorType::UndefinedBehavior => "case of undefined behavior",
+        }
+    }
+
+    /// The ID of the type in the `more_details` of the finding.json and
+    /// the rules of the SARIF report, e.g. "memory-leak".
+    pub fn id(self) -> &'static str {
+        match self {
+            ErrorType::Crash => "crash",
+            ErrorType::Leak => "memory-leak",
+            ErrorType::Timeout => "timeout",
+            ErrorType::OutOfMemory => "out-of-memory",
+            ErrorType::IntegerOverflow => "integer-overflow",
+            ErrorType::UndefinedBehavior => "undefined-behavior",
+        }
+    }
+
+    fn from_id(id: &str) -> Option<ErrorType> {
+        ERROR_TYPES
+            .into_iter()
+            .find(|error_type| error_type.id() == id)
+    }
+
+    /// The name of the type in the `more_details`, which the cifuzz CLI
+    /// shows.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ErrorType::Crash => "Crash",
+            ErrorType::Leak => "Memory Leak",
+            ErrorType::Timeout => "Timeout",
+            ErrorType::OutOfMemory => "Out of Memory",
+            ErrorType::IntegerOverflow => "Integer Overflow",
+            ErrorType::UndefinedBehavior => "Undefined Behavior",
+        }
+    }
+
+    /// The error type of the cifuzz CLI, under which bugs which don't
+    /// crash the fuzz test are warnings and failed overflow checks and
+    /// undefined behavior are runtime errors.
+    fn go_type(self) -> GoErrorType {
+        match self {
+            ErrorType::Crash => GoErrorType::Crash,
+            ErrorType::Leak | ErrorType::Timeout | ErrorType::OutOfMemory => GoErrorType::Warning,
+            ErrorType::IntegerOverflow | ErrorType::UndefinedBehavior => GoErrorType::RuntimeError,
+        }
+    }
+}
+
+/// Serialized as the error type of the cifuzz CLI.
+impl Serialize for ErrorType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.go_type().serialize(serializer)
+    }
+}
+
+/// The severity of a finding, by the kind of bug, as reported by the
+/// fuzz test or as set by the rules of the cifuzz.yaml, see
+/// [`crate::severity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Severity> {
+        [Severity::Low, Severity::Medium, Severity::High]
+            .into_iter()
+            .find(|severity| severity.name() == name)
+    }
+}
+
+/// The triage status of a finding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// The bug hasn't been fixed yet
+    #[default]
+    Open,
+    /// The bug has been fixed, the finding is reopened if it's found
+    /// again
+    Fixed,
+    /// The finding is accepted and skipped by the regression tests
+    Ignored,
+}
+
+impl Status {
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Open => "open",
+            Status::Fixed => "fixed",
+            Status::Ignored => "ignored",
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        *self == Status::Open
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StackFrame {
+    pub source_file: String,
+    pub line: u32,
+    pub column: u32,
+    pub frame_number: usize,
+    pub function: String,
+}
+
+/// Information about how the finding was found.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Metadata {
+    pub fuzz_test: String,
+    /// The git commit of the project, if it's a git repository
+    pub commit: Option<String>,
+    /// The RUSTFLAGS with which the fuzz test was built
+    pub build_flags: Vec<String>,
+    /// The additional arguments passed to `cargo test`
+    pub cargo_args: Vec<String>,
+    /// The fuzzing engine which found the finding
+    #[serde(default)]
+    pub engine: Engine,
+    /// The sanitizer the fuzz test was built with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sanitizer: Option<Sanitizer>,
+    /// The seed of libFuzzer's random number generator in the run which
+    /// found the finding
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// The `--seed` of the run, which seeds `cifuzz::rng()`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rng_seed: Option<u64>,
+    /// The libFuzzer flags which affect the execution of a single input,
+    /// e.g. the input timeout
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub libfuzzer_args: Vec<String>,
+    /// The version of rustc the fuzz test was built with, e.g.
+    /// "1.86.0-nightly (a580b5c37 2025-02-04)"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rustc: Option<String>,
+    /// The target triple the fuzz test was built for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+impl Finding {
+    /// Creates the finding of a crash from the crashing input which
+    /// libFuzzer wrote to `report.input_file`. Paths of source files in
+    /// the stack trace are resolved relative to `package_dir`, the
+    /// directory in which the fuzz test was executed, and stored
+    /// relative to the project.
+    pub fn new(
+        report: &CrashReport,
+        project_dir: &Path,
+        package_dir: &Path,
+        metadata: Metadata,
+    ) -> Result<Finding> {
+        let input_file = report
+            .input_file
+            .as_deref()
+            .context("libFuzzer didn't write the crashing input")?;
+        let input_data = std::fs::read(input_file)
+            .with_context(|| format!("failed to read {}", input_file.display()))?;
+        let name = input_hash(input_file);
+        let error_type = match error_type(input_file) {
+            // libFuzzer reports failed allocations as crashes
+            ErrorType::Crash
+                if report
+                    .error
+                    .as_deref()
+                    .is_some_and(|e| e.starts_with("out-of-memory")) =>
+            {
+                ErrorType::OutOfMemory
+            }
+            ErrorType::Crash
+                if report
+                    .panic_message
+                    .as_deref()
+                    .is_some_and(is_overflow_message) =>
+            {
+                ErrorType::IntegerOverflow
+            }
+            error_type => error_type,
+        };
+        let input_file = findings_dir(project_dir, &metadata.fuzz_test)
+            .join(&name)
+            .join(CRASHING_INPUT_FILE);
+
+        let stack_trace = stack_frames(report, project_dir, package_dir);
+        let (details, severity) = details(report);
+        let dedup_token = dedup_token(&stack_trace, &details);
+
+        Ok(Finding {
+            name,
+            error_type,
+            input_data,
+            logs: report.logs.clone(),
+            details,
+            created_at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            input_file: input_file
+                .strip_prefix(project_dir)
+                .unwrap_or(&input_file)
+                .to_path_buf(),
+            stack_trace,
+            operations: report.operations.clone(),
+            output: report.output.clone(),
+            severity,
+            dedup_token,
+            status: Status::Open,
+            metadata,
+        })
+    }
+
+    /// The severity of the finding by the `severity` rules of the
+    /// cifuzz.yaml, see [`severity::classify`].
+    pub fn severity(&self, rules: &[SeverityRule]) -> Severity {
+        severity::classify(self, rules)
+    }
+
+    /// Reads the finding.json in the directory of a finding.
+    pub fn load(dir: &Path) -> Result<Finding> {
+        let path = dir.join(FINDING_FILE);
+        let content =
+            std::fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_slice(&content)
+            .with_context(|| format!("failed to parse {}", path.display()))
+    }
+
+    /// Returns the directory of an existing finding of the same fuzz
+    /// test with the same dedup token.
+    pub fn find_duplicate(&self, project_dir: &Path) -> Result<Option<PathBuf>> {
+        let dir = findings_dir(project_dir, &self.metadata.fuzz_test);
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read {}", dir.display()))
+            }
+        };
+        for entry in entries {
+            let path = entry?.path().join(FINDING_FILE);
+            // Directories without a valid finding.json, e.g. findings
+            // added by hand, can't be compared
+            let Ok(content) = std::fs::read(&path) else {
+                continue;
+            };
+            let Ok(finding) = serde_json::from_slice::<Finding>(&content) else {
+                log::debug!("Ignoring invalid finding {}", path.display());
+                continue;
+            };
+            if finding.dedup_token == self.dedup_token {
+                return Ok(path.parent().map(Path::to_path_buf));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Stores the finding in the findings directory of the project and
+    /// returns the directory of the finding.
+    pub fn save(&self, project_dir: &Path) -> Result<PathBuf> {
+        let dir = findings_dir(project_dir, &self.metadata.fuzz_test).join(&self.name);
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+        std::fs::write(dir.join(CRASHING_INPUT_FILE), &self.input_data)
+            .context("failed to write the crashing input")?;
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(dir.join(FINDING_FILE), json + "\n")
+            .context("failed to write the finding")?;
+        Ok(dir)
+    }
+}
+
+/// Returns the directories of the findings of all fuzz tests, sorted.
+pub fn dirs(project_dir: &Path) -> Result<Vec<PathBuf>> {
+    let findings_root = project_dir.join(".cifuzz").join("findings");
+    let mut dirs = Vec::new();
+    if !findings_root.is_dir() {
+        return Ok(dirs);
+    }
+    for fuzz_test in std::fs::read_dir(&findings_root)
+        .with_context(|| format!("failed to read {}", findings_root.display()))?
+    {
+        let fuzz_test_dir = fuzz_test?.path();
+        if !fuzz_test_dir.is_dir() {
+            continue;
+        }
+        for entry in std::fs::read_dir(&fuzz_test_dir)
+            .with_context(|| format!("failed to read {}", fuzz_test_dir.display()))?
+        {
+            let dir = entry?.path();
+            if dir.join(FINDING_FILE).is_file() {
+                dirs.push(dir);
+            }
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// Returns the directory of a finding, which is either given by its path
+/// or by its name. The name may be abbreviated to a unique prefix and is
+/// looked up in the findings of all fuzz tests.
+pub fn find(project_dir: &Path, finding: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(finding);
+    if path.join(FINDING_FILE).is_file() {
+        return Ok(path);
+    }
+    let findings_root = project_dir.join(".cifuzz").join("findings");
+    let mut matches: Vec<PathBuf> = dirs(project_dir)?
+        .into_iter()
+        .filter(|dir| {
+            dir.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with(finding))
+        })
+        .collect();
+    match matches.len() {
+        1 => Ok(matches.pop().unwrap()),
+        0 => bail!(
+            "Finding {finding:?} not found in {}",
+            findings_root.display()
+        ),
+        _ => bail!(
+            "Finding {finding:?} is ambiguous, it matches:\n  {}",
+            matches
+                .iter()
+                .map(|dir| dir.display().to_string())
+                .collect::<Vec<_>>()
+                .join("\n  ")
+        ),
+    }
+}
+
+/// Returns the frames of the stack trace of a crash, see [`Finding::new`].
+fn stack_frames(report: &CrashReport, project_dir: &Path, package_dir: &Path) -> Vec<StackFrame> {
+    report
+        .stack_trace
+        .iter()
+        .enumerate()
+        .map(|(i, frame)| StackFrame {
+            source_file: frame
+                .file
+                .as_deref()
+                .map(|f| relative_source_path(f, project_dir, package_dir))
+                .unwrap_or_default(),
+            line: frame.line,
+            column: frame.column,
+            frame_number: i,
+            function: frame.function.clone(),
+        })
+        .collect()
+}
+
+/// Returns the details of a crash and the severity of a finding reported
+/// by the fuzz test, whose panic message is like "cifuzz finding (medium
+/// severity): round trip mismatch".
+fn details(report: &CrashReport) -> (String, Option<Severity>) {
+    let details = report.details();
+    let reported = details
+        .strip_prefix(REPORTED_FINDING_PREFIX)
+        .and_then(|rest| rest.split_once(" severity): "))
+        .and_then(|(severity, message)| Some((Severity::from_name(severity)?, message)));
+    match reported {
+        Some((severity, message)) => (message.to_string(), Some(severity)),
+        None => (details, None),
+    }
+}
+
+/// Returns the dedup token which the finding of a crash would have.
+pub fn crash_dedup_token(report: &CrashReport, project_dir: &Path, package_dir: &Path) -> String {
+    dedup_token(
+        &stack_frames(report, project_dir, package_dir),
+        &details(report).0,
+    )
+}
+
+/// Computes the dedup token of a crash from the functions and source
+/// locations of the top stack frames, which are the frames of the
+/// project after the runtime frames have been removed. Crashes without
+/// a stack trace, e.g. out-of-memory errors, are identified by their
+/// details.
+fn dedup_token(stack_trace: &[StackFrame], details: &str) -> String {
+    let mut hasher = sha1_smol::Sha1::new();
+    if stack_trace.is_empty() {
+        hasher.update(details.as_bytes());
+    }
+    for frame in stack_trace.iter().take(DEDUP_FRAMES) {
+        let frame = format!(
+            "{} {}:{}:{}\n",
+            frame.function, frame.source_file, frame.line, frame.column
+        );
+        hasher.update(frame.as_bytes());
+    }
+    hasher.digest().to_string()
+}
+
+/// Returns the hash which libFuzzer uses as the name of an artifact
+/// like `crash-<sha1>`, or the file name if it has no such prefix.
+fn input_hash(input_file: &Path) -> String {
+    let file_name = input_file
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match file_name.split_once('-') {
+        Some((_, hash)) if !hash.is_empty() => hash.to_string(),
+        _ => file_name,
+    }
+}
+
+/// Returns the type of the error for which libFuzzer wrote the
+/// artifact, which is the prefix of its name, e.g. `leak-<sha1>`.
+fn error_type(input_file: &Path) -> ErrorType {
+    let file_name = input_file
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match file_name.split_once('-') {
+        Some(("leak", _)) => ErrorType::Leak,
+        Some(("timeout", _)) => ErrorType::Timeout,
+        Some(("oom", _)) => ErrorType::OutOfMemory,
+        // The inputs on which Miri detected undefined behavior
+        Some(("ub", _)) => ErrorType::UndefinedBehavior,
+        _ => ErrorType::Crash,
+    }
+}
+
+/// Whether the panic message is the one of an overflow check, like
+/// "attempt to add with overflow" or "attempt to shift left with
+/// overflow".
+fn is_overflow_message(message: &str) -> bool {
+    message
+        .strip_prefix("attempt to ")
+        .is_some_and(|rest| rest.ends_with(" with overflow"))
+}
+
+fn relative_source_path(file: &str, project_dir: &Path, package_dir: &Path) -> String {
+    let path = Path::new(file);
+    let path = path.strip_prefix(".").unwrap_or(path);
+    let path = package_dir.join(path);
+    path.strip_prefix(project_dir)
+        .unwrap_or(&path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Returns the current git commit of the project.
+pub fn git_commit(project_dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .current_dir(project_dir)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Stores the input data as base64 like the findings of the cifuzz CLI.
+mod base64_bytes {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        STANDARD.decode(s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Returns a crash of `my_fuzz_test` for the tests, whose fields are
+/// overridden with the struct update syntax.
+#[cfg(test)]
+pub(crate) fn test_finding(name: &str) -> Finding {
+    Finding {
+        name: name.to_string(),
+        error_type: ErrorType::Crash,
+        input_data: Vec::new(),
+        logs: Vec::new(),
+        details: "branch 4 has been reached".to_string(),
+        created_at: "2024-01-01T00:00:00Z".to_string(),
+        input_file: PathBuf::from(format!(
+            ".cifuzz/findings/my_fuzz_test/{name}/{CRASHING_INPUT_FILE}"
+        )),
+        stack_trace: Vec::new(),
+        operations: Vec::new(),
+        output: Vec::new(),
+        severity: None,
+        dedup_token: String::new(),
+        status: Status::Open,
+        metadata: Metadata {
+            fuzz_test: "my_fuzz_test".to_string(),
+            commit: None,
+            build_flags: Vec::new(),
+            cargo_args: Vec::new(),
+            engine: Engine::Libfuzzer,
+            sanitizer: None,
+            seed: None,
+            rng_seed: None,
+            libfuzzer_args: Vec::new(),
+            rustc: None,
+            target: None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Frame;
+
+    fn report(input_file: &Path) -> CrashReport {
+        CrashReport {
+            panic_message: Some("branch 4 has been reached".to_string()),
+            panic_location: Some("src/explore_me.rs:14:21".to_string()),
+            error: Some("deadly signal".to_string()),
+            stack_trace: vec![
+                Frame {
+                    function: "cargo_example::explore_me::explore_me".to_string(),
+                    file: Some("./src/explore_me.rs".to_string()),
+                    line: 14,
+                    column: 21,
+                },
+                Frame {
+                    function: "cargo_example::my_fuzz_test::my_fuzz_test".to_string(),
+                    file: None,
+                    line: 0,
+                    column: 0,
+                },
+            ],
+            input_file: Some(input_file.to_path_buf()),
+            operations: Vec::new(),
+            output: Vec::new(),
+            logs: vec!["thread 'main' panicked at src/explore_me.rs:14:21:".to_string()],
+        }
+    }
+
+    fn metadata() -> Metadata {
+        Metadata {
+            fuzz_test: "my_fuzz_test".to_string(),
+            commit: Some("0123abcd".to_string()),
+            build_flags: vec!["--cfg".to_string(), "fuzzing".to_string()],
+            cargo_args: vec![],
+            engine: Engine::Libfuzzer,
+            sanitizer: Some(Sanitizer::Address),
+            seed: Some(1234),
+            rng_seed: None,
+            libfuzzer_args: vec!["-timeout=10".to_string()],
+            rustc: Some("1.86.0-nightly (a580b5c37 2025-02-04)".to_string()),
+            target: Some("x86_64-unknown-linux-gnu".to_string()),
+        }
+    }
+
+    #[test]
+    fn save_finding() {
+        let project = tempfile::tempdir().unwrap();
+        let artifacts = project.path().join(".cifuzz-artifacts");
+        std::fs::create_dir_all(&artifacts).unwrap();
+        let input_file = artifacts.join("crash-e6c1a2d3");
+        std::fs::write(&input_file, b"FUZZING\xff").unwrap();
+
+        let package_dir = project.path().join("crates/foo");
+        let finding = Finding::new(
+            &report(&input_file),
+            project.path(),
+            &package_dir,
+            metadata(),
+        )
+        .unwrap();
+        assert_eq!(finding.name, "e6c1a2d3");
+        assert_eq!(finding.details, "branch 4 has been reached");
+        assert_eq!(
+            finding.input_file,
+            Path::new(".cifuzz/findings/my_fuzz_test/e6c1a2d3/crashing-input")
+        );
+        assert_eq!(
+            finding.stack_trace[0].source_file,
+            "crates/foo/src/explore_me.rs"
+        );
+        assert_eq!(finding.stack_trace[1].frame_number, 1);
+
+        let dir = finding.save(project.path()).unwrap();
+        assert_eq!(
+            dir,
+            project
+                .path()
+                .join(".cifuzz/findings/my_fuzz_test/e6c1a2d3")
+        );
+        assert_eq!(
+            std::fs::read(dir.join(CRASHING_INPUT_FILE)).unwrap(),
+            b"FUZZING\xff"
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(dir.join(FINDING_FILE)).unwrap()).unwrap();
+        assert_eq!(json["type"], "CRASH");
+        assert_eq!(json["input_data"], "RlVaWklOR/8=");
+        assert_eq!(
+            json["stack_trace"][0]["SourceFile"],
+            "crates/foo/src/explore_me.rs"
+        );
+        assert_eq!(json["stack_trace"][0]["Line"], 14);
+        assert_eq!(json["metadata"]["commit"], "0123abcd");
+
+        assert_eq!(json["metadata"]["sanitizer"], "address");
+        assert_eq!(json["metadata"]["seed"], 1234);
+
+        let finding: Finding = serde_json::from_value(json).unwrap();
+        assert_eq!(finding.input_data, b"FUZZING\xff");
+        assert_eq!(Finding::load(&dir).unwrap().metadata.seed, Some(1234));
+    }
+
+    #[test]
+    fn metadata_of_older_findings() {
+        let metadata: Metadata = serde_json::from_str(
+            r#"{"fuzz_test": "my_fuzz_test", "commit": null, "build_flags": [], "cargo_args": []}"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.engine, Engine::Libfuzzer);
+        assert_eq!(metadata.sanitizer, None);
+        assert_eq!(metadata.seed, None);
+        assert!(metadata.libfuzzer_args.is_empty());
+    }
+
+    #[test]
+    fn find_findings() {
+        let project = tempfile::tempdir().unwrap();
+        for (fuzz_test, name) in [("a", "e6c1a2d3"), ("a", "e6c1ffff"), ("b", "0123abcd")] {
+            let dir = findings_dir(project.path(), fuzz_test).join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join(FINDING_FILE), "{}").unwrap();
+        }
+        let b = findings_dir(project.path(), "b").join("0123abcd");
+        assert_eq!(find(project.path(), "0123abcd").unwrap(), b);
+        assert_eq!(find(project.path(), "0123").unwrap(), b);
+        assert_eq!(find(project.path(), b.to_str().unwrap()).unwrap(), b);
+        assert!(find(project.path(), "e6c1")
+            .unwrap_err()
+            .to_string()
+            .contains("is ambiguous"));
+        assert!(find(project.path(), "ffff")
+            .unwrap_err()
+            .to_string()
+            .contains("not found"));
+    }
+
+    #[test]
+    fn reported_findings() {
+        let project = tempfile::tempdir().unwrap();
+        let input_file = project.path().join("crash-e6c1a2d3");
+        std::fs::write(&input_file, "a").unwrap();
+        let mut report = report(&input_file);
+        let finding = Finding::new(&report, project.path(), project.path(), metadata()).unwrap();
+        assert_eq!(finding.severity, None);
+        assert_eq!(finding.severity(&[]), Severity::Low);
+
+        report.panic_message =
+            Some("cifuzz finding (low severity): round trip mismatch: \"ä\" != \"a\"".to_string());
+        let finding = Finding::new(&report, project.path(), project.path(), metadata()).unwrap();
+        assert_eq!(finding.details, "round trip mismatch: \"ä\" != \"a\"");
+        assert_eq!(finding.severity, Some(Severity::Low));
+        let json = serde_json::to_value(&finding).unwrap();
+        assert_eq!(
+            json["more_details"]["severity"],
+            serde_json::json!({"description": "LOW", "score": 1.0})
+        );
+        assert!(json.get("severity").is_none());
+        let mut json = json;
+        let finding: Finding = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(finding.severity, Some(Severity::Low));
+        // The highest level of the cifuzz CLI
+        json["more_details"]["severity"]["description"] = "CRITICAL".into();
+        let finding: Finding = serde_json::from_value(json).unwrap();
+        assert_eq!(finding.severity, Some(Severity::High));
+
+        report.panic_message = Some("cifuzz finding (no severity): a".to_string());
+        let finding = Finding::new(&report, project.path(), project.path(), metadata()).unwrap();
+        assert_eq!(finding.details, "cifuzz finding (no severity): a");
+        assert_eq!(finding.severity, None);
+    }
+
+    #[test]
+    fn input_hashes() {
+        assert_eq!(input_hash(Path::new("/a/crash-e6c1a2d3")), "e6c1a2d3");
+        assert_eq!(input_hash(Path::new("/a/oom-1234")), "1234");
+        assert_eq!(input_hash(Path::new("/a/input")), "input");
+    }
+
+    #[test]
+    fn error_types() {
+        assert_eq!(error_type(Path::new("/a/crash-e6c1a2d3")), ErrorType::Crash);
+        assert_eq!(error_type(Path::new("/a/leak-e6c1a2d3")), ErrorType::Leak);
+        assert_eq!(error_type(Path::new("/a/timeout-e6c1")), ErrorType::Timeout);
+        assert_eq!(error_type(Path::new("/a/oom-e6c1")), ErrorType::OutOfMemory);
+        assert_eq!(error_type(Path::new("/a/leaky")), ErrorType::Crash);
+        assert_eq!(
+            serde_json::to_value(ErrorType::UndefinedBehavior).unwrap(),
+            "RUNTIME_ERROR"
+        );
+    }
+
+    #[test]
+    fn error_types_of_the_cifuzz_cli() {
+        let project = tempfile::tempdir().unwrap();
+        let input_file = project.path().join("leak-e6c1a2d3");
+        std::fs::write(&input_file, b"FUZZING").unwrap();
+        let leak = Finding::new(
+            &report(&input_file),
+            project.path(),
+            project.path(),
+            metadata(),
+        )
+        .unwrap();
+        let json = serde_json::to_value(&leak).unwrap();
+        assert_eq!(json["type"], "WARNING");
+        assert_eq!(
+            json["more_details"],
+            serde_json::json!({"id": "memory-leak", "name": "Memory Leak"})
+        );
+        let finding: Finding = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(finding.error_type, ErrorType::Leak);
+
+        // Findings of the cifuzz CLI without more details are crashes
+        let mut json = json;
+        json.as_object_mut().unwrap().remove("more_details");
+        let finding: Finding = serde_json::from_value(json).unwrap();
+        assert_eq!(finding.error_type, ErrorType::Crash);
+    }
+
+    #[test]
+    fn integer_overflows() {
+        let project = tempfile::tempdir().unwrap();
+        let input_file = project.path().join("crash-e6c1a2d3");
+        std::fs::write(&input_file, "a").unwrap();
+        let mut report = report(&input_file);
+        report.panic_message = Some("attempt to multiply with overflow".to_string());
+        let finding = Finding::new(&report, project.path(), project.path(), metadata()).unwrap();
+        assert_eq!(finding.error_type, ErrorType::IntegerOverflow);
+        let json = serde_json::to_value(&finding).unwrap();
+        assert_eq!(json["type"], "RUNTIME_ERROR");
+        assert_eq!(json["more_details"]["id"], "integer-overflow");
+        assert_eq!(json["details"], "attempt to multiply with overflow");
+        assert!(is_overflow_message("attempt to shift left with overflow"));
+        assert!(!is_overflow_message("attempt to divide by zero"));
+    }
+
+    #[test]
+    fn deduplicate_findings() {
+        let project = tempfile::tempdir().unwrap();
+        let new_finding = |hash: &str, line: u32| {
+            let input_file = project.path().join(format!("crash-{hash}"));
+            std::fs::write(&input_file, hash).unwrap();
+            let mut report = report(&input_file);
+            report.stack_trace[0].line = line;
+            Finding::new(&report, project.path(), project.path(), metadata()).unwrap()
+        };
+
+        let first = new_finding("aaaa", 14);
+        assert_eq!(first.find_duplicate(project.path()).unwrap(), None);
+        let dir = first.save(project.path()).unwrap();
+
+        // The same panic reached with another input
+        let second = new_finding("bbbb", 14);
+        assert_eq!(second.dedup_token, first.dedup_token);
+        assert_eq!(second.find_duplicate(project.path()).unwrap(), Some(dir));
+
+        // Another panic in the same function
+        let third = new_finding("cccc", 20);
+        assert_ne!(third.dedup_token, first.dedup_token);
+        assert_eq!(third.find_duplicate(project.path()).unwrap(), None);
+    }
+
+    #[test]
+    fn dedup_token_without_stack_trace() {
+        assert_eq!(
+            dedup_token(&[], "out-of-memory"),
+            dedup_token(&[], "out-of-memory")
+        );
+        assert_ne!(
+            dedup_token(&[], "out-of-memory"),
+            dedup_token(&[], "timeout")
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Resolving the function passed to `--focus-function` to the name by
+//! which libFuzzer finds it.
+//!
+//! libFuzzer compares `-focus_function` to the names of the functions
+//! of the instrumented code, as symbolized by the sanitizer runtime
+//! with llvm-symbolizer. These names are the demangled symbols, which
+//! for the legacy mangling scheme end with the hash, e.g.
+//! `cargo_example::explore_me::explore_me::h5d2b6140f0efc0bf`. The
+//! function is looked up by its path in the symbols of the test
+//! executable, so that it can be passed without the hash and the crate.
+
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::coverage;
+
+/// Returns the name of the function in the executable by which
+/// libFuzzer finds it. The function is given by its path, or a suffix
+/// of it, e.g. "explore_me::explore_me".
+pub fn resolve(executable: &Path, function: &str) -> Result<String> {
+    // The symbols are demangled below, so the nm of binutils does as
+    // well if the LLVM tools aren't installed
+    let nm = coverage::llvm_tool("llvm-nm").unwrap_or_else(|_| "nm".into());
+    let output = Command::new(&nm)
+        .arg("--defined-only")
+        .arg(executable)
+        .output()
+        .with_context(|| format!("failed to execute {}", nm.display()))?;
+    if !output.status.success() {
+        bail!(
+            "{} failed to list the symbols of {}: {}",
+            nm.display(),
+            executable.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let symbols = String::from_utf8_lossy(&output.stdout);
+    let mut candidates = find(&symbols, function);
+    candidates.dedup();
+    match candidates.as_slice() {
+        [] => bail!(
+            "The function {function} isn't part of the fuzz test. Functions which are inlined \
+             into all their callers, e.g. small ones in release builds, can't be focused"
+        ),
+        [name] => Ok(name.clone()),
+        names => bail!(
+            "The function {function} is ambiguous, specify one of:\n{}",
+            names.join("\n")
+        ),
+    }
+}
+
+/// Returns the symbolized names of the functions in the output of nm
+/// whose path is or ends with the given one, sorted.
+fn find(symbols: &str, function: &str) -> Vec<String> {
+    let suffix = format!("::{function}");
+    let mut names: Vec<String> = symbols
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (_address, kind, symbol) = (fields.next()?, fields.next()?, fields.next()?);
+            // Only functions, i.e. symbols in the text section
+            if !kind.eq_ignore_ascii_case("t") {
+                return None;
+            }
+            let demangled = rustc_demangle::try_demangle(symbol).ok()?;
+            let path = format!("{demangled:#}");
+            let name = symbolized_name(symbol);
+            (path == function || path.ends_with(&suffix) || name == function).then_some(name)
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+/// The name of a symbol as llvm-symbolizer prints it, with the hash of
+/// the legacy mangling scheme but without the crate disambiguators of
+/// the v0 scheme.
+fn symbolized_name(symbol: &str) -> String {
+    let demangled = rustc_demangle::demangle(symbol);
+    if symbol.starts_with("_R") {
+        format!("{demangled:#}")
+    } else {
+        demangled.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYMBOLS: &str = "\
+00000000001a5e60 t _RNvNtCs1t8GjPnQGfd_13cargo_example10explore_me10explore_me
+00000000001a51e0 t _RNvNtNtCs1t8GjPnQGfd_13cargo_example10explore_me5testss_15test_explore_me
+0000000000187e30 T _ZN13cargo_example6parser5parse17hc51f69dd9266f522E
+0000000000187f30 t _ZN4util5parse17h0123456789abcdefE
+00000000002b1000 d _ZN13cargo_example6parser5TABLE17h1111111111111111E
+0000000000067890 T LLVMFuzzerRunDriver
+";
+
+    #[test]
+    fn find_functions() {
+        assert_eq!(
+            find(SYMBOLS, "explore_me::explore_me"),
+            ["cargo_example::explore_me::explore_me"]
+        );
+        assert_eq!(
+            find(SYMBOLS, "cargo_example::explore_me::explore_me"),
+            ["cargo_example::explore_me::explore_me"]
+        );
+        assert_eq!(
+            find(SYMBOLS, "parser::parse"),
+            ["cargo_example::parser::parse::hc51f69dd9266f522"]
+        );
+        assert_eq!(
+            find(SYMBOLS, "parse"),
+            [
+                "cargo_example::parser::parse::hc51f69dd9266f522",
+                "util::parse::h0123456789abcdef"
+            ]
+        );
+        // The name printed for an ambiguous function selects it
+        assert_eq!(
+            find(SYMBOLS, "util::parse::h0123456789abcdef"),
+            ["util::parse::h0123456789abcdef"]
+        );
+        assert!(find(SYMBOLS, "parser::TABLE").is_empty());
+        assert!(find(SYMBOLS, "me::explore_me").is_empty());
+    }
+}
@@ -0,0 +1,889 @@
+//! Generating fuzz tests from the signatures of the functions of a
+//! crate.
+//!
+//! The modules of the crate are parsed starting at its root file. Every
+//! public function whose parameters can all be decoded from the input
+//! of the fuzzer is a candidate, e.g.
+//!
+//! ```text
+//! pub fn explore_me(a: i64, b: i64, c: &str)
+//! ```
+//!
+//! whose fuzz test takes an `(i64, i64, String)` and calls the function
+//! with its fields. Supported are the types implementing `Arbitrary`
+//! without a lifetime: primitives, strings, collections and tuples of
+//! them and the types of the crate deriving `Arbitrary`, which are
+//! passed by value or by reference.
+//!
+//! The fuzz test of a binary executes a copy of its `main` function
+//! with a `cifuzz::cli::Invocation`, in which the calls reading the
+//! command line and stdin of the process or exiting it are replaced by
+//! those of the runtime.
+
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{
+    Attribute, Expr, ExprCall, ExprMethodCall, ExprPath, FnArg, GenericArgument, GenericParam,
+    ImplItem, Item, Lit, Pat, PathArguments, ReturnType, Signature, Type, Visibility,
+};
+
+use crate::log;
+
+/// Primitive types and types of the standard library which are in the
+/// prelude and implement `Arbitrary`.
+const VALUE_TYPES: &[&str] = &[
+    "bool", "char", "f32", "f64", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32",
+    "u64", "u128", "usize", "String",
+];
+
+/// Generic types of the prelude which implement `Arbitrary` if their
+/// element type does.
+const CONTAINER_TYPES: &[&str] = &["Box", "Option", "Vec"];
+
+/// A function for which a fuzz test can be generated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    /// The path of the function relative to the crate root, e.g.
+    /// `parser::Parser::parse`
+    pub path: String,
+    params: Vec<Param>,
+    returns_value: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Param {
+    name: String,
+    /// The type which the fuzz test decodes from its input
+    ty: String,
+    passing: Passing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Passing {
+    Value,
+    Ref,
+    RefMut,
+}
+
+/// A module of the crate with the items declared in it.
+struct Module {
+    path: Vec<String>,
+    items: Vec<Item>,
+    /// Whether the public items of the module can be accessed from the
+    /// crate root, where the fuzz tests are generated
+    visible: bool,
+}
+
+/// A type of the crate deriving `Arbitrary`.
+struct ArbitraryType {
+    module: Vec<String>,
+    name: String,
+}
+
+/// Returns the candidates of the crate with the given root file, e.g.
+/// `src/lib.rs`, with the modules in the order of their declaration.
+pub fn find_candidates(crate_root: &Path) -> Result<Vec<Candidate>> {
+    let mut modules = Vec::new();
+    let dir = crate_root.parent().unwrap_or(Path::new("."));
+    load_module(crate_root, dir, Vec::new(), true, &mut modules)?;
+
+    let mut types = Vec::new();
+    for module in modules.iter().filter(|m| m.visible) {
+        for item in &module.items {
+            let (attrs, vis, ident, generics) = match item {
+                Item::Struct(s) => (&s.attrs, &s.vis, &s.ident, &s.generics),
+                Item::Enum(e) => (&e.attrs, &e.vis, &e.ident, &e.generics),
+                _ => continue,
+            };
+            if is_public(vis) && generics.params.is_empty() && derives_arbitrary(attrs) {
+                types.push(ArbitraryType {
+                    module: module.path.clone(),
+                    name: ident.to_string(),
+                });
+            }
+        }
+    }
+
+    let mut candidates = Vec::new();
+    for module in modules.iter().filter(|m| m.visible) {
+        let resolver = Resolver {
+            module: &module.path,
+            types: &types,
+        };
+        for item in &module.items {
+            match item {
+                Item::Fn(func) if is_public(&func.vis) && !is_cfg_test(&func.attrs) => {
+                    let path = item_path(&module.path, &[func.sig.ident.to_string()]);
+                    candidates.extend(candidate(path, &func.sig, &resolver));
+                }
+                Item::Impl(imp) if imp.trait_.is_none() && imp.generics.params.is_empty() => {
+                    let Some(self_ty) = plain_ident(&imp.self_ty) else {
+                        continue;
+                    };
+                    if !declares_public_type(&module.items, &self_ty) {
+                        continue;
+                    }
+                    for item in &imp.items {
+                        let ImplItem::Fn(func) = item else {
+                            continue;
+                        };
+                        if is_public(&func.vis) {
+                            let path = item_path(
+                                &module.path,
+                                &[self_ty.clone(), func.sig.ident.to_string()],
+                            );
+                            candidates.extend(candidate(path, &func.sig, &resolver));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(candidates)
+}
+
+/// Parses the module in `file` and its submodules. The files of the
+/// submodules declared as `mod foo;` are looked up in `dir`.
+fn load_module(
+    file: &Path,
+    dir: &Path,
+    path: Vec<String>,
+    visible: bool,
+    modules: &mut Vec<Module>,
+) -> Result<()> {
+    let source = std::fs::read_to_string(file)
+        .with_context(|| format!("failed to read {}", file.display()))?;
+    let items = match syn::parse_file(&source) {
+        Ok(parsed) => parsed.items,
+        // The compiler reports the errors, if the file is compiled at
+        // all
+        Err(err) => {
+            log::debug!("Failed to parse {}: {err}", file.display());
+            return Ok(());
+        }
+    };
+    add_module(items, dir, path, visible, modules)
+}
+
+fn add_module(
+    items: Vec<Item>,
+    dir: &Path,
+    path: Vec<String>,
+    visible: bool,
+    modules: &mut Vec<Module>,
+) -> Result<()> {
+    let index = modules.len();
+    modules.push(Module {
+        path: path.clone(),
+        items: Vec::new(),
+        visible,
+    });
+    for item in &items {
+        let Item::Mod(module) = item else {
+            continue;
+        };
+        if is_cfg_test(&module.attrs) || module.attrs.iter().any(|a| a.path().is_ident("path")) {
+            continue;
+        }
+        let name = module.ident.to_string();
+        let mut child_path = path.clone();
+        child_path.push(name.clone());
+        // Submodules of the crate root are accessible from everywhere in
+        // the crate, deeper ones only if they are public
+        let child_visible = visible && (path.is_empty() || is_public(&module.vis));
+        let child_dir = dir.join(&name);
+        match &module.content {
+            Some((_, items)) => add_module(
+                items.clone(),
+                &child_dir,
+                child_path,
+                child_visible,
+                modules,
+            )?,
+            None => {
+                let file = dir.join(format!("{name}.rs"));
+                let file = if file.is_file() {
+                    file
+                } else {
+                    child_dir.join("mod.rs")
+                };
+                if file.is_file() {
+                    load_module(&file, &child_dir, child_path, child_visible, modules)?;
+                }
+            }
+        }
+    }
+    modules[index].items = items;
+    Ok(())
+}
+
+fn is_cfg_test(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("cfg")
+            && attr
+                .meta
+                .require_list()
+                .is_ok_and(|list| list.tokens.to_string() == "test")
+    })
+}
+
+fn derives_arbitrary(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("derive")
+            && attr.meta.require_list().is_ok_and(|list| {
+                list.tokens
+                    .to_string()
+                    .split(',')
+                    .any(|derive| derive.trim().rsplit("::").next().unwrap().trim() == "Arbitrary")
+            })
+    })
+}
+
+/// Whether an item with the visibility can be accessed from everywhere
+/// in the crate, given that its module can.
+fn is_public(vis: &Visibility) -> bool {
+    match vis {
+        Visibility::Public(_) => true,
+        Visibility::Restricted(restricted) => restricted.path.is_ident("crate"),
+        Visibility::Inherited => false,
+    }
+}
+
+fn declares_public_type(items: &[Item], name: &str) -> bool {
+    items.iter().any(|item| match item {
+        Item::Struct(s) => s.ident == name && is_public(&s.vis),
+        Item::Enum(e) => e.ident == name && is_public(&e.vis),
+        _ => false,
+    })
+}
+
+fn item_path(module: &[String], names: &[String]) -> String {
+    module
+        .iter()
+        .chain(names)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// Returns the identifier of a type path without generic arguments.
+fn plain_ident(ty: &Type) -> Option<String> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    if path.qself.is_some() {
+        return None;
+    }
+    let ident = path.path.get_ident()?;
+    Some(ident.to_string())
+}
+
+/// Returns the candidate for the function with the signature, if it has
+/// parameters and all of them can be decoded.
+fn candidate(path: String, sig: &Signature, resolver: &Resolver) -> Option<Candidate> {
+    if sig.asyncness.is_some()
+        || sig.unsafety.is_some()
+        || sig.abi.is_some()
+        || sig.variadic.is_some()
+        || sig.inputs.is_empty()
+        || sig
+            .generics
+            .params
+            .iter()
+            .any(|param| !matches!(param, GenericParam::Lifetime(_)))
+    {
+        return None;
+    }
+    let mut params = Vec::new();
+    for (i, input) in sig.inputs.iter().enumerate() {
+        // Methods would need a receiver, which can't be decoded
+        let FnArg::Typed(arg) = input else {
+            return None;
+        };
+        let name = match &*arg.pat {
+            Pat::Ident(ident) if ident.ident != "input" => ident.ident.to_string(),
+            _ => format!("arg{i}"),
+        };
+        let (ty, passing) = match &*arg.ty {
+            Type::Reference(reference) => {
+                let ty = match &*reference.elem {
+                    Type::Path(path) if path.path.is_ident("str") => "String".to_string(),
+                    Type::Slice(slice) => format!("Vec<{}>", resolver.value_type(&slice.elem)?),
+                    elem => resolver.value_type(elem)?,
+                };
+                let passing = match reference.mutability {
+                    Some(_) => Passing::RefMut,
+                    None => Passing::Ref,
+                };
+                (ty, passing)
+            }
+            ty => (resolver.value_type(ty)?, Passing::Value),
+        };
+        params.push(Param { name, ty, passing });
+    }
+    Some(Candidate {
+        path,
+        params,
+        returns_value: !matches!(sig.output, ReturnType::Default),
+    })
+}
+
+/// Resolves the types of the parameters of the functions in a module.
+struct Resolver<'a> {
+    module: &'a [String],
+    types: &'a [ArbitraryType],
+}
+
+impl Resolver<'_> {
+    /// Returns the type as written in the fuzz test, if it implements
+    /// `Arbitrary` and doesn't borrow.
+    fn value_type(&self, ty: &Type) -> Option<String> {
+        match ty {
+            Type::Path(path) if path.qself.is_none() => {
+                let segments: Vec<String> = path
+                    .path
+                    .segments
+                    .iter()
+                    .map(|s| s.ident.to_string())
+                    .collect();
+                let last = path.path.segments.last()?;
+                match &last.arguments {
+                    PathArguments::None if segments.len() == 1 => {
+                        let name = &segments[0];
+                        if VALUE_TYPES.contains(&name.as_str()) {
+                            return Some(name.clone());
+                        }
+                        self.resolve(name)
+                    }
+                    PathArguments::None if segments[0] == "crate" => {
+                        let (name, module) = segments[1..].split_last()?;
+                        self.types
+                            .iter()
+                            .any(|t| t.module == module && t.name == *name)
+                            .then(|| segments.join("::"))
+                    }
+                    PathArguments::AngleBracketed(args)
+                        if segments.len() == 1
+                            && CONTAINER_TYPES.contains(&segments[0].as_str())
+                            && args.args.len() == 1 =>
+                    {
+                        let GenericArgument::Type(elem) = &args.args[0] else {
+                            return None;
+                        };
+                        Some(format!("{}<{}>", segments[0], self.value_type(elem)?))
+                    }
+                    _ => None,
+                }
+            }
+            Type::Tuple(tuple) if !tuple.elems.is_empty() => {
+                let elems = tuple
+                    .elems
+                    .iter()
+                    .map(|elem| self.value_type(elem))
+                    .collect::<Option<Vec<_>>>()?;
+                match elems.as_slice() {
+                    [elem] => Some(format!("({elem},)")),
+                    _ => Some(format!("({})", elems.join(", "))),
+                }
+            }
+            Type::Array(array) => {
+                let Expr::Lit(len) = &array.len else {
+                    return None;
+                };
+                let Lit::Int(len) = &len.lit else {
+                    return None;
+                };
+                Some(format!(
+                    "[{}; {}]",
+                    self.value_type(&array.elem)?,
+                    len.base10_digits()
+                ))
+            }
+            Type::Paren(paren) => self.value_type(&paren.elem),
+            _ => None,
+        }
+    }
+
+    /// Returns the path of the type of the crate deriving `Arbitrary`
+    /// which is named `name` in the module. Types of the module itself
+    /// shadow the ones of other modules, which are only considered if
+    /// the name is unique.
+    fn resolve(&self, name: &str) -> Option<String> {
+        let mut types = self.types.iter().filter(|t| t.name == name);
+        let found = match types.clone().find(|t| t.module == self.module) {
+            Some(found) => found,
+            None => {
+                let found = types.next()?;
+                if types.next().is_some() {
+                    return None;
+                }
+                found
+            }
+        };
+        Some(
+            std::iter::once("crate")
+                .chain(found.module.iter().map(String::as_str))
+                .chain([found.name.as_str()])
+                .collect::<Vec<_>>()
+                .join("::"),
+        )
+    }
+}
+
+impl Candidate {
+    /// Whether the candidate is selected by the path of a function given
+    /// by the user, which can omit leading modules.
+    pub fn matches(&self, function: &str) -> bool {
+        let function = function.trim_start_matches("crate::");
+        self.path == function || self.path.ends_with(&format!("::{function}"))
+    }
+}
+
+/// Returns the names of the fuzz tests for the candidates. The name is
+/// derived from the name of the function (and its type, for associated
+/// functions), preceded by the names of its modules if that is
+/// ambiguous.
+pub fn fuzz_test_names(candidates: &[Candidate]) -> Vec<String> {
+    let short = |c: &Candidate| {
+        let mut parts: Vec<&str> = c.path.rsplit("::").take(2).collect();
+        parts.reverse();
+        // Keep the type of associated functions, which is capitalized
+        if parts.len() == 2 && !parts[0].starts_with(char::is_uppercase) {
+            parts.remove(0);
+        }
+        fuzz_test_name(&parts)
+    };
+    let mut seen = BTreeSet::new();
+    let duplicates: BTreeSet<String> = candidates
+        .iter()
+        .map(short)
+        .filter(|name| !seen.insert(name.clone()))
+        .collect();
+    candidates
+        .iter()
+        .map(|c| {
+            let name = short(c);
+            if duplicates.contains(&name) {
+                fuzz_test_name(&c.path.split("::").collect::<Vec<_>>())
+            } else {
+                name
+            }
+        })
+        .collect()
+}
+
+fn fuzz_test_name(parts: &[&str]) -> String {
+    let mut name = String::new();
+    for part in parts {
+        let mut previous = None;
+        for c in part.chars() {
+            // CamelCase names of types are converted to snake case
+            if c.is_uppercase() && previous.is_some_and(|p: char| p.is_lowercase()) {
+                name.push('_');
+            }
+            name.push(c.to_ascii_lowercase());
+            previous = Some(c);
+        }
+        name.push('_');
+    }
+    name.push_str("fuzz_test");
+    name
+}
+
+/// Returns the source of the fuzz test named `name` for the candidate.
+pub fn fuzz_test_source(candidate: &Candidate, name: &str) -> String {
+    let params = &candidate.params;
+    let arg = |param: &Param| match param.passing {
+        Passing::Value => param.name.clone(),
+        Passing::Ref => format!("&{}", param.name),
+        Passing::RefMut => format!("&mut {}", param.name),
+    };
+    let binding = |param: &Param| match param.passing {
+        Passing::RefMut => format!("mut {}", param.name),
+        _ => param.name.clone(),
+    };
+
+    let mut body = String::new();
+    let input = match params.as_slice() {
+        // Byte slices are taken from the input of the fuzzer as is
+        [param] if param.ty == "Vec<u8>" && param.passing == Passing::Ref => {
+            format!("{}: &[u8]", param.name)
+        }
+        [param] => format!("{}: {}", binding(param), param.ty),
+        _ => {
+            let types: Vec<&str> = params.iter().map(|p| p.ty.as_str()).collect();
+            let bindings: Vec<String> = params.iter().map(binding).collect();
+            body.push_str(&format!("    let ({}) = input;\n", bindings.join(", ")));
+            format!("input: ({})", types.join(", "))
+        }
+    };
+    let args: Vec<String> = match params.as_slice() {
+        [param] if param.ty == "Vec<u8>" && param.passing == Passing::Ref => {
+            vec![param.name.clone()]
+        }
+        _ => params.iter().map(arg).collect(),
+    };
+    let call = format!("crate::{}({})", candidate.path, args.join(", "));
+    if candidate.returns_value {
+        body.push_str(&format!("    let _result = {call};\n"));
+    } else {
+        body.push_str(&format!("    {call};\n"));
+    }
+
+    format!(
+        "use cifuzz::fuzz_test;
+
+#[fuzz_test]
+fn {name}({input}) {{
+    // Generated by 'cargo cifuzz generate' from the signature of
+    // {path}. Panics are reported as findings, check the
+    // result of the call with assertions to find other bugs.
+{body}}}
+",
+        path = candidate.path,
+    )
+}
+
+/// The functions of the standard library which `main` must call from
+/// `cifuzz::cli` instead, by the end of their path.
+const CLI_FUNCTIONS: &[(&[&str], &str)] = &[
+    (&["env", "args"], "::cifuzz::cli::args"),
+    (&["env", "args_os"], "::cifuzz::cli::args_os"),
+    (&["io", "stdin"], "::cifuzz::cli::stdin"),
+    (&["process", "exit"], "::cifuzz::cli::exit"),
+];
+
+/// Handles an error of clap like `clap::Error::exit`, which would exit
+/// the process.
+const CLAP_EXIT: &str =
+    ".unwrap_or_else(|err| { let _ = err.print(); ::cifuzz::cli::exit(err.exit_code()) })";
+
+/// Returns the name of the fuzz test of the binary.
+pub fn cli_fuzz_test_name(bin: &str) -> String {
+    let mut name: String = bin
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' => c,
+            'A'..='Z' => c.to_ascii_lowercase(),
+            _ => '_',
+        })
+        .collect();
+    name.push_str("_cli_fuzz_test");
+    name
+}
+
+/// Returns the source of the fuzz test named `name` of the binary
+/// `bin`, whose root file `root` contains `source`. The `main` function
+/// is copied with the replaced calls, the other items of the root file
+/// are imported by the fuzz test from its parent module.
+pub fn cli_fuzz_test_source(bin: &str, root: &str, source: &str, name: &str) -> Result<String> {
+    /// The replacements in the source of `main`, by the byte ranges of
+    /// the replaced code.
+    #[derive(Default)]
+    struct Visitor {
+        replacements: Vec<(Range<usize>, String)>,
+    }
+
+    impl Visit<'_> for Visitor {
+        fn visit_expr_path(&mut self, expr: &ExprPath) {
+            let segments: Vec<String> = expr
+                .path
+                .segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect();
+            let replacement = CLI_FUNCTIONS.iter().find(|(path, _)| {
+                segments.len() >= path.len() && segments[segments.len() - path.len()..] == **path
+            });
+            if let Some((_, replacement)) = replacement {
+                self.replacements
+                    .push((expr.span().byte_range(), replacement.to_string()));
+            }
+            visit::visit_expr_path(self, expr);
+        }
+
+        fn visit_expr_call(&mut self, call: &ExprCall) {
+            // Parser::parse and Parser::try_parse of clap, which parse
+            // the arguments of the process
+            if let Expr::Path(func) = &*call.func {
+                let segments = &func.path.segments;
+                let method = segments.last().filter(|_| segments.len() > 1);
+                if let Some(method) = method.filter(|_| call.args.is_empty()) {
+                    let range =
+                        method.ident.span().byte_range().start..call.span().byte_range().end;
+                    if method.ident == "parse" {
+                        self.replacements.push((
+                            range,
+                            format!("try_parse_from(::cifuzz::cli::args_os()){CLAP_EXIT}"),
+                        ));
+                    } else if method.ident == "try_parse" {
+                        self.replacements.push((
+                            range,
+                            "try_parse_from(::cifuzz::cli::args_os())".to_string(),
+                        ));
+                    }
+                }
+            }
+            visit::visit_expr_call(self, call);
+        }
+
+        fn visit_expr_method_call(&mut self, call: &ExprMethodCall) {
+            // Command::get_matches and Command::try_get_matches of clap
+            if call.args.is_empty() && call.turbofish.is_none() {
+                let range = call.method.span().byte_range().start..call.span().byte_range().end;
+                if call.method == "get_matches" {
+                    self.replacements.push((
+                        range,
+                        format!("try_get_matches_from(::cifuzz::cli::args_os()){CLAP_EXIT}"),
+                    ));
+                } else if call.method == "try_get_matches" {
+                    self.replacements.push((
+                        range,
+                        "try_get_matches_from(::cifuzz::cli::args_os())".to_string(),
+                    ));
+                }
+            }
+            visit::visit_expr_method_call(self, call);
+        }
+    }
+
+    let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+    let file: syn::File =
+        syn::parse_str(source).with_context(|| format!("failed to parse {root}"))?;
+    let Some(main) = file.items.iter().find_map(|item| match item {
+        Item::Fn(f) if f.sig.ident == "main" => Some(f),
+        _ => None,
+    }) else {
+        bail!("{root} has no main function");
+    };
+    let range = main.span().byte_range();
+    let mut visitor = Visitor::default();
+    visitor.visit_item_fn(main);
+    let mut main_source = source[range.clone()].to_string();
+    // Back to front, so that the ranges of the remaining replacements
+    // stay valid
+    visitor.replacements.sort_by_key(|(r, _)| r.start);
+    for (r, replacement) in visitor.replacements.iter().rev() {
+        main_source.replace_range(r.start - range.start..r.end - range.start, replacement);
+    }
+
+    Ok(format!(
+        "//! The fuzz test of the command line of the binary {bin}, generated
+//! by 'cargo cifuzz generate --bin {bin}'. It executes the copy of the
+//! main function of {root} below with the arguments and stdin decoded
+//! from the input, see cifuzz::cli. Delete this file and generate it
+//! again when the main function changes.
+
+use cifuzz::{{fuzz_test, FuzzedDataProvider}};
+
+#[allow(unused_imports)]
+use super::*;
+
+#[fuzz_test]
+fn {name}(fdp: &mut FuzzedDataProvider) {{
+    let invocation: cifuzz::cli::Invocation = fdp.consume();
+    invocation.run({bin:?}, main);
+}}
+
+{main_source}
+"
+    ))
+}
+
+/// Returns the root file of the library or, if there is none, the
+/// binary of the package in the directory.
+pub fn crate_root(package_dir: &Path) -> Option<PathBuf> {
+    ["lib.rs", "main.rs"]
+        .iter()
+        .map(|file| package_dir.join("src").join(file))
+        .find(|file| file.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, file: &str, content: &str) {
+        let path = dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn paths(candidates: &[Candidate]) -> Vec<&str> {
+        candidates.iter().map(|c| c.path.as_str()).collect()
+    }
+
+    #[test]
+    fn candidates_of_crate() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            dir.path(),
+            "lib.rs",
+            "mod explore_me;\npub mod parser;\n#[cfg(test)]\nmod tests;\n\
+             pub fn root(data: &[u8]) {}\nfn private_root(n: u32) {}\n",
+        );
+        write(
+            dir.path(),
+            "explore_me.rs",
+            "pub fn explore_me(a: i64, b: i64, c: &str) {}\n\
+             fn private(a: i64) {}\n\
+             pub fn generic<T>(t: T) {}\n\
+             pub fn no_params() {}\n\
+             pub fn borrowing(s: Option<&str>) {}\n",
+        );
+        write(
+            dir.path(),
+            "parser/mod.rs",
+            "mod hidden;\npub mod ast;\n\
+             #[derive(Debug, arbitrary::Arbitrary)]\npub struct Config { depth: u8 }\n\
+             pub struct Parser;\n\
+             impl Parser {\n    pub fn new(config: Config) -> Self { Parser }\n    \
+             pub fn parse(&self, s: &str) {}\n}\n\
+             pub fn parse_all(inputs: &mut Vec<String>, config: &Config) -> usize { 0 }\n",
+        );
+        write(dir.path(), "parser/hidden.rs", "pub fn hidden(a: u8) {}\n");
+        write(
+            dir.path(),
+            "parser/ast.rs",
+            "pub fn from_config(config: Config, sizes: [(u8, char); 4]) {}\n",
+        );
+        write(dir.path(), "tests.rs", "pub fn test(a: u8) {}\n");
+
+        let candidates = find_candidates(&dir.path().join("lib.rs")).unwrap();
+        assert_eq!(
+            paths(&candidates),
+            [
+                "root",
+                "explore_me::explore_me",
+                "parser::Parser::new",
+                "parser::parse_all",
+                "parser::ast::from_config",
+            ]
+        );
+        assert_eq!(
+            candidates[4].params[0].ty, "crate::parser::Config",
+            "types deriving Arbitrary are resolved in other modules"
+        );
+        assert_eq!(candidates[4].params[1].ty, "[(u8, char); 4]");
+
+        assert!(candidates[1].matches("explore_me"));
+        assert!(candidates[2].matches("Parser::new"));
+        assert!(candidates[2].matches("crate::parser::Parser::new"));
+        assert!(!candidates[3].matches("all"));
+    }
+
+    #[test]
+    fn generated_fuzz_tests() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            dir.path(),
+            "main.rs",
+            "mod a;\nmod b;\nfn main() {}\n\
+             pub fn bytes(data: &[u8]) -> bool { true }\n\
+             pub fn string(s: &str) {}\n\
+             pub struct Stack;\n\
+             impl Stack { pub fn from_values(values: &mut Vec<u16>) {} }\n",
+        );
+        write(dir.path(), "a.rs", "pub fn parse(s: String, n: u8) {}\n");
+        write(
+            dir.path(),
+            "b.rs",
+            "pub fn parse(input: &[u8], n: &mut u8) {}\n",
+        );
+
+        let candidates = find_candidates(&dir.path().join("main.rs")).unwrap();
+        let names = fuzz_test_names(&candidates);
+        assert_eq!(
+            names,
+            [
+                "bytes_fuzz_test",
+                "string_fuzz_test",
+                "stack_from_values_fuzz_test",
+                "a_parse_fuzz_test",
+                "b_parse_fuzz_test",
+            ]
+        );
+        let sources: Vec<String> = candidates
+            .iter()
+            .zip(&names)
+            .map(|(c, name)| fuzz_test_source(c, name))
+            .collect();
+        assert!(sources[3].contains(
+            "fn a_parse_fuzz_test(input: (String, u8)) {\n\
+             \x20   // Generated by 'cargo cifuzz generate' from the signature of\n\
+             \x20   // a::parse. Panics are reported as findings, check the\n\
+             \x20   // result of the call with assertions to find other bugs.\n\
+             \x20   let (s, n) = input;\n\
+             \x20   crate::a::parse(s, n);\n}\n"
+        ));
+        assert!(sources[4].contains("let (arg0, mut n) = input;"));
+        assert!(sources[4].contains("crate::b::parse(&arg0, &mut n);"));
+        assert!(sources[0].contains("fn bytes_fuzz_test(data: &[u8]) {"));
+        assert!(sources[0].contains("let _result = crate::bytes(data);"));
+        assert!(sources[1].contains("fn string_fuzz_test(s: String) {"));
+        assert!(sources[1].contains("    crate::string(&s);\n"));
+        assert!(sources[2].contains("fn stack_from_values_fuzz_test(mut values: Vec<u16>) {"));
+        assert!(sources[2].contains("crate::Stack::from_values(&mut values);"));
+        assert_eq!(
+            fuzz_test_name(&["HTTPServer", "handle"]),
+            "httpserver_handle_fuzz_test"
+        );
+    }
+
+    #[test]
+    fn cli_fuzz_test_of_main() {
+        let source = r#"use std::io::Read;
+use std::{env, process};
+
+use clap::Parser;
+
+/// The entry point
+fn main() {
+    let cli = Cli::parse();
+    let path = env::args().nth(1);
+    let mut input = String::new();
+    if std::io::stdin().read_to_string(&mut input).is_err() {
+        process::exit(1);
+    }
+    let matches = command().get_matches();
+    run(cli, path, input, matches);
+}
+
+fn run() {}
+"#;
+        assert_eq!(cli_fuzz_test_name("my-Tool"), "my_tool_cli_fuzz_test");
+        let fuzz_test =
+            cli_fuzz_test_source("my-tool", "src/main.rs", source, "my_tool_cli_fuzz_test")
+                .unwrap();
+        assert!(fuzz_test.contains(
+            "fn my_tool_cli_fuzz_test(fdp: &mut FuzzedDataProvider) {\n\
+             \x20   let invocation: cifuzz::cli::Invocation = fdp.consume();\n\
+             \x20   invocation.run(\"my-tool\", main);\n}\n"
+        ));
+        let (_, main) = fuzz_test.split_once("}\n\n").unwrap();
+        assert_eq!(
+            main,
+            "/// The entry point
+fn main() {
+    let cli = Cli::try_parse_from(::cifuzz::cli::args_os()).unwrap_or_else(|err| { let _ = err.print(); ::cifuzz::cli::exit(err.exit_code()) });
+    let path = ::cifuzz::cli::args().nth(1);
+    let mut input = String::new();
+    if ::cifuzz::cli::stdin().read_to_string(&mut input).is_err() {
+        ::cifuzz::cli::exit(1);
+    }
+    let matches = command().try_get_matches_from(::cifuzz::cli::args_os()).unwrap_or_else(|err| { let _ = err.print(); ::cifuzz::cli::exit(err.exit_code()) });
+    run(cli, path, input, matches);
+}
+"
+        );
+        let err = cli_fuzz_test_source("lib", "src/lib.rs", "fn run() {}", "lib_cli_fuzz_test")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "src/lib.rs has no main function");
+    }
+}
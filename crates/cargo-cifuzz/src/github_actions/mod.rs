@@ -0,0 +1,82 @@
+//! The template of the GitHub Actions workflow which fuzzes the fuzz
+//! tests of a project, on pull requests only the ones affected by the
+//! changes.
+
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::workspace::CIFUZZ_GIT_URL;
+
+const WORKFLOW_TEMPLATE: &str = include_str!("workflow.yml.tmpl");
+
+/// The settings of the workflow.
+#[derive(Debug)]
+pub struct Workflow {
+    /// The default branch, whose pushes fuzz all fuzz tests
+    pub branch: String,
+    /// The time every fuzz test runs for, e.g. "5m"
+    pub timeout: String,
+}
+
+/// Writes the workflow to the file, which is only overwritten with
+/// `force`.
+pub fn write(workflow: &Workflow, path: &Path, force: bool) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    let mut file = match std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .create_new(!force)
+        .open(path)
+    {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+            bail!(
+                "{} already exists, pass --force to overwrite it",
+                path.display()
+            )
+        }
+        Err(err) => {
+            return Err(err).with_context(|| format!("failed to create {}", path.display()))
+        }
+    };
+    file.write_all(render(workflow).as_bytes())
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+fn render(workflow: &Workflow) -> String {
+    WORKFLOW_TEMPLATE
+        .replace("{branch}", &workflow.branch)
+        .replace("{timeout}", &workflow.timeout)
+        .replace("{cifuzz_git_url}", CIFUZZ_GIT_URL)
+        .replace("{cifuzz_version}", env!("CARGO_PKG_VERSION"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_workflow() {
+        let workflow = render(&Workflow {
+            branch: "main".to_string(),
+            timeout: "5m".to_string(),
+        });
+        assert!(workflow.contains("    branches: [main]\n"));
+        assert!(workflow.contains("cargo cifuzz run --all --timeout 5m\n"));
+        assert!(workflow.contains("--changed-since origin/${{ github.base_ref }}"));
+        for placeholder in [
+            "{branch}",
+            "{timeout}",
+            "{cifuzz_git_url}",
+            "{cifuzz_version}",
+        ] {
+            assert!(!workflow.contains(placeholder), "{workflow}");
+        }
+    }
+}
@@ -0,0 +1,373 @@
+//! A standalone HTML report of the findings and the coverage of the
+//! fuzz tests, e.g. to attach to a ticket of a security review.
+//!
+//! Findings with the same dedup token are the same bug, so they're
+//! grouped and shown once with the stack trace of the newest finding.
+//! The frames of the stack trace in the project are shown with a
+//! snippet of their source, in which the line of the frame is
+//! highlighted. The report has no external resources.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::config::SeverityRule;
+use crate::finding::{Finding, StackFrame, Status};
+use crate::lcov::Summary;
+
+/// The number of lines shown before and after the line of a frame.
+const SNIPPET_CONTEXT: u32 = 3;
+/// The number of frames in the project which are shown with a snippet.
+const SNIPPET_FRAMES: usize = 3;
+/// The number of bytes of the crashing input which are shown.
+const INPUT_PREVIEW: usize = 256;
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em auto; max-width: 72em; color: #222; }
+h1, h2 { border-bottom: 1px solid #ddd; padding-bottom: .2em; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { text-align: left; padding: .3em .8em; border-bottom: 1px solid #eee; }
+.bug { border: 1px solid #ddd; border-radius: 4px; padding: 0 1em 1em; margin: 1.5em 0; }
+.badge { display: inline-block; border-radius: 3px; padding: 0 .4em; margin-right: .3em; font-size: .85em; background: #eee; }
+.high { background: #f8d0d0; }
+.medium { background: #fbe7c6; }
+.low { background: #e0ecf8; }
+pre { background: #f6f8fa; padding: .6em; overflow-x: auto; }
+.snippet { padding: 0; }
+.snippet span { display: block; padding: 0 .6em; }
+.snippet .hit { background: #fde2e2; font-weight: bold; }
+.frames { font-family: monospace; }
+";
+
+/// The findings with the same dedup token.
+struct Bug<'a> {
+    /// The newest finding first
+    findings: Vec<&'a Finding>,
+}
+
+/// Returns the HTML report of the findings and of the line coverage of
+/// the fuzz tests, by name. The sources of the snippets are read from
+/// the project directory.
+pub fn report(
+    project_dir: &Path,
+    findings: &[Finding],
+    coverage: &[(String, Summary)],
+    rules: &[SeverityRule],
+) -> String {
+    let mut bugs: Vec<Bug> = Vec::new();
+    let mut sorted: Vec<&Finding> = findings.iter().collect();
+    sorted.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    for finding in sorted {
+        match bugs
+            .iter_mut()
+            .find(|bug| bug.findings[0].dedup_token == finding.dedup_token)
+        {
+            Some(bug) => bug.findings.push(finding),
+            None => bugs.push(Bug {
+                findings: vec![finding],
+            }),
+        }
+    }
+    // Open bugs first, the most severe first
+    bugs.sort_by_key(|bug| {
+        let finding = bug.findings[0];
+        (
+            finding.status != Status::Open,
+            std::cmp::Reverse(finding.severity(rules)),
+        )
+    });
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>cifuzz report</title>\n");
+    let _ = writeln!(html, "<style>\n{STYLE}</style>\n</head>\n<body>");
+    html.push_str("<h1>cifuzz report</h1>\n");
+    let open = bugs
+        .iter()
+        .filter(|bug| bug.findings[0].status == Status::Open)
+        .count();
+    let _ = writeln!(
+        html,
+        "<p>{} {} in {} {}, {open} open. Generated by cargo-cifuzz {}.</p>",
+        bugs.len(),
+        if bugs.len() == 1 { "bug" } else { "bugs" },
+        findings.len(),
+        if findings.len() == 1 {
+            "finding"
+        } else {
+            "findings"
+        },
+        env!("CARGO_PKG_VERSION")
+    );
+
+    if !coverage.is_empty() {
+        html.push_str("<h2>Coverage</h2>\n<table>\n");
+        html.push_str(
+            "<tr><th>Fuzz test</th><th>Functions</th><th>Lines</th><th>Branches</th></tr>\n",
+        );
+        for (fuzz_test, summary) in coverage {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(fuzz_test),
+                summary.functions,
+                summary.lines,
+                summary.branches
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("<h2>Findings</h2>\n");
+    if bugs.is_empty() {
+        html.push_str("<p>No findings.</p>\n");
+    }
+    for bug in &bugs {
+        write_bug(&mut html, project_dir, bug, rules);
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn write_bug(html: &mut String, project_dir: &Path, bug: &Bug, rules: &[SeverityRule]) {
+    let finding = bug.findings[0];
+    let severity = finding.severity(rules).name();
+    html.push_str("<div class=\"bug\">\n");
+    let _ = writeln!(
+        html,
+        "<h3>{}: {}</h3>",
+        escape(finding.error_type.description()),
+        escape(&finding.details)
+    );
+    let _ = writeln!(
+        html,
+        "<p><span class=\"badge {severity}\">{severity} severity</span>\
+         <span class=\"badge\">{}</span>\
+         <span class=\"badge\">dedup token {}</span></p>",
+        finding.status.name(),
+        escape(&finding.dedup_token)
+    );
+    let _ = writeln!(
+        html,
+        "<p>Found by {} in {} {}, first on {}, last on {}: {}.</p>",
+        escape(&finding.metadata.fuzz_test),
+        bug.findings.len(),
+        if bug.findings.len() == 1 {
+            "finding"
+        } else {
+            "findings"
+        },
+        escape(&bug.findings[bug.findings.len() - 1].created_at),
+        escape(&finding.created_at),
+        bug.findings
+            .iter()
+            .map(|f| escape(&f.name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    if !finding.stack_trace.is_empty() {
+        html.push_str("<h4>Stack trace</h4>\n<div class=\"frames\">\n");
+        let mut snippets = 0;
+        for frame in &finding.stack_trace {
+            let _ = writeln!(html, "<div>{}</div>", escape(&frame_text(frame)));
+            if snippets < SNIPPET_FRAMES {
+                if let Some(snippet) = snippet(project_dir, frame) {
+                    html.push_str(&snippet);
+                    snippets += 1;
+                }
+            }
+        }
+        html.push_str("</div>\n");
+    }
+    if !finding.operations.is_empty() {
+        html.push_str("<h4>Operations</h4>\n<pre>");
+        for (i, operation) in finding.operations.iter().enumerate() {
+            let _ = writeln!(html, "{i}: {}", escape(operation));
+        }
+        html.push_str("</pre>\n");
+    }
+    if !finding.output.is_empty() {
+        html.push_str("<h4>Output of the fuzz test</h4>\n<pre>");
+        for line in &finding.output {
+            let _ = writeln!(html, "{}", escape(line));
+        }
+        html.push_str("</pre>\n");
+    }
+    let _ = writeln!(
+        html,
+        "<h4>Crashing input</h4>\n<p>{} bytes, stored in {}</p>\n<pre>{}</pre>",
+        finding.input_data.len(),
+        escape(&finding.input_file.display().to_string()),
+        escape(&hex_dump(&finding.input_data))
+    );
+    html.push_str("</div>\n");
+}
+
+fn frame_text(frame: &StackFrame) -> String {
+    if frame.source_file.is_empty() {
+        format!("#{} {}", frame.frame_number, frame.function)
+    } else {
+        format!(
+            "#{} {} at {}:{}:{}",
+            frame.frame_number, frame.function, frame.source_file, frame.line, frame.column
+        )
+    }
+}
+
+/// Returns the lines around the line of the frame, if its source file
+/// is in the project.
+fn snippet(project_dir: &Path, frame: &StackFrame) -> Option<String> {
+    // Paths outside of the project are absolute
+    if frame.source_file.is_empty()
+        || Path::new(&frame.source_file).is_absolute()
+        || frame.line == 0
+    {
+        return None;
+    }
+    let source = std::fs::read_to_string(project_dir.join(&frame.source_file)).ok()?;
+    let first = frame.line.saturating_sub(SNIPPET_CONTEXT).max(1);
+    let last = frame.line + SNIPPET_CONTEXT;
+    let mut snippet = String::from("<pre class=\"snippet\">");
+    for (number, line) in source
+        .lines()
+        .zip(1..)
+        .map(|(line, number)| (number, line))
+        .filter(|(number, _)| (first..=last).contains(number))
+    {
+        let class = if number == frame.line {
+            " class=\"hit\""
+        } else {
+            ""
+        };
+        let _ = write!(snippet, "<span{class}>{number:>5}  {}</span>", escape(line));
+    }
+    snippet.push_str("</pre>\n");
+    Some(snippet)
+}
+
+/// Formats the beginning of the data like `xxd`.
+fn hex_dump(data: &[u8]) -> String {
+    let mut dump = String::new();
+    for (i, chunk) in data.chunks(16).take(INPUT_PREVIEW / 16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        let _ = writeln!(dump, "{:08x}  {:<47}  {ascii}", i * 16, hex.join(" "));
+    }
+    if data.len() > INPUT_PREVIEW {
+        let _ = writeln!(dump, "... {} more bytes", data.len() - INPUT_PREVIEW);
+    }
+    dump
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::finding::{test_finding, StackFrame};
+
+    fn finding(name: &str, created_at: &str, dedup_token: &str) -> Finding {
+        let frame = |source_file: &str, line, column, frame_number, function: &str| StackFrame {
+            source_file: source_file.to_string(),
+            line,
+            column,
+            frame_number,
+            function: function.to_string(),
+        };
+        Finding {
+            input_data: b"FUZZING".to_vec(),
+            details: "branch <4> has been reached".to_string(),
+            created_at: created_at.to_string(),
+            stack_trace: vec![
+                frame(
+                    "src/explore_me.rs",
+                    5,
+                    9,
+                    0,
+                    "cargo_example::explore_me::explore_me",
+                ),
+                frame(
+                    "/rustc/library/core/src/ops/function.rs",
+                    250,
+                    5,
+                    1,
+                    "core::ops::function::FnOnce::call_once",
+                ),
+            ],
+            dedup_token: dedup_token.to_string(),
+            ..test_finding(name)
+        }
+    }
+
+    #[test]
+    fn html_report() {
+        let project = tempfile::tempdir().unwrap();
+        std::fs::create_dir(project.path().join("src")).unwrap();
+        std::fs::write(
+            project.path().join("src/explore_me.rs"),
+            "fn a() {}\n\nfn explore_me() {\n    if c {\n        panic!(\"branch 4\");\n    }\n}\n",
+        )
+        .unwrap();
+        let findings = [
+            finding("aaa", "2024-01-01T00:00:00Z", "0d09a0cb"),
+            finding("bbb", "2024-01-02T00:00:00Z", "0d09a0cb"),
+            finding("ccc", "2024-01-03T00:00:00Z", "ffff0000"),
+        ];
+        let summary = crate::lcov::parse("SF:src/explore_me.rs\nDA:1,1\nDA:3,0\nend_of_record\n")
+            .unwrap()
+            .summary();
+        let html = report(
+            project.path(),
+            &findings,
+            &[("my_fuzz_test".to_string(), summary)],
+            &[],
+        );
+
+        assert!(html.contains("<p>2 bugs in 3 findings, 2 open."), "{html}");
+        assert!(html.contains("<td>my_fuzz_test</td><td>0/0 (100.0%)</td><td>1/2 (50.0%)</td>"));
+        assert!(html.contains("<h3>crash: branch &lt;4&gt; has been reached</h3>"));
+        assert!(html.contains(
+            "in 2 findings, first on 2024-01-01T00:00:00Z, last on 2024-01-02T00:00:00Z: bbb, aaa."
+        ));
+        // The panic line is highlighted, the frame outside of the project
+        // has no snippet
+        assert!(html
+            .contains("<span class=\"hit\">    5          panic!(&quot;branch 4&quot;);</span>"));
+        assert!(html.contains("<span>    2  </span>"));
+        assert!(!html.contains("<span>    1  "));
+        assert_eq!(html.matches("<pre class=\"snippet\">").count(), 2);
+        assert!(html.contains("00000000  46 55 5a 5a 49 4e 47"));
+    }
+
+    #[test]
+    fn hex_dumps() {
+        assert_eq!(
+            hex_dump(b"ab\0"),
+            "00000000  61 62 00                                         ab.\n"
+        );
+        let dump = hex_dump(&[0; 300]);
+        assert_eq!(dump.lines().count(), 17);
+        assert!(dump.ends_with("... 44 more bytes\n"));
+    }
+}
@@ -0,0 +1,225 @@
+//! Selective instrumentation, which applies the coverage
+//! instrumentation only to the crates selected by the `instrument`
+//! setting of the cifuzz.yaml.
+//!
+//! RUSTFLAGS apply to all crates, so the instrumentation flags are
+//! passed by cargo-cifuzz itself instead: cargo executes it as
+//! RUSTC_WRAPPER with the [`Filter`] in the environment, and the
+//! wrapper adds the flags to the compiler invocations of the selected
+//! crates. The other flags, like the `fuzzing` cfg and the sanitizers,
+//! which rustc requires to be the same for all crates of an executable,
+//! are still passed via RUSTFLAGS.
+
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::{Command, ExitCode};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Instrument;
+use crate::workspace::Workspace;
+
+/// The environment variable with the filter, which makes cargo-cifuzz
+/// run as the wrapper of rustc.
+pub const WRAPPER_ENV: &str = "CIFUZZ_RUSTC_WRAPPER";
+
+/// The name which stands for the members of the workspace in the
+/// `instrument` setting.
+const WORKSPACE: &str = "workspace";
+
+/// The crates of the cifuzz runtime, which are never instrumented, so
+/// that the fuzzer only gets the coverage of the project.
+const CIFUZZ_CRATES: &[&str] = &["cifuzz", "cifuzz_macros"];
+
+/// The flags which instrument the code for the coverage feedback of the
+/// fuzzer or for coverage reports.
+const INSTRUMENTATION_FLAGS: &[&str] = &[
+    "-Cpasses=sancov-module",
+    "-Cllvm-args=-sanitizer-coverage-",
+    "-Cinstrument-coverage",
+];
+
+/// Which crates the wrapper instruments, with which flags.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Filter {
+    /// The instrumentation flags
+    pub flags: Vec<String>,
+    /// The crates to instrument, with underscores instead of dashes, or
+    /// all crates if unset
+    include: Option<Vec<String>>,
+    exclude: Vec<String>,
+    /// The RUSTC_WRAPPER of the environment, which the wrapper executes
+    /// rustc with
+    wrapper: Option<String>,
+}
+
+impl Filter {
+    /// Returns the filter of the `instrument` setting, resolving
+    /// "workspace" to the members of the workspace of the project.
+    pub fn new(instrument: &Instrument, project_dir: &Path, flags: Vec<String>) -> Result<Filter> {
+        let (include, exclude) = match instrument {
+            Instrument::Include(include) => (Some(include.as_slice()), &[][..]),
+            Instrument::Filter { include, exclude } => (include.as_deref(), exclude.as_slice()),
+        };
+        let include = match include {
+            Some(include) if include.iter().any(|c| c == WORKSPACE) => {
+                let workspace = Workspace::load(project_dir)?;
+                let mut crates: Vec<String> = include
+                    .iter()
+                    .filter(|c| *c != WORKSPACE)
+                    .cloned()
+                    .collect();
+                crates.extend(workspace.members.into_iter().map(|m| m.name));
+                Some(crates)
+            }
+            include => include.map(<[String]>::to_vec),
+        };
+        Ok(Filter {
+            flags,
+            include: include.map(|crates| crates.iter().map(|c| crate_name(c)).collect()),
+            exclude: exclude.iter().map(|c| crate_name(c)).collect(),
+            wrapper: std::env::var("RUSTC_WRAPPER")
+                .ok()
+                .filter(|wrapper| !wrapper.is_empty()),
+        })
+    }
+
+    /// Whether the crate of the package is instrumented. Packages are
+    /// matched by their names as well, which may differ from the names
+    /// of their library crates.
+    fn instruments(&self, crate_name: &str, package: Option<&str>) -> bool {
+        let names: Vec<String> = [Some(crate_name.to_string()), package.map(self::crate_name)]
+            .into_iter()
+            .flatten()
+            .collect();
+        let matches = |crates: &[String]| names.iter().any(|name| crates.contains(name));
+        !names
+            .iter()
+            .any(|name| CIFUZZ_CRATES.contains(&name.as_str()))
+            && self.include.as_deref().is_none_or(matches)
+            && !matches(&self.exclude)
+    }
+}
+
+/// Crate names have underscores where package names may have dashes.
+fn crate_name(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+/// Splits the RUSTFLAGS into the instrumentation flags and the others.
+pub fn split_flags(rustflags: Vec<String>) -> (Vec<String>, Vec<String>) {
+    rustflags
+        .into_iter()
+        .partition(|flag| INSTRUMENTATION_FLAGS.iter().any(|f| flag.starts_with(f)))
+}
+
+/// Runs rustc as its wrapper, with the arguments cargo passed to the
+/// wrapper and the instrumentation flags if the crate is selected. Only
+/// the crates built for the target are instrumented, like RUSTFLAGS
+/// aren't applied to build scripts and proc macros when the target is
+/// passed explicitly.
+pub fn run_wrapper(filter: &str) -> ExitCode {
+    let Ok(filter) = serde_json::from_str::<Filter>(filter) else {
+        eprintln!("cargo-cifuzz: invalid {WRAPPER_ENV}");
+        return ExitCode::FAILURE;
+    };
+    let mut args: Vec<OsString> = std::env::args_os().skip(1).collect();
+    if args.is_empty() {
+        eprintln!("cargo-cifuzz: {WRAPPER_ENV} is set, but no rustc was passed");
+        return ExitCode::FAILURE;
+    }
+    let crate_name = args
+        .iter()
+        .position(|a| a == "--crate-name")
+        .and_then(|i| args.get(i + 1))
+        .map(|name| name.to_string_lossy().into_owned());
+    let package = std::env::var("CARGO_PKG_NAME").ok();
+    let for_target = args.iter().any(|a| a == "--target");
+    if let Some(crate_name) = crate_name.filter(|_| for_target) {
+        if filter.instruments(&crate_name, package.as_deref()) {
+            args.extend(filter.flags.iter().map(OsString::from));
+        }
+    }
+
+    let mut cmd = match &filter.wrapper {
+        Some(wrapper) => {
+            let mut cmd = Command::new(wrapper);
+            cmd.args(&args);
+            cmd
+        }
+        None => {
+            let mut cmd = Command::new(&args[0]);
+            cmd.args(&args[1..]);
+            cmd
+        }
+    };
+    match cmd.status() {
+        Ok(status) => ExitCode::from(status.code().map_or(1, |code| code as u8)),
+        Err(err) => {
+            eprintln!("cargo-cifuzz: failed to execute rustc: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: Option<&[&str]>, exclude: &[&str]) -> Filter {
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        Filter {
+            flags: Vec::new(),
+            include: include.map(names),
+            exclude: names(exclude),
+            wrapper: None,
+        }
+    }
+
+    #[test]
+    fn select_crates() {
+        let filter = filter(Some(&["my_crate", "my_dep"]), &[]);
+        assert!(filter.instruments("my_crate", Some("my-crate")));
+        assert!(filter.instruments("my_dep", None));
+        // A library named differently than its package
+        assert!(filter.instruments("renamed", Some("my-crate")));
+        assert!(!filter.instruments("serde", Some("serde")));
+        assert!(!filter.instruments("cifuzz", Some("cifuzz")));
+    }
+
+    #[test]
+    fn exclude_crates() {
+        let filter = filter(None, &["bindings"]);
+        assert!(filter.instruments("my_crate", Some("my-crate")));
+        assert!(filter.instruments("serde", Some("serde")));
+        assert!(!filter.instruments("bindings", Some("bindings")));
+        assert!(!filter.instruments("cifuzz_macros", Some("cifuzz-macros")));
+    }
+
+    #[test]
+    fn split_instrumentation_flags() {
+        let flags = [
+            "--cfg",
+            "fuzzing",
+            "-Cpasses=sancov-module",
+            "-Cllvm-args=-sanitizer-coverage-level=4",
+            "-Cdebuginfo=1",
+            "-Zsanitizer=address",
+        ]
+        .map(String::from)
+        .to_vec();
+        let (instrumentation, others) = split_flags(flags);
+        assert_eq!(
+            instrumentation,
+            [
+                "-Cpasses=sancov-module",
+                "-Cllvm-args=-sanitizer-coverage-level=4"
+            ]
+        );
+        assert_eq!(
+            others,
+            ["--cfg", "fuzzing", "-Cdebuginfo=1", "-Zsanitizer=address"]
+        );
+    }
+}
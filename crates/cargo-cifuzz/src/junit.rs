@@ -0,0 +1,161 @@
+//! Writing test results in the JUnit XML format, which CI systems show
+//! alongside the results of the unit tests.
+
+use std::fmt::Write;
+use std::time::Duration;
+
+/// A test suite, e.g. the fuzzing run of `cargo cifuzz run`.
+#[derive(Debug)]
+pub struct TestSuite {
+    pub name: String,
+    pub test_cases: Vec<TestCase>,
+}
+
+#[derive(Debug)]
+pub struct TestCase {
+    pub name: String,
+    pub classname: String,
+    pub time: Duration,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug)]
+pub enum Outcome {
+    Passed,
+    /// The test found a bug
+    Failure {
+        kind: String,
+        message: String,
+        text: String,
+    },
+    /// The test couldn't be run, e.g. because it failed to build
+    Error {
+        message: String,
+    },
+}
+
+/// Formats the test suites as a JUnit XML report.
+pub fn report(suites: &[TestSuite]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+    for suite in suites {
+        let count = |f: fn(&Outcome) -> bool| {
+            suite
+                .test_cases
+                .iter()
+                .filter(|test_case| f(&test_case.outcome))
+                .count()
+        };
+        let time: Duration = suite
+            .test_cases
+            .iter()
+            .map(|test_case| test_case.time)
+            .sum();
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">",
+            escape(&suite.name),
+            suite.test_cases.len(),
+            count(|outcome| matches!(outcome, Outcome::Failure { .. })),
+            count(|outcome| matches!(outcome, Outcome::Error { .. })),
+            time.as_secs_f64()
+        );
+        for test_case in &suite.test_cases {
+            let _ = write!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                escape(&test_case.name),
+                escape(&test_case.classname),
+                test_case.time.as_secs_f64()
+            );
+            match &test_case.outcome {
+                Outcome::Passed => xml.push_str("/>\n"),
+                Outcome::Failure {
+                    kind,
+                    message,
+                    text,
+                } => {
+                    let _ = writeln!(
+                        xml,
+                        ">\n      <failure type=\"{}\" message=\"{}\">{}</failure>\n    </testcase>",
+                        escape(kind),
+                        escape(message),
+                        escape(text)
+                    );
+                }
+                Outcome::Error { message } => {
+                    let _ = writeln!(
+                        xml,
+                        ">\n      <error message=\"{}\"/>\n    </testcase>",
+                        escape(message)
+                    );
+                }
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// Escapes text for XML attributes and content. Line breaks are
+/// escaped, so that they're kept in attributes, and control characters,
+/// which XML 1.0 doesn't allow, are replaced.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\t' => escaped.push_str("&#9;"),
+            c if c.is_control() => escaped.push('\u{fffd}'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn junit_report() {
+        let suite = TestSuite {
+            name: "cifuzz".to_string(),
+            test_cases: vec![
+                TestCase {
+                    name: "my_fuzz_test".to_string(),
+                    classname: "fuzzing".to_string(),
+                    time: Duration::from_millis(1500),
+                    outcome: Outcome::Failure {
+                        kind: "crash".to_string(),
+                        message: "branch 4 has been reached".to_string(),
+                        text: "at <src/explore_me.rs>\n\u{1b}".to_string(),
+                    },
+                },
+                TestCase {
+                    name: "other".to_string(),
+                    classname: "fuzzing".to_string(),
+                    time: Duration::from_millis(500),
+                    outcome: Outcome::Passed,
+                },
+            ],
+        };
+        assert_eq!(
+            report(&[suite]),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <testsuites>\n  \
+               <testsuite name=\"cifuzz\" tests=\"2\" failures=\"1\" errors=\"0\" time=\"2.000\">\n    \
+                 <testcase name=\"my_fuzz_test\" classname=\"fuzzing\" time=\"1.500\">\n      \
+                   <failure type=\"crash\" message=\"branch 4 has been reached\">\
+                   at &lt;src/explore_me.rs&gt;&#10;\u{fffd}</failure>\n    \
+                 </testcase>\n    \
+                 <testcase name=\"other\" classname=\"fuzzing\" time=\"0.500\"/>\n  \
+               </testsuite>\n\
+             </testsuites>\n"
+        );
+    }
+}
@@ -0,0 +1,421 @@
+//! Parsing coverage reports in the LCOV tracefile format, as exported by
+//! `llvm-cov export -format=lcov`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+/// The coverage of all source files of a report, by path.
+#[derive(Debug, Default, PartialEq)]
+pub struct Report {
+    pub files: BTreeMap<String, FileCoverage>,
+}
+
+/// The execution counts of the functions, lines and branches of a
+/// source file.
+#[derive(Debug, Default, PartialEq)]
+pub struct FileCoverage {
+    pub functions: BTreeMap<String, u64>,
+    pub lines: BTreeMap<u32, u64>,
+    /// Branches by their line and their block and branch number
+    pub branches: BTreeMap<(u32, String), u64>,
+}
+
+/// The number of covered and total items.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Counter {
+    pub covered: usize,
+    pub total: usize,
+}
+
+impl Counter {
+    fn of<'a>(counts: impl Iterator<Item = &'a u64>) -> Counter {
+        counts.fold(Counter::default(), |c, &count| Counter {
+            covered: c.covered + usize::from(count > 0),
+            total: c.total + 1,
+        })
+    }
+
+    fn add(self, other: Counter) -> Counter {
+        Counter {
+            covered: self.covered + other.covered,
+            total: self.total + other.total,
+        }
+    }
+
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            100.0
+        } else {
+            self.covered as f64 * 100.0 / self.total as f64
+        }
+    }
+}
+
+impl std::fmt::Display for Counter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{} ({:.1}%)",
+            self.covered,
+            self.total,
+            self.percent()
+        )
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub functions: Counter,
+    pub lines: Counter,
+    pub branches: Counter,
+}
+
+impl Summary {
+    fn add(self, other: Summary) -> Summary {
+        Summary {
+            functions: self.functions.add(other.functions),
+            lines: self.lines.add(other.lines),
+            branches: self.branches.add(other.branches),
+        }
+    }
+}
+
+impl FileCoverage {
+    pub fn summary(&self) -> Summary {
+        Summary {
+            functions: Counter::of(self.functions.values()),
+            lines: Counter::of(self.lines.values()),
+            branches: Counter::of(self.branches.values()),
+        }
+    }
+}
+
+impl Report {
+    /// The summary of all files.
+    pub fn summary(&self) -> Summary {
+        self.files
+            .values()
+            .map(FileCoverage::summary)
+            .fold(Summary::default(), Summary::add)
+    }
+
+    /// Makes the paths of the files relative to the directory, so that
+    /// reports created in different checkouts can be compared.
+    pub fn strip_prefix(self, dir: &Path) -> Report {
+        let files = self
+            .files
+            .into_iter()
+            .map(|(path, file)| match Path::new(&path).strip_prefix(dir) {
+                Ok(relative) => (relative.to_string_lossy().into_owned(), file),
+                Err(_) => (path, file),
+            })
+            .collect();
+        Report { files }
+    }
+}
+
+/// The changes of the coverage of a source file between two reports.
+#[derive(Debug, Default, PartialEq)]
+pub struct FileDiff {
+    pub newly_covered_lines: Vec<u32>,
+    pub newly_uncovered_lines: Vec<u32>,
+    pub newly_covered_branches: Vec<u32>,
+    pub newly_uncovered_branches: Vec<u32>,
+}
+
+impl FileDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == FileDiff::default()
+    }
+}
+
+/// Compares the coverage of two reports and returns the files whose
+/// coverage changed. Branches are reported by their line. Lines and
+/// branches which only exist in one of the reports, e.g. because the
+/// code was changed, count as not covered in the other one.
+pub fn diff(baseline: &Report, current: &Report) -> BTreeMap<String, FileDiff> {
+    let empty = FileCoverage::default();
+    let paths: BTreeSet<&String> = baseline.files.keys().chain(current.files.keys()).collect();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let before = baseline.files.get(path).unwrap_or(&empty);
+            let after = current.files.get(path).unwrap_or(&empty);
+            let (newly_covered_lines, newly_uncovered_lines) = changes(&before.lines, &after.lines);
+            let (covered_branches, uncovered_branches) = changes(&before.branches, &after.branches);
+            let lines_of = |branches: Vec<(u32, String)>| {
+                let mut lines: Vec<u32> = branches.into_iter().map(|(line, _)| line).collect();
+                lines.dedup();
+                lines
+            };
+            let diff = FileDiff {
+                newly_covered_lines,
+                newly_uncovered_lines,
+                newly_covered_branches: lines_of(covered_branches),
+                newly_uncovered_branches: lines_of(uncovered_branches),
+            };
+            (!diff.is_empty()).then(|| (path.clone(), diff))
+        })
+        .collect()
+}
+
+/// Returns the keys which are covered in `after` but not in `before` and
+/// vice versa.
+fn changes<K: Ord + Clone>(
+    before: &BTreeMap<K, u64>,
+    after: &BTreeMap<K, u64>,
+) -> (Vec<K>, Vec<K>) {
+    let covered = |counts: &BTreeMap<K, u64>, key: &K| counts.get(key).is_some_and(|&c| c > 0);
+    let newly_covered = after
+        .keys()
+        .filter(|k| covered(after, k) && !covered(before, k))
+        .cloned()
+        .collect();
+    let newly_uncovered = before
+        .keys()
+        .filter(|k| covered(before, k) && !covered(after, k))
+        .cloned()
+        .collect();
+    (newly_covered, newly_uncovered)
+}
+
+/// Formats line numbers as ranges like "4-6, 9".
+pub fn format_lines(lines: &[u32]) -> String {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for &line in lines {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == line => *end = line,
+            _ => ranges.push((line, line)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|&(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{start}-{end}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Parses an LCOV tracefile. Records of the same file are merged.
+pub fn parse(content: &str) -> Result<Report> {
+    let mut report = Report::default();
+    let mut current: Option<(String, FileCoverage)> = None;
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line == "end_of_record" {
+            let (path, file) = current.take().context("end_of_record without SF")?;
+            merge(report.files.entry(path).or_default(), file);
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let error = || format!("invalid line {}: {line:?}", i + 1);
+        if key == "SF" {
+            current = Some((value.to_string(), FileCoverage::default()));
+            continue;
+        }
+        let Some((_, file)) = current.as_mut() else {
+            continue;
+        };
+        match key {
+            "FNDA" => {
+                let (count, name) = value.split_once(',').with_context(error)?;
+                let count = parse_count(count).with_context(error)?;
+                *file.functions.entry(name.to_string()).or_default() += count;
+            }
+            "DA" => {
+                let mut parts = value.split(',');
+                let line = parts
+                    .next()
+                    .unwrap_or_default()
+                    .parse()
+                    .with_context(error)?;
+                let count = parse_count(parts.next().unwrap_or_default()).with_context(error)?;
+                *file.lines.entry(line).or_default() += count;
+            }
+            "BRDA" => {
+                let parts: Vec<&str> = value.split(',').collect();
+                let [line, block, branch, taken] = parts[..] else {
+                    bail!(error());
+                };
+                let line = line.parse().with_context(error)?;
+                // "-" means that the block containing the branch was
+                // never executed
+                let count = if taken == "-" {
+                    0
+                } else {
+                    parse_count(taken).with_context(error)?
+                };
+                *file
+                    .branches
+                    .entry((line, format!("{block},{branch}")))
+                    .or_default() += count;
+            }
+            // Functions without FNDA are not executed
+            "FN" => {
+                let (_, name) = value.split_once(',').with_context(error)?;
+                file.functions.entry(name.to_string()).or_default();
+            }
+            _ => {}
+        }
+    }
+    Ok(report)
+}
+
+/// Parses an execution count. llvm-cov prints large counts in
+/// scientific notation.
+fn parse_count(count: &str) -> Result<u64> {
+    match count.parse::<u64>() {
+        Ok(count) => Ok(count),
+        Err(_) => Ok(count.parse::<f64>()? as u64),
+    }
+}
+
+fn merge(into: &mut FileCoverage, file: FileCoverage) {
+    for (name, count) in file.functions {
+        *into.functions.entry(name).or_default() += count;
+    }
+    for (line, count) in file.lines {
+        *into.lines.entry(line).or_default() += count;
+    }
+    for (branch, count) in file.branches {
+        *into.branches.entry(branch).or_default() += count;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LCOV: &str = "\
+SF:src/explore_me.rs
+FN:4,_RNvNtCs1_13cargo_example10explore_me10explore_me
+FNDA:57,_RNvNtCs1_13cargo_example10explore_me10explore_me
+FNF:1
+FNH:1
+DA:4,57
+DA:5,57
+DA:7,57
+DA:8,12
+DA:14,0
+BRDA:7,0,0,12
+BRDA:7,0,1,45
+BRDA:14,0,0,-
+BRDA:14,0,1,-
+BRF:4
+BRH:2
+LF:5
+LH:4
+end_of_record
+SF:src/main.rs
+FN:7,_RNvCs1_13cargo_example4main
+FNF:1
+FNH:0
+DA:7,0
+LF:1
+LH:0
+end_of_record
+";
+
+    #[test]
+    fn parse_report() {
+        let report = parse(LCOV).unwrap();
+        assert_eq!(report.files.len(), 2);
+        let explore_me = &report.files["src/explore_me.rs"];
+        assert_eq!(explore_me.lines[&8], 12);
+        assert_eq!(explore_me.branches[&(14, "0,1".to_string())], 0);
+
+        let summary = explore_me.summary();
+        assert_eq!(
+            summary.lines,
+            Counter {
+                covered: 4,
+                total: 5
+            }
+        );
+        assert_eq!(
+            summary.branches,
+            Counter {
+                covered: 2,
+                total: 4
+            }
+        );
+        assert_eq!(
+            summary.functions,
+            Counter {
+                covered: 1,
+                total: 1
+            }
+        );
+
+        let summary = report.summary();
+        assert_eq!(
+            summary.functions,
+            Counter {
+                covered: 1,
+                total: 2
+            }
+        );
+        assert_eq!(summary.lines.to_string(), "4/6 (66.7%)");
+    }
+
+    #[test]
+    fn merge_records_of_the_same_file() {
+        let report =
+            parse("SF:a.rs\nDA:1,1\nend_of_record\nSF:a.rs\nDA:1,2\nDA:2,1e3\nend_of_record\n")
+                .unwrap();
+        assert_eq!(
+            report.files["a.rs"].lines,
+            BTreeMap::from([(1, 3), (2, 1000)])
+        );
+    }
+
+    #[test]
+    fn parse_invalid_report() {
+        assert!(parse("SF:a.rs\nDA:x,1\nend_of_record\n").is_err());
+        assert!(parse("end_of_record\n").is_err());
+    }
+
+    #[test]
+    fn diff_reports() {
+        let baseline = parse(
+            "SF:/old/src/a.rs\nDA:1,1\nDA:2,1\nDA:3,0\nBRDA:2,0,0,1\nBRDA:2,0,1,0\nend_of_record\n\
+             SF:/old/src/b.rs\nDA:1,1\nend_of_record\n",
+        )
+        .unwrap()
+        .strip_prefix(Path::new("/old"));
+        let current = parse(
+            "SF:/new/src/a.rs\nDA:1,1\nDA:2,0\nDA:3,5\nDA:4,1\nBRDA:2,0,0,1\nBRDA:2,0,1,3\nend_of_record\n\
+             SF:/new/src/b.rs\nDA:1,2\nend_of_record\n",
+        )
+        .unwrap()
+        .strip_prefix(Path::new("/new"));
+
+        let diff = diff(&baseline, &current);
+        assert_eq!(diff.len(), 1);
+        assert_eq!(
+            diff["src/a.rs"],
+            FileDiff {
+                newly_covered_lines: vec![3, 4],
+                newly_uncovered_lines: vec![2],
+                newly_covered_branches: vec![2],
+                newly_uncovered_branches: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn format_line_ranges() {
+        assert_eq!(format_lines(&[1, 2, 3, 5, 8, 9]), "1-3, 5, 8-9");
+        assert_eq!(format_lines(&[]), "");
+    }
+}
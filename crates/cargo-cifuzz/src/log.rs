@@ -0,0 +1,66 @@
+//! Logging to stderr, styled like the log output of the cifuzz CLI.
+
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+pub fn is_verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+// ANSI SGR parameters
+pub const BOLD: &str = "1";
+pub const RED: &str = "31";
+pub const GREEN: &str = "32";
+pub const GRAY: &str = "90";
+
+pub fn log(style: &[&str], icon: &str, msg: &str) {
+    let mut s = format!("{icon}{msg}");
+    // Written between the frames, the message would be overwritten
+    if crate::dashboard::log(&s) {
+        return;
+    }
+    if !s.ends_with('\n') {
+        s.push('\n');
+    }
+    // Only use colors if stderr is a terminal
+    if !style.is_empty() && std::io::stderr().is_terminal() {
+        s = format!("\x1b[{}m{}\x1b[0m", style.join(";"), s);
+    }
+    eprint!("{s}");
+}
+
+/// Highlights a message as successful
+macro_rules! success {
+    ($($arg:tt)*) => {
+        $crate::log::log(&[$crate::log::GREEN], "✅ ", &format!($($arg)*))
+    };
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::log::log(&[$crate::log::BOLD, $crate::log::RED], "❌ ", &format!($($arg)*))
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::log::log(&[], "", &format!($($arg)*))
+    };
+}
+
+/// Only prints the message if --verbose was specified
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::log::is_verbose() {
+            $crate::log::log(&[$crate::log::GRAY], "🔍 ", &format!($($arg)*))
+        }
+    };
+}
+
+pub(crate) use {debug, error, info, success};
@@ -0,0 +1,132 @@
+//! `cargo cifuzz`, the cargo subcommand to build and run fuzz tests
+//! written with the `cifuzz` crate.
+
+// The help texts of the subcommands are their doc comments, whose
+// indented examples are commands and their output, not Rust code
+#![allow(rustdoc::invalid_rust_codeblocks)]
+
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+
+mod affected;
+mod build;
+mod build_cache;
+mod campaign;
+mod cmd;
+mod config;
+mod corpus;
+mod coverage;
+mod dashboard;
+mod dictionary;
+mod events;
+mod exit_code;
+mod finding;
+mod focus;
+mod generate;
+mod github_actions;
+mod html_report;
+mod instrument;
+mod junit;
+mod lcov;
+mod log;
+mod merge;
+mod metrics;
+mod minimize;
+mod native;
+mod notify;
+mod oss_fuzz;
+mod parser;
+mod regression_test;
+mod runner;
+mod sarif;
+mod severity;
+mod slow_inputs;
+mod stubs;
+mod symbolize;
+mod sync;
+mod trace;
+mod workspace;
+
+/// cargo invokes subcommands as `cargo-cifuzz cifuzz <args>`
+#[derive(Parser)]
+#[command(name = "cargo", bin_name = "cargo")]
+enum Cargo {
+    Cifuzz(Cli),
+}
+
+/// Makes fuzz tests as easy as unit tests
+#[derive(clap::Args)]
+#[command(version)]
+struct Cli {
+    /// Show more verbose output, can be helpful for debugging problems
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    Bundle(cmd::bundle::BundleArgs),
+    Check(cmd::check::CheckArgs),
+    Corpus(cmd::corpus::CorpusArgs),
+    Coverage(cmd::coverage::CoverageArgs),
+    Create(cmd::create::CreateArgs),
+    Findings(cmd::findings::FindingsArgs),
+    Gc(cmd::gc::GcArgs),
+    Generate(cmd::generate::GenerateArgs),
+    Init(cmd::init::InitArgs),
+    Integrate(cmd::integrate::IntegrateArgs),
+    List(cmd::list::ListArgs),
+    Minimize(cmd::minimize::MinimizeArgs),
+    Replay(cmd::replay::ReplayArgs),
+    Report(cmd::report::ReportArgs),
+    Reproduce(cmd::reproduce::ReproduceArgs),
+    Run(Box<cmd::run::RunArgs>),
+    Status(cmd::status::StatusArgs),
+}
+
+fn main() -> ExitCode {
+    // Build scripts execute us as the C and C++ compiler for the
+    // instrumentation of native code
+    if let Some(cxx) = native::invoked_as_compiler() {
+        return native::run_wrapper(cxx);
+    }
+    // cargo executes us as the wrapper of rustc for selective
+    // instrumentation
+    if let Ok(filter) = std::env::var(instrument::WRAPPER_ENV) {
+        return instrument::run_wrapper(&filter);
+    }
+    let Cargo::Cifuzz(cli) = Cargo::parse();
+    log::set_verbose(cli.verbose);
+
+    let result = match cli.command {
+        Command::Bundle(args) => cmd::bundle::run(args),
+        Command::Check(args) => cmd::check::run(args),
+        Command::Corpus(args) => cmd::corpus::run(args),
+        Command::Coverage(args) => cmd::coverage::run(args),
+        Command::Create(args) => cmd::create::run(args),
+        Command::Findings(args) => cmd::findings::run(args),
+        Command::Gc(args) => cmd::gc::run(args),
+        Command::Generate(args) => cmd::generate::run(args),
+        Command::Init(args) => cmd::init::run(args),
+        Command::Integrate(args) => cmd::integrate::run(args),
+        Command::List(args) => cmd::list::run(args),
+        Command::Minimize(args) => cmd::minimize::run(args),
+        Command::Replay(args) => cmd::replay::run(args),
+        Command::Report(args) => cmd::report::run(args),
+        Command::Reproduce(args) => cmd::reproduce::run(args),
+        Command::Run(args) => cmd::run::run(*args),
+        Command::Status(args) => cmd::status::run(args),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            log::error!("{:#}", err);
+            ExitCode::from(exit_code::of(&err))
+        }
+    }
+}
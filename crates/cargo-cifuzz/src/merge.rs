@@ -0,0 +1,276 @@
+//! Merging corpora, i.e. selecting the inputs which add coverage.
+//!
+//! libFuzzer's `-merge=1` executes the inputs in child processes which
+//! it starts with the libFuzzer flags on the command line, but a libtest
+//! executable only accepts libtest flags. Instead, cargo-cifuzz runs the
+//! inner step of the merge itself: The fuzz test executes the inputs
+//! listed in a control file and appends the features and the edges
+//! covered by each of them to the file. If it crashes on an input, the
+//! step is restarted and continues with the next input. The inputs are
+//! then selected like libFuzzer does.
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+use crate::log;
+use crate::runner::libfuzzer::Runner;
+
+/// The coverage of an input, as recorded in the control file.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct InputCoverage {
+    pub size: u64,
+    /// The features of the input, i.e. the edges and their hit counts
+    pub features: Vec<u32>,
+    /// The edges covered by the input which weren't covered by the
+    /// inputs executed before it in the same process
+    pub new_edges: Vec<u32>,
+    /// Whether the fuzz test crashed on the input
+    pub crashed: bool,
+}
+
+/// The coverage of a set of inputs.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub inputs: usize,
+    pub bytes: u64,
+    pub features: usize,
+    pub edges: usize,
+}
+
+/// Executes the fuzz test with the inputs and returns their coverage.
+/// The first `first_corpus_len` inputs are those of the corpus which is
+/// merged into. The control file is overwritten.
+pub fn execute(
+    runner: &Runner,
+    control_file: &Path,
+    inputs: &[PathBuf],
+    first_corpus_len: usize,
+) -> Result<Vec<InputCoverage>> {
+    if inputs.is_empty() {
+        return Ok(Vec::new());
+    }
+    std::fs::write(control_file, control_file_header(inputs, first_corpus_len))
+        .with_context(|| format!("failed to write {}", control_file.display()))?;
+
+    let mut coverage = Vec::new();
+    while coverage.len() < inputs.len() {
+        let started = coverage.len();
+        let result = runner.merge_inner(control_file)?;
+        let content = std::fs::read_to_string(control_file)
+            .with_context(|| format!("failed to read {}", control_file.display()))?;
+        coverage = parse_control_file(&content)
+            .with_context(|| format!("failed to parse {}", control_file.display()))?;
+        // A crash before the first input doesn't depend on the inputs
+        if coverage.len() == started {
+            bail!(
+                "The fuzz test exited with {} before executing the inputs:\n{}",
+                result.status,
+                result.output.join("\n")
+            );
+        }
+        if let Some(input) = coverage.last().filter(|c| c.crashed) {
+            log::info!(
+                "The fuzz test crashed on {} ({} bytes), continuing with the next input",
+                inputs[coverage.len() - 1].display(),
+                input.size
+            );
+        }
+    }
+    Ok(coverage)
+}
+
+/// Returns the header of a control file listing the inputs, in the
+/// format expected by libFuzzer's `-merge_control_file`.
+fn control_file_header(inputs: &[PathBuf], first_corpus_len: usize) -> String {
+    let mut header = format!("{}\n{}\n", inputs.len(), first_corpus_len);
+    for input in inputs {
+        writeln!(header, "{}", input.display()).unwrap();
+    }
+    header
+}
+
+/// Parses the coverage recorded in a control file. The result contains
+/// the inputs which were executed so far.
+///
+/// ```text
+/// 2            the number of inputs
+/// 0            the number of inputs of the corpus merged into
+/// /corpus/a    the inputs, one per line
+/// /corpus/b
+/// STARTED 0 4  the index and size of an executed input
+/// FT 0 1 4 6   its features
+/// COV 0 7 8    the edges it covered first
+/// STARTED 1 2  without features, the fuzz test crashed on the input
+/// ```
+pub fn parse_control_file(content: &str) -> Result<Vec<InputCoverage>> {
+    let mut lines = content.lines();
+    let num_inputs: usize = lines.next().context("missing number of inputs")?.parse()?;
+    lines
+        .next()
+        .context("missing number of inputs in the first corpus")?;
+    for _ in 0..num_inputs {
+        lines.next().context("missing input")?;
+    }
+
+    let mut coverage: Vec<InputCoverage> = Vec::new();
+    for line in lines {
+        let mut words = line.split_whitespace();
+        let (Some(marker), Some(index)) = (words.next(), words.next()) else {
+            bail!("invalid line {line:?}");
+        };
+        let index: usize = index.parse()?;
+        let mut numbers = || -> Result<Vec<u32>> {
+            Ok(words.by_ref().map(str::parse).collect::<Result<_, _>>()?)
+        };
+        match marker {
+            "STARTED" if index == coverage.len() && index < num_inputs => {
+                coverage.push(InputCoverage {
+                    size: numbers()?.first().copied().unwrap_or_default().into(),
+                    // Until the features are recorded
+                    crashed: true,
+                    ..InputCoverage::default()
+                });
+            }
+            "FT" if index + 1 == coverage.len() => {
+                let input = &mut coverage[index];
+                input.features = numbers()?;
+                input.crashed = false;
+            }
+            "COV" if index + 1 == coverage.len() => coverage[index].new_edges = numbers()?,
+            _ => bail!("unexpected line {line:?}"),
+        }
+    }
+    Ok(coverage)
+}
+
+/// Returns the indices of the inputs after the first `first_corpus_len`
+/// ones which add features to the first corpus. Like libFuzzer, smaller
+/// inputs and then inputs with more features are preferred.
+pub fn select(coverage: &[InputCoverage], first_corpus_len: usize) -> Vec<usize> {
+    let mut features: HashSet<u32> = coverage[..first_corpus_len.min(coverage.len())]
+        .iter()
+        .flat_map(|input| input.features.iter().copied())
+        .collect();
+    let mut candidates: Vec<usize> = (first_corpus_len..coverage.len())
+        .filter(|&i| !coverage[i].crashed)
+        .collect();
+    candidates.sort_by_key(|&i| {
+        let input = &coverage[i];
+        let new_features = input
+            .features
+            .iter()
+            .filter(|f| !features.contains(f))
+            .count();
+        (input.size, std::cmp::Reverse(new_features))
+    });
+
+    let mut selected = Vec::new();
+    for i in candidates {
+        let before = features.len();
+        features.extend(coverage[i].features.iter().copied());
+        if features.len() > before {
+            selected.push(i);
+        }
+    }
+    selected.sort_unstable();
+    selected
+}
+
+/// Summarizes the coverage of the inputs, which must have been executed
+/// together.
+pub fn summarize(coverage: &[InputCoverage]) -> Summary {
+    let features: HashSet<u32> = coverage
+        .iter()
+        .flat_map(|input| input.features.iter().copied())
+        .collect();
+    let edges: HashSet<u32> = coverage
+        .iter()
+        .flat_map(|input| input.new_edges.iter().copied())
+        .collect();
+    Summary {
+        inputs: coverage.len(),
+        bytes: coverage.iter().map(|input| input.size).sum(),
+        features: features.len(),
+        edges: edges.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(size: u64, features: &[u32]) -> InputCoverage {
+        InputCoverage {
+            size,
+            features: features.to_vec(),
+            ..InputCoverage::default()
+        }
+    }
+
+    #[test]
+    fn header() {
+        assert_eq!(
+            control_file_header(&[PathBuf::from("/seeds/a"), PathBuf::from("/corpus/b")], 1),
+            "2\n1\n/seeds/a\n/corpus/b\n"
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let coverage = parse_control_file(
+            "3\n0\n/c/a\n/c/b\n/c/c\nSTARTED 0 4\nFT 0 1 4 6\nCOV 0 7 8\nSTARTED 1 2\nSTARTED 2 9\nFT 2 4\nCOV 2\n",
+        )
+        .unwrap();
+        assert_eq!(
+            coverage,
+            [
+                InputCoverage {
+                    size: 4,
+                    features: vec![1, 4, 6],
+                    new_edges: vec![7, 8],
+                    crashed: false,
+                },
+                InputCoverage {
+                    size: 2,
+                    crashed: true,
+                    ..InputCoverage::default()
+                },
+                input(9, &[4]),
+            ]
+        );
+        assert!(parse_control_file("1\n0\n/c/a\nFT 0 1\n").is_err());
+    }
+
+    #[test]
+    fn selection() {
+        let coverage = [
+            // The first corpus
+            input(1, &[1, 2]),
+            // Adds nothing
+            input(2, &[1]),
+            // Preferred over the larger input with the same features
+            input(5, &[3, 4]),
+            input(3, &[3, 4]),
+            // Adds feature 5
+            input(8, &[3, 5]),
+            InputCoverage {
+                size: 1,
+                crashed: true,
+                ..InputCoverage::default()
+            },
+        ];
+        assert_eq!(select(&coverage, 1), [3, 4]);
+        assert_eq!(
+            summarize(&coverage),
+            Summary {
+                inputs: 6,
+                bytes: 20,
+                features: 5,
+                edges: 0,
+            }
+        );
+    }
+}
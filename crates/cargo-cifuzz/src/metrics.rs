@@ -0,0 +1,429 @@
+//! The metrics of a fuzzing run for `--metrics-endpoint`, which are
+//! either served to Prometheus or pushed to StatsD, so that the
+//! operators of long-running campaigns can alert on stalled fuzzers and
+//! coverage plateaus.
+//!
+//! Like the events, the metrics are kept in a global, so that the
+//! runners can update them with the progress they parse. The metrics of
+//! every fuzz test of the run are kept, e.g. with `--all`. The executed
+//! inputs are a counter over restarts of the fuzz test, while the other
+//! progress is the latest one.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream, UdpSocket};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+
+use crate::log;
+use crate::parser::Stats;
+
+/// How often the metrics are pushed to StatsD.
+const STATSD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The maximum size of an HTTP request of Prometheus.
+const MAX_REQUEST_SIZE: usize = 8192;
+
+static METRICS: Mutex<Option<Metrics>> = Mutex::new(None);
+
+/// Where the metrics are exported to.
+#[derive(Debug, PartialEq)]
+pub enum Endpoint {
+    /// Served at `http://<addr>/metrics` for Prometheus to scrape
+    Prometheus(String),
+    /// Pushed to a StatsD server over UDP
+    Statsd(String),
+}
+
+impl Endpoint {
+    pub fn parse(endpoint: &str) -> Result<Endpoint> {
+        if let Some(addr) = endpoint.strip_prefix("http://") {
+            Ok(Endpoint::Prometheus(
+                addr.trim_end_matches("/metrics")
+                    .trim_end_matches('/')
+                    .to_string(),
+            ))
+        } else if let Some(addr) = endpoint.strip_prefix("statsd://") {
+            Ok(Endpoint::Statsd(addr.trim_end_matches('/').to_string()))
+        } else {
+            bail!(
+                "Unsupported metrics endpoint {endpoint}, expected http://<address>:<port> \
+                 for Prometheus or statsd://<host>:<port>"
+            )
+        }
+    }
+}
+
+#[derive(Default)]
+struct Metrics {
+    /// The fuzz test whose progress is reported
+    current: Option<String>,
+    fuzz_tests: BTreeMap<String, FuzzTestMetrics>,
+    /// The executed inputs and findings already pushed to StatsD
+    pushed: BTreeMap<String, (u64, u64)>,
+}
+
+struct FuzzTestMetrics {
+    /// When the fuzz test reported its first progress, i.e. after it
+    /// was built
+    start: Option<Instant>,
+    /// When the fuzz test stopped, after which its uptime doesn't grow
+    stop: Option<Instant>,
+    /// The executed inputs of the runs before a restart
+    execs_before: u64,
+    stats: Stats,
+    findings: u64,
+    /// When the fuzz test last covered new edges
+    last_new_coverage: Instant,
+}
+
+impl FuzzTestMetrics {
+    fn execs(&self) -> u64 {
+        self.execs_before + self.stats.execs
+    }
+
+    fn uptime(&self) -> Duration {
+        self.start.map_or(Duration::ZERO, |start| {
+            self.stop.unwrap_or_else(Instant::now) - start
+        })
+    }
+}
+
+/// Starts exporting the metrics to the endpoint.
+pub fn start(endpoint: &Endpoint) -> Result<()> {
+    *METRICS.lock().unwrap() = Some(Metrics::default());
+    match endpoint {
+        Endpoint::Prometheus(addr) => {
+            let listener = TcpListener::bind(addr)
+                .with_context(|| format!("failed to listen on {addr} for the metrics"))?;
+            log::info!(
+                "Serving the metrics at http://{}/metrics",
+                listener.local_addr()?
+            );
+            std::thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    if let Err(err) = serve(stream) {
+                        log::debug!("Failed to serve the metrics: {err:#}");
+                    }
+                }
+            });
+        }
+        Endpoint::Statsd(addr) => {
+            let socket = UdpSocket::bind("0.0.0.0:0").context("failed to create a UDP socket")?;
+            socket
+                .connect(addr)
+                .with_context(|| format!("failed to resolve the StatsD server {addr}"))?;
+            log::info!("Pushing the metrics to StatsD at {addr}");
+            std::thread::spawn(move || loop {
+                std::thread::sleep(STATSD_INTERVAL);
+                let lines = METRICS.lock().unwrap().as_mut().map(Metrics::statsd);
+                if let Some(lines) = lines.filter(|lines| !lines.is_empty()) {
+                    if let Err(err) = socket.send(lines.as_bytes()) {
+                        log::debug!("Failed to push the metrics: {err}");
+                    }
+                }
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Records that the fuzz test started, whose progress the following
+/// calls report.
+pub fn start_fuzz_test(fuzz_test: &str) {
+    if let Some(metrics) = METRICS.lock().unwrap().as_mut() {
+        metrics.current = Some(fuzz_test.to_string());
+        metrics
+            .fuzz_tests
+            .entry(fuzz_test.to_string())
+            .or_insert_with(|| FuzzTestMetrics {
+                start: None,
+                stop: None,
+                execs_before: 0,
+                stats: Stats::default(),
+                findings: 0,
+                last_new_coverage: Instant::now(),
+            })
+            .stop = None;
+    }
+}
+
+/// Records the progress of the running fuzz test.
+pub fn progress(stats: &Stats) {
+    with_current(|metrics| {
+        if metrics.start.is_none() {
+            metrics.start = Some(Instant::now());
+            metrics.last_new_coverage = Instant::now();
+        }
+        // The executed inputs are counted from zero after a restart
+        if stats.execs < metrics.stats.execs {
+            metrics.execs_before += metrics.stats.execs;
+        }
+        if stats.cov > metrics.stats.cov {
+            metrics.last_new_coverage = Instant::now();
+        }
+        metrics.stats = Stats {
+            cov: stats.cov.max(metrics.stats.cov),
+            ..*stats
+        };
+    });
+}
+
+/// Records a finding of the running fuzz test.
+pub fn finding() {
+    with_current(|metrics| metrics.findings += 1);
+}
+
+/// Records that the running fuzz test stopped.
+pub fn stop_fuzz_test() {
+    with_current(|metrics| metrics.stop = Some(Instant::now()));
+}
+
+fn with_current(f: impl FnOnce(&mut FuzzTestMetrics)) {
+    let mut metrics = METRICS.lock().unwrap();
+    let Some(metrics) = metrics.as_mut() else {
+        return;
+    };
+    if let Some(current) = metrics
+        .current
+        .as_ref()
+        .and_then(|name| metrics.fuzz_tests.get_mut(name))
+    {
+        f(current);
+    }
+}
+
+/// Answers an HTTP request of Prometheus.
+fn serve(mut stream: TcpStream) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST_SIZE {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+    let response = if request.starts_with("GET ") && (path == "/metrics" || path == "/") {
+        let body = METRICS
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(Metrics::prometheus)
+            .unwrap_or_default();
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+    };
+    stream.write_all(response.as_bytes())?;
+    Ok(())
+}
+
+/// The metrics, with the function returning the value for a fuzz test.
+type Metric = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&FuzzTestMetrics) -> u64,
+);
+
+const METRICS_LIST: &[Metric] = &[
+    (
+        "executions_total",
+        "counter",
+        "The inputs the fuzz test executed",
+        FuzzTestMetrics::execs,
+    ),
+    (
+        "executions_per_second",
+        "gauge",
+        "The inputs the fuzz test executes per second",
+        |m| m.stats.execs_per_sec,
+    ),
+    (
+        "edges_covered",
+        "gauge",
+        "The edges the corpus of the fuzz test covers",
+        |m| m.stats.cov,
+    ),
+    (
+        "features",
+        "gauge",
+        "The coverage features of the corpus, including edge counters",
+        |m| m.stats.features,
+    ),
+    ("corpus_size", "gauge", "The inputs in the corpus", |m| {
+        m.stats.corpus
+    }),
+    (
+        "rss_bytes",
+        "gauge",
+        "The resident memory of the fuzz test",
+        |m| m.stats.rss_mb * 1024 * 1024,
+    ),
+    (
+        "findings_total",
+        "counter",
+        "The findings of the fuzz test, including duplicates",
+        |m| m.findings,
+    ),
+    (
+        "uptime_seconds",
+        "gauge",
+        "How long the fuzz test has been running",
+        |m| m.uptime().as_secs(),
+    ),
+    (
+        "seconds_since_new_coverage",
+        "gauge",
+        "How long ago the fuzz test last covered new edges",
+        |m| m.last_new_coverage.elapsed().as_secs(),
+    ),
+];
+
+impl Metrics {
+    /// Formats the metrics in the text format of Prometheus.
+    fn prometheus(&self) -> String {
+        let mut text = String::new();
+        for (name, kind, help, value) in METRICS_LIST {
+            text.push_str(&format!(
+                "# HELP cifuzz_{name} {help}.\n# TYPE cifuzz_{name} {kind}\n"
+            ));
+            for (fuzz_test, metrics) in &self.fuzz_tests {
+                text.push_str(&format!(
+                    "cifuzz_{name}{{fuzz_test=\"{}\"}} {}\n",
+                    escape_label(fuzz_test),
+                    value(metrics)
+                ));
+            }
+        }
+        text
+    }
+
+    /// Formats the metrics as StatsD lines, the counters as the increase
+    /// since the last push.
+    fn statsd(&mut self) -> String {
+        let mut lines = String::new();
+        for (fuzz_test, metrics) in &self.fuzz_tests {
+            let prefix = format!("cifuzz.{}", statsd_name(fuzz_test));
+            let pushed = self.pushed.entry(fuzz_test.clone()).or_default();
+            let execs = metrics.execs();
+            lines.push_str(&format!(
+                "{prefix}.executions:{}|c\n{prefix}.findings:{}|c\n",
+                execs - pushed.0,
+                metrics.findings - pushed.1
+            ));
+            *pushed = (execs, metrics.findings);
+            for (name, kind, _, value) in METRICS_LIST {
+                if *kind == "gauge" {
+                    lines.push_str(&format!("{prefix}.{name}:{}|g\n", value(metrics)));
+                }
+            }
+        }
+        lines
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// StatsD separates the parts of the metric names by dots, so the
+/// other characters except letters, digits and underscores are
+/// replaced.
+fn statsd_name(fuzz_test: &str) -> String {
+    fuzz_test
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics() -> Metrics {
+        let start = Instant::now();
+        Metrics {
+            current: None,
+            fuzz_tests: BTreeMap::from([(
+                "my_fuzz_test".to_string(),
+                FuzzTestMetrics {
+                    start: Some(start),
+                    stop: Some(start + Duration::from_secs(90)),
+                    execs_before: 1000,
+                    stats: Stats {
+                        execs: 234,
+                        cov: 61,
+                        features: 70,
+                        corpus: 12,
+                        execs_per_sec: 617,
+                        rss_mb: 28,
+                    },
+                    findings: 2,
+                    last_new_coverage: start,
+                },
+            )]),
+            pushed: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn parse_endpoint() {
+        assert_eq!(
+            Endpoint::parse("http://0.0.0.0:9464/metrics").unwrap(),
+            Endpoint::Prometheus("0.0.0.0:9464".to_string())
+        );
+        assert_eq!(
+            Endpoint::parse("statsd://localhost:8125").unwrap(),
+            Endpoint::Statsd("localhost:8125".to_string())
+        );
+        assert!(Endpoint::parse("localhost:8125").is_err());
+    }
+
+    #[test]
+    fn prometheus_format() {
+        let text = metrics().prometheus();
+        assert!(text.contains(
+            "# HELP cifuzz_executions_total The inputs the fuzz test executed.\n\
+             # TYPE cifuzz_executions_total counter\n\
+             cifuzz_executions_total{fuzz_test=\"my_fuzz_test\"} 1234\n"
+        ));
+        assert!(text.contains("cifuzz_rss_bytes{fuzz_test=\"my_fuzz_test\"} 29360128\n"));
+        assert!(text.contains("cifuzz_uptime_seconds{fuzz_test=\"my_fuzz_test\"} 90\n"));
+        assert_eq!(escape_label("a\"b"), "a\\\"b");
+    }
+
+    #[test]
+    fn statsd_format() {
+        let mut metrics = metrics();
+        let lines = metrics.statsd();
+        assert!(lines.starts_with(
+            "cifuzz.my_fuzz_test.executions:1234|c\ncifuzz.my_fuzz_test.findings:2|c\n"
+        ));
+        assert!(lines.contains("cifuzz.my_fuzz_test.edges_covered:61|g\n"));
+        // Counters are pushed as their increase
+        metrics
+            .fuzz_tests
+            .get_mut("my_fuzz_test")
+            .unwrap()
+            .stats
+            .execs = 300;
+        assert!(metrics.statsd().starts_with(
+            "cifuzz.my_fuzz_test.executions:66|c\ncifuzz.my_fuzz_test.findings:0|c\n"
+        ));
+        assert_eq!(statsd_name("parser::my_fuzz_test"), "parser__my_fuzz_test");
+    }
+}
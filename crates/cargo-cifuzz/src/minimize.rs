@@ -0,0 +1,499 @@
+//! Shrinking crashing inputs.
+//!
+//! An input is only replaced by a smaller one if the smaller one still
+//! causes the same crash, i.e. if its crash has the same dedup token.
+//! Shrinking is done in two ways:
+//!
+//! * Structure-aware, along the calls of the `FuzzedDataProvider`, which
+//!   the runtime records when asked to (see [`trace`](crate::trace)):
+//!   Bytes which were never consumed are removed, values consumed from
+//!   the front like strings are removed as a whole or shortened, and
+//!   integers consumed from the end are moved towards zero, or towards
+//!   the minimum of their range if it doesn't contain zero. The latter
+//!   also shortens collections, whose length is such an integer.
+//! * By removing chunks of bytes of decreasing size, which also works
+//!   for fuzz tests taking raw bytes.
+
+use anyhow::{bail, Result};
+
+use crate::trace::Call;
+
+/// The methods of the provider which consume strings terminated by a
+/// backslash followed by another byte.
+const TERMINATED_STRING_METHODS: &[&str] = &[
+    "consume_string(",
+    "consume_ascii_string(",
+    "consume_string_from_charset(",
+];
+
+/// A region of the input consumed by a single call of the
+/// `FuzzedDataProvider`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub start: usize,
+    pub len: usize,
+    /// Whether the region was consumed from the end, like integers
+    pub from_end: bool,
+}
+
+impl Region {
+    fn end(&self) -> usize {
+        self.start + self.len
+    }
+}
+
+/// Parses the regions of a call written by the runtime, one per line as
+/// `<front|back> <start> <len>`.
+pub fn parse_trace(trace: &str) -> Result<Vec<Region>> {
+    trace
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(|line| {
+            let mut parts = line.split(' ');
+            let (Some(side), Some(start), Some(len), None) =
+                (parts.next(), parts.next(), parts.next(), parts.next())
+            else {
+                bail!("invalid trace line {line:?}");
+            };
+            let from_end = match side {
+                "front" => false,
+                "back" => true,
+                _ => bail!("invalid trace line {line:?}"),
+            };
+            Ok(Region {
+                start: start.parse()?,
+                len: len.parse()?,
+                from_end,
+            })
+        })
+        .collect()
+}
+
+/// The result of executing the fuzz test with a candidate input.
+#[derive(Debug)]
+pub enum Outcome {
+    /// The input causes the same crash, with the given calls of the
+    /// provider
+    Reproduces(Vec<Call>),
+    DoesNotReproduce,
+}
+
+pub struct Minimizer<F> {
+    execute: F,
+    max_runs: usize,
+    runs: usize,
+}
+
+impl<F: FnMut(&[u8]) -> Result<Outcome>> Minimizer<F> {
+    /// Creates a minimizer which checks candidates with `execute` and
+    /// stops after `max_runs` executions.
+    pub fn new(max_runs: usize, execute: F) -> Self {
+        Minimizer {
+            execute,
+            max_runs,
+            runs: 0,
+        }
+    }
+
+    /// The number of executions so far.
+    pub fn runs(&self) -> usize {
+        self.runs
+    }
+
+    /// Returns the smallest input found which still reproduces the
+    /// crash of `input`.
+    pub fn minimize(&mut self, input: Vec<u8>) -> Result<Vec<u8>> {
+        let Outcome::Reproduces(calls) = (self.execute)(&input)? else {
+            bail!("The input doesn't reproduce the crash");
+        };
+        self.runs += 1;
+
+        let mut state = State { input, calls };
+        loop {
+            let input = state.input.clone();
+            self.shrink_structure(&mut state)?;
+            self.remove_chunks(&mut state)?;
+            if state.input == input || self.exhausted() {
+                return Ok(state.input);
+            }
+        }
+    }
+
+    fn exhausted(&self) -> bool {
+        self.runs >= self.max_runs
+    }
+
+    /// Executes the candidate and replaces the current input with it if
+    /// it reproduces the crash.
+    fn try_candidate(&mut self, state: &mut State, candidate: Vec<u8>) -> Result<bool> {
+        if self.exhausted() || candidate == state.input {
+            return Ok(false);
+        }
+        self.runs += 1;
+        match (self.execute)(&candidate)? {
+            Outcome::Reproduces(calls) => {
+                *state = State {
+                    input: candidate,
+                    calls,
+                };
+                Ok(true)
+            }
+            Outcome::DoesNotReproduce => Ok(false),
+        }
+    }
+
+    fn shrink_structure(&mut self, state: &mut State) -> Result<()> {
+        // Every accepted candidate changes the regions, so the
+        // candidates are recomputed from the start
+        'restart: loop {
+            if let Some(candidate) = state.without_unconsumed_bytes() {
+                if self.try_candidate(state, candidate)? {
+                    continue 'restart;
+                }
+            }
+            for call in state.calls.clone() {
+                for &region in &call.regions {
+                    if region.from_end {
+                        let Some(value) = state.integer(region) else {
+                            continue;
+                        };
+                        // The candidates are only created when they're
+                        // executed, there are many of them for large inputs
+                        let target = zero_offset(&call.method)
+                            .filter(|&target| fits(target, region.len))
+                            .unwrap_or(0);
+                        for offset in closer(value, target) {
+                            let candidate = state.with_integer(region, offset);
+                            if self.try_candidate(state, candidate)? {
+                                continue 'restart;
+                            }
+                        }
+                    } else {
+                        let candidate = state.without(region.start, region.len);
+                        if self.try_candidate(state, candidate)? {
+                            continue 'restart;
+                        }
+                        // Strings are shortened from their end, keeping the
+                        // terminator
+                        let end = region.end() - state.terminator_len(&call, region);
+                        let len = end - region.start;
+                        for kept in closer(len as u64, 0) {
+                            let kept = kept as usize;
+                            let candidate = state.without(region.start + kept, len - kept);
+                            if self.try_candidate(state, candidate)? {
+                                continue 'restart;
+                            }
+                        }
+                    }
+                }
+            }
+            return Ok(());
+        }
+    }
+
+    fn remove_chunks(&mut self, state: &mut State) -> Result<()> {
+        let mut chunk_len = state.input.len() / 2;
+        while chunk_len > 0 && !self.exhausted() {
+            let mut start = 0;
+            while start < state.input.len() && !self.exhausted() {
+                let candidate = state.without(start, chunk_len);
+                if !self.try_candidate(state, candidate)? {
+                    start += chunk_len;
+                }
+            }
+            chunk_len /= 2;
+        }
+        Ok(())
+    }
+}
+
+struct State {
+    input: Vec<u8>,
+    calls: Vec<Call>,
+}
+
+/// Returns the values from `target` towards `value`, excluding `value`,
+/// in steps which halve the remaining distance, e.g. 0, 50, 75, ..., 99
+/// from 100 to 0.
+fn closer(value: u64, target: u64) -> impl Iterator<Item = u64> {
+    let distance = value.abs_diff(target);
+    (0..u64::BITS)
+        .map(move |k| distance - (distance >> k))
+        .take_while(move |&kept| kept < distance)
+        .map(move |kept| {
+            if value > target {
+                target + kept
+            } else {
+                target - kept
+            }
+        })
+}
+
+/// Returns the offset from the minimum which the provider decodes to
+/// zero of an integer consumed by the method, if its range contains zero
+/// and the minimum isn't zero, e.g. for `consume_int::<i32>()` or
+/// `consume_int_in_range::<i8>(-10, 10)`.
+fn zero_offset(method: &str) -> Option<u64> {
+    if let Some(rest) = method.strip_prefix("consume_int::<") {
+        let bits = match rest.strip_suffix(">()")? {
+            "i8" => 8,
+            "i16" => 16,
+            "i32" => 32,
+            "i64" | "isize" => 64,
+            _ => return None,
+        };
+        return Some(1 << (bits - 1));
+    }
+    let (_, range) = method
+        .strip_prefix("consume_int_in_range::<")?
+        .split_once(">(")?;
+    let (min, max) = range.strip_suffix(')')?.split_once(", ")?;
+    let (min, max): (i128, i128) = (min.parse().ok()?, max.parse().ok()?);
+    (min < 0 && max >= 0).then(|| min.unsigned_abs() as u64)
+}
+
+/// Whether the offset can be encoded in `len` bytes.
+fn fits(offset: u64, len: usize) -> bool {
+    len >= 8 || offset >> (8 * len) == 0
+}
+
+impl State {
+    /// Returns the integer which the provider decoded from a region
+    /// consumed from the end, as the offset from the minimum of its
+    /// range. The last byte of the input is the most significant one.
+    fn integer(&self, region: Region) -> Option<u64> {
+        let bytes = self.input.get(region.start..region.end())?;
+        (bytes.len() <= 8).then(|| {
+            bytes
+                .iter()
+                .rev()
+                .fold(0, |value, &byte| (value << 8) | u64::from(byte))
+        })
+    }
+
+    /// Encodes the offset in the region, see [`Self::integer`].
+    fn with_integer(&self, region: Region, offset: u64) -> Vec<u8> {
+        let mut candidate = self.input.clone();
+        for (i, byte) in candidate[region.start..region.end()].iter_mut().enumerate() {
+            *byte = (offset >> (8 * i)) as u8;
+        }
+        candidate
+    }
+
+    /// Returns the length of the terminator at the end of a region
+    /// consumed as a string, i.e. 2 if the string ends with a backslash
+    /// followed by another byte, which isn't part of the string, and 0
+    /// otherwise.
+    fn terminator_len(&self, call: &Call, region: Region) -> usize {
+        if !TERMINATED_STRING_METHODS
+            .iter()
+            .any(|method| call.method.starts_with(method))
+        {
+            return 0;
+        }
+        let Some([bytes @ .., b'\\', last]) = self.input.get(region.start..region.end()) else {
+            return 0;
+        };
+        // An escaped backslash consists of two of them
+        let backslashes = bytes.iter().rev().take_while(|&&b| b == b'\\').count() + 1;
+        if *last != b'\\' && backslashes % 2 == 1 {
+            2
+        } else {
+            0
+        }
+    }
+
+    /// Removes the bytes between the regions consumed from the front and
+    /// those consumed from the end.
+    fn without_unconsumed_bytes(&self) -> Option<Vec<u8>> {
+        let front_end = self
+            .regions()
+            .filter(|r| !r.from_end)
+            .map(Region::end)
+            .max()
+            .unwrap_or(0);
+        let back_start = self
+            .regions()
+            .filter(|r| r.from_end)
+            .map(|r| r.start)
+            .min()
+            .unwrap_or(self.input.len());
+        (back_start > front_end).then(|| self.without(front_end, back_start - front_end))
+    }
+
+    fn regions(&self) -> impl Iterator<Item = &Region> {
+        self.calls.iter().flat_map(|call| &call.regions)
+    }
+
+    fn without(&self, start: usize, len: usize) -> Vec<u8> {
+        let start = start.min(self.input.len());
+        let end = (start + len).min(self.input.len());
+        let mut candidate = self.input[..start].to_vec();
+        candidate.extend_from_slice(&self.input[end..]);
+        candidate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_trace_lines() {
+        assert_eq!(
+            parse_trace("back 6 2\nfront 0 3\n").unwrap(),
+            vec![
+                Region {
+                    start: 6,
+                    len: 2,
+                    from_end: true
+                },
+                Region {
+                    start: 0,
+                    len: 3,
+                    from_end: false
+                },
+            ]
+        );
+        assert!(parse_trace("middle 0 1").is_err());
+        assert!(parse_trace("front 0").is_err());
+    }
+
+    /// A fake fuzz test which consumes a length byte from the end and
+    /// then that many bytes from the front, crashing if they contain
+    /// "FUZZ".
+    fn execute(input: &[u8]) -> Result<Outcome> {
+        let Some((&len, rest)) = input.split_last() else {
+            return Ok(Outcome::DoesNotReproduce);
+        };
+        let len = usize::from(len).min(rest.len());
+        let front = &rest[..len];
+        if !front.windows(4).any(|w| w == b"FUZZ") {
+            return Ok(Outcome::DoesNotReproduce);
+        }
+        Ok(Outcome::Reproduces(vec![
+            call("consume_int::<u8>()", input.len() - 1, 1, true),
+            call("consume_bytes(255)", 0, len, false),
+        ]))
+    }
+
+    fn call(method: &str, start: usize, len: usize, from_end: bool) -> Call {
+        Call {
+            regions: vec![Region {
+                start,
+                len,
+                from_end,
+            }],
+            method: method.to_string(),
+            value: String::new(),
+        }
+    }
+
+    #[test]
+    fn minimize_input() {
+        let mut input = b"xxxxxxxxFUZZyyyy".to_vec();
+        input.extend_from_slice(&[0xaa; 100]);
+        input.push(14);
+        let mut minimizer = Minimizer::new(10_000, execute);
+        let minimized = minimizer.minimize(input).unwrap();
+        // Only "FUZZ" and its length are left
+        assert_eq!(minimized, b"FUZZ\x04");
+    }
+
+    #[test]
+    fn shrink_signed_integers_towards_zero() {
+        // Crashes if an i32 consumed from the end is at least 1000
+        let execute = |input: &[u8]| {
+            let Some(bytes) = input.last_chunk::<4>() else {
+                return Ok(Outcome::DoesNotReproduce);
+            };
+            let value = i64::from(i32::MIN) + i64::from(u32::from_le_bytes(*bytes));
+            if value < 1000 {
+                return Ok(Outcome::DoesNotReproduce);
+            }
+            Ok(Outcome::Reproduces(vec![call(
+                "consume_int::<i32>()",
+                input.len() - 4,
+                4,
+                true,
+            )]))
+        };
+        let input = (i32::MAX as u32 + 2_000_000_001).to_le_bytes().to_vec();
+        let minimized = Minimizer::new(10_000, execute).minimize(input).unwrap();
+        let value =
+            i64::from(i32::MIN) + i64::from(u32::from_le_bytes(minimized[..].try_into().unwrap()));
+        assert_eq!(value, 1000);
+    }
+
+    #[test]
+    fn shorten_strings() {
+        // Consumes a string terminated by "\\x" like consume_string and
+        // crashes if it starts with "FU"
+        let execute = |input: &[u8]| {
+            let end = input.windows(2).position(|w| w == b"\\x");
+            let string = &input[..end.unwrap_or(input.len())];
+            if !string.starts_with(b"FU") {
+                return Ok(Outcome::DoesNotReproduce);
+            }
+            let len = end.map_or(input.len(), |end| end + 2);
+            Ok(Outcome::Reproduces(vec![call(
+                "consume_string(100)",
+                0,
+                len,
+                false,
+            )]))
+        };
+        let input = b"FUZZING and more\\xrest".to_vec();
+        let mut minimizer = Minimizer::new(10_000, execute);
+        let minimized = minimizer.minimize(input).unwrap();
+        assert_eq!(minimized, b"FU");
+        let state = State {
+            input: b"a\\\\\\x".to_vec(),
+            calls: vec![call("consume_string(100)", 0, 5, false)],
+        };
+        assert_eq!(
+            state.terminator_len(&state.calls[0], state.calls[0].regions[0]),
+            2
+        );
+        let state = State {
+            input: b"a\\\\".to_vec(),
+            calls: vec![call("consume_string(100)", 0, 3, false)],
+        };
+        assert_eq!(
+            state.terminator_len(&state.calls[0], state.calls[0].regions[0]),
+            0
+        );
+    }
+
+    #[test]
+    fn closer_values() {
+        assert_eq!(
+            closer(100, 0).collect::<Vec<_>>(),
+            [0, 50, 75, 88, 94, 97, 99]
+        );
+        assert_eq!(closer(10, 16).collect::<Vec<_>>(), [16, 13, 11]);
+        assert_eq!(closer(5, 5).count(), 0);
+        assert_eq!(zero_offset("consume_int::<i8>()"), Some(128));
+        assert_eq!(zero_offset("consume_int::<u32>()"), None);
+        assert_eq!(
+            zero_offset("consume_int_in_range::<i64>(-10, 10)"),
+            Some(10)
+        );
+        assert_eq!(zero_offset("consume_int_in_range::<i64>(1, 10)"), None);
+    }
+
+    #[test]
+    fn minimize_respects_max_runs() {
+        let mut minimizer = Minimizer::new(3, execute);
+        let input = b"xxFUZZyyyy\x06".to_vec();
+        let minimized = minimizer.minimize(input.clone()).unwrap();
+        assert_eq!(minimizer.runs(), 3);
+        assert!(minimized.len() < input.len());
+    }
+
+    #[test]
+    fn minimize_input_which_does_not_crash() {
+        let mut minimizer = Minimizer::new(10, execute);
+        assert!(minimizer.minimize(b"abc\x03".to_vec()).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! Instrumentation of native code, i.e. the C and C++ code which fuzz
+//! tests call via FFI and which build scripts compile, e.g. with the
+//! `cc` or `cmake` crates.
+//!
+//! The native code is compiled with the clang flags corresponding to
+//! the instrumentation of the Rust code, so that the fuzzer gets its
+//! coverage and the sanitizer detects its bugs, and with debug info, so
+//! that its frames are symbolized in the stack traces. CFLAGS apply to
+//! all packages, including libfuzzer-sys, which compiles libFuzzer
+//! itself and must not be instrumented, so the flags are passed by
+//! cargo-cifuzz instead: the build sets CC and CXX to links to
+//! cargo-cifuzz named `cifuzz-clang` and `cifuzz-clang++`, with the
+//! [`Compilers`] in the environment, and the wrapper executes the
+//! actual compiler with the flags.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// The environment variable with the compilers, which the wrapper
+/// executes.
+const WRAPPER_ENV: &str = "CIFUZZ_CC_WRAPPER";
+
+/// The names of the links to cargo-cifuzz which are passed as CC and
+/// CXX. The `cc` crate of old versions detects compilers by their name.
+const CC_NAME: &str = "cifuzz-clang";
+const CXX_NAME: &str = "cifuzz-clang++";
+
+/// The packages whose native code is never instrumented, so that the
+/// fuzzer doesn't get the coverage of its own code.
+const UNINSTRUMENTED_PACKAGES: &[&str] = &["libfuzzer-sys"];
+
+/// The compilers the wrapper executes, with which flags.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Compilers {
+    /// The C and C++ compilers, from CC and CXX or clang by default.
+    /// They may be prefixed by a wrapper like ccache.
+    cc: String,
+    cxx: String,
+    /// The instrumentation flags
+    flags: Vec<String>,
+}
+
+impl Compilers {
+    /// Returns the compilers of the environment with the flags, or none
+    /// if there are no flags or CC isn't set and clang isn't installed.
+    /// The flags are those of clang, other compilers are expected to be
+    /// compatible if they're set explicitly.
+    pub fn new(flags: Vec<String>) -> Option<Compilers> {
+        if flags.is_empty() {
+            return None;
+        }
+        let env = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let (cc, cxx) = match (env("CC"), env("CXX")) {
+            (Some(cc), Some(cxx)) => (cc, cxx),
+            (cc, cxx) if cc.is_some() || is_installed("clang") => (
+                cc.unwrap_or_else(|| "clang".to_string()),
+                cxx.unwrap_or_else(|| "clang++".to_string()),
+            ),
+            _ => return None,
+        };
+        Some(Compilers { cc, cxx, flags })
+    }
+
+    /// Creates the links to cargo-cifuzz in the directory and returns
+    /// the environment variables which make the build scripts use them.
+    pub fn env(&self, dir: &Path) -> Result<Vec<(&'static str, OsString)>> {
+        let exe = std::env::current_exe().context("failed to find cargo-cifuzz")?;
+        let cc = link(&exe, dir, CC_NAME)?;
+        let cxx = link(&exe, dir, CXX_NAME)?;
+        Ok(vec![
+            ("CC", cc.into_os_string()),
+            ("CXX", cxx.into_os_string()),
+            (WRAPPER_ENV, serde_json::to_string(self)?.into()),
+        ])
+    }
+
+    /// The command which executes the C or C++ compiler with the
+    /// arguments, and with the flags unless the package is one of
+    /// [`UNINSTRUMENTED_PACKAGES`].
+    fn command(&self, cxx: bool, package: Option<&str>, args: &[OsString]) -> Command {
+        let compiler = if cxx { &self.cxx } else { &self.cc };
+        let mut words = compiler.split_whitespace();
+        let mut cmd = Command::new(words.next().unwrap_or(compiler));
+        cmd.args(words).args(args);
+        if !package.is_some_and(|package| UNINSTRUMENTED_PACKAGES.contains(&package)) {
+            cmd.args(&self.flags);
+        }
+        cmd
+    }
+}
+
+/// Whether the program is found in the PATH.
+fn is_installed(program: &str) -> bool {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    std::env::split_paths(&path).any(|dir| dir.join(program).is_file())
+}
+
+/// Links the executable into the directory under the name, replacing
+/// the link of a previous build.
+fn link(exe: &Path, dir: &Path, name: &str) -> Result<PathBuf> {
+    let path = dir.join(format!("{name}{}", std::env::consts::EXE_SUFFIX));
+    if path.symlink_metadata().is_ok() {
+        std::fs::remove_file(&path)
+            .with_context(|| format!("failed to remove {}", path.display()))?;
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(exe, &path)
+        .with_context(|| format!("failed to create {}", path.display()))?;
+    #[cfg(not(unix))]
+    std::fs::copy(exe, &path).with_context(|| format!("failed to create {}", path.display()))?;
+    Ok(path)
+}
+
+/// Returns whether cargo-cifuzz was executed as the C++ compiler, or
+/// none if it wasn't executed as a compiler.
+pub fn invoked_as_compiler() -> Option<bool> {
+    let arg0 = std::env::args_os().next()?;
+    let name = Path::new(&arg0).file_stem()?.to_str()?.to_string();
+    match name.as_str() {
+        CC_NAME => Some(false),
+        CXX_NAME => Some(true),
+        _ => None,
+    }
+}
+
+/// Runs the C or C++ compiler as its wrapper, with the arguments the
+/// build script passed to the wrapper.
+pub fn run_wrapper(cxx: bool) -> ExitCode {
+    let compilers = std::env::var(WRAPPER_ENV)
+        .ok()
+        .and_then(|json| serde_json::from_str::<Compilers>(&json).ok());
+    let Some(compilers) = compilers else {
+        eprintln!("cargo-cifuzz: {WRAPPER_ENV} isn't set or invalid");
+        return ExitCode::FAILURE;
+    };
+    let args: Vec<OsString> = std::env::args_os().skip(1).collect();
+    let package = std::env::var("CARGO_PKG_NAME").ok();
+    match compilers.command(cxx, package.as_deref(), &args).status() {
+        Ok(status) => ExitCode::from(status.code().map_or(1, |code| code as u8)),
+        Err(err) => {
+            eprintln!("cargo-cifuzz: failed to execute the compiler: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compiler_commands() {
+        let compilers = Compilers {
+            cc: "ccache clang-18".to_string(),
+            cxx: "clang++".to_string(),
+            flags: vec!["-fsanitize=fuzzer-no-link".to_string()],
+        };
+        let args = ["-c".into(), "parser.c".into()];
+        let cmd = compilers.command(false, Some("my-crate"), &args);
+        assert_eq!(cmd.get_program(), "ccache");
+        assert_eq!(
+            cmd.get_args().collect::<Vec<_>>(),
+            ["clang-18", "-c", "parser.c", "-fsanitize=fuzzer-no-link"]
+        );
+
+        let cmd = compilers.command(true, Some("libfuzzer-sys"), &args);
+        assert_eq!(cmd.get_program(), "clang++");
+        assert_eq!(cmd.get_args().collect::<Vec<_>>(), ["-c", "parser.c"]);
+    }
+}
@@ -0,0 +1,191 @@
+//! Notifications about new findings, for always-on fuzzing machines
+//! whose runs nobody watches.
+//!
+//! With `notify: { webhook: <url> }` in the cifuzz.yaml, a JSON payload
+//! is posted with curl for every finding which isn't a duplicate of an
+//! existing one. The `text` field makes it a valid message for the
+//! incoming webhooks of Slack and Microsoft Teams, the other fields are
+//! for other receivers:
+//!
+//! ```json
+//! {
+//!   "text": "cifuzz found a new crash in my_fuzz_test: ...",
+//!   "fuzz_test": "my_fuzz_test",
+//!   "finding": "5ed1b771cc61",
+//!   "type": "CRASH",
+//!   "severity": "low",
+//!   "kind": "panic",
+//!   "details": "...",
+//!   "dedup_token": "e6c1a2d3",
+//!   "stack_trace": ["#0 cargo_example::explore_me at src/explore_me.rs:14:21"],
+//!   "artifact": "/path/to/.cifuzz/findings/my_fuzz_test/5ed1b771cc61/crashing-input",
+//!   "commit": "0f3c2e1"
+//! }
+//! ```
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+
+use crate::config::Notify;
+use crate::finding::{Finding, Severity, CRASHING_INPUT_FILE};
+use crate::{log, severity};
+
+/// Announces a new finding, which is stored in `dir`. Failing to notify
+/// doesn't fail the run.
+pub fn new_finding(notify: &Notify, finding: &Finding, severity: Severity, dir: &Path) {
+    let Some(url) = &notify.webhook else {
+        return;
+    };
+    match post(url, &payload(finding, severity, dir)) {
+        Ok(()) => log::debug!("Posted the finding {} to the webhook", finding.name),
+        Err(err) => log::error!(
+            "Failed to notify the webhook of the finding {}: {err:#}",
+            finding.name
+        ),
+    }
+}
+
+/// The JSON payload announcing the finding.
+fn payload(finding: &Finding, severity: Severity, dir: &Path) -> Value {
+    let stack_trace: Vec<String> = finding
+        .stack_trace
+        .iter()
+        .map(|frame| {
+            if frame.source_file.is_empty() {
+                format!("#{} {}", frame.frame_number, frame.function)
+            } else {
+                format!(
+                    "#{} {} at {}:{}:{}",
+                    frame.frame_number, frame.function, frame.source_file, frame.line, frame.column
+                )
+            }
+        })
+        .collect();
+    // Slack and Teams show only the text, so it carries the location
+    let mut text = format!(
+        "cifuzz found a new {} in {}: {}",
+        finding.error_type.description(),
+        finding.metadata.fuzz_test,
+        finding.details.lines().next().unwrap_or_default()
+    );
+    if let Some(frame) = finding
+        .stack_trace
+        .iter()
+        .find(|frame| !frame.source_file.is_empty() && !frame.source_file.starts_with('/'))
+    {
+        text.push_str(&format!(
+            "\nat {}:{} (dedup token {})",
+            frame.source_file, frame.line, finding.dedup_token
+        ));
+    } else {
+        text.push_str(&format!("\n(dedup token {})", finding.dedup_token));
+    }
+    json!({
+        "text": text,
+        "fuzz_test": finding.metadata.fuzz_test,
+        "finding": finding.name,
+        "type": finding.error_type,
+        "severity": severity.name(),
+        "kind": severity::kind(finding),
+        "details": finding.details,
+        "dedup_token": finding.dedup_token,
+        "stack_trace": stack_trace,
+        "artifact": dir.join(CRASHING_INPUT_FILE),
+        "commit": finding.metadata.commit,
+    })
+}
+
+fn post(url: &str, payload: &Value) -> Result<()> {
+    let mut cmd = Command::new("curl");
+    // Fail on HTTP errors, the payload is read from stdin
+    cmd.args(["-fsS", "-X", "POST", "-H", "Content-Type: application/json"])
+        .args(["--max-time", "30", "--data-binary", "@-", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    log::debug!("Command: {:?}", cmd);
+    let mut child = cmd
+        .spawn()
+        .context("failed to execute curl, which posts to the webhook")?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(payload.to_string().as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "curl failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::finding::{test_finding, StackFrame};
+
+    #[test]
+    fn finding_payload() {
+        let mut finding = Finding {
+            details: "branch 4 has been reached\nnote: run with `RUST_BACKTRACE=1`".to_string(),
+            stack_trace: vec![
+                StackFrame {
+                    source_file: "/rustc/abc/library/core/src/panicking.rs".to_string(),
+                    line: 75,
+                    column: 14,
+                    frame_number: 0,
+                    function: "core::panicking::panic".to_string(),
+                },
+                StackFrame {
+                    source_file: "src/explore_me.rs".to_string(),
+                    line: 14,
+                    column: 21,
+                    frame_number: 1,
+                    function: "cargo_example::explore_me".to_string(),
+                },
+                StackFrame {
+                    source_file: String::new(),
+                    line: 0,
+                    column: 0,
+                    frame_number: 2,
+                    function: "cargo_example::my_fuzz_test".to_string(),
+                },
+            ],
+            dedup_token: "e6c1a2d3".to_string(),
+            ..test_finding("5ed1b771cc61")
+        };
+        finding.metadata.commit = Some("0f3c2e1".to_string());
+        let dir = Path::new("/project/.cifuzz/findings/my_fuzz_test/5ed1b771cc61");
+        let payload = payload(&finding, Severity::Low, dir);
+        assert_eq!(
+            payload["text"],
+            "cifuzz found a new crash in my_fuzz_test: branch 4 has been reached\n\
+             at src/explore_me.rs:14 (dedup token e6c1a2d3)"
+        );
+        assert_eq!(payload["dedup_token"], "e6c1a2d3");
+        assert_eq!(payload["type"], "CRASH");
+        assert_eq!(payload["severity"], "low");
+        assert_eq!(payload["kind"], "panic");
+        assert_eq!(
+            payload["stack_trace"],
+            json!([
+                "#0 core::panicking::panic at /rustc/abc/library/core/src/panicking.rs:75:14",
+                "#1 cargo_example::explore_me at src/explore_me.rs:14:21",
+                "#2 cargo_example::my_fuzz_test",
+            ])
+        );
+        assert_eq!(
+            payload["artifact"],
+            "/project/.cifuzz/findings/my_fuzz_test/5ed1b771cc61/crashing-input"
+        );
+        assert_eq!(payload["commit"], "0f3c2e1");
+    }
+}
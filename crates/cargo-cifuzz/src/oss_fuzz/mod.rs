@@ -0,0 +1,112 @@
+//! Templates for the OSS-Fuzz project of a crate.
+//!
+//! OSS-Fuzz builds a project with the Dockerfile and the build.sh in
+//! its `projects/<name>` directory, which install cargo-cifuzz and run
+//! `cargo cifuzz bundle --oss-fuzz`, and configures it with the
+//! project.yaml.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+use crate::workspace::CIFUZZ_GIT_URL;
+
+const TEMPLATES: &[(&str, &str)] = &[
+    ("Dockerfile", include_str!("Dockerfile.tmpl")),
+    ("build.sh", include_str!("build.sh.tmpl")),
+    ("project.yaml", include_str!("project.yaml.tmpl")),
+];
+
+/// The settings of an OSS-Fuzz project.
+#[derive(Debug)]
+pub struct Project {
+    pub name: String,
+    pub main_repo: String,
+    pub homepage: String,
+    pub primary_contact: String,
+}
+
+/// Writes the files of the OSS-Fuzz project into the directory and
+/// returns their paths. Existing files are only overwritten with
+/// `force`.
+pub fn write(project: &Project, dir: &Path, force: bool) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    if !force {
+        for (file, _) in TEMPLATES {
+            if dir.join(file).exists() {
+                bail!(
+                    "{} already exists, pass --force to overwrite it",
+                    dir.join(file).display()
+                );
+            }
+        }
+    }
+    let mut paths = Vec::new();
+    for (file, template) in TEMPLATES {
+        let path = dir.join(file);
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        if *file == "build.sh" {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o755);
+        }
+        options
+            .open(&path)
+            .and_then(|mut f| f.write_all(render(template, project).as_bytes()))
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+fn render(template: &str, project: &Project) -> String {
+    template
+        .replace("{name}", &project.name)
+        .replace("{main_repo}", &project.main_repo)
+        .replace("{homepage}", &project.homepage)
+        .replace("{primary_contact}", &project.primary_contact)
+        .replace("{cifuzz_git_url}", CIFUZZ_GIT_URL)
+        .replace("{cifuzz_version}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Returns the OSS-Fuzz project name for a crate, which may only
+/// consist of lowercase letters, digits, '-' and '_'.
+pub fn project_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            c @ ('a'..='z' | '0'..='9' | '-' | '_') => c,
+            _ => '-',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = Project {
+            name: project_name("My.Crate"),
+            main_repo: "https://github.com/me/my-crate".to_string(),
+            homepage: "https://my-crate.rs".to_string(),
+            primary_contact: "me@example.com".to_string(),
+        };
+        assert_eq!(project.name, "my-crate");
+        write(&project, dir.path(), false).unwrap();
+
+        let dockerfile = std::fs::read_to_string(dir.path().join("Dockerfile")).unwrap();
+        assert!(dockerfile
+            .contains("RUN git clone --depth 1 https://github.com/me/my-crate my-crate\n"));
+        assert!(dockerfile.contains("WORKDIR $SRC/my-crate\n"));
+        let project_yaml = std::fs::read_to_string(dir.path().join("project.yaml")).unwrap();
+        assert!(project_yaml.contains("primary_contact: \"me@example.com\"\n"));
+        assert!(!project_yaml.contains('{'));
+
+        assert!(write(&project, dir.path(), false).is_err());
+        write(&project, dir.path(), true).unwrap();
+    }
+}
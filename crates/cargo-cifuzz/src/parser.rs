@@ -0,0 +1,1260 @@
+//! Parsing the output of fuzz tests run under libFuzzer.
+//!
+//! A crash is reported by a Rust panic message followed by the stack
+//! trace printed with `RUST_BACKTRACE=1`, and by libFuzzer, which
+//! prints the kind of the error and the path of the crashing input:
+//!
+//! ```text
+//! thread 'my_fuzz_test::my_fuzz_test::fuzz' panicked at src/explore_me.rs:14:21:
+//! branch 4 has been reached
+//! stack backtrace:
+//!    0: cargo_example::explore_me::explore_me
+//!              at ./src/explore_me.rs:14:21
+//! ...
+//! ==1234== ERROR: libFuzzer: deadly signal
+//! ...
+//! artifact_prefix='/p/.cifuzz-artifacts/my_fuzz_test/'; Test unit written to /p/.cifuzz-artifacts/my_fuzz_test/crash-e6c1...
+//! ```
+//!
+//! Errors detected by a sanitizer are reported by the sanitizer itself,
+//! including the stack trace:
+//!
+//! ```text
+//! ==1234==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x7b87b7402a02 at pc ...
+//! READ of size 1 at 0x7b87b7402a02 thread T1
+//!     #0 0x5561ba0cbb95 in cargo_example::read_at /p/src/lib.rs:5:18
+//! ...
+//! ```
+//!
+//! When the fuzz test runs longer than the input timeout, the watchdog
+//! of the runtime prints its stack trace before libFuzzer reports the
+//! timeout:
+//!
+//! ```text
+//! thread 'my_fuzz_test::fuzz' timed out after 10 seconds
+//! stack backtrace:
+//!    0: cifuzz::watchdog::print_stack_trace
+//! ...
+//! ==1234== ERROR: libFuzzer: timeout after 10 seconds
+//! ```
+//!
+//! Leaks are reported by LeakSanitizer with the stack trace of the
+//! allocation, after libFuzzer detected that an input leaked memory:
+//!
+//! ```text
+//! ==1234==ERROR: LeakSanitizer: detected memory leaks
+//!
+//! Direct leak of 10 byte(s) in 1 object(s) allocated from:
+//!     #0 0x55b2c9e3a1f8 in malloc
+//! ...
+//! INFO: to ignore leaks on libFuzzer side use -detect_leaks=0.
+//! ...
+//! artifact_prefix='/p/.cifuzz-artifacts/my_fuzz_test/'; Test unit written to /p/.cifuzz-artifacts/my_fuzz_test/leak-0c2d...
+//! ```
+//!
+//! Undefined behavior is reported by Miri, which replays the inputs
+//! with `cargo cifuzz run --engine miri`, with the stack trace of the
+//! interpreted program:
+//!
+//! ```text
+//! error: Undefined Behavior: in-bounds pointer arithmetic failed: attempting to offset pointer by 5 bytes, ...
+//!  --> src/lib.rs:5:14
+//! ...
+//!   = note: stack backtrace:
+//!           0: cargo_example::read_at
+//!               at src/lib.rs:5:14: 5:30
+//! ...
+//! ```
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::symbolize;
+
+/// A crash reported in the output of a fuzz test.
+#[derive(Debug, Default, PartialEq)]
+pub struct CrashReport {
+    /// The panic message, if the crash was caused by a panic
+    pub panic_message: Option<String>,
+    /// The source location of the panic, e.g. "src/explore_me.rs:14:21"
+    pub panic_location: Option<String>,
+    /// The error reported by libFuzzer, e.g. "deadly signal", or by a
+    /// sanitizer, e.g. "AddressSanitizer: heap-buffer-overflow"
+    pub error: Option<String>,
+    /// The stack frames of the panic or the sanitizer error, starting
+    /// with the innermost frame
+    pub stack_trace: Vec<Frame>,
+    /// The file to which libFuzzer wrote the crashing input
+    pub input_file: Option<PathBuf>,
+    /// The operations which the `cifuzz::ops!` of the fuzz test executed
+    /// until the panic, e.g. "Push { value: 3 }"
+    pub operations: Vec<String>,
+    /// The output of the fuzz test on the crashing input before the
+    /// report, if it was captured by the runtime
+    pub output: Vec<String>,
+    /// The output starting at the first line of the report
+    pub logs: Vec<String>,
+}
+
+/// A frame of a stack trace printed by the Rust standard library or by
+/// a sanitizer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub function: String,
+    pub file: Option<String>,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl CrashReport {
+    /// A short description of the crash, the panic message if there is
+    /// one.
+    pub fn details(&self) -> String {
+        match (&self.panic_message, &self.error) {
+            (Some(message), _) => message.clone(),
+            (None, Some(error)) => error.clone(),
+            (None, None) => "unknown error".to_string(),
+        }
+    }
+}
+
+/// The progress of libFuzzer as printed in its status lines like
+/// "#1234\tNEW    cov: 61 ft: 70 corp: 12/345b lim: 4 exec/s: 617 rss: 28Mb".
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Stats {
+    /// The number of executed inputs
+    pub execs: u64,
+    /// The number of covered edges
+    pub cov: u64,
+    /// The number of features, i.e. coverage including counters etc.
+    pub features: u64,
+    /// The number of inputs in the corpus
+    pub corpus: u64,
+    pub execs_per_sec: u64,
+    pub rss_mb: u64,
+}
+
+/// Parses a status line of libFuzzer. Returns `None` for other lines.
+pub fn parse_stats(line: &str) -> Option<Stats> {
+    let (execs, rest) = line.strip_prefix('#')?.split_once('\t')?;
+    let mut stats = Stats {
+        execs: execs.parse().ok()?,
+        ..Stats::default()
+    };
+    let mut words = rest.split_whitespace();
+    while let Some(word) = words.next() {
+        let field = match word {
+            "cov:" => &mut stats.cov,
+            "ft:" => &mut stats.features,
+            "corp:" => &mut stats.corpus,
+            "exec/s:" => &mut stats.execs_per_sec,
+            "rss:" => &mut stats.rss_mb,
+            _ => continue,
+        };
+        // Values like "12/345b" or "28Mb" start with the number
+        let value = words.next()?;
+        let end = value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len());
+        *field = value[..end].parse().ok()?;
+    }
+    Some(stats)
+}
+
+/// Parses the summary which honggfuzz prints when it exits, like
+/// "Summary iterations:1000 time:10 speed:100 crashes_count:0
+/// timeout_count:0 new_units_added:12 slowest_unit_ms:3 guard_nb:200
+/// branch_coverage_percent:30 peak_rss_mb:25", into the progress of
+/// libFuzzer. Returns `None` for other lines.
+pub fn parse_honggfuzz_summary(line: &str) -> Option<Stats> {
+    let (_, summary) = line.split_once("Summary iterations:")?;
+    let summary = format!("iterations:{summary}");
+    let mut values = HashMap::new();
+    for field in summary.split_whitespace() {
+        if let Some((key, value)) = field.split_once(':') {
+            values.insert(key, value.parse::<u64>().ok()?);
+        }
+    }
+    let value = |key| values.get(key).copied();
+    // honggfuzz only reports the percentage of the covered edges
+    let cov = value("guard_nb")? * value("branch_coverage_percent")? / 100;
+    Some(Stats {
+        execs: value("iterations")?,
+        cov,
+        features: cov,
+        corpus: value("new_units_added")?,
+        execs_per_sec: value("speed")?,
+        rss_mb: value("peak_rss_mb").unwrap_or_default(),
+    })
+}
+
+/// Returns whether libFuzzer finished executing the initial corpus, i.e.
+/// printed its "INITED" status line. If it didn't, the fuzz test crashed
+/// on an input of the corpus.
+pub fn corpus_loaded(output: &[String]) -> bool {
+    output
+        .iter()
+        .any(|line| parse_stats(line).is_some() && line.split_whitespace().nth(1) == Some("INITED"))
+}
+
+/// Returns the seed of libFuzzer's random number generator, which it
+/// prints at startup as `INFO: Seed: <seed>`.
+pub fn seed(output: &[String]) -> Option<u64> {
+    output.iter().find_map(|line| parse_seed(line))
+}
+
+/// Parses the line with the seed, see [`seed`].
+pub fn parse_seed(line: &str) -> Option<u64> {
+    line.strip_prefix("INFO: Seed: ")?.trim().parse().ok()
+}
+
+/// Returns the number of inputs in the corpus which reach the focus
+/// function set with `-focus_function`, or `None` if libFuzzer didn't
+/// report it. libFuzzer reports it after loading the initial corpus as
+/// "INFO: 1/12 inputs touch the focus function" and in its status lines
+/// as "focus: 3".
+pub fn focus_inputs(output: &[String]) -> Option<u64> {
+    output
+        .iter()
+        .filter_map(|line| {
+            if let Some(info) = line.strip_suffix(" inputs touch the focus function") {
+                let (inputs, _total) = info.strip_prefix("INFO: ")?.split_once('/')?;
+                return inputs.parse().ok();
+            }
+            parse_stats(line)?;
+            let mut words = line.split_whitespace();
+            words.find(|word| *word == "focus:")?;
+            words.next()?.parse().ok()
+        })
+        .max()
+}
+
+/// Prefixes of frames which belong to the runtime or the fuzzing engine
+/// and aren't relevant for the user.
+const IGNORED_FRAME_PREFIXES: &[&str] = &[
+    "std::",
+    "core::",
+    "alloc::",
+    "__rustc::",
+    "<std::",
+    "<core::",
+    "<alloc::",
+    // The allocator functions at the top of the allocation stacks of
+    // leaks
+    "malloc",
+    "calloc",
+    "realloc",
+    "rust_begin_unwind",
+    "panic_unwind::",
+    "__rust_",
+    // The interceptors and error reporting of the sanitizers
+    "__sanitizer",
+    "__asan",
+    "__lsan",
+    "__msan",
+    "__tsan",
+    "__interceptor_",
+    "test::",
+    "libfuzzer_sys::",
+    "LLVMFuzzer",
+    "_ZN6fuzzer",
+    "fuzzer::",
+    "__libc_start",
+    "_start",
+    // The signal handler of the watchdog and the signal trampoline
+    "cifuzz::watchdog::",
+    // The function which panics with the message of a reported finding
+    "cifuzz::oracle::",
+    "<unknown>",
+];
+
+/// Parts of the paths of the sources of the standard library, as
+/// printed for frames of a prebuilt and of a rebuilt standard library.
+/// Frames of generic functions like `<[u8]>::to_vec` are only
+/// recognizable by their source.
+const STD_SOURCE_PATHS: &[&str] = &["/rustc/", "/rustlib/src/rust/library/"];
+
+/// The line before the operations printed by `cifuzz::ops!`, must be
+/// kept in sync with crates/cifuzz/src/ops.rs
+const OPERATIONS_HEADER: &str = "Operations executed by the fuzz test:";
+
+/// The line before the output of the crashing input, which the runtime
+/// prints if it captured the output. Must be kept in sync with
+/// crates/cifuzz/src/capture.rs
+const OUTPUT_HEADER: &str = "Output of the fuzz test on the crashing input:";
+
+/// Frames which mark the beginning of the fuzz test harness. All frames
+/// below them are dropped.
+const HARNESS_FRAME_PREFIXES: &[&str] = &[
+    "cifuzz::harness::",
+    "cifuzz::regression::",
+    // Miri names the functions of the harness by their re-exports
+    "cifuzz::__private::",
+];
+
+/// Suffixes of the functions which the fuzz test macro wraps the fuzz
+/// test in, i.e. the `test_one_input` function of the fuzz test and the
+/// closure calling the fuzz test, as printed by the standard library and
+/// by the sanitizers.
+const WRAPPER_FRAME_SUFFIXES: &[&str] = &[
+    "::fuzz::{{closure}}",
+    "::fuzz::{closure#0}",
+    "::test_one_input",
+    "::test_one_input::{{closure}}",
+    "::test_one_input::{closure#0}",
+];
+
+/// Parses the output of a fuzz test which crashed. Returns `None` if the
+/// output doesn't contain a crash.
+pub fn parse_crash(output: &[String]) -> Option<CrashReport> {
+    let mut report = CrashReport::default();
+    let mut start = None;
+    let mut output_start = None;
+
+    let mut lines = output.iter().enumerate().peekable();
+    while let Some((i, line)) = lines.next() {
+        if line == OUTPUT_HEADER {
+            // The captured output ends with the report
+            output_start.get_or_insert(i + 1);
+        } else if let Some(location) = parse_panic_line(line) {
+            // Only the first panic is relevant, libtest may report
+            // later ones while unwinding
+            if report.panic_location.is_some() {
+                continue;
+            }
+            start.get_or_insert(i);
+            report.panic_location = Some(location.to_string());
+
+            let mut message = Vec::new();
+            while let Some((_, line)) =
+                lines.next_if(|(_, l)| *l != "stack backtrace:" && !l.starts_with("note: "))
+            {
+                message.push(line.as_str());
+            }
+            report.panic_message = Some(message.join("\n"));
+
+            if lines.next_if(|(_, l)| *l == "stack backtrace:").is_some() {
+                report.stack_trace = parse_stack_trace(&mut lines);
+            }
+        } else if is_watchdog_line(line) {
+            // The watchdog of the runtime prints the stack trace of the
+            // fuzz test before the timeout or out-of-memory error
+            start.get_or_insert(i);
+            if lines.next_if(|(_, l)| *l == "stack backtrace:").is_some() {
+                report.stack_trace = parse_stack_trace(&mut lines);
+            }
+        } else if is_allocation_failure(line) {
+            // The standard library aborts if an allocation fails, which
+            // libFuzzer reports as a deadly signal
+            start.get_or_insert(i);
+            report.error = Some(format!("out-of-memory ({line})"));
+        } else if let Some(error) = parse_libfuzzer_error(line) {
+            start.get_or_insert(i);
+            report.error.get_or_insert_with(|| error.to_string());
+        } else if let Some(error) = parse_miri_error(line) {
+            start.get_or_insert(i);
+            report.error = Some(error.to_string());
+            report.stack_trace = parse_miri_stack_trace(&mut lines);
+        } else if let Some(error) = parse_sanitizer_error(line) {
+            // A sanitizer error is reported before libFuzzer's
+            // "deadly signal"
+            start.get_or_insert(i);
+            report.error = Some(error);
+            if report.stack_trace.is_empty() {
+                // The access, e.g. "READ of size 1 at ...", or the leak,
+                // e.g. "Direct leak of 8 byte(s) ...", is printed before
+                // the stack trace
+                while lines
+                    .next_if(|(_, l)| !l.trim_start().starts_with("#0 "))
+                    .is_some_and(|(_, l)| !l.starts_with("SUMMARY: "))
+                {}
+                report.stack_trace = parse_sanitizer_stack_trace(&mut lines);
+            }
+        } else if line == OPERATIONS_HEADER && report.operations.is_empty() {
+            // The operations are printed while unwinding from the panic
+            while let Some((_, line)) = lines.next_if(|(_, l)| parse_operation_line(l).is_some()) {
+                report
+                    .operations
+                    .push(parse_operation_line(line).unwrap().to_string());
+            }
+        } else if let Some((_, path)) = line.split_once("Test unit written to ") {
+            report.input_file = Some(PathBuf::from(path.trim()));
+        }
+    }
+
+    let start = start?;
+    if let Some(output_start) = output_start.filter(|&s| s <= start) {
+        report.output = output[output_start..start].to_vec();
+        // The standard library prints an empty line before the panic
+        while report.output.last().is_some_and(String::is_empty) {
+            report.output.pop();
+        }
+    }
+    report.logs = output[start..].to_vec();
+    Some(report)
+}
+
+/// Returns the location of the panic if the line is the first line of
+/// a panic message like "thread 'main' panicked at src/main.rs:1:5:".
+fn parse_panic_line(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix("thread '")?;
+    let (_, rest) = rest.split_once("' ")?;
+    // Newer Rust versions print the thread ID after the name
+    let rest = match rest.strip_prefix('(') {
+        Some(rest) => rest.split_once(") ")?.1,
+        None => rest,
+    };
+    rest.strip_prefix("panicked at ")?.strip_suffix(':')
+}
+
+/// Whether the line is the message of the runtime's watchdog like
+/// "thread 'my_fuzz_test::fuzz' timed out after 10 seconds" or "thread
+/// 'my_fuzz_test::fuzz' exceeded the memory limit of 2048 MB".
+fn is_watchdog_line(line: &str) -> bool {
+    line.starts_with("thread '")
+        && (line.contains("' timed out after ") || line.contains("' exceeded the memory limit "))
+}
+
+/// Whether the line is the message of the standard library's allocation
+/// error handler like "memory allocation of 4294967296 bytes failed".
+fn is_allocation_failure(line: &str) -> bool {
+    line.strip_prefix("memory allocation of ")
+        .and_then(|rest| rest.strip_suffix(" bytes failed"))
+        .is_some_and(|size| size.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Returns the error of a libFuzzer error line like
+/// "==1234== ERROR: libFuzzer: deadly signal".
+fn parse_libfuzzer_error(line: &str) -> Option<&str> {
+    let (_, error) = line.split_once("ERROR: libFuzzer: ")?;
+    line.starts_with("==").then(|| error.trim())
+}
+
+/// Returns the error of a sanitizer error line like
+/// "==1234==ERROR: AddressSanitizer: heap-buffer-overflow on address
+/// 0x7b87b7402a02 at pc ..." or "WARNING: ThreadSanitizer: data race
+/// (pid=1234)" without the addresses and the process ID.
+fn parse_sanitizer_error(line: &str) -> Option<String> {
+    // Not all sanitizers print the process ID before the error
+    let rest = match line.strip_prefix("==") {
+        Some(rest) => {
+            let (pid, rest) = rest.split_once("==")?;
+            if !pid.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            rest
+        }
+        None => line,
+    };
+    let error = rest
+        .strip_prefix("ERROR: ")
+        .or_else(|| rest.strip_prefix("WARNING: "))?;
+    let (sanitizer, _) = error.split_once(": ")?;
+    if !sanitizer.ends_with("Sanitizer") {
+        return None;
+    }
+    let error = [" on address ", " on unknown address", " (pid="]
+        .iter()
+        .fold(error, |error, suffix| {
+            error.split(suffix).next().unwrap_or(error)
+        });
+    Some(error.trim().to_string())
+}
+
+/// Returns the error of Miri's report of undefined behavior like
+/// "error: Undefined Behavior: out-of-bounds pointer use: ...". libtest
+/// may print the name of the test before it on the same line.
+fn parse_miri_error(line: &str) -> Option<&str> {
+    let (_, error) = line.split_once("error: Undefined Behavior: ")?;
+    Some(error.trim())
+}
+
+/// Parses the stack trace of Miri's report, which follows the source
+/// snippet and the notes of the error.
+fn parse_miri_stack_trace<'a>(
+    lines: &mut std::iter::Peekable<impl Iterator<Item = (usize, &'a String)>>,
+) -> Vec<Frame> {
+    while lines
+        .next_if(|(_, l)| !l.is_empty() && l.trim() != "= note: stack backtrace:")
+        .is_some()
+    {}
+    if lines.next_if(|(_, l)| !l.is_empty()).is_none() {
+        return Vec::new();
+    }
+    let mut frames = Vec::new();
+    while let Some((_, line)) = lines.next_if(|(_, l)| parse_frame_line(l).is_some()) {
+        let function = parse_frame_line(line).unwrap().to_string();
+        // The location has the end of the span, e.g. "at src/lib.rs:5:14: 5:30"
+        let location = lines
+            .next_if(|(_, l)| parse_miri_location_line(l).is_some())
+            .and_then(|(_, l)| parse_miri_location_line(l));
+        let (file, line, column) = match location {
+            Some((file, line, column)) => (Some(file.to_string()), line, column),
+            None => (None, 0, 0),
+        };
+        frames.push(Frame {
+            function,
+            file,
+            line,
+            column,
+        });
+    }
+    relevant_frames(frames)
+}
+
+fn parse_miri_location_line(line: &str) -> Option<(&str, u32, u32)> {
+    let location = line.trim_start().strip_prefix("at ")?;
+    let start = location
+        .rsplit_once(": ")
+        .map_or(location, |(start, _)| start);
+    let (rest, column) = start.rsplit_once(':')?;
+    let (file, line) = rest.rsplit_once(':')?;
+    Some((file, line.parse().ok()?, column.parse().ok()?))
+}
+
+fn parse_sanitizer_stack_trace<'a>(
+    lines: &mut std::iter::Peekable<impl Iterator<Item = (usize, &'a String)>>,
+) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    while let Some((_, line)) = lines.next_if(|(_, l)| parse_sanitizer_frame(l).is_some()) {
+        frames.push(parse_sanitizer_frame(line).unwrap());
+    }
+    relevant_frames(frames)
+}
+
+/// Parses a frame line of a sanitizer like
+/// "    #0 0x5561ba0cbb95 in foo::bar /p/src/lib.rs:5:18" or, as printed
+/// by ThreadSanitizer, "    #0 foo::bar /p/src/lib.rs:5:18 (foo+0x1234)
+/// (BuildId: 8a45)".
+/// Frames of code without debug info have the binary instead of a
+/// location.
+fn parse_sanitizer_frame(line: &str) -> Option<Frame> {
+    let rest = line.trim_start().strip_prefix('#')?;
+    let (number, rest) = rest.split_once(' ')?;
+    if !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let rest = match rest.split_once(' ') {
+        Some((address, rest)) if address.starts_with("0x") => {
+            // Frames without a symbol like "#3 0x55 (/p/foo+0x123)"
+            rest.strip_prefix("in ").unwrap_or(rest)
+        }
+        _ => rest,
+    };
+    // Strip the module and build ID like "(foo+0x1234) (BuildId: 8a45)"
+    let mut symbol = rest;
+    while let Some((prefix, suffix)) = symbol.rsplit_once(" (") {
+        if !suffix.ends_with(')') || !(suffix.contains("+0x") || suffix.starts_with("BuildId: ")) {
+            break;
+        }
+        symbol = prefix;
+    }
+    let location = symbol
+        .rsplit_once(' ')
+        .and_then(|(function, location)| Some((function, parse_location(location)?)));
+    Some(match location {
+        Some((function, (file, line, column))) => Frame {
+            function: symbolize::demangle(function),
+            file: Some(file.to_string()),
+            line,
+            column,
+        },
+        None => Frame {
+            function: symbolize::demangle(symbol.trim()),
+            file: None,
+            line: 0,
+            column: 0,
+        },
+    })
+}
+
+/// Parses a location like "/p/src/lib.rs:5:18" or "/p/src/lib.rs:5".
+fn parse_location(location: &str) -> Option<(&str, u32, u32)> {
+    let (rest, last) = location.rsplit_once(':')?;
+    let last: u32 = last.parse().ok()?;
+    match rest.rsplit_once(':') {
+        Some((file, line)) if line.parse::<u32>().is_ok() => Some((file, line.parse().ok()?, last)),
+        _ => Some((rest, last, 0)),
+    }
+}
+
+fn parse_stack_trace<'a>(
+    lines: &mut std::iter::Peekable<impl Iterator<Item = (usize, &'a String)>>,
+) -> Vec<Frame> {
+    // The standard library can't capture backtraces on wasm, so its note
+    // follows the header, and cifuzz-wasm-driver prints the frames
+    lines.next_if(|(_, l)| l.starts_with("note: "));
+    let mut frames = Vec::new();
+    while let Some((_, line)) = lines.next_if(|(_, l)| parse_frame_line(l).is_some()) {
+        let function = symbolize::demangle(parse_frame_line(line).unwrap());
+        let location = lines
+            .next_if(|(_, l)| parse_location_line(l).is_some())
+            .and_then(|(_, l)| parse_location_line(l));
+        let (file, line, column) = match location {
+            Some((file, line, column)) => (Some(file.to_string()), line, column),
+            None => (None, 0, 0),
+        };
+        frames.push(Frame {
+            function,
+            file,
+            line,
+            column,
+        });
+    }
+    relevant_frames(frames)
+}
+
+/// Parses the function of a frame line like "   2: foo::bar".
+fn parse_frame_line(line: &str) -> Option<&str> {
+    let (number, function) = line.trim_start().split_once(": ")?;
+    number
+        .bytes()
+        .all(|b| b.is_ascii_digit())
+        .then_some(function.trim())
+}
+
+/// Parses an operation line like "  1: Push { value: 3 }".
+fn parse_operation_line(line: &str) -> Option<&str> {
+    let (number, operation) = line.strip_prefix("  ")?.split_once(": ")?;
+    number
+        .bytes()
+        .all(|b| b.is_ascii_digit())
+        .then_some(operation)
+}
+
+/// Parses a location line like "             at ./src/foo.rs:14:21".
+fn parse_location_line(line: &str) -> Option<(&str, u32, u32)> {
+    let location = line.trim_start().strip_prefix("at ")?;
+    let (rest, column) = location.rsplit_once(':')?;
+    let (file, line) = rest.rsplit_once(':')?;
+    Some((file, line.parse().ok()?, column.parse().ok()?))
+}
+
+/// Removes the frames of the panic machinery at the top and of the
+/// harness and fuzzing engine at the bottom of the stack trace.
+fn relevant_frames(frames: Vec<Frame>) -> Vec<Frame> {
+    frames
+        .into_iter()
+        .take_while(|f| {
+            !HARNESS_FRAME_PREFIXES
+                .iter()
+                .any(|p| f.function.starts_with(p))
+        })
+        .filter(|f| {
+            !IGNORED_FRAME_PREFIXES
+                .iter()
+                .any(|p| f.function.starts_with(p))
+        })
+        .filter(|f| {
+            !f.file
+                .as_ref()
+                .is_some_and(|file| STD_SOURCE_PATHS.iter().any(|p| file.contains(p)))
+        })
+        .filter(|f| {
+            !WRAPPER_FRAME_SUFFIXES
+                .iter()
+                .any(|s| f.function.ends_with(s))
+        })
+        // Calls of closures via the Fn traits like
+        // "<foo::{closure#0} as core::ops::function::FnOnce<()>>::call_once"
+        .filter(|f| !f.function.contains(" as core::ops::function::Fn"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(output: &str) -> Vec<String> {
+        output.lines().map(String::from).collect()
+    }
+
+    const CRASH_OUTPUT: &str = "\
+INFO: Running with entropic power schedule (0xFF, 100).
+#2\tINITED cov: 12 ft: 12 corp: 1/1b exec/s: 0 rss: 30Mb
+thread 'my_fuzz_test::my_fuzz_test::fuzz' (4242) panicked at examples/cargo/src/explore_me.rs:14:21:
+branch 4 has been reached
+stack backtrace:
+   0: __rustc::rust_begin_unwind
+             at /rustc/abc/library/std/src/panicking.rs:697:5
+   1: core::panicking::panic_fmt
+             at /rustc/abc/library/core/src/panicking.rs:75:14
+   2: cargo_example::explore_me::explore_me
+             at ./src/explore_me.rs:14:21
+   3: cargo_example::my_fuzz_test::my_fuzz_test
+             at ./src/my_fuzz_test.rs:14:5
+   4: cargo_example::my_fuzz_test::my_fuzz_test::fuzz::{{closure}}
+             at ./src/my_fuzz_test.rs:5:1
+   5: core::ops::function::Fn::call
+             at /rustc/abc/library/core/src/ops/function.rs:79:5
+   6: cifuzz::harness::libfuzzer::run
+             at /cifuzz/crates/cifuzz/src/harness.rs:90:22
+   7: LLVMFuzzerRunDriver
+note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.
+==4242== ERROR: libFuzzer: deadly signal
+SUMMARY: libFuzzer: deadly signal
+MS: 2 ChangeBit-CMP-; base unit: adc83b19e793491b1c6ea0fd8b46cd9f32e592fc
+artifact_prefix='/p/.cifuzz-artifacts/my_fuzz_test/'; Test unit written to /p/.cifuzz-artifacts/my_fuzz_test/crash-e6c1a2d3
+";
+
+    #[test]
+    fn parse_panic() {
+        let report = parse_crash(&lines(CRASH_OUTPUT)).unwrap();
+        assert_eq!(
+            report.panic_message.as_deref(),
+            Some("branch 4 has been reached")
+        );
+        assert_eq!(report.details(), "branch 4 has been reached");
+        assert_eq!(
+            report.panic_location.as_deref(),
+            Some("examples/cargo/src/explore_me.rs:14:21")
+        );
+        assert_eq!(report.error.as_deref(), Some("deadly signal"));
+        assert_eq!(
+            report.input_file,
+            Some(PathBuf::from(
+                "/p/.cifuzz-artifacts/my_fuzz_test/crash-e6c1a2d3"
+            ))
+        );
+        assert_eq!(report.logs.len(), 23);
+        assert!(report.logs[0].starts_with("thread 'my_fuzz_test"));
+
+        assert_eq!(
+            report.stack_trace,
+            vec![
+                Frame {
+                    function: "cargo_example::explore_me::explore_me".to_string(),
+                    file: Some("./src/explore_me.rs".to_string()),
+                    line: 14,
+                    column: 21,
+                },
+                Frame {
+                    function: "cargo_example::my_fuzz_test::my_fuzz_test".to_string(),
+                    file: Some("./src/my_fuzz_test.rs".to_string()),
+                    line: 14,
+                    column: 5,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn harness_frames() {
+        let output = "\
+thread 'my_fuzz_test::fuzz' panicked at src/explore_me.rs:14:21:
+branch 4 has been reached
+stack backtrace:
+   0: __rustc::rust_begin_unwind
+             at /rustc/abc/library/std/src/panicking.rs:697:5
+   1: core::panicking::panic_fmt
+             at /rustc/abc/library/core/src/panicking.rs:75:14
+   2: cargo_example::explore_me::explore_me::h5d2b6140f0efc0bf
+             at ./src/explore_me.rs:14:21
+   3: cargo_example::my_fuzz_test::my_fuzz_test
+             at ./src/my_fuzz_test.rs:14:5
+   4: cargo_example::my_fuzz_test::test_one_input::{{closure}}
+             at ./src/my_fuzz_test.rs:5:1
+   5: cargo_example::my_fuzz_test::test_one_input
+             at /cifuzz/crates/cifuzz/src/harness.rs:63:13
+   6: cifuzz::harness::libfuzzer::run::{{closure}}
+             at /cifuzz/crates/cifuzz/src/harness.rs:204:62
+   7: __rust_try
+   8: fuzzer::Fuzzer::ExecuteCallback
+   9: _start
+==4242== ERROR: libFuzzer: deadly signal
+";
+        let report = parse_crash(&lines(output)).unwrap();
+        let frames: Vec<(&str, Option<&str>, u32)> = report
+            .stack_trace
+            .iter()
+            .map(|f| (f.function.as_str(), f.file.as_deref(), f.line))
+            .collect();
+        assert_eq!(
+            frames,
+            [
+                (
+                    "cargo_example::explore_me::explore_me",
+                    Some("./src/explore_me.rs"),
+                    14
+                ),
+                (
+                    "cargo_example::my_fuzz_test::my_fuzz_test",
+                    Some("./src/my_fuzz_test.rs"),
+                    14
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn wasm_frames() {
+        let output = "\
+thread 'main' (1) panicked at src/explore_me.rs:14:21:
+branch 4 has been reached
+stack backtrace:
+note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.
+   0: __rustc::rust_begin_unwind
+             at /rustc/abc/library/std/src/panicking.rs:689:5
+   1: core::panicking::panic_fmt
+             at /rustc/abc/library/core/src/panicking.rs:80:14
+   2: cargo_example::explore_me::explore_me
+             at /p/src/explore_me.rs:14:21
+   3: cifuzz::harness::run_input
+             at /cifuzz/crates/cifuzz/src/harness.rs:148:16
+   4: cifuzz_test_one_input
+             at /cifuzz/crates/cifuzz/src/harness.rs:701:9
+==4242== ERROR: libFuzzer: deadly signal
+";
+        let report = parse_crash(&lines(output)).unwrap();
+        let frames: Vec<&str> = report
+            .stack_trace
+            .iter()
+            .map(|f| f.function.as_str())
+            .collect();
+        assert_eq!(frames, ["cargo_example::explore_me::explore_me"]);
+        assert_eq!(
+            report.panic_message.as_deref(),
+            Some("branch 4 has been reached")
+        );
+    }
+
+    #[test]
+    fn parse_operations() {
+        let output = "\
+thread 'vec_fuzz_test::fuzz' panicked at src/lib.rs:20:31:
+index out of bounds: the len is 0 but the index is 0
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+Operations executed by the fuzz test:
+  0: Push { value: 3 }
+  1: Pop
+  2: Get { index: 0 }
+==4242== ERROR: libFuzzer: deadly signal
+";
+        let report = parse_crash(&lines(output)).unwrap();
+        assert_eq!(
+            report.operations,
+            ["Push { value: 3 }", "Pop", "Get { index: 0 }"]
+        );
+        assert_eq!(report.error.as_deref(), Some("deadly signal"));
+    }
+
+    #[test]
+    fn parse_captured_output() {
+        let output = "\
+INFO: Seed: 1234
+#2\tINITED cov: 61 ft: 70 corp: 1/1b exec/s: 0 rss: 28Mb
+Output of the fuzz test on the crashing input:
+a: 397652, b: 3082562284
+c: FUZZING
+
+thread 'my_fuzz_test::fuzz' panicked at src/explore_me.rs:14:21:
+branch 4 has been reached
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+==4242== ERROR: libFuzzer: deadly signal
+";
+        let report = parse_crash(&lines(output)).unwrap();
+        assert_eq!(report.output, ["a: 397652, b: 3082562284", "c: FUZZING"]);
+        assert!(report.logs[0].starts_with("thread 'my_fuzz_test::fuzz' panicked"));
+
+        let report = parse_crash(&lines(CRASH_OUTPUT)).unwrap();
+        assert!(report.output.is_empty());
+    }
+
+    const ASAN_OUTPUT: &str = "\
+INFO: A corpus is not provided, starting from an empty corpus
+=================================================================
+==11298==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x7b87b7402a02 at pc 0x5561ba0cbb96 bp 0x7b67b4dfd1b0 sp 0x7b67b4dfd1a8
+READ of size 1 at 0x7b87b7402a02 thread T1
+    #0 0x5561ba0cbb95 in asan_example::read_at /p/src/lib.rs:5:18
+    #1 0x5561ba0cafc6 in asan_example::fuzz::read_fuzz_test /p/src/lib.rs:17:9
+    #2 0x5561ba0cbd60 in asan_example::fuzz::read_fuzz_test::fuzz::{closure#0} /p/src/lib.rs:15:5
+    #3 0x5561ba0cb5be in <asan_example::fuzz::read_fuzz_test::fuzz::{closure#0} as core::ops::function::FnOnce<(&[u8],)>>::call_once /rustc/e50a/library/core/src/ops/function.rs:250:5
+    #4 0x5561ba1128cb in cifuzz::harness::libfuzzer::run::{closure#1} /cifuzz/crates/cifuzz/src/harness.rs:204:62
+    #5 0x5561ba13550d in fuzzer::Fuzzer::ExecuteCallback(unsigned char const*, unsigned long) /libfuzzer/FuzzerLoop.cpp:619:15
+
+0x7b87b7402a02 is located 270 bytes after 4-byte region [0x7b87b74028f0,0x7b87b74028f4)
+allocated by thread T1 here:
+    #0 0x5561ba08d5a4 in malloc (/p/target/deps/asan_example-123+0x2815a4)
+    #1 0x5561ba0cbb11 in alloc::alloc::alloc /rustc/e50a/library/alloc/src/alloc.rs:95:9
+
+SUMMARY: AddressSanitizer: heap-buffer-overflow /p/src/lib.rs:5:18 in asan_example::read_at
+==11298==ABORTING
+MS: 4 CopyPart-CopyPart-CopyPart-InsertByte-; base unit: 748c6614cdaf3f29b1d4dc74d77e5d9f6d229432
+artifact_prefix='/p/.cifuzz-artifacts/read_fuzz_test/'; Test unit written to /p/.cifuzz-artifacts/read_fuzz_test/crash-7c21e94d
+";
+
+    #[test]
+    fn parse_address_sanitizer_error() {
+        let report = parse_crash(&lines(ASAN_OUTPUT)).unwrap();
+        assert_eq!(report.panic_message, None);
+        assert_eq!(report.details(), "AddressSanitizer: heap-buffer-overflow");
+        assert_eq!(
+            report.input_file,
+            Some(PathBuf::from(
+                "/p/.cifuzz-artifacts/read_fuzz_test/crash-7c21e94d"
+            ))
+        );
+        assert!(report.logs[0].starts_with("==11298==ERROR"));
+        assert_eq!(
+            report.stack_trace,
+            vec![
+                Frame {
+                    function: "asan_example::read_at".to_string(),
+                    file: Some("/p/src/lib.rs".to_string()),
+                    line: 5,
+                    column: 18,
+                },
+                Frame {
+                    function: "asan_example::fuzz::read_fuzz_test".to_string(),
+                    file: Some("/p/src/lib.rs".to_string()),
+                    line: 17,
+                    column: 9,
+                },
+            ]
+        );
+    }
+
+    const MIRI_OUTPUT: &str = "\
+Running /p/.cifuzz-corpus/my_fuzz_test/ab12
+error: Undefined Behavior: in-bounds pointer arithmetic failed: attempting to offset pointer by 5 bytes, but got alloc49880 which is only 2 bytes from the end of the allocation
+ --> src/explore_me.rs:9:27
+  |
+9 |         let _ = unsafe { *v.as_ptr().add(5) };
+  |                           ^^^^^^^^^^^^^^^^^ Undefined Behavior occurred here
+  |
+  = help: this indicates a bug in the program: it performed an invalid operation, and caused Undefined Behavior
+help: alloc49880 was allocated here:
+ --> src/explore_me.rs:8:17
+  |
+8 |         let v = vec![1u8; 2];
+  |                 ^^^^^^^^^^^^
+  = note: this is on thread `my_fuzz_test::m`
+  = note: stack backtrace:
+          0: explore_me::explore_me
+              at src/explore_me.rs:9:27: 9:44
+          1: my_fuzz_test::my_fuzz_test
+              at src/my_fuzz_test.rs:13:5: 13:25
+          2: my_fuzz_test::my_fuzz_test::test_one_input::{closure#0}
+              at src/my_fuzz_test.rs:7:1: 7:13
+          3: cifuzz::__private::run_input::<{closure@src/my_fuzz_test.rs:7:1: 7:13}>
+              at /cifuzz/crates/cifuzz/src/harness.rs:168:9: 168:29
+          4: my_fuzz_test::my_fuzz_test::test_one_input
+              at /cifuzz/crates/cifuzz/src/harness.rs:138:13: 138:80
+          5: cifuzz::regression::replay::{closure#1}
+              at /cifuzz/crates/cifuzz/src/regression.rs:114:57: 114:78
+
+note: some details are omitted, run with `MIRIFLAGS=-Zmiri-backtrace=full` for a verbose backtrace
+
+error: aborting due to 1 previous error
+";
+
+    #[test]
+    fn parse_miri_undefined_behavior() {
+        let report = parse_crash(&lines(MIRI_OUTPUT)).unwrap();
+        assert_eq!(report.panic_message, None);
+        assert!(report
+            .details()
+            .starts_with("in-bounds pointer arithmetic failed: attempting to offset pointer"));
+        assert!(report.logs[0].starts_with("error: Undefined Behavior: "));
+        assert_eq!(
+            report.stack_trace,
+            vec![
+                Frame {
+                    function: "explore_me::explore_me".to_string(),
+                    file: Some("src/explore_me.rs".to_string()),
+                    line: 9,
+                    column: 27,
+                },
+                Frame {
+                    function: "my_fuzz_test::my_fuzz_test".to_string(),
+                    file: Some("src/my_fuzz_test.rs".to_string()),
+                    line: 13,
+                    column: 5,
+                },
+            ]
+        );
+    }
+
+    const TIMEOUT_OUTPUT: &str = "\
+Running: /p/.cifuzz-artifacts/hang_fuzz_test/timeout-6a0350ae
+thread 'fuzz::hang_fuzz_test::fuzz' timed out after 2 seconds
+stack backtrace:
+   0: cifuzz::watchdog::print_stack_trace
+             at /cifuzz/crates/cifuzz/src/watchdog.rs:95:9
+   1: <unknown>
+   2: core::hint::black_box
+             at /rustc/5980/library/core/src/hint.rs:490:0
+   3: hang_example::spin
+             at ./src/lib.rs:5:17
+   4: hang_example::fuzz::hang_fuzz_test
+             at ./src/lib.rs:17:9
+   5: hang_example::fuzz::hang_fuzz_test::fuzz::{{closure}}
+             at ./src/lib.rs:15:5
+   6: core::ops::function::FnOnce::call_once
+             at /rustc/5980/library/core/src/ops/function.rs:250:5
+   7: cifuzz::harness::libfuzzer::run::{{closure}}
+             at /cifuzz/crates/cifuzz/src/harness.rs:231:62
+  46: <unknown>
+
+ALARM: working on the last Unit for 2 seconds
+       and the timeout value is 2 (use -timeout=N to change)
+MS: 1 ChangeByte-; base unit: adc83b19e793491b1c6ea0fd8b46cd9f32e592fc
+artifact_prefix='/p/.cifuzz-artifacts/hang_fuzz_test/'; Test unit written to /p/.cifuzz-artifacts/hang_fuzz_test/timeout-6a0350ae
+==30677== ERROR: libFuzzer: timeout after 2 seconds
+SUMMARY: libFuzzer: timeout
+";
+
+    #[test]
+    fn parse_timeout() {
+        let report = parse_crash(&lines(TIMEOUT_OUTPUT)).unwrap();
+        assert_eq!(report.panic_message, None);
+        assert_eq!(report.details(), "timeout after 2 seconds");
+        assert!(report.logs[0].ends_with("timed out after 2 seconds"));
+        assert_eq!(
+            report.input_file,
+            Some(PathBuf::from(
+                "/p/.cifuzz-artifacts/hang_fuzz_test/timeout-6a0350ae"
+            ))
+        );
+        let functions: Vec<_> = report
+            .stack_trace
+            .iter()
+            .map(|f| f.function.as_str())
+            .collect();
+        assert_eq!(
+            functions,
+            ["hang_example::spin", "hang_example::fuzz::hang_fuzz_test"]
+        );
+        assert_eq!(report.stack_trace[0].file.as_deref(), Some("./src/lib.rs"));
+    }
+
+    const LSAN_OUTPUT: &str = "\
+#2345\tNEW    cov: 12 ft: 13 corp: 3/9b lim: 4 exec/s: 0 rss: 30Mb L: 3/4 MS: 1 CrossOver-
+
+=================================================================
+==25753==ERROR: LeakSanitizer: detected memory leaks
+
+Direct leak of 6 byte(s) in 1 object(s) allocated from:
+    #0 0x55d1f09ed18f in malloc /rustc/llvm/src/llvm-project/compiler-rt/lib/lsan/lsan_interceptors.cpp:74:3
+    #1 0x55d1f0afe5be in <alloc::raw_vec::RawVecInner>::try_allocate_in (/p/target/deps/leak_example-123+0x1a25be) (BuildId: 9e99)
+    #2 0x55d1f0abe40a in <alloc::vec::Vec<u8>>::with_capacity_in /rustlib/src/rust/library/alloc/src/vec/mod.rs:977:20
+    #3 0x55d1f0abe602 in <[u8]>::to_vec /rustlib/src/rust/library/alloc/src/slice.rs:376:14
+    #4 0x55d1f0a12a8e in leak_example::leak /p/src/lib.rs:3:31
+    #5 0x55d1f0a12b16 in leak_example::fuzz::leak_fuzz_test /p/src/lib.rs:13:9
+    #6 0x55d1f0a53fbd in cifuzz::harness::libfuzzer::run /cifuzz/crates/cifuzz/src/harness.rs:204:22
+
+SUMMARY: LeakSanitizer: 6 byte(s) leaked in 1 allocation(s).
+INFO: to ignore leaks on libFuzzer side use -detect_leaks=0.
+
+MS: 1 CrossOver-; base unit: 32af48d991b3e60f76e28f7248a0cfc910f09bba
+artifact_prefix='/p/.cifuzz-artifacts/leak_fuzz_test/'; Test unit written to /p/.cifuzz-artifacts/leak_fuzz_test/leak-72a3d8db
+";
+
+    #[test]
+    fn parse_leak() {
+        let report = parse_crash(&lines(LSAN_OUTPUT)).unwrap();
+        assert_eq!(report.details(), "LeakSanitizer: detected memory leaks");
+        assert_eq!(
+            report.input_file,
+            Some(PathBuf::from(
+                "/p/.cifuzz-artifacts/leak_fuzz_test/leak-72a3d8db"
+            ))
+        );
+        // The allocator and the standard library frames are dropped
+        let functions: Vec<_> = report
+            .stack_trace
+            .iter()
+            .map(|f| f.function.as_str())
+            .collect();
+        assert_eq!(
+            functions,
+            ["leak_example::leak", "leak_example::fuzz::leak_fuzz_test"]
+        );
+    }
+
+    #[test]
+    fn sanitizer_frames() {
+        let frame = parse_sanitizer_frame("    #12 0x55 in foo::bar /p/src/lib.rs:3").unwrap();
+        assert_eq!(
+            (frame.function.as_str(), frame.file.as_deref(), frame.line),
+            ("foo::bar", Some("/p/src/lib.rs"), 3)
+        );
+        let frame = parse_sanitizer_frame("    #0 0x55 in malloc (/p/foo+0x2815a4)").unwrap();
+        assert_eq!((frame.function.as_str(), frame.file), ("malloc", None));
+        let frame = parse_sanitizer_frame(
+            "    #1 race::spawn::{closure#0} /p/src/lib.rs:12:13 (race+0x1250)",
+        )
+        .unwrap();
+        assert_eq!(
+            (
+                frame.function.as_str(),
+                frame.file.as_deref(),
+                frame.line,
+                frame.column
+            ),
+            ("race::spawn::{closure#0}", Some("/p/src/lib.rs"), 12, 13)
+        );
+        let frame = parse_sanitizer_frame(
+            "    #2 0x55 in cargo_example::explore_me::h5d2b6140f0efc0bf /p/src/explore_me.rs:14:21",
+        )
+        .unwrap();
+        assert_eq!(frame.function, "cargo_example::explore_me");
+        assert_eq!(parse_sanitizer_frame("MS: 4 CopyPart-"), None);
+    }
+
+    #[test]
+    fn sanitizer_errors() {
+        assert_eq!(
+            parse_sanitizer_error("WARNING: ThreadSanitizer: data race (pid=5678)").as_deref(),
+            Some("ThreadSanitizer: data race")
+        );
+        assert_eq!(
+            parse_sanitizer_error("==12==WARNING: MemorySanitizer: use-of-uninitialized-value")
+                .as_deref(),
+            Some("MemorySanitizer: use-of-uninitialized-value")
+        );
+        assert_eq!(
+            parse_sanitizer_error(
+                "==12==ERROR: AddressSanitizer: SEGV on unknown address 0x000 (pc 0x1 bp 0x2)"
+            )
+            .as_deref(),
+            Some("AddressSanitizer: SEGV")
+        );
+        assert_eq!(
+            parse_sanitizer_error("==12== ERROR: libFuzzer: deadly signal"),
+            None
+        );
+        assert_eq!(parse_sanitizer_error("WARNING: something else: x"), None);
+    }
+
+    #[test]
+    fn parse_libfuzzer_error_without_panic() {
+        let output = lines(
+            "#1 INITED\n==1== ERROR: libFuzzer: out-of-memory (malloc(4294967296))\n\
+             SUMMARY: libFuzzer: out-of-memory\n",
+        );
+        let report = parse_crash(&output).unwrap();
+        assert_eq!(report.panic_message, None);
+        assert_eq!(report.details(), "out-of-memory (malloc(4294967296))");
+        assert!(report.stack_trace.is_empty());
+        assert_eq!(report.logs.len(), 2);
+    }
+
+    #[test]
+    fn parse_allocation_failure() {
+        let output = lines(
+            "memory allocation of 4294967296 bytes failed\n\
+             ==1== ERROR: libFuzzer: deadly signal\n\
+             artifact_prefix='/p/'; Test unit written to /p/crash-e6c1a2d3\n",
+        );
+        let report = parse_crash(&output).unwrap();
+        assert_eq!(
+            report.details(),
+            "out-of-memory (memory allocation of 4294967296 bytes failed)"
+        );
+        assert_eq!(report.logs.len(), 3);
+    }
+
+    #[test]
+    fn status_lines() {
+        assert_eq!(
+            parse_stats(
+                "#1234\tNEW    cov: 61 ft: 70 corp: 12/345b lim: 4 exec/s: 617 rss: 28Mb L: 3/3 MS: 1 CrossOver-"
+            ),
+            Some(Stats {
+                execs: 1234,
+                cov: 61,
+                features: 70,
+                corpus: 12,
+                execs_per_sec: 617,
+                rss_mb: 28,
+            })
+        );
+        assert_eq!(
+            parse_stats("#2\tINITED cov: 60 ft: 60 corp: 1/1b exec/s: 0 rss: 28Mb")
+                .map(|s| (s.execs, s.corpus)),
+            Some((2, 1))
+        );
+        assert_eq!(parse_stats("#0 0x5561ba0cbb95 in foo"), None);
+        assert_eq!(parse_stats("INFO: Seed: 1"), None);
+    }
+
+    #[test]
+    fn initial_corpus() {
+        let output =
+            lines("INFO: Seed: 1\n#2\tINITED cov: 60 ft: 60 corp: 1/1b exec/s: 0 rss: 28Mb\n");
+        assert!(corpus_loaded(&output));
+        let output = lines("INFO: Seed: 1\nthread 'fuzz' panicked at src/lib.rs:3:5:\n");
+        assert!(!corpus_loaded(&output));
+    }
+
+    #[test]
+    fn honggfuzz_summary() {
+        let line = "[2024-05-02T10:00:00+0000][I][4242] main():471 Summary iterations:5000 \
+                    time:10 speed:500 crashes_count:1 timeout_count:0 new_units_added:12 \
+                    slowest_unit_ms:3 guard_nb:200 branch_coverage_percent:30 peak_rss_mb:25";
+        assert_eq!(
+            parse_honggfuzz_summary(line),
+            Some(Stats {
+                execs: 5000,
+                cov: 60,
+                features: 60,
+                corpus: 12,
+                execs_per_sec: 500,
+                rss_mb: 25,
+            })
+        );
+        assert_eq!(
+            parse_honggfuzz_summary("Sz:12 Tm:120us (i/b/h/e/p/c)"),
+            None
+        );
+    }
+
+    #[test]
+    fn libfuzzer_seed() {
+        assert_eq!(
+            seed(&lines(
+                "INFO: Running with entropic power schedule\nINFO: Seed: 3639972260\n"
+            )),
+            Some(3639972260)
+        );
+        assert_eq!(seed(&lines("Done 1000 runs in 10 second(s)\n")), None);
+    }
+
+    #[test]
+    fn focus_function_inputs() {
+        let output = lines(
+            "INFO: Focus function is set to 'cargo_example::explore_me::explore_me'\n\
+             #2\tINITED cov: 179 ft: 179 corp: 1/1b focus: 1 exec/s: 0 rss: 35Mb\n\
+             INFO: 1/1 inputs touch the focus function\n\
+             #21\tNEW    cov: 179 ft: 189 corp: 4/10b focus: 4 lim: 4 exec/s: 0 rss: 36Mb\n",
+        );
+        assert_eq!(focus_inputs(&output), Some(4));
+        let output = lines("INFO: 0/3 inputs touch the focus function\n");
+        assert_eq!(focus_inputs(&output), Some(0));
+        let output = lines("#2\tINITED cov: 60 ft: 60 corp: 1/1b exec/s: 0 rss: 28Mb\nfocus: 3\n");
+        assert_eq!(focus_inputs(&output), None);
+    }
+
+    #[test]
+    fn parse_without_crash() {
+        let output = lines("INFO: Seed: 1\nDone 1000 runs in 10 second(s)\n");
+        assert_eq!(parse_crash(&output), None);
+    }
+
+    #[test]
+    fn panic_lines() {
+        assert_eq!(
+            parse_panic_line("thread 'main' panicked at src/main.rs:1:5:"),
+            Some("src/main.rs:1:5")
+        );
+        assert_eq!(
+            parse_panic_line("thread 'a::fuzz' (12) panicked at src/a.rs:2:3:"),
+            Some("src/a.rs:2:3")
+        );
+        assert_eq!(
+            parse_panic_line("thread 'main' has overflowed its stack"),
+            None
+        );
+    }
+}
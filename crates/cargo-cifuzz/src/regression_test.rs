@@ -0,0 +1,215 @@
+//! Regression tests generated for findings.
+//!
+//! The crashing inputs of the findings are replayed by the regression
+//! test of the fuzz test as long as they are in `.cifuzz/findings`. To
+//! keep a bug from coming back after it's fixed, every crash also gets a
+//! unit test which embeds its input, in
+//! `cifuzz_regressions/<fuzz_test>.rs` next to the source file defining
+//! the fuzz test. The file is a module to include next to the fuzz test,
+//! so that its tests can call the `test_one_input` function of the
+//! harness generated by `#[fuzz_test]`, which is private to the module.
+//!
+//! Timeouts, leaks and out-of-memory errors don't make a unit test fail,
+//! so only crashes get a test.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::finding::{ErrorType, Finding};
+
+/// The directory next to the source file of a fuzz test which contains
+/// its generated regression tests.
+pub const REGRESSIONS_DIR: &str = "cifuzz_regressions";
+
+/// The width after which the embedded inputs are wrapped.
+const LINE_WIDTH: usize = 80;
+
+/// The maximum width of a line of rustfmt.
+const MAX_WIDTH: usize = 100;
+
+/// Adds a test for the finding to the regression tests of the fuzz test
+/// defined in `source`, unless it already has one. Returns the file of
+/// the regression tests and whether it was created, or `None` if no test
+/// was added.
+pub fn add(finding: &Finding, source: &Path) -> Result<Option<(PathBuf, bool)>> {
+    if !matches!(
+        finding.error_type,
+        ErrorType::Crash | ErrorType::IntegerOverflow
+    ) {
+        return Ok(None);
+    }
+    let fuzz_test = &finding.metadata.fuzz_test;
+    let dir = source.with_file_name(REGRESSIONS_DIR);
+    let file = dir.join(format!("{fuzz_test}.rs"));
+    let test_name = test_name(&finding.name);
+
+    let created = !file.exists();
+    let mut content = if created {
+        header(fuzz_test)
+    } else {
+        std::fs::read_to_string(&file)
+            .with_context(|| format!("failed to read {}", file.display()))?
+    };
+    if content.contains(&format!("fn {test_name}()")) {
+        return Ok(None);
+    }
+    content.push('\n');
+    content.push_str(&test(finding, &test_name));
+
+    std::fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    std::fs::write(&file, content)
+        .with_context(|| format!("failed to write {}", file.display()))?;
+    Ok(Some((file, created)))
+}
+
+/// The module declaration which includes the regression tests of the
+/// fuzz test, to be added next to it.
+pub fn module_declaration(fuzz_test: &str) -> String {
+    format!(
+        "#[cfg(test)]\n#[path = \"{REGRESSIONS_DIR}/{fuzz_test}.rs\"]\nmod {fuzz_test}_regressions;"
+    )
+}
+
+fn header(fuzz_test: &str) -> String {
+    let declaration: String = module_declaration(fuzz_test)
+        .lines()
+        .map(|line| format!("//!     {line}\n"))
+        .collect();
+    format!(
+        "//! Regression tests for the findings of the fuzz test {fuzz_test},\n\
+         //! generated by `cargo cifuzz run`. Every test fails until the bug\n\
+         //! it was generated for is fixed. Include this file next to the\n\
+         //! fuzz test with\n\
+         //!\n\
+         {declaration}"
+    )
+}
+
+fn test(finding: &Finding, test_name: &str) -> String {
+    let function = format!("super::{}::test_one_input", finding.metadata.fuzz_test);
+    let literal = byte_string_literal(&finding.input_data, 8);
+    // Like rustfmt would format the call
+    let call = if !literal.contains('\n') && function.len() + literal.len() + 7 <= MAX_WIDTH {
+        format!("    {function}({literal});\n")
+    } else {
+        format!("    {function}(\n        {literal},\n    );\n")
+    };
+    format!(
+        "/// {}: {}\n\
+         /// (.cifuzz/findings/{}/{})\n\
+         #[test]\n\
+         fn {test_name}() {{\n\
+         {call}\
+         }}\n",
+        finding.error_type.description(),
+        finding.details.lines().next().unwrap_or_default(),
+        finding.metadata.fuzz_test,
+        finding.name,
+    )
+}
+
+/// The name of the test of a finding, e.g. `finding_5ed1b771cc61`.
+fn test_name(finding: &str) -> String {
+    let name: String = finding
+        .chars()
+        .take(12)
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("finding_{name}")
+}
+
+/// Formats the data as a byte string literal, which is wrapped with
+/// line continuations if it doesn't fit on a line indented by `indent`.
+fn byte_string_literal(data: &[u8], indent: usize) -> String {
+    let mut lines = vec![String::new()];
+    for &byte in data {
+        let line = lines.last_mut().unwrap();
+        let escaped = match byte {
+            b'"' => "\\\"".to_string(),
+            b'\\' => "\\\\".to_string(),
+            // Line continuations skip the whitespace at the start of the
+            // next line
+            b' ' if line.is_empty() => "\\x20".to_string(),
+            b' '..=b'~' => (byte as char).to_string(),
+            b'\n' => "\\n".to_string(),
+            b'\t' => "\\t".to_string(),
+            _ => format!("\\x{byte:02x}"),
+        };
+        line.push_str(&escaped);
+        if line.len() >= LINE_WIDTH - indent - 2 {
+            lines.push(String::new());
+        }
+    }
+    if lines.len() > 1 && lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+    let separator = format!("\\\n{}", " ".repeat(indent));
+    format!("b\"{}\"", lines.join(&separator))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::Sanitizer;
+    use crate::finding::test_finding;
+
+    fn finding(name: &str, error_type: ErrorType) -> Finding {
+        let mut finding = Finding {
+            error_type,
+            input_data: b"FUZZING\xff".to_vec(),
+            ..test_finding(name)
+        };
+        finding.metadata.sanitizer = Some(Sanitizer::Address);
+        finding
+    }
+
+    #[test]
+    fn add_tests() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("src").join("my_fuzz_test.rs");
+        let file = dir.path().join("src/cifuzz_regressions/my_fuzz_test.rs");
+
+        let first = finding("5ed1b771cc615694e9475f93cb25a4ed52d773d7", ErrorType::Crash);
+        assert_eq!(add(&first, &source).unwrap(), Some((file.clone(), true)));
+        assert_eq!(add(&first, &source).unwrap(), None);
+        let second = finding("0123abcd", ErrorType::Crash);
+        assert_eq!(add(&second, &source).unwrap(), Some((file.clone(), false)));
+        let timeout = finding("4567", ErrorType::Timeout);
+        assert_eq!(add(&timeout, &source).unwrap(), None);
+
+        let content = std::fs::read_to_string(&file).unwrap();
+        assert!(content.contains("//!     #[path = \"cifuzz_regressions/my_fuzz_test.rs\"]\n"));
+        assert!(content.ends_with(
+            "/// crash: branch 4 has been reached\n\
+             /// (.cifuzz/findings/my_fuzz_test/0123abcd)\n\
+             #[test]\n\
+             fn finding_0123abcd() {\n    \
+                 super::my_fuzz_test::test_one_input(b\"FUZZING\\xff\");\n\
+             }\n"
+        ));
+        assert_eq!(content.matches("fn finding_5ed1b771cc61()").count(), 1);
+
+        let mut long = finding("89ab", ErrorType::Crash);
+        long.input_data = vec![b'A'; 100];
+        add(&long, &source).unwrap();
+        let content = std::fs::read_to_string(&file).unwrap();
+        assert!(content.contains("    super::my_fuzz_test::test_one_input(\n        b\"AAAA"));
+        assert!(content.lines().all(|line| line.len() <= MAX_WIDTH));
+    }
+
+    #[test]
+    fn byte_strings() {
+        assert_eq!(byte_string_literal(b"", 8), "b\"\"");
+        assert_eq!(
+            byte_string_literal(b"a \"b\"\\\n\0", 8),
+            "b\"a \\\"b\\\"\\\\\\n\\x00\""
+        );
+
+        let literal = byte_string_literal(&[b' '; 100], 4);
+        let lines: Vec<&str> = literal.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].len() <= LINE_WIDTH);
+        assert!(lines[1].starts_with("    \\x20"));
+    }
+}
@@ -0,0 +1,328 @@
+//! Runs a fuzz test harness under AFL++.
+//!
+//! The harness is built with the `cifuzz_afl` cfg, so that the runtime
+//! executes the fuzz test in AFL++'s persistent mode and reads the
+//! inputs from stdin. afl-fuzz starts the libtest executable with the
+//! arguments which select the fuzz test. It can't use the corpus
+//! directories of libFuzzer directly, so the corpus is exported to its
+//! input directory before the run and its queue is imported into the
+//! generated corpus afterwards (see [`crate::corpus`]).
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::corpus;
+use crate::events;
+use crate::log;
+
+use super::{FuzzingResult, RunResult, DEFAULT_SEED};
+
+#[derive(Debug)]
+pub struct RunnerOptions {
+    /// The test executable containing the fuzz test
+    pub executable: PathBuf,
+    /// The full path of the libtest test which runs the fuzz test
+    pub test_name: String,
+    /// The directory in which the fuzz test is executed
+    pub working_dir: PathBuf,
+    /// The directory into which the inputs generated by AFL++ are
+    /// imported
+    pub generated_corpus_dir: PathBuf,
+    /// Directories with inputs AFL++ starts from
+    pub seed_corpus_dirs: Vec<PathBuf>,
+    /// The dictionary file passed to AFL++
+    pub dictionary: Option<PathBuf>,
+    /// The directory containing the input and output directories of
+    /// afl-fuzz, which are overwritten by every run
+    pub work_dir: PathBuf,
+    /// Maximum time to run the fuzz test, runs indefinitely if unset
+    pub timeout: Option<Duration>,
+    /// Maximum time to run the fuzz test on a single input, after which
+    /// the input is reported as a hang. Defaults to the timeout AFL++
+    /// derives from the execution time of the seeds.
+    pub input_timeout: Option<Duration>,
+    /// Maximum memory of the fuzz test in MB. Defaults to no limit.
+    pub rss_limit_mb: Option<u64>,
+    /// Whether afl-fuzz continues after the first crash
+    pub keep_going: bool,
+    /// Additional arguments for afl-fuzz
+    pub engine_args: Vec<String>,
+}
+
+pub struct Runner {
+    opts: RunnerOptions,
+}
+
+impl Runner {
+    pub fn new(opts: RunnerOptions) -> Self {
+        Runner { opts }
+    }
+
+    fn input_dir(&self) -> PathBuf {
+        self.opts.work_dir.join("in")
+    }
+
+    fn output_dir(&self) -> PathBuf {
+        self.opts.work_dir.join("out")
+    }
+
+    /// The command-line arguments for afl-fuzz.
+    pub fn afl_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "-i".to_string(),
+            self.input_dir().display().to_string(),
+            "-o".to_string(),
+            self.output_dir().display().to_string(),
+        ];
+        if let Some(timeout) = self.opts.timeout {
+            args.push("-V".to_string());
+            args.push(timeout.as_secs().to_string());
+        }
+        if let Some(input_timeout) = self.opts.input_timeout {
+            args.push("-t".to_string());
+            args.push(input_timeout.as_millis().max(1).to_string());
+        }
+        // AFL++ limits the virtual memory instead of the RSS
+        match self.opts.rss_limit_mb {
+            Some(0) | None => {}
+            Some(rss_limit_mb) => {
+                args.push("-m".to_string());
+                args.push(rss_limit_mb.to_string());
+            }
+        }
+        if let Some(dictionary) = &self.opts.dictionary {
+            args.push("-x".to_string());
+            args.push(dictionary.display().to_string());
+        }
+
+        args.extend(self.opts.engine_args.iter().cloned());
+
+        // The arguments of the fuzz test, which reads the inputs from
+        // stdin. libtest must not capture the output or spawn the fuzz
+        // test in a thread pool.
+        args.push("--".to_string());
+        args.push(self.opts.executable.display().to_string());
+        args.extend(
+            [
+                "--exact",
+                &self.opts.test_name,
+                "--nocapture",
+                "--test-threads",
+                "1",
+            ]
+            .map(String::from),
+        );
+        args
+    }
+
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new(afl_fuzz());
+        cmd.args(self.afl_args())
+            .current_dir(&self.opts.working_dir)
+            // Print the progress as lines instead of the interactive
+            // status screen, and don't refuse to run on systems whose
+            // CPU frequency scaling isn't tuned for fuzzing
+            .env("AFL_SKIP_CPUFREQ", "1")
+            .env("AFL_NO_UI", "1");
+        if !self.opts.keep_going {
+            cmd.env("AFL_BENCH_UNTIL_CRASH", "1");
+        }
+        cmd
+    }
+
+    /// Runs afl-fuzz on the corpus and imports the inputs it generated
+    /// into the generated corpus.
+    pub fn run(&self) -> Result<FuzzingResult> {
+        let input_dir = self.input_dir();
+        let output_dir = self.output_dir();
+        for dir in [&input_dir, &output_dir] {
+            if dir.exists() {
+                std::fs::remove_dir_all(dir)
+                    .with_context(|| format!("failed to remove {}", dir.display()))?;
+            }
+        }
+        let mut corpus_dirs = vec![self.opts.generated_corpus_dir.clone()];
+        corpus_dirs.extend(self.opts.seed_corpus_dirs.iter().cloned());
+        let inputs = corpus::list_inputs(&corpus_dirs)?;
+        if corpus::export(&inputs, &input_dir)? == 0 {
+            std::fs::write(input_dir.join("default"), DEFAULT_SEED)
+                .context("failed to write the default seed")?;
+        }
+
+        let mut cmd = self.command();
+        if events::uses_stdout() {
+            // afl-fuzz prints its status to stdout
+            cmd.stdout(std::io::stderr());
+        }
+        log::debug!("Command: {:?}", cmd);
+        let status = cmd
+            .status()
+            .context("failed to execute afl-fuzz, is AFL++ installed?")?;
+
+        let mut crashes = Vec::new();
+        let mut hangs = Vec::new();
+        if output_dir.is_dir() {
+            for queue in corpus::afl_queue_dirs(&output_dir)? {
+                let imported = corpus::import(
+                    &corpus::afl_queue_inputs(&queue)?,
+                    &self.opts.generated_corpus_dir,
+                )?;
+                log::debug!("Imported {} inputs from {}", imported, queue.display());
+                let instance_dir = queue.parent().unwrap_or(&output_dir);
+                crashes.extend(artifacts(&instance_dir.join("crashes"))?);
+                hangs.extend(artifacts(&instance_dir.join("hangs"))?);
+            }
+        }
+        Ok(FuzzingResult {
+            status,
+            crashes,
+            hangs,
+        })
+    }
+
+    /// Executes the fuzz test with a single input instead of fuzzing,
+    /// e.g. to get the panic message and the stack trace of a crash. The
+    /// output is not forwarded.
+    pub fn run_input(&self, input: &Path) -> Result<RunResult> {
+        super::run_input_from_stdin(
+            &self.opts.executable,
+            &self.opts.test_name,
+            &self.opts.working_dir,
+            input,
+        )
+    }
+}
+
+/// Returns the path of afl-fuzz, which is in `$AFL_PATH` or on the
+/// `PATH`.
+fn afl_fuzz() -> PathBuf {
+    std::env::var_os("AFL_PATH")
+        .map(|dir| PathBuf::from(dir).join("afl-fuzz"))
+        .filter(|path| path.is_file())
+        .unwrap_or_else(|| PathBuf::from("afl-fuzz"))
+}
+
+/// Returns the inputs in a crashes or hangs directory of AFL++, which
+/// also contains a README.txt describing the run.
+fn artifacts(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    Ok(corpus::afl_queue_inputs(dir)?
+        .into_iter()
+        .filter(|path| !path.ends_with("README.txt"))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> RunnerOptions {
+        RunnerOptions {
+            executable: PathBuf::from("/build/deps/foo-123"),
+            test_name: "my_fuzz_test::fuzz".to_string(),
+            working_dir: PathBuf::from("/project"),
+            generated_corpus_dir: PathBuf::from("/project/.cifuzz-corpus/my_fuzz_test"),
+            seed_corpus_dirs: Vec::new(),
+            dictionary: None,
+            work_dir: PathBuf::from("/project/.cifuzz-build/afl/work/my_fuzz_test"),
+            timeout: None,
+            input_timeout: None,
+            rss_limit_mb: None,
+            keep_going: false,
+            engine_args: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn default_args() {
+        let runner = Runner::new(options());
+        assert_eq!(
+            runner.afl_args(),
+            [
+                "-i",
+                "/project/.cifuzz-build/afl/work/my_fuzz_test/in",
+                "-o",
+                "/project/.cifuzz-build/afl/work/my_fuzz_test/out",
+                "--",
+                "/build/deps/foo-123",
+                "--exact",
+                "my_fuzz_test::fuzz",
+                "--nocapture",
+                "--test-threads",
+                "1",
+            ]
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let runner = Runner::new(RunnerOptions {
+            timeout: Some(Duration::from_secs(90)),
+            input_timeout: Some(Duration::from_millis(2500)),
+            rss_limit_mb: Some(512),
+            dictionary: Some(PathBuf::from("/project/my_fuzz_test.dict")),
+            ..options()
+        });
+        assert_eq!(
+            runner.afl_args()[4..12],
+            [
+                "-V",
+                "90",
+                "-t",
+                "2500",
+                "-m",
+                "512",
+                "-x",
+                "/project/my_fuzz_test.dict"
+            ]
+        );
+
+        // 0 disables the limit, like it does for libFuzzer
+        let runner = Runner::new(RunnerOptions {
+            rss_limit_mb: Some(0),
+            ..options()
+        });
+        assert_eq!(runner.afl_args()[4], "--");
+    }
+
+    #[test]
+    fn engine_args() {
+        let runner = Runner::new(RunnerOptions {
+            engine_args: vec!["-p".to_string(), "explore".to_string()],
+            ..options()
+        });
+        assert_eq!(runner.afl_args()[4..7], ["-p", "explore", "--"]);
+    }
+
+    #[test]
+    fn stop_at_first_crash() {
+        let has_env = |runner: &Runner| {
+            runner
+                .command()
+                .get_envs()
+                .any(|(k, _)| k == "AFL_BENCH_UNTIL_CRASH")
+        };
+        assert!(has_env(&Runner::new(options())));
+        assert!(!has_env(&Runner::new(RunnerOptions {
+            keep_going: true,
+            ..options()
+        })));
+    }
+
+    #[test]
+    fn crashes_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("README.txt"), "").unwrap();
+        std::fs::write(dir.path().join("id:000000,sig:06,src:000001"), "").unwrap();
+        assert_eq!(
+            artifacts(dir.path()).unwrap(),
+            [dir.path().join("id:000000,sig:06,src:000001")]
+        );
+        assert!(artifacts(&dir.path().join("missing")).unwrap().is_empty());
+    }
+}
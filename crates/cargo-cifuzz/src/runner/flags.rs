@@ -0,0 +1,231 @@
+//! The flags of libFuzzer, to validate the arguments passed with
+//! `--engine-arg` and in the `engine-args` of the cifuzz.yaml.
+//!
+//! The arguments are passed to `LLVMFuzzerRunDriver`, which only prints
+//! a warning for flags it doesn't know and ignores them, so a typo would
+//! silently have no effect.
+
+use anyhow::{bail, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flag {
+    Int,
+    String,
+    /// A flag which can't be used, because cargo-cifuzz sets it itself
+    /// or it doesn't work in a libtest executable. The text says what to
+    /// do instead.
+    Unsupported(&'static str),
+}
+
+/// The flags of libFuzzer, as defined in its FuzzerFlags.def.
+const LIBFUZZER_FLAGS: &[(&str, Flag)] = &[
+    ("verbosity", Flag::Int),
+    ("seed", Flag::Int),
+    ("runs", Flag::Int),
+    ("max_len", Flag::Int),
+    ("len_control", Flag::Int),
+    ("seed_inputs", Flag::String),
+    ("keep_seed", Flag::Int),
+    ("cross_over", Flag::Int),
+    ("cross_over_uniform_dist", Flag::Int),
+    ("mutate_depth", Flag::Int),
+    ("reduce_depth", Flag::Int),
+    ("shuffle", Flag::Int),
+    ("prefer_small", Flag::Int),
+    ("timeout", Flag::Int),
+    ("error_exitcode", Flag::Int),
+    ("timeout_exitcode", Flag::Int),
+    ("max_total_time", Flag::Int),
+    ("help", Flag::Unsupported("run the fuzz test without it")),
+    (
+        "fork",
+        Flag::Unsupported("use --jobs to run multiple instances"),
+    ),
+    ("fork_corpus_groups", Flag::Int),
+    ("ignore_timeouts", Flag::Int),
+    ("ignore_ooms", Flag::Int),
+    ("ignore_crashes", Flag::Int),
+    (
+        "merge",
+        Flag::Unsupported("use `cargo cifuzz corpus prune`"),
+    ),
+    (
+        "set_cover_merge",
+        Flag::Unsupported("use `cargo cifuzz corpus prune`"),
+    ),
+    ("stop_file", Flag::String),
+    (
+        "merge_inner",
+        Flag::Unsupported("use `cargo cifuzz corpus prune`"),
+    ),
+    (
+        "merge_control_file",
+        Flag::Unsupported("use `cargo cifuzz corpus prune`"),
+    ),
+    (
+        "minimize_crash",
+        Flag::Unsupported("use `cargo cifuzz minimize`"),
+    ),
+    ("cleanse_crash", Flag::Unsupported("it isn't supported")),
+    (
+        "minimize_crash_internal_step",
+        Flag::Unsupported("use `cargo cifuzz minimize`"),
+    ),
+    ("mutation_graph_file", Flag::String),
+    ("use_counters", Flag::Int),
+    ("use_memmem", Flag::Int),
+    ("use_value_profile", Flag::Int),
+    ("use_cmp", Flag::Int),
+    ("shrink", Flag::Int),
+    ("reduce_inputs", Flag::Int),
+    (
+        "jobs",
+        Flag::Unsupported("use --jobs to run multiple instances"),
+    ),
+    (
+        "workers",
+        Flag::Unsupported("use --jobs to run multiple instances"),
+    ),
+    ("reload", Flag::Int),
+    ("report_slow_units", Flag::Int),
+    ("only_ascii", Flag::Int),
+    ("dict", Flag::Unsupported("use --dict")),
+    (
+        "artifact_prefix",
+        Flag::Unsupported("cargo-cifuzz stores the artifacts itself"),
+    ),
+    (
+        "exact_artifact_path",
+        Flag::Unsupported("cargo-cifuzz stores the artifacts itself"),
+    ),
+    ("print_pcs", Flag::Int),
+    ("print_funcs", Flag::Int),
+    ("print_final_stats", Flag::Int),
+    ("print_corpus_stats", Flag::Int),
+    ("print_coverage", Flag::Int),
+    ("print_full_coverage", Flag::Int),
+    ("dump_coverage", Flag::Int),
+    ("handle_segv", Flag::Int),
+    ("handle_bus", Flag::Int),
+    ("handle_abrt", Flag::Int),
+    ("handle_ill", Flag::Int),
+    ("handle_fpe", Flag::Int),
+    ("handle_int", Flag::Int),
+    ("handle_term", Flag::Int),
+    ("handle_xfsz", Flag::Int),
+    ("handle_usr1", Flag::Int),
+    (
+        "handle_usr2",
+        Flag::Unsupported("the watchdog of the runtime handles SIGUSR2"),
+    ),
+    ("handle_winexcept", Flag::Int),
+    ("close_fd_mask", Flag::Int),
+    ("detect_leaks", Flag::Unsupported("use --detect-leaks")),
+    ("purge_allocator_interval", Flag::Int),
+    ("trace_malloc", Flag::Int),
+    ("rss_limit_mb", Flag::Int),
+    ("malloc_limit_mb", Flag::Int),
+    ("exit_on_src_pos", Flag::String),
+    ("exit_on_item", Flag::String),
+    ("ignore_remaining_args", Flag::Int),
+    ("focus_function", Flag::String),
+    ("entropic", Flag::Int),
+    ("entropic_feature_frequency_threshold", Flag::Int),
+    ("entropic_number_of_rarest_features", Flag::Int),
+    ("entropic_scale_per_exec_time", Flag::Int),
+    ("analyze_dict", Flag::Int),
+    ("use_clang_coverage", Flag::Int),
+    ("data_flow_trace", Flag::String),
+    ("collect_data_flow", Flag::String),
+    ("create_missing_dirs", Flag::Int),
+];
+
+/// Checks that the arguments are libFuzzer flags of the form
+/// `-name=value` which can be passed to the fuzz test.
+pub fn validate_libfuzzer_args(args: &[String]) -> Result<()> {
+    for arg in args {
+        let Some((name, value)) = arg.strip_prefix('-').and_then(|a| a.split_once('=')) else {
+            bail!("Invalid libFuzzer argument {arg:?}, flags have the form -name=value");
+        };
+        let Some(&(_, flag)) = LIBFUZZER_FLAGS.iter().find(|(n, _)| *n == name) else {
+            match closest_flag(name) {
+                Some(suggestion) => {
+                    bail!("Unknown libFuzzer flag -{name}, did you mean -{suggestion}?")
+                }
+                None => bail!("Unknown libFuzzer flag -{name}"),
+            }
+        };
+        match flag {
+            Flag::Int if value.parse::<i64>().is_err() => {
+                bail!("Invalid value {value:?} for the libFuzzer flag -{name}, expected an integer")
+            }
+            Flag::Unsupported(instead) => {
+                bail!("The libFuzzer flag -{name} can't be passed to the fuzz test, {instead}")
+            }
+            Flag::Int | Flag::String => {}
+        }
+    }
+    Ok(())
+}
+
+/// Returns the known flag which is most similar to the unknown one, if
+/// any is similar enough to be a typo.
+fn closest_flag(name: &str) -> Option<&'static str> {
+    LIBFUZZER_FLAGS
+        .iter()
+        .map(|(flag, _)| (edit_distance(name, flag), *flag))
+        .filter(|(distance, _)| *distance <= name.len() / 3)
+        .min()
+        .map(|(_, flag)| flag)
+}
+
+/// The Levenshtein distance between the strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(args: &[&str]) -> Result<()> {
+        validate_libfuzzer_args(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn valid_args() {
+        validate(&["-max_len=4096", "-seed=1", "-focus_function=parse"]).unwrap();
+    }
+
+    #[test]
+    fn invalid_args() {
+        let error = |args: &[&str]| validate(args).unwrap_err().to_string();
+        assert_eq!(
+            error(&["-max_lenn=10"]),
+            "Unknown libFuzzer flag -max_lenn, did you mean -max_len?"
+        );
+        assert_eq!(error(&["-foo=1"]), "Unknown libFuzzer flag -foo");
+        assert!(error(&["max_len=10"]).contains("flags have the form -name=value"));
+        assert!(error(&["-max_len"]).contains("flags have the form -name=value"));
+        assert!(error(&["-runs=many"]).contains("expected an integer"));
+        assert!(error(&["-jobs=4"]).ends_with("use --jobs to run multiple instances"));
+    }
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("seed", "seed"), 0);
+        assert_eq!(edit_distance("sed", "seed"), 1);
+        assert_eq!(edit_distance("max_total", "max_len"), 5);
+    }
+}
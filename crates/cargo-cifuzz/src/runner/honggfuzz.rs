@@ -0,0 +1,336 @@
+//! Runs a fuzz test harness under honggfuzz.
+//!
+//! The harness is built with the `cifuzz_honggfuzz` cfg, so that the
+//! runtime fetches the inputs with `HF_ITER` of honggfuzz's persistent
+//! mode. honggfuzz starts the libtest executable with the arguments
+//! which select the fuzz test. It reads the inputs from a single
+//! directory, so the corpus is copied to its input directory before the
+//! run and the inputs it generated are imported into the generated
+//! corpus afterwards.
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::corpus;
+use crate::events;
+use crate::log;
+use crate::metrics;
+use crate::parser;
+
+use super::libfuzzer::format_progress;
+use super::{FuzzingResult, RunResult, DEFAULT_SEED};
+
+// Must be kept in sync with the runtime in crates/cifuzz/src/harness.rs
+pub const HONGGFUZZ_ENV: &str = "CIFUZZ_HONGGFUZZ";
+
+/// The input timeout of libFuzzer, which is used instead of the one
+/// second of honggfuzz if no input timeout is set.
+const DEFAULT_INPUT_TIMEOUT: Duration = Duration::from_secs(20 * 60);
+
+#[derive(Debug)]
+pub struct RunnerOptions {
+    /// The test executable containing the fuzz test
+    pub executable: PathBuf,
+    /// The full path of the libtest test which runs the fuzz test
+    pub test_name: String,
+    /// The directory in which the fuzz test is executed
+    pub working_dir: PathBuf,
+    /// The directory into which the inputs generated by honggfuzz are
+    /// imported
+    pub generated_corpus_dir: PathBuf,
+    /// Directories with inputs honggfuzz starts from
+    pub seed_corpus_dirs: Vec<PathBuf>,
+    /// The dictionary file passed to honggfuzz
+    pub dictionary: Option<PathBuf>,
+    /// The workspace of honggfuzz, containing its input, output and
+    /// crash directories, which are overwritten by every run
+    pub work_dir: PathBuf,
+    /// Maximum time to run the fuzz test, runs indefinitely if unset
+    pub timeout: Option<Duration>,
+    /// Maximum time to run the fuzz test on a single input. Defaults to
+    /// the 20 minutes of libFuzzer.
+    pub input_timeout: Option<Duration>,
+    /// Maximum RSS of the fuzz test in MB. Defaults to no limit.
+    pub rss_limit_mb: Option<u64>,
+    /// The number of fuzzing threads of honggfuzz, which share the
+    /// corpus
+    pub jobs: usize,
+    /// Whether honggfuzz continues after the first crash
+    pub keep_going: bool,
+    /// Additional arguments for honggfuzz
+    pub engine_args: Vec<String>,
+}
+
+pub struct Runner {
+    opts: RunnerOptions,
+}
+
+impl Runner {
+    pub fn new(opts: RunnerOptions) -> Self {
+        Runner { opts }
+    }
+
+    fn input_dir(&self) -> PathBuf {
+        self.opts.work_dir.join("in")
+    }
+
+    fn output_dir(&self) -> PathBuf {
+        self.opts.work_dir.join("out")
+    }
+
+    fn crash_dir(&self) -> PathBuf {
+        self.opts.work_dir.join("crashes")
+    }
+
+    /// The command-line arguments for honggfuzz.
+    pub fn honggfuzz_args(&self) -> Vec<String> {
+        let input_timeout = self.opts.input_timeout.unwrap_or(DEFAULT_INPUT_TIMEOUT);
+        let mut args = vec![
+            "--input".to_string(),
+            self.input_dir().display().to_string(),
+            "--output".to_string(),
+            self.output_dir().display().to_string(),
+            "--crashdir".to_string(),
+            self.crash_dir().display().to_string(),
+            "--workspace".to_string(),
+            self.opts.work_dir.display().to_string(),
+            "--threads".to_string(),
+            self.opts.jobs.to_string(),
+            // honggfuzz only supports whole seconds
+            "--timeout".to_string(),
+            (input_timeout.as_secs_f64().ceil().max(1.0) as u64).to_string(),
+            "--persistent".to_string(),
+            // Log lines instead of the interactive status screen
+            "--verbose".to_string(),
+        ];
+        if let Some(timeout) = self.opts.timeout {
+            args.push("--run_time".to_string());
+            args.push(timeout.as_secs().to_string());
+        }
+        if let Some(rss_limit_mb) = self.opts.rss_limit_mb {
+            args.push("--rlimit_rss".to_string());
+            args.push(rss_limit_mb.to_string());
+        }
+        if let Some(dictionary) = &self.opts.dictionary {
+            args.push("--dict".to_string());
+            args.push(dictionary.display().to_string());
+        }
+        if !self.opts.keep_going {
+            args.push("--exit_upon_crash".to_string());
+        }
+
+        args.extend(self.opts.engine_args.iter().cloned());
+
+        // The arguments of the fuzz test. libtest must not capture the
+        // output or spawn the fuzz test in a thread pool.
+        args.push("--".to_string());
+        args.push(self.opts.executable.display().to_string());
+        args.extend(
+            [
+                "--exact",
+                &self.opts.test_name,
+                "--nocapture",
+                "--test-threads",
+                "1",
+            ]
+            .map(String::from),
+        );
+        args
+    }
+
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new(honggfuzz());
+        cmd.args(self.honggfuzz_args())
+            .env(HONGGFUZZ_ENV, "1")
+            .current_dir(&self.opts.working_dir);
+        cmd
+    }
+
+    /// Runs honggfuzz on the corpus and imports the inputs it generated
+    /// into the generated corpus. The output of honggfuzz is forwarded
+    /// and its summary is printed as the progress of libFuzzer.
+    pub fn run(&self) -> Result<FuzzingResult> {
+        for dir in [self.input_dir(), self.output_dir(), self.crash_dir()] {
+            if dir.exists() {
+                std::fs::remove_dir_all(&dir)
+                    .with_context(|| format!("failed to remove {}", dir.display()))?;
+            }
+        }
+        let mut corpus_dirs = vec![self.opts.generated_corpus_dir.clone()];
+        corpus_dirs.extend(self.opts.seed_corpus_dirs.iter().cloned());
+        let inputs = corpus::list_inputs(&corpus_dirs)?;
+        if corpus::import(&inputs, &self.input_dir())? == 0 {
+            std::fs::write(self.input_dir().join("default"), DEFAULT_SEED)
+                .context("failed to write the default seed")?;
+        }
+        std::fs::create_dir_all(self.output_dir())
+            .with_context(|| format!("failed to create {}", self.output_dir().display()))?;
+
+        let mut cmd = self.command();
+        cmd.stderr(Stdio::piped());
+        if events::uses_stdout() {
+            cmd.stdout(std::io::stderr());
+        }
+        log::debug!("Command: {:?}", cmd);
+        let mut child = cmd
+            .spawn()
+            .context("failed to execute honggfuzz, is it installed?")?;
+        let mut summary = None;
+        let mut stderr = BufReader::new(child.stderr.take().unwrap());
+        let mut line = Vec::new();
+        while stderr.read_until(b'\n', &mut line)? > 0 {
+            std::io::stderr().write_all(&line)?;
+            summary = parser::parse_honggfuzz_summary(&String::from_utf8_lossy(&line)).or(summary);
+            line.clear();
+        }
+        let status = child.wait().context("failed to wait for honggfuzz")?;
+        if let Some(stats) = summary {
+            log::info!("{}", format_progress(&stats, self.opts.jobs));
+            events::progress(&stats);
+            metrics::progress(&stats);
+        }
+
+        let imported = corpus::import(
+            &corpus::list_inputs(&[self.output_dir()])?,
+            &self.opts.generated_corpus_dir,
+        )?;
+        log::debug!("Imported {} inputs generated by honggfuzz", imported);
+        Ok(FuzzingResult {
+            status,
+            crashes: crashes(&self.crash_dir())?,
+            // Inputs on which the fuzz test times out are only counted
+            hangs: Vec::new(),
+        })
+    }
+
+    /// Executes the fuzz test with a single input instead of fuzzing,
+    /// e.g. to get the panic message and the stack trace of a crash. The
+    /// output is not forwarded.
+    pub fn run_input(&self, input: &Path) -> Result<RunResult> {
+        super::run_input_from_stdin(
+            &self.opts.executable,
+            &self.opts.test_name,
+            &self.opts.working_dir,
+            input,
+        )
+    }
+}
+
+/// Returns the path of honggfuzz, which is in `$HONGGFUZZ_PATH` or on
+/// the `PATH`.
+fn honggfuzz() -> PathBuf {
+    std::env::var_os("HONGGFUZZ_PATH")
+        .map(|dir| PathBuf::from(dir).join("honggfuzz"))
+        .filter(|path| path.is_file())
+        .unwrap_or_else(|| PathBuf::from("honggfuzz"))
+}
+
+/// Returns the crashing inputs in the crash directory of honggfuzz, which
+/// are named after the signal and the stack hash of the crash, e.g.
+/// `SIGABRT.PC.7ffff7a42428.STACK.18b1c5a6d3.CODE.-6.ADDR.0.INSTR.mov.fuzz`.
+fn crashes(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    Ok(corpus::list_inputs(&[dir.to_path_buf()])?
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "fuzz"))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> RunnerOptions {
+        RunnerOptions {
+            executable: PathBuf::from("/build/deps/foo-123"),
+            test_name: "my_fuzz_test::fuzz".to_string(),
+            working_dir: PathBuf::from("/project"),
+            generated_corpus_dir: PathBuf::from("/project/.cifuzz-corpus/my_fuzz_test"),
+            seed_corpus_dirs: Vec::new(),
+            dictionary: None,
+            work_dir: PathBuf::from("/w"),
+            timeout: None,
+            input_timeout: None,
+            rss_limit_mb: None,
+            jobs: 1,
+            keep_going: false,
+            engine_args: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn default_args() {
+        let runner = Runner::new(options());
+        assert_eq!(
+            runner.honggfuzz_args(),
+            [
+                "--input",
+                "/w/in",
+                "--output",
+                "/w/out",
+                "--crashdir",
+                "/w/crashes",
+                "--workspace",
+                "/w",
+                "--threads",
+                "1",
+                "--timeout",
+                "1200",
+                "--persistent",
+                "--verbose",
+                "--exit_upon_crash",
+                "--",
+                "/build/deps/foo-123",
+                "--exact",
+                "my_fuzz_test::fuzz",
+                "--nocapture",
+                "--test-threads",
+                "1",
+            ]
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let runner = Runner::new(RunnerOptions {
+            timeout: Some(Duration::from_secs(90)),
+            input_timeout: Some(Duration::from_millis(2500)),
+            rss_limit_mb: Some(512),
+            dictionary: Some(PathBuf::from("/project/my_fuzz_test.dict")),
+            jobs: 4,
+            keep_going: true,
+            ..options()
+        });
+        let args = runner.honggfuzz_args();
+        assert_eq!(args[9..12], ["4", "--timeout", "3"]);
+        assert_eq!(
+            args[14..20],
+            [
+                "--run_time",
+                "90",
+                "--rlimit_rss",
+                "512",
+                "--dict",
+                "/project/my_fuzz_test.dict"
+            ]
+        );
+        assert_eq!(args[20], "--");
+    }
+
+    #[test]
+    fn crash_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let crash = dir
+            .path()
+            .join("SIGABRT.PC.7ffff7a42428.STACK.18b1c5a6d3.fuzz");
+        std::fs::write(&crash, "").unwrap();
+        std::fs::write(dir.path().join("HONGGFUZZ.REPORT.TXT"), "").unwrap();
+        assert_eq!(crashes(dir.path()).unwrap(), [crash]);
+    }
+}
@@ -0,0 +1,960 @@
+//! Runs a fuzz test harness under libFuzzer.
+//!
+//! The harness is a libtest test, so the libFuzzer arguments can't be
+//! passed on the command line. Instead, they are passed via the
+//! `CIFUZZ_LIBFUZZER_ARGS` environment variable to the cifuzz runtime,
+//! which hands them to `LLVMFuzzerRunDriver`.
+
+use std::cell::RefCell;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
+use crate::build::Sanitizer;
+use crate::campaign;
+use crate::dashboard;
+use crate::events;
+use crate::log;
+use crate::metrics;
+use crate::parser::{self, Stats};
+use crate::symbolize;
+
+use super::RunResult;
+
+/// How often the combined progress of parallel jobs is printed.
+const JOBS_PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+/// How often the coverage is checked for a plateau while the fuzz test
+/// prints nothing, which libFuzzer does for long stretches without new
+/// coverage.
+const PLATEAU_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// Must be kept in sync with the runtime in crates/cifuzz/src/harness.rs
+pub const LIBFUZZER_ARGS_ENV: &str = "CIFUZZ_LIBFUZZER_ARGS";
+pub const FDP_CALLS_ENV: &str = "CIFUZZ_FDP_CALLS";
+pub const CAPTURE_OUTPUT_ENV: &str = "CIFUZZ_CAPTURE_OUTPUT";
+// Must be kept in sync with the runtime in crates/cifuzz/src/rng.rs
+pub const SEED_ENV: &str = "CIFUZZ_SEED";
+// Must be kept in sync with the runtime in crates/cifuzz/src/slow_inputs.rs
+pub const SLOW_INPUTS_DIR_ENV: &str = "CIFUZZ_SLOW_INPUTS_DIR";
+pub const SLOW_INPUTS_COUNT_ENV: &str = "CIFUZZ_SLOW_INPUTS_COUNT";
+
+#[derive(Debug)]
+pub struct RunnerOptions {
+    /// The test executable containing the fuzz test
+    pub executable: PathBuf,
+    /// The full path of the libtest test which runs the fuzz test
+    pub test_name: String,
+    /// The directory in which the fuzz test is executed
+    pub working_dir: PathBuf,
+    /// The directory in which libFuzzer stores the inputs it generates
+    pub generated_corpus_dir: PathBuf,
+    /// Directories with inputs libFuzzer starts from, which it doesn't
+    /// modify
+    pub seed_corpus_dirs: Vec<PathBuf>,
+    /// The dictionary file passed to libFuzzer
+    pub dictionary: Option<PathBuf>,
+    /// The directory in which libFuzzer stores crashing inputs
+    pub artifact_dir: PathBuf,
+    /// Maximum time to run the fuzz test, runs indefinitely if unset
+    pub timeout: Option<Duration>,
+    /// Maximum time to run the fuzz test on a single input, after which
+    /// the input is reported as a timeout. Defaults to libFuzzer's 20
+    /// minutes.
+    pub input_timeout: Option<Duration>,
+    /// Maximum RSS of the fuzz test in MB, after which the current input
+    /// is reported as out-of-memory. Defaults to libFuzzer's 2048 MB.
+    pub rss_limit_mb: Option<u64>,
+    /// The seed of libFuzzer's random number generator and of
+    /// `cifuzz::rng()`, which makes the run reproducible. Parallel jobs
+    /// use consecutive seeds for libFuzzer.
+    pub seed: Option<u64>,
+    /// Whether libFuzzer uses the values of comparisons as feedback
+    pub use_value_profile: bool,
+    /// The sanitizer the fuzz test was built with
+    pub sanitizer: Option<Sanitizer>,
+    /// Whether libFuzzer checks for leaks after executing an input,
+    /// which requires a sanitizer which detects leaks
+    pub detect_leaks: bool,
+    /// Additional flags for libFuzzer, which take precedence over the
+    /// ones set by cargo-cifuzz
+    pub engine_args: Vec<String>,
+    /// Whether the runtime silences the output of the fuzz test and only
+    /// prints the output of a crashing input
+    pub capture_output: bool,
+    /// The directory in which the runtime writes the slowest inputs
+    /// when the fuzz test exits. No inputs are timed if unset.
+    pub slow_inputs_dir: Option<PathBuf>,
+    /// The number of slowest inputs the runtime writes
+    pub slow_inputs: usize,
+    /// Stop the fuzz test when it covered no new edges for this long
+    pub exit_on_plateau: Option<Duration>,
+}
+
+pub struct Runner {
+    opts: RunnerOptions,
+    /// When the coverage last grew, kept across restarts
+    plateau: RefCell<Option<Plateau>>,
+}
+
+impl Runner {
+    pub fn new(opts: RunnerOptions) -> Self {
+        Runner {
+            opts,
+            plateau: RefCell::new(None),
+        }
+    }
+
+    /// Changes the maximum time to run the fuzz test, e.g. for the rest
+    /// of the run after a restart.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.opts.timeout = timeout;
+    }
+
+    /// The command-line arguments for libFuzzer.
+    pub fn libfuzzer_args(&self) -> Vec<String> {
+        self.libfuzzer_args_with_seed(self.opts.seed)
+    }
+
+    /// The command-line arguments for libFuzzer in parallel job `job`,
+    /// which must not use the same seed as the other jobs.
+    fn job_args(&self, job: usize) -> Vec<String> {
+        self.libfuzzer_args_with_seed(self.opts.seed.map(|seed| job_seed(seed, job)))
+    }
+
+    fn libfuzzer_args_with_seed(&self, seed: Option<u64>) -> Vec<String> {
+        let mut args = Vec::new();
+
+        // Tell libfuzzer to exit after the timeout
+        let timeout_seconds = self.opts.timeout.map_or(0, |t| t.as_secs());
+        args.push(format!("-max_total_time={timeout_seconds}"));
+
+        // Report inputs which take longer than the input timeout. The
+        // runtime's watchdog makes sure that libFuzzer notices it.
+        if let Some(input_timeout) = self.opts.input_timeout {
+            // libFuzzer only supports whole seconds
+            let seconds = input_timeout.as_secs_f64().ceil().max(1.0) as u64;
+            args.push(format!("-timeout={seconds}"));
+        }
+
+        if let Some(rss_limit_mb) = self.opts.rss_limit_mb {
+            args.push(format!("-rss_limit_mb={rss_limit_mb}"));
+        }
+
+        // Set the directory in which fuzzing artifacts (e.g. crashes)
+        // are stored. The trailing slash is required, because
+        // libFuzzer uses it as a prefix of the file names.
+        args.push(format!(
+            "-artifact_prefix={}/",
+            self.opts.artifact_dir.display()
+        ));
+
+        // Let libfuzzer use the distance between the operands of
+        // comparisons as feedback, which helps to solve comparisons
+        // like `b - a > 100000` but also grows the corpus
+        if self.opts.use_value_profile {
+            args.push("-use_value_profile=1".to_string());
+        }
+
+        // Let libfuzzer check for leaks after every input which allocates
+        // more memory than it frees, so that leaks are reported as
+        // findings with the input that caused them
+        if self.opts.sanitizer.is_some_and(Sanitizer::detects_leaks) {
+            args.push(format!(
+                "-detect_leaks={}",
+                u8::from(self.opts.detect_leaks)
+            ));
+        }
+
+        if let Some(dictionary) = &self.opts.dictionary {
+            args.push(format!("-dict={}", dictionary.display()));
+        }
+
+        // The seed fixes the mutations and the order in which the corpus
+        // is loaded
+        if let Some(seed) = seed {
+            args.push(format!("-seed={seed}"));
+        }
+
+        // libFuzzer uses the last value of a flag, so the flags of the
+        // user override the defaults above
+        args.extend(self.opts.engine_args.iter().cloned());
+
+        // Tell libfuzzer which corpus directories it should use. It
+        // stores new inputs in the first one.
+        args.push(self.opts.generated_corpus_dir.display().to_string());
+        args.extend(
+            self.opts
+                .seed_corpus_dirs
+                .iter()
+                .map(|d| d.display().to_string()),
+        );
+
+        args
+    }
+
+    /// The flags of [`Self::libfuzzer_args`] which affect the execution
+    /// of a single input, e.g. the limits, which are recorded with a
+    /// finding to reproduce it.
+    pub fn input_args(&self) -> Vec<String> {
+        self.libfuzzer_args()
+            .into_iter()
+            .filter(|arg| {
+                let name = arg
+                    .strip_prefix('-')
+                    .and_then(|arg| arg.split_once('='))
+                    .map(|(name, _)| name);
+                // The corpus directories have no name
+                !matches!(
+                    name,
+                    None | Some("max_total_time" | "artifact_prefix" | "dict")
+                )
+            })
+            .collect()
+    }
+
+    pub fn command(&self) -> Command {
+        self.command_with_args(&self.libfuzzer_args())
+    }
+
+    fn command_with_args(&self, libfuzzer_args: &[String]) -> Command {
+        let mut cmd = Command::new(&self.opts.executable);
+        cmd.args(["--exact", &self.opts.test_name])
+            // libtest must not capture the output, because it's the
+            // output of libFuzzer, and it must not spawn the fuzz test
+            // in a thread pool
+            .args(["--nocapture", "--test-threads", "1"])
+            .env(LIBFUZZER_ARGS_ENV, libfuzzer_args.join("\n"))
+            .current_dir(&self.opts.working_dir);
+        // Panics must print a stack trace, so that it can be stored
+        // with the finding
+        if std::env::var_os("RUST_BACKTRACE").is_none() {
+            cmd.env("RUST_BACKTRACE", "1");
+        }
+        if self.opts.capture_output {
+            cmd.env(CAPTURE_OUTPUT_ENV, "1");
+        }
+        // All jobs use the same seed for the generator of the runtime, so
+        // that the numbers of an input don't depend on the job
+        if let Some(seed) = self.opts.seed {
+            cmd.env(SEED_ENV, seed.to_string());
+        }
+        if let Some(dir) = &self.opts.slow_inputs_dir {
+            cmd.env(SLOW_INPUTS_DIR_ENV, dir)
+                .env(SLOW_INPUTS_COUNT_ENV, self.opts.slow_inputs.to_string());
+        }
+        if let Some(sanitizer) = self.opts.sanitizer {
+            let env = sanitizer.options_env();
+            let mut options: Vec<String> = sanitizer
+                .options(self.opts.detect_leaks)
+                .iter()
+                .map(|option| option.to_string())
+                .collect();
+            // Without llvm-symbolizer, the frames of the stack traces
+            // of the sanitizers have no functions and locations
+            if let Some(symbolizer) = symbolize::llvm_symbolizer() {
+                options.push(format!("external_symbolizer_path={}", symbolizer.display()));
+            }
+            // Options set by the user take precedence, because the
+            // sanitizers use the last value of an option
+            if let Ok(user_options) = std::env::var(env) {
+                options.push(user_options);
+            }
+            if !options.is_empty() {
+                cmd.env(env, options.join(":"));
+            }
+        }
+        cmd
+    }
+
+    pub fn run(&self) -> Result<RunResult> {
+        std::fs::create_dir_all(&self.opts.generated_corpus_dir)
+            .context("failed to create the generated corpus directory")?;
+        std::fs::create_dir_all(&self.opts.artifact_dir)
+            .context("failed to create the artifact directory")?;
+
+        let mut cmd = self.command();
+        cmd.stderr(Stdio::piped());
+        if dashboard::is_active() {
+            // The output of the fuzz test would end up in the dashboard
+            cmd.stdout(Stdio::null());
+        } else if events::uses_stdout() {
+            cmd.stdout(std::io::stderr());
+        }
+        log::debug!("Command: {:?}", cmd);
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("failed to execute {}", self.opts.executable.display()))?;
+
+        // Forward the output to our stderr while collecting it. The
+        // lines are read in a thread, so that a plateau is noticed while
+        // the fuzz test prints nothing.
+        let (sender, receiver) = mpsc::channel();
+        let stderr = child.stderr.take().unwrap();
+        std::thread::spawn(move || forward_lines(0, stderr, sender));
+        let mut output = Vec::new();
+        let mut plateaued = false;
+        loop {
+            let line = match receiver.recv_timeout(self.receive_timeout()) {
+                Ok((_, Some(line))) => Some(line),
+                Ok((_, None)) | Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => None,
+            };
+            let mut stats = None;
+            if let Some(line) = line {
+                if dashboard::is_active() {
+                    dashboard::line(0, &line);
+                } else {
+                    eprintln!("{line}");
+                }
+                stats = parser::parse_stats(&line);
+                if let Some(stats) = &stats {
+                    events::progress(stats);
+                    metrics::progress(stats);
+                    campaign::progress(stats);
+                } else if let Some(seed) = parser::parse_seed(&line) {
+                    campaign::seed(seed);
+                }
+                output.push(line);
+            }
+            if self.plateaued(stats.as_ref()) {
+                let _ = child.kill();
+                plateaued = true;
+                break;
+            }
+        }
+
+        let status = child.wait().context("failed to wait for the fuzz test")?;
+        Ok(RunResult {
+            status,
+            output,
+            plateaued,
+        })
+    }
+
+    /// How long to wait for the next line of the output of the fuzz test
+    /// before checking for a plateau.
+    fn receive_timeout(&self) -> Duration {
+        match self.opts.exit_on_plateau {
+            Some(_) => PLATEAU_CHECK_INTERVAL,
+            None => Duration::MAX,
+        }
+    }
+
+    /// Records the progress of the fuzz test, if it printed any, and
+    /// returns whether its coverage didn't grow for the plateau window.
+    fn plateaued(&self, stats: Option<&Stats>) -> bool {
+        let Some(window) = self.opts.exit_on_plateau else {
+            return false;
+        };
+        let mut plateau = self.plateau.borrow_mut();
+        let plateau = plateau.get_or_insert_with(|| Plateau::new(window));
+        if let Some(stats) = stats {
+            plateau.progress(stats);
+        }
+        plateau.reached()
+    }
+
+    /// Runs `jobs` instances of the fuzz test in parallel. They share the
+    /// generated corpus directory, which libFuzzer reloads periodically
+    /// to pick up the inputs found by the other instances. Instead of
+    /// their output, the combined progress is printed. When an instance
+    /// crashes, the others are stopped and its output is returned.
+    pub fn run_jobs(&self, jobs: usize) -> Result<RunResult> {
+        std::fs::create_dir_all(&self.opts.generated_corpus_dir)
+            .context("failed to create the generated corpus directory")?;
+        std::fs::create_dir_all(&self.opts.artifact_dir)
+            .context("failed to create the artifact directory")?;
+
+        let (sender, receiver) = mpsc::channel();
+        let mut children = Vec::new();
+        for job in 0..jobs {
+            let mut cmd = self.command_with_args(&self.job_args(job));
+            // The output of the fuzz test itself would be interleaved
+            cmd.stdout(Stdio::null()).stderr(Stdio::piped());
+            log::debug!("Command of job {}: {:?}", job, cmd);
+            let mut child = match cmd.spawn() {
+                Ok(child) => child,
+                Err(err) => {
+                    kill_all(&mut children);
+                    return Err(err).with_context(|| {
+                        format!("failed to execute {}", self.opts.executable.display())
+                    });
+                }
+            };
+            let stderr = child.stderr.take().unwrap();
+            let sender = sender.clone();
+            std::thread::spawn(move || forward_lines(job, stderr, sender));
+            children.push(child);
+        }
+        drop(sender);
+
+        let mut outputs = vec![Vec::new(); jobs];
+        let mut stats = vec![Stats::default(); jobs];
+        let mut running = jobs;
+        let mut last_progress = Instant::now();
+        let mut result = None;
+        // The reader threads send `None` when the output of their job
+        // ends, i.e. when it exited
+        loop {
+            let (job, line) = match receiver.recv_timeout(self.receive_timeout()) {
+                Ok(received) => received,
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.plateaued(None) {
+                        return self.stop_plateaued(&mut children, &mut outputs);
+                    }
+                    continue;
+                }
+            };
+            let Some(line) = line else {
+                running -= 1;
+                let status = children[job]
+                    .wait()
+                    .context("failed to wait for the fuzz test")?;
+                if !status.success() {
+                    kill_all(&mut children);
+                    let output = std::mem::take(&mut outputs[job]);
+                    // Only the output of the crashed job is shown
+                    if !dashboard::is_active() {
+                        for line in &output {
+                            eprintln!("{line}");
+                        }
+                    }
+                    return Ok(RunResult {
+                        status,
+                        output,
+                        plateaued: false,
+                    });
+                }
+                result.get_or_insert(RunResult {
+                    status,
+                    output: std::mem::take(&mut outputs[job]),
+                    plateaued: false,
+                });
+                if running == 0 {
+                    break;
+                }
+                continue;
+            };
+            dashboard::line(job, &line);
+            if let Some(job_stats) = parser::parse_stats(&line) {
+                stats[job] = job_stats;
+                if self.plateaued(Some(&combine_stats(&stats))) {
+                    outputs[job].push(line);
+                    return self.stop_plateaued(&mut children, &mut outputs);
+                }
+                if last_progress.elapsed() >= JOBS_PROGRESS_INTERVAL {
+                    last_progress = Instant::now();
+                    let combined = combine_stats(&stats);
+                    if !dashboard::is_active() {
+                        log::info!("{}", format_progress(&combined, running));
+                    }
+                    events::progress(&combined);
+                    metrics::progress(&combined);
+                    campaign::progress(&combined);
+                }
+            }
+            outputs[job].push(line);
+        }
+        let combined = combine_stats(&stats);
+        log::info!("{}", format_progress(&combined, jobs));
+        events::progress(&combined);
+        metrics::progress(&combined);
+        campaign::progress(&combined);
+        result.context("no fuzz test job was started")
+    }
+
+    /// Stops the parallel jobs because their coverage plateaued and
+    /// returns the output of the first one.
+    fn stop_plateaued(
+        &self,
+        children: &mut [Child],
+        outputs: &mut [Vec<String>],
+    ) -> Result<RunResult> {
+        kill_all(children);
+        let status = children[0]
+            .wait()
+            .context("failed to wait for the fuzz test")?;
+        Ok(RunResult {
+            status,
+            output: std::mem::take(&mut outputs[0]),
+            plateaued: true,
+        })
+    }
+
+    /// Executes the fuzz test with a single input instead of fuzzing.
+    /// The output is not forwarded. If `calls_file` is given, the
+    /// runtime writes the calls of the `FuzzedDataProvider` to it, see
+    /// [`trace`](crate::trace).
+    pub fn run_input(&self, input: &Path, calls_file: Option<&Path>) -> Result<RunResult> {
+        let mut cmd = self.command_with_args(&[input.display().to_string()]);
+        if let Some(calls_file) = calls_file {
+            cmd.env(FDP_CALLS_ENV, calls_file);
+        }
+        self.output(cmd)
+    }
+
+    /// Executes the fuzz test with a single input and the flags of
+    /// [`Self::input_args`], i.e. under the limits with which it was
+    /// fuzzed, unlike [`Self::run_input`]. The output is not forwarded.
+    pub fn reproduce(&self, input: &Path) -> Result<RunResult> {
+        std::fs::create_dir_all(&self.opts.artifact_dir)
+            .context("failed to create the artifact directory")?;
+        let mut args = self.input_args();
+        args.push(format!(
+            "-artifact_prefix={}/",
+            self.opts.artifact_dir.display()
+        ));
+        args.push(input.display().to_string());
+        self.output(self.command_with_args(&args))
+    }
+
+    /// Executes the fuzz test with all inputs in the corpus directories
+    /// without fuzzing, writing the coverage profile of a build with
+    /// `-Cinstrument-coverage` to `profile_file`. The output is not
+    /// forwarded.
+    pub fn replay_corpus(&self, corpus_dirs: &[PathBuf], profile_file: &Path) -> Result<RunResult> {
+        let mut args = vec!["-runs=0".to_string()];
+        args.extend(corpus_dirs.iter().map(|d| d.display().to_string()));
+        let mut cmd = self.command_with_args(&args);
+        cmd.env("LLVM_PROFILE_FILE", profile_file);
+        self.output(cmd)
+    }
+
+    /// Executes libFuzzer's inner merge step, which runs the fuzz test
+    /// with the inputs listed in the control file and appends their
+    /// coverage to it (see [`crate::merge`]), under the limits of
+    /// [`Self::input_args`]. The output is not forwarded.
+    pub fn merge_inner(&self, control_file: &Path) -> Result<RunResult> {
+        let mut args = self.input_args();
+        args.extend([
+            // Record all features of every input instead of only those
+            // which weren't covered by earlier inputs
+            "-merge_inner=2".to_string(),
+            format!("-merge_control_file={}", control_file.display()),
+            format!("-artifact_prefix={}/", self.opts.artifact_dir.display()),
+        ]);
+        let cmd = self.command_with_args(&args);
+        std::fs::create_dir_all(&self.opts.artifact_dir)
+            .context("failed to create the artifact directory")?;
+        self.output(cmd)
+    }
+
+    /// Executes the fuzz test `runs` times with a single input under
+    /// `perf record`, which writes the profile with call graphs to
+    /// `perf_data`. The output is not forwarded.
+    pub fn record_profile(
+        &self,
+        perf: &Path,
+        input: &Path,
+        runs: u64,
+        perf_data: &Path,
+    ) -> Result<RunResult> {
+        let fuzz_test =
+            self.command_with_args(&[format!("-runs={runs}"), input.display().to_string()]);
+        let mut cmd = Command::new(perf);
+        cmd.arg("record")
+            // Rust code is mostly built without frame pointers
+            .arg("--call-graph=dwarf")
+            .arg("-o")
+            .arg(perf_data)
+            .arg("--")
+            .arg(fuzz_test.get_program())
+            .args(fuzz_test.get_args());
+        for (key, value) in fuzz_test.get_envs() {
+            if let Some(value) = value {
+                cmd.env(key, value);
+            }
+        }
+        cmd.current_dir(&self.opts.working_dir);
+        self.output(cmd)
+    }
+
+    fn output(&self, mut cmd: Command) -> Result<RunResult> {
+        log::debug!("Command: {:?}", cmd);
+        let output = cmd
+            .output()
+            .with_context(|| format!("failed to execute {}", self.opts.executable.display()))?;
+        Ok(RunResult {
+            status: output.status,
+            output: String::from_utf8_lossy(&output.stderr)
+                .lines()
+                .map(String::from)
+                .collect(),
+            plateaued: false,
+        })
+    }
+}
+
+/// Tracks when the fuzz test last covered new edges, for
+/// `--exit-on-plateau`.
+#[derive(Debug)]
+struct Plateau {
+    window: Duration,
+    /// The most edges covered so far
+    edges: u64,
+    /// When the fuzz test first covered them
+    since: Instant,
+}
+
+impl Plateau {
+    fn new(window: Duration) -> Self {
+        Plateau {
+            window,
+            edges: 0,
+            since: Instant::now(),
+        }
+    }
+
+    fn progress(&mut self, stats: &Stats) {
+        if stats.cov > self.edges {
+            self.edges = stats.cov;
+            self.since = Instant::now();
+        }
+    }
+
+    /// Whether the fuzz test covered no new edges for the window.
+    fn reached(&self) -> bool {
+        self.since.elapsed() >= self.window
+    }
+}
+
+/// Sends the lines of the output of a job to the channel, followed by
+/// `None` when the output ends.
+fn forward_lines(job: usize, output: impl Read, sender: mpsc::Sender<(usize, Option<String>)>) {
+    let mut reader = BufReader::new(output);
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line).unwrap_or(0) > 0 {
+        let text = String::from_utf8_lossy(&line);
+        let text = text.trim_end_matches(['\n', '\r']).to_string();
+        if sender.send((job, Some(text))).is_err() {
+            return;
+        }
+        line.clear();
+    }
+    let _ = sender.send((job, None));
+}
+
+fn kill_all(children: &mut [Child]) {
+    for child in children {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// Combines the progress of parallel jobs. The jobs share the corpus,
+/// so the coverage is the maximum, while the executions add up.
+pub fn combine_stats(stats: &[Stats]) -> Stats {
+    stats.iter().fold(Stats::default(), |total, job| Stats {
+        execs: total.execs + job.execs,
+        cov: total.cov.max(job.cov),
+        features: total.features.max(job.features),
+        corpus: total.corpus.max(job.corpus),
+        execs_per_sec: total.execs_per_sec + job.execs_per_sec,
+        rss_mb: total.rss_mb + job.rss_mb,
+    })
+}
+
+/// Formats the progress like the status lines of libFuzzer, with the
+/// number of jobs which contributed to it.
+pub fn format_progress(stats: &Stats, jobs: usize) -> String {
+    format!(
+        "#{}\tjobs: {jobs} cov: {} ft: {} corp: {} exec/s: {} rss: {}Mb",
+        stats.execs, stats.cov, stats.features, stats.corpus, stats.execs_per_sec, stats.rss_mb
+    )
+}
+
+/// The seed of libFuzzer in parallel job `job`. libFuzzer's seeds are 32
+/// bits, and 0 makes it pick a random one.
+fn job_seed(seed: u64, job: usize) -> u64 {
+    (seed - 1 + job as u64) % u64::from(u32::MAX) + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> RunnerOptions {
+        RunnerOptions {
+            executable: PathBuf::from("/build/deps/foo-123"),
+            test_name: "my_fuzz_test::fuzz".to_string(),
+            working_dir: PathBuf::from("/project"),
+            generated_corpus_dir: PathBuf::from("/project/.cifuzz-corpus/my_fuzz_test"),
+            seed_corpus_dirs: Vec::new(),
+            dictionary: None,
+            artifact_dir: PathBuf::from("/project/.cifuzz-artifacts/my_fuzz_test"),
+            timeout: None,
+            input_timeout: None,
+            rss_limit_mb: None,
+            seed: None,
+            use_value_profile: false,
+            sanitizer: None,
+            detect_leaks: false,
+            engine_args: Vec::new(),
+            capture_output: false,
+            slow_inputs_dir: None,
+            slow_inputs: 0,
+            exit_on_plateau: None,
+        }
+    }
+
+    #[test]
+    fn default_args() {
+        let runner = Runner::new(options());
+        assert_eq!(
+            runner.libfuzzer_args(),
+            vec![
+                "-max_total_time=0",
+                "-artifact_prefix=/project/.cifuzz-artifacts/my_fuzz_test/",
+                "/project/.cifuzz-corpus/my_fuzz_test",
+            ]
+        );
+    }
+
+    #[test]
+    fn timeout() {
+        let runner = Runner::new(RunnerOptions {
+            timeout: Some(Duration::from_secs(90)),
+            ..options()
+        });
+        assert_eq!(runner.libfuzzer_args()[0], "-max_total_time=90");
+    }
+
+    #[test]
+    fn input_timeout() {
+        let runner = Runner::new(RunnerOptions {
+            input_timeout: Some(Duration::from_millis(2500)),
+            ..options()
+        });
+        assert_eq!(runner.libfuzzer_args()[1], "-timeout=3");
+    }
+
+    #[test]
+    fn rss_limit() {
+        let runner = Runner::new(RunnerOptions {
+            rss_limit_mb: Some(512),
+            ..options()
+        });
+        assert_eq!(runner.libfuzzer_args()[1], "-rss_limit_mb=512");
+    }
+
+    #[test]
+    fn combined_progress() {
+        let job = |execs, cov, execs_per_sec| Stats {
+            execs,
+            cov,
+            features: cov + 10,
+            corpus: cov / 10,
+            execs_per_sec,
+            rss_mb: 30,
+        };
+        let total = combine_stats(&[job(1000, 60, 100), job(500, 80, 50)]);
+        assert_eq!(
+            format_progress(&total, 2),
+            "#1500\tjobs: 2 cov: 80 ft: 90 corp: 8 exec/s: 150 rss: 60Mb"
+        );
+    }
+
+    #[test]
+    fn plateau() {
+        let stats = |cov| Stats {
+            cov,
+            ..Stats::default()
+        };
+        let mut plateau = Plateau::new(Duration::from_secs(60));
+        plateau.progress(&stats(50));
+        assert!(!plateau.reached());
+        plateau.since -= Duration::from_secs(60);
+        assert!(plateau.reached());
+        // Fewer edges after a restart aren't new coverage
+        plateau.progress(&stats(40));
+        assert!(plateau.reached());
+        plateau.progress(&stats(51));
+        assert!(!plateau.reached());
+        assert_eq!(plateau.edges, 51);
+
+        let runner = Runner::new(options());
+        assert!(!runner.plateaued(None));
+        let runner = Runner::new(RunnerOptions {
+            exit_on_plateau: Some(Duration::ZERO),
+            ..options()
+        });
+        assert!(runner.plateaued(Some(&stats(10))));
+    }
+
+    #[test]
+    fn seed_corpus_dirs() {
+        let runner = Runner::new(RunnerOptions {
+            seed_corpus_dirs: vec![PathBuf::from("/project/src/my_fuzz_test_inputs")],
+            ..options()
+        });
+        assert_eq!(
+            runner.libfuzzer_args()[2..],
+            [
+                "/project/.cifuzz-corpus/my_fuzz_test",
+                "/project/src/my_fuzz_test_inputs"
+            ]
+        );
+    }
+
+    #[test]
+    fn value_profile() {
+        let runner = Runner::new(RunnerOptions {
+            use_value_profile: true,
+            ..options()
+        });
+        assert_eq!(runner.libfuzzer_args()[2], "-use_value_profile=1");
+    }
+
+    #[test]
+    fn sanitizer_options() {
+        let runner = Runner::new(RunnerOptions {
+            sanitizer: Some(Sanitizer::Thread),
+            ..options()
+        });
+        let cmd = runner.command();
+        let tsan_options = cmd
+            .get_envs()
+            .find(|(k, _)| *k == "TSAN_OPTIONS")
+            .and_then(|(_, v)| v);
+        assert!(tsan_options.is_some_and(|v| v.to_string_lossy().starts_with("halt_on_error=1")));
+    }
+
+    #[test]
+    fn leak_detection() {
+        let runner = Runner::new(RunnerOptions {
+            sanitizer: Some(Sanitizer::Leak),
+            detect_leaks: true,
+            ..options()
+        });
+        assert_eq!(runner.libfuzzer_args()[2], "-detect_leaks=1");
+
+        // AddressSanitizer only reports leaks if they're requested
+        let runner = Runner::new(RunnerOptions {
+            sanitizer: Some(Sanitizer::Address),
+            ..options()
+        });
+        assert_eq!(runner.libfuzzer_args()[2], "-detect_leaks=0");
+        let cmd = runner.command();
+        let asan_options = cmd
+            .get_envs()
+            .find(|(k, _)| *k == "ASAN_OPTIONS")
+            .and_then(|(_, v)| v);
+        assert!(asan_options.is_some_and(|v| v.to_string_lossy().starts_with("detect_leaks=0")));
+    }
+
+    #[test]
+    fn dictionary() {
+        let runner = Runner::new(RunnerOptions {
+            dictionary: Some(PathBuf::from("/project/my_fuzz_test.dict")),
+            ..options()
+        });
+        assert_eq!(
+            runner.libfuzzer_args()[2],
+            "-dict=/project/my_fuzz_test.dict"
+        );
+    }
+
+    #[test]
+    fn engine_args() {
+        let runner = Runner::new(RunnerOptions {
+            rss_limit_mb: Some(512),
+            engine_args: vec!["-rss_limit_mb=4096".to_string()],
+            ..options()
+        });
+        let args = runner.libfuzzer_args();
+        assert_eq!(
+            args[1..3],
+            [
+                "-rss_limit_mb=512",
+                "-artifact_prefix=/project/.cifuzz-artifacts/my_fuzz_test/"
+            ]
+        );
+        assert_eq!(args[3], "-rss_limit_mb=4096");
+        assert_eq!(args[4], "/project/.cifuzz-corpus/my_fuzz_test");
+    }
+
+    #[test]
+    fn seed() {
+        let runner = Runner::new(RunnerOptions {
+            seed: Some(42),
+            engine_args: vec!["-max_len=64".to_string()],
+            ..options()
+        });
+        let args = runner.libfuzzer_args();
+        assert_eq!(args[2..4], ["-seed=42", "-max_len=64"]);
+        assert_eq!(runner.job_args(0)[2], "-seed=42");
+        assert_eq!(runner.job_args(3)[2], "-seed=45");
+        let cmd = runner.command();
+        assert!(cmd
+            .get_envs()
+            .any(|(k, v)| k == SEED_ENV && v == Some("42".as_ref())));
+
+        assert_eq!(job_seed(u32::MAX.into(), 1), 1);
+        assert_eq!(job_seed(1, 2), 3);
+    }
+
+    #[test]
+    fn input_args() {
+        let runner = Runner::new(RunnerOptions {
+            timeout: Some(Duration::from_secs(60)),
+            input_timeout: Some(Duration::from_secs(10)),
+            dictionary: Some(PathBuf::from("/project/my_fuzz_test.dict")),
+            sanitizer: Some(Sanitizer::Address),
+            detect_leaks: true,
+            engine_args: vec!["-seed=7".to_string()],
+            ..options()
+        });
+        assert_eq!(
+            runner.input_args(),
+            ["-timeout=10", "-detect_leaks=1", "-seed=7"]
+        );
+    }
+
+    #[test]
+    fn command() {
+        let runner = Runner::new(options());
+        let cmd = runner.command();
+        let args: Vec<_> = cmd.get_args().collect();
+        assert_eq!(args[..2], ["--exact", "my_fuzz_test::fuzz"]);
+        let env: Vec<_> = cmd.get_envs().map(|(k, _)| k).collect();
+        assert_eq!(env[0], LIBFUZZER_ARGS_ENV);
+        if std::env::var_os("RUST_BACKTRACE").is_none() {
+            assert_eq!(env[1], "RUST_BACKTRACE");
+        }
+        assert!(!env.contains(&CAPTURE_OUTPUT_ENV.as_ref()));
+
+        let runner = Runner::new(RunnerOptions {
+            capture_output: true,
+            ..options()
+        });
+        let cmd = runner.command();
+        assert!(cmd
+            .get_envs()
+            .any(|(k, v)| k == CAPTURE_OUTPUT_ENV && v == Some("1".as_ref())));
+    }
+
+    #[test]
+    fn slow_inputs() {
+        let runner = Runner::new(options());
+        let cmd = runner.command();
+        assert!(!cmd.get_envs().any(|(k, _)| k == SLOW_INPUTS_DIR_ENV));
+
+        let runner = Runner::new(RunnerOptions {
+            slow_inputs_dir: Some(PathBuf::from("/project/.cifuzz-artifacts/slow-inputs")),
+            slow_inputs: 5,
+            ..options()
+        });
+        let cmd = runner.command();
+        let env: Vec<_> = cmd.get_envs().collect();
+        assert!(env.contains(&(
+            SLOW_INPUTS_DIR_ENV.as_ref(),
+            Some("/project/.cifuzz-artifacts/slow-inputs".as_ref())
+        )));
+        assert!(env.contains(&(SLOW_INPUTS_COUNT_ENV.as_ref(), Some("5".as_ref()))));
+    }
+}
@@ -0,0 +1,229 @@
+//! Replays the inputs of a fuzz test under Miri.
+//!
+//! Miri interprets the fuzz test instead of fuzzing it, which is too
+//! slow for fuzzing, but detects undefined behavior which sanitizers
+//! miss, like violations of the aliasing rules. The fuzz test is
+//! executed as a regression test by `cargo miri test`, with the inputs
+//! listed in a file passed to the runtime via `CIFUZZ_REPLAY_INPUTS`.
+//! Miri aborts on the first undefined behavior, so the replay is
+//! restarted after the input it aborted on, which is the last input the
+//! runtime announced.
+//!
+//! cargo-miri executes the tests with the environment variables of their
+//! build, which take precedence over the current ones, so the list is
+//! always written to the same file, in the build directory of Miri.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{bail, Context, Result};
+
+use crate::build;
+use crate::log;
+
+use super::{FuzzingResult, RunResult};
+
+/// The environment variable with the file listing the inputs to replay.
+// Must be kept in sync with crates/cifuzz/src/regression.rs
+const REPLAY_INPUTS_ENV: &str = "CIFUZZ_REPLAY_INPUTS";
+
+/// The line the runtime prints before it executes an input, followed by
+/// the path of the input.
+const RUNNING_PREFIX: &str = "Running ";
+
+/// The flags Miri needs to replay the inputs: The runtime reads the
+/// inputs from the file system, and the fuzz tests may leak memory on
+/// purpose, e.g. for `'static` state.
+const MIRI_FLAGS: &[&str] = &["-Zmiri-disable-isolation", "-Zmiri-ignore-leaks"];
+
+#[derive(Debug)]
+pub struct RunnerOptions {
+    /// The full path of the libtest test which runs the fuzz test when
+    /// it's built for fuzzing, e.g. "my_fuzz_test::fuzz"
+    pub test_name: String,
+    /// The directory of the package containing the fuzz test, in which
+    /// `cargo miri test` is executed
+    pub working_dir: PathBuf,
+    /// The inputs to replay, in this order
+    pub inputs: Vec<PathBuf>,
+    /// The target directory of `cargo miri test`, which also contains
+    /// the list of inputs
+    pub build_dir: PathBuf,
+    /// Additional arguments for `cargo miri test`
+    pub cargo_args: Vec<String>,
+    /// Additional flags for Miri, which are appended to `MIRIFLAGS`
+    pub engine_args: Vec<String>,
+}
+
+pub struct Runner {
+    opts: RunnerOptions,
+}
+
+impl Runner {
+    pub fn new(opts: RunnerOptions) -> Self {
+        Runner { opts }
+    }
+
+    /// Replays all inputs and returns those on which Miri detected
+    /// undefined behavior as the crashes.
+    pub fn run(&self) -> Result<FuzzingResult> {
+        let mut remaining = self.opts.inputs.as_slice();
+        let mut crashes = Vec::new();
+        loop {
+            let result = self.replay(remaining)?;
+            if result.status.success() {
+                return Ok(FuzzingResult {
+                    status: result.status,
+                    crashes,
+                    hangs: Vec::new(),
+                });
+            }
+            let error = miri_error(&result.output);
+            let input = result
+                .output
+                .iter()
+                .rev()
+                .find_map(|line| line.strip_prefix(RUNNING_PREFIX))
+                .and_then(|path| remaining.iter().position(|input| input.as_os_str() == path));
+            let Some(i) = input else {
+                bail!(
+                    "Miri failed before it executed an input ({}):\n{}",
+                    result.status,
+                    result.output.join("\n")
+                );
+            };
+            match error {
+                Some(error) if error.starts_with("Undefined Behavior: ") => {
+                    crashes.push(remaining[i].clone());
+                }
+                Some(error) if error.starts_with("unsupported operation: ") => bail!(
+                    "Miri can't execute the fuzz test on {}: {error}",
+                    remaining[i].display()
+                ),
+                error => log::error!(
+                    "Miri failed on {}: {}",
+                    remaining[i].display(),
+                    error.unwrap_or("the fuzz test aborted")
+                ),
+            }
+            remaining = &remaining[i + 1..];
+            if remaining.is_empty() {
+                return Ok(FuzzingResult {
+                    status: result.status,
+                    crashes,
+                    hangs: Vec::new(),
+                });
+            }
+            log::info!(
+                "Continuing the replay with the {} remaining inputs",
+                remaining.len()
+            );
+        }
+    }
+
+    /// Replays a single input, e.g. to get the report of Miri.
+    pub fn run_input(&self, input: &Path) -> Result<RunResult> {
+        self.replay(&[input.to_path_buf()])
+    }
+
+    fn replay(&self, inputs: &[PathBuf]) -> Result<RunResult> {
+        std::fs::create_dir_all(&self.opts.build_dir)
+            .with_context(|| format!("failed to create {}", self.opts.build_dir.display()))?;
+        let list = self.opts.build_dir.join("inputs.txt");
+        let paths: Vec<String> = inputs
+            .iter()
+            .map(|input| input.display().to_string() + "\n")
+            .collect();
+        std::fs::write(&list, paths.concat())
+            .with_context(|| format!("failed to write {}", list.display()))?;
+
+        let module = self
+            .opts
+            .test_name
+            .strip_suffix("::fuzz")
+            .unwrap_or(&self.opts.test_name);
+        let mut miri_flags: Vec<String> = std::env::var("MIRIFLAGS")
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        miri_flags.extend(MIRI_FLAGS.iter().map(|flag| flag.to_string()));
+        miri_flags.extend(self.opts.engine_args.iter().cloned());
+
+        let mut cmd = Command::new(build::cargo());
+        cmd.args(["miri", "test"])
+            .args(&self.opts.cargo_args)
+            .args(["--", "--exact", &format!("{module}::regression")])
+            .args(["--nocapture", "--test-threads", "1"])
+            .env("CARGO_TARGET_DIR", &self.opts.build_dir)
+            .env(REPLAY_INPUTS_ENV, &list)
+            .env("MIRIFLAGS", miri_flags.join(" "))
+            .stdout(Stdio::null())
+            .current_dir(&self.opts.working_dir);
+        // Miri prints its own backtrace, capturing the one of every panic
+        // of the findings is slow under Miri
+        if std::env::var_os("RUST_BACKTRACE").is_none() {
+            cmd.env("RUST_BACKTRACE", "0");
+        }
+        log::debug!("Command: {:?}", cmd);
+        let output = cmd.output().context("failed to execute cargo miri")?;
+        Ok(RunResult {
+            status: output.status,
+            output: String::from_utf8_lossy(&output.stderr)
+                .lines()
+                .map(String::from)
+                .collect(),
+            plateaued: false,
+        })
+    }
+}
+
+/// Checks that Miri is installed for the toolchain of cargo, which
+/// `--engine miri` requires.
+pub fn check_installed() -> Result<()> {
+    let installed = Command::new(build::cargo())
+        .args(["miri", "--version"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !installed {
+        bail!(
+            "--engine miri requires Miri, which requires a nightly toolchain, install it with \
+             `rustup +nightly component add miri` and run `cargo +nightly cifuzz run --engine miri`"
+        );
+    }
+    Ok(())
+}
+
+/// Returns the error of Miri's report, e.g. "Undefined Behavior: ..." or
+/// "unsupported operation: ...".
+fn miri_error(output: &[String]) -> Option<&str> {
+    output
+        .iter()
+        .find_map(|line| line.split_once("error: ").map(|(_, error)| error))
+        .filter(|error| !error.starts_with("aborting due to ") && !error.starts_with("test failed"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn miri_errors() {
+        let output: Vec<String> = [
+            "Running /p/.cifuzz-corpus/my_fuzz_test/a",
+            "test my_fuzz_test::regression ... error: Undefined Behavior: out-of-bounds pointer use",
+            " --> src/lib.rs:5:14",
+            "error: aborting due to 1 previous error",
+        ]
+        .map(String::from)
+        .to_vec();
+        assert_eq!(
+            miri_error(&output),
+            Some("Undefined Behavior: out-of-bounds pointer use")
+        );
+        assert_eq!(miri_error(&output[..1]), None);
+        assert_eq!(miri_error(&output[3..]), None);
+    }
+}
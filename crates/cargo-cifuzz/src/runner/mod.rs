@@ -0,0 +1,111 @@
+//! Runners which execute fuzz tests with a fuzzing engine.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::log;
+
+pub mod afl;
+pub mod flags;
+pub mod honggfuzz;
+pub mod libfuzzer;
+pub mod miri;
+
+/// The input AFL++ and honggfuzz start from if the fuzz test has no
+/// corpus yet, because they require at least one.
+const DEFAULT_SEED: &[u8] = b"\0";
+
+/// The fuzzing engine which executes the fuzz test.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    #[default]
+    Libfuzzer,
+    /// AFL++, which must be installed
+    Afl,
+    /// honggfuzz, which must be installed
+    Honggfuzz,
+    /// libFuzzer running fuzz tests built for wasm32-wasip1 under
+    /// wasmtime, which requires cifuzz-wasm-driver
+    Wasm,
+    /// Miri replaying the corpus and the findings to detect undefined
+    /// behavior, which requires the miri component of a nightly
+    /// toolchain
+    Miri,
+}
+
+impl Engine {
+    pub fn name(self) -> &'static str {
+        match self {
+            Engine::Libfuzzer => "libFuzzer",
+            Engine::Afl => "AFL++",
+            Engine::Honggfuzz => "honggfuzz",
+            Engine::Wasm => "wasm",
+            Engine::Miri => "Miri",
+        }
+    }
+}
+
+/// The result of a fuzz test execution.
+#[derive(Debug)]
+pub struct RunResult {
+    pub status: ExitStatus,
+    /// The lines printed to stderr, i.e. the output of the fuzzing
+    /// engine and the panic messages
+    pub output: Vec<String>,
+    /// Whether cargo-cifuzz stopped the fuzz test because its coverage
+    /// stopped growing, see `--exit-on-plateau`
+    pub plateaued: bool,
+}
+
+/// The result of a run of a fuzzing engine which stores the inputs it
+/// found in directories instead of reporting them in its output like
+/// libFuzzer.
+#[derive(Debug)]
+pub struct FuzzingResult {
+    pub status: ExitStatus,
+    /// The inputs on which the fuzz test crashed
+    pub crashes: Vec<PathBuf>,
+    /// The inputs on which the fuzz test exceeded the input timeout
+    pub hangs: Vec<PathBuf>,
+}
+
+/// Executes the fuzz test built for AFL++ or honggfuzz outside of the
+/// fuzzer with a single input, which its harness reads from stdin, e.g.
+/// to get the panic message and the stack trace of a crash. The output
+/// is not forwarded.
+pub fn run_input_from_stdin(
+    executable: &Path,
+    test_name: &str,
+    working_dir: &Path,
+    input: &Path,
+) -> Result<RunResult> {
+    let stdin = std::fs::File::open(input)
+        .with_context(|| format!("failed to open {}", input.display()))?;
+    let mut cmd = Command::new(executable);
+    cmd.args(["--exact", test_name])
+        .args(["--nocapture", "--test-threads", "1"])
+        .stdin(stdin)
+        .stdout(Stdio::null())
+        .current_dir(working_dir);
+    // Panics must print a stack trace, so that it can be stored with the
+    // finding
+    if std::env::var_os("RUST_BACKTRACE").is_none() {
+        cmd.env("RUST_BACKTRACE", "1");
+    }
+    log::debug!("Command: {:?}", cmd);
+    let output = cmd
+        .output()
+        .with_context(|| format!("failed to execute {}", executable.display()))?;
+    Ok(RunResult {
+        status: output.status,
+        output: String::from_utf8_lossy(&output.stderr)
+            .lines()
+            .map(String::from)
+            .collect(),
+        plateaued: false,
+    })
+}
@@ -0,0 +1,262 @@
+//! Exporting findings in the SARIF 2.1.0 format, which GitHub code
+//! scanning and other static analysis tooling import.
+//!
+//! Every finding is a result located at the top frame of its stack trace
+//! in the project, with the whole stack trace as its stack. Results are
+//! fingerprinted with the dedup token of the finding, so that the same
+//! bug found again is recognized as the same alert.
+
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::config::SeverityRule;
+use crate::finding::{Finding, Severity, StackFrame, ERROR_TYPES};
+use crate::severity;
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Returns the SARIF log with the findings as the results of a single
+/// run of cargo-cifuzz.
+pub fn report(findings: &[Finding], severity_rules: &[SeverityRule]) -> Value {
+    let rules: Vec<Value> = ERROR_TYPES
+        .into_iter()
+        .filter(|error_type| findings.iter().any(|f| f.error_type == *error_type))
+        .map(|error_type| {
+            json!({
+                "id": error_type.id(),
+                "name": error_type.name(),
+                "shortDescription": {
+                    "text": format!("The fuzz test found a {}", error_type.description()),
+                },
+                "defaultConfiguration": { "level": "error" },
+            })
+        })
+        .collect();
+    json!({
+        "$schema": SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "cargo-cifuzz",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "results": findings
+                .iter()
+                .map(|finding| result(finding, severity_rules))
+                .collect::<Vec<_>>(),
+        }],
+    })
+}
+
+fn result(finding: &Finding, rules: &[SeverityRule]) -> Value {
+    let severity = finding.severity(rules);
+    let input_file = uri(&finding.input_file.to_string_lossy());
+    // Code scanning requires a location, findings without a stack trace
+    // in the project are located at their crashing input
+    let location = finding
+        .stack_trace
+        .iter()
+        .find(|frame| is_project_file(&frame.source_file))
+        .map_or_else(
+            || json!({ "physicalLocation": { "artifactLocation": input_file.clone() } }),
+            location,
+        );
+    let mut result = json!({
+        "ruleId": finding.error_type.id(),
+        "level": level(severity),
+        "message": {
+            "text": format!(
+                "The fuzz test {} found a {}: {}\nThe crashing input is stored in {}",
+                finding.metadata.fuzz_test,
+                finding.error_type.description(),
+                finding.details,
+                finding.input_file.display()
+            ),
+        },
+        "locations": [location],
+        "partialFingerprints": { "dedupToken/v1": finding.dedup_token },
+        "properties": {
+            "fuzzTest": finding.metadata.fuzz_test,
+            "finding": finding.name,
+            "inputFile": input_file["uri"],
+            "createdAt": finding.created_at,
+            "severity": severity.name(),
+            "kind": severity::kind(finding),
+        },
+    });
+    if !finding.stack_trace.is_empty() {
+        result["stacks"] = json!([{
+            "message": { "text": "Stack trace" },
+            "frames": finding.stack_trace.iter().map(stack_frame).collect::<Vec<_>>(),
+        }]);
+    }
+    result
+}
+
+/// The level of the results by the severity of their finding, so that
+/// code scanning shows high severity findings as errors.
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::High => "error",
+        Severity::Medium => "warning",
+        Severity::Low => "note",
+    }
+}
+
+fn location(frame: &StackFrame) -> Value {
+    let mut region = json!({ "startLine": frame.line.max(1) });
+    if frame.column > 0 {
+        region["startColumn"] = json!(frame.column);
+    }
+    json!({
+        "physicalLocation": {
+            "artifactLocation": uri(&frame.source_file),
+            "region": region,
+        },
+        "logicalLocations": [{ "fullyQualifiedName": frame.function, "kind": "function" }],
+    })
+}
+
+fn stack_frame(frame: &StackFrame) -> Value {
+    if frame.source_file.is_empty() {
+        return json!({
+            "location": {
+                "logicalLocations": [{ "fullyQualifiedName": frame.function, "kind": "function" }],
+            },
+        });
+    }
+    json!({ "location": location(frame) })
+}
+
+/// Source files of the project are stored relative to the project
+/// directory, other paths like the sources of the standard library are
+/// absolute.
+fn is_project_file(path: &str) -> bool {
+    !path.is_empty() && Path::new(path).is_relative()
+}
+
+/// The artifact location of a path. Paths in the project are relative
+/// to the root of the sources, which the consumer of the log resolves.
+fn uri(path: &str) -> Value {
+    let encoded = encode_path(path);
+    if is_project_file(path) {
+        json!({ "uri": encoded, "uriBaseId": "%SRCROOT%" })
+    } else {
+        json!({ "uri": format!("file://{encoded}") })
+    }
+}
+
+/// Percent-encodes the characters of the path which aren't allowed in a
+/// URI.
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::new();
+    for byte in path.replace('\\', "/").bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::finding::{test_finding, ErrorType};
+
+    fn finding(error_type: ErrorType, stack_trace: Vec<StackFrame>) -> Finding {
+        Finding {
+            error_type,
+            stack_trace,
+            dedup_token: "e6c1a2d3".to_string(),
+            ..test_finding("5ed1b771cc61")
+        }
+    }
+
+    fn frame(function: &str, source_file: &str, line: u32) -> StackFrame {
+        StackFrame {
+            source_file: source_file.to_string(),
+            line,
+            column: 21,
+            frame_number: 0,
+            function: function.to_string(),
+        }
+    }
+
+    #[test]
+    fn crash_location() {
+        let crash = finding(
+            ErrorType::Crash,
+            vec![
+                frame(
+                    "core::panicking::panic",
+                    "/rustc/abc/library/core/src/panicking.rs",
+                    75,
+                ),
+                frame("cargo_example::explore_me", "src/explore_me.rs", 14),
+                frame("cargo_example::my_fuzz_test", "", 0),
+            ],
+        );
+        let report = report(&[crash], &[]);
+        let run = &report["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "crash");
+        assert_eq!(run["tool"]["driver"]["rules"][0]["name"], "Crash");
+        let result = &run["results"][0];
+        assert_eq!(result["ruleId"], "crash");
+        assert_eq!(result["partialFingerprints"]["dedupToken/v1"], "e6c1a2d3");
+        assert_eq!(result["level"], "note");
+        assert_eq!(result["properties"]["severity"], "low");
+        assert_eq!(result["properties"]["kind"], "panic");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "src/explore_me.rs");
+        assert_eq!(location["artifactLocation"]["uriBaseId"], "%SRCROOT%");
+        assert_eq!(location["region"]["startLine"], 14);
+        assert_eq!(location["region"]["startColumn"], 21);
+
+        let frames = result["stacks"][0]["frames"].as_array().unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(
+            frames[0]["location"]["physicalLocation"]["artifactLocation"]["uri"],
+            "file:///rustc/abc/library/core/src/panicking.rs"
+        );
+        assert!(frames[2]["location"].get("physicalLocation").is_none());
+    }
+
+    #[test]
+    fn location_without_stack_trace() {
+        let report = report(
+            &[
+                finding(ErrorType::OutOfMemory, Vec::new()),
+                finding(ErrorType::Crash, Vec::new()),
+            ],
+            &[],
+        );
+        let run = &report["runs"][0];
+        let rules: Vec<&str> = run["tool"]["driver"]["rules"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|rule| rule["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(rules, ["crash", "out-of-memory"]);
+        let result = &run["results"][0];
+        assert_eq!(
+            result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            ".cifuzz/findings/my_fuzz_test/5ed1b771cc61/crashing-input"
+        );
+        assert!(result.get("stacks").is_none());
+        assert_eq!(result["level"], "warning");
+    }
+
+    #[test]
+    fn encoded_paths() {
+        assert_eq!(encode_path("src/a b.rs"), "src/a%20b.rs");
+        assert_eq!(encode_path("src\\lib.rs"), "src/lib.rs");
+    }
+}
@@ -0,0 +1,238 @@
+//! Classification of findings by the kind of bug and its severity.
+//!
+//! The kind of a finding is derived from its type and details, e.g.
+//! `heap-buffer-overflow` for a crash reported by AddressSanitizer,
+//! `integer-overflow` for a failed overflow check or `unwrap` for a
+//! panic of `Option::unwrap`. Every kind has a default severity: memory
+//! errors detected by a sanitizer and undefined behavior are high,
+//! because they may be exploitable, bugs which make a program abort or
+//! hang on untrusted input, like arithmetic overflows, out-of-bounds
+//! indexing or timeouts, are medium, and explicit panics, unwraps,
+//! assertions and leaks are low.
+//!
+//! The `severity` rules of the cifuzz.yaml take precedence over the
+//! severity given to `cifuzz::report_finding!`, which takes precedence
+//! over the default of the kind:
+//!
+//! ```yaml
+//! severity:
+//!   - kind: assertion
+//!     severity: medium
+//!   - message: "^invariant violated"
+//!     fuzz-test: my_fuzz_test
+//!     severity: high
+//! ```
+
+use crate::config::SeverityRule;
+use crate::finding::{ErrorType, Finding, Severity};
+
+/// Kinds of panics by (a part of) their message.
+const PANIC_KINDS: [(&str, &str); 9] = [
+    ("index out of bounds", "index-out-of-bounds"),
+    ("is not a char boundary", "index-out-of-bounds"),
+    ("out of range for slice", "index-out-of-bounds"),
+    ("attempt to divide by zero", "division-by-zero"),
+    (
+        "attempt to calculate the remainder with a divisor of zero",
+        "division-by-zero",
+    ),
+    ("called `Option::unwrap()`", "unwrap"),
+    ("called `Result::unwrap()`", "unwrap"),
+    ("assertion", "assertion"),
+    ("capacity overflow", "capacity-overflow"),
+];
+
+/// Kinds of sanitizer errors which are medium instead of high, because
+/// they exhaust resources rather than corrupt memory.
+const MEDIUM_SANITIZER_KINDS: [&str; 4] = [
+    "stack-overflow",
+    "allocation-size-too-big",
+    "requested-allocation-size-exceeds-maximum-supported-size",
+    "out-of-memory",
+];
+
+/// Returns the kind of bug of the finding, e.g. `heap-buffer-overflow`,
+/// `integer-overflow` or `panic`.
+pub fn kind(finding: &Finding) -> String {
+    match finding.error_type {
+        ErrorType::Crash => crash_kind(&finding.details),
+        error_type => error_type.id().to_string(),
+    }
+}
+
+/// Returns the kind of a crash by its details, the error of the
+/// sanitizer like "AddressSanitizer: heap-buffer-overflow", the panic
+/// message or "deadly signal" for other crashes.
+fn crash_kind(details: &str) -> String {
+    if let Some((sanitizer, error)) = details.split_once(": ") {
+        if sanitizer.ends_with("Sanitizer") && !sanitizer.contains(' ') {
+            return slug(error);
+        }
+    }
+    if details == "deadly signal" || details == "unknown error" {
+        return "crash".to_string();
+    }
+    PANIC_KINDS
+        .iter()
+        .find(|(message, _)| details.contains(message))
+        .map_or("panic", |(_, kind)| kind)
+        .to_string()
+}
+
+/// Converts an error of a sanitizer to a kind, e.g. "data race" to
+/// `data-race`.
+fn slug(error: &str) -> String {
+    error
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Returns the default severity of a kind of bug.
+pub fn default_severity(kind: &str) -> Severity {
+    match kind {
+        "panic" | "unwrap" | "assertion" | "memory-leak" => Severity::Low,
+        "integer-overflow"
+        | "index-out-of-bounds"
+        | "division-by-zero"
+        | "capacity-overflow"
+        | "timeout" => Severity::Medium,
+        kind if MEDIUM_SANITIZER_KINDS.contains(&kind) => Severity::Medium,
+        _ => Severity::High,
+    }
+}
+
+/// Returns the severity of the finding: the one of the first matching
+/// rule, else the reported one, else the default of its kind.
+pub fn classify(finding: &Finding, rules: &[SeverityRule]) -> Severity {
+    let kind = kind(finding);
+    rules
+        .iter()
+        .find(|rule| rule.matches(finding, &kind))
+        .map(|rule| rule.severity)
+        .or(finding.severity)
+        .unwrap_or_else(|| default_severity(&kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::finding::test_finding;
+
+    fn finding(error_type: ErrorType, details: &str) -> Finding {
+        Finding {
+            error_type,
+            details: details.to_string(),
+            dedup_token: "0d09a0cb426d9115".to_string(),
+            ..test_finding("5ed1b771")
+        }
+    }
+
+    #[test]
+    fn classify_kinds() {
+        let cases = [
+            (
+                ErrorType::Crash,
+                "AddressSanitizer: heap-buffer-overflow",
+                "heap-buffer-overflow",
+                Severity::High,
+            ),
+            (
+                ErrorType::Crash,
+                "ThreadSanitizer: data race",
+                "data-race",
+                Severity::High,
+            ),
+            (
+                ErrorType::Crash,
+                "AddressSanitizer: stack-overflow",
+                "stack-overflow",
+                Severity::Medium,
+            ),
+            (ErrorType::Crash, "deadly signal", "crash", Severity::High),
+            (
+                ErrorType::Crash,
+                "index out of bounds: the len is 3 but the index is 5",
+                "index-out-of-bounds",
+                Severity::Medium,
+            ),
+            (
+                ErrorType::Crash,
+                "attempt to divide by zero",
+                "division-by-zero",
+                Severity::Medium,
+            ),
+            (
+                ErrorType::Crash,
+                "called `Option::unwrap()` on a `None` value",
+                "unwrap",
+                Severity::Low,
+            ),
+            (
+                ErrorType::Crash,
+                "assertion failed: len < 8",
+                "assertion",
+                Severity::Low,
+            ),
+            (
+                ErrorType::Crash,
+                "branch 4 has been reached",
+                "panic",
+                Severity::Low,
+            ),
+            (
+                ErrorType::IntegerOverflow,
+                "attempt to add with overflow",
+                "integer-overflow",
+                Severity::Medium,
+            ),
+            (
+                ErrorType::Timeout,
+                "timeout after 10 seconds",
+                "timeout",
+                Severity::Medium,
+            ),
+            (
+                ErrorType::OutOfMemory,
+                "out-of-memory (malloc(2048))",
+                "out-of-memory",
+                Severity::Medium,
+            ),
+            (
+                ErrorType::Leak,
+                "LeakSanitizer: detected memory leaks",
+                "memory-leak",
+                Severity::Low,
+            ),
+            (
+                ErrorType::UndefinedBehavior,
+                "Undefined Behavior: ...",
+                "undefined-behavior",
+                Severity::High,
+            ),
+        ];
+        for (error_type, details, expected_kind, severity) in cases {
+            let finding = finding(error_type, details);
+            assert_eq!(kind(&finding), expected_kind, "{details}");
+            assert_eq!(classify(&finding, &[]), severity, "{details}");
+        }
+    }
+
+    #[test]
+    fn rules_take_precedence() {
+        let rule = |yaml: &str| serde_yaml::from_str::<SeverityRule>(yaml).unwrap();
+        let mut finding = finding(ErrorType::Crash, "assertion failed: len < 8");
+        finding.severity = Some(Severity::Medium);
+        assert_eq!(classify(&finding, &[]), Severity::Medium);
+
+        let rules = [
+            rule("kind: panic\nseverity: high"),
+            rule("message: ^assertion\nfuzz-test: other\nseverity: low"),
+            rule("kind: assertion\nseverity: high"),
+        ];
+        assert_eq!(classify(&finding, &rules), Severity::High);
+        assert_eq!(classify(&finding, &rules[..2]), Severity::Medium);
+    }
+}
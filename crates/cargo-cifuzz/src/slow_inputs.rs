@@ -0,0 +1,232 @@
+//! The slowest inputs of a run, for `cargo cifuzz run --slow-inputs`.
+//!
+//! The runtime measures how long the fuzz test takes on every input and
+//! keeps the slowest ones. When the fuzz test exits, it writes them to
+//! the directory passed via `CIFUZZ_SLOW_INPUTS_DIR`, named by their
+//! SHA-1 like the inputs of the corpus, with an index file per process
+//! whose lines are `<nanoseconds>\t<sha1>`. The index files of parallel
+//! jobs and restarts are merged here.
+//!
+//! With `--flamegraph`, the fuzz test is profiled with perf on the
+//! slowest input, and the profile is rendered with inferno if it's
+//! installed.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+
+use crate::log;
+use crate::runner::libfuzzer::Runner;
+
+/// How long the fuzz test is profiled on the slowest input, by
+/// executing it repeatedly.
+const PROFILE_DURATION: Duration = Duration::from_secs(2);
+
+/// A slow input written by the runtime.
+#[derive(Debug, PartialEq)]
+pub struct SlowInput {
+    pub path: PathBuf,
+    /// The longest time the fuzz test took on the input
+    pub duration: Duration,
+    /// Whether libFuzzer added the input to the generated corpus
+    pub in_corpus: bool,
+}
+
+/// Empties the directory into which the runtime writes the slow inputs,
+/// so that only the inputs of this run are reported.
+pub fn prepare(dir: &Path) -> Result<()> {
+    if dir.exists() {
+        std::fs::remove_dir_all(dir)
+            .with_context(|| format!("failed to remove {}", dir.display()))?;
+    }
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))
+}
+
+/// Returns the `count` slowest inputs written to the directory, the
+/// slowest first.
+pub fn read(dir: &Path, count: usize, corpus_dir: &Path) -> Result<Vec<SlowInput>> {
+    let mut durations: HashMap<String, Duration> = HashMap::new();
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?
+    {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "tsv") {
+            continue;
+        }
+        let index = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        for line in index.lines() {
+            let Some((nanos, sha1)) = line.split_once('\t') else {
+                continue;
+            };
+            let Ok(nanos) = nanos.parse() else {
+                continue;
+            };
+            // The same input may be executed by several jobs or runs
+            let duration = durations.entry(sha1.to_string()).or_default();
+            *duration = (*duration).max(Duration::from_nanos(nanos));
+        }
+    }
+    let mut inputs: Vec<SlowInput> = durations
+        .into_iter()
+        .filter(|(sha1, _)| dir.join(sha1).is_file())
+        .map(|(sha1, duration)| SlowInput {
+            in_corpus: corpus_dir.join(&sha1).is_file(),
+            path: dir.join(sha1),
+            duration,
+        })
+        .collect();
+    inputs.sort_by(|a, b| {
+        b.duration
+            .cmp(&a.duration)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    inputs.truncate(count);
+    Ok(inputs)
+}
+
+/// Prints the slow inputs of the fuzz test.
+pub fn report(fuzz_test: &str, inputs: &[SlowInput]) {
+    if inputs.is_empty() {
+        log::info!("The fuzz test {fuzz_test} recorded no slow inputs");
+        return;
+    }
+    log::info!(
+        "The {} slowest {} of {fuzz_test}:",
+        inputs.len(),
+        if inputs.len() == 1 { "input" } else { "inputs" }
+    );
+    for input in inputs {
+        log::info!(
+            "{:>12.3} ms  {}{}",
+            input.duration.as_secs_f64() * 1000.0,
+            input.path.display(),
+            if input.in_corpus {
+                " (in the corpus)"
+            } else {
+                ""
+            }
+        );
+    }
+}
+
+/// Returns the path of perf, which `--flamegraph` requires.
+pub fn perf() -> Result<PathBuf> {
+    match find_in_path("perf") {
+        Some(perf) => Ok(perf),
+        None => bail!(
+            "--flamegraph requires perf, install it with the package manager, e.g. \
+             `apt install linux-tools-generic`"
+        ),
+    }
+}
+
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|tool| tool.is_file())
+}
+
+/// Profiles the fuzz test on the input with perf and writes the profile
+/// to the directory: perf.data, and flamegraph.svg if the inferno tools
+/// are installed. Returns the path of the profile to show.
+pub fn flamegraph(runner: &Runner, input: &SlowInput, dir: &Path) -> Result<PathBuf> {
+    let perf = perf()?;
+    // A single execution of a fast input has too few samples
+    let runs = (PROFILE_DURATION.as_nanos() / input.duration.as_nanos().max(1)).clamp(1, 100_000);
+    let perf_data = dir.join("perf.data");
+    let result = runner.record_profile(&perf, &input.path, runs as u64, &perf_data)?;
+    if !perf_data.is_file() {
+        bail!(
+            "perf failed to profile the fuzz test ({}): {}",
+            result.status,
+            result.output.join("\n")
+        );
+    }
+
+    let (Some(collapse), Some(render)) = (
+        find_in_path("inferno-collapse-perf"),
+        find_in_path("inferno-flamegraph"),
+    ) else {
+        log::info!(
+            "Install inferno with `cargo install inferno` to render the profile as a flamegraph"
+        );
+        return Ok(perf_data);
+    };
+    let script = std::fs::File::create(dir.join("perf.script"))?;
+    let status = Command::new(&perf)
+        .arg("script")
+        .arg("-i")
+        .arg(&perf_data)
+        .stdout(script)
+        .stderr(Stdio::null())
+        .status()
+        .with_context(|| format!("failed to execute {}", perf.display()))?;
+    if !status.success() {
+        bail!("perf script failed with {status}");
+    }
+    let collapsed = dir.join("perf.folded");
+    pipe(&collapse, &dir.join("perf.script"), &collapsed)?;
+    let svg = dir.join("flamegraph.svg");
+    pipe(&render, &collapsed, &svg)?;
+    Ok(svg)
+}
+
+/// Executes the tool with the input file as stdin and the output file
+/// as stdout.
+fn pipe(tool: &Path, input: &Path, output: &Path) -> Result<()> {
+    let status = Command::new(tool)
+        .stdin(std::fs::File::open(input)?)
+        .stdout(std::fs::File::create(output)?)
+        .status()
+        .with_context(|| format!("failed to execute {}", tool.display()))?;
+    if !status.success() {
+        bail!("{} failed with {status}", tool.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merged_index_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let corpus = tempfile::tempdir().unwrap();
+        for sha1 in ["aaa", "bbb", "ccc"] {
+            std::fs::write(dir.path().join(sha1), sha1).unwrap();
+        }
+        std::fs::write(corpus.path().join("bbb"), "bbb").unwrap();
+        std::fs::write(dir.path().join("1.tsv"), "3000000\taaa\n1000\tccc\n").unwrap();
+        // The slowest input wasn't written
+        std::fs::write(
+            dir.path().join("2.tsv"),
+            "9000000\tddd\n5000000\tbbb\n2000\tccc\n",
+        )
+        .unwrap();
+
+        let inputs = read(dir.path(), 2, corpus.path()).unwrap();
+        assert_eq!(
+            inputs,
+            [
+                SlowInput {
+                    path: dir.path().join("bbb"),
+                    duration: Duration::from_millis(5),
+                    in_corpus: true,
+                },
+                SlowInput {
+                    path: dir.path().join("aaa"),
+                    duration: Duration::from_millis(3),
+                    in_corpus: false,
+                },
+            ]
+        );
+        let inputs = read(dir.path(), 10, corpus.path()).unwrap();
+        assert_eq!(inputs[2].duration, Duration::from_micros(2));
+    }
+}
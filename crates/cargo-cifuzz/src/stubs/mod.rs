@@ -0,0 +1,200 @@
+//! Templates for new fuzz tests.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+const FUZZ_TEST_STUB: &str = include_str!("fuzz_test.rs.tmpl");
+
+/// The files which can declare the modules of the files in their
+/// directory.
+const PARENT_MODULE_FILES: &[&str] = &["mod.rs", "lib.rs", "main.rs"];
+
+/// Creates a fuzz test stub at the given path. The name of the fuzz test
+/// is the file stem, which must be a valid Rust identifier.
+pub fn create(path: &Path) -> Result<()> {
+    let name = module_name(path)?;
+    let content = FUZZ_TEST_STUB.replace("__FUZZ_TEST_NAME__", name);
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?;
+    file.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+/// Returns a proposal for the path of a new fuzz test in the given
+/// directory, which doesn't exist yet.
+pub fn fuzz_test_filename(dir: &Path) -> PathBuf {
+    (1..)
+        .map(|counter| dir.join(format!("my_fuzz_test_{counter}.rs")))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+/// Returns the name of the module which a file defines.
+pub fn module_name(path: &Path) -> Result<&str> {
+    let name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .with_context(|| format!("invalid file name {}", path.display()))?;
+    if !is_identifier(name) || path.extension().is_none_or(|ext| ext != "rs") {
+        bail!(
+            "Invalid file name {}: The fuzz test must be a .rs file whose name is a valid Rust identifier",
+            path.display()
+        );
+    }
+    Ok(name)
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+        && s != "_"
+}
+
+/// Returns the file which must declare the module defined by the file
+/// at the given path, if there is one.
+pub fn parent_module(path: &Path) -> Option<PathBuf> {
+    let dir = path.parent()?;
+    PARENT_MODULE_FILES
+        .iter()
+        .map(|file| dir.join(file))
+        .chain(std::iter::once(dir.with_extension("rs")))
+        .find(|file| file.is_file() && file != path)
+}
+
+/// Declares the test module `name` in the parent module file, after the
+/// last module declaration, and returns whether it was added. Nothing
+/// is changed if the module is already declared.
+pub fn register_module(parent: &Path, name: &str) -> Result<bool> {
+    register_module_at(parent, name, None)
+}
+
+/// Declares the test module `name` like [`register_module`], with a
+/// `#[path]` attribute if its file isn't where rustc looks for it.
+pub fn register_module_at(parent: &Path, name: &str, path: Option<&str>) -> Result<bool> {
+    let content = std::fs::read_to_string(parent)
+        .with_context(|| format!("failed to read {}", parent.display()))?;
+    let Some(content) = add_module_declaration(&content, name, path) else {
+        return Ok(false);
+    };
+    std::fs::write(parent, content)
+        .with_context(|| format!("failed to write {}", parent.display()))?;
+    Ok(true)
+}
+
+fn add_module_declaration(content: &str, name: &str, path: Option<&str>) -> Option<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    if lines.iter().any(|l| module_declaration(l) == Some(name)) {
+        return None;
+    }
+
+    // Insert after the last module declaration or, if there is none,
+    // after the inner doc comments and attributes at the top of the file
+    let index = match lines.iter().rposition(|l| module_declaration(l).is_some()) {
+        Some(i) => i + 1,
+        None => lines
+            .iter()
+            .take_while(|l| l.starts_with("//!") || l.starts_with("#!["))
+            .count(),
+    };
+
+    let mut new_lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+    let mut declaration = vec!["#[cfg(test)]".to_string()];
+    if let Some(path) = path {
+        declaration.push(format!("#[path = {path:?}]"));
+    }
+    declaration.push(format!("mod {name};"));
+    if index == 0 && !lines.is_empty() {
+        declaration.push(String::new());
+    } else if index > 0 && module_declaration(lines[index - 1]).is_none() {
+        declaration.insert(0, String::new());
+    }
+    new_lines.splice(index..index, declaration);
+
+    let mut content = new_lines.join("\n");
+    content.push('\n');
+    Some(content)
+}
+
+/// Returns the module name if the line is a declaration of a module
+/// in another file like `pub(crate) mod foo;`.
+fn module_declaration(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let line = match line.strip_prefix("pub") {
+        Some(rest) if rest.starts_with('(') => rest.split_once(')')?.1.trim_start(),
+        Some(rest) => rest.trim_start(),
+        None => line,
+    };
+    let name = line.strip_prefix("mod ")?.trim().strip_suffix(';')?;
+    is_identifier(name).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_stub() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = fuzz_test_filename(dir.path());
+        assert_eq!(path, dir.path().join("my_fuzz_test_1.rs"));
+        create(&path).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains("fn my_fuzz_test_1(fdp: &mut FuzzedDataProvider)"));
+
+        assert_eq!(
+            fuzz_test_filename(dir.path()),
+            dir.path().join("my_fuzz_test_2.rs")
+        );
+        // Existing files are not overwritten
+        assert!(create(&path).is_err());
+        assert!(create(&dir.path().join("my-fuzz-test.rs")).is_err());
+    }
+
+    #[test]
+    fn module_declarations() {
+        assert_eq!(module_declaration("mod foo;"), Some("foo"));
+        assert_eq!(module_declaration("pub mod foo;"), Some("foo"));
+        assert_eq!(module_declaration("  pub(crate) mod foo;"), Some("foo"));
+        assert_eq!(module_declaration("mod foo {"), None);
+        assert_eq!(module_declaration("// mod foo;"), None);
+    }
+
+    #[test]
+    fn add_declaration_after_modules() {
+        let content = "mod explore_me;\n#[cfg(test)]\nmod my_fuzz_test;\n\nfn main() {}\n";
+        assert_eq!(
+            add_module_declaration(content, "parse_fuzz_test", None).unwrap(),
+            "mod explore_me;\n#[cfg(test)]\nmod my_fuzz_test;\n\
+             #[cfg(test)]\nmod parse_fuzz_test;\n\nfn main() {}\n"
+        );
+        assert_eq!(add_module_declaration(content, "my_fuzz_test", None), None);
+    }
+
+    #[test]
+    fn add_declaration_without_modules() {
+        assert_eq!(
+            add_module_declaration("//! Docs\n\nfn main() {}\n", "t", None).unwrap(),
+            "//! Docs\n\n#[cfg(test)]\nmod t;\n\nfn main() {}\n"
+        );
+        assert_eq!(
+            add_module_declaration("fn main() {}\n", "t", None).unwrap(),
+            "#[cfg(test)]\nmod t;\n\nfn main() {}\n"
+        );
+        assert_eq!(
+            add_module_declaration("", "t", None).unwrap(),
+            "#[cfg(test)]\nmod t;\n"
+        );
+        assert_eq!(
+            add_module_declaration("fn main() {}\n", "t", Some("tool/t.rs")).unwrap(),
+            "#[cfg(test)]\n#[path = \"tool/t.rs\"]\nmod t;\n\nfn main() {}\n"
+        );
+    }
+}
@@ -0,0 +1,65 @@
+//! Symbolization of the frames of stack traces.
+//!
+//! The standard library prints demangled functions and their locations,
+//! but the sanitizers rely on llvm-symbolizer, without which their
+//! frames only have the binary and offset, and print the functions of
+//! the legacy mangling scheme with their hash like
+//! `cargo_example::explore_me::h5d2b6140f0efc0bf`.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::coverage;
+
+/// Returns the path of llvm-symbolizer, preferring the one of the
+/// llvm-tools rustup component, or `None` if it's not installed.
+pub fn llvm_symbolizer() -> Option<&'static Path> {
+    static SYMBOLIZER: OnceLock<Option<PathBuf>> = OnceLock::new();
+    SYMBOLIZER
+        .get_or_init(|| coverage::llvm_tool("llvm-symbolizer").ok())
+        .as_deref()
+}
+
+/// Demangles the function of a frame. Mangled symbols like
+/// `_ZN13cargo_example10explore_me17h5d2b6140f0efc0bfE` are demangled
+/// and the hash of demangled legacy symbols is removed, other functions
+/// are returned unchanged.
+pub fn demangle(function: &str) -> String {
+    if let Ok(symbol) = rustc_demangle::try_demangle(function) {
+        // The alternate format omits the hash
+        return format!("{symbol:#}");
+    }
+    match function.rsplit_once("::h") {
+        Some((path, hash)) if hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            path.to_string()
+        }
+        _ => function.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demangled_functions() {
+        assert_eq!(
+            demangle("_ZN13cargo_example10explore_me10explore_me17h5d2b6140f0efc0bfE"),
+            "cargo_example::explore_me::explore_me"
+        );
+        assert_eq!(
+            demangle("_RNvNtCs1234_13cargo_example10explore_me10explore_me"),
+            "cargo_example::explore_me::explore_me"
+        );
+        assert_eq!(
+            demangle("cargo_example::explore_me::explore_me::h5d2b6140f0efc0bf"),
+            "cargo_example::explore_me::explore_me"
+        );
+        assert_eq!(
+            demangle("<alloc::vec::Vec<u8> as core::clone::Clone>::clone"),
+            "<alloc::vec::Vec<u8> as core::clone::Clone>::clone"
+        );
+        assert_eq!(demangle("malloc"), "malloc");
+        assert_eq!(demangle("foo::hash"), "foo::hash");
+    }
+}
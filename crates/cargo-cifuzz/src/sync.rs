@@ -0,0 +1,369 @@
+//! Synchronizing the generated corpus of a fuzz test with a remote
+//! corpus, which CI jobs and developers share.
+//!
+//! The remote corpus of a fuzz test is the `<fuzz_test>` directory of
+//! the remote, which is one of
+//!
+//! ```text
+//! s3://my-bucket/corpus       synced with the AWS CLI (`aws s3`)
+//! gs://my-bucket/corpus       synced with the Google Cloud CLI (`gcloud storage`)
+//! https://example.com/corpus  downloaded with GET and uploaded with PUT by curl
+//! /mnt/shared/corpus          a directory, e.g. on a network file system
+//! ```
+//!
+//! The inputs are named after the SHA-1 of their content, like libFuzzer
+//! names the inputs it generates, so the same input always has the same
+//! name and inputs pushed by different runs never conflict. Inputs are
+//! only added, never removed or overwritten. Over HTTP, the names of the
+//! inputs are taken from the listing which the server returns for the
+//! directory, e.g. the index page of nginx's autoindex.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+
+use crate::corpus;
+use crate::log;
+
+/// A remote corpus.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Remote {
+    S3(String),
+    Gcs(String),
+    Http(String),
+    Dir(PathBuf),
+}
+
+impl Remote {
+    /// Parses the URL of a remote corpus. Directories are relative to
+    /// the project directory.
+    pub fn parse(url: &str, project_dir: &Path) -> Result<Remote> {
+        let trimmed = url.trim_end_matches('/').to_string();
+        Ok(match url.split_once("://") {
+            Some(("s3", _)) => Remote::S3(trimmed),
+            Some(("gs", _)) => Remote::Gcs(trimmed),
+            Some(("http" | "https", _)) => Remote::Http(trimmed),
+            Some(("file", path)) => Remote::Dir(project_dir.join(path)),
+            Some((scheme, _)) => bail!(
+                "Unsupported corpus remote {url:?}, the scheme {scheme} is none of s3, gs, \
+                 http, https and file"
+            ),
+            None => Remote::Dir(project_dir.join(url)),
+        })
+    }
+
+    /// The URL of the remote corpus of the fuzz test.
+    fn url(&self, fuzz_test: &str) -> String {
+        match self {
+            Remote::S3(url) | Remote::Gcs(url) | Remote::Http(url) => format!("{url}/{fuzz_test}"),
+            Remote::Dir(dir) => dir.join(fuzz_test).display().to_string(),
+        }
+    }
+
+    /// Downloads the inputs of the remote corpus of the fuzz test which
+    /// are missing in the directory. Returns the number of downloaded
+    /// inputs.
+    pub fn pull(&self, fuzz_test: &str, dir: &Path) -> Result<usize> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+        let before = count_inputs(dir)?;
+        let url = self.url(fuzz_test);
+        match self {
+            Remote::S3(_) => {
+                // Inputs with the same name have the same content
+                run(Command::new("aws")
+                    .args(["s3", "sync", "--only-show-errors", "--size-only"])
+                    .arg(&url)
+                    .arg(dir))?;
+            }
+            Remote::Gcs(_) => {
+                run(Command::new("gcloud")
+                    .args(["storage", "rsync", "--no-clobber"])
+                    .arg(&url)
+                    .arg(dir))?;
+            }
+            Remote::Http(_) => {
+                let missing: Vec<String> = self
+                    .list(fuzz_test)?
+                    .into_iter()
+                    .filter(|name| !dir.join(name).exists())
+                    .collect();
+                // One curl for many inputs reuses the connection
+                for names in missing.chunks(100) {
+                    let mut cmd = curl();
+                    for name in names {
+                        cmd.arg("-o")
+                            .arg(dir.join(name))
+                            .arg(format!("{url}/{name}"));
+                    }
+                    run(&mut cmd)?;
+                }
+            }
+            Remote::Dir(_) => {
+                corpus::import(&corpus::list_inputs(&[PathBuf::from(&url)])?, dir)?;
+            }
+        }
+        Ok(count_inputs(dir)?.saturating_sub(before))
+    }
+
+    /// Uploads the inputs in the directory which are missing in the
+    /// remote corpus of the fuzz test, named after their content. The
+    /// inputs to upload are staged in `staging_dir`. Returns the number
+    /// of uploaded inputs.
+    pub fn push(&self, fuzz_test: &str, dir: &Path, staging_dir: &Path) -> Result<usize> {
+        let remote = self.list(fuzz_test)?;
+        let mut inputs = Vec::new();
+        for input in corpus::list_inputs(&[dir.to_path_buf()])? {
+            let data = std::fs::read(&input)
+                .with_context(|| format!("failed to read {}", input.display()))?;
+            if !remote.contains(&sha1_smol::Sha1::from(&data).digest().to_string()) {
+                inputs.push(input);
+            }
+        }
+        if inputs.is_empty() {
+            return Ok(0);
+        }
+
+        if staging_dir.exists() {
+            std::fs::remove_dir_all(staging_dir)
+                .with_context(|| format!("failed to remove {}", staging_dir.display()))?;
+        }
+        let staged = corpus::import(&inputs, staging_dir)?;
+        let url = self.url(fuzz_test);
+        match self {
+            Remote::S3(_) => {
+                run(Command::new("aws")
+                    .args(["s3", "cp", "--recursive", "--only-show-errors"])
+                    .arg(staging_dir)
+                    .arg(&url))?;
+            }
+            Remote::Gcs(_) => {
+                run(Command::new("gcloud")
+                    .args(["storage", "rsync", "--no-clobber"])
+                    .arg(staging_dir)
+                    .arg(&url))?;
+            }
+            Remote::Http(_) => {
+                let files = corpus::list_inputs(&[staging_dir.to_path_buf()])?;
+                for files in files.chunks(100) {
+                    let mut cmd = curl();
+                    for file in files {
+                        let name = file.file_name().unwrap_or_default().to_string_lossy();
+                        cmd.arg("-T").arg(file).arg(format!("{url}/{name}"));
+                    }
+                    run(&mut cmd)?;
+                }
+            }
+            Remote::Dir(_) => {
+                corpus::import(
+                    &corpus::list_inputs(&[staging_dir.to_path_buf()])?,
+                    Path::new(&url),
+                )?;
+            }
+        }
+        std::fs::remove_dir_all(staging_dir)
+            .with_context(|| format!("failed to remove {}", staging_dir.display()))?;
+        Ok(staged)
+    }
+
+    /// Returns the names of the inputs of the remote corpus of the fuzz
+    /// test, which is empty if it doesn't exist yet.
+    fn list(&self, fuzz_test: &str) -> Result<HashSet<String>> {
+        let url = self.url(fuzz_test);
+        let listing = match self {
+            Remote::S3(_) => {
+                let output = output(
+                    Command::new("aws")
+                        .args(["s3", "ls"])
+                        .arg(format!("{url}/")),
+                )?;
+                // Listing a missing prefix fails without an error message
+                if !output.status.success() && !output.stderr.is_empty() {
+                    bail!(
+                        "aws s3 ls failed: {}",
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    );
+                }
+                output.stdout
+            }
+            Remote::Gcs(_) => {
+                let output = output(
+                    Command::new("gcloud")
+                        .args(["storage", "ls"])
+                        .arg(format!("{url}/")),
+                )?;
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if !output.status.success() && !stderr.contains("matched no objects") {
+                    bail!("gcloud storage ls failed: {}", stderr.trim());
+                }
+                output.stdout
+            }
+            Remote::Http(_) => {
+                let output = output(
+                    Command::new("curl")
+                        .args(["-sSL", "-w", "\n%{http_code}"])
+                        .arg(format!("{url}/")),
+                )?;
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let (body, status) = stdout.rsplit_once('\n').unwrap_or(("", &stdout));
+                match status.trim() {
+                    "404" => Vec::new(),
+                    status if output.status.success() && status.starts_with('2') => {
+                        body.as_bytes().to_vec()
+                    }
+                    status => bail!(
+                        "Failed to list {url}/: HTTP status {status} {}",
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ),
+                }
+            }
+            Remote::Dir(_) => {
+                let dir = PathBuf::from(&url);
+                let mut names = HashSet::new();
+                if dir.is_dir() {
+                    for input in corpus::list_inputs(&[dir])? {
+                        names.insert(
+                            input
+                                .file_name()
+                                .unwrap_or_default()
+                                .to_string_lossy()
+                                .into_owned(),
+                        );
+                    }
+                }
+                return Ok(names);
+            }
+        };
+        Ok(input_names(&String::from_utf8_lossy(&listing)))
+    }
+}
+
+/// Returns the names of the inputs in a listing of a remote corpus,
+/// i.e. the SHA-1 hashes in it, whether it's the output of `aws s3 ls`,
+/// of `gcloud storage ls` or an HTML index page.
+fn input_names(listing: &str) -> HashSet<String> {
+    let re = Regex::new(r"(?:^|[^0-9A-Za-z])([0-9a-f]{40})(?:$|[^0-9A-Za-z])").unwrap();
+    listing
+        .lines()
+        .flat_map(|line| re.captures_iter(line).map(|c| c[1].to_string()))
+        .collect()
+}
+
+/// Returns the directory in which the inputs to push are staged.
+pub fn staging_dir(project_dir: &Path, fuzz_test: &str) -> PathBuf {
+    project_dir
+        .join(".cifuzz-build")
+        .join("sync")
+        .join(fuzz_test)
+}
+
+fn count_inputs(dir: &Path) -> Result<usize> {
+    Ok(corpus::list_inputs(&[dir.to_path_buf()])?.len())
+}
+
+fn curl() -> Command {
+    let mut cmd = Command::new("curl");
+    // Fail on HTTP errors instead of storing the error page
+    cmd.args(["-fsSL", "--create-dirs"]);
+    cmd
+}
+
+fn output(cmd: &mut Command) -> Result<Output> {
+    log::debug!("Command: {:?}", cmd);
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    cmd.output()
+        .with_context(|| format!("failed to execute {program}, which syncs the corpus"))
+}
+
+fn run(cmd: &mut Command) -> Result<()> {
+    let output = output(cmd)?;
+    if !output.status.success() {
+        bail!(
+            "{} failed with {}: {}",
+            cmd.get_program().to_string_lossy(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_remotes() {
+        let project = Path::new("/p");
+        assert_eq!(
+            Remote::parse("s3://bucket/corpus/", project).unwrap(),
+            Remote::S3("s3://bucket/corpus".to_string())
+        );
+        assert_eq!(
+            Remote::parse("gs://bucket", project).unwrap(),
+            Remote::Gcs("gs://bucket".to_string())
+        );
+        assert_eq!(
+            Remote::parse("https://example.com/corpus", project)
+                .unwrap()
+                .url("my_fuzz_test"),
+            "https://example.com/corpus/my_fuzz_test"
+        );
+        assert_eq!(
+            Remote::parse("file:///mnt/corpus", project).unwrap(),
+            Remote::Dir(PathBuf::from("/mnt/corpus"))
+        );
+        assert_eq!(
+            Remote::parse("../corpus", project).unwrap(),
+            Remote::Dir(PathBuf::from("/p/../corpus"))
+        );
+        assert!(Remote::parse("ftp://example.com/corpus", project).is_err());
+    }
+
+    #[test]
+    fn listed_inputs() {
+        let hash = "da39a3ee5e6b4b0d3255bfef95601890afd80709";
+        let other = "0beec7b5ea3f0fdbc95d0dd47f3c5bc275da8a33";
+        let s3 = format!(
+            "                           PRE seeds/\n2024-05-02 10:00:00         12 {hash}\n"
+        );
+        assert_eq!(input_names(&s3), HashSet::from([hash.to_string()]));
+        let gcs = format!(
+            "gs://bucket/corpus/my_fuzz_test/{hash}\ngs://bucket/corpus/my_fuzz_test/{other}\n"
+        );
+        assert_eq!(input_names(&gcs).len(), 2);
+        let html = format!(
+            "<a href=\"../\">../</a>\n<a href=\"{hash}\">{hash}</a> 02-May-2024 10:00 12\n\
+             <a href=\"{hash}0\">{hash}0</a>\n"
+        );
+        assert_eq!(input_names(&html), HashSet::from([hash.to_string()]));
+    }
+
+    #[test]
+    fn sync_with_dir() {
+        let project = tempfile::tempdir().unwrap();
+        let remote = Remote::Dir(project.path().join("remote"));
+        let local = project.path().join("local");
+        let staging = project.path().join("staging");
+        std::fs::create_dir_all(&local).unwrap();
+        std::fs::write(local.join("input"), b"FUZZ").unwrap();
+
+        assert_eq!(remote.push("my_fuzz_test", &local, &staging).unwrap(), 1);
+        assert_eq!(remote.push("my_fuzz_test", &local, &staging).unwrap(), 0);
+        assert!(!staging.exists());
+        let name = sha1_smol::Sha1::from(b"FUZZ").digest().to_string();
+        assert_eq!(
+            remote.list("my_fuzz_test").unwrap(),
+            HashSet::from([name.clone()])
+        );
+
+        let other = project.path().join("other");
+        assert_eq!(remote.pull("my_fuzz_test", &other).unwrap(), 1);
+        assert_eq!(std::fs::read(other.join(&name)).unwrap(), b"FUZZ");
+        assert_eq!(remote.pull("my_fuzz_test", &other).unwrap(), 0);
+        assert_eq!(remote.list("missing").unwrap(), HashSet::new());
+    }
+}
@@ -0,0 +1,180 @@
+//! Annotating an input with the values the `FuzzedDataProvider` decodes
+//! from it, for `cargo cifuzz run --trace`.
+//!
+//! The runtime records every call of the provider which isn't made by
+//! another one, with the regions of the input it consumed and the value
+//! it returned (see crates/cifuzz/src/fdp/trace.rs), and writes them to
+//! the file passed via `CIFUZZ_FDP_CALLS`.
+
+use anyhow::{bail, Result};
+
+use crate::minimize::{self, Region};
+
+/// The number of bytes of a call which are printed, longer values are
+/// shortened.
+const MAX_PRINTED_BYTES: usize = 8;
+
+/// A call of the `FuzzedDataProvider`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    /// The regions of the input consumed by the call
+    pub regions: Vec<Region>,
+    /// The method with its type and arguments, e.g.
+    /// `consume_int::<u32>()`
+    pub method: String,
+    /// The returned value, formatted with `Debug`
+    pub value: String,
+}
+
+/// Parses the calls written by the runtime, one per line as
+/// `<regions>\t<method>\t<value>`, with the regions formatted like in
+/// the trace of [`minimize::parse_trace`] and separated by commas.
+pub fn parse_calls(calls: &str) -> Result<Vec<Call>> {
+    calls
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let (Some(regions), Some(method), Some(value)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                bail!("invalid call {line:?}");
+            };
+            Ok(Call {
+                regions: minimize::parse_trace(&regions.replace(',', "\n"))?,
+                method: method.to_string(),
+                value: value.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Formats the calls as a table with the offsets and the bytes of the
+/// input they consumed, e.g.
+///
+/// ```text
+/// OFFSET  BYTES                       CALL                  VALUE
+/// 11..15  00 94 35 77                 consume_int::<u32>()  2000000000
+/// 0..9    46 55 5a 5a 49 4e 47 5c ..  consume_string(100)   "FUZZING"
+/// ```
+pub fn format(calls: &[Call], input: &[u8]) -> String {
+    let rows: Vec<[String; 4]> = calls
+        .iter()
+        .map(|call| {
+            let offsets: Vec<String> = call
+                .regions
+                .iter()
+                .map(|r| format!("{}..{}", r.start, r.start + r.len))
+                .collect();
+            let bytes: Vec<u8> = call
+                .regions
+                .iter()
+                .flat_map(|r| input.get(r.start..r.start + r.len).unwrap_or_default())
+                .copied()
+                .collect();
+            [
+                if offsets.is_empty() {
+                    "-".to_string()
+                } else {
+                    offsets.join(",")
+                },
+                format_bytes(&bytes),
+                call.method.clone(),
+                call.value.clone(),
+            ]
+        })
+        .collect();
+
+    let header = ["OFFSET", "BYTES", "CALL", "VALUE"];
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let format_row = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    let mut table = format_row(header.to_vec()) + "\n";
+    for row in &rows {
+        table += &format_row(row.iter().map(String::as_str).collect());
+        table.push('\n');
+    }
+    table
+}
+
+/// Returns the number of bytes of the input which no call consumed.
+pub fn unconsumed_bytes(calls: &[Call], input: &[u8]) -> usize {
+    let consumed: usize = calls
+        .iter()
+        .flat_map(|call| &call.regions)
+        .map(|r| r.len)
+        .sum();
+    input.len().saturating_sub(consumed)
+}
+
+/// Formats bytes in hex, shortened to [`MAX_PRINTED_BYTES`].
+fn format_bytes(bytes: &[u8]) -> String {
+    let mut hex: Vec<String> = bytes
+        .iter()
+        .take(MAX_PRINTED_BYTES)
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    if bytes.len() > MAX_PRINTED_BYTES {
+        hex.push("..".to_string());
+    }
+    hex.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_calls() {
+        // The calls recorded by the runtime for a fuzz test which
+        // consumes an u32, a string and three bools from the input
+        let calls = parse_calls(
+            "back 11 4\tconsume_int::<u32>()\t2000000000\n\
+             front 0 9\tconsume_string(100)\t\"FUZZING\"\n\
+             back 10 1\tconsume_bool()\ttrue\n\
+             back 9 1\tconsume_bool()\ttrue\n\
+             \tconsume_bool()\tfalse\n",
+        )
+        .unwrap();
+        assert_eq!(
+            calls[0],
+            Call {
+                regions: vec![Region {
+                    start: 11,
+                    len: 4,
+                    from_end: true,
+                }],
+                method: "consume_int::<u32>()".to_string(),
+                value: "2000000000".to_string(),
+            }
+        );
+        assert!(calls[4].regions.is_empty());
+
+        let input = b"FUZZING\\x??\x00\x94\x35\x77";
+        assert_eq!(
+            format(&calls, input),
+            "OFFSET  BYTES                       CALL                  VALUE\n\
+             11..15  00 94 35 77                 consume_int::<u32>()  2000000000\n\
+             0..9    46 55 5a 5a 49 4e 47 5c ..  consume_string(100)   \"FUZZING\"\n\
+             10..11  3f                          consume_bool()        true\n\
+             9..10   3f                          consume_bool()        true\n\
+             -                                   consume_bool()        false\n"
+        );
+        assert_eq!(unconsumed_bytes(&calls, input), 0);
+        assert_eq!(unconsumed_bytes(&calls[..2], input), 2);
+        assert!(parse_calls("front 0 8\tconsume_string(100)").is_err());
+    }
+}
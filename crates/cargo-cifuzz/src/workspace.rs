@@ -0,0 +1,276 @@
+//! Inspecting and editing the cargo workspace of a project.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use toml_edit::{DocumentMut, InlineTable, Item, Table};
+
+use crate::build::cargo;
+
+/// The git repository from which the cifuzz crates are fetched by
+/// default.
+pub const CIFUZZ_GIT_URL: &str = "https://github.com/CodeIntelligenceTesting/cifuzz";
+
+/// The packages of the cifuzz crates themselves, which must not depend
+/// on the runtime.
+const CIFUZZ_PACKAGES: &[&str] = &["cifuzz", "cifuzz-macros"];
+
+#[derive(Debug)]
+pub struct Workspace {
+    pub root: PathBuf,
+    pub members: Vec<Member>,
+}
+
+#[derive(Debug)]
+pub struct Member {
+    pub name: String,
+    pub manifest_path: PathBuf,
+    pub bins: Vec<Bin>,
+}
+
+/// A binary target of a member.
+#[derive(Debug)]
+pub struct Bin {
+    pub name: String,
+    /// The root file of the binary, e.g. src/main.rs
+    pub src_path: PathBuf,
+}
+
+/// Where the cifuzz dependency is taken from.
+#[derive(Debug, Clone)]
+pub enum CifuzzSource {
+    Git(String),
+    Path(PathBuf),
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    packages: Vec<Package>,
+    workspace_members: Vec<String>,
+    workspace_root: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    id: String,
+    name: String,
+    manifest_path: PathBuf,
+    #[serde(default)]
+    targets: Vec<Target>,
+}
+
+#[derive(Deserialize)]
+struct Target {
+    name: String,
+    kind: Vec<String>,
+    src_path: PathBuf,
+}
+
+impl Workspace {
+    /// Loads the workspace containing the given directory via
+    /// `cargo metadata`.
+    pub fn load(dir: &Path) -> Result<Workspace> {
+        let output = Command::new(cargo())
+            .args(["metadata", "--no-deps", "--format-version", "1"])
+            .current_dir(dir)
+            .output()
+            .context("failed to execute cargo")?;
+        if !output.status.success() {
+            bail!(
+                "Failed to find a cargo workspace in {}: {}",
+                dir.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let metadata: Metadata =
+            serde_json::from_slice(&output.stdout).context("failed to parse cargo metadata")?;
+        Ok(Workspace::from_metadata(metadata))
+    }
+
+    fn from_metadata(metadata: Metadata) -> Workspace {
+        let members = metadata
+            .packages
+            .into_iter()
+            .filter(|p| metadata.workspace_members.contains(&p.id))
+            .map(|p| Member {
+                name: p.name,
+                manifest_path: p.manifest_path,
+                bins: p
+                    .targets
+                    .into_iter()
+                    .filter(|t| t.kind.iter().any(|kind| kind == "bin"))
+                    .map(|t| Bin {
+                        name: t.name,
+                        src_path: t.src_path,
+                    })
+                    .collect(),
+            })
+            .collect();
+        Workspace {
+            root: metadata.workspace_root,
+            members,
+        }
+    }
+
+    /// Adds the cifuzz crate as a dev-dependency to all members of the
+    /// workspace which don't depend on it already and returns the names
+    /// of the modified members.
+    pub fn add_cifuzz_dev_dependency(&self, source: &CifuzzSource) -> Result<Vec<String>> {
+        let root_manifest = self.root.join("Cargo.toml");
+        let root_manifest = std::fs::read_to_string(&root_manifest)
+            .with_context(|| format!("failed to read {}", root_manifest.display()))?;
+        let inherit = has_workspace_dependency(&root_manifest)?;
+
+        let mut modified = Vec::new();
+        for member in &self.members {
+            if CIFUZZ_PACKAGES.contains(&member.name.as_str()) {
+                continue;
+            }
+            let path = &member.manifest_path;
+            let manifest = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let Some(manifest) = add_dev_dependency(&manifest, source, inherit)
+                .with_context(|| format!("failed to edit {}", path.display()))?
+            else {
+                continue;
+            };
+            std::fs::write(path, manifest)
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            modified.push(member.name.clone());
+        }
+        Ok(modified)
+    }
+}
+
+/// Checks whether the cifuzz crate is declared in the
+/// `[workspace.dependencies]` of the root manifest, in which case
+/// members inherit it.
+fn has_workspace_dependency(root_manifest: &str) -> Result<bool> {
+    let doc: DocumentMut = root_manifest.parse()?;
+    Ok(doc
+        .get("workspace")
+        .and_then(|w| w.get("dependencies"))
+        .and_then(|d| d.get("cifuzz"))
+        .is_some())
+}
+
+/// Adds the cifuzz dev-dependency to the manifest, preserving its
+/// formatting. Returns `None` if the package already depends on cifuzz.
+fn add_dev_dependency(
+    manifest: &str,
+    source: &CifuzzSource,
+    inherit: bool,
+) -> Result<Option<String>> {
+    let mut doc: DocumentMut = manifest.parse()?;
+    let depends_on_cifuzz = ["dependencies", "dev-dependencies"]
+        .iter()
+        .any(|table| doc.get(table).and_then(|t| t.get("cifuzz")).is_some());
+    if depends_on_cifuzz {
+        return Ok(None);
+    }
+
+    let mut dependency = InlineTable::new();
+    if inherit {
+        dependency.insert("workspace", true.into());
+    } else {
+        match source {
+            CifuzzSource::Git(url) => dependency.insert("git", url.as_str().into()),
+            CifuzzSource::Path(path) => {
+                dependency.insert("path", path.to_string_lossy().as_ref().into())
+            }
+        };
+    }
+
+    let dev_dependencies = doc
+        .entry("dev-dependencies")
+        .or_insert_with(|| Item::Table(Table::new()))
+        .as_table_like_mut()
+        .context("dev-dependencies is not a table")?;
+    dev_dependencies.insert("cifuzz", Item::Value(dependency.into()));
+    Ok(Some(doc.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git_source() -> CifuzzSource {
+        CifuzzSource::Git(CIFUZZ_GIT_URL.to_string())
+    }
+
+    #[test]
+    fn add_dev_dependency_preserves_manifest() {
+        let manifest = "[package]\nname = \"foo\" # the name\n\n\
+                        [dev-dependencies]\nserde = \"1\"\n";
+        let manifest = add_dev_dependency(manifest, &git_source(), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            manifest,
+            format!(
+                "[package]\nname = \"foo\" # the name\n\n\
+                 [dev-dependencies]\nserde = \"1\"\ncifuzz = {{ git = \"{CIFUZZ_GIT_URL}\" }}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn add_dev_dependency_variants() {
+        let manifest = "[package]\nname = \"foo\"\n";
+        let source = CifuzzSource::Path(PathBuf::from("/cifuzz/crates/cifuzz"));
+        let with_path = add_dev_dependency(manifest, &source, false)
+            .unwrap()
+            .unwrap();
+        assert!(
+            with_path
+                .ends_with("[dev-dependencies]\ncifuzz = { path = \"/cifuzz/crates/cifuzz\" }\n"),
+            "{with_path}"
+        );
+
+        let inherited = add_dev_dependency(manifest, &source, true)
+            .unwrap()
+            .unwrap();
+        assert!(
+            inherited.ends_with("[dev-dependencies]\ncifuzz = { workspace = true }\n"),
+            "{inherited}"
+        );
+    }
+
+    #[test]
+    fn add_dev_dependency_skips_existing() {
+        for manifest in [
+            "[package]\nname = \"foo\"\n[dev-dependencies]\ncifuzz = \"0.1\"\n",
+            "[package]\nname = \"foo\"\n[dependencies.cifuzz]\npath = \"../cifuzz\"\n",
+        ] {
+            assert_eq!(
+                add_dev_dependency(manifest, &git_source(), false).unwrap(),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn workspace_from_metadata() {
+        let metadata = r#"{
+            "packages": [
+                {"id": "a 0.1.0", "name": "a", "manifest_path": "/ws/a/Cargo.toml", "targets": [
+                    {"name": "a", "kind": ["lib"], "src_path": "/ws/a/src/lib.rs"},
+                    {"name": "a-cli", "kind": ["bin"], "src_path": "/ws/a/src/main.rs"}
+                ]},
+                {"id": "b 0.1.0", "name": "b", "manifest_path": "/ws/b/Cargo.toml"}
+            ],
+            "workspace_members": ["a 0.1.0"],
+            "workspace_root": "/ws"
+        }"#;
+        let workspace = Workspace::from_metadata(serde_json::from_str(metadata).unwrap());
+        assert_eq!(workspace.root, Path::new("/ws"));
+        assert_eq!(workspace.members.len(), 1);
+        assert_eq!(workspace.members[0].name, "a");
+        let bins = &workspace.members[0].bins;
+        assert_eq!(bins.len(), 1);
+        assert_eq!(bins[0].name, "a-cli");
+        assert_eq!(bins[0].src_path, Path::new("/ws/a/src/main.rs"));
+    }
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use common::{cargo_cifuzz, copy_example};
+
+#[test]
+#[ignore = "builds the cargo example with libFuzzer, run with --ignored"]
+fn fuzz_command_line_of_example() {
+    let dir = copy_example();
+
+    let output = cargo_cifuzz(dir.path(), &["generate", "--bin"]);
+    assert!(output.status.success());
+    let src = dir.path().join("src");
+    let fuzz_test = std::fs::read_to_string(src.join("cargo_example_cli_fuzz_test.rs")).unwrap();
+    assert!(
+        fuzz_test.contains("::cifuzz::cli::args().skip(1)"),
+        "{fuzz_test}"
+    );
+    assert!(fuzz_test.contains("::cifuzz::cli::exit(2)"), "{fuzz_test}");
+    let main = std::fs::read_to_string(src.join("main.rs")).unwrap();
+    assert!(main.contains("#[cfg(test)]\nmod cargo_example_cli_fuzz_test;"));
+
+    // A seed which invokes the tool with the arguments 20001 3000000
+    // FUZZING: The strings are taken from the front of the input and
+    // terminated by a backslash and a space, their number from the end.
+    // The inputs which make the tool exit aren't findings.
+    let seeds = dir
+        .path()
+        .join("corpus")
+        .join("cargo_example_cli_fuzz_test");
+    std::fs::create_dir_all(&seeds).unwrap();
+    std::fs::write(seeds.join("branch-4"), b"20001\\ 3000000\\ FUZZING\x03").unwrap();
+    let output = cargo_cifuzz(
+        dir.path(),
+        &["run", "cargo_example_cli_fuzz_test", "--timeout", "10m"],
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("branch 4 has been reached"), "{stderr}");
+}
@@ -0,0 +1,62 @@
+//! Helpers for the integration tests, which run cargo-cifuzz on a copy
+//! of the cargo example.
+
+// Not every test crate uses every helper
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+pub fn repo_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../..")
+        .canonicalize()
+        .unwrap()
+}
+
+/// Copies the cargo example to a temporary directory, so that the
+/// files created by cargo-cifuzz don't end up in the repository.
+pub fn copy_example() -> TempDir {
+    let src = repo_dir().join("examples").join("cargo");
+    let dir = tempfile::tempdir().unwrap();
+    copy_dir(&src, dir.path());
+
+    // Use an absolute path to the cifuzz crate and make the example a
+    // workspace of its own
+    let manifest_path = dir.path().join("Cargo.toml");
+    let manifest = std::fs::read_to_string(&manifest_path).unwrap();
+    let cifuzz_path = repo_dir().join("crates").join("cifuzz");
+    let manifest = manifest.replace("../../crates/cifuzz", cifuzz_path.to_str().unwrap());
+    std::fs::write(&manifest_path, format!("{manifest}\n[workspace]\n")).unwrap();
+    dir
+}
+
+fn copy_dir(src: &Path, dst: &Path) {
+    std::fs::create_dir_all(dst).unwrap();
+    for entry in std::fs::read_dir(src).unwrap() {
+        let entry = entry.unwrap();
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with(".cifuzz-") || name == "target" {
+            continue;
+        }
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &dst.join(name));
+        } else {
+            std::fs::copy(entry.path(), dst.join(name)).unwrap();
+        }
+    }
+}
+
+/// Runs `cargo cifuzz <args>` in the given directory.
+pub fn cargo_cifuzz(dir: &Path, args: &[&str]) -> Output {
+    let output = Command::new(env!("CARGO_BIN_EXE_cargo-cifuzz"))
+        .arg("cifuzz")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    eprintln!("{}", String::from_utf8_lossy(&output.stderr));
+    output
+}
@@ -0,0 +1,36 @@
+mod common;
+
+use common::{cargo_cifuzz, copy_example};
+
+#[test]
+#[ignore = "requires the llvm-tools rustup component, run with --ignored"]
+fn coverage_of_example() {
+    let dir = copy_example();
+    let corpus = dir.path().join(".cifuzz-corpus").join("my_fuzz_test");
+    std::fs::create_dir_all(&corpus).unwrap();
+    std::fs::write(
+        corpus.join("input"),
+        b"FUZZ\x00\x00\x00\x00\x00\x00\x00\x80",
+    )
+    .unwrap();
+
+    let output = cargo_cifuzz(dir.path(), &["coverage", "my_fuzz_test"]);
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("src/explore_me.rs"), "{stderr}");
+    let reports = dir.path().join(".cifuzz-coverage").join("my_fuzz_test");
+    let lcov = std::fs::read_to_string(reports.join("coverage.lcov")).unwrap();
+    assert!(lcov.contains("explore_me.rs"), "{lcov}");
+    // The closure of the harness decoding the parameters is attributed to
+    // the fuzz test instead of the #[fuzz_test] attribute
+    let fuzz_test_record = lcov
+        .split("end_of_record")
+        .find(|record| record.contains("my_fuzz_test.rs"))
+        .unwrap();
+    assert!(
+        fuzz_test_record.contains("test_one_input"),
+        "{fuzz_test_record}"
+    );
+    assert!(reports.join("html").join("index.html").is_file());
+}
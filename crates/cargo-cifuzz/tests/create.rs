@@ -0,0 +1,45 @@
+mod common;
+
+use std::process::Command;
+
+use common::{cargo_cifuzz, copy_example};
+
+#[test]
+fn create_compiles_as_unit_test() {
+    let dir = copy_example();
+    let src = dir.path().join("src");
+
+    let output = cargo_cifuzz(&src, &["create"]);
+    assert!(output.status.success());
+    assert!(src.join("my_fuzz_test_1.rs").is_file());
+    let main = std::fs::read_to_string(src.join("main.rs")).unwrap();
+    assert!(
+        main.contains("mod my_fuzz_test;\n#[cfg(test)]\nmod my_fuzz_test_1;\n"),
+        "{main}"
+    );
+
+    // The stub is a regular unit test which is run with the empty input
+    let output = Command::new(env!("CARGO"))
+        .args(["test", "my_fuzz_test_1"])
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("my_fuzz_test_1::regression ... ok"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn create_with_invalid_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = cargo_cifuzz(dir.path(), &["create", "-o", "my-fuzz-test.rs"]);
+    assert!(!output.status.success());
+    assert!(!dir.path().join("my-fuzz-test.rs").exists());
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use common::{cargo_cifuzz, repo_dir};
+
+#[test]
+fn init_workspace() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("Cargo.toml"),
+        "[workspace]\nmembers = [\"a\", \"b\"]\n",
+    )
+    .unwrap();
+    for member in ["a", "b"] {
+        let src = dir.path().join(member).join("src");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::write(src.join("lib.rs"), "").unwrap();
+    }
+    std::fs::write(
+        dir.path().join("a").join("Cargo.toml"),
+        "[package]\nname = \"a\"\nversion = \"0.1.0\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("b").join("Cargo.toml"),
+        "[package]\nname = \"b\"\nversion = \"0.1.0\"\n\n\
+         [dev-dependencies]\ncifuzz = \"0.1\"\n",
+    )
+    .unwrap();
+
+    let cifuzz_path = repo_dir().join("crates").join("cifuzz");
+    let output = cargo_cifuzz(
+        &dir.path().join("b"),
+        &["init", "--cifuzz-path", cifuzz_path.to_str().unwrap()],
+    );
+    assert!(output.status.success());
+    assert!(dir.path().join("cifuzz.yaml").is_file());
+
+    let manifest = std::fs::read_to_string(dir.path().join("a").join("Cargo.toml")).unwrap();
+    assert!(
+        manifest.contains(&format!("cifuzz = {{ path = {:?} }}", cifuzz_path)),
+        "{manifest}"
+    );
+    // Existing dependencies are kept
+    let manifest = std::fs::read_to_string(dir.path().join("b").join("Cargo.toml")).unwrap();
+    assert!(manifest.ends_with("cifuzz = \"0.1\"\n"), "{manifest}");
+
+    // A project can only be initialized once
+    let output = cargo_cifuzz(dir.path(), &["init"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Config already exists"));
+}
+
+#[test]
+fn init_outside_of_workspace() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = cargo_cifuzz(dir.path(), &["init"]);
+    assert!(!output.status.success());
+    assert!(!dir.path().join("cifuzz.yaml").exists());
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use common::{cargo_cifuzz, copy_example};
+
+const FUZZ_TEST: &str = r#"use cifuzz::{fuzz_test, FuzzedDataProvider};
+
+#[fuzz_test]
+fn minimize_fuzz_test(fdp: &mut FuzzedDataProvider) {
+    let _prefix = fdp.consume_string(100);
+    let _n: u8 = fdp.consume_int();
+    let word = fdp.consume_string(100);
+    if word.contains("FUZZ") {
+        panic!("found FUZZ");
+    }
+}
+"#;
+
+#[test]
+#[ignore = "builds the cargo example with libFuzzer, run with --ignored"]
+fn minimize_crashing_input() {
+    let dir = copy_example();
+    let src = dir.path().join("src");
+    std::fs::write(src.join("minimize_fuzz_test.rs"), FUZZ_TEST).unwrap();
+    let main = std::fs::read_to_string(src.join("main.rs")).unwrap();
+    std::fs::write(
+        src.join("main.rs"),
+        format!("#[cfg(test)]\nmod minimize_fuzz_test;\n{main}"),
+    )
+    .unwrap();
+
+    let input = dir.path().join("crash");
+    std::fs::write(&input, b"some prefix\\-xxFUZZyy\\-unused bytes\x2a").unwrap();
+    let output = cargo_cifuzz(
+        dir.path(),
+        &["minimize", "minimize_fuzz_test", input.to_str().unwrap()],
+    );
+    assert!(output.status.success());
+
+    let minimized = std::fs::read(dir.path().join("crash-minimized")).unwrap();
+    // Only the terminator of the prefix, the word and the integer are left
+    assert_eq!(minimized, b"\\-FUZZ\x00");
+}
+
+#[test]
+fn minimize_missing_input() {
+    let dir = copy_example();
+    let output = cargo_cifuzz(dir.path(), &["minimize", "my_fuzz_test", "does-not-exist"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("neither a file nor a finding"), "{stderr}");
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use std::process::Command;
+
+use common::copy_example;
+
+/// An input for which the fuzz test of the example reaches the panic in
+/// explore_me: The integers are consumed from the end of the input.
+fn crashing_input() -> Vec<u8> {
+    let a: i64 = 30_000;
+    let b: i64 = 3_000_000;
+    let mut input = b"FUZZING".to_vec();
+    for n in [b, a] {
+        let bits = (n as u64).wrapping_sub(i64::MIN as u64);
+        input.extend(bits.to_le_bytes());
+    }
+    input
+}
+
+#[test]
+fn regression_test_replays_inputs() {
+    let dir = copy_example();
+    let inputs_dir = dir.path().join("src").join("my_fuzz_test_inputs");
+    std::fs::create_dir_all(&inputs_dir).unwrap();
+    std::fs::write(inputs_dir.join("harmless"), b"FUZZ").unwrap();
+
+    let cargo_test = || {
+        Command::new(env!("CARGO"))
+            .args(["test", "my_fuzz_test", "--", "--nocapture"])
+            .current_dir(dir.path())
+            .output()
+            .unwrap()
+    };
+
+    let output = cargo_test();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(
+        stdout.contains("Running src/my_fuzz_test_inputs/harmless"),
+        "{stdout}"
+    );
+
+    // A crashing input in the findings directory makes the test fail
+    let finding_dir = dir.path().join(".cifuzz/findings/my_fuzz_test/1234");
+    std::fs::create_dir_all(&finding_dir).unwrap();
+    std::fs::write(finding_dir.join("crashing-input"), crashing_input()).unwrap();
+
+    let output = cargo_test();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("branch 4 has been reached"), "{stderr}");
+    assert!(
+        stderr.contains("The fuzz test my_fuzz_test failed for 1 of 3 inputs"),
+        "{stderr}"
+    );
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+
+use common::{cargo_cifuzz, copy_example};
+
+const FUZZ_TEST: &str = r#"use cifuzz::fuzz_test;
+
+#[fuzz_test]
+fn replay_fuzz_test(data: &[u8]) {
+    if data.starts_with(b"FUZZ") {
+        // BUG
+    }
+}
+"#;
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args([
+            "-c",
+            "user.name=cifuzz",
+            "-c",
+            "user.email=cifuzz@example.com",
+        ])
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {args:?} failed");
+}
+
+#[test]
+#[ignore = "builds the cargo example with libFuzzer, run with --ignored"]
+fn replay_and_bisect_corpus() {
+    let dir = copy_example();
+    let src = dir.path().join("src");
+    let fuzz_test = src.join("replay_fuzz_test.rs");
+    std::fs::write(&fuzz_test, FUZZ_TEST).unwrap();
+    let main = std::fs::read_to_string(src.join("main.rs")).unwrap();
+    std::fs::write(
+        src.join("main.rs"),
+        format!("#[cfg(test)]\nmod replay_fuzz_test;\n{main}"),
+    )
+    .unwrap();
+    std::fs::write(dir.path().join(".gitignore"), ".cifuzz-*\n/target\n").unwrap();
+    git(dir.path(), &["init", "--quiet"]);
+    git(dir.path(), &["add", "."]);
+    git(
+        dir.path(),
+        &["commit", "--quiet", "-m", "Add the fuzz test"],
+    );
+    std::fs::write(dir.path().join("README.md"), "Unrelated\n").unwrap();
+    git(
+        dir.path(),
+        &["commit", "--quiet", "-am", "Change the README"],
+    );
+    let buggy = FUZZ_TEST.replace("// BUG", "panic!(\"found FUZZ\");");
+    std::fs::write(&fuzz_test, buggy).unwrap();
+    git(dir.path(), &["commit", "--quiet", "-am", "Introduce a bug"]);
+    std::fs::write(dir.path().join("README.md"), "Unrelated again\n").unwrap();
+    git(
+        dir.path(),
+        &["commit", "--quiet", "-am", "Change the README again"],
+    );
+
+    let corpus = dir.path().join(".cifuzz-corpus").join("replay_fuzz_test");
+    std::fs::create_dir_all(&corpus).unwrap();
+    std::fs::write(corpus.join("passes"), "FUZ").unwrap();
+    std::fs::write(corpus.join("fails"), "FUZZING").unwrap();
+    std::fs::write(corpus.join("passes-too"), "").unwrap();
+
+    let output = cargo_cifuzz(
+        dir.path(),
+        &["replay", "replay_fuzz_test", "--bisect", "--good", "HEAD~3"],
+    );
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("fails: found FUZZ"), "{stderr}");
+    assert!(stderr.contains("1 of 3 inputs failed"), "{stderr}");
+    assert!(
+        stderr.contains("The first commit at which the input fails is"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("Introduce a bug"), "{stderr}");
+    assert!(!dir.path().join(".cifuzz-build").join("bisect").exists());
+}
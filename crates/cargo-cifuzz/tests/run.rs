@@ -0,0 +1,82 @@
+mod common;
+
+use common::{cargo_cifuzz, copy_example};
+
+#[test]
+#[ignore = "builds the cargo example with libFuzzer, run with --ignored"]
+fn run_finds_crash_in_example() {
+    let dir = copy_example();
+
+    let output = cargo_cifuzz(dir.path(), &["run", "my_fuzz_test", "--timeout", "10m"]);
+    assert!(!output.status.success());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("branch 4 has been reached"), "{stderr}");
+    let artifacts = std::fs::read_dir(dir.path().join(".cifuzz-artifacts").join("my_fuzz_test"))
+        .unwrap()
+        .count();
+    assert_eq!(artifacts, 1);
+
+    let findings: Vec<_> = std::fs::read_dir(
+        dir.path()
+            .join(".cifuzz")
+            .join("findings")
+            .join("my_fuzz_test"),
+    )
+    .unwrap()
+    .map(|e| e.unwrap().path())
+    .collect();
+    assert_eq!(findings.len(), 1);
+    assert!(findings[0].join("crashing-input").is_file());
+    let finding = std::fs::read_to_string(findings[0].join("finding.json")).unwrap();
+    assert!(
+        finding.contains("\"details\": \"branch 4 has been reached\""),
+        "{finding}"
+    );
+    assert!(finding.contains("src/explore_me.rs"), "{finding}");
+}
+
+#[test]
+#[ignore = "builds the cargo example with libFuzzer, run with --ignored"]
+fn run_with_custom_mutator() {
+    let dir = copy_example();
+    let src = dir.path().join("src");
+    std::fs::write(
+        src.join("mutator_fuzz_test.rs"),
+        "use cifuzz::{fuzz_test, CustomMutator};\n\
+         \n\
+         struct Mutator;\n\
+         \n\
+         impl CustomMutator for Mutator {\n    \
+             fn mutate(data: &mut Vec<u8>, _max_size: usize, _seed: u32) {\n        \
+                 *data = b\"mutated\".to_vec();\n    \
+             }\n\
+         }\n\
+         \n\
+         #[fuzz_test(mutator = Mutator)]\n\
+         fn mutator_fuzz_test(data: &[u8]) {\n    \
+             assert_ne!(data, b\"mutated\", \"the custom mutator was used\");\n\
+         }\n",
+    )
+    .unwrap();
+    let main = std::fs::read_to_string(src.join("main.rs")).unwrap();
+    std::fs::write(
+        src.join("main.rs"),
+        format!("#[cfg(test)]\nmod mutator_fuzz_test;\n{main}"),
+    )
+    .unwrap();
+
+    let output = cargo_cifuzz(dir.path(), &["run", "mutator_fuzz_test", "--timeout", "5m"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("the custom mutator was used"), "{stderr}");
+}
+
+#[test]
+fn run_outside_of_project() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = cargo_cifuzz(dir.path(), &["run", "my_fuzz_test"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("not a cifuzz project"), "{stderr}");
+}
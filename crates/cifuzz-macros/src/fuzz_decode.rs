@@ -0,0 +1,226 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{Attribute, Data, DeriveInput, Expr, ExprRange, Fields, GenericParam, RangeLimits, Type};
+
+/// How the value of a field is consumed.
+enum Strategy {
+    /// `ConsumeFromFdp::from_fdp`
+    Consume,
+    /// `#[fuzz(range = min..=max)]`
+    Range(Box<Expr>, Box<Expr>),
+    /// `#[fuzz(max_len = n)]`
+    MaxLen(Expr),
+    /// `#[fuzz(default)]`, the field is not consumed
+    Default,
+}
+
+pub fn expand(item: TokenStream) -> syn::Result<TokenStream> {
+    let mut input: DeriveInput = syn::parse2(item)?;
+
+    // The generated code has the spans of the item, so that coverage
+    // reports attribute it to the lines of the fields instead of the
+    // derive attribute. The body of `from_fdp` spans the braces of the
+    // item, which contain the spans of the fields.
+    let body_span = match &input.data {
+        Data::Struct(data) => fields_span(&data.fields).unwrap_or(input.ident.span()),
+        Data::Enum(data) => data.brace_token.span.join(),
+        Data::Union(data) => data.fields.brace_token.span.join(),
+    };
+    let body = match &input.data {
+        Data::Struct(data) => construct(quote_spanned!(body_span=> Self), &data.fields)?,
+        Data::Enum(data) => {
+            if data.variants.is_empty() {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "#[derive(FuzzDecode)] requires at least one variant",
+                ));
+            }
+            let max_index = data.variants.len() as u32 - 1;
+            let arms = data
+                .variants
+                .iter()
+                .enumerate()
+                .map(|(i, variant)| {
+                    let i = i as u32;
+                    let ident = &variant.ident;
+                    let span = fields_span(&variant.fields).unwrap_or(ident.span());
+                    let value = construct(quote_spanned!(span=> Self::#ident), &variant.fields)?;
+                    Ok(quote_spanned! {span=> #i => #value, })
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+            // The variant is selected like by FuzzedDataProvider::consume_enum
+            quote_spanned! {body_span=>
+                match fdp.consume_int_in_range(0u32, #max_index) {
+                    #(#arms)*
+                    _ => ::core::unreachable!(),
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "#[derive(FuzzDecode)] is not supported for unions",
+            ))
+        }
+    };
+
+    for param in &mut input.generics.params {
+        if let GenericParam::Type(param) = param {
+            param
+                .bounds
+                .push(syn::parse_quote!(::cifuzz::ConsumeFromFdp));
+        }
+    }
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let body = quote_spanned! {body_span=> { #body } };
+    Ok(quote! {
+        impl #impl_generics ::cifuzz::ConsumeFromFdp for #name #ty_generics #where_clause {
+            fn from_fdp(fdp: &mut ::cifuzz::FuzzedDataProvider<'_>) -> Self #body
+        }
+    })
+}
+
+/// Returns the span of the braces or parentheses around the fields, none
+/// for a unit struct or variant.
+fn fields_span(fields: &Fields) -> Option<Span> {
+    match fields {
+        Fields::Named(fields) => Some(fields.brace_token.span.join()),
+        Fields::Unnamed(fields) => Some(fields.paren_token.span.join()),
+        Fields::Unit => None,
+    }
+}
+
+/// Returns an expression which constructs the struct or variant `path`,
+/// consuming its fields in declaration order.
+fn construct(path: TokenStream, fields: &Fields) -> syn::Result<TokenStream> {
+    let values = fields
+        .iter()
+        .map(|field| {
+            let strategy = parse_strategy(&field.attrs)?;
+            Ok(consume_field(&field.ty, strategy))
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(match fields {
+        Fields::Named(named) => {
+            let names = fields.iter().map(|f| &f.ident);
+            quote_spanned! {named.brace_token.span.join()=> #path { #(#names: #values,)* } }
+        }
+        Fields::Unnamed(unnamed) => {
+            quote_spanned! {unnamed.paren_token.span.join()=> #path(#(#values,)*) }
+        }
+        Fields::Unit => path,
+    })
+}
+
+/// Returns the expression consuming the field, with the span of its
+/// type.
+fn consume_field(ty: &Type, strategy: Strategy) -> TokenStream {
+    let span = ty.span();
+    match strategy {
+        Strategy::Consume => quote_spanned! {span=>
+            <#ty as ::cifuzz::ConsumeFromFdp>::from_fdp(fdp)
+        },
+        Strategy::Range(min, max) => quote_spanned! {span=>
+            <#ty as ::cifuzz::__private::ConsumeInRange>::consume_in_range(fdp, #min, #max)
+        },
+        Strategy::MaxLen(max_len) => quote_spanned! {span=>
+            <#ty as ::cifuzz::__private::ConsumeWithMaxLen>::consume_with_max_len(fdp, #max_len)
+        },
+        Strategy::Default => quote_spanned! {span=>
+            <#ty as ::core::default::Default>::default()
+        },
+    }
+}
+
+fn parse_strategy(attrs: &[Attribute]) -> syn::Result<Strategy> {
+    let mut strategy = None;
+    for attr in attrs.iter().filter(|a| a.path().is_ident("fuzz")) {
+        attr.parse_nested_meta(|meta| {
+            let value = if meta.path.is_ident("range") {
+                let range: ExprRange = meta.value()?.parse()?;
+                let (Some(min), Some(max), RangeLimits::Closed(_)) =
+                    (range.start, range.end, range.limits)
+                else {
+                    return Err(meta.error("expected an inclusive range like `1..=10`"));
+                };
+                Strategy::Range(min, max)
+            } else if meta.path.is_ident("max_len") {
+                Strategy::MaxLen(meta.value()?.parse()?)
+            } else if meta.path.is_ident("default") {
+                Strategy::Default
+            } else {
+                return Err(meta.error("expected `range`, `max_len` or `default`"));
+            };
+            if strategy.replace(value).is_some() {
+                return Err(syn::Error::new(
+                    attr.span(),
+                    "only one of `range`, `max_len` and `default` can be specified",
+                ));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(strategy.unwrap_or(Strategy::Consume))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_str(item: &str) -> String {
+        expand(item.parse().unwrap()).unwrap().to_string()
+    }
+
+    fn error_of(item: &str) -> String {
+        expand(item.parse().unwrap()).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn expands_struct() {
+        let tokens = expand_str(
+            "struct S<T> { a: u8, #[fuzz(range = 1..=10)] b: i32, \
+             #[fuzz(max_len = 16)] c: String, #[fuzz(default)] d: T }",
+        );
+        assert!(
+            tokens.contains("impl < T : :: cifuzz :: ConsumeFromFdp >"),
+            "{tokens}"
+        );
+        assert!(tokens.contains("a : < u8 as :: cifuzz :: ConsumeFromFdp > :: from_fdp (fdp)"));
+        assert!(
+            tokens.contains("consume_in_range (fdp , 1 , 10)"),
+            "{tokens}"
+        );
+        assert!(
+            tokens.contains("consume_with_max_len (fdp , 16)"),
+            "{tokens}"
+        );
+        assert!(tokens.contains("< T as :: core :: default :: Default > :: default ()"));
+    }
+
+    #[test]
+    fn expands_enum() {
+        let tokens = expand_str("enum E { A, B(u8), C { x: bool } }");
+        assert!(
+            tokens.contains("consume_int_in_range (0u32 , 2u32)"),
+            "{tokens}"
+        );
+        assert!(tokens.contains("0u32 => Self :: A ,"), "{tokens}");
+        assert!(tokens.contains("2u32 => Self :: C { x :"), "{tokens}");
+    }
+
+    #[test]
+    fn rejects_invalid_attributes() {
+        assert!(error_of("struct S { #[fuzz(range = 1..10)] a: u8 }").contains("inclusive range"));
+        assert!(error_of("struct S { #[fuzz(len = 1)] a: u8 }").contains("expected `range`"));
+        assert!(
+            error_of("struct S { #[fuzz(default, max_len = 1)] a: String }")
+                .contains("only one of")
+        );
+        assert!(error_of("enum E {}").contains("at least one variant"));
+        assert!(error_of("union U { a: u8 }").contains("not supported for unions"));
+    }
+}
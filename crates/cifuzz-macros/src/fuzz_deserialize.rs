@@ -0,0 +1,132 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{bracketed, Ident, Token, Type};
+
+/// The formats and the types of the runtime which implement them.
+const FORMATS: [(&str, &str); 3] = [("json", "Json"), ("yaml", "Yaml"), ("toml", "Toml")];
+
+/// The input of `fuzz_deserialize!(MyConfig, [json, yaml])`.
+struct Input {
+    ty: Type,
+    formats: Punctuated<Ident, Token![,]>,
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ty = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let content;
+        bracketed!(content in input);
+        let formats = content.parse_terminated(Ident::parse, Token![,])?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(Input { ty, formats })
+    }
+}
+
+pub fn expand(input: TokenStream) -> syn::Result<TokenStream> {
+    let Input { ty, formats } = syn::parse2(input)?;
+    let Type::Path(path) = &ty else {
+        return Err(syn::Error::new_spanned(&ty, "expected the path of a type"));
+    };
+    let name = path
+        .path
+        .segments
+        .last()
+        .map(|segment| snake_case(&segment.ident.to_string()))
+        .unwrap_or_default();
+    if formats.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &ty,
+            "expected at least one format, e.g. `[json, yaml, toml]`",
+        ));
+    }
+
+    let mut seen = Vec::new();
+    let mut fuzz_tests = Vec::new();
+    for format in &formats {
+        let format_str = format.to_string();
+        let Some((_, runtime)) = FORMATS.iter().find(|(name, _)| *name == format_str) else {
+            return Err(syn::Error::new_spanned(
+                format,
+                "unknown format, expected `json`, `yaml` or `toml`",
+            ));
+        };
+        if seen.contains(&format_str) {
+            return Err(syn::Error::new_spanned(format, "duplicate format"));
+        }
+        seen.push(format_str.clone());
+        let fuzz_test = format_ident!("deserialize_{}_{}", name, format_str);
+        let runtime = format_ident!("{}", runtime);
+        fuzz_tests.push(quote! {
+            #[::cifuzz::fuzz_test]
+            fn #fuzz_test(data: &[u8]) {
+                ::cifuzz::__private::check_deserialize::<#ty, ::cifuzz::__private::#runtime>(data);
+            }
+        });
+    }
+    Ok(quote! { #(#fuzz_tests)* })
+}
+
+/// Converts the name of a type to snake case, e.g. `HTTPConfig` to
+/// `http_config`.
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|c| c.is_lowercase());
+            if prev.is_lowercase() || prev.is_ascii_digit() || prev.is_uppercase() && next_is_lower
+            {
+                snake.push('_');
+            }
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_str(input: &str) -> syn::Result<TokenStream> {
+        expand(input.parse().unwrap())
+    }
+
+    #[test]
+    fn expands_fuzz_tests() {
+        let tokens = expand_str("config::MyConfig, [json, toml,]")
+            .unwrap()
+            .to_string();
+        assert!(
+            tokens.contains("fn deserialize_my_config_json (data : & [u8])")
+                && tokens.contains(
+                    "check_deserialize :: < config :: MyConfig , :: cifuzz :: __private :: Toml >"
+                )
+                && !tokens.contains("Yaml"),
+            "{}",
+            tokens
+        );
+    }
+
+    #[test]
+    fn converts_to_snake_case() {
+        assert_eq!(snake_case("MyConfig"), "my_config");
+        assert_eq!(snake_case("HTTPConfig"), "http_config");
+        assert_eq!(snake_case("Config2Json"), "config2_json");
+        assert_eq!(snake_case("config"), "config");
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        let error_of = |input| expand_str(input).unwrap_err().to_string();
+        assert!(error_of("MyConfig, [xml]").contains("unknown format"));
+        assert!(error_of("MyConfig, [json, json]").contains("duplicate"));
+        assert!(error_of("MyConfig, []").contains("at least one format"));
+        assert!(error_of("&str, [json]").contains("path of a type"));
+        assert!(expand_str("MyConfig").is_err());
+    }
+}
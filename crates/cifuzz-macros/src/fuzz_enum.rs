@@ -0,0 +1,79 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields};
+
+pub fn expand(item: TokenStream) -> syn::Result<TokenStream> {
+    let input: DeriveInput = syn::parse2(item)?;
+    let Data::Enum(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "#[derive(FuzzEnum)] is only supported for enums",
+        ));
+    };
+    if data.variants.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "#[derive(FuzzEnum)] requires at least one variant",
+        ));
+    }
+    if let Some(variant) = data
+        .variants
+        .iter()
+        .find(|v| !matches!(v.fields, Fields::Unit))
+    {
+        return Err(syn::Error::new_spanned(
+            variant,
+            "#[derive(FuzzEnum)] is only supported for enums without fields",
+        ));
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let count = data.variants.len() as u32;
+    let arms = data.variants.iter().enumerate().map(|(i, v)| {
+        let i = i as u32;
+        let variant = &v.ident;
+        quote! { #i => #name::#variant, }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::cifuzz::FuzzEnum for #name #ty_generics #where_clause {
+            const VARIANT_COUNT: u32 = #count;
+
+            fn from_index(index: u32) -> Self {
+                match index {
+                    #(#arms)*
+                    _ => panic!("variant index out of range"),
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_of(item: &str) -> String {
+        expand(item.parse().unwrap()).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn expands_unit_variants() {
+        let tokens = expand("enum Op { Add, Sub = 5, Mul }".parse().unwrap())
+            .unwrap()
+            .to_string();
+        assert!(
+            tokens.contains("const VARIANT_COUNT : u32 = 3u32"),
+            "{tokens}"
+        );
+        assert!(tokens.contains("1u32 => Op :: Sub"), "{tokens}");
+    }
+
+    #[test]
+    fn rejects_unsupported_items() {
+        assert!(error_of("struct S;").contains("only supported for enums"));
+        assert!(error_of("enum E {}").contains("at least one variant"));
+        assert!(error_of("enum E { A, B(u8) }").contains("without fields"));
+    }
+}
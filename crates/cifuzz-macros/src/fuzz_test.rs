@@ -0,0 +1,830 @@
+use std::path::Path;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, quote_spanned};
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{
+    Expr, ExprLit, FnArg, ItemFn, Lit, LitByteStr, LitStr, Macro, Meta, MetaNameValue,
+    PathArguments, ReturnType, Token, Type,
+};
+
+/// The kinds of parameters a fuzz test function can take.
+enum Input {
+    /// `data: &[u8]`
+    Bytes,
+    /// `s: &str`
+    Str,
+    /// `fdp: &mut FuzzedDataProvider`
+    Provider,
+    /// `input: T` where `T: arbitrary::Arbitrary`
+    Arbitrary(Box<Type>),
+    /// `a: A, b: B, ...` where the types implement `ConsumeFromFdp`,
+    /// which are decoded in declaration order
+    Params(Vec<Type>),
+    /// `input: T` generated by the proptest strategy of `strategy = ...`
+    Strategy(Box<Expr>),
+}
+
+/// The arguments of the `#[fuzz_test]` attribute.
+#[derive(Default)]
+struct Args {
+    /// The patterns of `ignore_panics = "invalid input|parse error"`.
+    /// Panics whose message contains one of them aren't findings.
+    ignore_panics: Vec<String>,
+    /// The runtime of `runtime = "tokio"`, which executes an async fuzz
+    /// test, and the span of the argument
+    runtime: Option<(Runtime, Span)>,
+    /// The type of `mutator = JsonMutator`, which implements
+    /// `cifuzz::CustomMutator`
+    mutator: Option<syn::Path>,
+    /// The function of `init = setup`, which is called once per process
+    /// before the first input
+    init: Option<syn::Path>,
+    /// How `invalid_utf8 = "skip"` or `"lossy"` passes inputs which
+    /// aren't valid UTF-8 to a fuzz test taking a `&str`, and the span
+    /// of the argument
+    invalid_utf8: Option<(InvalidUtf8, Span)>,
+    /// The expression of `strategy = (0..10u32, any::<bool>())`, which
+    /// evaluates to the proptest strategy generating the input
+    strategy: Option<Expr>,
+}
+
+/// How inputs which aren't valid UTF-8 are passed to a fuzz test taking
+/// a `&str`.
+#[derive(Clone, Copy)]
+enum InvalidUtf8 {
+    /// The input is skipped
+    Skip,
+    /// The invalid sequences are replaced with U+FFFD
+    Lossy,
+}
+
+/// The runtimes which can execute async fuzz tests.
+#[derive(Clone, Copy)]
+enum Runtime {
+    Tokio,
+    AsyncStd,
+}
+
+pub fn expand(attr: TokenStream, item: TokenStream) -> syn::Result<TokenStream> {
+    let args = parse_args(attr)?;
+    let mut func: ItemFn = syn::parse2(item)?;
+    let mut input = validate_signature(&func)?;
+    if let Some(strategy) = &args.strategy {
+        let Input::Arbitrary(_) = input else {
+            return Err(syn::Error::new(
+                strategy.span(),
+                "`strategy` is only supported for fuzz tests taking one owned argument",
+            ));
+        };
+        input = Input::Strategy(Box::new(strategy.clone()));
+    }
+    if func.sig.asyncness.is_some() && matches!(input, Input::Provider) {
+        elide_provider_lifetime(&mut func);
+    }
+
+    let name = &func.sig.ident;
+    let name_str = name.to_string();
+    // The closure executing the fuzz test has the span of its parameter
+    // list, so that coverage reports attribute the decoding of the input
+    // to the lines of the parameters instead of the attribute. The spans
+    // of the types, which the decoding of the parameters has, are inside
+    // of it.
+    let span = func.sig.paren_token.span.join();
+    // Coverage reports leave out the spans of items nested in functions,
+    // so the imports keep the span of the attribute
+    let imports = quote! {
+        #[allow(unused_imports)]
+        use super::*;
+    };
+    // Async fuzz tests are executed to completion for every input, on the
+    // thread of the harness, so that the input timeout applies to them
+    let block_on = match (&func.sig.asyncness, args.runtime) {
+        (None, None) => None,
+        (None, Some((_, span))) => {
+            return Err(syn::Error::new(
+                span,
+                "`runtime` is only supported for async fuzz tests",
+            ))
+        }
+        (Some(_), None) => Some(quote_spanned! {span=> ::cifuzz::__private::block_on }),
+        (Some(_), Some((Runtime::Tokio, _))) => {
+            Some(quote_spanned! {span=> ::cifuzz::__private::block_on_tokio })
+        }
+        (Some(_), Some((Runtime::AsyncStd, _))) => {
+            Some(quote_spanned! {span=> ::cifuzz::__private::block_on_async_std })
+        }
+    };
+    let invalid_utf8 = match (&input, args.invalid_utf8) {
+        (Input::Str, invalid_utf8) => invalid_utf8.map_or(InvalidUtf8::Skip, |(i, _)| i),
+        (_, None) => InvalidUtf8::Skip,
+        (_, Some((_, span))) => {
+            return Err(syn::Error::new(
+                span,
+                "`invalid_utf8` is only supported for fuzz tests taking a `&str`",
+            ))
+        }
+    };
+    let call = |arg: TokenStream| match &block_on {
+        Some(block_on) => quote_spanned! {span=> #block_on(super::#name(#arg)) },
+        None => quote_spanned! {span=> super::#name(#arg) },
+    };
+    let input_type = match &input {
+        Input::Arbitrary(ty) => Some(ty.clone()),
+        _ => None,
+    };
+    let test_one_input = match input {
+        Input::Bytes => {
+            let call = call(quote_spanned! {span=> data });
+            quote_spanned! {span=>
+                |data: &[u8]| #call
+            }
+        }
+        Input::Str => match invalid_utf8 {
+            InvalidUtf8::Skip => {
+                let call = call(quote_spanned! {span=> input });
+                quote_spanned! {span=>
+                    |data: &[u8]| {
+                        if let ::core::result::Result::Ok(input) = ::core::str::from_utf8(data) {
+                            #call
+                        }
+                    }
+                }
+            }
+            InvalidUtf8::Lossy => {
+                let call = call(quote_spanned! {span=> &input });
+                quote_spanned! {span=>
+                    |data: &[u8]| {
+                        let input = ::std::string::String::from_utf8_lossy(data);
+                        #call
+                    }
+                }
+            }
+        },
+        Input::Provider => {
+            let call = call(quote_spanned! {span=> &mut fdp });
+            quote_spanned! {span=>
+                |data: &[u8]| {
+                    let mut fdp = ::cifuzz::FuzzedDataProvider::new(data);
+                    #call
+                }
+            }
+        }
+        Input::Params(types) => {
+            let args: Vec<_> = (0..types.len()).map(|i| format_ident!("arg{i}")).collect();
+            // The types are resolved in the module of the fuzz test, and
+            // the errors of types which can't be consumed point to them
+            let decode = args.iter().zip(&types).map(|(arg, ty)| {
+                quote_spanned! {ty.span()=>
+                    let #arg: #ty = fdp.consume();
+                }
+            });
+            let call = call(quote_spanned! {span=> #(#args),* });
+            quote_spanned! {span=>
+                |data: &[u8]| {
+                    #imports
+                    let mut fdp = ::cifuzz::FuzzedDataProvider::new(data);
+                    #(#decode)*
+                    #call
+                }
+            }
+        }
+        // The strategy is evaluated in the module of the fuzz test for
+        // every input, inputs for which it fails are skipped
+        Input::Strategy(strategy) => {
+            let call = call(quote_spanned! {span=> input });
+            let generate = quote_spanned! {strategy.span()=>
+                fdp.consume_proptest(&(#strategy))
+            };
+            quote_spanned! {span=>
+                |data: &[u8]| {
+                    #imports
+                    let mut fdp = ::cifuzz::FuzzedDataProvider::new(data);
+                    if let ::core::result::Result::Ok(input) = #generate {
+                        #call
+                    }
+                }
+            }
+        }
+        // Inputs which can't be decoded are skipped, like cargo-fuzz does
+        Input::Arbitrary(ty) => {
+            let call = call(quote_spanned! {span=> input });
+            let decode_input = quote! { use ::cifuzz::__private::DecodeInput as _; };
+            quote_spanned! {span=>
+                |data: &[u8]| {
+                    #imports
+                    #decode_input
+                    if let ::core::option::Option::Some(input) =
+                        (&&::cifuzz::__private::Input::<#ty>::new()).decode(data)
+                    {
+                        #call
+                    }
+                }
+            }
+        }
+    };
+
+    let seed_corpus = embedded_seed_corpus(&name_str);
+    let dictionary = dictionary_tokens(&func)?;
+    let ignore_panics = &args.ignore_panics;
+    // The paths of the mutator and the input type are resolved in the
+    // module of the fuzz test.
+    // Fuzz tests taking a value use the mutator of its type, if any.
+    let mutator = match (&args.mutator, &input_type) {
+        (Some(mutator), _) => quote! {
+            ::core::option::Option::Some({
+                #[allow(unused_imports)]
+                use super::*;
+                ::cifuzz::__private::Mutator::of::<#mutator>()
+            })
+        },
+        (None, Some(ty)) => quote! {{
+            #[allow(unused_imports)]
+            use super::*;
+            use ::cifuzz::__private::DecodeInput as _;
+            (&&::cifuzz::__private::Input::<#ty>::new()).mutator()
+        }},
+        (None, None) => quote! { ::core::option::Option::None },
+    };
+    let init = match &args.init {
+        Some(init) => quote! {
+            ::core::option::Option::Some({
+                #[allow(unused_imports)]
+                use super::*;
+                let init: fn() = #init;
+                init
+            })
+        },
+        None => quote! { ::core::option::Option::None },
+    };
+
+    // The harness is generated into a module of the same name as the
+    // fuzz test function (modules and functions live in different
+    // namespaces), so that the fuzz test can be selected by its name
+    // via the test filter of libtest.
+    Ok(quote! {
+        #[cfg_attr(not(test), allow(dead_code))]
+        #func
+
+        #[cfg(test)]
+        mod #name {
+            ::cifuzz::__fuzz_test_harness!(
+                #name_str,
+                #test_one_input,
+                #seed_corpus,
+                &[#(#dictionary),*],
+                &[#(#ignore_panics),*],
+                #mutator,
+                #init
+            );
+        }
+    })
+}
+
+fn parse_args(attr: TokenStream) -> syn::Result<Args> {
+    let mut args = Args::default();
+    let mut ignore_panics = false;
+    let metas = Punctuated::<Meta, Token![,]>::parse_terminated.parse2(attr)?;
+    for meta in metas {
+        if meta.path().is_ident("ignore_panics") {
+            if ignore_panics {
+                return Err(syn::Error::new(meta.span(), "duplicate `ignore_panics`"));
+            }
+            ignore_panics = true;
+            let value = string_value(&meta, "ignore_panics = \"<pattern>|<pattern>...\"")?;
+            let patterns: Vec<String> = value.value().split('|').map(String::from).collect();
+            if patterns.iter().any(String::is_empty) {
+                return Err(syn::Error::new(
+                    value.span(),
+                    "the patterns of `ignore_panics` must not be empty",
+                ));
+            }
+            args.ignore_panics = patterns;
+        } else if meta.path().is_ident("runtime") {
+            if args.runtime.is_some() {
+                return Err(syn::Error::new(meta.span(), "duplicate `runtime`"));
+            }
+            let value = string_value(&meta, "runtime = \"tokio\" or \"async-std\"")?;
+            let runtime = match value.value().as_str() {
+                "tokio" => Runtime::Tokio,
+                "async-std" => Runtime::AsyncStd,
+                _ => {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        "unknown runtime, expected \"tokio\" or \"async-std\"",
+                    ))
+                }
+            };
+            args.runtime = Some((runtime, meta.span()));
+        } else if meta.path().is_ident("mutator") {
+            if args.mutator.is_some() {
+                return Err(syn::Error::new(meta.span(), "duplicate `mutator`"));
+            }
+            let Meta::NameValue(MetaNameValue {
+                value: Expr::Path(path),
+                ..
+            }) = &meta
+            else {
+                return Err(syn::Error::new(
+                    meta.span(),
+                    "expected `mutator = <type implementing CustomMutator>`",
+                ));
+            };
+            args.mutator = Some(path.path.clone());
+        } else if meta.path().is_ident("init") {
+            if args.init.is_some() {
+                return Err(syn::Error::new(meta.span(), "duplicate `init`"));
+            }
+            let Meta::NameValue(MetaNameValue {
+                value: Expr::Path(path),
+                ..
+            }) = &meta
+            else {
+                return Err(syn::Error::new(
+                    meta.span(),
+                    "expected `init = <function without arguments>`",
+                ));
+            };
+            args.init = Some(path.path.clone());
+        } else if meta.path().is_ident("invalid_utf8") {
+            if args.invalid_utf8.is_some() {
+                return Err(syn::Error::new(meta.span(), "duplicate `invalid_utf8`"));
+            }
+            let value = string_value(&meta, "invalid_utf8 = \"skip\" or \"lossy\"")?;
+            let invalid_utf8 = match value.value().as_str() {
+                "skip" => InvalidUtf8::Skip,
+                "lossy" => InvalidUtf8::Lossy,
+                _ => {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        "unknown value, expected \"skip\" or \"lossy\"",
+                    ))
+                }
+            };
+            args.invalid_utf8 = Some((invalid_utf8, meta.span()));
+        } else if meta.path().is_ident("strategy") {
+            if args.strategy.is_some() {
+                return Err(syn::Error::new(meta.span(), "duplicate `strategy`"));
+            }
+            let Meta::NameValue(MetaNameValue { value, .. }) = &meta else {
+                return Err(syn::Error::new(
+                    meta.span(),
+                    "expected `strategy = <proptest strategy>`",
+                ));
+            };
+            args.strategy = Some(value.clone());
+        } else {
+            return Err(syn::Error::new(
+                meta.path().span(),
+                "unknown argument, #[fuzz_test] only takes `ignore_panics`, `init`, \
+                 `invalid_utf8`, `mutator`, `runtime` and `strategy`",
+            ));
+        }
+    }
+    Ok(args)
+}
+
+/// Returns the string literal of an argument like `name = "value"`.
+fn string_value<'a>(meta: &'a Meta, expected: &str) -> syn::Result<&'a LitStr> {
+    if let Meta::NameValue(meta) = meta {
+        if let Expr::Lit(ExprLit {
+            lit: Lit::Str(value),
+            ..
+        }) = &meta.value
+        {
+            return Ok(value);
+        }
+    }
+    Err(syn::Error::new(
+        meta.span(),
+        format!("expected `{expected}`"),
+    ))
+}
+
+/// The environment variable via which `cifuzz::build::embed_seed_corpus`
+/// passes the directory of the generated seed corpus files.
+// Must be kept in sync with crates/cifuzz/src/build.rs
+const SEED_CORPUS_DIR_ENV: &str = "CIFUZZ_SEED_CORPUS_DIR";
+
+/// Returns the seed corpus of the fuzz test embedded by the build script
+/// of the package, or an empty slice if there is none. The environment
+/// of the build script is visible to the macro, because it runs in the
+/// rustc process compiling the package.
+fn embedded_seed_corpus(name: &str) -> TokenStream {
+    let Some(dir) = std::env::var_os(SEED_CORPUS_DIR_ENV) else {
+        return quote! { &[] };
+    };
+    let file = Path::new(&dir).join(format!("{name}.rs"));
+    if !file.is_file() {
+        return quote! { &[] };
+    }
+    let file = file.display().to_string();
+    quote! { ::core::include!(#file) }
+}
+
+/// Collects the tokens of the `dictionary!` invocations in the body of
+/// the fuzz test as byte string literals.
+fn dictionary_tokens(func: &ItemFn) -> syn::Result<Vec<LitByteStr>> {
+    struct Visitor {
+        tokens: Vec<LitByteStr>,
+        error: Option<syn::Error>,
+    }
+
+    impl Visit<'_> for Visitor {
+        fn visit_macro(&mut self, mac: &Macro) {
+            let is_dictionary = mac
+                .path
+                .segments
+                .last()
+                .is_some_and(|s| s.ident == "dictionary");
+            if !is_dictionary || self.error.is_some() {
+                return visit::visit_macro(self, mac);
+            }
+            let lits = match mac.parse_body_with(Punctuated::<Lit, Token![,]>::parse_terminated) {
+                Ok(lits) => lits,
+                Err(err) => {
+                    self.error = Some(err);
+                    return;
+                }
+            };
+            for lit in lits {
+                match lit {
+                    Lit::Str(s) => self
+                        .tokens
+                        .push(LitByteStr::new(s.value().as_bytes(), s.span())),
+                    Lit::ByteStr(b) => self.tokens.push(b),
+                    lit => {
+                        self.error = Some(syn::Error::new(
+                            lit.span(),
+                            "dictionary! only takes string and byte string literals",
+                        ));
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut visitor = Visitor {
+        tokens: Vec::new(),
+        error: None,
+    };
+    visitor.visit_block(&func.block);
+    match visitor.error {
+        Some(err) => Err(err),
+        None => Ok(visitor.tokens),
+    }
+}
+
+fn validate_signature(func: &ItemFn) -> syn::Result<Input> {
+    let sig = &func.sig;
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            sig.generics.span(),
+            "fuzz tests can't be generic",
+        ));
+    }
+    if let ReturnType::Type(_, ty) = &sig.output {
+        let is_unit = matches!(&**ty, Type::Tuple(t) if t.elems.is_empty());
+        if !is_unit {
+            return Err(syn::Error::new(
+                ty.span(),
+                "fuzz tests must not return a value",
+            ));
+        }
+    }
+
+    let mut args = Vec::new();
+    for arg in &sig.inputs {
+        match arg {
+            FnArg::Typed(arg) => args.push(arg),
+            FnArg::Receiver(_) => {
+                return Err(syn::Error::new(arg.span(), "fuzz tests can't be methods"))
+            }
+        }
+    }
+    let arg = match args.as_slice() {
+        [] => {
+            return Err(syn::Error::new(
+                sig.inputs.span(),
+                "fuzz tests must take one argument of type `&[u8]`, `&str`, \
+                 `&mut FuzzedDataProvider` or a type implementing `Arbitrary`, or several \
+                 arguments of types implementing `ConsumeFromFdp`",
+            ))
+        }
+        [arg] => arg,
+        args => return params_input(args),
+    };
+
+    classify_input(&arg.ty).ok_or_else(|| {
+        syn::Error::new(
+            arg.ty.span(),
+            "expected an argument of type `&[u8]`, `&str`, `&mut FuzzedDataProvider` \
+             or a type implementing `Arbitrary`",
+        )
+    })
+}
+
+/// The input of a fuzz test taking several arguments, which must be
+/// owned, because they're decoded into local variables of the harness.
+fn params_input(args: &[&syn::PatType]) -> syn::Result<Input> {
+    let mut types = Vec::new();
+    for arg in args {
+        if let Type::Reference(_) | Type::ImplTrait(_) | Type::Infer(_) = &*arg.ty {
+            return Err(syn::Error::new(
+                arg.ty.span(),
+                "the arguments of fuzz tests taking several arguments must be owned types \
+                 implementing `ConsumeFromFdp`, e.g. `String` instead of `&str`",
+            ));
+        }
+        types.push((*arg.ty).clone());
+    }
+    Ok(Input::Params(types))
+}
+
+/// Adds the elided lifetime to `&mut FuzzedDataProvider`, which async
+/// functions require to be written as `FuzzedDataProvider<'_>`.
+fn elide_provider_lifetime(func: &mut ItemFn) {
+    let Some(FnArg::Typed(arg)) = func.sig.inputs.first_mut() else {
+        return;
+    };
+    let Type::Reference(reference) = &mut *arg.ty else {
+        return;
+    };
+    let Type::Path(path) = &mut *reference.elem else {
+        return;
+    };
+    if let Some(last) = path.path.segments.last_mut() {
+        if last.arguments.is_none() {
+            last.arguments = PathArguments::AngleBracketed(syn::parse_quote! { <'_> });
+        }
+    }
+}
+
+fn classify_input(ty: &Type) -> Option<Input> {
+    let reference = match ty {
+        Type::Reference(reference) => reference,
+        // Other types which can't implement Arbitrary are rejected by
+        // the compiler
+        Type::ImplTrait(_) | Type::Infer(_) | Type::Never(_) => return None,
+        _ => return Some(Input::Arbitrary(Box::new(ty.clone()))),
+    };
+    match &*reference.elem {
+        Type::Slice(slice) if reference.mutability.is_none() && is_ident(&slice.elem, "u8") => {
+            Some(Input::Bytes)
+        }
+        elem if reference.mutability.is_none() && is_ident(elem, "str") => Some(Input::Str),
+        Type::Path(path) if reference.mutability.is_some() => {
+            let last = path.path.segments.last()?;
+            (last.ident == "FuzzedDataProvider").then_some(Input::Provider)
+        }
+        _ => None,
+    }
+}
+
+fn is_ident(ty: &Type, ident: &str) -> bool {
+    matches!(ty, Type::Path(path) if path.qself.is_none() && path.path.is_ident(ident))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_str(item: &str) -> syn::Result<TokenStream> {
+        expand(TokenStream::new(), item.parse().unwrap())
+    }
+
+    fn error_of(item: &str) -> String {
+        expand_str(item).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn expands_bytes_signature() {
+        let tokens = expand_str("fn my_fuzz_test(data: &[u8]) {}")
+            .unwrap()
+            .to_string();
+        assert!(tokens.contains("mod my_fuzz_test"));
+        assert!(tokens.contains("__fuzz_test_harness ! (\"my_fuzz_test\""));
+        assert!(!tokens.contains("FuzzedDataProvider :: new"));
+    }
+
+    #[test]
+    fn expands_provider_signature() {
+        for item in [
+            "fn t(fdp: &mut FuzzedDataProvider) {}",
+            "fn t(fdp: &mut cifuzz::FuzzedDataProvider<'_>) {}",
+        ] {
+            let tokens = expand_str(item).unwrap().to_string();
+            assert!(tokens.contains("FuzzedDataProvider :: new"), "{}", tokens);
+        }
+    }
+
+    #[test]
+    fn expands_arbitrary_signature() {
+        for item in ["fn t(input: Vec<u8>) {}", "fn t((a, b): (u32, String)) {}"] {
+            let tokens = expand_str(item).unwrap().to_string();
+            assert!(
+                tokens.contains("__private :: Input :: <") && tokens.contains(". decode (data)"),
+                "{}",
+                tokens
+            );
+        }
+    }
+
+    #[test]
+    fn expands_str_signature() {
+        let tokens = expand_str("fn t(s: &str) {}").unwrap().to_string();
+        assert!(
+            tokens.contains("str :: from_utf8 (data)") && tokens.contains("super :: t (input)"),
+            "{}",
+            tokens
+        );
+        let tokens = expand(
+            "invalid_utf8 = \"lossy\"".parse().unwrap(),
+            "fn t(s: &str) {}".parse().unwrap(),
+        )
+        .unwrap()
+        .to_string();
+        assert!(
+            tokens.contains("String :: from_utf8_lossy (data)")
+                && tokens.contains("super :: t (& input)"),
+            "{}",
+            tokens
+        );
+    }
+
+    #[test]
+    fn expands_params_signature() {
+        let tokens = expand_str("fn t(a: u32, (b, c): (bool, u8), d: String) {}")
+            .unwrap()
+            .to_string();
+        assert!(
+            tokens.contains("let arg0 : u32 = fdp . consume () ;")
+                && tokens.contains("let arg1 : (bool , u8) = fdp . consume () ;")
+                && tokens.contains("let arg2 : String = fdp . consume () ;")
+                && tokens.contains("super :: t (arg0 , arg1 , arg2)"),
+            "{}",
+            tokens
+        );
+        let tokens = expand_str("async fn t(a: u32, b: u32) {}")
+            .unwrap()
+            .to_string();
+        assert!(
+            tokens.contains("block_on (super :: t (arg0 , arg1))"),
+            "{}",
+            tokens
+        );
+    }
+
+    #[test]
+    fn expands_strategy_signature() {
+        let tokens = expand(
+            "strategy = (0..10u32, any::<bool>())".parse().unwrap(),
+            "fn t((n, b): (u32, bool)) {}".parse().unwrap(),
+        )
+        .unwrap()
+        .to_string();
+        assert!(
+            tokens.contains("fdp . consume_proptest (& ((0 .. 10u32 , any :: < bool > ())))")
+                && tokens.contains("super :: t (input)")
+                && !tokens.contains(". decode (data)"),
+            "{}",
+            tokens
+        );
+    }
+
+    #[test]
+    fn collects_dictionary_tokens() {
+        let tokens = expand_str(
+            "fn t(data: &[u8]) {
+                cifuzz::dictionary![\"FUZZING\", b\"\\x00\"];
+                if data.is_empty() { dictionary!(\"x\",); }
+            }",
+        )
+        .unwrap()
+        .to_string();
+        assert!(
+            tokens.contains("& [b\"FUZZING\" , b\"\\x00\" , b\"x\"]"),
+            "{}",
+            tokens
+        );
+        assert!(error_of("fn t(data: &[u8]) { dictionary![1]; }").contains("string literals"));
+    }
+
+    #[test]
+    fn rejects_invalid_signatures() {
+        assert!(error_of("fn t() {}").contains("must take one argument"));
+        assert!(error_of("fn t(a: &[u8], b: &[u8]) {}").contains("must be owned types"));
+        assert!(error_of("fn t(a: u32, s: &str) {}").contains("instead of `&str`"));
+        assert!(error_of("fn t(data: &mut [u8]) {}").contains("expected an argument"));
+        assert!(error_of("fn t(s: &mut str) {}").contains("expected an argument"));
+        assert!(error_of("fn t(fdp: &FuzzedDataProvider) {}").contains("expected an argument"));
+        assert!(error_of("fn t<T>(data: &[u8]) {}").contains("generic"));
+        assert!(error_of("fn t(data: &[u8]) -> bool { true }").contains("return a value"));
+    }
+
+    fn expand_with_args(attr: &str) -> syn::Result<TokenStream> {
+        expand(
+            attr.parse().unwrap(),
+            "fn t(data: &[u8]) {}".parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn expands_ignored_panics() {
+        let tokens = expand_with_args("ignore_panics = \"invalid input|parse error\"")
+            .unwrap()
+            .to_string();
+        assert!(
+            tokens.contains("& [\"invalid input\" , \"parse error\"]"),
+            "{}",
+            tokens
+        );
+        let tokens = expand_str("fn t(data: &[u8]) {}").unwrap().to_string();
+        assert!(tokens.contains("& [] , & []"), "{}", tokens);
+    }
+
+    #[test]
+    fn expands_mutators() {
+        let tokens = expand_with_args("mutator = json::JsonMutator")
+            .unwrap()
+            .to_string();
+        assert!(
+            tokens.contains("Mutator :: of :: < json :: JsonMutator > ()"),
+            "{}",
+            tokens
+        );
+    }
+
+    #[test]
+    fn expands_init_functions() {
+        let tokens = expand_with_args("init = setup::load_model")
+            .unwrap()
+            .to_string();
+        assert!(
+            tokens.contains("let init : fn () = setup :: load_model ;"),
+            "{}",
+            tokens
+        );
+        let tokens = expand_str("fn t(data: &[u8]) {}").unwrap().to_string();
+        assert!(
+            tokens.contains("Option :: None , :: core :: option :: Option :: None)"),
+            "{}",
+            tokens
+        );
+    }
+
+    #[test]
+    fn expands_async_fuzz_tests() {
+        let tokens = expand_str("async fn t(fdp: &mut FuzzedDataProvider) {}")
+            .unwrap()
+            .to_string();
+        assert!(
+            tokens.contains("__private :: block_on (super :: t (& mut fdp))"),
+            "{}",
+            tokens
+        );
+        assert!(
+            tokens.contains("fdp : & mut FuzzedDataProvider < '_ >"),
+            "{}",
+            tokens
+        );
+        let tokens = expand(
+            "runtime = \"tokio\"".parse().unwrap(),
+            "async fn t(data: &[u8]) {}".parse().unwrap(),
+        )
+        .unwrap()
+        .to_string();
+        assert!(
+            tokens.contains("block_on_tokio (super :: t (data))"),
+            "{}",
+            tokens
+        );
+        let tokens = expand_str("fn t(data: &[u8]) {}").unwrap().to_string();
+        assert!(!tokens.contains("block_on"), "{}", tokens);
+    }
+
+    #[test]
+    fn rejects_arguments() {
+        let error_of = |attr| expand_with_args(attr).unwrap_err().to_string();
+        assert!(error_of("foo").contains("unknown argument"));
+        assert!(error_of("ignore_panics").contains("expected `ignore_panics ="));
+        assert!(error_of("ignore_panics = 1").contains("expected `ignore_panics ="));
+        assert!(error_of("ignore_panics = \"a||b\"").contains("must not be empty"));
+        assert!(error_of("ignore_panics = \"a\", ignore_panics = \"b\"").contains("duplicate"));
+        assert!(error_of("runtime = \"smol\"").contains("unknown runtime"));
+        assert!(error_of("mutator = \"JsonMutator\"").contains("expected `mutator ="));
+        assert!(error_of("mutator = A, mutator = B").contains("duplicate"));
+        assert!(error_of("init = \"setup\"").contains("expected `init ="));
+        assert!(error_of("init = a, init = b").contains("duplicate"));
+        assert!(error_of("runtime = \"tokio\"").contains("only supported for async"));
+        assert!(error_of("invalid_utf8 = \"replace\"").contains("unknown value"));
+        assert!(error_of("invalid_utf8 = \"lossy\"").contains("taking a `&str`"));
+        assert!(error_of("strategy").contains("expected `strategy ="));
+        assert!(error_of("strategy = a(), strategy = b()").contains("duplicate"));
+        assert!(error_of("strategy = any::<u8>()").contains("one owned argument"));
+    }
+}
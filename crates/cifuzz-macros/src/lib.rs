@@ -0,0 +1,80 @@
+//! Procedural macros of the `cifuzz` crate.
+//!
+//! Don't depend on this crate directly, use the re-exports of the
+//! `cifuzz` crate instead.
+
+use proc_macro::TokenStream;
+
+mod fuzz_decode;
+mod fuzz_deserialize;
+mod fuzz_enum;
+mod fuzz_test;
+
+/// Turns a function into a fuzz test. See the documentation of the
+/// `cifuzz` crate for details.
+///
+/// `#[fuzz_test(ignore_panics = "invalid input|parse error")]` makes
+/// panics whose message contains one of the patterns rejections of the
+/// input instead of findings.
+///
+/// Fuzz tests taking several arguments decode them from the input in
+/// declaration order with `cifuzz::ConsumeFromFdp`.
+///
+/// `#[fuzz_test(init = setup)]` calls the function without arguments
+/// once per process before the first input.
+///
+/// `#[fuzz_test(invalid_utf8 = "lossy")]` passes inputs which aren't
+/// valid UTF-8 to a fuzz test taking a `&str` with the invalid sequences
+/// replaced, instead of skipping them.
+///
+/// `#[fuzz_test(mutator = JsonMutator)]` makes libFuzzer mutate the
+/// inputs with the `cifuzz::CustomMutator` implemented by the type.
+///
+/// `#[fuzz_test(strategy = any::<u32>())]` generates the argument of
+/// the fuzz test with the proptest strategy.
+#[proc_macro_attribute]
+pub fn fuzz_test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    fuzz_test::expand(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Generates fuzz tests which deserialize the input into the type with
+/// serde formats and check that the values survive a round trip. See
+/// the documentation of the `cifuzz` crate for details.
+#[proc_macro]
+pub fn fuzz_deserialize(input: TokenStream) -> TokenStream {
+    fuzz_deserialize::expand(input.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `cifuzz::FuzzEnum` for an enum without fields. See the
+/// documentation of the `cifuzz` crate for details.
+#[proc_macro_derive(FuzzEnum)]
+pub fn derive_fuzz_enum(item: TokenStream) -> TokenStream {
+    fuzz_enum::expand(item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `cifuzz::ConsumeFromFdp` for a struct or enum whose fields
+/// implement it. See the documentation of the `cifuzz` crate for
+/// details.
+///
+/// Fields are consumed in declaration order, the variant of an enum is
+/// selected before its fields. The way a field is consumed can be
+/// changed with one of these attributes:
+///
+/// * `#[fuzz(range = min..=max)]`: Consume an integer or float in the
+///   inclusive range.
+/// * `#[fuzz(max_len = n)]`: Consume a `String` of at most `n` bytes of
+///   input or a `Vec` of at most `n` elements.
+/// * `#[fuzz(default)]`: Don't consume the field, use its
+///   `Default::default()` value instead.
+#[proc_macro_derive(FuzzDecode, attributes(fuzz))]
+pub fn derive_fuzz_decode(item: TokenStream) -> TokenStream {
+    fuzz_decode::expand(item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,454 @@
+//! `cifuzz-wasm-driver`, which runs the test executables of fuzz tests
+//! built for wasm32-wasip1 by `cargo cifuzz run --engine wasm`.
+//!
+//! The test executable is a WASI module which is executed by wasmtime
+//! like a native test executable, with the same arguments and
+//! environment. When the harness of a fuzz test starts fuzzing, it calls
+//! the `cifuzz.run_libfuzzer` import, which runs libFuzzer in the
+//! driver: libFuzzer passes every input to the `cifuzz_test_one_input`
+//! export of the module, and the inline 8-bit counters of the
+//! SanitizerCoverage instrumentation of the module are copied to the
+//! counters libFuzzer observes after every input, which is how libFuzzer
+//! gets coverage feedback from code it can't observe directly.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CString};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use wasmtime::error::Context;
+use wasmtime::{
+    bail, Caller, Config, Engine, Error, Extern, Linker, Memory, Module, Result, Store, Trap,
+    TypedFunc, WasmBacktrace, WasmBacktraceDetails,
+};
+use wasmtime_wasi::p1::{self, WasiP1Ctx};
+use wasmtime_wasi::{FsPerms, I32Exit, WasiCtxBuilder};
+
+// Link the libFuzzer runtime which is built by libfuzzer-sys
+use libfuzzer_sys as _;
+
+/// The exit code of a test executable which failed, the same as libtest
+/// uses.
+const FAILURE_EXIT_CODE: i32 = 101;
+
+extern "C" {
+    fn LLVMFuzzerRunDriver(
+        argc: *mut c_int,
+        argv: *mut *mut *mut c_char,
+        user_cb: extern "C" fn(data: *const u8, size: usize) -> c_int,
+    ) -> c_int;
+
+    // Defined by libFuzzer, which observes the counters in the range
+    fn __sanitizer_cov_8bit_counters_init(start: *mut u8, stop: *mut u8);
+}
+
+thread_local! {
+    /// The fuzz test which libFuzzer is running on the thread
+    static HARNESS: RefCell<Option<Harness>> = const { RefCell::new(None) };
+}
+
+/// The exports of the module which libFuzzer executes the inputs with.
+struct Harness {
+    /// The caller of `cifuzz.run_libfuzzer`, which libFuzzer is running
+    /// in and which outlives it
+    caller: *mut Caller<'static, WasiP1Ctx>,
+    memory: Memory,
+    /// Returns a buffer in the module for an input of the size
+    input_buffer: TypedFunc<u32, u32>,
+    /// Executes the fuzz test with the input in the buffer
+    test_one_input: TypedFunc<u32, ()>,
+    /// The inline 8-bit counters in the memory of the module
+    counters: Range<usize>,
+    /// The counters which libFuzzer observes
+    host_counters: &'static mut [u8],
+}
+
+impl Harness {
+    fn run(&mut self, data: &[u8]) -> Result<()> {
+        // SAFETY: The caller outlives LLVMFuzzerRunDriver, which is the
+        // only one calling this
+        let caller = unsafe { &mut *self.caller };
+        let len = u32::try_from(data.len()).context("the input doesn't fit into the module")?;
+        let buffer = self.input_buffer.call(&mut *caller, len)? as usize;
+        self.memory.write(&mut *caller, buffer, data)?;
+        self.memory.data_mut(&mut *caller)[self.counters.clone()].fill(0);
+        let result = self.test_one_input.call(&mut *caller, len);
+        // The memory may have been moved by growing it, so the counters
+        // can't be observed by libFuzzer directly
+        self.host_counters
+            .copy_from_slice(&self.memory.data(&*caller)[self.counters.clone()]);
+        result
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let Some(module) = args.next() else {
+        eprintln!("Usage: cifuzz-wasm-driver <MODULE> [ARGS]...");
+        process::exit(2);
+    };
+    let module = PathBuf::from(module);
+    let args: Vec<String> = args.collect();
+    if let Err(err) = run(&module, &args) {
+        exit_with_error(err);
+    }
+}
+
+/// Executes the `_start` function of the module, i.e. its `main`.
+fn run(path: &Path, args: &[String]) -> Result<()> {
+    let mut config = Config::new();
+    config.wasm_backtrace_details(WasmBacktraceDetails::Enable);
+    let engine = Engine::new(&config)?;
+    let module = load_module(&engine, path)?;
+
+    let mut linker: Linker<WasiP1Ctx> = Linker::new(&engine);
+    p1::add_to_linker_sync(&mut linker, |ctx| ctx)?;
+    let module_path = path.display().to_string();
+    linker.func_wrap(
+        "cifuzz",
+        "run_libfuzzer",
+        move |caller: Caller<'_, WasiP1Ctx>,
+              args: u32,
+              args_len: u32,
+              dictionary: u32,
+              dictionary_len: u32,
+              counters: u32,
+              counters_len: u32| {
+            run_libfuzzer(
+                caller,
+                &module_path,
+                args..args + args_len,
+                dictionary..dictionary + dictionary_len,
+                counters..counters + counters_len,
+            )
+        },
+    )?;
+
+    // The module accesses the files of the host by the same paths,
+    // relative ones in the current directory
+    let current_dir = std::env::current_dir().context("failed to get the current directory")?;
+    let wasi = WasiCtxBuilder::new()
+        .inherit_stdio()
+        .inherit_env()
+        .arg(path.display().to_string())
+        .args(args)
+        .preopened_dir("/", "/", FsPerms::ReadWrite)?
+        .preopened_dir(&current_dir, ".", FsPerms::ReadWrite)?
+        .build_p1();
+    let mut store = Store::new(&engine, wasi);
+    let instance = linker.instantiate(&mut store, &module)?;
+    let start = instance.get_typed_func::<(), ()>(&mut store, "_start")?;
+    start.call(&mut store, ())
+}
+
+/// Compiles the module, or loads it from the `.cwasm` file next to it
+/// which a previous execution compiled it to. Compiling a test
+/// executable takes seconds, and cargo-cifuzz executes it several times,
+/// e.g. to list the fuzz tests in it.
+fn load_module(engine: &Engine, path: &Path) -> Result<Module> {
+    let compiled = path.with_extension("cwasm");
+    let modified = |path: &Path| path.metadata().and_then(|m| m.modified()).ok();
+    if modified(&compiled) >= modified(path) {
+        // SAFETY: The file was written by us below, and files of other
+        // versions of wasmtime or other configs are rejected
+        if let Ok(module) = unsafe { Module::deserialize_file(engine, &compiled) } {
+            return Ok(module);
+        }
+    }
+
+    let module = Module::from_file(engine, path)
+        .with_context(|| format!("failed to compile {}", path.display()))?;
+    // Concurrent executions may compile the module at the same time, so
+    // the file is replaced atomically. Not caching it is no error.
+    let tmp = compiled.with_extension(format!("cwasm.{}", process::id()));
+    if let Ok(bytes) = module.serialize() {
+        if std::fs::write(&tmp, bytes).is_ok() && std::fs::rename(&tmp, &compiled).is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+    }
+    Ok(module)
+}
+
+/// Implements the `cifuzz.run_libfuzzer` import, which the harness of a
+/// fuzz test calls with its libFuzzer arguments (separated by newlines),
+/// the dictionary of its `dictionary!` invocations and the range of its
+/// coverage counters. Returns the exit code of libFuzzer.
+fn run_libfuzzer(
+    mut caller: Caller<'_, WasiP1Ctx>,
+    module_path: &str,
+    args: Range<u32>,
+    dictionary: Range<u32>,
+    counters: Range<u32>,
+) -> Result<i32> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .context("the module doesn't export its memory")?;
+    let read = |caller: &Caller<'_, WasiP1Ctx>, range: Range<u32>| {
+        memory
+            .data(caller)
+            .get(range.start as usize..range.end as usize)
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+            .context("the arguments are out of the bounds of the memory")
+    };
+    let mut args: Vec<String> = std::iter::once(module_path.to_string())
+        .chain(read(&caller, args)?.lines().map(String::from))
+        .filter(|a| !a.is_empty())
+        .collect();
+    let dictionary = read(&caller, dictionary)?;
+    if !dictionary.is_empty() {
+        add_dictionary(&mut args, &dictionary);
+    }
+
+    let export = |caller: &mut Caller<'_, WasiP1Ctx>, name: &str| {
+        caller
+            .get_export(name)
+            .and_then(Extern::into_func)
+            .with_context(|| format!("the module doesn't export {name}"))
+    };
+    let input_buffer = export(&mut caller, "cifuzz_input_buffer")?.typed(&caller)?;
+    let test_one_input = export(&mut caller, "cifuzz_test_one_input")?.typed(&caller)?;
+    let counters = counters.start as usize..counters.end as usize;
+    if memory.data(&caller).get(counters.clone()).is_none() {
+        bail!("the coverage counters are out of the bounds of the memory");
+    }
+    if counters.is_empty() {
+        eprintln!(
+            "warning: the module isn't instrumented for coverage, libFuzzer doesn't get any \
+             feedback"
+        );
+    }
+    let host_counters = Vec::leak(vec![0; counters.len()]);
+    if !host_counters.is_empty() {
+        let range = host_counters.as_mut_ptr_range();
+        unsafe { __sanitizer_cov_8bit_counters_init(range.start, range.end) };
+    }
+
+    HARNESS.with_borrow_mut(|harness| {
+        *harness = Some(Harness {
+            // The lifetime is erased, the caller outlives the harness
+            caller: (&mut caller as *mut Caller<'_, WasiP1Ctx>).cast(),
+            memory,
+            input_buffer,
+            test_one_input,
+            counters,
+            host_counters,
+        })
+    });
+
+    let args: Vec<CString> = args
+        .into_iter()
+        .map(|a| CString::new(a).expect("libFuzzer arguments must not contain NUL bytes"))
+        .collect();
+    // libFuzzer expects a NULL-terminated argv, which it may modify
+    let mut argv: Vec<*mut c_char> = args.iter().map(|a| a.as_ptr() as *mut c_char).collect();
+    argv.push(std::ptr::null_mut());
+    let mut argc = args.len() as c_int;
+    let mut argv_ptr = argv.as_mut_ptr();
+    let status = unsafe { LLVMFuzzerRunDriver(&mut argc, &mut argv_ptr, test_one_input_callback) };
+    HARNESS.with_borrow_mut(|harness| *harness = None);
+    Ok(status)
+}
+
+/// Writes the tokens of the `dictionary!` invocations to a dictionary
+/// file and passes it to libFuzzer. libFuzzer only accepts a single
+/// dictionary, so the one passed by cargo-cifuzz is merged into it. The
+/// harness can't do this itself, because the temporary directory isn't
+/// accessible from WASI.
+fn add_dictionary(args: &mut Vec<String>, dictionary: &str) {
+    let mut dict = String::new();
+    if let Some(pos) = args.iter().position(|a| a.starts_with("-dict=")) {
+        let path = args.remove(pos)["-dict=".len()..].to_string();
+        match std::fs::read_to_string(&path) {
+            Ok(content) => dict.push_str(&content),
+            Err(err) => eprintln!("failed to read {path}: {err}"),
+        }
+        if !dict.is_empty() && !dict.ends_with('\n') {
+            dict.push('\n');
+        }
+    }
+    dict.push_str(dictionary);
+
+    let path = std::env::temp_dir().join(format!("cifuzz-wasm-{}.dict", process::id()));
+    if let Err(err) = std::fs::write(&path, dict) {
+        eprintln!("failed to write {}: {err}", path.display());
+        return;
+    }
+    args.push(format!("-dict={}", path.display()));
+}
+
+extern "C" fn test_one_input_callback(data: *const u8, size: usize) -> c_int {
+    let data = if size == 0 {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(data, size) }
+    };
+    run_input(data);
+    0
+}
+
+fn run_input(data: &[u8]) {
+    let result = HARNESS.with_borrow_mut(|harness| {
+        harness
+            .as_mut()
+            .expect("libFuzzer is not running")
+            .run(data)
+    });
+    if let Err(err) = result {
+        exit_with_error(err);
+    }
+}
+
+/// Called by the `LLVMFuzzerTestOneInput` symbol of libfuzzer-sys,
+/// which isn't used because the inputs are passed to the callback of
+/// `LLVMFuzzerRunDriver`.
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+extern "C" fn rust_fuzzer_test_input(data: &[u8]) -> i32 {
+    run_input(data);
+    0
+}
+
+/// Exits like the test executable would have natively: with its exit
+/// code if it exited, and by aborting if it trapped, which is what a
+/// panic does with `panic = "abort"`, the only panic strategy of
+/// wasm32-wasip1. Aborting makes libFuzzer detect the crash and store
+/// the input.
+fn exit_with_error(err: Error) -> ! {
+    if let Some(exit) = err.downcast_ref::<I32Exit>() {
+        process::exit(exit.0);
+    }
+    let Some(trap) = err.downcast_ref::<Trap>() else {
+        eprintln!("Error: {err:#}");
+        process::exit(FAILURE_EXIT_CODE);
+    };
+    // The panic hook of the module already printed the message of a
+    // panic, which aborts with an unreachable instruction, and the
+    // header of the backtrace, which the standard library can't capture
+    // on wasm
+    let panicked = *trap == Trap::UnreachableCodeReached;
+    if !panicked {
+        eprintln!("wasm trap: {trap}");
+    }
+    let style = BacktraceStyle::from_env();
+    if let Some(backtrace) = err.downcast_ref::<WasmBacktrace>() {
+        if style != BacktraceStyle::Off {
+            if !panicked {
+                eprintln!("stack backtrace:");
+            }
+            eprint!("{}", format_backtrace(backtrace, style));
+        }
+    }
+    process::abort();
+}
+
+/// How backtraces are printed, selected by `RUST_BACKTRACE` like for
+/// the standard library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BacktraceStyle {
+    Off,
+    Short,
+    Full,
+}
+
+impl BacktraceStyle {
+    fn from_env() -> Self {
+        match std::env::var("RUST_BACKTRACE").as_deref() {
+            Ok("full") => BacktraceStyle::Full,
+            Ok("0") | Err(_) => BacktraceStyle::Off,
+            Ok(_) => BacktraceStyle::Short,
+        }
+    }
+}
+
+/// Formats the frames of the backtrace of a trap the way the standard
+/// library formats those of a panic, which the stack trace parser of
+/// cargo-cifuzz understands. Inlined functions are frames of their own.
+fn format_backtrace(backtrace: &WasmBacktrace, style: BacktraceStyle) -> String {
+    let demangle = |name: &str| format!("{:#}", rustc_demangle::demangle(name));
+    let mut frames: Vec<(String, Option<String>)> = Vec::new();
+    for frame in backtrace.frames() {
+        let func_name = frame
+            .func_name()
+            .map(demangle)
+            .unwrap_or_else(|| format!("<wasm function {}>", frame.func_index()));
+        if frame.symbols().is_empty() {
+            frames.push((func_name.clone(), None));
+        }
+        for symbol in frame.symbols() {
+            let name = symbol
+                .name()
+                .map(demangle)
+                .unwrap_or_else(|| func_name.clone());
+            let location = symbol.file().map(|file| {
+                let line = symbol.line().unwrap_or_default();
+                let column = symbol.column().unwrap_or_default();
+                format!("{file}:{line}:{column}")
+            });
+            frames.push((name, location));
+        }
+    }
+    if style == BacktraceStyle::Short {
+        short_backtrace(&mut frames);
+    }
+
+    let mut lines = Vec::new();
+    for (index, (name, location)) in frames.iter().enumerate() {
+        lines.push(format!("{index:>4}: {name}"));
+        if let Some(location) = location {
+            lines.push(format!("             at {location}"));
+        }
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Removes the frames of the panic machinery and of the test harness,
+/// which the standard library marks with the frames of
+/// `__rust_end_short_backtrace` and `__rust_begin_short_backtrace`.
+fn short_backtrace<T>(frames: &mut Vec<(String, T)>) {
+    let is_marker = |frames: &[(String, T)], i: usize, marker: &str| frames[i].0.contains(marker);
+    if let Some(end) =
+        (0..frames.len()).rfind(|&i| is_marker(frames, i, "__rust_end_short_backtrace"))
+    {
+        frames.drain(..=end);
+    }
+    if let Some(begin) =
+        (0..frames.len()).find(|&i| is_marker(frames, i, "__rust_begin_short_backtrace"))
+    {
+        frames.truncate(begin);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_backtraces() {
+        let mut frames: Vec<(String, ())> = [
+            "abort",
+            "std::panicking::panic_with_hook",
+            "std::sys::backtrace::__rust_end_short_backtrace::<std::panicking::panic_handler::{closure#0}, !>",
+            "core::panicking::panic_fmt",
+            "my_crate::my_fuzz_test",
+            "cifuzz_test_one_input",
+            "std::sys::backtrace::__rust_begin_short_backtrace::<fn(), ()>",
+            "main",
+        ]
+        .into_iter()
+        .map(|name| (name.to_string(), ()))
+        .collect();
+        short_backtrace(&mut frames);
+        let names: Vec<&str> = frames.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "core::panicking::panic_fmt",
+                "my_crate::my_fuzz_test",
+                "cifuzz_test_one_input"
+            ]
+        );
+    }
+}
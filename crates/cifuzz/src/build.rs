@@ -0,0 +1,185 @@
+//! Embedding the seed corpora of fuzz tests into the test executable.
+//!
+//! By default, the regression tests read the seed corpus from the
+//! source tree when they are executed. CI pipelines which run the test
+//! executable on another machine or in a sandbox can embed the seed
+//! corpora instead, by calling [`embed_seed_corpus`] in the `main`
+//! function of the build script of the package containing the fuzz
+//! tests:
+//!
+//! ```no_run
+//! cifuzz::build::embed_seed_corpus();
+//! ```
+//!
+//! This requires `cifuzz` to also be a build dependency of the package.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The environment variable via which the build script tells the
+/// `#[fuzz_test]` macro where the generated seed corpus files are.
+// Must be kept in sync with crates/cifuzz-macros/src/fuzz_test.rs
+const SEED_CORPUS_DIR_ENV: &str = "CIFUZZ_SEED_CORPUS_DIR";
+
+/// Embeds the seed corpora of all fuzz tests of the package, i.e. the
+/// `<fuzz_test>_inputs` directories and the directories in `corpus`,
+/// into the test executable. Must be called from a build script.
+///
+/// # Panics
+///
+/// Panics if it's not called from a build script or if the seed corpora
+/// can't be read.
+pub fn embed_seed_corpus() {
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR")
+        .expect("embed_seed_corpus must be called from a build script");
+    let out_dir =
+        std::env::var_os("OUT_DIR").expect("embed_seed_corpus must be called from a build script");
+    let manifest_dir = Path::new(&manifest_dir);
+    let out_dir = Path::new(&out_dir).join("cifuzz-seed-corpus");
+
+    // New inputs and seed corpus directories must cause the build
+    // script to be run again. Paths which don't exist would cause it to
+    // be run on every build.
+    for dir in ["src", "tests", "corpus"] {
+        let dir = manifest_dir.join(dir);
+        if dir.exists() {
+            println!("cargo:rerun-if-changed={}", dir.display());
+        }
+    }
+    generate(manifest_dir, &out_dir).expect("failed to embed the seed corpora");
+    println!(
+        "cargo:rustc-env={SEED_CORPUS_DIR_ENV}={}",
+        out_dir.display()
+    );
+}
+
+/// Writes a file `<fuzz_test>.rs` for every seed corpus directory of the
+/// package, which contains an expression of the type
+/// `&[(&str, &[u8])]`.
+fn generate(manifest_dir: &Path, out_dir: &Path) -> io::Result<()> {
+    if out_dir.exists() {
+        fs::remove_dir_all(out_dir)?;
+    }
+    fs::create_dir_all(out_dir)?;
+
+    // A fuzz test can have both kinds of seed corpus directories
+    let mut corpora: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for (name, dir) in seed_corpus_dirs(manifest_dir)? {
+        list_files(&dir, corpora.entry(name).or_default())?;
+    }
+    for (name, files) in corpora {
+        let mut code = "&[\n".to_string();
+        for file in files {
+            let file = file.canonicalize()?;
+            let path = file
+                .strip_prefix(manifest_dir.canonicalize()?)
+                .unwrap_or(&file);
+            writeln!(
+                code,
+                "    ({:?}, include_bytes!({:?})),",
+                path.display().to_string(),
+                file.display().to_string()
+            )
+            .unwrap();
+        }
+        code.push_str("]\n");
+        fs::write(out_dir.join(format!("{name}.rs")), code)?;
+    }
+    Ok(())
+}
+
+/// Returns the names of the fuzz tests which have a seed corpus in the
+/// package together with the seed corpus directories.
+fn seed_corpus_dirs(manifest_dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut dirs = Vec::new();
+    find_inputs_dirs(manifest_dir, &mut dirs)?;
+    let corpus_dir = manifest_dir.join("corpus");
+    if corpus_dir.is_dir() {
+        for dir in read_dir_sorted(&corpus_dir)? {
+            if let (true, Some(name)) = (dir.is_dir(), dir.file_name()) {
+                dirs.push((name.to_string_lossy().into_owned(), dir));
+            }
+        }
+    }
+    Ok(dirs)
+}
+
+/// Finds the `<fuzz_test>_inputs` directories in the directory, skipping
+/// hidden directories and cargo target directories.
+fn find_inputs_dirs(dir: &Path, dirs: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+    for path in read_dir_sorted(dir)? {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        if !path.is_dir() || file_name.starts_with('.') || file_name == "target" {
+            continue;
+        }
+        match file_name.strip_suffix("_inputs") {
+            Some(name) if !name.is_empty() => dirs.push((name.to_string(), path.clone())),
+            _ => find_inputs_dirs(&path, dirs)?,
+        }
+    }
+    Ok(())
+}
+
+fn list_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for path in read_dir_sorted(dir)? {
+        if path.is_dir() {
+            list_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn read_dir_sorted(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, content: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn generates_seed_corpus_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let package = dir.path().join("package");
+        write(&package.join("src").join("a_inputs").join("seed"), b"a");
+        write(&package.join("corpus").join("b").join("x").join("y"), b"b");
+        write(&package.join("target").join("c_inputs").join("seed"), b"c");
+        let out_dir = dir.path().join("out");
+        write(&out_dir.join("stale.rs"), b"");
+
+        generate(&package, &out_dir).unwrap();
+        let mut generated: Vec<_> = read_dir_sorted(&out_dir)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        generated.sort();
+        assert_eq!(generated, ["a.rs", "b.rs"]);
+
+        let code = fs::read_to_string(out_dir.join("a.rs")).unwrap();
+        let seed = package.join("src").join("a_inputs").join("seed");
+        assert!(code.contains(&format!(
+            "({:?}, include_bytes!({:?}))",
+            Path::new("src")
+                .join("a_inputs")
+                .join("seed")
+                .display()
+                .to_string(),
+            seed.canonicalize().unwrap().display().to_string()
+        )));
+    }
+}
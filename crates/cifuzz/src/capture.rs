@@ -0,0 +1,125 @@
+//! Captures the output of the fuzz test while libFuzzer fuzzes it.
+//!
+//! Printing is slow compared to executing an input, a fuzz test which
+//! prints a few lines per input is executed orders of magnitude less
+//! often than one which doesn't. When cargo-cifuzz enables capturing,
+//! libFuzzer is passed `-close_fd_mask=3`, which makes it print its own
+//! output and the reports of the sanitizers to a duplicate of stderr.
+//! The stdout and stderr of the fuzz test are redirected into a file
+//! instead, which is emptied before every input.
+//!
+//! When the fuzz test panics on an input or the watchdog reports it,
+//! the captured output of the input, which includes the panic message,
+//! is printed to the original stderr after [`OUTPUT_HEADER`] and
+//! capturing stops, so that cargo-cifuzz stores the output with the
+//! finding. The output of inputs which crash with a sanitizer error or
+//! a signal is lost.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+/// The line before the captured output of a crashing input, must be
+/// kept in sync with crates/cargo-cifuzz/src/parser.rs
+const OUTPUT_HEADER: &str = "Output of the fuzz test on the crashing input:";
+
+struct Capture {
+    /// The file into which stdout and stderr are redirected, opened for
+    /// appending so that it can be emptied while they point to it
+    file: File,
+    /// Duplicates of the original stdout and stderr
+    stdout: OwnedFd,
+    stderr: OwnedFd,
+}
+
+static CAPTURE: OnceLock<Capture> = OnceLock::new();
+/// Whether stdout and stderr are redirected into the file
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+
+/// Prepares capturing the output of the fuzz test, which starts with
+/// its first input. Must be called before libFuzzer closes stdout and
+/// stderr. Returns `false` if the output can't be captured.
+pub(crate) fn start() -> bool {
+    let path = std::env::temp_dir().join(format!("cifuzz-output-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let file = match OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create_new(true)
+        .open(&path)
+    {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("failed to create {}: {err}", path.display());
+            return false;
+        }
+    };
+    // The file stays accessible via its file descriptor
+    let _ = std::fs::remove_file(&path);
+    let (Some(stdout), Some(stderr)) = (duplicate(1), duplicate(2)) else {
+        eprintln!(
+            "failed to duplicate stdout and stderr: {}",
+            std::io::Error::last_os_error()
+        );
+        return false;
+    };
+    CAPTURE
+        .set(Capture {
+            file,
+            stdout,
+            stderr,
+        })
+        .is_ok()
+}
+
+fn duplicate(fd: i32) -> Option<OwnedFd> {
+    let duplicate = unsafe { libc::dup(fd) };
+    (duplicate >= 0).then(|| unsafe { OwnedFd::from_raw_fd(duplicate) })
+}
+
+/// Must be called before the fuzz test executes an input.
+pub(crate) fn input_started() {
+    let Some(capture) = CAPTURE.get() else {
+        return;
+    };
+    // libFuzzer closed stdout and stderr before the first input
+    if !CAPTURING.swap(true, Ordering::SeqCst) {
+        unsafe {
+            libc::dup2(capture.file.as_raw_fd(), 1);
+            libc::dup2(capture.file.as_raw_fd(), 2);
+        }
+    }
+    let _ = capture.file.set_len(0);
+}
+
+/// Restores stdout and stderr and prints the output captured for the
+/// current input to stderr. Called when the fuzz test crashes on the
+/// input, so that the report of the crash is printed to stderr as well.
+pub(crate) fn finish() {
+    let Some(capture) = CAPTURE.get() else {
+        return;
+    };
+    if !CAPTURING.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    unsafe {
+        libc::dup2(capture.stdout.as_raw_fd(), 1);
+        libc::dup2(capture.stderr.as_raw_fd(), 2);
+    }
+    let mut output = Vec::new();
+    let mut file = &capture.file;
+    if file.seek(SeekFrom::Start(0)).is_err() || file.read_to_end(&mut output).is_err() {
+        return;
+    }
+    if output.is_empty() {
+        return;
+    }
+    if !output.ends_with(b"\n") {
+        output.push(b'\n');
+    }
+    let mut stderr = std::io::stderr().lock();
+    let _ = writeln!(stderr, "{OUTPUT_HEADER}");
+    let _ = stderr.write_all(&output);
+}
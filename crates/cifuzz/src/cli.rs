@@ -0,0 +1,295 @@
+//! Fuzzing command-line tools through their `main` function.
+//!
+//! An [`Invocation`] is decoded from the input of the fuzz test: the
+//! arguments the tool is called with and the data on its stdin.
+//! [`Invocation::run`] executes a `main` function with it, in which
+//! [`args`], [`args_os`] and [`stdin`] return the arguments and stdin of
+//! the invocation instead of those of the process, and [`exit`] ends the
+//! invocation instead of the process:
+//!
+//! ```
+//! use std::io::Read;
+//!
+//! use cifuzz::{cli, fuzz_test, FuzzedDataProvider};
+//!
+//! fn main() {
+//!     let name = cli::args().nth(1).unwrap_or_default();
+//!     let mut input = String::new();
+//!     if cli::stdin().read_to_string(&mut input).is_err() {
+//!         cli::exit(1);
+//!     }
+//!     // handle the input of the tool
+//!     # let _ = name;
+//! }
+//!
+//! #[fuzz_test]
+//! fn my_tool_fuzz_test(fdp: &mut FuzzedDataProvider) {
+//!     let invocation: cli::Invocation = fdp.consume();
+//!     invocation.run("my-tool", main);
+//! }
+//! ```
+//!
+//! `cargo cifuzz generate --bin` generates such a fuzz test for a binary
+//! of the workspace, with a copy of its `main` function in which the
+//! calls of `std::env::args`, `std::io::stdin`, `std::process::exit`
+//! and the parsers of clap are replaced by these functions.
+//!
+//! Exiting isn't a finding, neither with [`exit`] nor by returning an
+//! error or a failing `ExitCode` from `main`: Tools reject invalid
+//! command lines by design. Outside of [`Invocation::run`], the functions
+//! return the arguments of the process and an empty stdin, and [`exit`]
+//! exits the process.
+
+use std::cell::RefCell;
+use std::ffi::OsString;
+use std::io::{self, BufRead, Read};
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::process::Termination;
+
+use crate::{ConsumeFromFdp, FuzzedDataProvider};
+
+/// The maximum number of arguments of a decoded invocation.
+const MAX_ARGS: usize = 8;
+
+thread_local! {
+    static CURRENT: RefCell<Option<Current>> = const { RefCell::new(None) };
+}
+
+/// The state of the invocation which is being run.
+struct Current {
+    args: Vec<OsString>,
+    stdin: Vec<u8>,
+    /// The number of bytes of stdin which were read
+    pos: usize,
+}
+
+/// The payload of the unwinding by [`exit`].
+struct Exit;
+
+/// The command line of a tool and the data on its stdin, see the
+/// [module documentation](self).
+///
+/// It's decoded by a [`FuzzedDataProvider`] as up to 8 arguments taken
+/// from the front of the input, followed by the remaining bytes as
+/// stdin.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Invocation {
+    /// The arguments, without the name of the program
+    pub args: Vec<String>,
+    pub stdin: Vec<u8>,
+}
+
+impl ConsumeFromFdp for Invocation {
+    fn from_fdp(fdp: &mut FuzzedDataProvider<'_>) -> Self {
+        let args = fdp.consume_vec(MAX_ARGS);
+        let stdin = fdp.consume_remaining_bytes().to_vec();
+        Invocation { args, stdin }
+    }
+}
+
+impl Invocation {
+    /// Executes `main` with the invocation of the program `name`, which
+    /// [`args`] returns as the first argument. Panics of `main` are
+    /// propagated, exits are not.
+    pub fn run<T: Termination>(self, name: &str, main: fn() -> T) {
+        let args = std::iter::once(name.to_string())
+            .chain(self.args)
+            .map(OsString::from)
+            .collect();
+        let current = Current {
+            args,
+            stdin: self.stdin,
+            pos: 0,
+        };
+        let previous = CURRENT.with(|c| c.replace(Some(current)));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            // Prints the error of a failing main like the runtime does
+            let _ = main().report();
+        }));
+        CURRENT.with(|c| c.replace(previous));
+        if let Err(payload) = result {
+            if !payload.is::<Exit>() {
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+}
+
+/// Returns the arguments of the invocation, starting with the name of
+/// the program, like `std::env::args`.
+pub fn args() -> std::vec::IntoIter<String> {
+    args_os()
+        .map(|arg| {
+            arg.into_string()
+                .unwrap_or_else(|arg| arg.to_string_lossy().into_owned())
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+/// Returns the arguments of the invocation, starting with the name of
+/// the program, like `std::env::args_os`.
+pub fn args_os() -> std::vec::IntoIter<OsString> {
+    CURRENT
+        .with(|c| c.borrow().as_ref().map(|current| current.args.clone()))
+        .unwrap_or_else(|| std::env::args_os().collect())
+        .into_iter()
+}
+
+/// Ends the invocation, like `std::process::exit` ends the process.
+/// The exit code is ignored.
+pub fn exit(code: i32) -> ! {
+    if CURRENT.with(|c| c.borrow().is_none()) {
+        std::process::exit(code);
+    }
+    // Unwinds without calling the panic hook, which would report it
+    panic::resume_unwind(Box::new(Exit))
+}
+
+/// Returns a handle to the stdin of the invocation, like
+/// `std::io::stdin`.
+pub fn stdin() -> Stdin {
+    Stdin { _private: () }
+}
+
+/// The stdin of the invocation, see [`stdin`].
+#[derive(Debug)]
+pub struct Stdin {
+    _private: (),
+}
+
+impl Stdin {
+    /// Locks the handle for reading lines and buffered reads, like
+    /// `std::io::Stdin::lock`.
+    pub fn lock(&self) -> StdinLock<'static> {
+        let (data, pos) = CURRENT.with(|c| match &mut *c.borrow_mut() {
+            Some(current) => (std::mem::take(&mut current.stdin), current.pos),
+            None => (Vec::new(), 0),
+        });
+        StdinLock {
+            data,
+            pos,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Reads a line into `buf`, like `std::io::Stdin::read_line`.
+    pub fn read_line(&self, buf: &mut String) -> io::Result<usize> {
+        self.lock().read_line(buf)
+    }
+
+    /// Returns an iterator over the lines, like `std::io::Stdin::lines`.
+    pub fn lines(self) -> io::Lines<StdinLock<'static>> {
+        self.lock().lines()
+    }
+}
+
+impl Read for Stdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.lock().read(buf)
+    }
+}
+
+/// A locked handle to the stdin of the invocation, see
+/// [`Stdin::lock`]. The bytes which weren't read are returned to the
+/// invocation when it's dropped.
+#[derive(Debug)]
+pub struct StdinLock<'a> {
+    data: Vec<u8>,
+    pos: usize,
+    _marker: PhantomData<&'a ()>,
+}
+
+impl Read for StdinLock<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.fill_buf()?.read(buf)?;
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl BufRead for StdinLock<'_> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(&self.data[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.data.len());
+    }
+}
+
+impl Drop for StdinLock<'_> {
+    fn drop(&mut self) {
+        CURRENT.with(|c| {
+            if let Some(current) = &mut *c.borrow_mut() {
+                current.stdin = std::mem::take(&mut self.data);
+                current.pos = self.pos;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invocation(args: &[&str], stdin: &[u8]) -> Invocation {
+        Invocation {
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            stdin: stdin.to_vec(),
+        }
+    }
+
+    #[test]
+    fn run_with_args_and_stdin() {
+        fn main() {
+            let args: Vec<String> = args().collect();
+            assert_eq!(args, ["tool", "-n", "2"]);
+            let mut first = String::new();
+            stdin().read_line(&mut first).unwrap();
+            assert_eq!(first, "a\n");
+            let rest: Vec<String> = stdin().lines().map(Result::unwrap).collect();
+            assert_eq!(rest, ["b", "c"]);
+            let mut end = Vec::new();
+            assert_eq!(stdin().read_to_end(&mut end).unwrap(), 0);
+        }
+        invocation(&["-n", "2"], b"a\nb\nc").run("tool", main);
+        // The process is back to its own arguments afterwards
+        assert_eq!(args().next(), std::env::args().next());
+    }
+
+    #[test]
+    fn exit_ends_the_invocation() {
+        fn main() -> Result<(), String> {
+            if args().len() > 1 {
+                exit(2);
+            }
+            Err("no arguments".to_string())
+        }
+        invocation(&["--help"], b"").run("tool", main);
+        invocation(&[], b"").run("tool", main);
+    }
+
+    #[test]
+    fn panics_are_propagated() {
+        fn main() {
+            panic!("invalid {}", args().nth(1).unwrap());
+        }
+        let result = panic::catch_unwind(|| invocation(&["flag"], b"").run("tool", main));
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<String>().unwrap(), "invalid flag");
+    }
+
+    #[test]
+    fn decode_invocation() {
+        // Two strings from the front and the number of arguments from
+        // the end, the rest is stdin
+        let data = b"--verbose\\ file\\ stdin\x02";
+        let invocation: Invocation = FuzzedDataProvider::new(data).consume();
+        assert_eq!(
+            invocation,
+            self::invocation(&["--verbose", "file"], b"stdin")
+        );
+    }
+}
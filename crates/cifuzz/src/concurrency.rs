@@ -0,0 +1,317 @@
+//! Fuzzing concurrent code with interleavings chosen by the input.
+//!
+//! [`Threads`] runs closures on threads of their own, but only one of
+//! them at a time: a thread runs until it reaches a [`yield_point`],
+//! where the input decides which thread continues. Races whose window is
+//! a few instructions wide, which the threads would hardly ever hit when
+//! they run in parallel, are then just another input the fuzzer finds,
+//! and an input reproduces the same interleaving every time:
+//!
+//! ```
+//! use std::sync::atomic::{AtomicU32, Ordering};
+//!
+//! use cifuzz::concurrency::{yield_point, Threads};
+//! use cifuzz::{fuzz_test, FuzzedDataProvider};
+//!
+//! #[fuzz_test]
+//! fn counter_fuzz_test(fdp: &mut FuzzedDataProvider) {
+//!     let counter = AtomicU32::new(0);
+//!     let increment = || {
+//!         let value = counter.load(Ordering::SeqCst);
+//!         yield_point();
+//!         counter.store(value + 1, Ordering::SeqCst);
+//!     };
+//!     let mut threads = Threads::new(fdp);
+//!     threads.spawn(increment);
+//!     threads.spawn(increment);
+//!     threads.run();
+//!     // Fails for the inputs which switch threads between the load and
+//!     // the store
+//!     # if false {
+//!     assert_eq!(counter.load(Ordering::SeqCst), 2);
+//!     # }
+//! }
+//! ```
+//!
+//! [`yield_point`] does nothing on threads which aren't started by
+//! [`Threads`], so the code under test can call it where interleavings
+//! matter, e.g. under `#[cfg(fuzzing)]`, between taking a snapshot and
+//! publishing an update. A thread which blocks before it reaches the
+//! next yield point, e.g. on a lock held by another thread, can't keep
+//! the others waiting: if it doesn't reach one within 100ms, the next
+//! thread is scheduled and the blocked one continues in parallel once
+//! it's unblocked. Threads which never unblock are a deadlock, which the
+//! fuzzer reports as a timeout.
+//!
+//! With the `loom` feature, `explore` checks all interleavings of a
+//! closure with the loom model checker, for code which uses the
+//! synchronization primitives of loom under `#[cfg(loom)]`.
+
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::FuzzedDataProvider;
+
+/// The number of choices of the next thread which are decoded from the
+/// input, after which the running thread continues at yield points.
+const MAX_CHOICES: usize = 64;
+
+/// The time after which a thread which doesn't reach its next yield
+/// point is considered blocked.
+const BLOCKED_TIMEOUT: Duration = Duration::from_millis(100);
+
+thread_local! {
+    /// The scheduler of the thread and its index, on threads started by
+    /// [`Threads::run`]
+    static SCHEDULED: RefCell<Option<(Arc<Scheduler>, usize)>> = const { RefCell::new(None) };
+}
+
+/// Closures which are run on threads with the interleaving chosen by
+/// the input, see the [module documentation](self).
+pub struct Threads<'env> {
+    closures: Vec<Box<dyn FnOnce() + Send + 'env>>,
+    choices: Vec<u8>,
+}
+
+impl<'env> Threads<'env> {
+    /// Creates an empty set of threads, with up to 64 choices of the
+    /// next thread at yield points decoded from the input.
+    pub fn new(fdp: &mut FuzzedDataProvider) -> Self {
+        Threads {
+            closures: Vec::new(),
+            choices: fdp.consume_vec(MAX_CHOICES),
+        }
+    }
+
+    /// Adds a closure which runs on a thread of its own. The closure may
+    /// borrow from the caller of [`run`](Self::run).
+    pub fn spawn(&mut self, f: impl FnOnce() + Send + 'env) {
+        self.closures.push(Box::new(f));
+    }
+
+    /// Runs the closures until all of them returned. If one of them
+    /// panicked, the first panic is resumed once all of them returned.
+    pub fn run(self) {
+        let scheduler = Arc::new(Scheduler {
+            state: Mutex::new(State {
+                current: None,
+                finished: vec![false; self.closures.len()],
+                choices: self.choices,
+                next_choice: 0,
+                switches: 0,
+            }),
+            turn: Condvar::new(),
+        });
+        scheduler.switch(&mut scheduler.lock(), false);
+
+        let mut panic = None;
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .closures
+                .into_iter()
+                .enumerate()
+                .map(|(index, f)| {
+                    let scheduler = scheduler.clone();
+                    scope.spawn(move || {
+                        SCHEDULED.with(|s| *s.borrow_mut() = Some((scheduler.clone(), index)));
+                        scheduler.wait_for_turn(index);
+                        let result = panic::catch_unwind(AssertUnwindSafe(f));
+                        scheduler.finish(index);
+                        SCHEDULED.with(|s| *s.borrow_mut() = None);
+                        result
+                    })
+                })
+                .collect();
+            for handle in handles {
+                if let Ok(Err(payload)) = handle.join() {
+                    panic.get_or_insert(payload);
+                }
+            }
+        });
+        if let Some(payload) = panic {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+/// Lets the input decide which thread continues, if the current thread
+/// was started by [`Threads`]. Does nothing otherwise.
+pub fn yield_point() {
+    let scheduled = SCHEDULED.with(|s| s.borrow().clone());
+    let Some((scheduler, index)) = scheduled else {
+        return;
+    };
+    {
+        let mut state = scheduler.lock();
+        // A thread which was considered blocked continues without its
+        // turn, so it doesn't choose the next thread, but waits for it
+        if state.current == Some(index) {
+            scheduler.switch(&mut state, false);
+        }
+    }
+    scheduler.wait_for_turn(index);
+}
+
+struct Scheduler {
+    state: Mutex<State>,
+    /// Notified when the current thread changes
+    turn: Condvar,
+}
+
+struct State {
+    /// The thread which is running, none once all threads finished
+    current: Option<usize>,
+    finished: Vec<bool>,
+    choices: Vec<u8>,
+    next_choice: usize,
+    /// The number of times the current thread was chosen, which tells
+    /// waiting threads whether it made progress
+    switches: u64,
+}
+
+impl Scheduler {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Chooses the next thread among the ones which haven't finished,
+    /// by the next choice of the input or, once they're exhausted, the
+    /// current thread if it hasn't finished. A blocked current thread
+    /// isn't chosen.
+    fn switch(&self, state: &mut State, blocked: bool) {
+        let runnable: Vec<usize> = (0..state.finished.len())
+            .filter(|&i| !state.finished[i] && (!blocked || state.current != Some(i)))
+            .collect();
+        state.current = match state.choices.get(state.next_choice) {
+            _ if runnable.is_empty() => None,
+            Some(&choice) => {
+                state.next_choice += 1;
+                Some(runnable[usize::from(choice) % runnable.len()])
+            }
+            None => state
+                .current
+                .filter(|current| runnable.contains(current))
+                .or(Some(runnable[0])),
+        };
+        state.switches += 1;
+        self.turn.notify_all();
+    }
+
+    /// Waits until it's the thread's turn. If the current thread makes no
+    /// progress for [`BLOCKED_TIMEOUT`], the next one is chosen.
+    fn wait_for_turn(&self, index: usize) {
+        let mut state = self.lock();
+        while state.current != Some(index) {
+            let switches = state.switches;
+            let (guard, timeout) = self
+                .turn
+                .wait_timeout(state, BLOCKED_TIMEOUT)
+                .unwrap_or_else(|e| e.into_inner());
+            state = guard;
+            if timeout.timed_out() && state.switches == switches && state.current != Some(index) {
+                self.switch(&mut state, true);
+            }
+        }
+    }
+
+    fn finish(&self, index: usize) {
+        let mut state = self.lock();
+        state.finished[index] = true;
+        if state.current == Some(index) {
+            self.switch(&mut state, false);
+        }
+    }
+}
+
+/// Checks all interleavings of the closure with up to
+/// `preemption_bound` preemptions with the loom model checker, which
+/// panics with the failing interleaving. The closure is executed once
+/// per interleaving, and usually starts threads with `loom::thread`.
+///
+/// ```ignore
+/// #[fuzz_test]
+/// fn queue_fuzz_test(values: Vec<u32>) {
+///     cifuzz::concurrency::explore(2, move || {
+///         let queue = Arc::new(Queue::new());
+///         let producer = loom::thread::spawn({
+///             let (queue, values) = (queue.clone(), values.clone());
+///             move || values.into_iter().for_each(|v| queue.push(v))
+///         });
+///         while queue.pop().is_some() {}
+///         producer.join().unwrap();
+///     });
+/// }
+/// ```
+#[cfg(feature = "loom")]
+pub fn explore(preemption_bound: usize, f: impl Fn() + Sync + Send + 'static) {
+    let mut builder = loom::model::Builder::new();
+    builder.preemption_bound = Some(preemption_bound);
+    builder.check(f);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+    use crate::regression;
+
+    /// Increments the counter on two threads without synchronization and
+    /// returns its value.
+    fn racy_increments(data: &[u8]) -> u32 {
+        let counter = AtomicU32::new(0);
+        let increment = || {
+            let value = counter.load(Ordering::SeqCst);
+            yield_point();
+            counter.store(value + 1, Ordering::SeqCst);
+        };
+        let mut threads = Threads::new(&mut FuzzedDataProvider::new(data));
+        threads.spawn(increment);
+        threads.spawn(increment);
+        threads.run();
+        counter.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn interleavings() {
+        // Without choices, every thread runs to completion
+        assert_eq!(racy_increments(&[]), 2);
+        // Thread 0 starts, then thread 1 runs between its load and store,
+        // the choices are consumed from the end
+        assert_eq!(racy_increments(&[0x00, 0x01, 0x00, 0x03]), 1);
+        // Thread 0 continues after its yield point
+        assert_eq!(racy_increments(&[0x00, 0x00, 0x00, 0x03]), 2);
+    }
+
+    #[test]
+    fn blocked_threads() {
+        // Thread 1 blocks on the lock held by thread 0, which waits for its
+        // turn at the yield point
+        let lock = Mutex::new(());
+        let mut threads = Threads::new(&mut FuzzedDataProvider::new(&[0x01, 0x00, 0x02]));
+        threads.spawn(|| {
+            let _guard = lock.lock().unwrap();
+            yield_point();
+        });
+        threads.spawn(|| drop(lock.lock().unwrap()));
+        threads.run();
+    }
+
+    #[test]
+    fn resume_panics() {
+        let result = panic::catch_unwind(|| {
+            let mut threads = Threads::new(&mut FuzzedDataProvider::new(&[]));
+            threads.spawn(|| {});
+            threads.spawn(|| panic!("invariant violated"));
+            threads.run();
+        });
+        assert_eq!(
+            regression::panic_message(&*result.unwrap_err()),
+            "invariant violated"
+        );
+        // Yield points outside of the threads do nothing
+        yield_point();
+    }
+}
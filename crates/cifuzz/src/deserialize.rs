@@ -0,0 +1,104 @@
+//! The runtime of the fuzz tests generated by `fuzz_deserialize!`,
+//! which deserialize the input with a serde format and check that the
+//! value survives a round trip through the format.
+
+use std::fmt::Debug;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::check_roundtrip;
+
+/// A serde format of `fuzz_deserialize!`.
+pub trait Format {
+    fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, String>;
+
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, String>;
+}
+
+#[cfg(feature = "json")]
+pub struct Json;
+
+#[cfg(feature = "json")]
+impl Format for Json {
+    fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, String> {
+        serde_json::from_slice(data).map_err(|err| err.to_string())
+    }
+
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+        serde_json::to_vec(value).map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(feature = "yaml")]
+pub struct Yaml;
+
+#[cfg(feature = "yaml")]
+impl Format for Yaml {
+    fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, String> {
+        serde_yaml::from_slice(data).map_err(|err| err.to_string())
+    }
+
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+        serde_yaml::to_string(value)
+            .map(String::into_bytes)
+            .map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(feature = "toml")]
+pub struct Toml;
+
+#[cfg(feature = "toml")]
+impl Format for Toml {
+    fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, String> {
+        let data = std::str::from_utf8(data).map_err(|err| err.to_string())?;
+        toml::from_str(data).map_err(|err| err.to_string())
+    }
+
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+        toml::to_string(value)
+            .map(String::into_bytes)
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// Deserializes the input with the format and checks that serializing
+/// the value and deserializing it again returns the same value, see
+/// [`check_roundtrip`].
+#[track_caller]
+pub fn check_deserialize<T, F>(data: &[u8])
+where
+    T: DeserializeOwned + Serialize + PartialEq + Debug,
+    F: Format,
+{
+    check_roundtrip(data, F::deserialize::<T>, F::serialize);
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::panic::{self, AssertUnwindSafe};
+
+    use super::*;
+    use crate::regression;
+
+    type Settings = BTreeMap<String, Vec<u32>>;
+
+    // Expands to the fuzz test deserialize_settings_json, whose regression
+    // test is executed by cargo test
+    crate::fuzz_deserialize!(Settings, [json]);
+
+    #[test]
+    fn check_json_roundtrips() {
+        check_deserialize::<BTreeMap<String, u32>, Json>(br#"{"b": 2, "a": 1}"#);
+        check_deserialize::<BTreeMap<String, u32>, Json>(b"not json");
+
+        // The number overflows to infinity, which is serialized as null
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            check_deserialize::<BTreeMap<String, f32>, Json>(br#"{"a": 1e39}"#)
+        }));
+        let message = regression::panic_message(&*result.unwrap_err());
+        assert!(message.contains("cifuzz finding"), "{message}");
+    }
+}
@@ -0,0 +1,65 @@
+//! Dictionaries, i.e. tokens which libFuzzer inserts into the inputs it
+//! generates. They help to find inputs which contain magic values like
+//! keywords, which are unlikely to be generated byte by byte.
+
+/// Declares tokens of the dictionary of the fuzz test it's used in. The
+/// tokens are string or byte string literals.
+///
+/// The `#[fuzz_test]` macro collects the tokens of all `dictionary!`
+/// invocations in the body of the fuzz test and passes them to
+/// libFuzzer, in addition to the tokens of the `<fuzz_test>.dict` file
+/// and the `--dict` flag of `cargo cifuzz run`. The invocation itself
+/// does nothing, so it can be anywhere in the fuzz test.
+///
+/// ```
+/// use cifuzz::{dictionary, fuzz_test, FuzzedDataProvider};
+///
+/// #[fuzz_test]
+/// fn my_fuzz_test(fdp: &mut FuzzedDataProvider) {
+///     dictionary!["FUZZING", b"\x7fELF"];
+///     let s = fdp.consume_remaining_as_string();
+///     // call the code under test with s
+///     # let _ = s;
+/// }
+/// ```
+#[macro_export]
+macro_rules! dictionary {
+    ($($token:literal),* $(,)?) => {
+        ()
+    };
+}
+
+/// Formats the tokens in the dictionary format of libFuzzer, one quoted
+/// token per line.
+#[cfg_attr(any(not(fuzzing), cifuzz_afl, cifuzz_honggfuzz), allow(dead_code))]
+pub(crate) fn format(tokens: &[&[u8]]) -> String {
+    let mut dict = String::new();
+    for token in tokens {
+        dict.push('"');
+        for &byte in *token {
+            match byte {
+                b'"' | b'\\' => {
+                    dict.push('\\');
+                    dict.push(byte as char);
+                }
+                b' '..=b'~' => dict.push(byte as char),
+                _ => dict.push_str(&format!("\\x{byte:02X}")),
+            }
+        }
+        dict.push_str("\"\n");
+    }
+    dict
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_tokens() {
+        assert_eq!(
+            format(&[b"FUZZING", b"a \"b\" \\c", b"\x7fELF\n"]),
+            "\"FUZZING\"\n\"a \\\"b\\\" \\\\c\"\n\"\\x7FELF\\x0A\"\n"
+        );
+    }
+}
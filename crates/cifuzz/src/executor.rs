@@ -0,0 +1,87 @@
+//! Executing async fuzz tests. Every input is executed to completion on
+//! the thread of the harness, so that the input timeout and the stack
+//! traces of the watchdog apply to async fuzz tests like to the others.
+//!
+//! Without a `runtime` argument of `#[fuzz_test]`, the future is polled
+//! by a minimal executor, which suffices for code which doesn't depend
+//! on the timers or I/O of a runtime, like async parsers. With
+//! `runtime = "tokio"` and `runtime = "async-std"` (and the feature of
+//! the same name), it's executed on a runtime which is reused across
+//! the inputs.
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+/// Wakes the thread which executes the future.
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Polls the future on the current thread until it completes.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+/// Executes the future on a current-thread tokio runtime with all
+/// drivers enabled. Every thread gets a runtime of its own, because the
+/// regression tests of different fuzz tests run in parallel.
+#[cfg(feature = "tokio")]
+pub fn block_on_tokio<F: Future>(future: F) -> F::Output {
+    thread_local! {
+        static RUNTIME: tokio::runtime::Runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to create the tokio runtime of the fuzz test");
+    }
+    RUNTIME.with(|runtime| runtime.block_on(future))
+}
+
+/// Executes the future on the global executor of async-std.
+#[cfg(feature = "async-std")]
+pub fn block_on_async_std<F: Future>(future: F) -> F::Output {
+    async_std::task::block_on(future)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A future which is pending once and wakes itself from another
+    /// thread.
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = u32;
+
+        fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut Context) -> Poll<u32> {
+            if self.0 {
+                return Poll::Ready(42);
+            }
+            self.0 = true;
+            let waker = cx.waker().clone();
+            thread::spawn(move || waker.wake());
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn blocks_on_futures() {
+        assert_eq!(block_on(YieldOnce(false)), 42);
+        assert_eq!(block_on(async { YieldOnce(false).await + 1 }), 43);
+    }
+}
@@ -0,0 +1,1442 @@
+//! A Rust port of libFuzzer's `FuzzedDataProvider`.
+//!
+//! The provider splits the raw input of a fuzz test into values of the
+//! types the code under test expects. Values are decoded exactly like
+//! the C++ implementation in `include/fuzzer/FuzzedDataProvider.h` does
+//! it, so that the same input produces the same values regardless of
+//! the language the fuzz test is written in: Integers are consumed from
+//! the end of the input, bytes and strings from the beginning. The self
+//! tests compare the values of both implementations, and `fdp_fuzz_test`
+//! fuzzes the provider itself.
+//!
+//! The provider is `no_std`. Strings, vectors and the recording of seeds
+//! need the `alloc` feature.
+
+mod consume;
+#[cfg(feature = "alloc")]
+pub(crate) mod record;
+#[cfg(all(test, feature = "std"))]
+mod self_test;
+pub(crate) mod trace;
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::SystemTime;
+
+pub use consume::{ConsumeFromFdp, ConsumeInRange, ConsumeWithMaxLen};
+#[cfg(feature = "alloc")]
+pub use record::SeedValue;
+
+#[cfg(feature = "alloc")]
+use record::{Recording, Value};
+use trace::{Bytes, TypeName};
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Primitive integer types which can be consumed from a
+/// [`FuzzedDataProvider`].
+///
+/// This trait is sealed, it is implemented for all primitive integer
+/// types of at most 64 bits.
+pub trait Integral: Copy + PartialOrd + sealed::Sealed {
+    /// The size of the type in bits.
+    const BITS: u32;
+    /// The smallest value of the type.
+    const MIN: Self;
+    /// The largest value of the type.
+    const MAX: Self;
+
+    /// Converts the value to an `u64`, sign-extending signed values
+    /// like a C++ `static_cast<uint64_t>` does.
+    fn to_u64(self) -> u64;
+
+    /// Converts an `u64` to the type, discarding the high bits.
+    fn from_u64(value: u64) -> Self;
+
+    #[doc(hidden)]
+    fn to_i128(self) -> i128;
+}
+
+macro_rules! impl_integral {
+    ($($unsigned:ty),* ; $($signed:ty),*) => {
+        $(
+            impl sealed::Sealed for $unsigned {}
+            impl Integral for $unsigned {
+                const BITS: u32 = <$unsigned>::BITS;
+                const MIN: Self = <$unsigned>::MIN;
+                const MAX: Self = <$unsigned>::MAX;
+
+                fn to_u64(self) -> u64 {
+                    self as u64
+                }
+
+                fn from_u64(value: u64) -> Self {
+                    value as Self
+                }
+
+                fn to_i128(self) -> i128 {
+                    self as i128
+                }
+            }
+        )*
+        $(
+            impl sealed::Sealed for $signed {}
+            impl Integral for $signed {
+                const BITS: u32 = <$signed>::BITS;
+                const MIN: Self = <$signed>::MIN;
+                const MAX: Self = <$signed>::MAX;
+
+                fn to_u64(self) -> u64 {
+                    self as i64 as u64
+                }
+
+                fn from_u64(value: u64) -> Self {
+                    value as Self
+                }
+
+                fn to_i128(self) -> i128 {
+                    self as i128
+                }
+            }
+        )*
+    };
+}
+
+impl_integral!(u8, u16, u32, u64, usize ; i8, i16, i32, i64, isize);
+
+/// Primitive floating-point types which can be consumed from a
+/// [`FuzzedDataProvider`].
+///
+/// This trait is sealed, it is implemented for `f32` and `f64`.
+pub trait Float: Copy + PartialOrd + sealed::Sealed {
+    /// The smallest finite value of the type.
+    const MIN: Self;
+    /// The largest finite value of the type.
+    const MAX: Self;
+    #[doc(hidden)]
+    const NAN: Self;
+    #[doc(hidden)]
+    const INFINITY: Self;
+    #[doc(hidden)]
+    const NEG_INFINITY: Self;
+
+    #[doc(hidden)]
+    fn consume_in_range(fdp: &mut FuzzedDataProvider<'_>, min: Self, max: Self) -> Self;
+
+    #[doc(hidden)]
+    fn to_f64(self) -> f64;
+
+    /// Returns the half of the range and the probability from which
+    /// `consume_in_range` decodes the value closest to `value`.
+    #[doc(hidden)]
+    fn encode_in_range(value: f64, min: Self, max: Self) -> (Option<bool>, u64);
+}
+
+macro_rules! impl_float {
+    ($($float:ty => $integral:ty),*) => {
+        $(
+            impl sealed::Sealed for $float {}
+            impl Float for $float {
+                const MIN: Self = <$float>::MIN;
+                const MAX: Self = <$float>::MAX;
+                const NAN: Self = <$float>::NAN;
+                const INFINITY: Self = <$float>::INFINITY;
+                const NEG_INFINITY: Self = <$float>::NEG_INFINITY;
+
+                fn consume_in_range(fdp: &mut FuzzedDataProvider<'_>, min: Self, max: Self) -> Self {
+                    let mut result = min;
+                    let range = if max > 0.0 && min < 0.0 && max > min + <$float>::MAX {
+                        // The range doesn't fit into the type, so split
+                        // it into two halves and use one bit to pick one
+                        let range = max / 2.0 - min / 2.0;
+                        if fdp.consume_bool() {
+                            result += range;
+                        }
+                        range
+                    } else {
+                        max - min
+                    };
+                    // Like ConsumeProbability of the C++ implementation
+                    let probability =
+                        fdp.consume_int::<$integral>() as $float / <$integral>::MAX as $float;
+                    result + range * probability
+                }
+
+                fn to_f64(self) -> f64 {
+                    self.into()
+                }
+
+                fn encode_in_range(value: f64, min: Self, max: Self) -> (Option<bool>, u64) {
+                    let value = value as $float;
+                    let (start, range, half) =
+                        if max > 0.0 && min < 0.0 && max > min + <$float>::MAX {
+                            let range = max / 2.0 - min / 2.0;
+                            if value >= min + range {
+                                (min + range, range, Some(true))
+                            } else {
+                                (min, range, Some(false))
+                            }
+                        } else {
+                            (min, max - min, None)
+                        };
+                    let probability = if range > 0.0 {
+                        ((value - start) / range).clamp(0.0, 1.0)
+                    } else {
+                        0.0
+                    };
+                    // Rounded by hand, round() isn't available without
+                    // std. The cast saturates at the maximum
+                    let scaled = probability * <$integral>::MAX as $float;
+                    let truncated = scaled as $integral;
+                    let probability = if scaled - truncated as $float >= 0.5 {
+                        truncated.saturating_add(1)
+                    } else {
+                        truncated
+                    };
+                    (half, probability.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_float!(f32 => u32, f64 => u64);
+
+/// Enums whose variants can be selected with
+/// [`FuzzedDataProvider::consume_enum`].
+///
+/// Derive it for enums without fields:
+///
+/// ```
+/// use cifuzz::{FuzzEnum, FuzzedDataProvider};
+///
+/// #[derive(Debug, PartialEq, FuzzEnum)]
+/// enum Op {
+///     Insert,
+///     Remove,
+///     Clear,
+/// }
+///
+/// let mut fdp = FuzzedDataProvider::new(&[0x04]);
+/// assert_eq!(fdp.consume_enum::<Op>(), Op::Remove);
+/// ```
+pub trait FuzzEnum: Sized {
+    /// The number of variants, which must be at least one.
+    const VARIANT_COUNT: u32;
+
+    /// Returns the variant with the given index in declaration order.
+    fn from_index(index: u32) -> Self;
+}
+
+/// The number of values of the selector consumed before floats if
+/// non-finite floats are enabled. Three of them select NaN and the
+/// infinities, so that one in six floats is not finite.
+const NON_FINITE_FLOAT_SELECTORS: u8 = 18;
+
+/// Decodes values of common types from the input of a fuzz test.
+///
+/// ```
+/// use cifuzz::FuzzedDataProvider;
+///
+/// let mut fdp = FuzzedDataProvider::new(b"FUZZING\x01\x02");
+/// let n: u16 = fdp.consume_int();
+/// assert_eq!(n, 0x0201);
+/// assert_eq!(fdp.consume_remaining_as_string(), "FUZZING");
+/// ```
+#[derive(Debug, Clone)]
+pub struct FuzzedDataProvider<'a> {
+    /// The whole input, of which `data` is the part not consumed yet
+    input: &'a [u8],
+    data: &'a [u8],
+    non_finite_floats: bool,
+    /// The state of a provider created by [`recording`](Self::recording)
+    #[cfg(feature = "alloc")]
+    recording: Option<Box<Recording<'a>>>,
+}
+
+impl<'a> FuzzedDataProvider<'a> {
+    /// Creates a provider which consumes the given input.
+    pub fn new(data: &'a [u8]) -> Self {
+        FuzzedDataProvider {
+            input: data,
+            data,
+            non_finite_floats: false,
+            #[cfg(feature = "alloc")]
+            recording: None,
+        }
+    }
+
+    /// Creates a provider which returns the given values instead of
+    /// decoding them, and records the input from which a regular
+    /// provider decodes the same values, see [`seed!`](crate::seed!).
+    #[doc(hidden)]
+    #[cfg(feature = "alloc")]
+    pub fn recording(values: Vec<Value<'a>>) -> Self {
+        FuzzedDataProvider {
+            recording: Some(Box::new(Recording::new(values))),
+            ..FuzzedDataProvider::new(&[])
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn recorded(&self) -> Option<&Recording<'a>> {
+        self.recording.as_deref()
+    }
+
+    /// Enables NaN and positive and negative infinity as results of
+    /// [`consume_float`](Self::consume_float).
+    ///
+    /// Like the C++ implementation, the provider only returns finite
+    /// floats by default. If enabled, an additional byte is consumed for
+    /// every float to decide whether it is finite.
+    pub fn set_non_finite_floats(&mut self, enabled: bool) {
+        self.non_finite_floats = enabled;
+    }
+
+    /// Returns the number of bytes which were not consumed yet.
+    ///
+    /// A provider recording a seed returns the number of values which
+    /// were not consumed yet instead, which is only zero if the recorded
+    /// input is consumed completely.
+    pub fn remaining_bytes(&self) -> usize {
+        #[cfg(feature = "alloc")]
+        if let Some(recording) = &self.recording {
+            return recording.remaining_values();
+        }
+        self.data.len()
+    }
+
+    /// Consumes an integer of any value of type `T`.
+    ///
+    /// If the input is exhausted, the remaining bits are zero, so with
+    /// an empty input this returns `T::MIN`.
+    pub fn consume_int<T: Integral>(&mut self) -> T {
+        let entered = trace::enter();
+        let value = self.consume_int_in_range(T::MIN, T::MAX);
+        trace::exit(
+            entered,
+            format_args!("consume_int::<{}>()", TypeName(core::any::type_name::<T>())),
+            &value.to_i128(),
+        );
+        value
+    }
+
+    /// Consumes an integer in the inclusive range `[min, max]`.
+    ///
+    /// Only as many bytes as are needed to represent the size of the
+    /// range are consumed, so values in small ranges are cheap for the
+    /// fuzzer to find. If the input is exhausted, the remaining bits are
+    /// zero, so with an empty input this returns `min`.
+    ///
+    /// ```
+    /// use cifuzz::FuzzedDataProvider;
+    ///
+    /// let mut fdp = FuzzedDataProvider::new(&[0x2a, 0x07]);
+    /// assert_eq!(fdp.consume_int_in_range(1, 6), 2);
+    /// assert_eq!(fdp.consume_int_in_range(-100i64, 100), -58);
+    /// assert_eq!(fdp.consume_int_in_range(5u8, 5), 5);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` is greater than `max`.
+    pub fn consume_int_in_range<T: Integral>(&mut self, min: T, max: T) -> T {
+        assert!(min <= max, "min must be smaller than or equal to max");
+
+        let range = max.to_u64().wrapping_sub(min.to_u64());
+        #[cfg(feature = "alloc")]
+        if let Some(recording) = &mut self.recording {
+            let value = recording.next_int("consume_int_in_range");
+            assert!(
+                min.to_i128() <= value && value <= max.to_i128(),
+                "consume_int_in_range can't return {value}, it is not in the range [{}, {}]",
+                min.to_i128(),
+                max.to_i128()
+            );
+            let result = (value - min.to_i128()) as u64;
+            // The most significant byte is consumed first
+            let mut bytes = Vec::new();
+            let mut offset: u32 = 0;
+            while offset < T::BITS && (range >> offset) > 0 {
+                offset += u8::BITS;
+            }
+            while offset > 0 {
+                offset -= u8::BITS;
+                bytes.push((result >> offset) as u8);
+            }
+            recording.record_back(&bytes);
+            return T::from_u64(value as u64);
+        }
+
+        let entered = trace::enter();
+        let mut result: u64 = 0;
+        let mut offset: u32 = 0;
+
+        let before = self.data;
+        while offset < T::BITS && (range >> offset) > 0 {
+            let Some((&byte, rest)) = self.data.split_last() else {
+                break;
+            };
+            self.data = rest;
+            result = (result << u8::BITS) | u64::from(byte);
+            offset += u8::BITS;
+        }
+        trace::record(self.input, before, self.data);
+
+        if range != u64::MAX {
+            result %= range + 1;
+        }
+
+        let value = T::from_u64(min.to_u64().wrapping_add(result));
+        trace::exit(
+            entered,
+            format_args!(
+                "consume_int_in_range::<{}>({}, {})",
+                TypeName(core::any::type_name::<T>()),
+                min.to_i128(),
+                max.to_i128()
+            ),
+            &value.to_i128(),
+        );
+        value
+    }
+
+    /// Consumes a bool. Like `ConsumeBool` of the C++ implementation,
+    /// this is the lowest bit of a `u8`, so that mutations of the other
+    /// bits don't change it.
+    ///
+    /// If the input is exhausted, this returns `false`.
+    pub fn consume_bool(&mut self) -> bool {
+        let entered = trace::enter();
+        let value = self.consume_int::<u8>() & 1 == 1;
+        trace::exit(entered, format_args!("consume_bool()"), &value);
+        value
+    }
+
+    /// Consumes a probability in the inclusive range `[0, 1]`, for
+    /// decisions which should be biased, e.g. corrupting a header in 10%
+    /// of the inputs:
+    ///
+    /// ```
+    /// use cifuzz::FuzzedDataProvider;
+    ///
+    /// let mut fdp = FuzzedDataProvider::new(&[0xff; 8]);
+    /// let corrupt = fdp.consume_probability() < 0.1;
+    /// assert!(!corrupt);
+    /// ```
+    ///
+    /// Like `ConsumeProbability` of the C++ implementation, this is an
+    /// `u64` divided by `u64::MAX`, so that small mutations of the input
+    /// change the probability a little and rarely flip the decision.
+    ///
+    /// If the input is exhausted, this returns 0.
+    pub fn consume_probability(&mut self) -> f64 {
+        let entered = trace::enter();
+        let value = self.consume_float_in_range(0.0, 1.0);
+        trace::exit(entered, format_args!("consume_probability()"), &value);
+        value
+    }
+
+    /// Consumes a float of any finite value of type `T`, or any value
+    /// including NaN and the infinities if enabled via
+    /// [`set_non_finite_floats`](Self::set_non_finite_floats).
+    ///
+    /// If the input is exhausted, this returns `T::MIN`.
+    pub fn consume_float<T: Float>(&mut self) -> T {
+        let entered = trace::enter();
+        let value: T = self.consume_any_float();
+        trace::exit(
+            entered,
+            format_args!(
+                "consume_float::<{}>()",
+                TypeName(core::any::type_name::<T>())
+            ),
+            &value.to_f64(),
+        );
+        value
+    }
+
+    fn consume_any_float<T: Float>(&mut self) -> T {
+        if self.non_finite_floats {
+            // The selector is recorded before the float, which is only
+            // consumed if it is finite
+            #[cfg(feature = "alloc")]
+            if let Some(recording) = &mut self.recording {
+                let selector = match recording.peek("consume_float") {
+                    Value::Float(value) if value.is_nan() => 0,
+                    Value::Float(value) if *value == f64::INFINITY => 1,
+                    Value::Float(value) if *value == f64::NEG_INFINITY => 2,
+                    _ => NON_FINITE_FLOAT_SELECTORS - 1,
+                };
+                if selector < 3 {
+                    recording.next_float("consume_float");
+                }
+                recording.push_front(Value::Int(selector.into()));
+            }
+            match self.consume_int_in_range(0, NON_FINITE_FLOAT_SELECTORS - 1) {
+                0 => return T::NAN,
+                1 => return T::INFINITY,
+                2 => return T::NEG_INFINITY,
+                _ => {}
+            }
+        }
+        self.consume_float_in_range(T::MIN, T::MAX)
+    }
+
+    /// Consumes an `f32`, see [`consume_float`](Self::consume_float).
+    pub fn consume_f32(&mut self) -> f32 {
+        self.consume_float()
+    }
+
+    /// Consumes an `f64`, see [`consume_float`](Self::consume_float).
+    pub fn consume_f64(&mut self) -> f64 {
+        self.consume_float()
+    }
+
+    /// Consumes a float in the inclusive range `[min, max]`.
+    ///
+    /// ```
+    /// use cifuzz::FuzzedDataProvider;
+    ///
+    /// let mut fdp = FuzzedDataProvider::new(&[0x00, 0x00, 0x00, 0x80]);
+    /// assert_eq!(fdp.consume_float_in_range(-1.0f32, 1.0), 0.0);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` is greater than `max` or if one of them is not
+    /// finite.
+    pub fn consume_float_in_range<T: Float>(&mut self, min: T, max: T) -> T {
+        assert!(T::MIN <= min && max <= T::MAX, "min and max must be finite");
+        assert!(min <= max, "min must be smaller than or equal to max");
+        #[cfg(feature = "alloc")]
+        if let Some(recording) = &mut self.recording {
+            let value = recording.next_float("consume_float_in_range");
+            assert!(
+                min.to_f64() <= value && value <= max.to_f64(),
+                "consume_float_in_range can't return {value}, it is not in the range [{}, {}]",
+                min.to_f64(),
+                max.to_f64()
+            );
+            // Recorded as the values which consume_in_range consumes,
+            // which may round the float
+            let (half, probability) = T::encode_in_range(value, min, max);
+            recording.push_front(Value::Int(probability.into()));
+            if let Some(half) = half {
+                recording.push_front(Value::Int(half.into()));
+            }
+        }
+        let entered = trace::enter();
+        let value = T::consume_in_range(self, min, max);
+        trace::exit(
+            entered,
+            format_args!(
+                "consume_float_in_range::<{}>({:?}, {:?})",
+                TypeName(core::any::type_name::<T>()),
+                min.to_f64(),
+                max.to_f64()
+            ),
+            &value.to_f64(),
+        );
+        value
+    }
+
+    /// Consumes up to `num_bytes` bytes. Fewer bytes are returned if the
+    /// input doesn't contain enough data.
+    pub fn consume_bytes(&mut self, num_bytes: usize) -> &'a [u8] {
+        #[cfg(feature = "alloc")]
+        if let Some(recording) = &mut self.recording {
+            let bytes = recording.next_bytes("consume_bytes");
+            assert_eq!(
+                bytes.len(),
+                num_bytes,
+                "consume_bytes can't return {} bytes, {num_bytes} bytes are consumed",
+                bytes.len()
+            );
+            recording.record_front(bytes);
+            return bytes;
+        }
+        let entered = trace::enter();
+        let max_bytes = num_bytes;
+        let num_bytes = num_bytes.min(self.data.len());
+        let (bytes, rest) = self.data.split_at(num_bytes);
+        trace::record(self.input, self.data, rest);
+        self.data = rest;
+        trace::exit(
+            entered,
+            format_args!("consume_bytes({max_bytes})"),
+            &Bytes(bytes),
+        );
+        bytes
+    }
+
+    /// Consumes up to `num_bytes` bytes and appends the terminator, e.g.
+    /// to pass them to code expecting a null-terminated string.
+    #[cfg(feature = "alloc")]
+    pub fn consume_bytes_with_terminator(&mut self, num_bytes: usize, terminator: u8) -> Vec<u8> {
+        let mut bytes = self.consume_bytes(num_bytes).to_vec();
+        bytes.push(terminator);
+        bytes
+    }
+
+    /// Consumes all remaining bytes.
+    pub fn consume_remaining_bytes(&mut self) -> &'a [u8] {
+        #[cfg(feature = "alloc")]
+        if let Some(recording) = &mut self.recording {
+            let bytes = recording.next_bytes("consume_remaining_bytes");
+            recording.record_remaining(bytes);
+            return bytes;
+        }
+        let entered = trace::enter();
+        let bytes = self.consume_bytes(self.data.len());
+        trace::exit(
+            entered,
+            format_args!("consume_remaining_bytes()"),
+            &Bytes(bytes),
+        );
+        bytes
+    }
+
+    /// Picks one of the values of a slice, giving every element the same
+    /// chance. Like `PickValueInArray` of the C++ implementation, the
+    /// index is consumed like an integer.
+    ///
+    /// ```
+    /// use cifuzz::FuzzedDataProvider;
+    ///
+    /// let mut fdp = FuzzedDataProvider::new(&[0x0e]);
+    /// assert_eq!(fdp.pick_value_in_slice(&["GET", "PUT", "POST", "HEAD"]), &"POST");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slice is empty.
+    pub fn pick_value_in_slice<'s, T>(&mut self, values: &'s [T]) -> &'s T {
+        assert!(!values.is_empty(), "values must not be empty");
+        let entered = trace::enter();
+        let index = self.consume_int_in_range(0, values.len() - 1);
+        trace::exit(
+            entered,
+            format_args!("pick_value_in_slice(<{} values>)", values.len()),
+            &format_args!("values[{index}]"),
+        );
+        &values[index]
+    }
+
+    /// Picks one of the values of a slice of values and their weights,
+    /// giving every value a chance proportional to its weight. Values
+    /// with a weight of zero are never picked.
+    ///
+    /// ```
+    /// use cifuzz::FuzzedDataProvider;
+    ///
+    /// #[derive(Debug, PartialEq)]
+    /// enum Header {
+    ///     Valid,
+    ///     Corrupted,
+    /// }
+    ///
+    /// let mut fdp = FuzzedDataProvider::new(&[0x5a]);
+    /// let choices = [(Header::Valid, 9), (Header::Corrupted, 1)];
+    /// assert_eq!(fdp.pick_weighted(&choices), &Header::Valid);
+    /// ```
+    ///
+    /// The choice is consumed like an integer in the range of the sum of
+    /// the weights, each value taking up a contiguous part of it, so
+    /// that small mutations of the input mostly keep the value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sum of the weights is zero.
+    pub fn pick_weighted<'s, T>(&mut self, choices: &'s [(T, u32)]) -> &'s T {
+        let total: u64 = choices.iter().map(|(_, weight)| u64::from(*weight)).sum();
+        assert!(total > 0, "the sum of the weights must be positive");
+        let entered = trace::enter();
+        let mut choice = self.consume_int_in_range(0, total - 1);
+        let index = choices
+            .iter()
+            .position(|(_, weight)| match choice.checked_sub(u64::from(*weight)) {
+                Some(rest) => {
+                    choice = rest;
+                    false
+                }
+                None => true,
+            })
+            .expect("the choice is smaller than the sum of the weights");
+        trace::exit(
+            entered,
+            format_args!("pick_weighted(<{} values>)", choices.len()),
+            &format_args!("values[{index}]"),
+        );
+        &choices[index].0
+    }
+
+    /// Consumes a variant of an enum, see [`FuzzEnum`]. Like `ConsumeEnum`
+    /// of the C++ implementation, the variant index is consumed like an
+    /// `u32`.
+    pub fn consume_enum<E: FuzzEnum>(&mut self) -> E {
+        let entered = trace::enter();
+        let index = self.consume_int_in_range(0, E::VARIANT_COUNT - 1);
+        trace::exit(
+            entered,
+            format_args!(
+                "consume_enum::<{}>()",
+                TypeName(core::any::type_name::<E>())
+            ),
+            &format_args!("variant {index}"),
+        );
+        E::from_index(index)
+    }
+
+    /// Consumes a value of a type implementing `arbitrary::Arbitrary`,
+    /// so that decoders written for cargo-fuzz can be reused.
+    ///
+    /// The bytes used by `Arbitrary` are taken from the front and, for
+    /// lengths, from the end of the input, just like the provider does it.
+    ///
+    /// ```
+    /// use cifuzz::FuzzedDataProvider;
+    ///
+    /// let mut fdp = FuzzedDataProvider::new(&[0x01, 0x02, 0x03]);
+    /// let value: u16 = fdp.consume_arbitrary().unwrap();
+    /// assert_eq!(value, 0x0201);
+    /// assert_eq!(fdp.remaining_bytes(), 1);
+    /// ```
+    #[cfg(feature = "arbitrary")]
+    pub fn consume_arbitrary<T: arbitrary::Arbitrary<'a>>(&mut self) -> arbitrary::Result<T> {
+        assert!(
+            self.recording.is_none(),
+            "consume_arbitrary can't be used by fuzz tests recording a seed"
+        );
+        let entered = trace::enter();
+        let mut u = arbitrary::Unstructured::new(self.data);
+        let value = T::arbitrary(&mut u);
+        let rest = u.take_rest();
+        trace::record(self.input, self.data, rest);
+        self.data = rest;
+        trace::exit(
+            entered,
+            format_args!(
+                "consume_arbitrary::<{}>()",
+                TypeName(core::any::type_name::<T>())
+            ),
+            &format_args!(
+                "{}",
+                match &value {
+                    Ok(_) => "Ok(..)",
+                    Err(_) => "Err(..)",
+                }
+            ),
+        );
+        value
+    }
+
+    /// Generates a value with a `proptest::strategy::Strategy`, so that
+    /// the generators written for property tests can be reused.
+    ///
+    /// The strategy draws its random numbers from the remaining bytes of
+    /// the input instead of a random number generator, followed by
+    /// zeros once they're exhausted. How many bytes it draws isn't
+    /// known, so all of them are consumed. Values aren't shrunk, the
+    /// fuzzer minimizes the input instead. An error is returned if the
+    /// strategy rejects too many values, e.g. with `prop_filter`.
+    ///
+    /// ```
+    /// use cifuzz::FuzzedDataProvider;
+    /// use proptest::prelude::*;
+    ///
+    /// let mut fdp = FuzzedDataProvider::new(&[0x01, 0x02, 0x03]);
+    /// let value = fdp.consume_proptest(&(0..10u8, any::<bool>())).unwrap();
+    /// assert!(value.0 < 10);
+    /// assert_eq!(fdp.remaining_bytes(), 0);
+    /// ```
+    #[cfg(feature = "proptest")]
+    pub fn consume_proptest<S: proptest::strategy::Strategy>(
+        &mut self,
+        strategy: &S,
+    ) -> Result<S::Value, proptest::test_runner::Reason> {
+        use proptest::strategy::ValueTree;
+        use proptest::test_runner::{Config, RngAlgorithm, TestRng, TestRunner};
+
+        assert!(
+            self.recording.is_none(),
+            "consume_proptest can't be used by fuzz tests recording a seed"
+        );
+        let entered = trace::enter();
+        let rng = TestRng::from_seed(RngAlgorithm::PassThrough, self.data);
+        let mut runner = TestRunner::new_with_rng(Config::default(), rng);
+        let value = strategy.new_tree(&mut runner).map(|tree| tree.current());
+        trace::record(self.input, self.data, &[]);
+        self.data = &[];
+        trace::exit(
+            entered,
+            format_args!(
+                "consume_proptest::<{}>()",
+                TypeName(core::any::type_name::<S>())
+            ),
+            &format_args!(
+                "{}",
+                match &value {
+                    Ok(_) => "Ok(..)",
+                    Err(_) => "Err(..)",
+                }
+            ),
+        );
+        value
+    }
+
+    /// Consumes a value of any type implementing [`ConsumeFromFdp`].
+    pub fn consume<T: ConsumeFromFdp>(&mut self) -> T {
+        T::from_fdp(self)
+    }
+
+    /// Consumes a vector of at most `max_len` elements. The length is
+    /// consumed like an integer in the range `[0, max_len]` first. If
+    /// the input is exhausted before all elements are consumed, the
+    /// vector is shorter.
+    ///
+    /// ```
+    /// use cifuzz::FuzzedDataProvider;
+    ///
+    /// let mut fdp = FuzzedDataProvider::new(b"a\\-b\\-c\x02");
+    /// assert_eq!(fdp.consume_vec::<String>(3), ["a", "b"]);
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn consume_vec<T: ConsumeFromFdp>(&mut self, max_len: usize) -> Vec<T> {
+        let len = self.consume_int_in_range(0, max_len);
+        let mut vec = Vec::with_capacity(len.min(self.data.len()));
+        while vec.len() < len && self.remaining_bytes() > 0 {
+            vec.push(T::from_fdp(self));
+        }
+        vec
+    }
+
+    /// Consumes all remaining bytes as a string. Byte sequences which
+    /// are not valid UTF-8 are replaced with U+FFFD.
+    #[cfg(feature = "alloc")]
+    pub fn consume_remaining_as_string(&mut self) -> String {
+        let entered = trace::enter();
+        let bytes = self.consume_remaining_bytes();
+        let value = String::from_utf8_lossy(bytes).into_owned();
+        trace::exit(
+            entered,
+            format_args!("consume_remaining_as_string()"),
+            &value,
+        );
+        value
+    }
+
+    /// Consumes a string of at most `max_len` bytes of input.
+    ///
+    /// Like `ConsumeRandomLengthString` of the C++ implementation, the
+    /// length is encoded in the input itself: A backslash followed by
+    /// another backslash is a single backslash, a backslash followed by
+    /// any other byte terminates the string. Byte sequences which are
+    /// not valid UTF-8 are replaced with U+FFFD, so the string may be
+    /// longer than `max_len` bytes, but all ASCII bytes of the input end
+    /// up unchanged in the string.
+    ///
+    /// ```
+    /// use cifuzz::FuzzedDataProvider;
+    ///
+    /// let mut fdp = FuzzedDataProvider::new(b"FUZZ\\\\ING\\xrest");
+    /// assert_eq!(fdp.consume_string(100), "FUZZ\\ING");
+    /// assert_eq!(fdp.consume_string(2), "re");
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn consume_string(&mut self, max_len: usize) -> String {
+        let entered = trace::enter();
+        let value =
+            String::from_utf8_lossy(&self.consume_random_length_bytes(max_len)).into_owned();
+        trace::exit(entered, format_args!("consume_string({max_len})"), &value);
+        value
+    }
+
+    /// Consumes a string of at most `max_len` ASCII characters, encoded
+    /// like in [`consume_string`](Self::consume_string). The high bit of
+    /// every byte is ignored.
+    #[cfg(feature = "alloc")]
+    pub fn consume_ascii_string(&mut self, max_len: usize) -> String {
+        let entered = trace::enter();
+        let value: String = self
+            .consume_random_length_bytes(max_len)
+            .into_iter()
+            .map(|b| char::from(b & 0x7f))
+            .collect();
+        trace::exit(
+            entered,
+            format_args!("consume_ascii_string({max_len})"),
+            &value,
+        );
+        value
+    }
+
+    /// Consumes a string of at most `max_len` characters, all of which
+    /// are contained in `charset`. The length is encoded like in
+    /// [`consume_string`](Self::consume_string) and every other byte
+    /// selects one of the characters.
+    ///
+    /// ```
+    /// use cifuzz::FuzzedDataProvider;
+    ///
+    /// let mut fdp = FuzzedDataProvider::new(&[0, 1, 2, 3, 4]);
+    /// assert_eq!(fdp.consume_string_from_charset("0123456789abcdef", 4), "0123");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `charset` is empty.
+    #[cfg(feature = "alloc")]
+    pub fn consume_string_from_charset(&mut self, charset: &str, max_len: usize) -> String {
+        let chars: Vec<char> = charset.chars().collect();
+        assert!(!chars.is_empty(), "charset must not be empty");
+        if let Some(recording) = &mut self.recording {
+            let method = "consume_string_from_charset";
+            let value = String::from_utf8_lossy(recording.next_bytes(method));
+            let bytes: Vec<u8> = value
+                .chars()
+                .map(|c| {
+                    chars
+                        .iter()
+                        .position(|&other| other == c)
+                        .and_then(|index| u8::try_from(index).ok())
+                        .unwrap_or_else(|| panic!("{method} can't return {c:?}"))
+                })
+                .collect();
+            recording.record_random_length(method, &bytes, max_len);
+            return value.into_owned();
+        }
+        let entered = trace::enter();
+        let value: String = self
+            .consume_random_length_bytes(max_len)
+            .into_iter()
+            .map(|b| chars[usize::from(b) % chars.len()])
+            .collect();
+        trace::exit(
+            entered,
+            format_args!("consume_string_from_charset({charset:?}, {max_len})"),
+            &value,
+        );
+        value
+    }
+
+    /// Consumes a Unicode scalar value, i.e. any `char`. Like integers,
+    /// chars are consumed from the end of the input.
+    pub fn consume_char(&mut self) -> char {
+        const SURROGATES: u32 = 0xe000 - 0xd800;
+
+        let entered = trace::enter();
+        let value = self.consume_int_in_range(0, char::MAX as u32 - SURROGATES);
+        let value = if value >= 0xd800 {
+            value + SURROGATES
+        } else {
+            value
+        };
+        let value = char::from_u32(value).expect("surrogates are skipped");
+        trace::exit(entered, format_args!("consume_char()"), &value);
+        value
+    }
+
+    /// Consumes a duration of at most `max`. The seconds are consumed
+    /// like an integer in the range of the seconds of `max`, then the
+    /// nanoseconds.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use cifuzz::FuzzedDataProvider;
+    ///
+    /// let mut fdp = FuzzedDataProvider::new(&[0x00, 0x01, 0x1e]);
+    /// assert_eq!(
+    ///     fdp.consume_duration(Duration::from_secs(60)),
+    ///     Duration::new(30, 256)
+    /// );
+    /// ```
+    pub fn consume_duration(&mut self, max: Duration) -> Duration {
+        let entered = trace::enter();
+        let secs = self.consume_int_in_range(0, max.as_secs());
+        let max_nanos = if secs == max.as_secs() {
+            max.subsec_nanos()
+        } else {
+            999_999_999
+        };
+        let value = Duration::new(secs, self.consume_int_in_range(0, max_nanos));
+        trace::exit(entered, format_args!("consume_duration({max:?})"), &value);
+        value
+    }
+
+    /// Consumes a time of at most `max_offset` before or after `base`,
+    /// e.g. the current time or the timestamp of a certificate. A bool
+    /// decides the direction, then the offset is consumed like in
+    /// [`consume_duration`](Self::consume_duration). Times which the
+    /// platform can't represent are replaced with `base`.
+    #[cfg(feature = "std")]
+    pub fn consume_system_time(&mut self, base: SystemTime, max_offset: Duration) -> SystemTime {
+        let entered = trace::enter();
+        let before = self.consume_bool();
+        let offset = self.consume_duration(max_offset);
+        let value = if before {
+            base.checked_sub(offset)
+        } else {
+            base.checked_add(offset)
+        }
+        .unwrap_or(base);
+        trace::exit(
+            entered,
+            format_args!("consume_system_time(<base>, {max_offset:?})"),
+            &value,
+        );
+        value
+    }
+
+    /// Consumes an IPv4 address, which is consumed like an `u32`.
+    pub fn consume_ipv4_addr(&mut self) -> Ipv4Addr {
+        let entered = trace::enter();
+        let value = Ipv4Addr::from(self.consume_int::<u32>());
+        trace::exit(entered, format_args!("consume_ipv4_addr()"), &value);
+        value
+    }
+
+    /// Consumes an IPv6 address, which is consumed like two `u64`s, the
+    /// upper half first.
+    pub fn consume_ipv6_addr(&mut self) -> Ipv6Addr {
+        let entered = trace::enter();
+        let high = u128::from(self.consume_int::<u64>());
+        let low = u128::from(self.consume_int::<u64>());
+        let value = Ipv6Addr::from(high << 64 | low);
+        trace::exit(entered, format_args!("consume_ipv6_addr()"), &value);
+        value
+    }
+
+    /// Consumes an IPv4 or an IPv6 address, the version is decided by a
+    /// bool.
+    ///
+    /// ```
+    /// use std::net::{IpAddr, Ipv4Addr};
+    ///
+    /// use cifuzz::FuzzedDataProvider;
+    ///
+    /// let mut fdp = FuzzedDataProvider::new(&[0x01, 0x00, 0x00, 0x7f, 0x00]);
+    /// assert_eq!(fdp.consume_ip_addr(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+    /// ```
+    pub fn consume_ip_addr(&mut self) -> IpAddr {
+        let entered = trace::enter();
+        let value = if self.consume_bool() {
+            IpAddr::V6(self.consume_ipv6_addr())
+        } else {
+            IpAddr::V4(self.consume_ipv4_addr())
+        };
+        trace::exit(entered, format_args!("consume_ip_addr()"), &value);
+        value
+    }
+
+    /// Consumes a socket address, an IP address of either version like
+    /// [`consume_ip_addr`](Self::consume_ip_addr) followed by the port.
+    /// The flow info and the scope ID of IPv6 addresses are zero.
+    pub fn consume_socket_addr(&mut self) -> SocketAddr {
+        let entered = trace::enter();
+        let value = match self.consume_ip_addr() {
+            IpAddr::V4(ip) => SocketAddr::V4(SocketAddrV4::new(ip, self.consume_int())),
+            IpAddr::V6(ip) => SocketAddr::V6(SocketAddrV6::new(ip, self.consume_int(), 0, 0)),
+        };
+        trace::exit(entered, format_args!("consume_socket_addr()"), &value);
+        value
+    }
+
+    #[cfg(feature = "alloc")]
+    fn consume_random_length_bytes(&mut self, max_len: usize) -> Vec<u8> {
+        if let Some(recording) = &mut self.recording {
+            let bytes = recording.next_bytes("consume_string");
+            recording.record_random_length("consume_string", bytes, max_len);
+            return bytes.to_vec();
+        }
+        let mut bytes = Vec::new();
+        let mut i = 0;
+        while i < self.data.len() && bytes.len() < max_len {
+            let mut byte = self.data[i];
+            i += 1;
+            if byte == b'\\' {
+                match self.data.get(i) {
+                    Some(&b'\\') => {
+                        i += 1;
+                        byte = b'\\';
+                    }
+                    Some(_) => {
+                        i += 1;
+                        break;
+                    }
+                    // A trailing backslash is kept, like in C++
+                    None => {}
+                }
+            }
+            bytes.push(byte);
+        }
+        trace::record(self.input, self.data, &self.data[i..]);
+        self.data = &self.data[i..];
+        bytes
+    }
+}
+
+impl<'a> From<&'a [u8]> for FuzzedDataProvider<'a> {
+    fn from(data: &'a [u8]) -> Self {
+        FuzzedDataProvider::new(data)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consume_int_reads_from_the_end() {
+        let mut fdp = FuzzedDataProvider::new(&[0x01, 0x02, 0x03, 0x04, 0x05]);
+        assert_eq!(fdp.consume_int::<u32>(), 0x05040302);
+        assert_eq!(fdp.remaining_bytes(), 1);
+        assert_eq!(fdp.consume_int::<u8>(), 0x01);
+        assert_eq!(fdp.remaining_bytes(), 0);
+    }
+
+    #[test]
+    fn consume_int_signed() {
+        let mut fdp = FuzzedDataProvider::new(&[0xff, 0xff, 0x00, 0x00]);
+        // Like the C++ implementation, the minimum value is the offset
+        // to which the consumed bits are added.
+        assert_eq!(fdp.consume_int::<i16>(), i16::MIN);
+        assert_eq!(fdp.consume_int::<i16>(), i16::MAX);
+    }
+
+    #[test]
+    fn consume_int_exhausted() {
+        let mut fdp = FuzzedDataProvider::new(&[]);
+        assert_eq!(fdp.consume_int::<u64>(), 0);
+        assert_eq!(fdp.consume_int::<i32>(), i32::MIN);
+
+        // Missing bytes are not padded, the consumed bytes are the
+        // least significant ones
+        let mut fdp = FuzzedDataProvider::new(&[0x2a]);
+        assert_eq!(fdp.consume_int::<u32>(), 0x2a);
+    }
+
+    #[test]
+    fn consume_int_in_range() {
+        // Only the bytes needed for the size of the range are consumed
+        let mut fdp = FuzzedDataProvider::new(&[0x01, 0x02, 0x03]);
+        assert_eq!(fdp.consume_int_in_range(10u32, 10 + 0xff), 10 + 0x03);
+        assert_eq!(fdp.remaining_bytes(), 2);
+        assert_eq!(fdp.consume_int_in_range(0u32, 0x100), 0x0201 % 0x101);
+        assert_eq!(fdp.remaining_bytes(), 0);
+
+        // Ranges spanning negative and positive values
+        let mut fdp = FuzzedDataProvider::new(&[0xff, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(fdp.consume_int_in_range(i32::MIN, i32::MAX), i32::MAX);
+        assert_eq!(fdp.consume_int_in_range(-1i8, 1), -1);
+
+        // Ranges of a single value don't consume any data
+        let mut fdp = FuzzedDataProvider::new(&[0x2a]);
+        assert_eq!(fdp.consume_int_in_range(-7i64, -7), -7);
+        assert_eq!(fdp.remaining_bytes(), 1);
+    }
+
+    #[test]
+    fn consume_int_in_range_exhausted() {
+        let mut fdp = FuzzedDataProvider::new(&[]);
+        assert_eq!(fdp.consume_int_in_range(20000i64, 30000), 20000);
+        assert_eq!(fdp.consume_int_in_range(u64::MIN, u64::MAX), 0);
+        assert_eq!(fdp.consume_int_in_range(-5isize, 5), -5);
+    }
+
+    #[test]
+    #[should_panic(expected = "min must be smaller than or equal to max")]
+    fn consume_int_in_range_invalid() {
+        FuzzedDataProvider::new(&[]).consume_int_in_range(2u8, 1);
+    }
+
+    #[test]
+    fn consume_float_full_range() {
+        assert_eq!(FuzzedDataProvider::new(&[]).consume_f64(), f64::MIN);
+        assert_eq!(FuzzedDataProvider::new(&[]).consume_f32(), f32::MIN);
+
+        // The last byte selects the upper half of the range, the others
+        // the position in it
+        let mut fdp = FuzzedDataProvider::new(&[0xff, 0xff, 0xff, 0xff, 0x01]);
+        assert_eq!(fdp.consume_f32(), f32::MAX);
+        let mut fdp = FuzzedDataProvider::new(&[0x00, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(fdp.consume_f32(), 0.0);
+        assert_eq!(fdp.remaining_bytes(), 0);
+    }
+
+    #[test]
+    fn consume_float_in_range() {
+        let mut fdp = FuzzedDataProvider::new(&[0x00, 0x00, 0x00, 0x80]);
+        assert_eq!(fdp.consume_float_in_range(0.0f32, 1.0), 0.5);
+
+        let mut fdp = FuzzedDataProvider::new(&[0xff; 8]);
+        assert_eq!(fdp.consume_float_in_range(-2.5f64, 10.0), 10.0);
+
+        let mut fdp = FuzzedDataProvider::new(&[0x12, 0x34]);
+        assert_eq!(fdp.consume_float_in_range(3.0f64, 3.0), 3.0);
+        assert!(fdp.consume_float_in_range(-1.0f32, 1.0).abs() <= 1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "min and max must be finite")]
+    fn consume_float_in_range_infinite() {
+        FuzzedDataProvider::new(&[]).consume_float_in_range(0.0, f64::INFINITY);
+    }
+
+    #[test]
+    fn consume_non_finite_floats() {
+        // Finite by default
+        assert_eq!(FuzzedDataProvider::new(&[0x00]).consume_f64(), f64::MIN);
+
+        let mut fdp = FuzzedDataProvider::new(&[0x03, 0x02, 0x01, 0x00]);
+        fdp.set_non_finite_floats(true);
+        assert!(fdp.consume_f64().is_nan());
+        assert_eq!(fdp.consume_f32(), f32::INFINITY);
+        assert_eq!(fdp.consume_f64(), f64::NEG_INFINITY);
+        assert_eq!(fdp.consume_f32(), f32::MIN);
+    }
+
+    #[test]
+    fn consume_bytes() {
+        let mut fdp = FuzzedDataProvider::new(b"abcdef");
+        assert_eq!(fdp.consume_bytes(2), b"ab");
+        assert_eq!(fdp.consume_bytes(0), b"");
+        assert_eq!(fdp.consume_bytes(10), b"cdef");
+        assert_eq!(fdp.consume_bytes(1), b"");
+    }
+
+    #[test]
+    fn consume_bytes_with_terminator() {
+        let mut fdp = FuzzedDataProvider::new(b"abc");
+        assert_eq!(fdp.consume_bytes_with_terminator(2, 0), b"ab\0");
+        assert_eq!(fdp.consume_bytes_with_terminator(2, b'!'), b"c!");
+        assert_eq!(fdp.consume_bytes_with_terminator(2, 0), b"\0");
+    }
+
+    #[test]
+    fn consume_vec() {
+        let mut fdp = FuzzedDataProvider::new(&[0x01, 0x02, 0x03, 0x04, 0x02]);
+        assert_eq!(fdp.consume_vec::<u8>(5), [0x04, 0x03]);
+        assert_eq!(fdp.consume_remaining_bytes(), [0x01, 0x02]);
+
+        // The vector is shorter if the input is exhausted
+        let mut fdp = FuzzedDataProvider::new(&[0x01, 0x00, 0x03]);
+        assert_eq!(fdp.consume_vec::<u16>(3), [0x0001]);
+        assert_eq!(FuzzedDataProvider::new(&[]).consume_vec::<u8>(3), [0u8; 0]);
+    }
+
+    #[test]
+    fn pick_value_in_slice() {
+        let mut fdp = FuzzedDataProvider::new(&[0x02, 0x01, 0x05]);
+        let values = [10, 20, 30];
+        assert_eq!(fdp.pick_value_in_slice(&values), &30);
+        assert_eq!(fdp.pick_value_in_slice(&values), &20);
+        // Single values don't consume any data
+        assert_eq!(fdp.pick_value_in_slice(&[42]), &42);
+        assert_eq!(fdp.remaining_bytes(), 1);
+        assert_eq!(
+            FuzzedDataProvider::new(&[]).pick_value_in_slice(&values),
+            &10
+        );
+    }
+
+    #[test]
+    fn pick_weighted() {
+        let choices = [("valid", 9), ("never", 0), ("corrupted", 1)];
+        let mut fdp = FuzzedDataProvider::new(&[0x09, 0x08, 0x13]);
+        assert_eq!(fdp.pick_weighted(&choices), &"corrupted");
+        assert_eq!(fdp.pick_weighted(&choices), &"valid");
+        assert_eq!(fdp.pick_weighted(&choices), &"corrupted");
+        assert_eq!(
+            FuzzedDataProvider::new(&[]).pick_weighted(&choices),
+            &"valid"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "the sum of the weights must be positive")]
+    fn pick_weighted_without_weights() {
+        FuzzedDataProvider::new(&[]).pick_weighted(&[("never", 0)]);
+    }
+
+    #[test]
+    fn consume_probability() {
+        let mut fdp = FuzzedDataProvider::new(&[0xff; 8]);
+        assert_eq!(fdp.consume_probability(), 1.0);
+        assert_eq!(fdp.consume_probability(), 0.0);
+        let mut fdp = FuzzedDataProvider::new(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80]);
+        assert!((fdp.consume_probability() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    #[should_panic(expected = "values must not be empty")]
+    fn pick_value_in_empty_slice() {
+        FuzzedDataProvider::new(&[]).pick_value_in_slice::<u8>(&[]);
+    }
+
+    #[test]
+    fn consume_enum() {
+        #[derive(Debug, PartialEq)]
+        enum Mode {
+            Read,
+            Write,
+        }
+
+        impl FuzzEnum for Mode {
+            const VARIANT_COUNT: u32 = 2;
+
+            fn from_index(index: u32) -> Self {
+                [Mode::Read, Mode::Write]
+                    .into_iter()
+                    .nth(index as usize)
+                    .unwrap()
+            }
+        }
+
+        let mut fdp = FuzzedDataProvider::new(&[0xff, 0x02, 0x03]);
+        assert_eq!(fdp.consume_enum::<Mode>(), Mode::Write);
+        assert_eq!(fdp.consume_enum::<Mode>(), Mode::Read);
+        assert_eq!(fdp.consume_enum::<Mode>(), Mode::Write);
+        assert_eq!(fdp.consume_enum::<Mode>(), Mode::Read);
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn consume_arbitrary() {
+        let mut fdp = FuzzedDataProvider::new(&[0x01, 0x02, 0x03, 0x04, 0x05]);
+        let value: u32 = fdp.consume_arbitrary().unwrap();
+        assert_eq!(value, 0x04030201);
+        assert_eq!(fdp.consume_int::<u8>(), 0x05);
+
+        // Arbitrary doesn't fail if the input is exhausted
+        let value: (bool, String) = fdp.consume_arbitrary().unwrap();
+        assert_eq!(value, (false, String::new()));
+    }
+
+    #[cfg(feature = "proptest")]
+    #[test]
+    fn consume_proptest() {
+        use proptest::prelude::*;
+
+        // The same input generates the same value
+        let data = [0x3f, 0x00, 0x00, 0xa0, 0x05];
+        let mut fdp = FuzzedDataProvider::new(&data);
+        let value = fdp.consume_proptest(&(0..100u32)).unwrap();
+        assert!(value < 100);
+        assert_eq!(fdp.remaining_bytes(), 0);
+        let mut other = FuzzedDataProvider::new(&data);
+        assert_eq!(other.consume_proptest(&(0..100u32)).unwrap(), value);
+
+        // The strategy draws zeros once the input is exhausted
+        let value = fdp.consume_proptest(&any::<(u64, bool)>()).unwrap();
+        assert_eq!(value, (0, false));
+
+        let never = any::<u8>().prop_filter("never", |_| false);
+        assert!(fdp.consume_proptest(&never).is_err());
+    }
+
+    #[test]
+    fn consume_remaining_as_string() {
+        let mut fdp = FuzzedDataProvider::from(&b"FUZZ\xffING"[..]);
+        assert_eq!(fdp.consume_remaining_as_string(), "FUZZ\u{fffd}ING");
+        assert_eq!(fdp.remaining_bytes(), 0);
+        assert_eq!(fdp.consume_remaining_as_string(), "");
+    }
+
+    #[test]
+    fn consume_string() {
+        let mut fdp = FuzzedDataProvider::new(b"ab\\\\c\\de\\");
+        assert_eq!(fdp.consume_string(10), "ab\\c");
+        assert_eq!(fdp.remaining_bytes(), 2);
+        assert_eq!(fdp.consume_string(10), "e\\");
+        assert_eq!(fdp.consume_string(10), "");
+
+        // The limit applies to the bytes of the input
+        let mut fdp = FuzzedDataProvider::new(b"FUZZ\xffING");
+        assert_eq!(fdp.consume_string(5), "FUZZ\u{fffd}");
+        assert_eq!(fdp.consume_string(0), "");
+        assert_eq!(fdp.consume_string(usize::MAX), "ING");
+    }
+
+    #[test]
+    fn consume_ascii_string() {
+        let mut fdp = FuzzedDataProvider::new(b"F\xd5ZZ\\ING");
+        assert_eq!(fdp.consume_ascii_string(10), "FUZZ");
+        assert_eq!(fdp.consume_ascii_string(10), "NG");
+    }
+
+    #[test]
+    fn consume_string_from_charset() {
+        let mut fdp = FuzzedDataProvider::new(&[0, 1, 2, 3, b'\\', 0, 0xff]);
+        assert_eq!(fdp.consume_string_from_charset("äb", 10), "äbäb");
+        assert_eq!(fdp.consume_string_from_charset("xyz", 10), "x");
+    }
+
+    #[test]
+    fn consume_char() {
+        assert_eq!(FuzzedDataProvider::new(&[]).consume_char(), '\0');
+        assert_eq!(FuzzedDataProvider::new(b"A").consume_char(), 'A');
+        // Surrogates are skipped
+        let mut fdp = FuzzedDataProvider::new(&[0x00, 0xd8, 0x00]);
+        assert_eq!(fdp.consume_char(), '\u{e000}');
+        let mut fdp = FuzzedDataProvider::new(&[0xff, 0xf7, 0x10]);
+        assert_eq!(fdp.consume_char(), char::MAX);
+    }
+
+    #[test]
+    fn consume_duration() {
+        let max = Duration::from_millis(1500);
+        // The nanoseconds are limited by the ones of the maximum if the
+        // seconds are the ones of the maximum
+        let mut fdp = FuzzedDataProvider::new(&[0xff, 0xff, 0xff, 0xff, 0x01]);
+        assert_eq!(fdp.consume_duration(max), Duration::new(1, 294_967_287));
+        let mut fdp = FuzzedDataProvider::new(&[0xff, 0xff, 0xff, 0xff, 0x00]);
+        assert_eq!(fdp.consume_duration(max), Duration::new(0, 294_967_295));
+        assert_eq!(
+            FuzzedDataProvider::new(&[]).consume_duration(max),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn consume_system_time() {
+        let base = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let max_offset = Duration::from_secs(3600);
+        let mut fdp = FuzzedDataProvider::new(&[0x00, 0x00, 0x10, 0x0e, 0x01]);
+        assert_eq!(fdp.consume_system_time(base, max_offset), base - max_offset);
+        let mut fdp = FuzzedDataProvider::new(&[0x2a, 0x00]);
+        assert_eq!(
+            fdp.consume_system_time(base, max_offset),
+            base + Duration::from_secs(0x2a)
+        );
+    }
+
+    #[test]
+    fn consume_ip_addrs() {
+        // The low half is zero once the input is consumed by the high one
+        let mut fdp = FuzzedDataProvider::new(&[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(
+            fdp.consume_ipv6_addr(),
+            Ipv6Addr::new(0, 0, 0, 1, 0, 0, 0, 0)
+        );
+        let mut fdp = FuzzedDataProvider::new(&[0xc0, 0xa8, 0x00, 0x01, 0x01]);
+        assert_eq!(
+            fdp.consume_ip_addr(),
+            IpAddr::V6(Ipv6Addr::new(0, 0, 0x0100, 0xa8c0, 0, 0, 0, 0))
+        );
+    }
+
+    #[test]
+    fn mixed_consumption() {
+        let data = [b'F', b'U', b'Z', b'Z', 0x00, 0x00, 0x00, 0x2a];
+        let mut fdp = FuzzedDataProvider::new(&data);
+        assert_eq!(fdp.consume_int::<u32>(), 0x2a000000);
+        assert_eq!(fdp.consume_remaining_as_string(), "FUZZ");
+    }
+}
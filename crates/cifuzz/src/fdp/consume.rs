@@ -0,0 +1,264 @@
+//! Types which can be consumed from a [`FuzzedDataProvider`] as a whole.
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use core::time::Duration;
+
+use super::FuzzedDataProvider;
+
+/// Types which can be decoded from the input of a fuzz test.
+///
+/// The trait is implemented for the primitive types, strings and
+/// collections of types implementing it, so that [`consume`] and
+/// [`consume_vec`] can be used to decode them. For structs and enums
+/// composed of such types, it can be derived with
+/// [`FuzzDecode`](crate::FuzzDecode).
+///
+/// ```
+/// use cifuzz::FuzzedDataProvider;
+///
+/// let mut fdp = FuzzedDataProvider::new(&[0x01, 0x02, 0x03, 0x02]);
+/// let (a, b): (u8, bool) = fdp.consume();
+/// assert_eq!((a, b), (0x02, true));
+/// assert_eq!(fdp.consume_vec::<u8>(10), vec![0x01]);
+/// ```
+///
+/// [`consume`]: FuzzedDataProvider::consume
+/// [`consume_vec`]: FuzzedDataProvider::consume_vec
+pub trait ConsumeFromFdp: Sized {
+    /// Consumes a value of the type from the provider.
+    fn from_fdp(fdp: &mut FuzzedDataProvider<'_>) -> Self;
+}
+
+macro_rules! impl_consume {
+    ($method:ident: $($ty:ty),*) => {
+        $(
+            impl ConsumeFromFdp for $ty {
+                fn from_fdp(fdp: &mut FuzzedDataProvider<'_>) -> Self {
+                    fdp.$method()
+                }
+            }
+        )*
+    };
+}
+
+impl_consume!(consume_int: u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+impl_consume!(consume_float: f32, f64);
+impl_consume!(consume_bool: bool);
+impl_consume!(consume_char: char);
+impl_consume!(consume_ipv4_addr: Ipv4Addr);
+impl_consume!(consume_ipv6_addr: Ipv6Addr);
+impl_consume!(consume_ip_addr: IpAddr);
+impl_consume!(consume_socket_addr: SocketAddr);
+
+impl ConsumeFromFdp for SocketAddrV4 {
+    fn from_fdp(fdp: &mut FuzzedDataProvider<'_>) -> Self {
+        SocketAddrV4::new(fdp.consume_ipv4_addr(), fdp.consume_int())
+    }
+}
+
+impl ConsumeFromFdp for SocketAddrV6 {
+    fn from_fdp(fdp: &mut FuzzedDataProvider<'_>) -> Self {
+        SocketAddrV6::new(fdp.consume_ipv6_addr(), fdp.consume_int(), 0, 0)
+    }
+}
+
+/// Durations are consumed with [`FuzzedDataProvider::consume_duration`],
+/// up to `Duration::MAX`.
+impl ConsumeFromFdp for Duration {
+    fn from_fdp(fdp: &mut FuzzedDataProvider<'_>) -> Self {
+        fdp.consume_duration(Duration::MAX)
+    }
+}
+
+#[cfg(feature = "alloc")]
+/// Strings are consumed with [`FuzzedDataProvider::consume_string`],
+/// limited only by the remaining input.
+impl ConsumeFromFdp for String {
+    fn from_fdp(fdp: &mut FuzzedDataProvider<'_>) -> Self {
+        fdp.consume_string(usize::MAX)
+    }
+}
+
+#[cfg(feature = "alloc")]
+/// Vectors are consumed with [`FuzzedDataProvider::consume_vec`],
+/// limited only by the remaining input.
+impl<T: ConsumeFromFdp> ConsumeFromFdp for Vec<T> {
+    fn from_fdp(fdp: &mut FuzzedDataProvider<'_>) -> Self {
+        let max_len = fdp.remaining_bytes();
+        fdp.consume_vec(max_len)
+    }
+}
+
+impl<T: ConsumeFromFdp> ConsumeFromFdp for Option<T> {
+    fn from_fdp(fdp: &mut FuzzedDataProvider<'_>) -> Self {
+        fdp.consume_bool().then(|| T::from_fdp(fdp))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ConsumeFromFdp> ConsumeFromFdp for Box<T> {
+    fn from_fdp(fdp: &mut FuzzedDataProvider<'_>) -> Self {
+        Box::new(T::from_fdp(fdp))
+    }
+}
+
+impl<T: ConsumeFromFdp, const N: usize> ConsumeFromFdp for [T; N] {
+    fn from_fdp(fdp: &mut FuzzedDataProvider<'_>) -> Self {
+        core::array::from_fn(|_| T::from_fdp(fdp))
+    }
+}
+
+macro_rules! impl_consume_tuple {
+    ($($name:ident)*) => {
+        impl<$($name: ConsumeFromFdp),*> ConsumeFromFdp for ($($name,)*) {
+            fn from_fdp(fdp: &mut FuzzedDataProvider<'_>) -> Self {
+                // The fields of a tuple expression are evaluated from
+                // left to right
+                ($($name::from_fdp(fdp),)*)
+            }
+        }
+    };
+}
+
+impl_consume_tuple!(A);
+impl_consume_tuple!(A B);
+impl_consume_tuple!(A B C);
+impl_consume_tuple!(A B C D);
+impl_consume_tuple!(A B C D E);
+impl_consume_tuple!(A B C D E F);
+
+/// Types which can be consumed in a range, used by the
+/// `#[fuzz(range = ...)]` attribute of `#[derive(FuzzDecode)]`.
+#[doc(hidden)]
+pub trait ConsumeInRange: Sized {
+    fn consume_in_range(fdp: &mut FuzzedDataProvider<'_>, min: Self, max: Self) -> Self;
+}
+
+macro_rules! impl_consume_in_range {
+    ($method:ident: $($ty:ty),*) => {
+        $(
+            impl ConsumeInRange for $ty {
+                fn consume_in_range(fdp: &mut FuzzedDataProvider<'_>, min: Self, max: Self) -> Self {
+                    fdp.$method(min, max)
+                }
+            }
+        )*
+    };
+}
+
+impl_consume_in_range!(consume_int_in_range: u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+impl_consume_in_range!(consume_float_in_range: f32, f64);
+
+impl ConsumeInRange for Duration {
+    fn consume_in_range(fdp: &mut FuzzedDataProvider<'_>, min: Self, max: Self) -> Self {
+        min + fdp.consume_duration(max.saturating_sub(min))
+    }
+}
+
+/// Types whose length can be limited, used by the
+/// `#[fuzz(max_len = ...)]` attribute of `#[derive(FuzzDecode)]`.
+#[doc(hidden)]
+pub trait ConsumeWithMaxLen: Sized {
+    fn consume_with_max_len(fdp: &mut FuzzedDataProvider<'_>, max_len: usize) -> Self;
+}
+
+#[cfg(feature = "alloc")]
+impl ConsumeWithMaxLen for String {
+    fn consume_with_max_len(fdp: &mut FuzzedDataProvider<'_>, max_len: usize) -> Self {
+        fdp.consume_string(max_len)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ConsumeFromFdp> ConsumeWithMaxLen for Vec<T> {
+    fn consume_with_max_len(fdp: &mut FuzzedDataProvider<'_>, max_len: usize) -> Self {
+        fdp.consume_vec(max_len)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consume_primitives() {
+        let mut fdp = FuzzedDataProvider::new(b"ab\x01\x00\x00\x00\x01");
+        let (a, b, c): (u32, bool, String) = fdp.consume();
+        assert_eq!((a, b, c.as_str()), (0x01000000, true, "ab"));
+        assert_eq!(fdp.remaining_bytes(), 0);
+    }
+
+    #[test]
+    fn consume_containers() {
+        let mut fdp = FuzzedDataProvider::new(&[0x00, 0x03, 0x02, 0x01, 0x00]);
+        let (a, b): (Option<u8>, [u16; 2]) = fdp.consume();
+        assert_eq!(a, None);
+        assert_eq!(b, [0x0102, 0x0300]);
+
+        let mut fdp = FuzzedDataProvider::new(&[]);
+        let v: Vec<Option<Box<i8>>> = fdp.consume();
+        assert!(v.is_empty());
+    }
+
+    #[test]
+    fn consume_std_types() {
+        let mut fdp = FuzzedDataProvider::new(&[0x50, 0x00, 0x01, 0x00, 0x00, 0x7f]);
+        let addr: SocketAddrV4 = fdp.consume();
+        assert_eq!(addr, "127.0.0.1:80".parse().unwrap());
+
+        let mut fdp = FuzzedDataProvider::new(&[0x01; 19]);
+        let addr: SocketAddr = fdp.consume();
+        assert_eq!(
+            addr,
+            "[101:101:101:101:101:101:101:101]:257".parse().unwrap()
+        );
+        assert_eq!(fdp.remaining_bytes(), 0);
+
+        let mut fdp = FuzzedDataProvider::new(&[0xff; 12]);
+        assert_eq!(
+            fdp.consume::<Duration>(),
+            Duration::new(u64::MAX, 294_967_295)
+        );
+        let mut fdp = FuzzedDataProvider::new(&[]);
+        assert_eq!(fdp.consume::<Duration>(), Duration::ZERO);
+    }
+
+    #[derive(Debug, PartialEq, crate::FuzzDecode)]
+    struct Header {
+        #[fuzz(range = 1..=4)]
+        version: u8,
+        #[fuzz(max_len = 3)]
+        name: String,
+        #[fuzz(default)]
+        checksum: Option<u32>,
+        flags: (bool, bool),
+    }
+
+    #[derive(Debug, PartialEq, crate::FuzzDecode)]
+    enum Message {
+        Ping,
+        Data(#[fuzz(max_len = 2)] Vec<u8>),
+    }
+
+    #[test]
+    fn derive_fuzz_decode() {
+        let mut fdp = FuzzedDataProvider::new(b"abcd\x00\x01\x06");
+        assert_eq!(
+            fdp.consume::<Header>(),
+            Header {
+                version: 3,
+                name: "abc".to_string(),
+                checksum: None,
+                flags: (true, false),
+            }
+        );
+        // The remaining "d" isn't used
+        assert_eq!(fdp.remaining_bytes(), 1);
+
+        let mut fdp = FuzzedDataProvider::new(&[0x07, 0x08, 0x02, 0x01]);
+        assert_eq!(fdp.consume::<Message>(), Message::Data(vec![0x08, 0x07]));
+        assert_eq!(fdp.consume::<Message>(), Message::Ping);
+    }
+}
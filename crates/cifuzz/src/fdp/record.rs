@@ -0,0 +1,299 @@
+//! Recording the input which makes a [`FuzzedDataProvider`] return
+//! given values.
+//!
+//! A recording provider doesn't decode an input. Instead, its methods
+//! return the next of the values it was created with and append the
+//! bytes to the input from which a regular provider would decode that
+//! value with the same call, so that running the fuzz test with the
+//! recorded input repeats the run with the values. Like the values
+//! decoded from an input, the bytes of integers are recorded at the
+//! end of the input and the bytes of slices and strings at the front.
+//!
+//! [`FuzzedDataProvider`]: super::FuzzedDataProvider
+
+use alloc::collections::VecDeque;
+use alloc::{string::String, vec::Vec};
+
+use super::trace::Region;
+
+/// A value given to a recording provider, see [`SeedValue`].
+#[doc(hidden)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value<'a> {
+    Int(i128),
+    Float(f64),
+    Bytes(&'a [u8]),
+}
+
+impl Value<'_> {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "an integer",
+            Value::Float(_) => "a float",
+            Value::Bytes(_) => "bytes or a string",
+        }
+    }
+}
+
+/// Values which a fuzz test can consume from the input recorded by
+/// [`seed!`](crate::seed!).
+///
+/// Values are given as the [`FuzzedDataProvider`] consumes them:
+/// Integers for the integer methods and `consume_bool`, which also
+/// take `bool`s, `char`s for `consume_char`, floats for the float
+/// methods and byte slices or strings for the methods consuming bytes
+/// and strings. The lengths of vectors and the indices of picked values
+/// and enum variants are given as integers.
+///
+/// [`FuzzedDataProvider`]: super::FuzzedDataProvider
+pub trait SeedValue<'a> {
+    #[doc(hidden)]
+    fn into_value(self) -> Value<'a>;
+}
+
+macro_rules! impl_seed_value {
+    ($($ty:ty),*) => {
+        $(
+            impl SeedValue<'_> for $ty {
+                fn into_value(self) -> Value<'static> {
+                    Value::Int(self as i128)
+                }
+            }
+        )*
+    };
+}
+
+impl_seed_value!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl SeedValue<'_> for bool {
+    fn into_value(self) -> Value<'static> {
+        Value::Int(self.into())
+    }
+}
+
+/// Chars are recorded like [`consume_char`] consumes them, i.e. as an
+/// integer without the surrogates.
+///
+/// [`consume_char`]: super::FuzzedDataProvider::consume_char
+impl SeedValue<'_> for char {
+    fn into_value(self) -> Value<'static> {
+        let value = u32::from(self);
+        Value::Int(
+            if value >= 0xe000 {
+                value - (0xe000 - 0xd800)
+            } else {
+                value
+            }
+            .into(),
+        )
+    }
+}
+
+impl SeedValue<'_> for f32 {
+    fn into_value(self) -> Value<'static> {
+        Value::Float(self.into())
+    }
+}
+
+impl SeedValue<'_> for f64 {
+    fn into_value(self) -> Value<'static> {
+        Value::Float(self)
+    }
+}
+
+impl<'a> SeedValue<'a> for &'a [u8] {
+    fn into_value(self) -> Value<'a> {
+        Value::Bytes(self)
+    }
+}
+
+impl<'a, const N: usize> SeedValue<'a> for &'a [u8; N] {
+    fn into_value(self) -> Value<'a> {
+        Value::Bytes(self)
+    }
+}
+
+impl<'a> SeedValue<'a> for &'a Vec<u8> {
+    fn into_value(self) -> Value<'a> {
+        Value::Bytes(self)
+    }
+}
+
+impl<'a> SeedValue<'a> for &'a str {
+    fn into_value(self) -> Value<'a> {
+        Value::Bytes(self.as_bytes())
+    }
+}
+
+impl<'a> SeedValue<'a> for &'a String {
+    fn into_value(self) -> Value<'a> {
+        Value::Bytes(self.as_bytes())
+    }
+}
+
+fn wrong_kind(method: &str, value: &Value) -> ! {
+    panic!(
+        "{method} can't consume {}, the values of the seed must be given in the order in which \
+         the fuzz test consumes them",
+        value.kind()
+    )
+}
+
+/// The state of a recording provider.
+#[derive(Debug, Clone)]
+pub(crate) struct Recording<'a> {
+    values: VecDeque<Value<'a>>,
+    /// The bytes consumed from the front of the input
+    front: Vec<u8>,
+    /// The bytes consumed from the end of the input, in the order in
+    /// which they are consumed, i.e. reversed
+    back: Vec<u8>,
+    /// The regions the calls consume, with the offsets of regions at
+    /// the end relative to the end of the input
+    regions: Vec<Region>,
+    /// Whether the remaining bytes were consumed, after which nothing
+    /// can be consumed anymore
+    exhausted: bool,
+}
+
+impl<'a> Recording<'a> {
+    pub fn new(values: Vec<Value<'a>>) -> Self {
+        Recording {
+            values: values.into(),
+            front: Vec::new(),
+            back: Vec::new(),
+            regions: Vec::new(),
+            exhausted: false,
+        }
+    }
+
+    /// The number of values which were not consumed yet.
+    pub fn remaining_values(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns the recorded input.
+    #[cfg(feature = "std")]
+    pub fn input(&self) -> Vec<u8> {
+        let mut input = self.front.clone();
+        input.extend(self.back.iter().rev());
+        input
+    }
+
+    /// Returns the regions which a provider consumes from the recorded
+    /// input, like [`trace`](super::trace) records them.
+    #[cfg(feature = "std")]
+    pub fn regions(&self) -> Vec<Region> {
+        let len = self.front.len() + self.back.len();
+        self.regions
+            .iter()
+            .map(|region| Region {
+                start: if region.from_end {
+                    len - region.start - region.len
+                } else {
+                    region.start
+                },
+                ..*region
+            })
+            .collect()
+    }
+
+    /// Inserts a value which is consumed next, for calls which are
+    /// recorded as other calls.
+    pub fn push_front(&mut self, value: Value<'a>) {
+        self.values.push_front(value);
+    }
+
+    pub fn peek(&self, method: &str) -> &Value<'a> {
+        match self.values.front() {
+            Some(value) => value,
+            None => panic!("{method} was called after all values of the seed were consumed"),
+        }
+    }
+
+    fn next(&mut self, method: &str) -> Value<'a> {
+        assert!(
+            !self.exhausted,
+            "{method} was called after the remaining bytes were consumed"
+        );
+        self.peek(method);
+        self.values.pop_front().unwrap()
+    }
+
+    pub fn next_int(&mut self, method: &str) -> i128 {
+        match self.next(method) {
+            Value::Int(value) => value,
+            value => wrong_kind(method, &value),
+        }
+    }
+
+    pub fn next_float(&mut self, method: &str) -> f64 {
+        match self.next(method) {
+            Value::Float(value) => value,
+            value => wrong_kind(method, &value),
+        }
+    }
+
+    pub fn next_bytes(&mut self, method: &str) -> &'a [u8] {
+        match self.next(method) {
+            Value::Bytes(bytes) => bytes,
+            value => wrong_kind(method, &value),
+        }
+    }
+
+    /// Records bytes consumed from the end of the input, in the order in
+    /// which they are consumed.
+    pub fn record_back(&mut self, bytes: &[u8]) {
+        self.record(bytes, true);
+    }
+
+    /// Records bytes consumed from the front of the input.
+    pub fn record_front(&mut self, bytes: &[u8]) {
+        self.record(bytes, false);
+    }
+
+    /// Records the rest of the input, which is consumed from the front.
+    pub fn record_remaining(&mut self, bytes: &[u8]) {
+        self.record_front(bytes);
+        self.exhausted = true;
+    }
+
+    /// Records the bytes of a string of at most `max_len` bytes, encoded
+    /// like `consume_random_length_bytes` decodes it.
+    pub fn record_random_length(&mut self, method: &str, bytes: &[u8], max_len: usize) {
+        assert!(
+            bytes.len() <= max_len,
+            "{method} can't consume {} bytes, they are longer than max_len {max_len}",
+            bytes.len()
+        );
+        let mut encoded = Vec::with_capacity(bytes.len() + 2);
+        for &byte in bytes {
+            encoded.push(byte);
+            if byte == b'\\' {
+                encoded.push(b'\\');
+            }
+        }
+        // The string ends after max_len bytes anyway
+        if bytes.len() < max_len {
+            encoded.extend_from_slice(b"\\ ");
+        }
+        self.record_front(&encoded);
+    }
+
+    fn record(&mut self, bytes: &[u8], from_end: bool) {
+        if bytes.is_empty() {
+            return;
+        }
+        let part = if from_end {
+            &mut self.back
+        } else {
+            &mut self.front
+        };
+        self.regions.push(Region {
+            start: part.len(),
+            len: bytes.len(),
+            from_end,
+        });
+        part.extend_from_slice(bytes);
+    }
+}
@@ -0,0 +1,131 @@
+// The script of fdp/self_test.rs executed with the C++ implementation of
+// the FuzzedDataProvider, which prints the values it consumes from the
+// input files given as arguments, one block per file, in the same
+// format as the Rust implementation.
+
+#include <cstdint>
+#include <cstdio>
+#include <cstring>
+#include <fstream>
+#include <iterator>
+#include <string>
+#include <vector>
+
+#include <fuzzer/FuzzedDataProvider.h>
+
+enum class Color { Red, Green, Blue, kMaxValue = Blue };
+
+static void PrintBytes(const char *name, const std::vector<uint8_t> &bytes,
+                       uint8_t mask) {
+  std::printf("%s ", name);
+  for (uint8_t byte : bytes) {
+    std::printf("%02x", byte & mask);
+  }
+  std::printf("\n");
+}
+
+static void RunScript(const std::vector<uint8_t> &data) {
+  static const double kRanges[][2] = {
+      {-1.0, 1.0}, {0.0, 1e10}, {-1.7976931348623157e308, 1.7976931348623157e308}};
+  static const int kValues[] = {10, 20, 30, 40, 50};
+
+  FuzzedDataProvider fdp(data.data(), data.size());
+  while (fdp.remaining_bytes() > 0) {
+    switch (fdp.ConsumeIntegralInRange<int32_t>(0, 15)) {
+    case 0:
+      std::printf("u8 %u\n", fdp.ConsumeIntegral<uint8_t>());
+      break;
+    case 1:
+      std::printf("i16 %d\n", fdp.ConsumeIntegral<int16_t>());
+      break;
+    case 2:
+      std::printf("u32 %u\n", fdp.ConsumeIntegral<uint32_t>());
+      break;
+    case 3:
+      std::printf("i64 %lld\n",
+                  static_cast<long long>(fdp.ConsumeIntegral<int64_t>()));
+      break;
+    case 4: {
+      int32_t a = fdp.ConsumeIntegral<int32_t>();
+      int32_t b = fdp.ConsumeIntegral<int32_t>();
+      int32_t min = a < b ? a : b;
+      int32_t max = a < b ? b : a;
+      std::printf("i32 in %d..=%d %d\n", min, max,
+                  fdp.ConsumeIntegralInRange<int32_t>(min, max));
+      break;
+    }
+    case 5: {
+      uint64_t max = fdp.ConsumeIntegral<uint16_t>();
+      std::printf("u64 in 0..=%llu %llu\n", static_cast<unsigned long long>(max),
+                  static_cast<unsigned long long>(
+                      fdp.ConsumeIntegralInRange<uint64_t>(0, max)));
+      break;
+    }
+    case 6: {
+      size_t len = fdp.ConsumeIntegralInRange<size_t>(0, 16);
+      PrintBytes("bytes", fdp.ConsumeBytes<uint8_t>(len), 0xff);
+      break;
+    }
+    case 7: {
+      size_t max_len = fdp.ConsumeIntegralInRange<size_t>(0, 16);
+      std::string s = fdp.ConsumeRandomLengthString(max_len);
+      PrintBytes("string", std::vector<uint8_t>(s.begin(), s.end()), 0x7f);
+      break;
+    }
+    case 8: {
+      double value = fdp.ConsumeFloatingPoint<double>();
+      uint64_t bits;
+      std::memcpy(&bits, &value, sizeof(bits));
+      std::printf("f64 %016llx\n", static_cast<unsigned long long>(bits));
+      break;
+    }
+    case 9: {
+      float value = fdp.ConsumeFloatingPoint<float>();
+      uint32_t bits;
+      std::memcpy(&bits, &value, sizeof(bits));
+      std::printf("f32 %08x\n", bits);
+      break;
+    }
+    case 10: {
+      size_t range = fdp.ConsumeIntegralInRange<size_t>(0, 2);
+      double value =
+          fdp.ConsumeFloatingPointInRange<double>(kRanges[range][0], kRanges[range][1]);
+      uint64_t bits;
+      std::memcpy(&bits, &value, sizeof(bits));
+      std::printf("f64 in range %zu %016llx\n", range,
+                  static_cast<unsigned long long>(bits));
+      break;
+    }
+    case 11:
+      std::printf("pick %d\n", fdp.PickValueInArray(kValues));
+      break;
+    case 12:
+      std::printf("enum %d\n", static_cast<int>(fdp.ConsumeEnum<Color>()));
+      break;
+    case 13:
+      std::printf("bool %d\n", fdp.ConsumeBool() ? 1 : 0);
+      break;
+    case 14: {
+      double value = fdp.ConsumeProbability<double>();
+      uint64_t bits;
+      std::memcpy(&bits, &value, sizeof(bits));
+      std::printf("probability %016llx\n", static_cast<unsigned long long>(bits));
+      break;
+    }
+    default:
+      PrintBytes("remaining", fdp.ConsumeRemainingBytes<uint8_t>(), 0xff);
+      break;
+    }
+  }
+}
+
+int main(int argc, char **argv) {
+  for (int i = 1; i < argc; i++) {
+    std::ifstream file(argv[i], std::ios::binary);
+    std::vector<uint8_t> data((std::istreambuf_iterator<char>(file)),
+                              std::istreambuf_iterator<char>());
+    RunScript(data);
+    std::printf("---\n");
+  }
+  return 0;
+}
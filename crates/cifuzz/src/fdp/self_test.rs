@@ -0,0 +1,178 @@
+//! Self tests of the provider: a fuzz test which calls its methods in
+//! the order decoded from the input and checks their invariants, and a
+//! differential test which checks that the C++ implementation decodes
+//! the same values from the same inputs.
+
+use std::process::Command;
+
+use crate::{fuzz_test, FuzzEnum, FuzzedDataProvider};
+
+/// The ranges of `consume_float_in_range`, picked by the input.
+const FLOAT_RANGES: [(f64, f64); 3] = [(-1.0, 1.0), (0.0, 1e10), (f64::MIN, f64::MAX)];
+
+/// The values of `pick_value_in_slice`.
+const VALUES: [i32; 5] = [10, 20, 30, 40, 50];
+
+#[derive(Debug, FuzzEnum)]
+enum Color {
+    Red,
+    Green,
+    Blue,
+}
+
+/// Calls the methods of the provider in the order decoded from the
+/// input until it is consumed, and returns the consumed values in the
+/// format of `self_test.cc`. Panics if a value violates the contract of
+/// its method, or if more data is consumed than the input holds.
+fn script(data: &[u8]) -> Vec<String> {
+    let mut fdp = FuzzedDataProvider::new(data);
+    // Bytes and strings are consumed from the front
+    let mut front = 0;
+    let mut lines = Vec::new();
+    while fdp.remaining_bytes() > 0 {
+        let remaining = fdp.remaining_bytes();
+        let line = match fdp.consume_int_in_range(0i32, 15) {
+            0 => format!("u8 {}", fdp.consume_int::<u8>()),
+            1 => format!("i16 {}", fdp.consume_int::<i16>()),
+            2 => format!("u32 {}", fdp.consume_int::<u32>()),
+            3 => format!("i64 {}", fdp.consume_int::<i64>()),
+            4 => {
+                let (a, b) = (fdp.consume_int::<i32>(), fdp.consume_int::<i32>());
+                let (min, max) = (a.min(b), a.max(b));
+                let value = fdp.consume_int_in_range(min, max);
+                assert!((min..=max).contains(&value), "{value} not in {min}..={max}");
+                format!("i32 in {min}..={max} {value}")
+            }
+            5 => {
+                let max = u64::from(fdp.consume_int::<u16>());
+                let value = fdp.consume_int_in_range(0, max);
+                assert!(value <= max, "{value} not in 0..={max}");
+                format!("u64 in 0..={max} {value}")
+            }
+            6 => {
+                let len = fdp.consume_int_in_range(0, 16);
+                let bytes = fdp.consume_bytes(len);
+                assert!(bytes.len() <= len);
+                assert_eq!(bytes, &data[front..front + bytes.len()]);
+                front += bytes.len();
+                format!("bytes {}", hex(bytes))
+            }
+            7 => {
+                let max_len = fdp.consume_int_in_range(0, 16);
+                let before = fdp.remaining_bytes();
+                let value = fdp.consume_ascii_string(max_len);
+                assert!(value.len() <= max_len && value.is_ascii(), "{value:?}");
+                front += before - fdp.remaining_bytes();
+                format!("string {}", hex(value.as_bytes()))
+            }
+            8 => {
+                let value = fdp.consume_f64();
+                assert!(value.is_finite(), "{value}");
+                format!("f64 {:016x}", value.to_bits())
+            }
+            9 => {
+                let value = fdp.consume_f32();
+                assert!(value.is_finite(), "{value}");
+                format!("f32 {:08x}", value.to_bits())
+            }
+            10 => {
+                let range = fdp.consume_int_in_range(0, FLOAT_RANGES.len() - 1);
+                let (min, max) = FLOAT_RANGES[range];
+                let value = fdp.consume_float_in_range(min, max);
+                assert!((min..=max).contains(&value), "{value} not in {min}..={max}");
+                format!("f64 in range {range} {:016x}", value.to_bits())
+            }
+            11 => format!("pick {}", fdp.pick_value_in_slice(&VALUES)),
+            12 => format!("enum {}", fdp.consume_enum::<Color>() as i32),
+            13 => format!("bool {}", u8::from(fdp.consume_bool())),
+            14 => {
+                let value = fdp.consume_probability();
+                assert!((0.0..=1.0).contains(&value), "{value}");
+                format!("probability {:016x}", value.to_bits())
+            }
+            _ => {
+                let bytes = fdp.consume_remaining_bytes();
+                assert_eq!(bytes.len(), remaining - 1);
+                assert_eq!(bytes, &data[front..front + bytes.len()]);
+                front += bytes.len();
+                format!("remaining {}", hex(bytes))
+            }
+        };
+        assert!(fdp.remaining_bytes() < remaining, "{line} consumed no data");
+        assert!(front + fdp.remaining_bytes() <= data.len());
+        lines.push(line);
+    }
+    lines
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[fuzz_test]
+fn fdp_fuzz_test(data: &[u8]) {
+    script(data);
+}
+
+/// Returns pseudo-random inputs of up to 128 bytes. Every other input
+/// consists of few different bytes, which contain backslashes and the
+/// extremes much more often.
+fn inputs(count: usize) -> Vec<Vec<u8>> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    (0..count)
+        .map(|i| {
+            let len = next() as usize % 129;
+            (0..len)
+                .map(|_| match i % 2 {
+                    0 => next() as u8,
+                    _ => [0x00, 0x01, b'\\', b'a', 0x7f, 0x80, 0xff][next() as usize % 7],
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn decode_like_cpp() {
+    let cxx = std::env::var("CXX").unwrap_or_else(|_| "c++".to_string());
+    let dir = tempfile::tempdir().unwrap();
+    let driver = dir.path().join("self_test");
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let status = Command::new(&cxx)
+        .args(["-std=c++17", "-O1", "-I"])
+        .arg(format!("{manifest_dir}/../../include"))
+        .arg(format!("{manifest_dir}/src/fdp/self_test.cc"))
+        .arg("-o")
+        .arg(&driver)
+        .status();
+    match status {
+        Ok(status) => assert!(status.success(), "{cxx} failed to compile self_test.cc"),
+        Err(err) => {
+            eprintln!("Skipping the comparison with the C++ implementation, {cxx} failed: {err}");
+            return;
+        }
+    }
+
+    let inputs = inputs(512);
+    let mut files = Vec::new();
+    for (i, input) in inputs.iter().enumerate() {
+        let file = dir.path().join(format!("input-{i}"));
+        std::fs::write(&file, input).unwrap();
+        files.push(file);
+    }
+    let output = Command::new(&driver).args(&files).output().unwrap();
+    assert!(output.status.success());
+    let output = String::from_utf8(output.stdout).unwrap();
+    let blocks: Vec<&str> = output.split_terminator("---\n").collect();
+    assert_eq!(blocks.len(), inputs.len());
+    for (input, block) in inputs.iter().zip(blocks) {
+        let expected: Vec<&str> = block.lines().collect();
+        assert_eq!(script(input), expected, "input {}", hex(input));
+    }
+}
@@ -0,0 +1,278 @@
+//! Recording which parts of the input a [`FuzzedDataProvider`] consumes.
+//!
+//! `cargo cifuzz minimize` uses the [`Call`]s which consumed the regions
+//! to shrink crashing inputs along the boundaries and types of the
+//! consumed values instead of only removing arbitrary bytes, and
+//! `cargo cifuzz run --trace` prints them with the values they returned.
+//! Recording is disabled unless the harness enables it for the current
+//! thread. Without std, nothing is recorded.
+//!
+//! [`FuzzedDataProvider`]: super::FuzzedDataProvider
+
+use core::fmt;
+#[cfg(feature = "std")]
+use std::cell::{Cell, RefCell};
+
+/// A part of the input which was consumed by a single call.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    /// The offset of the region in the input
+    pub start: usize,
+    pub len: usize,
+    /// Whether the region was consumed from the end, like integers
+    pub from_end: bool,
+}
+
+/// A call of a method of the provider which isn't made by another one,
+/// e.g. a `consume_int` but not the `consume_int_in_range` it calls.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    /// The method with its type and arguments, e.g.
+    /// `consume_int::<u32>()`
+    pub method: String,
+    /// The returned value, formatted with `Debug`
+    pub value: String,
+    /// The regions of the input consumed by the call
+    pub regions: Vec<Region>,
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+    static REGIONS: RefCell<Vec<Region>> = const { RefCell::new(Vec::new()) };
+    static CALLS_ENABLED: Cell<bool> = const { Cell::new(false) };
+    /// The number of calls which were entered but not exited yet
+    static DEPTH: Cell<u32> = const { Cell::new(0) };
+    static CALLS: RefCell<Vec<Call>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Starts recording the regions consumed on the current thread, and
+/// the calls which consumed them if `calls` is set.
+#[cfg(feature = "std")]
+pub fn start(calls: bool) {
+    REGIONS.with_borrow_mut(Vec::clear);
+    CALLS.with_borrow_mut(Vec::clear);
+    DEPTH.set(0);
+    ENABLED.set(true);
+    CALLS_ENABLED.set(calls);
+}
+
+/// Stops recording and returns the regions consumed since [`start`].
+/// The calls are returned by [`take_calls`].
+#[cfg(feature = "std")]
+pub fn finish() -> Vec<Region> {
+    ENABLED.set(false);
+    CALLS_ENABLED.set(false);
+    REGIONS.take()
+}
+
+/// Returns the calls recorded since [`start`].
+#[cfg(feature = "std")]
+#[cfg_attr(
+    any(not(fuzzing), cifuzz_afl, cifuzz_honggfuzz, cifuzz_wasm),
+    allow(dead_code)
+)]
+pub fn take_calls() -> Vec<Call> {
+    CALLS.take()
+}
+
+/// A call entered by [`enter`], which must be passed to [`exit`] when
+/// it returns.
+pub(super) struct Entered {
+    /// The number of regions recorded before the call, or none if calls
+    /// aren't recorded or the call is made by another one
+    #[cfg(feature = "std")]
+    first_region: Option<usize>,
+}
+
+/// Called by the methods of the provider before they consume the input.
+#[cfg(feature = "std")]
+#[inline]
+pub(super) fn enter() -> Entered {
+    if !CALLS_ENABLED.get() {
+        return Entered { first_region: None };
+    }
+    let depth = DEPTH.get();
+    DEPTH.set(depth + 1);
+    Entered {
+        first_region: (depth == 0).then(|| REGIONS.with_borrow(Vec::len)),
+    }
+}
+
+/// Called by the methods of the provider with the call and the returned
+/// value, which are only formatted if the call is recorded.
+#[cfg(feature = "std")]
+#[inline]
+pub(super) fn exit(entered: Entered, method: fmt::Arguments<'_>, value: &dyn fmt::Debug) {
+    if !CALLS_ENABLED.get() {
+        return;
+    }
+    DEPTH.set(DEPTH.get().saturating_sub(1));
+    if let Some(first_region) = entered.first_region {
+        let regions = REGIONS.with_borrow(|regions| regions[first_region..].to_vec());
+        CALLS.with_borrow_mut(|calls| {
+            calls.push(Call {
+                method: method.to_string(),
+                value: format!("{value:?}"),
+                regions,
+            })
+        });
+    }
+}
+
+#[cfg(not(feature = "std"))]
+#[inline]
+pub(super) fn enter() -> Entered {
+    Entered {}
+}
+
+#[cfg(not(feature = "std"))]
+#[inline]
+pub(super) fn exit(_entered: Entered, _method: fmt::Arguments<'_>, _value: &dyn fmt::Debug) {}
+
+/// Formats a type name without the module paths, e.g. `Vec<u8>`
+/// instead of `alloc::vec::Vec<u8>`.
+pub(super) struct TypeName(pub &'static str);
+
+impl fmt::Display for TypeName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rest = self.0;
+        while !rest.is_empty() {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':'))
+                .unwrap_or(rest.len());
+            let (path, tail) = rest.split_at(end);
+            f.write_str(path.rsplit("::").next().unwrap_or(path))?;
+            let separator = tail.chars().next().map_or(0, char::len_utf8);
+            f.write_str(&tail[..separator])?;
+            rest = &tail[separator..];
+        }
+        Ok(())
+    }
+}
+
+/// Formats bytes like a byte string literal, e.g. `b"FUZZ\x00"`.
+pub(super) struct Bytes<'a>(pub &'a [u8]);
+
+impl fmt::Debug for Bytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("b\"")?;
+        for &byte in self.0 {
+            fmt::Display::fmt(&core::ascii::escape_default(byte), f)?;
+        }
+        f.write_str("\"")
+    }
+}
+
+/// Records the regions consumed by a call which changed the remaining
+/// data of a provider from `before` to `after`. Both are subslices of
+/// `input`.
+#[cfg(feature = "std")]
+#[inline]
+pub(super) fn record(input: &[u8], before: &[u8], after: &[u8]) {
+    if !ENABLED.get() {
+        return;
+    }
+    let offset = |s: &[u8]| s.as_ptr() as usize - input.as_ptr() as usize;
+    let (before_start, after_start) = (offset(before), offset(after));
+    let (before_end, after_end) = (before_start + before.len(), after_start + after.len());
+    REGIONS.with_borrow_mut(|regions| {
+        if after_start > before_start {
+            regions.push(Region {
+                start: before_start,
+                len: after_start - before_start,
+                from_end: false,
+            });
+        }
+        if before_end > after_end {
+            regions.push(Region {
+                start: after_end,
+                len: before_end - after_end,
+                from_end: true,
+            });
+        }
+    });
+}
+
+#[cfg(not(feature = "std"))]
+#[inline]
+pub(super) fn record(_input: &[u8], _before: &[u8], _after: &[u8]) {}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::FuzzedDataProvider;
+
+    #[test]
+    fn record_regions() {
+        let data = b"abc\\xdef\x01\x02\x03";
+        start(false);
+        let mut fdp = FuzzedDataProvider::new(data);
+        let _: u16 = fdp.consume_int();
+        fdp.consume_string(10);
+        fdp.consume_bytes(2);
+        let regions = finish();
+        assert_eq!(
+            regions,
+            vec![
+                Region {
+                    start: 9,
+                    len: 2,
+                    from_end: true
+                },
+                Region {
+                    start: 0,
+                    len: 5,
+                    from_end: false
+                },
+                Region {
+                    start: 5,
+                    len: 2,
+                    from_end: false
+                },
+            ]
+        );
+
+        // Nothing is recorded when disabled
+        fdp.consume_bytes(2);
+        assert!(REGIONS.with_borrow(Vec::is_empty));
+    }
+
+    #[test]
+    fn record_calls() {
+        let data = b"FUZZING\\x\x01\x00\x94\x35\x77";
+        start(true);
+        let mut fdp = FuzzedDataProvider::new(data);
+        let _: u32 = fdp.consume_int();
+        fdp.consume_string(100);
+        let _: Option<u8> = fdp.consume();
+        fdp.consume_remaining_bytes();
+        finish();
+        let calls: Vec<(String, String)> = take_calls()
+            .into_iter()
+            .map(|call| (call.method, call.value))
+            .collect();
+        assert_eq!(
+            calls,
+            [
+                ("consume_int::<u32>()", "2000000000"),
+                ("consume_string(100)", "\"FUZZING\""),
+                ("consume_bool()", "true"),
+                ("consume_int::<u8>()", "0"),
+                ("consume_remaining_bytes()", "b\"\""),
+            ]
+            .map(|(method, value)| (method.to_string(), value.to_string()))
+        );
+    }
+
+    #[test]
+    fn type_names() {
+        assert_eq!(
+            TypeName("alloc::vec::Vec<(u8, core::option::Option<my_crate::Op>)>").to_string(),
+            "Vec<(u8, Option<Op>)>"
+        );
+        assert_eq!(format!("{:?}", Bytes(b"a\"\x00")), "b\"a\\\"\\x00\"");
+    }
+}
@@ -0,0 +1,685 @@
+//! Generating and mutating inputs which are valid according to a
+//! grammar, for fuzzing parsers of languages and formats like queries,
+//! expressions or configuration files. Byte-level mutations of such
+//! inputs mostly produce ones which are rejected by the tokenizer.
+//!
+//! Grammars are built from rules, whose right-hand sides are combined
+//! from [`lit`], [`byte_range`], [`rule`], [`seq`], [`alt`], [`repeat`]
+//! and [`opt`]:
+//!
+//! ```
+//! use cifuzz::grammar::{alt, byte_range, lit, repeat, rule, seq, Grammar};
+//!
+//! let grammar = Grammar::builder("expr")
+//!     .rule("expr", alt([seq([rule("term"), lit("+"), rule("expr")]), rule("term")]))
+//!     .rule("term", alt([rule("number"), seq([lit("("), rule("expr"), lit(")")])]))
+//!     .rule("number", repeat(byte_range(b'0', b'9'), 1..=4))
+//!     .build();
+//! assert!(grammar.matches(b"(1+23)+4"));
+//! ```
+//!
+//! The grammar can be used in two ways:
+//!
+//! * As a generator, with [`Grammar::generate`], which turns the input
+//!   of a fuzz test taking a [`FuzzedDataProvider`] into a valid input.
+//!   The corpus then consists of the choices the generator made.
+//! * As a custom mutator, with [`Grammar::mutate`] and
+//!   [`Grammar::crossover`] called by a
+//!   [`CustomMutator`](crate::CustomMutator). The corpus then consists
+//!   of the generated inputs themselves, which are mutated by replacing
+//!   the derivation of a rule with a newly generated one.
+//!
+//! ```
+//! use std::sync::OnceLock;
+//!
+//! use cifuzz::grammar::{byte_range, repeat, Grammar};
+//! use cifuzz::{fuzz_test, CustomMutator};
+//!
+//! fn grammar() -> &'static Grammar {
+//!     static GRAMMAR: OnceLock<Grammar> = OnceLock::new();
+//!     GRAMMAR.get_or_init(|| {
+//!         Grammar::builder("number")
+//!             .rule("number", repeat(byte_range(b'0', b'9'), 1..=8))
+//!             .build()
+//!     })
+//! }
+//!
+//! struct NumberMutator;
+//!
+//! impl CustomMutator for NumberMutator {
+//!     fn mutate(data: &mut Vec<u8>, max_size: usize, seed: u32) {
+//!         grammar().mutate(data, max_size, seed);
+//!     }
+//!
+//!     fn crossover(first: &[u8], second: &[u8], out: &mut Vec<u8>, max_size: usize, seed: u32) {
+//!         grammar().crossover(first, second, out, max_size, seed);
+//!     }
+//! }
+//!
+//! #[fuzz_test(mutator = NumberMutator)]
+//! fn number_fuzz_test(data: &[u8]) {
+//!     // parse the number
+//!     # let _ = data;
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+use crate::mutator::Rng;
+use crate::FuzzedDataProvider;
+
+/// The depth of nested rules up to which the alternatives and
+/// repetitions are chosen freely. Beyond it, the shortest way to
+/// complete the input is taken.
+pub const DEFAULT_MAX_DEPTH: usize = 16;
+
+/// The steps the parser of [`Grammar::mutate`] may take, which bounds
+/// the time spent on ambiguous grammars.
+const MAX_PARSE_STEPS: usize = 100_000;
+
+/// The nesting of the parser, which bounds its use of the stack.
+const MAX_PARSE_NESTING: usize = 4096;
+
+/// The attempts of [`Grammar::mutate`] to generate a derivation which
+/// fits into the maximum size of the input.
+const MAX_MUTATION_ATTEMPTS: usize = 8;
+
+/// The right-hand side of a rule.
+#[derive(Debug, Clone)]
+pub enum Node {
+    /// The bytes themselves
+    Literal(Vec<u8>),
+    /// A single byte in the inclusive range
+    ByteRange(u8, u8),
+    /// The rule of the name
+    Rule(String),
+    /// The nodes one after the other
+    Seq(Vec<Node>),
+    /// One of the nodes
+    Alt(Vec<Node>),
+    /// The node repeated between min and max times
+    Repeat(Box<Node>, usize, usize),
+}
+
+/// A literal string or byte string.
+pub fn lit(bytes: impl AsRef<[u8]>) -> Node {
+    Node::Literal(bytes.as_ref().to_vec())
+}
+
+/// A single byte in the inclusive range, e.g. `byte_range(b'a', b'z')`.
+pub fn byte_range(min: u8, max: u8) -> Node {
+    Node::ByteRange(min.min(max), min.max(max))
+}
+
+/// A reference to the rule of the name.
+pub fn rule(name: &str) -> Node {
+    Node::Rule(name.to_string())
+}
+
+/// The nodes one after the other.
+pub fn seq(nodes: impl IntoIterator<Item = Node>) -> Node {
+    Node::Seq(nodes.into_iter().collect())
+}
+
+/// One of the nodes. When the maximum depth is reached, the alternative
+/// with the shortest derivation is chosen.
+pub fn alt(nodes: impl IntoIterator<Item = Node>) -> Node {
+    Node::Alt(nodes.into_iter().collect())
+}
+
+/// The node repeated a number of times in the range.
+pub fn repeat(node: Node, count: RangeInclusive<usize>) -> Node {
+    Node::Repeat(Box::new(node), *count.start(), *count.end())
+}
+
+/// The node or nothing.
+pub fn opt(node: Node) -> Node {
+    repeat(node, 0..=1)
+}
+
+/// The node of a rule after the names of the rules it refers to were
+/// resolved to their indices.
+#[derive(Debug)]
+enum Resolved {
+    Literal(Vec<u8>),
+    ByteRange(u8, u8),
+    Rule(usize),
+    Seq(Vec<Resolved>),
+    Alt(Vec<Resolved>),
+    Repeat(Box<Resolved>, usize, usize),
+}
+
+/// Builds a [`Grammar`] from its rules, see [`Grammar::builder`].
+#[derive(Debug)]
+pub struct GrammarBuilder {
+    start: String,
+    rules: Vec<(String, Node)>,
+    max_depth: usize,
+}
+
+impl GrammarBuilder {
+    /// Adds the rule of the name. A rule which is added again is
+    /// replaced.
+    pub fn rule(mut self, name: &str, node: Node) -> Self {
+        self.rules.retain(|(n, _)| n != name);
+        self.rules.push((name.to_string(), node));
+        self
+    }
+
+    /// Sets the depth of nested rules up to which inputs are generated
+    /// freely, [`DEFAULT_MAX_DEPTH`] by default.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Builds the grammar.
+    ///
+    /// # Panics
+    ///
+    /// If a rule, including the start rule, isn't defined, or if a rule
+    /// has no derivation of a finite length, e.g. `a = "x" a`.
+    pub fn build(self) -> Grammar {
+        let indices: HashMap<&str, usize> = self
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| (name.as_str(), i))
+            .collect();
+        let start = *indices
+            .get(self.start.as_str())
+            .unwrap_or_else(|| panic!("the start rule `{}` isn't defined", self.start));
+        let rules = self
+            .rules
+            .iter()
+            .map(|(_, node)| resolve(node, &indices))
+            .collect();
+        let mut grammar = Grammar {
+            names: self.rules.into_iter().map(|(name, _)| name).collect(),
+            rules,
+            min_depths: Vec::new(),
+            start,
+            max_depth: self.max_depth,
+        };
+        grammar.min_depths = grammar.compute_min_depths();
+        if let Some(i) = grammar.min_depths.iter().position(|&d| d == INFINITE) {
+            panic!(
+                "the rule `{}` has no derivation of a finite length",
+                grammar.names[i]
+            );
+        }
+        grammar
+    }
+}
+
+fn resolve(node: &Node, indices: &HashMap<&str, usize>) -> Resolved {
+    let all = |nodes: &[Node]| nodes.iter().map(|n| resolve(n, indices)).collect();
+    match node {
+        Node::Literal(bytes) => Resolved::Literal(bytes.clone()),
+        Node::ByteRange(min, max) => Resolved::ByteRange(*min, *max),
+        Node::Rule(name) => Resolved::Rule(
+            *indices
+                .get(name.as_str())
+                .unwrap_or_else(|| panic!("the rule `{name}` isn't defined")),
+        ),
+        Node::Seq(nodes) => Resolved::Seq(all(nodes)),
+        Node::Alt(nodes) => Resolved::Alt(all(nodes)),
+        Node::Repeat(node, min, max) => {
+            Resolved::Repeat(Box::new(resolve(node, indices)), *min, (*max).max(*min))
+        }
+    }
+}
+
+const INFINITE: usize = usize::MAX;
+
+/// A grammar of the inputs of a fuzz test, see the [module
+/// documentation](self).
+#[derive(Debug)]
+pub struct Grammar {
+    names: Vec<String>,
+    rules: Vec<Resolved>,
+    /// The minimum depth of nested rules of a derivation of every rule
+    min_depths: Vec<usize>,
+    start: usize,
+    max_depth: usize,
+}
+
+/// The source of the choices made while generating an input.
+trait Choices {
+    fn choose(&mut self, min: usize, max: usize) -> usize;
+
+    /// Whether no more choices can be made, which makes the generator
+    /// complete the input the shortest way
+    fn exhausted(&self) -> bool {
+        false
+    }
+}
+
+impl Choices for FuzzedDataProvider<'_> {
+    fn choose(&mut self, min: usize, max: usize) -> usize {
+        self.consume_int_in_range(min, max)
+    }
+
+    fn exhausted(&self) -> bool {
+        self.remaining_bytes() == 0
+    }
+}
+
+impl Choices for Rng {
+    fn choose(&mut self, min: usize, max: usize) -> usize {
+        self.in_range(min, max)
+    }
+}
+
+/// The part of an input which was derived from a rule.
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    rule: usize,
+    start: usize,
+    end: usize,
+}
+
+impl Grammar {
+    /// Starts building a grammar whose inputs are derived from the start
+    /// rule.
+    pub fn builder(start: &str) -> GrammarBuilder {
+        GrammarBuilder {
+            start: start.to_string(),
+            rules: Vec::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    /// Generates an input with the choices consumed from the provider.
+    /// Once the provider is exhausted, the shortest derivation is
+    /// chosen, so that every input of the fuzz test is turned into a
+    /// valid input.
+    pub fn generate(&self, fdp: &mut FuzzedDataProvider) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.generate_rule(self.start, 0, fdp, &mut out);
+        out
+    }
+
+    /// Whether the input is derived from the start rule.
+    pub fn matches(&self, input: &[u8]) -> bool {
+        self.parse(input).is_some()
+    }
+
+    /// Mutates an input of the grammar by replacing the derivation of one
+    /// of its rules with a newly generated one. Inputs which aren't
+    /// derived from the grammar are replaced with a newly generated one.
+    pub fn mutate(&self, data: &mut Vec<u8>, max_size: usize, seed: u32) {
+        let mut rng = Rng::new(seed);
+        let spans = self.parse(data).unwrap_or_default();
+        for _ in 0..MAX_MUTATION_ATTEMPTS {
+            let mutated = if spans.is_empty() {
+                let mut out = Vec::new();
+                self.generate_rule(self.start, 0, &mut rng, &mut out);
+                out
+            } else {
+                let index = rng.choose(0, spans.len() - 1);
+                let span = spans[index];
+                let mut out = data[..span.start].to_vec();
+                self.generate_rule(span.rule, depth_of(&spans, index), &mut rng, &mut out);
+                out.extend_from_slice(&data[span.end..]);
+                out
+            };
+            if mutated.len() <= max_size {
+                *data = mutated;
+                return;
+            }
+        }
+    }
+
+    /// Combines two inputs of the grammar by replacing the derivation of
+    /// a rule in the first one with a derivation of the same rule in the
+    /// second one. `out` is left empty if the inputs have no rule in
+    /// common.
+    pub fn crossover(
+        &self,
+        first: &[u8],
+        second: &[u8],
+        out: &mut Vec<u8>,
+        max_size: usize,
+        seed: u32,
+    ) {
+        let (Some(first_spans), Some(second_spans)) = (self.parse(first), self.parse(second))
+        else {
+            return;
+        };
+        let mut rng = Rng::new(seed);
+        for _ in 0..MAX_MUTATION_ATTEMPTS {
+            let span = first_spans[rng.choose(0, first_spans.len() - 1)];
+            let candidates: Vec<&Span> = second_spans
+                .iter()
+                .filter(|s| s.rule == span.rule)
+                .collect();
+            if candidates.is_empty() {
+                continue;
+            }
+            let other = candidates[rng.choose(0, candidates.len() - 1)];
+            let len = first.len() - (span.end - span.start) + (other.end - other.start);
+            if len <= max_size {
+                out.extend_from_slice(&first[..span.start]);
+                out.extend_from_slice(&second[other.start..other.end]);
+                out.extend_from_slice(&first[span.end..]);
+                return;
+            }
+        }
+    }
+
+    fn generate_rule(
+        &self,
+        rule: usize,
+        depth: usize,
+        choices: &mut impl Choices,
+        out: &mut Vec<u8>,
+    ) {
+        self.generate_node(&self.rules[rule], depth + 1, choices, out);
+    }
+
+    fn generate_node(
+        &self,
+        node: &Resolved,
+        depth: usize,
+        choices: &mut impl Choices,
+        out: &mut Vec<u8>,
+    ) {
+        let free = depth < self.max_depth && !choices.exhausted();
+        match node {
+            Resolved::Literal(bytes) => out.extend_from_slice(bytes),
+            Resolved::ByteRange(min, max) => {
+                out.push(choices.choose(usize::from(*min), usize::from(*max)) as u8)
+            }
+            Resolved::Rule(rule) => self.generate_rule(*rule, depth, choices, out),
+            Resolved::Seq(nodes) => {
+                for node in nodes {
+                    self.generate_node(node, depth, choices, out);
+                }
+            }
+            Resolved::Alt(nodes) if nodes.is_empty() => {}
+            Resolved::Alt(nodes) => {
+                let node = if free {
+                    &nodes[choices.choose(0, nodes.len() - 1)]
+                } else {
+                    nodes
+                        .iter()
+                        .min_by_key(|node| self.min_depth(node))
+                        .unwrap()
+                };
+                self.generate_node(node, depth, choices, out);
+            }
+            Resolved::Repeat(node, min, max) => {
+                let count = if free {
+                    choices.choose(*min, *max)
+                } else {
+                    *min
+                };
+                for _ in 0..count {
+                    self.generate_node(node, depth, choices, out);
+                }
+            }
+        }
+    }
+
+    /// Computes the minimum depths of the rules by iterating until they
+    /// don't change anymore. Rules without a finite derivation keep an
+    /// infinite depth.
+    fn compute_min_depths(&self) -> Vec<usize> {
+        let mut depths = vec![INFINITE; self.rules.len()];
+        loop {
+            let mut changed = false;
+            for (i, rule) in self.rules.iter().enumerate() {
+                let depth = min_depth(rule, &depths).saturating_add(1);
+                if depth < depths[i] {
+                    depths[i] = depth;
+                    changed = true;
+                }
+            }
+            if !changed {
+                return depths;
+            }
+        }
+    }
+
+    fn min_depth(&self, node: &Resolved) -> usize {
+        min_depth(node, &self.min_depths)
+    }
+
+    /// Parses the input and returns the spans of the rules it's derived
+    /// from, sorted by their start, or `None` if the input can't be
+    /// derived from the grammar or the parser gives up.
+    fn parse(&self, input: &[u8]) -> Option<Vec<Span>> {
+        let mut parser = Parser {
+            grammar: self,
+            input,
+            spans: Vec::new(),
+            steps: 0,
+            nesting: 0,
+        };
+        let start = Resolved::Rule(self.start);
+        parser
+            .parse(&start, 0, &mut |_, end| end == input.len())
+            .then_some(parser.spans)
+    }
+}
+
+/// The depth of the rule of a span, i.e. the number of spans which
+/// contain it. Spans are sorted in the order the parser entered the
+/// rules, so the spans containing it come before it.
+fn depth_of(spans: &[Span], index: usize) -> usize {
+    let span = spans[index];
+    spans[..index]
+        .iter()
+        .filter(|s| s.start <= span.start && span.end <= s.end)
+        .count()
+}
+
+fn min_depth(node: &Resolved, rule_depths: &[usize]) -> usize {
+    match node {
+        Resolved::Literal(_) | Resolved::ByteRange(..) => 0,
+        Resolved::Rule(rule) => rule_depths[*rule],
+        Resolved::Seq(nodes) => nodes
+            .iter()
+            .map(|n| min_depth(n, rule_depths))
+            .max()
+            .unwrap_or(0),
+        Resolved::Alt(nodes) => nodes
+            .iter()
+            .map(|n| min_depth(n, rule_depths))
+            .min()
+            .unwrap_or(INFINITE),
+        Resolved::Repeat(_, 0, _) => 0,
+        Resolved::Repeat(node, ..) => min_depth(node, rule_depths),
+    }
+}
+
+/// A backtracking parser, which tries all derivations of the input in
+/// continuation-passing style until one of them consumes it completely.
+struct Parser<'g, 'a> {
+    grammar: &'g Grammar,
+    input: &'a [u8],
+    spans: Vec<Span>,
+    steps: usize,
+    nesting: usize,
+}
+
+type Continuation<'k, 'g, 'a> = dyn FnMut(&mut Parser<'g, 'a>, usize) -> bool + 'k;
+
+impl<'g, 'a> Parser<'g, 'a> {
+    fn parse(&mut self, node: &'g Resolved, pos: usize, k: &mut Continuation<'_, 'g, 'a>) -> bool {
+        self.steps += 1;
+        if self.steps > MAX_PARSE_STEPS || self.nesting > MAX_PARSE_NESTING {
+            return false;
+        }
+        self.nesting += 1;
+        let matched = self.parse_node(node, pos, k);
+        self.nesting -= 1;
+        matched
+    }
+
+    fn parse_node(
+        &mut self,
+        node: &'g Resolved,
+        pos: usize,
+        k: &mut Continuation<'_, 'g, 'a>,
+    ) -> bool {
+        match node {
+            Resolved::Literal(bytes) => {
+                self.input[pos..].starts_with(bytes) && k(self, pos + bytes.len())
+            }
+            Resolved::ByteRange(min, max) => {
+                self.input
+                    .get(pos)
+                    .is_some_and(|b| (min..=max).contains(&b))
+                    && k(self, pos + 1)
+            }
+            Resolved::Rule(rule) => {
+                let index = self.spans.len();
+                self.spans.push(Span {
+                    rule: *rule,
+                    start: pos,
+                    end: pos,
+                });
+                let grammar = self.grammar;
+                let matched = self.parse(&grammar.rules[*rule], pos, &mut |p, end| {
+                    p.spans[index].end = end;
+                    k(p, end)
+                });
+                if !matched {
+                    self.spans.truncate(index);
+                }
+                matched
+            }
+            Resolved::Seq(nodes) => self.parse_seq(nodes, pos, k),
+            Resolved::Alt(nodes) => nodes.iter().any(|node| self.parse(node, pos, k)),
+            Resolved::Repeat(node, min, max) => self.parse_repeat(node, *min, *max, 0, pos, k),
+        }
+    }
+
+    fn parse_seq(
+        &mut self,
+        nodes: &'g [Resolved],
+        pos: usize,
+        k: &mut Continuation<'_, 'g, 'a>,
+    ) -> bool {
+        match nodes.split_first() {
+            None => k(self, pos),
+            Some((first, rest)) => {
+                self.parse(first, pos, &mut |p, next| p.parse_seq(rest, next, k))
+            }
+        }
+    }
+
+    fn parse_repeat(
+        &mut self,
+        node: &'g Resolved,
+        min: usize,
+        max: usize,
+        count: usize,
+        pos: usize,
+        k: &mut Continuation<'_, 'g, 'a>,
+    ) -> bool {
+        if count >= min && k(self, pos) {
+            return true;
+        }
+        // Repetitions which consume nothing can't lead to other
+        // derivations once the minimum is reached
+        count < max
+            && self.parse(node, pos, &mut |p, next| {
+                (next > pos || count < min) && p.parse_repeat(node, min, max, count + 1, next, k)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expressions() -> Grammar {
+        Grammar::builder("expr")
+            .rule(
+                "expr",
+                alt([seq([rule("term"), lit("+"), rule("expr")]), rule("term")]),
+            )
+            .rule(
+                "term",
+                alt([seq([lit("("), rule("expr"), lit(")")]), rule("number")]),
+            )
+            .rule("number", repeat(byte_range(b'0', b'9'), 1..=3))
+            .max_depth(6)
+            .build()
+    }
+
+    #[test]
+    fn generated_inputs_match() {
+        let grammar = expressions();
+        for data in [&b""[..], b"\x00", b"\xff\xff\xff\xff\xff\xff", b"FUZZING"] {
+            let input = grammar.generate(&mut FuzzedDataProvider::new(data));
+            assert!(grammar.matches(&input), "{:?}", String::from_utf8(input));
+        }
+        assert_eq!(
+            grammar.generate(&mut FuzzedDataProvider::new(b"")),
+            b"0".to_vec()
+        );
+    }
+
+    #[test]
+    fn parses_inputs() {
+        let grammar = expressions();
+        assert!(grammar.matches(b"1+(23+4)"));
+        assert!(!grammar.matches(b"1+"));
+        assert!(!grammar.matches(b"1234"));
+        let spans = grammar.parse(b"(1)+2").unwrap();
+        let rules: Vec<(&str, usize, usize)> = spans
+            .iter()
+            .map(|s| (grammar.names[s.rule].as_str(), s.start, s.end))
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                ("expr", 0, 5),
+                ("term", 0, 3),
+                ("expr", 1, 2),
+                ("term", 1, 2),
+                ("number", 1, 2),
+                ("expr", 4, 5),
+                ("term", 4, 5),
+                ("number", 4, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn mutated_inputs_match() {
+        let grammar = expressions();
+        let mut data = b"1+2".to_vec();
+        for seed in 0..100 {
+            grammar.mutate(&mut data, 64, seed);
+            assert!(data.len() <= 64);
+            assert!(grammar.matches(&data), "{:?}", String::from_utf8(data));
+        }
+        let mut data = b"not an expression".to_vec();
+        grammar.mutate(&mut data, 64, 1);
+        assert!(grammar.matches(&data));
+
+        let mut out = Vec::new();
+        grammar.crossover(b"(1+2)", b"33+(4)", &mut out, 64, 3);
+        assert!(grammar.matches(&out), "{:?}", String::from_utf8(out));
+    }
+
+    #[test]
+    #[should_panic(expected = "the rule `a` has no derivation of a finite length")]
+    fn rejects_infinite_rules() {
+        Grammar::builder("a")
+            .rule("a", seq([lit("x"), rule("a")]))
+            .build();
+    }
+
+    #[test]
+    #[should_panic(expected = "the rule `b` isn't defined")]
+    fn rejects_undefined_rules() {
+        Grammar::builder("a").rule("a", rule("b")).build();
+    }
+}
@@ -0,0 +1,841 @@
+//! The code which runs a fuzz test, either by handing it to libFuzzer
+//! (when built with `--cfg fuzzing`), to AFL++ (when built with
+//! `--cfg fuzzing --cfg cifuzz_afl`), to honggfuzz (when built with
+//! `--cfg fuzzing --cfg cifuzz_honggfuzz`), to libFuzzer running in
+//! cifuzz-wasm-driver (when built for wasm32-wasip1 with
+//! `--cfg fuzzing --cfg cifuzz_wasm`) or by executing it as a regular
+//! unit test.
+//!
+//! The `#[fuzz_test]` macro expands to a call of
+//! [`__fuzz_test_harness`], which is defined differently depending on
+//! the `fuzzing` cfg. Evaluating the cfg in this crate instead of in the
+//! crate containing the fuzz test means that the cfg only has to be
+//! declared here.
+
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, Once};
+
+use crate::mutator::Mutator;
+use crate::{regression, rng};
+
+/// A function which executes the fuzz test with a single input.
+pub type TestOneInput = fn(&[u8]);
+
+/// Information about a fuzz test which is determined at compile time
+/// of the crate containing it.
+#[derive(Debug)]
+pub struct FuzzTest {
+    /// The name of the fuzz test function
+    pub name: &'static str,
+    /// The module of the harness, i.e. the path of the fuzz test
+    /// function, as returned by `module_path!()`
+    pub module_path: &'static str,
+    /// The source file containing the fuzz test, as returned by `file!()`
+    pub file: &'static str,
+    /// The line of the `#[fuzz_test]` attribute
+    pub line: u32,
+    /// The manifest directory of the package containing the fuzz test
+    pub manifest_dir: &'static str,
+    /// The seed corpus embedded by [`crate::build::embed_seed_corpus`],
+    /// as pairs of the path of the input and its content
+    pub embedded_seed_corpus: &'static [(&'static str, &'static [u8])],
+    /// The tokens of the `dictionary!` invocations in the fuzz test
+    pub dictionary: &'static [&'static [u8]],
+    /// The custom mutator of `#[fuzz_test(mutator = ...)]`
+    pub mutator: Option<Mutator>,
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __fuzz_test {
+    ($name:expr, $seed_corpus:expr, $dictionary:expr, $mutator:expr) => {
+        $crate::__private::FuzzTest {
+            name: $name,
+            module_path: ::core::module_path!(),
+            file: ::core::file!(),
+            line: ::core::line!(),
+            manifest_dir: ::core::env!("CARGO_MANIFEST_DIR"),
+            embedded_seed_corpus: $seed_corpus,
+            dictionary: $dictionary,
+            mutator: $mutator,
+        }
+    };
+}
+
+#[cfg(fuzzing)]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __fuzz_test_harness {
+    (
+        $name:expr,
+        $test_one_input:expr,
+        $seed_corpus:expr,
+        $dictionary:expr,
+        $ignore_panics:expr,
+        $mutator:expr,
+        $init:expr
+    ) => {
+        #[test]
+        fn fuzz() {
+            let test = $crate::__fuzz_test!($name, $seed_corpus, $dictionary, $mutator);
+            if $crate::__private::register(&test) {
+                return;
+            }
+            // Before the fuzzing engine starts, like LLVMFuzzerInitialize
+            init();
+            $crate::__private::fuzz(&test, test_one_input);
+        }
+
+        $crate::__fuzz_test_one_input!($test_one_input, $ignore_panics, $init);
+    };
+}
+
+#[cfg(not(fuzzing))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __fuzz_test_harness {
+    (
+        $name:expr,
+        $test_one_input:expr,
+        $seed_corpus:expr,
+        $dictionary:expr,
+        $ignore_panics:expr,
+        $mutator:expr,
+        $init:expr
+    ) => {
+        #[test]
+        fn regression() {
+            $crate::__private::regression(
+                &$crate::__fuzz_test!($name, $seed_corpus, $dictionary, $mutator),
+                test_one_input,
+            );
+        }
+
+        $crate::__fuzz_test_one_input!($test_one_input, $ignore_panics, $init);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __fuzz_test_one_input {
+    ($test_one_input:expr, $ignore_panics:expr, $init:expr) => {
+        /// Calls the `init` function of the fuzz test, if any, unless it
+        /// was already called in this process.
+        fn init() {
+            static INIT: ::std::sync::Once = ::std::sync::Once::new();
+            let init: ::core::option::Option<fn()> = $init;
+            if let ::core::option::Option::Some(init) = init {
+                INIT.call_once(init);
+            }
+        }
+
+        /// Executes the fuzz test with a single input, e.g. in the
+        /// regression tests generated by `cargo cifuzz run`.
+        pub(super) fn test_one_input(data: &[u8]) {
+            init();
+            $crate::__private::run_input(data, $test_one_input, $ignore_panics)
+        }
+    };
+}
+
+pub use crate::registry::register;
+pub use crate::regression::regression;
+
+thread_local! {
+    /// The `ignore_panics` patterns of the input which is executed on
+    /// the thread, which the panic hook doesn't print the panics of.
+    static IGNORED_PANICS: Cell<&'static [&'static str]> = const { Cell::new(&[]) };
+    /// Whether the thread is unwinding from an ignored panic
+    static IGNORING_PANIC: Cell<bool> = const { Cell::new(false) };
+    /// Whether the panics on the thread are caught by [`catch_panic`],
+    /// which the panic hook doesn't print either
+    static CATCHING_PANIC: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether the panic hook records the message of the first panic in
+/// [`RECORDED_PANIC`], see [`record_panics`]
+static RECORDING_PANICS: AtomicBool = AtomicBool::new(false);
+static RECORDED_PANIC: Mutex<Option<String>> = Mutex::new(None);
+
+/// Executes the fuzz test with the input. Panics whose message contains
+/// one of the `ignore_panics` patterns of the fuzz test are the expected
+/// rejections of invalid inputs, they're neither printed nor treated as
+/// a crash.
+pub fn run_input(
+    data: &[u8],
+    test_one_input: impl FnOnce(&[u8]),
+    ignore_panics: &'static [&'static str],
+) {
+    rng::input_started(data);
+    if ignore_panics.is_empty() {
+        test_one_input(data);
+        rng::input_finished();
+        return;
+    }
+    install_panic_hook();
+    IGNORED_PANICS.with(|ignored| ignored.set(ignore_panics));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| test_one_input(data)));
+    IGNORED_PANICS.with(|ignored| ignored.set(&[]));
+    rng::input_finished();
+    if let Err(payload) = result {
+        if !is_ignored(ignore_panics, &regression::panic_message(&*payload)) {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+fn is_ignored(ignore_panics: &[&str], message: &str) -> bool {
+    ignore_panics
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+/// Executes the closure and returns the message of its panic, if it
+/// panicked. The panic isn't printed, because it's not a finding by
+/// itself.
+pub(crate) fn catch_panic<R>(f: impl FnOnce() -> R) -> Result<R, String> {
+    install_panic_hook();
+    let catching = CATCHING_PANIC.with(|catching| catching.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING_PANIC.with(|c| c.set(catching));
+    result.map_err(|payload| regression::panic_message(&*payload))
+}
+
+/// Starts recording the first panic on any thread, including panics
+/// which are caught by the code under test, e.g. by a web framework
+/// which turns them into a response. [`recorded_panic`] returns it.
+#[cfg_attr(not(feature = "http"), allow(dead_code))]
+pub(crate) fn record_panics() {
+    install_panic_hook();
+    *RECORDED_PANIC.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    RECORDING_PANICS.store(true, Ordering::SeqCst);
+}
+
+/// Stops recording panics and returns the message of the first panic
+/// since [`record_panics`], if any.
+#[cfg_attr(not(feature = "http"), allow(dead_code))]
+pub(crate) fn recorded_panic() -> Option<String> {
+    RECORDING_PANICS.store(false, Ordering::SeqCst);
+    RECORDED_PANIC
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+}
+
+/// Whether the thread is unwinding from a panic which isn't a finding,
+/// because it matches the `ignore_panics` patterns of the fuzz test.
+pub(crate) fn is_ignoring_panic() -> bool {
+    std::thread::panicking() && IGNORING_PANIC.with(Cell::get)
+}
+
+/// Installs a panic hook which skips the ignored and the caught panics
+/// and passes all others to the previous hook, which prints them.
+fn install_panic_hook() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let message = info.payload_as_str().unwrap_or_default();
+            let ignored = CATCHING_PANIC.with(Cell::get)
+                || IGNORED_PANICS.with(|ignored| is_ignored(ignored.get(), message));
+            IGNORING_PANIC.with(|ignoring| ignoring.set(ignored));
+            if !ignored && RECORDING_PANICS.load(Ordering::SeqCst) {
+                let mut recorded = RECORDED_PANIC.lock().unwrap_or_else(|e| e.into_inner());
+                recorded.get_or_insert_with(|| message.to_string());
+            }
+            if !ignored {
+                hook(info);
+            }
+        }));
+    });
+}
+
+/// The type of the input of a fuzz test taking a value, which selects
+/// how the input is decoded by autoref specialization: the macro calls
+/// `(&&Input::<T>::new()).decode(data)`, which resolves to the
+/// implementation of [`DecodeInput`] for `&Input<T>` for protobuf
+/// messages (with the `protobuf` feature) and to the one for `Input<T>`
+/// for types implementing `Arbitrary` otherwise.
+pub struct Input<T>(PhantomData<T>);
+
+impl<T> Input<T> {
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        Input(PhantomData)
+    }
+}
+
+pub trait DecodeInput<'a, T> {
+    /// Decodes the input, or returns `None` if it can't be decoded
+    fn decode(&self, data: &'a [u8]) -> Option<T>;
+
+    /// The mutator which keeps inputs decodable
+    fn mutator(&self) -> Option<Mutator> {
+        None
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, T: arbitrary::Arbitrary<'a>> DecodeInput<'a, T> for Input<T> {
+    fn decode(&self, data: &'a [u8]) -> Option<T> {
+        T::arbitrary_take_rest(arbitrary::Unstructured::new(data)).ok()
+    }
+}
+
+#[cfg(all(fuzzing, not(any(cifuzz_afl, cifuzz_honggfuzz, cifuzz_wasm))))]
+pub use self::libfuzzer::fuzz;
+
+#[cfg(all(fuzzing, cifuzz_afl))]
+pub use self::afl::fuzz;
+
+#[cfg(all(fuzzing, cifuzz_honggfuzz))]
+pub use self::honggfuzz::fuzz;
+
+#[cfg(all(fuzzing, cifuzz_wasm))]
+pub use self::wasm::fuzz;
+
+#[cfg(all(fuzzing, not(any(cifuzz_afl, cifuzz_honggfuzz, cifuzz_wasm))))]
+mod libfuzzer {
+    #[cfg(any(cifuzz_custom_mutator, feature = "protobuf"))]
+    use std::ffi::c_uint;
+    use std::ffi::{c_char, c_int, CString};
+    use std::panic::{self, AssertUnwindSafe};
+    use std::path::{Path, PathBuf};
+    use std::process;
+    use std::sync::OnceLock;
+    #[cfg(unix)]
+    use std::time::{Duration, Instant};
+
+    // Link the libFuzzer runtime which is built by libfuzzer-sys
+    use libfuzzer_sys as _;
+
+    use super::{FuzzTest, TestOneInput};
+    #[cfg(unix)]
+    use crate::capture;
+    use crate::dictionary;
+    use crate::fdp::trace::{self, Call, Region};
+    #[cfg(any(cifuzz_custom_mutator, feature = "protobuf"))]
+    use crate::mutator::{self, Mutator};
+    #[cfg(unix)]
+    use crate::{slow_inputs, watchdog};
+
+    /// The environment variable via which cargo-cifuzz passes the
+    /// libFuzzer arguments, separated by newlines. The test binary
+    /// itself only accepts libtest arguments.
+    const LIBFUZZER_ARGS_ENV: &str = "CIFUZZ_LIBFUZZER_ARGS";
+
+    /// The environment variable via which `cargo cifuzz minimize` and
+    /// `cargo cifuzz run --trace` ask for the calls of the
+    /// `FuzzedDataProvider` made by the fuzz test, with the regions of the
+    /// input they consumed and the values they returned.
+    const FDP_CALLS_ENV: &str = "CIFUZZ_FDP_CALLS";
+
+    /// The environment variable via which `cargo cifuzz run` asks to
+    /// capture the output of the fuzz test, see [`capture`].
+    #[cfg(unix)]
+    const CAPTURE_OUTPUT_ENV: &str = "CIFUZZ_CAPTURE_OUTPUT";
+
+    /// The default of libFuzzer's `-timeout` flag.
+    #[cfg(unix)]
+    const DEFAULT_INPUT_TIMEOUT_SECS: u64 = 1200;
+
+    /// The default of libFuzzer's `-rss_limit_mb` flag.
+    #[cfg(unix)]
+    const DEFAULT_RSS_LIMIT_MB: u64 = 2048;
+
+    /// The default of libFuzzer's `-len_control` flag.
+    const DEFAULT_LEN_CONTROL: u32 = 100;
+
+    static TEST_ONE_INPUT: OnceLock<TestOneInput> = OnceLock::new();
+    #[cfg(any(cifuzz_custom_mutator, feature = "protobuf"))]
+    static MUTATOR: OnceLock<Mutator> = OnceLock::new();
+    static CALLS_FILE: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+    extern "C" {
+        fn LLVMFuzzerRunDriver(
+            argc: *mut c_int,
+            argv: *mut *mut *mut c_char,
+            user_cb: extern "C" fn(data: *const u8, size: usize) -> c_int,
+        ) -> c_int;
+    }
+
+    /// Runs the fuzz test under libFuzzer. This never returns, libFuzzer
+    /// exits the process when it's done.
+    pub fn fuzz(test: &FuzzTest, test_one_input: TestOneInput) -> ! {
+        if TEST_ONE_INPUT.set(test_one_input).is_err() {
+            panic!(
+                "only a single fuzz test can be run per process, but {} is the second one",
+                test.name
+            );
+        }
+        #[cfg(any(cifuzz_custom_mutator, feature = "protobuf"))]
+        if let Some(mutator) = test.mutator {
+            let _ = MUTATOR.set(mutator);
+        }
+
+        let mut args = vec![std::env::args()
+            .next()
+            .unwrap_or_else(|| test.name.to_string())];
+        if let Ok(libfuzzer_args) = std::env::var(LIBFUZZER_ARGS_ENV) {
+            args.extend(
+                libfuzzer_args
+                    .lines()
+                    .filter(|a| !a.is_empty())
+                    .map(String::from),
+            );
+        }
+        // libFuzzer disables its length control if the binary defines a
+        // custom mutator, which it does if any of its fuzz tests has one
+        if test.mutator.is_none() && !args.iter().any(|a| a.starts_with("-len_control=")) {
+            args.push(format!("-len_control={DEFAULT_LEN_CONTROL}"));
+        }
+        if !test.dictionary.is_empty() {
+            add_dictionary(test, &mut args);
+        }
+        // The output isn't captured if the user closes stdout or stderr
+        // with libFuzzer's flag
+        #[cfg(unix)]
+        if std::env::var_os(CAPTURE_OUTPUT_ENV).is_some()
+            && !args.iter().any(|a| a.starts_with("-close_fd_mask="))
+            && capture::start()
+        {
+            args.push("-close_fd_mask=3".to_string());
+        }
+        #[cfg(unix)]
+        watchdog::start(limits(&args));
+        #[cfg(unix)]
+        slow_inputs::start();
+        let args: Vec<CString> = args
+            .into_iter()
+            .map(|a| CString::new(a).expect("libFuzzer arguments must not contain NUL bytes"))
+            .collect();
+        // libFuzzer expects a NULL-terminated argv, which it may modify
+        let mut argv: Vec<*mut c_char> = args.iter().map(|a| a.as_ptr() as *mut c_char).collect();
+        argv.push(std::ptr::null_mut());
+        let mut argc = args.len() as c_int;
+        let mut argv_ptr = argv.as_mut_ptr();
+
+        let status =
+            unsafe { LLVMFuzzerRunDriver(&mut argc, &mut argv_ptr, test_one_input_callback) };
+        process::exit(status);
+    }
+
+    /// Writes the tokens of the `dictionary!` invocations to a dictionary
+    /// file and passes it to libFuzzer. libFuzzer only accepts a single
+    /// dictionary, so the one passed by cargo-cifuzz is merged into it.
+    fn add_dictionary(test: &FuzzTest, args: &mut Vec<String>) {
+        let mut dict = String::new();
+        if let Some(pos) = args.iter().position(|a| a.starts_with("-dict=")) {
+            let path = args.remove(pos)["-dict=".len()..].to_string();
+            match std::fs::read_to_string(&path) {
+                Ok(content) => dict.push_str(&content),
+                Err(err) => eprintln!("failed to read {path}: {err}"),
+            }
+            if !dict.is_empty() && !dict.ends_with('\n') {
+                dict.push('\n');
+            }
+        }
+        dict.push_str(&dictionary::format(test.dictionary));
+
+        let path =
+            std::env::temp_dir().join(format!("cifuzz-{}-{}.dict", test.name, process::id()));
+        if let Err(err) = std::fs::write(&path, dict) {
+            eprintln!("failed to write {}: {err}", path.display());
+            return;
+        }
+        args.push(format!("-dict={}", path.display()));
+    }
+
+    /// The limits passed to libFuzzer, which the watchdog enforces as
+    /// well. libFuzzer's defaults apply to limits which aren't passed.
+    #[cfg(unix)]
+    fn limits(args: &[String]) -> watchdog::Limits {
+        // libFuzzer uses the last value of a flag
+        let flag = |name: &str| {
+            args.iter()
+                .rev()
+                .find_map(|a| a.strip_prefix(name)?.strip_prefix('='))
+        };
+        let number = |name: &str, default: u64| {
+            flag(name)
+                .map_or(Some(default), |value| value.parse().ok())
+                .filter(|&n| n > 0)
+        };
+        watchdog::Limits {
+            timeout: number("-timeout", DEFAULT_INPUT_TIMEOUT_SECS).map(Duration::from_secs),
+            rss_limit_mb: number("-rss_limit_mb", DEFAULT_RSS_LIMIT_MB),
+            artifact_prefix: flag("-artifact_prefix").unwrap_or_default().to_string(),
+        }
+    }
+
+    extern "C" fn test_one_input_callback(data: *const u8, size: usize) -> c_int {
+        let data = if size == 0 {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(data, size) }
+        };
+        run(data);
+        0
+    }
+
+    fn run(data: &[u8]) {
+        let test_one_input = TEST_ONE_INPUT.get().expect("fuzz test was not registered");
+        let calls_file =
+            CALLS_FILE.get_or_init(|| std::env::var_os(FDP_CALLS_ENV).map(PathBuf::from));
+        if calls_file.is_some() {
+            trace::start(true);
+        }
+        #[cfg(unix)]
+        capture::input_started();
+        #[cfg(unix)]
+        watchdog::input_started(data);
+        #[cfg(unix)]
+        let start = Instant::now();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| test_one_input(data)));
+        #[cfg(unix)]
+        {
+            // The time of a panicking input includes the stack trace
+            if result.is_ok() {
+                slow_inputs::input_finished(data, start.elapsed());
+            }
+            watchdog::input_finished();
+        }
+        if let Some(path) = calls_file {
+            trace::finish();
+            write_calls(path, &trace::take_calls());
+        }
+        // The panic hook already printed the panic message, abort so
+        // that libFuzzer detects the crash and stores the input
+        if result.is_err() {
+            #[cfg(unix)]
+            {
+                let _ = std::io::Write::flush(&mut std::io::stdout());
+                capture::finish();
+                slow_inputs::write();
+            }
+            process::abort();
+        }
+    }
+
+    /// Writes the calls of the provider made by the fuzz test, one per
+    /// line as `<regions>\t<method>\t<value>`, where the regions are
+    /// formatted as `<front|back> <start> <len>` and separated by commas.
+    fn write_calls(path: &Path, calls: &[Call]) {
+        let content: String = calls
+            .iter()
+            .map(|call| {
+                let regions: Vec<String> = call.regions.iter().map(format_region).collect();
+                format!("{}\t{}\t{}\n", regions.join(","), call.method, call.value)
+            })
+            .collect();
+        if let Err(err) = std::fs::write(path, content) {
+            eprintln!("failed to write {}: {err}", path.display());
+        }
+    }
+
+    fn format_region(region: &Region) -> String {
+        let side = if region.from_end { "back" } else { "front" };
+        format!("{side} {} {}", region.start, region.len)
+    }
+
+    /// Called by the `LLVMFuzzerTestOneInput` symbol of libfuzzer-sys.
+    #[no_mangle]
+    #[allow(improper_ctypes_definitions)]
+    extern "C" fn rust_fuzzer_test_input(data: &[u8]) -> i32 {
+        run(data);
+        0
+    }
+
+    /// Mutates the input with the custom mutator of the fuzz test, or
+    /// with the built-in mutations of libFuzzer if another fuzz test of
+    /// the executable has the mutator. Not defined unless a fuzz test
+    /// has a custom mutator, see [`mutator`].
+    #[cfg(any(cifuzz_custom_mutator, feature = "protobuf"))]
+    #[no_mangle]
+    extern "C" fn LLVMFuzzerCustomMutator(
+        data: *mut u8,
+        size: usize,
+        max_size: usize,
+        seed: c_uint,
+    ) -> usize {
+        let buffer = unsafe { std::slice::from_raw_parts_mut(data, max_size) };
+        let mut input = buffer[..size].to_vec();
+        match MUTATOR.get() {
+            Some(mutator) => (mutator.mutate)(&mut input, max_size, seed),
+            None => mutator::mutate_bytes(&mut input, max_size),
+        }
+        input.truncate(max_size);
+        buffer[..input.len()].copy_from_slice(&input);
+        input.len()
+    }
+
+    /// Combines two inputs with the custom mutator of the fuzz test.
+    /// Returning 0 makes libFuzzer pick another mutation.
+    #[cfg(any(cifuzz_custom_mutator, feature = "protobuf"))]
+    #[no_mangle]
+    extern "C" fn LLVMFuzzerCustomCrossOver(
+        first: *const u8,
+        first_size: usize,
+        second: *const u8,
+        second_size: usize,
+        out: *mut u8,
+        max_out_size: usize,
+        seed: c_uint,
+    ) -> usize {
+        let Some(mutator) = MUTATOR.get() else {
+            return 0;
+        };
+        let (first, second) = unsafe {
+            (
+                std::slice::from_raw_parts(first, first_size),
+                std::slice::from_raw_parts(second, second_size),
+            )
+        };
+        let mut input = Vec::new();
+        (mutator.crossover)(first, second, &mut input, max_out_size, seed);
+        input.truncate(max_out_size);
+        unsafe {
+            std::slice::from_raw_parts_mut(out, max_out_size)[..input.len()].copy_from_slice(&input)
+        };
+        input.len()
+    }
+}
+
+#[cfg(all(fuzzing, cifuzz_afl))]
+mod afl {
+    use std::ffi::c_int;
+    use std::io::Read;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::process;
+
+    use super::{FuzzTest, TestOneInput};
+
+    /// The number of inputs a process forked by the fork server executes
+    /// before AFL++ forks a new one, the same as afl.rs uses.
+    const PERSISTENT_LOOP_COUNT: u32 = 1000;
+
+    // afl-fuzz searches the executable for these signatures to detect
+    // that the fork server is started by `__afl_manual_init` and that
+    // the target loops over the inputs in persistent mode
+    static PERSISTENT_SIGNATURE: [u8; 23] = *b"##SIG_AFL_PERSISTENT##\0";
+    static DEFER_SIGNATURE: [u8; 26] = *b"##SIG_AFL_DEFER_FORKSRV##\0";
+
+    // Defined by afl-compiler-rt.o of AFL++
+    extern "C" {
+        fn __afl_manual_init();
+        fn __afl_persistent_loop(max_count: u32) -> c_int;
+    }
+
+    /// Runs the fuzz test under AFL++ in persistent mode. The fork server
+    /// is started here, after libtest started the test, instead of
+    /// before `main`. Every forked process reads its inputs from stdin.
+    /// Outside of AFL++, the fuzz test is executed once with the input
+    /// from stdin, which is how crashes are reproduced.
+    pub fn fuzz(_test: &FuzzTest, test_one_input: TestOneInput) -> ! {
+        unsafe {
+            // The signatures must not be removed by the linker
+            std::ptr::read_volatile(&PERSISTENT_SIGNATURE);
+            std::ptr::read_volatile(&DEFER_SIGNATURE);
+            __afl_manual_init();
+        }
+
+        let mut input = Vec::new();
+        while unsafe { __afl_persistent_loop(PERSISTENT_LOOP_COUNT) } != 0 {
+            input.clear();
+            if let Err(err) = std::io::stdin().read_to_end(&mut input) {
+                eprintln!("failed to read the input from stdin: {err}");
+                process::exit(1);
+            }
+            // The panic hook already printed the panic message, abort so
+            // that AFL++ detects the crash
+            if panic::catch_unwind(AssertUnwindSafe(|| test_one_input(&input))).is_err() {
+                process::abort();
+            }
+        }
+        process::exit(0);
+    }
+}
+
+#[cfg(all(fuzzing, cifuzz_honggfuzz))]
+mod honggfuzz {
+    use std::io::Read;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::process;
+
+    use super::{FuzzTest, TestOneInput};
+
+    /// Set by cargo-cifuzz when it runs the fuzz test under honggfuzz.
+    // Must be kept in sync with crates/cargo-cifuzz/src/runner/honggfuzz.rs
+    const HONGGFUZZ_ENV: &str = "CIFUZZ_HONGGFUZZ";
+
+    // Defined by libhfuzz.a of honggfuzz
+    extern "C" {
+        fn HF_ITER(buf: *mut *const u8, len: *mut usize);
+    }
+
+    /// Runs the fuzz test under honggfuzz in persistent mode, where
+    /// honggfuzz passes the inputs to the process one after the other.
+    /// Outside of honggfuzz, the fuzz test is executed once with the
+    /// input from stdin, which is how crashes are reproduced.
+    pub fn fuzz(_test: &FuzzTest, test_one_input: TestOneInput) -> ! {
+        if std::env::var_os(HONGGFUZZ_ENV).is_none() {
+            let mut input = Vec::new();
+            if let Err(err) = std::io::stdin().read_to_end(&mut input) {
+                eprintln!("failed to read the input from stdin: {err}");
+                process::exit(1);
+            }
+            run(test_one_input, &input);
+            process::exit(0);
+        }
+
+        loop {
+            let mut data = std::ptr::null();
+            let mut len = 0;
+            let input = unsafe {
+                HF_ITER(&mut data, &mut len);
+                if len == 0 {
+                    &[]
+                } else {
+                    std::slice::from_raw_parts(data, len)
+                }
+            };
+            run(test_one_input, input);
+        }
+    }
+
+    fn run(test_one_input: TestOneInput, input: &[u8]) {
+        // The panic hook already printed the panic message, abort so
+        // that honggfuzz detects the crash
+        if panic::catch_unwind(AssertUnwindSafe(|| test_one_input(input))).is_err() {
+            process::abort();
+        }
+    }
+}
+
+#[cfg(all(fuzzing, cifuzz_wasm))]
+mod wasm {
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Mutex, OnceLock};
+
+    use super::{FuzzTest, TestOneInput};
+    use crate::dictionary;
+
+    /// The environment variable via which cargo-cifuzz passes the
+    /// libFuzzer arguments, separated by newlines, see the libFuzzer
+    /// harness.
+    const LIBFUZZER_ARGS_ENV: &str = "CIFUZZ_LIBFUZZER_ARGS";
+
+    static TEST_ONE_INPUT: OnceLock<TestOneInput> = OnceLock::new();
+    /// The buffer which the driver writes the inputs to
+    static INPUT: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+    /// The range of the inline 8-bit counters of all instrumented code
+    static COUNTERS_START: AtomicUsize = AtomicUsize::new(usize::MAX);
+    static COUNTERS_END: AtomicUsize = AtomicUsize::new(0);
+
+    // Defined by cifuzz-wasm-driver
+    #[link(wasm_import_module = "cifuzz")]
+    extern "C" {
+        fn run_libfuzzer(
+            args: *const u8,
+            args_len: usize,
+            dictionary: *const u8,
+            dictionary_len: usize,
+            counters: *const u8,
+            counters_len: usize,
+        ) -> i32;
+    }
+
+    /// Runs the fuzz test under libFuzzer in cifuzz-wasm-driver, which
+    /// passes the inputs to [`cifuzz_test_one_input`]. Panics abort on
+    /// wasm32-wasip1, so the `ignore_panics` patterns can't be applied
+    /// and every panic is a crash. Custom mutators aren't supported
+    /// either, libFuzzer doesn't call into the module to mutate.
+    pub fn fuzz(test: &FuzzTest, test_one_input: TestOneInput) -> ! {
+        if TEST_ONE_INPUT.set(test_one_input).is_err() {
+            panic!(
+                "only a single fuzz test can be run per process, but {} is the second one",
+                test.name
+            );
+        }
+        if test.mutator.is_some() {
+            eprintln!(
+                "warning: custom mutators aren't supported on wasm, {} is mutated by libFuzzer",
+                test.name
+            );
+        }
+
+        let args = std::env::var(LIBFUZZER_ARGS_ENV).unwrap_or_default();
+        let dictionary = dictionary::format(test.dictionary);
+        let start = COUNTERS_START.load(Ordering::Relaxed);
+        let end = COUNTERS_END.load(Ordering::Relaxed);
+        let (counters, counters_len) = if start < end {
+            (start, end - start)
+        } else {
+            (0, 0)
+        };
+        let status = unsafe {
+            run_libfuzzer(
+                args.as_ptr(),
+                args.len(),
+                dictionary.as_ptr(),
+                dictionary.len(),
+                counters as *const u8,
+                counters_len,
+            )
+        };
+        process::exit(status);
+    }
+
+    /// Returns the buffer for an input of `len` bytes, which the driver
+    /// writes the input to before calling [`cifuzz_test_one_input`].
+    #[no_mangle]
+    extern "C" fn cifuzz_input_buffer(len: usize) -> *mut u8 {
+        let mut input = INPUT.lock().unwrap_or_else(|e| e.into_inner());
+        input.resize(len, 0);
+        input.as_mut_ptr()
+    }
+
+    /// Executes the fuzz test with the input in the buffer.
+    #[no_mangle]
+    extern "C" fn cifuzz_test_one_input(len: usize) {
+        let test_one_input = TEST_ONE_INPUT.get().expect("fuzz test was not registered");
+        let input = std::mem::take(&mut *INPUT.lock().unwrap_or_else(|e| e.into_inner()));
+        test_one_input(&input[..len]);
+        // Keep the allocation for the next input
+        *INPUT.lock().unwrap_or_else(|e| e.into_inner()) = input;
+    }
+
+    /// Called for the counters of every instrumented module by the
+    /// SanitizerCoverage instrumentation, before `main`. The linker
+    /// places all counters in a single section, so they're passed to the
+    /// driver as a single range.
+    #[no_mangle]
+    extern "C" fn __sanitizer_cov_8bit_counters_init(start: *const u8, stop: *const u8) {
+        COUNTERS_START.fetch_min(start as usize, Ordering::Relaxed);
+        COUNTERS_END.fetch_max(stop as usize, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignored_panics() {
+        const IGNORE_PANICS: &[&str] = &["invalid input", "parse error"];
+        run_input(b"a", |_| panic!("parse error at {}", 1), IGNORE_PANICS);
+        run_input(b"a", |_| {}, IGNORE_PANICS);
+        let result = panic::catch_unwind(|| {
+            run_input(b"a", |_| panic!("index out of bounds"), IGNORE_PANICS)
+        });
+        assert_eq!(
+            regression::panic_message(&*result.unwrap_err()),
+            "index out of bounds"
+        );
+        assert!(panic::catch_unwind(|| run_input(b"a", |_| panic!("parse error"), &[])).is_err());
+    }
+}
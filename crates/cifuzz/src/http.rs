@@ -0,0 +1,311 @@
+//! Fuzzing the handlers of web services with HTTP requests decoded from
+//! the input, with the `http` feature.
+//!
+//! [`consume_request`] decodes a request with a method, a path with a
+//! query, headers and a body. The adapters dispatch it through the
+//! router or service of the web service, without a server or sockets:
+//!
+//! * [`call_handler`] for a function returning the response,
+//! * [`call_tower`] for a tower service, like an axum `Router`, with the
+//!   `tower` feature,
+//! * [`call_hyper`] for a hyper service, with the `hyper` feature,
+//! * [`call_actix`] for an actix-web app initialized with
+//!   `actix_web::test::init_service`, with the `actix` feature.
+//!
+//! ```ignore
+//! #[fuzz_test(runtime = "tokio")]
+//! async fn api_fuzz_test(fdp: &mut FuzzedDataProvider) {
+//!     let _ = cifuzz::http::call_tower::<_, axum::body::Body, _>(app(), fdp).await;
+//! }
+//! ```
+//!
+//! Services which accept requests with different bodies, like an axum
+//! `Router`, need the type of the body to be given.
+//!
+//! Panics of the handlers are findings like any other panic. Web
+//! frameworks often catch them and respond with a server error instead,
+//! e.g. with tower-http's `CatchPanicLayer`. The adapters report such
+//! responses as findings: if a panic happened on any thread while the
+//! request was handled and the response has a 5xx status, the message
+//! of the panic is reported. Server errors without a panic are regular
+//! responses, because services respond with them by design, e.g. when a
+//! backend is unavailable.
+
+use ::http::{Method, Request, Response, Uri};
+
+use crate::oracle::report_finding;
+use crate::{harness, FuzzedDataProvider, Severity};
+
+/// The methods of the requests.
+const METHODS: [Method; 9] = [
+    Method::GET,
+    Method::POST,
+    Method::PUT,
+    Method::DELETE,
+    Method::PATCH,
+    Method::HEAD,
+    Method::OPTIONS,
+    Method::CONNECT,
+    Method::TRACE,
+];
+
+/// The names of the headers of the requests, which handlers and
+/// extractors commonly read.
+const HEADERS: [&str; 12] = [
+    "accept",
+    "accept-encoding",
+    "authorization",
+    "content-encoding",
+    "content-length",
+    "content-type",
+    "cookie",
+    "host",
+    "if-none-match",
+    "origin",
+    "range",
+    "user-agent",
+];
+
+/// The characters of the path and the query, after the leading `/`.
+const PATH_CHARS: &str = "abcdefghijklmnopqrstuvwxyz0123456789/-._~%?=&+:@";
+
+/// The characters of the header values.
+const VALUE_CHARS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 \
+                           !\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+const MAX_PATH_LEN: usize = 64;
+const MAX_HEADERS: usize = 8;
+const MAX_HEADER_VALUE_LEN: usize = 64;
+
+/// Decodes a request from the input: the method, the path with the
+/// query, up to 8 headers and the remaining bytes as the body. Paths
+/// which aren't valid URIs are replaced with `/`.
+///
+/// ```
+/// use cifuzz::FuzzedDataProvider;
+///
+/// let mut fdp = FuzzedDataProvider::new(b"\x00\x01\x02\\ {}\x00\x00");
+/// let request = cifuzz::http::consume_request(&mut fdp);
+/// assert_eq!(request.method(), "GET");
+/// assert_eq!(request.uri(), "/abc");
+/// assert!(request.headers().is_empty());
+/// assert_eq!(request.body(), b"{}");
+/// ```
+pub fn consume_request(fdp: &mut FuzzedDataProvider) -> Request<Vec<u8>> {
+    let method = fdp.pick_value_in_slice(&METHODS).clone();
+    let path = fdp.consume_string_from_charset(PATH_CHARS, MAX_PATH_LEN);
+    let uri = Uri::try_from(format!("/{path}")).unwrap_or_else(|_| Uri::from_static("/"));
+    let mut builder = Request::builder().method(method).uri(uri);
+    for _ in 0..fdp.consume_int_in_range(0, MAX_HEADERS) {
+        let name = *fdp.pick_value_in_slice(&HEADERS);
+        let value = fdp.consume_string_from_charset(VALUE_CHARS, MAX_HEADER_VALUE_LEN);
+        builder = builder.header(name, value.trim());
+    }
+    builder
+        .body(fdp.consume_remaining_bytes().to_vec())
+        .expect("the decoded request is valid")
+}
+
+/// Decodes a request with [`consume_request`], passes it to the handler
+/// and returns the response. Reports a server error after a panic as a
+/// finding, see the [module documentation](self).
+pub fn call_handler<B>(
+    fdp: &mut FuzzedDataProvider,
+    handler: impl FnOnce(Request<Vec<u8>>) -> Response<B>,
+) -> Response<B> {
+    let request = consume_request(fdp);
+    let line = request_line(&request);
+    harness::record_panics();
+    let response = handler(request);
+    check_response(&line, response.status().as_u16(), harness::recorded_panic());
+    response
+}
+
+/// Decodes a request with [`consume_request`], calls the tower service
+/// with it once it's ready and returns its response. The body of the
+/// request is converted from the bytes, e.g. into an `axum::body::Body`.
+/// Reports a server error after a panic as a finding, see the
+/// [module documentation](self).
+#[cfg(feature = "tower")]
+pub async fn call_tower<S, B, R>(
+    mut service: S,
+    fdp: &mut FuzzedDataProvider<'_>,
+) -> Result<Response<R>, S::Error>
+where
+    S: tower_service::Service<Request<B>, Response = Response<R>>,
+    B: From<Vec<u8>>,
+{
+    let request = consume_request(fdp).map(B::from);
+    let line = request_line(&request);
+    harness::record_panics();
+    let result = async {
+        std::future::poll_fn(|cx| service.poll_ready(cx)).await?;
+        service.call(request).await
+    }
+    .await;
+    let panic = harness::recorded_panic();
+    if let Ok(response) = &result {
+        check_response(&line, response.status().as_u16(), panic);
+    }
+    result
+}
+
+/// Decodes a request with [`consume_request`], calls the hyper service
+/// with it and returns its response. The body of the request is
+/// converted from the bytes, e.g. into an `http_body_util::Full`.
+/// Reports a server error after a panic as a finding, see the
+/// [module documentation](self).
+#[cfg(feature = "hyper")]
+pub async fn call_hyper<S, B, R>(
+    service: &S,
+    fdp: &mut FuzzedDataProvider<'_>,
+) -> Result<Response<R>, S::Error>
+where
+    S: hyper::service::Service<Request<B>, Response = Response<R>>,
+    B: From<Vec<u8>>,
+{
+    let request = consume_request(fdp).map(B::from);
+    let line = request_line(&request);
+    harness::record_panics();
+    let result = service.call(request).await;
+    let panic = harness::recorded_panic();
+    if let Ok(response) = &result {
+        check_response(&line, response.status().as_u16(), panic);
+    }
+    result
+}
+
+/// Decodes a request with [`consume_request`], calls the actix-web app
+/// with it and returns its response. Errors of the app are checked with
+/// the status of their error response. Reports a server error after a
+/// panic as a finding, see the [module documentation](self).
+///
+/// ```ignore
+/// let app = actix_web::test::init_service(App::new().service(index)).await;
+/// let _ = cifuzz::http::call_actix(&app, fdp).await;
+/// ```
+#[cfg(feature = "actix")]
+pub async fn call_actix<S, R>(
+    app: &S,
+    fdp: &mut FuzzedDataProvider<'_>,
+) -> Result<actix_web::dev::ServiceResponse<R>, actix_web::Error>
+where
+    S: actix_web::dev::Service<
+        actix_http::Request,
+        Response = actix_web::dev::ServiceResponse<R>,
+        Error = actix_web::Error,
+    >,
+{
+    // actix-web uses the types of version 0.2 of the http crate
+    let request = consume_request(fdp);
+    let line = request_line(&request);
+    let method = actix_web::http::Method::from_bytes(request.method().as_str().as_bytes())
+        .expect("the method is valid");
+    let mut test_request = actix_web::test::TestRequest::default()
+        .method(method)
+        .uri(&request.uri().to_string());
+    for (name, value) in request.headers() {
+        test_request = test_request.append_header((name.as_str(), value.as_bytes()));
+    }
+    let request = test_request.set_payload(request.into_body()).to_request();
+
+    harness::record_panics();
+    let result = app.call(request).await;
+    let status = match &result {
+        Ok(response) => response.status(),
+        Err(err) => err.as_response_error().status_code(),
+    };
+    check_response(&line, status.as_u16(), harness::recorded_panic());
+    result
+}
+
+/// The method and the URI of the request, for the message of a finding.
+fn request_line<B>(request: &Request<B>) -> String {
+    format!("{} {}", request.method(), request.uri())
+}
+
+/// Reports a finding if the response is a server error and a panic
+/// happened while the request was handled.
+#[track_caller]
+fn check_response(request_line: &str, status: u16, panic: Option<String>) {
+    if let Some(message) = panic {
+        if (500..600).contains(&status) {
+            report_finding(
+                Severity::High,
+                format_args!(
+                    "the response to {request_line} is a server error ({status}) after a panic: \
+                     {message}"
+                ),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{self, AssertUnwindSafe};
+
+    use ::http::StatusCode;
+
+    use super::*;
+    use crate::regression;
+
+    /// A handler which catches the panic of the request to `/panic`, like
+    /// a web framework.
+    fn handler(request: Request<Vec<u8>>) -> Response<()> {
+        let panics = request.uri() == "/panic";
+        let result = panic::catch_unwind(|| {
+            if panics {
+                panic!("unwrapped a None");
+            }
+        });
+        let status = match result {
+            Ok(()) => StatusCode::OK,
+            Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Response::builder().status(status).body(()).unwrap()
+    }
+
+    #[test]
+    fn decode_requests() {
+        // The path and the header value are taken from the front of the
+        // input as indices into their charsets, the method, the number of
+        // headers and the header name from the end
+        let data = b"\x08\x13\x04\x0c\x12\x2a\x08\x03\x2b\x1b\\ \
+                     \x00\x0f\x0f\x0b\x08\x02\x00\x13\x08\x0e\x0d\x4d\x09\x12\x0e\x0d\\ \
+                     {}\x05\x01\x01";
+        let mut fdp = FuzzedDataProvider::new(data);
+        let request = consume_request(&mut fdp);
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(request.uri(), "/items?id=1");
+        assert_eq!(request.headers().len(), 1);
+        assert_eq!(request.headers()["content-type"], "application/json");
+        assert_eq!(request.body(), b"{}");
+
+        let mut fdp = FuzzedDataProvider::new(&[]);
+        let request = consume_request(&mut fdp);
+        assert_eq!(request.method(), Method::GET);
+        assert_eq!(request.uri(), "/");
+        assert!(request.body().is_empty());
+    }
+
+    #[test]
+    fn report_server_errors_after_panics() {
+        // GET /panic
+        let data = b"\x0f\x00\x0d\x08\x02\\ \x00\x00";
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            call_handler(&mut FuzzedDataProvider::new(data), handler)
+        }));
+        let message = regression::panic_message(&*result.unwrap_err());
+        assert!(
+            message.contains(
+                "the response to GET /panic is a server error (500) after a panic: \
+                 unwrapped a None"
+            ),
+            "{message}"
+        );
+
+        let response = call_handler(&mut FuzzedDataProvider::new(b"\x00"), handler);
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
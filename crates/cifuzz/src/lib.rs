@@ -0,0 +1,322 @@
+//! Runtime support for cifuzz fuzz tests written in Rust.
+//!
+//! A fuzz test is a function annotated with [`fuzz_test`] which takes
+//! either the raw input generated by the fuzzer or a
+//! [`FuzzedDataProvider`] which turns it into the values the code under
+//! test expects:
+//!
+//! ```
+//! use cifuzz::{fuzz_test, FuzzedDataProvider};
+//!
+//! #[fuzz_test]
+//! fn my_fuzz_test(fdp: &mut FuzzedDataProvider) {
+//!     let n: u32 = fdp.consume_int();
+//!     let s = fdp.consume_remaining_as_string();
+//!     // call the code under test with n and s
+//!     # let _ = (n, s);
+//! }
+//! ```
+//!
+//! Parsers of text can take a `&str` instead. Inputs which aren't valid
+//! UTF-8 are skipped, or with `#[fuzz_test(invalid_utf8 = "lossy")]`
+//! passed with the invalid sequences replaced by U+FFFD:
+//!
+//! ```
+//! use cifuzz::fuzz_test;
+//!
+//! #[fuzz_test(invalid_utf8 = "lossy")]
+//! fn query_fuzz_test(query: &str) {
+//!     // call the parser with query
+//!     # let _ = query;
+//! }
+//! ```
+//!
+//! Fuzz tests of functions with several parameters can take them as
+//! arguments, which are decoded from the input one after the other with
+//! [`ConsumeFromFdp`]. References can't be decoded, a fuzz test takes a
+//! `String` instead of a `&str`:
+//!
+//! ```
+//! use cifuzz::fuzz_test;
+//!
+//! #[fuzz_test]
+//! fn call_fuzz_test(n: u32, flag: bool, s: String) {
+//!     // call the code under test with n, flag and s
+//!     # let _ = (n, flag, s);
+//! }
+//! ```
+//!
+//! Inputs with more structure can be decoded into types deriving
+//! [`FuzzDecode`]:
+//!
+//! ```
+//! use cifuzz::{fuzz_test, FuzzDecode, FuzzedDataProvider};
+//!
+//! #[derive(Debug, FuzzDecode)]
+//! enum Request {
+//!     Get { path: String },
+//!     Put {
+//!         #[fuzz(max_len = 64)]
+//!         path: String,
+//!         #[fuzz(range = 0..=1024)]
+//!         size: u32,
+//!     },
+//! }
+//!
+//! #[fuzz_test]
+//! fn request_fuzz_test(fdp: &mut FuzzedDataProvider) {
+//!     let requests: Vec<Request> = fdp.consume_vec(8);
+//!     // handle the requests
+//!     # let _ = requests;
+//! }
+//! ```
+//!
+//! Fuzz tests can also take a single argument of a type implementing
+//! `arbitrary::Arbitrary` (with the default `arbitrary` feature), which
+//! makes it easy to migrate fuzz targets written for cargo-fuzz:
+//!
+//! ```
+//! use cifuzz::fuzz_test;
+//!
+//! #[fuzz_test]
+//! fn parse_fuzz_test((name, values): (String, Vec<u32>)) {
+//!     // call the code under test with name and values
+//!     # let _ = (name, values);
+//! }
+//! ```
+//!
+//! Fuzz tests can be `async fn`s as well, which are executed to
+//! completion for every input:
+//!
+//! ```
+//! use cifuzz::fuzz_test;
+//!
+//! #[fuzz_test]
+//! async fn decode_fuzz_test(data: &[u8]) {
+//!     // await the async decoder with data
+//!     # let _ = data;
+//! }
+//! ```
+//!
+//! A minimal executor is used by default. Code which uses the timers or
+//! the I/O of a runtime needs `#[fuzz_test(runtime = "tokio")]` or
+//! `#[fuzz_test(runtime = "async-std")]` and the feature of the same
+//! name, which executes the fuzz test on a runtime reused across
+//! inputs.
+//!
+//! When the crate is built with `--cfg fuzzing` (which `cargo cifuzz`
+//! takes care of), the fuzz test is compiled into a `fuzz` test which
+//! runs it under libFuzzer. Otherwise it's compiled into a `regression`
+//! test which is executed by `cargo test`. The regression test executes
+//! the fuzz test with the empty input, all files in the
+//! `<fuzz_test>_inputs` directory next to the source file of the fuzz
+//! test, all files in the `corpus/<fuzz_test>` directory of the package
+//! and the crashing inputs of the findings of the fuzz test. The name of
+//! every input is printed before it's executed. `cargo cifuzz run` also
+//! passes these seed corpus directories to libFuzzer. See [`build`] for
+//! embedding the seed corpus into the test executable and [`seed!`] for
+//! recording inputs of the seed corpus in unit tests.
+//!
+//! APIs which panic on invalid inputs by design would make every such
+//! panic a finding. Panics whose message contains one of the
+//! `|`-separated patterns of `ignore_panics` are treated as rejected
+//! inputs instead:
+//!
+//! ```
+//! use cifuzz::fuzz_test;
+//!
+//! #[fuzz_test(ignore_panics = "invalid input|parse error")]
+//! fn parse_fuzz_test(data: &[u8]) {
+//!     // call the parser, which panics with "parse error" on invalid data
+//!     # let _ = data;
+//! }
+//! ```
+//!
+//! Setup which is too expensive to repeat for every input, like loading
+//! a model or installing a panic hook, is done by the function passed as
+//! `init`. It's called once per process, before the fuzzing engine
+//! starts or before the first input of the regression test, like
+//! `LLVMFuzzerInitialize`:
+//!
+//! ```
+//! use cifuzz::fuzz_test;
+//!
+//! fn setup() {
+//!     // load the model, set environment variables, ...
+//! }
+//!
+//! #[fuzz_test(init = setup)]
+//! fn model_fuzz_test(data: &[u8]) {
+//!     // call the code under test with data
+//!     # let _ = data;
+//! }
+//! ```
+//!
+//! Code under test which needs randomness gets it from [`rng`], whose
+//! numbers are derived from the input, so that the coverage of an input
+//! and its findings don't change between executions.
+//!
+//! Bugs which don't cause a panic, like a violated invariant, are
+//! reported with [`report_finding!`], which `cargo cifuzz` stores like a
+//! crash. Values which don't survive a round trip through an encoder are
+//! reported by [`check_roundtrip`], differences between two
+//! implementations of the same functionality by
+//! [`assert_same_behavior`].
+//!
+//! Inputs of formats which byte-level mutations rarely keep valid can be
+//! mutated by a [`CustomMutator`] instead, e.g. one derived from a
+//! grammar with [`grammar`]. With the `protobuf` feature, fuzz tests
+//! taking a message generated by prost are mutated field by field.
+//!
+//! Races of concurrent code are found by running it on [`concurrency`]
+//! threads, which switch at yield points in the order chosen by the
+//! input.
+//!
+//! Servers and clients of network protocols are fuzzed over a
+//! [`net::Duplex`], an in-memory connection whose peer is scripted by
+//! the input, instead of a socket. With the `http` feature, `cifuzz::http`
+//! decodes HTTP requests and dispatches them through the router of a web
+//! service, with adapters for tower, hyper and actix-web.
+//!
+//! Command-line tools are fuzzed through their `main` function with a
+//! [`cli::Invocation`], whose arguments and stdin are decoded from the
+//! input. `cargo cifuzz generate --bin` generates such a fuzz test for a
+//! binary of the workspace.
+//!
+//! Types which are deserialized with serde, like configuration files or
+//! the bodies of API requests, are fuzzed by the fuzz tests generated by
+//! [`fuzz_deserialize!`], one per format, with the features `json`,
+//! `yaml` and `toml`:
+//!
+//! ```ignore
+//! cifuzz::fuzz_deserialize!(config::MyConfig, [json, yaml, toml]);
+//! ```
+//!
+//! The fuzz tests, like `deserialize_my_config_json`, deserialize the
+//! input with the format and check that values which deserialize survive
+//! a round trip, see [`check_roundtrip`]. The type must implement
+//! `Serialize`, `Deserialize`, `PartialEq` and `Debug`.
+//!
+//! With the `proptest` feature, the input of a fuzz test taking a single
+//! argument can be generated by a proptest strategy, so that the
+//! generators of property tests are reused for fuzzing. The strategy
+//! draws its random numbers from the fuzzer's input instead of a random
+//! number generator:
+//!
+//! ```ignore
+//! #[fuzz_test(strategy = (any::<u32>(), "[a-z]{1,8}"))]
+//! fn lookup_fuzz_test((id, name): (u32, String)) {
+//!     lookup(id, &name);
+//! }
+//! ```
+//!
+//! The strategy is evaluated for every input, in the module of the fuzz
+//! test. `FuzzedDataProvider::consume_proptest` generates a value with
+//! a strategy from the remaining bytes of a provider.
+//!
+//! Without the default `std` feature, the crate is `no_std` and only
+//! provides the [`FuzzedDataProvider`], [`FuzzDecode`] and [`FuzzEnum`],
+//! so that embedded and kernel crates can decode inputs in harnesses of
+//! their own with the same methods. The methods and impls which return
+//! strings, vectors and boxes need the `alloc` feature.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+// Allows the code generated by the macros to refer to this crate as
+// ::cifuzz in its own tests
+#[cfg(test)]
+extern crate self as cifuzz;
+
+#[cfg(feature = "std")]
+pub mod build;
+#[cfg(all(fuzzing, not(any(cifuzz_afl, cifuzz_honggfuzz, cifuzz_wasm)), unix))]
+mod capture;
+#[cfg(feature = "std")]
+pub mod cli;
+#[cfg(feature = "std")]
+pub mod concurrency;
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml"))]
+mod deserialize;
+#[cfg(feature = "std")]
+mod dictionary;
+#[cfg(feature = "std")]
+mod executor;
+mod fdp;
+#[cfg(feature = "std")]
+pub mod grammar;
+#[cfg(feature = "std")]
+mod harness;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "std")]
+mod mutator;
+#[cfg(feature = "std")]
+pub mod net;
+#[cfg(feature = "std")]
+mod ops;
+#[cfg(feature = "std")]
+mod oracle;
+#[cfg(feature = "protobuf")]
+mod protobuf;
+#[cfg(feature = "std")]
+mod registry;
+#[cfg(feature = "std")]
+mod regression;
+#[cfg(feature = "std")]
+mod rng;
+#[cfg(feature = "std")]
+mod seed;
+#[cfg(all(fuzzing, not(any(cifuzz_afl, cifuzz_honggfuzz, cifuzz_wasm)), unix))]
+mod slow_inputs;
+#[cfg(all(fuzzing, not(any(cifuzz_afl, cifuzz_honggfuzz, cifuzz_wasm)), unix))]
+mod watchdog;
+
+#[cfg(feature = "arbitrary")]
+pub use arbitrary;
+#[cfg(feature = "proptest")]
+pub use proptest;
+
+#[cfg(feature = "std")]
+pub use cifuzz_macros::{fuzz_deserialize, fuzz_test};
+pub use cifuzz_macros::{FuzzDecode, FuzzEnum};
+#[cfg(feature = "alloc")]
+pub use fdp::SeedValue;
+pub use fdp::{ConsumeFromFdp, Float, FuzzEnum, FuzzedDataProvider, Integral};
+#[cfg(feature = "std")]
+pub use mutator::{mutate_bytes, CustomMutator};
+#[cfg(feature = "std")]
+pub use oracle::{assert_same_behavior, check_roundtrip, Fallible, Severity};
+#[cfg(feature = "std")]
+pub use rng::{rng, Rng};
+
+#[doc(hidden)]
+pub mod __private {
+    #[cfg(feature = "json")]
+    pub use crate::deserialize::Json;
+    #[cfg(feature = "toml")]
+    pub use crate::deserialize::Toml;
+    #[cfg(feature = "yaml")]
+    pub use crate::deserialize::Yaml;
+    #[cfg(any(feature = "json", feature = "yaml", feature = "toml"))]
+    pub use crate::deserialize::{check_deserialize, Format};
+    #[cfg(feature = "std")]
+    pub use crate::executor::block_on;
+    #[cfg(feature = "async-std")]
+    pub use crate::executor::block_on_async_std;
+    #[cfg(feature = "tokio")]
+    pub use crate::executor::block_on_tokio;
+    pub use crate::fdp::{ConsumeInRange, ConsumeWithMaxLen};
+    #[cfg(feature = "std")]
+    pub use crate::harness::*;
+    #[cfg(feature = "std")]
+    pub use crate::mutator::Mutator;
+    #[cfg(feature = "std")]
+    pub use crate::ops::{OpsTrace, DEFAULT_MAX_OPS};
+    #[cfg(feature = "std")]
+    pub use crate::oracle::report_finding;
+    #[cfg(feature = "std")]
+    pub use crate::seed::{record_seed, SeedTarget};
+}
@@ -0,0 +1,152 @@
+//! Custom mutators, which libFuzzer calls instead of mutating the raw
+//! bytes of an input, see [`CustomMutator`].
+//!
+//! libFuzzer uses a custom mutator if the fuzz test binary defines
+//! `LLVMFuzzerCustomMutator`, and then replaces its own mutations and
+//! crossover by the custom ones. The runtime only defines it if the fuzz
+//! test is built with the `cifuzz_custom_mutator` cfg, which
+//! `cargo cifuzz` sets for fuzz tests with `#[fuzz_test(mutator = ...)]`,
+//! or with the `protobuf` feature. It forwards to the mutator of the
+//! fuzz test, or to libFuzzer's own mutations for other fuzz tests of
+//! the same executable. AFL++ and honggfuzz ignore custom mutators.
+
+/// A mutator which is aware of the structure of the inputs of a fuzz
+/// test, e.g. one which parses a JSON document, mutates its values and
+/// serializes it again. Byte-level mutations of such inputs mostly
+/// produce ones which the code under test rejects early.
+///
+/// ```
+/// use cifuzz::{fuzz_test, mutate_bytes, CustomMutator};
+///
+/// /// Mutates the numbers of comma-separated lists of numbers.
+/// struct ListMutator;
+///
+/// impl CustomMutator for ListMutator {
+///     fn mutate(data: &mut Vec<u8>, max_size: usize, seed: u32) {
+///         let Ok(list) = std::str::from_utf8(data) else {
+///             return mutate_bytes(data, max_size);
+///         };
+///         let mut numbers: Vec<u32> = list.split(',').filter_map(|n| n.parse().ok()).collect();
+///         let index = seed as usize % (numbers.len() + 1);
+///         match numbers.get_mut(index) {
+///             Some(n) => *n = n.wrapping_add(seed),
+///             None => numbers.push(seed),
+///         }
+///         let list: Vec<String> = numbers.iter().map(u32::to_string).collect();
+///         *data = list.join(",").into_bytes();
+///     }
+/// }
+///
+/// #[fuzz_test(mutator = ListMutator)]
+/// fn list_fuzz_test(data: &[u8]) {
+///     // parse the list and call the code under test with it
+///     # let _ = data;
+/// }
+/// ```
+///
+/// The mutator is passed the input to mutate and the seed libFuzzer
+/// derives from its `-seed`, so that runs are reproducible if mutators
+/// only use the seed as their source of randomness. Inputs longer than
+/// `max_size` after the mutation are truncated.
+pub trait CustomMutator {
+    /// Mutates the input in place.
+    fn mutate(data: &mut Vec<u8>, max_size: usize, seed: u32);
+
+    /// Combines two inputs into `out`, which is empty when called. If
+    /// `out` is left empty, libFuzzer picks another mutation. By
+    /// default, no inputs are combined.
+    fn crossover(first: &[u8], second: &[u8], out: &mut Vec<u8>, max_size: usize, seed: u32) {
+        let _ = (first, second, out, max_size, seed);
+    }
+}
+
+/// The functions of a [`CustomMutator`], which the fuzz test is
+/// registered with.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy)]
+pub struct Mutator {
+    pub mutate: fn(&mut Vec<u8>, usize, u32),
+    pub crossover: fn(&[u8], &[u8], &mut Vec<u8>, usize, u32),
+}
+
+impl Mutator {
+    pub const fn of<M: CustomMutator>() -> Self {
+        Mutator {
+            mutate: M::mutate,
+            crossover: M::crossover,
+        }
+    }
+}
+
+/// Mutates the bytes of the input with libFuzzer's built-in mutations,
+/// e.g. for inputs a custom mutator can't parse. Outside of libFuzzer,
+/// e.g. in unit tests of a mutator, the input is only truncated to
+/// `max_size`.
+pub fn mutate_bytes(data: &mut Vec<u8>, max_size: usize) {
+    #[cfg(all(fuzzing, not(any(cifuzz_afl, cifuzz_honggfuzz, cifuzz_wasm))))]
+    {
+        extern "C" {
+            fn LLVMFuzzerMutate(data: *mut u8, size: usize, max_size: usize) -> usize;
+        }
+        let size = data.len().min(max_size);
+        data.resize(max_size.max(size), 0);
+        let size = unsafe { LLVMFuzzerMutate(data.as_mut_ptr(), size, max_size) };
+        data.truncate(size);
+    }
+    data.truncate(max_size);
+}
+
+/// A SplitMix64 generator, which makes the choices of a mutator depend
+/// only on the seed passed by libFuzzer.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u32) -> Self {
+        Rng(u64::from(seed))
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in the inclusive range.
+    pub(crate) fn in_range(&mut self, min: usize, max: usize) -> usize {
+        let range = (max - min) as u64;
+        let z = self.next_u64();
+        min + if range == u64::MAX {
+            z
+        } else {
+            z % (range + 1)
+        } as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Doubler;
+
+    impl CustomMutator for Doubler {
+        fn mutate(data: &mut Vec<u8>, max_size: usize, _seed: u32) {
+            data.extend_from_within(..);
+            mutate_bytes(data, max_size);
+        }
+    }
+
+    #[test]
+    fn registered_mutator() {
+        let mutator = Mutator::of::<Doubler>();
+        let mut data = b"abc".to_vec();
+        (mutator.mutate)(&mut data, 5, 0);
+        assert_eq!(data, b"abcab");
+
+        let mut out = Vec::new();
+        (mutator.crossover)(b"a", b"b", &mut out, 5, 0);
+        assert!(out.is_empty());
+    }
+}
@@ -0,0 +1,278 @@
+//! Fuzzing implementations of network protocols without sockets.
+//!
+//! A [`Duplex`] is an in-memory connection whose peer is scripted by the
+//! input of the fuzz test: what the peer sends, in chunks of which
+//! sizes, how many bytes of a write it accepts and, if enabled, when
+//! the connection fails. It implements `Read` and `Write`, and
+//! `AsyncRead` and `AsyncWrite` of tokio and async-std with the features
+//! of the same name, so a server or client can be fuzzed by passing it
+//! in place of a `TcpStream`:
+//!
+//! ```
+//! use std::io::{BufRead, BufReader, Write};
+//!
+//! use cifuzz::fuzz_test;
+//! use cifuzz::net::Duplex;
+//!
+//! #[fuzz_test]
+//! fn server_fuzz_test(data: &[u8]) {
+//!     let mut conn = BufReader::new(Duplex::new(data));
+//!     let mut line = String::new();
+//!     while conn.read_line(&mut line).is_ok_and(|n| n > 0) {
+//!         // handle the request of the client
+//!         let _ = conn.get_mut().write_all(b"OK\r\n");
+//!         line.clear();
+//!     }
+//! }
+//! ```
+//!
+//! The connection never blocks: a read returns the next chunk of the
+//! script right away, and the end of the script closes the connection,
+//! so reads return 0 from then on and writes are accepted completely.
+//! Every execution of an input sees the same sequence of events, so
+//! there are no timeouts and no nondeterminism.
+
+use std::io::{self, Read, Write};
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use std::pin::Pin;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use std::task::{Context, Poll};
+
+use crate::FuzzedDataProvider;
+
+/// The default of [`Duplex::set_max_chunk_len`].
+const DEFAULT_MAX_CHUNK_LEN: usize = 4096;
+
+/// The errors a read can fail with, if enabled.
+const READ_ERRORS: [io::ErrorKind; 3] = [
+    io::ErrorKind::Interrupted,
+    io::ErrorKind::ConnectionReset,
+    io::ErrorKind::TimedOut,
+];
+
+/// The errors a write can fail with, if enabled.
+const WRITE_ERRORS: [io::ErrorKind; 3] = [
+    io::ErrorKind::Interrupted,
+    io::ErrorKind::BrokenPipe,
+    io::ErrorKind::ConnectionReset,
+];
+
+/// An in-memory connection whose peer is scripted by the input, see the
+/// [module documentation](self).
+///
+/// The script is decoded with a [`FuzzedDataProvider`]: the data the
+/// peer sends is taken from the front of the input, the sizes of the
+/// chunks and of the accepted writes from the end.
+#[derive(Debug)]
+pub struct Duplex<'a> {
+    script: FuzzedDataProvider<'a>,
+    written: Vec<u8>,
+    max_chunk_len: usize,
+    errors: bool,
+}
+
+impl<'a> Duplex<'a> {
+    /// Creates a connection whose peer is scripted by the input.
+    pub fn new(data: &'a [u8]) -> Self {
+        Duplex {
+            script: FuzzedDataProvider::new(data),
+            written: Vec::new(),
+            max_chunk_len: DEFAULT_MAX_CHUNK_LEN,
+            errors: false,
+        }
+    }
+
+    /// Sets the size up to which the peer sends data in a single chunk,
+    /// 4096 bytes by default. Smaller chunks make it more likely that
+    /// messages are split across reads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is zero.
+    pub fn set_max_chunk_len(&mut self, len: usize) {
+        assert!(len > 0, "the chunks must be able to hold a byte");
+        self.max_chunk_len = len;
+    }
+
+    /// Enables failing reads and writes, e.g. with `Interrupted` or
+    /// `ConnectionReset`. If enabled, an additional byte is consumed for
+    /// every read and write to decide whether it fails.
+    pub fn set_errors(&mut self, enabled: bool) {
+        self.errors = enabled;
+    }
+
+    /// Returns the bytes which were written to the peer so far.
+    pub fn written(&self) -> &[u8] {
+        &self.written
+    }
+
+    /// Whether the script is exhausted, after which the peer has closed
+    /// the connection.
+    pub fn is_closed(&self) -> bool {
+        self.script.remaining_bytes() == 0
+    }
+
+    /// Returns the error the operation fails with, if errors are enabled
+    /// and the script says so.
+    fn error(&mut self, errors: &[io::ErrorKind]) -> Option<io::Error> {
+        if !self.errors || self.is_closed() {
+            return None;
+        }
+        // One in eight operations fails
+        let choice = self.script.consume_int_in_range(0, 8 * errors.len() - 1);
+        errors.get(choice).map(|&kind| io::Error::from(kind))
+    }
+}
+
+impl Read for Duplex<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.is_closed() {
+            return Ok(0);
+        }
+        if let Some(err) = self.error(&READ_ERRORS) {
+            return Err(err);
+        }
+        let len = self
+            .script
+            .consume_int_in_range(1, buf.len().min(self.max_chunk_len));
+        let chunk = self.script.consume_bytes(len);
+        buf[..chunk.len()].copy_from_slice(chunk);
+        Ok(chunk.len())
+    }
+}
+
+impl Write for Duplex<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if let Some(err) = self.error(&WRITE_ERRORS) {
+            return Err(err);
+        }
+        let len = if self.is_closed() {
+            buf.len()
+        } else {
+            self.script.consume_int_in_range(1, buf.len())
+        };
+        self.written.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncRead for Duplex<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let len = self.get_mut().read(buf.initialize_unfilled())?;
+        buf.advance(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for Duplex<'_> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(self.get_mut().write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl async_std::io::Read for Duplex<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(self.get_mut().read(buf))
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl async_std::io::Write for Duplex<'_> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(self.get_mut().write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_chunks() {
+        // The data is sent in chunks of 2 and 3 bytes, whose sizes are
+        // taken from the end of the input
+        let mut conn = Duplex::new(b"HELLO\x02\x01");
+        let mut buf = [0; 8];
+        assert_eq!(conn.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"HE");
+        assert_eq!(conn.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"LLO");
+        assert!(conn.is_closed());
+        assert_eq!(conn.read(&mut buf).unwrap(), 0);
+
+        let mut conn = Duplex::new(b"HELLO\x02\x01");
+        conn.set_max_chunk_len(1);
+        assert_eq!(conn.read(&mut buf).unwrap(), 1);
+        assert_eq!(conn.read(&mut [][..]).unwrap(), 0);
+    }
+
+    #[test]
+    fn partial_writes() {
+        let mut conn = Duplex::new(&[0x01]);
+        assert_eq!(conn.write(b"OK\r\n").unwrap(), 2);
+        // The peer accepts everything once the script is exhausted
+        assert_eq!(conn.write(b"\r\n").unwrap(), 2);
+        conn.write_all(b"BYE").unwrap();
+        assert_eq!(conn.written(), b"OK\r\nBYE");
+    }
+
+    #[test]
+    fn failing_operations() {
+        let mut conn = Duplex::new(&[0x09, 0x01, 0x02]);
+        conn.set_errors(true);
+        let mut buf = [0; 8];
+        assert_eq!(
+            conn.read(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::TimedOut
+        );
+        assert_eq!(
+            conn.write(b"OK").unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+        // The last byte doesn't make the read fail, but is consumed by it
+        assert_eq!(conn.read(&mut buf).unwrap(), 0);
+        assert!(conn.is_closed());
+    }
+}
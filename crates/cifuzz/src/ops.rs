@@ -0,0 +1,188 @@
+//! Fuzzing stateful APIs, like collections, state machines of
+//! connections or databases, with sequences of operations decoded from
+//! the input, see [`ops!`].
+//!
+//! If an operation panics, the operations executed until then are
+//! printed after the panic message, e.g.
+//!
+//! ```text
+//! Operations executed by the fuzz test:
+//!   0: Push { value: 3 }
+//!   1: Pop
+//! ```
+//!
+//! `cargo cifuzz` stores them with the finding, so that the sequence
+//! which led to the crash can be replayed by hand.
+
+use std::fmt::{Debug, Write};
+
+use crate::harness;
+
+/// The header of the printed operations, must be kept in sync with
+/// crates/cargo-cifuzz/src/parser.rs
+const TRACE_HEADER: &str = "Operations executed by the fuzz test:";
+
+/// The maximum number of operations executed for an input, unless
+/// [`ops!`](crate::ops!) is passed a `max`.
+pub const DEFAULT_MAX_OPS: usize = 64;
+
+/// Executes a sequence of operations decoded from the input of the fuzz
+/// test. Every operation has a name, optionally fields of types
+/// implementing [`ConsumeFromFdp`](crate::ConsumeFromFdp), and the code
+/// to execute:
+///
+/// ```
+/// use cifuzz::{fuzz_test, ops, FuzzedDataProvider};
+///
+/// #[fuzz_test]
+/// fn vec_fuzz_test(fdp: &mut FuzzedDataProvider) {
+///     let mut vec = Vec::new();
+///     ops!(fdp, max = 32,
+///         Push { value: u8 } => vec.push(value),
+///         Pop => {
+///             vec.pop();
+///         },
+///         Truncate { len: usize } => vec.truncate(len),
+///     );
+/// }
+/// ```
+///
+/// Operations are executed until `max` (by default [`DEFAULT_MAX_OPS`])
+/// operations were executed or the input is exhausted. For every one,
+/// the operation is selected first, followed by its fields in order.
+/// The provider expression is evaluated for every operation, so that the
+/// code of the operations can consume from it as well.
+#[macro_export]
+macro_rules! ops {
+    (
+        $fdp:expr,
+        $($op:ident $({ $($field:ident : $ty:ty),* $(,)? })? => $body:expr),+ $(,)?
+    ) => {
+        $crate::ops!(
+            $fdp,
+            max = $crate::__private::DEFAULT_MAX_OPS,
+            $($op $({ $($field: $ty),* })? => $body),+
+        )
+    };
+    (
+        $fdp:expr,
+        max = $max:expr,
+        $($op:ident $({ $($field:ident : $ty:ty),* $(,)? })? => $body:expr),+ $(,)?
+    ) => {{
+        #[derive(Debug)]
+        enum Op {
+            $($op $({ $($field: $ty),* })?),+
+        }
+        const COUNT: usize = [$(::core::stringify!($op)),+].len();
+
+        let mut trace = $crate::__private::OpsTrace::new();
+        while trace.len() < $max && $fdp.remaining_bytes() > 0 {
+            let index: usize = $fdp.consume_int_in_range(0, COUNT - 1);
+            let mut i = 0;
+            #[allow(unused_assignments)]
+            let op = 'op: {
+                $(
+                    if index == i {
+                        break 'op Op::$op $({ $($field: $fdp.consume::<$ty>()),* })?;
+                    }
+                    i += 1;
+                )+
+                ::core::unreachable!()
+            };
+            trace.push(&op);
+            match op {
+                $(Op::$op $({ $($field),* })? => {
+                    $body;
+                })+
+            }
+        }
+    }};
+}
+
+/// The operations executed for the current input, which are printed if
+/// the fuzz test panics while they're executed.
+#[doc(hidden)]
+pub struct OpsTrace {
+    ops: Vec<String>,
+}
+
+impl OpsTrace {
+    pub fn new() -> Self {
+        OpsTrace { ops: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub fn push(&mut self, op: &impl Debug) {
+        self.ops.push(format!("{op:?}"));
+    }
+
+    fn format(&self) -> String {
+        let mut trace = format!("{TRACE_HEADER}\n");
+        for (i, op) in self.ops.iter().enumerate() {
+            let _ = writeln!(trace, "  {i}: {op}");
+        }
+        trace
+    }
+}
+
+impl Default for OpsTrace {
+    fn default() -> Self {
+        OpsTrace::new()
+    }
+}
+
+impl Drop for OpsTrace {
+    fn drop(&mut self) {
+        if std::thread::panicking() && !self.is_empty() && !harness::is_ignoring_panic() {
+            eprint!("{}", self.format());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::FuzzedDataProvider;
+
+    #[test]
+    fn execute_operations() {
+        let mut fdp = FuzzedDataProvider::new(&[5, 1, 0, 7, 0]);
+        let log = RefCell::new(Vec::new());
+        ops!(&mut fdp,
+            Push { value: u8 } => log.borrow_mut().push(format!("push {value}")),
+            Pop => log.borrow_mut().push("pop".to_string()),
+        );
+        assert_eq!(*log.borrow(), ["push 7", "push 1", "pop"]);
+
+        let mut fdp = FuzzedDataProvider::new(&[0; 16]);
+        let mut count = 0;
+        ops!(&mut fdp, max = 3, Nop => count += 1);
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn trace_format() {
+        #[derive(Debug)]
+        #[allow(dead_code)]
+        enum Op {
+            Push { value: u8 },
+            Pop,
+        }
+        let mut trace = OpsTrace::new();
+        trace.push(&Op::Push { value: 3 });
+        trace.push(&Op::Pop);
+        assert_eq!(
+            trace.format(),
+            "Operations executed by the fuzz test:\n  0: Push { value: 3 }\n  1: Pop\n"
+        );
+    }
+}
@@ -0,0 +1,361 @@
+//! Reporting bugs which don't crash the fuzz test, e.g. a violated
+//! invariant, a value which doesn't survive a round trip (see
+//! [`check_roundtrip`]) or two implementations which behave differently
+//! (see [`assert_same_behavior`]).
+//!
+//! A reported finding panics with a message starting with
+//! `cifuzz finding (<severity> severity): `, so that `cargo cifuzz`
+//! stores and deduplicates it like any other panic, but with the
+//! severity and the message of the report.
+
+use std::borrow::Borrow;
+use std::fmt::{self, Debug};
+
+use crate::harness;
+
+/// The prefix of the panic message of a reported finding, must be kept
+/// in sync with crates/cargo-cifuzz/src/finding.rs
+const MESSAGE_PREFIX: &str = "cifuzz finding";
+
+/// The severity of a finding reported with
+/// [`report_finding!`](crate::report_finding!).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+        }
+    }
+}
+
+/// Reports a finding with a message formatted like with [`panic!`] and
+/// stops the execution of the input. The severity is high unless it's
+/// given before the message:
+///
+/// ```should_panic
+/// use cifuzz::report_finding;
+///
+/// let (input, decoded) = ("ä", "a");
+/// if decoded != input {
+///     report_finding!(severity = Medium, "round trip mismatch: {input:?} != {decoded:?}");
+/// }
+/// ```
+#[macro_export]
+macro_rules! report_finding {
+    (severity = $severity:ident, $($arg:tt)+) => {
+        $crate::__private::report_finding(
+            $crate::Severity::$severity,
+            ::core::format_args!($($arg)+),
+        )
+    };
+    ($($arg:tt)+) => {
+        $crate::__private::report_finding(
+            $crate::Severity::High,
+            ::core::format_args!($($arg)+),
+        )
+    };
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn report_finding(severity: Severity, message: fmt::Arguments) -> ! {
+    panic!("{MESSAGE_PREFIX} ({} severity): {message}", severity.name())
+}
+
+/// Executes two implementations of the same functionality, e.g. a Rust
+/// rewrite and the C library it replaces, with the same input and
+/// reports a finding if they behave differently, i.e. if they return
+/// different values or only one of them panics. Returns the result of
+/// the first implementation.
+///
+/// ```
+/// use cifuzz::{assert_same_behavior, fuzz_test};
+///
+/// fn parse_old(data: &[u8]) -> Option<u32> {
+///     std::str::from_utf8(data).ok()?.parse().ok()
+/// }
+///
+/// fn parse_new(data: &[u8]) -> Option<u32> {
+///     std::str::from_utf8(data).ok()?.trim().parse().ok()
+/// }
+///
+/// #[fuzz_test]
+/// fn parse_fuzz_test(data: &[u8]) {
+///     assert_same_behavior(data, parse_old, parse_new);
+/// }
+/// ```
+///
+/// Panics of the implementations are compared by their message. If both
+/// panic with the same message, the panic is resumed, so that it's
+/// reported like any other crash of the fuzz test.
+#[track_caller]
+pub fn assert_same_behavior<I, A, B>(
+    input: &I,
+    first: impl FnOnce(&I) -> A,
+    second: impl FnOnce(&I) -> B,
+) -> A
+where
+    I: ?Sized,
+    A: PartialEq<B> + Debug,
+    B: Debug,
+{
+    let first = harness::catch_panic(|| first(input));
+    let second = harness::catch_panic(|| second(input));
+    match (first, second) {
+        (Ok(first), Ok(second)) if first == second => first,
+        (Err(first), Err(second)) if first == second => panic!("{first}"),
+        (first, second) => report_finding(
+            Severity::High,
+            format_args!(
+                "implementations behave differently: the first {}, the second {}",
+                Behavior(&first),
+                Behavior(&second)
+            ),
+        ),
+    }
+}
+
+struct Behavior<'a, T>(&'a Result<T, String>);
+
+impl<T: Debug> fmt::Display for Behavior<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Ok(value) => write!(f, "returned {value:?}"),
+            Err(message) => write!(f, "panicked with {message:?}"),
+        }
+    }
+}
+
+/// The result of decoding or encoding a value, which failed if it's
+/// `None` or an `Err`.
+pub trait Fallible {
+    type Value;
+
+    /// Returns the value, or the description of the error.
+    fn into_result(self) -> Result<Self::Value, String>;
+}
+
+impl<T> Fallible for Option<T> {
+    type Value = T;
+
+    fn into_result(self) -> Result<T, String> {
+        self.ok_or_else(|| "None".to_string())
+    }
+}
+
+impl<T, E: Debug> Fallible for Result<T, E> {
+    type Value = T;
+
+    fn into_result(self) -> Result<T, String> {
+        self.map_err(|error| format!("{error:?}"))
+    }
+}
+
+impl Fallible for Vec<u8> {
+    type Value = Vec<u8>;
+
+    fn into_result(self) -> Result<Vec<u8>, String> {
+        Ok(self)
+    }
+}
+
+impl Fallible for String {
+    type Value = String;
+
+    fn into_result(self) -> Result<String, String> {
+        Ok(self)
+    }
+}
+
+/// Checks that the value decoded from the input survives a round trip
+/// through the encoder, i.e. that encoding it and decoding the encoding
+/// again returns the same value. Returns the decoded value, or `None` if
+/// the input can't be decoded, which isn't a bug.
+///
+/// ```
+/// use cifuzz::{check_roundtrip, fuzz_test};
+///
+/// fn decode(data: &[u8]) -> Option<u64> {
+///     std::str::from_utf8(data).ok()?.parse().ok()
+/// }
+///
+/// #[fuzz_test]
+/// fn number_fuzz_test(data: &[u8]) {
+///     check_roundtrip(data, decode, |n| n.to_string().into_bytes());
+/// }
+/// ```
+///
+/// The encoding isn't compared with the input, because most formats
+/// have more than one encoding of a value. A finding is reported if the
+/// encoder fails, if the decoder fails to decode the encoding or if the
+/// values differ, with the part of their [`Debug`] representations which
+/// differs.
+#[track_caller]
+pub fn check_roundtrip<I, D, E>(
+    input: &I,
+    mut decode: impl FnMut(&I) -> D,
+    encode: impl FnOnce(&D::Value) -> E,
+) -> Option<D::Value>
+where
+    I: Debug + ?Sized,
+    D: Fallible,
+    D::Value: PartialEq + Debug,
+    E: Fallible,
+    E::Value: Borrow<I>,
+{
+    let value = decode(input).into_result().ok()?;
+    let encoded = match encode(&value).into_result() {
+        Ok(encoded) => encoded,
+        Err(error) => report_finding(
+            Severity::Medium,
+            format_args!("failed to encode the decoded value {value:?}: {error}"),
+        ),
+    };
+    let encoded = encoded.borrow();
+    match decode(encoded).into_result() {
+        Ok(decoded) if decoded == value => Some(value),
+        Ok(decoded) => report_finding(
+            Severity::Medium,
+            format_args!(
+                "the value changed in the round trip: {}",
+                diff(&format!("{value:?}"), &format!("{decoded:?}"))
+            ),
+        ),
+        Err(error) => report_finding(
+            Severity::Medium,
+            format_args!("failed to decode the encoding {encoded:?} of {value:?}: {error}"),
+        ),
+    }
+}
+
+/// The characters around the difference shown by [`diff`]
+const DIFF_CONTEXT: usize = 16;
+
+/// Returns the part of the two strings which differs, with a few
+/// characters of context, e.g. `…, y: 1 }` became `…, y: 2 }`.
+fn diff(before: &str, after: &str) -> String {
+    let before: Vec<char> = before.chars().collect();
+    let after: Vec<char> = after.chars().collect();
+    let prefix = before
+        .iter()
+        .zip(&after)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = before[prefix..]
+        .iter()
+        .rev()
+        .zip(after[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let excerpt = |chars: &[char]| {
+        let start = prefix.saturating_sub(DIFF_CONTEXT);
+        let end = (chars.len() - suffix + DIFF_CONTEXT).min(chars.len());
+        let mut excerpt = String::new();
+        if start > 0 {
+            excerpt.push('…');
+        }
+        excerpt.extend(&chars[start..end]);
+        if end < chars.len() {
+            excerpt.push('…');
+        }
+        excerpt
+    };
+    format!("`{}` became `{}`", excerpt(&before), excerpt(&after))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic;
+
+    use super::diff;
+    use crate::regression::panic_message;
+    use crate::{assert_same_behavior, check_roundtrip};
+
+    #[test]
+    #[should_panic(expected = "cifuzz finding (low severity): 1 != 2")]
+    fn reported_finding() {
+        crate::report_finding!(severity = Low, "{} != {}", 1, 2);
+    }
+
+    fn message<T>(result: std::thread::Result<T>) -> String {
+        panic_message(&*result.err().unwrap())
+    }
+
+    #[test]
+    fn same_behavior() {
+        assert_eq!(
+            assert_same_behavior("12", |s| s.len(), |s| s.chars().count()),
+            2
+        );
+
+        let result =
+            panic::catch_unwind(|| assert_same_behavior("ä", |s| s.len(), |s| s.chars().count()));
+        assert_eq!(
+            message(result),
+            "cifuzz finding (high severity): implementations behave differently: \
+             the first returned 2, the second returned 1"
+        );
+
+        let result = panic::catch_unwind(|| {
+            assert_same_behavior(&[1u8][..], |data| data[0], |data| data[1])
+        });
+        assert!(message(result).ends_with(
+            "the first returned 1, the second panicked with \
+             \"index out of bounds: the len is 1 but the index is 1\""
+        ));
+
+        let result = panic::catch_unwind(|| {
+            assert_same_behavior(&[][..], |data: &[u8]| data[0], |data| data[0])
+        });
+        assert_eq!(
+            message(result),
+            "index out of bounds: the len is 0 but the index is 0"
+        );
+    }
+
+    #[test]
+    fn roundtrips() {
+        let parse = |s: &str| s.parse::<f64>();
+        assert_eq!(check_roundtrip("1.50", parse, |v| v.to_string()), Some(1.5));
+        assert_eq!(check_roundtrip("a", parse, |v| v.to_string()), None);
+
+        let result = panic::catch_unwind(|| check_roundtrip("1.5", parse, |v| format!("{v:.0}")));
+        assert_eq!(
+            message(result),
+            "cifuzz finding (medium severity): the value changed in the round trip: \
+             `1.5` became `2.0`"
+        );
+        let result = panic::catch_unwind(|| check_roundtrip("1", parse, |v| format!("{v}x")));
+        assert_eq!(
+            message(result),
+            "cifuzz finding (medium severity): failed to decode the encoding \"1x\" of 1.0: \
+             ParseFloatError { kind: Invalid }"
+        );
+    }
+
+    #[test]
+    fn diffs() {
+        assert_eq!(
+            diff(
+                "Point { x: 1, y: 2, name: \"a very long name\" }",
+                "Point { x: 1, y: 3, name: \"a very long name\" }"
+            ),
+            "`…oint { x: 1, y: 2, name: \"a very …` became `…oint { x: 1, y: 3, name: \"a very …`"
+        );
+        assert_eq!(
+            diff(
+                "[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]",
+                "[1, 2, 3, 4, 5, 6, 7, 8, 9]"
+            ),
+            "`…4, 5, 6, 7, 8, 9, 10]` became `…4, 5, 6, 7, 8, 9]`"
+        );
+    }
+}
@@ -0,0 +1,791 @@
+//! Fuzz tests taking protobuf messages generated by prost, with the
+//! `protobuf` feature:
+//!
+//! ```ignore
+//! #[fuzz_test]
+//! fn request_fuzz_test(request: proto::Request) {
+//!     handle(request);
+//! }
+//! ```
+//!
+//! Instead of mutating the raw bytes of the input, which mostly breaks
+//! the encoding of the message, libFuzzer mutates the fields of the
+//! message: their values are changed, and fields are removed, duplicated,
+//! reordered or added. Nested messages are mutated the same way.
+//!
+//! The corpus stores the messages in the text format of `protoc
+//! --decode_raw`, which is readable without the schema of the message:
+//!
+//! ```text
+//! 1: "/index.html"
+//! 2: 1024
+//! 3 {
+//!   1: 0x0000000000000001
+//!   2: 0x00000002
+//! }
+//! ```
+//!
+//! Every field is written as its number and its value: varints as
+//! decimal numbers, 64-bit and 32-bit fixed-size values as hexadecimal
+//! numbers of 16 and 8 digits, length-delimited values as a nested
+//! message in braces or a string with C escapes. Comments start with
+//! `#`. Inputs which aren't in the text format, e.g. seeds in the binary
+//! encoding, are decoded as binary messages.
+
+use std::any::TypeId;
+use std::fmt::Write;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+use prost::Message;
+
+use crate::harness::{DecodeInput, Input};
+use crate::mutator::{self, CustomMutator, Mutator, Rng};
+
+/// The attempts to mutate an input into one which the message decodes.
+const MAX_MUTATION_ATTEMPTS: usize = 16;
+
+/// The nesting of messages up to which length-delimited values are
+/// printed and mutated as messages.
+const MAX_NESTING: usize = 16;
+
+/// The field numbers which are probed for the fields a message knows.
+const MAX_PROBED_FIELD_NUMBER: u32 = 32;
+
+/// The nesting of messages up to which their fields are probed.
+const MAX_PROBED_NESTING: usize = 3;
+
+/// The largest field number, which messages hardly ever use. A value
+/// which keeps a field of this number when it's nested isn't a message,
+/// but a string or bytes.
+const MAX_FIELD_NUMBER: u32 = (1 << 29) - 1;
+
+/// Values which often reach edge cases, e.g. -1 as a varint of an
+/// `int64` field.
+const INTERESTING_VALUES: [u64; 12] = [
+    0,
+    1,
+    2,
+    0x7f,
+    0x80,
+    0xff,
+    0xffff,
+    0x7fff_ffff,
+    0xffff_ffff,
+    0x8000_0000_0000_0000,
+    0x7fff_ffff_ffff_ffff,
+    u64::MAX,
+];
+
+impl<'a, T: Message + Default + 'static> DecodeInput<'a, T> for &Input<T> {
+    fn decode(&self, data: &'a [u8]) -> Option<T> {
+        match parse_text(data) {
+            Some(fields) => T::decode(&*encode(&fields)).ok(),
+            None => T::decode(data).ok(),
+        }
+    }
+
+    fn mutator(&self) -> Option<Mutator> {
+        Some(Mutator::of::<ProtobufMutator<T>>())
+    }
+}
+
+/// The mutator of fuzz tests taking a message of type `T`.
+struct ProtobufMutator<T>(PhantomData<T>);
+
+impl<T: Message + Default + 'static> CustomMutator for ProtobufMutator<T> {
+    fn mutate(data: &mut Vec<u8>, max_size: usize, seed: u32) {
+        let mut rng = Rng::new(seed);
+        let schema = schema::<T>();
+        let fields = parse_input(data);
+        for _ in 0..MAX_MUTATION_ATTEMPTS {
+            let mut mutated = fields.clone();
+            mutate_fields(&mut mutated, Some(schema), &mut rng, 0);
+            match valid_text::<T>(&mutated, max_size) {
+                Some(text) if text != *data => {
+                    *data = text;
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn crossover(first: &[u8], second: &[u8], out: &mut Vec<u8>, max_size: usize, seed: u32) {
+        let mut rng = Rng::new(seed);
+        let (mut fields, other) = (parse_input(first), parse_input(second));
+        if other.is_empty() {
+            return;
+        }
+        let field = other[rng.in_range(0, other.len() - 1)].clone();
+        match fields.iter().position(|f| f.number == field.number) {
+            Some(i) if rng.in_range(0, 1) == 0 => fields[i] = field,
+            _ => fields.insert(rng.in_range(0, fields.len()), field),
+        }
+        if let Some(text) = valid_text::<T>(&fields, max_size) {
+            *out = text;
+        }
+    }
+}
+
+/// Returns the fields of an input in the text format or the binary
+/// encoding, or no fields if it's neither.
+fn parse_input(data: &[u8]) -> Vec<Field> {
+    parse_text(data)
+        .or_else(|| parse_message(data))
+        .unwrap_or_default()
+}
+
+/// Returns the text format of the message of type `T` which the fields
+/// decode to, if it fits into the size. The message is encoded again,
+/// so that fields it doesn't know, which prost skips, aren't kept in
+/// the corpus.
+fn valid_text<T: Message + Default>(fields: &[Field], max_size: usize) -> Option<Vec<u8>> {
+    let message = T::decode(&*encode(fields)).ok()?;
+    let text = format_text(&parse_message(&message.encode_to_vec())?).into_bytes();
+    (text.len() <= max_size).then_some(text)
+}
+
+/// The fields a message knows, which the mutator adds. prost has no
+/// reflection, so they are found by decoding messages with a single
+/// field and checking whether the field is kept when the message is
+/// encoded again, as prost skips unknown fields.
+#[derive(Debug, Default)]
+struct Schema {
+    fields: Vec<KnownField>,
+}
+
+#[derive(Debug)]
+struct KnownField {
+    number: u32,
+    /// The value the field was probed with, which has its wire type
+    value: Value,
+    /// The schema of the field if it's a nested message
+    nested: Option<Schema>,
+}
+
+impl Schema {
+    fn nested(&self, number: u32) -> Option<&Schema> {
+        self.fields
+            .iter()
+            .find(|f| f.number == number)
+            .and_then(|f| f.nested.as_ref())
+    }
+}
+
+/// Returns the schema of the message type, which is probed once.
+fn schema<T: Message + Default + 'static>() -> &'static Schema {
+    static SCHEMAS: Mutex<Vec<(TypeId, &'static Schema)>> = Mutex::new(Vec::new());
+    let mut schemas = SCHEMAS.lock().unwrap();
+    if let Some((_, schema)) = schemas.iter().find(|(id, _)| *id == TypeId::of::<T>()) {
+        return schema;
+    }
+    let schema = Box::leak(Box::new(probe::<T>(&[])));
+    schemas.push((TypeId::of::<T>(), schema));
+    schema
+}
+
+/// Probes the fields of the message at the path of field numbers of
+/// nested messages.
+fn probe<T: Message + Default>(path: &[u32]) -> Schema {
+    // Whether the field is kept, or `None` if the message can't be
+    // decoded with it
+    let kept = |field: &Field| {
+        let message = T::decode(&*encode(&[nest(path, field.clone())])).ok()?;
+        let mut fields = parse_message(&message.encode_to_vec()).unwrap_or_default();
+        for &number in path {
+            fields = match fields.iter().find(|f| f.number == number) {
+                Some(Field {
+                    value: Value::LengthDelimited(bytes),
+                    ..
+                }) => parse_message(bytes).unwrap_or_default(),
+                _ => return Some(false),
+            };
+        }
+        Some(fields.iter().any(|f| f.number == field.number))
+    };
+    // A nested message skips the fields it doesn't know, a string fails
+    // to decode them and bytes keep them
+    let unknown = Field {
+        number: MAX_FIELD_NUMBER,
+        value: Value::Varint(1),
+    };
+    if !path.is_empty() && kept(&unknown) != Some(false) {
+        return Schema::default();
+    }
+
+    let mut schema = Schema::default();
+    for number in 1..=MAX_PROBED_FIELD_NUMBER {
+        let values = [
+            Value::Varint(1),
+            Value::Fixed64(1),
+            Value::Fixed32(1),
+            Value::LengthDelimited(Vec::new()),
+            Value::LengthDelimited(b"a".to_vec()),
+        ];
+        let Some(value) = values.into_iter().find(|value| {
+            kept(&Field {
+                number,
+                value: value.clone(),
+            }) == Some(true)
+        }) else {
+            continue;
+        };
+        let nested = match value {
+            Value::LengthDelimited(_) if path.len() < MAX_PROBED_NESTING => {
+                Some(probe::<T>(&[path, &[number]].concat())).filter(|s| !s.fields.is_empty())
+            }
+            _ => None,
+        };
+        schema.fields.push(KnownField {
+            number,
+            value,
+            nested,
+        });
+    }
+    schema
+}
+
+/// Wraps the field into the nested messages of the path.
+fn nest(path: &[u32], field: Field) -> Field {
+    path.iter().rev().fold(field, |field, &number| Field {
+        number,
+        value: Value::LengthDelimited(encode(&[field])),
+    })
+}
+
+/// A field of a message in the wire format.
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    number: u32,
+    value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Varint(u64),
+    Fixed64(u64),
+    Fixed32(u32),
+    LengthDelimited(Vec<u8>),
+}
+
+/// Parses a message in the wire format. Groups, which are deprecated,
+/// aren't supported.
+fn parse_message(mut data: &[u8]) -> Option<Vec<Field>> {
+    let mut fields = Vec::new();
+    while !data.is_empty() {
+        let key = parse_varint(&mut data)?;
+        let number = u32::try_from(key >> 3).ok().filter(|&n| n > 0)?;
+        let value = match key & 7 {
+            0 => Value::Varint(parse_varint(&mut data)?),
+            1 => {
+                let (bytes, rest) = data.split_first_chunk::<8>()?;
+                data = rest;
+                Value::Fixed64(u64::from_le_bytes(*bytes))
+            }
+            2 => {
+                let len = usize::try_from(parse_varint(&mut data)?).ok()?;
+                let bytes = data.get(..len)?;
+                data = &data[len..];
+                Value::LengthDelimited(bytes.to_vec())
+            }
+            5 => {
+                let (bytes, rest) = data.split_first_chunk::<4>()?;
+                data = rest;
+                Value::Fixed32(u32::from_le_bytes(*bytes))
+            }
+            _ => return None,
+        };
+        fields.push(Field { number, value });
+    }
+    Some(fields)
+}
+
+fn parse_varint(data: &mut &[u8]) -> Option<u64> {
+    let mut value = 0;
+    for (i, &byte) in data.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            *data = &data[i + 1..];
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn encode(fields: &[Field]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for field in fields {
+        let wire_type = match field.value {
+            Value::Varint(_) => 0,
+            Value::Fixed64(_) => 1,
+            Value::LengthDelimited(_) => 2,
+            Value::Fixed32(_) => 5,
+        };
+        encode_varint(u64::from(field.number) << 3 | wire_type, &mut out);
+        match &field.value {
+            Value::Varint(value) => encode_varint(*value, &mut out),
+            Value::Fixed64(value) => out.extend_from_slice(&value.to_le_bytes()),
+            Value::Fixed32(value) => out.extend_from_slice(&value.to_le_bytes()),
+            Value::LengthDelimited(bytes) => {
+                encode_varint(bytes.len() as u64, &mut out);
+                out.extend_from_slice(bytes);
+            }
+        }
+    }
+    out
+}
+
+fn encode_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Returns the fields of a length-delimited value which is a nested
+/// message. Values which are printable strings are rather taken to be
+/// strings, and values which wouldn't be encoded the same way again to
+/// be bytes.
+fn nested_message(bytes: &[u8]) -> Option<Vec<Field>> {
+    if bytes.is_empty() || is_printable(bytes) {
+        return None;
+    }
+    parse_message(bytes).filter(|fields| encode(fields) == bytes)
+}
+
+fn is_printable(bytes: &[u8]) -> bool {
+    std::str::from_utf8(bytes).is_ok_and(|s| {
+        s.chars()
+            .all(|c| !c.is_control() || matches!(c, '\n' | '\t' | '\r'))
+    })
+}
+
+fn format_text(fields: &[Field]) -> String {
+    let mut text = String::new();
+    format_fields(fields, 0, &mut text);
+    text
+}
+
+fn format_fields(fields: &[Field], nesting: usize, text: &mut String) {
+    let indent = "  ".repeat(nesting);
+    for field in fields {
+        let _ = match &field.value {
+            Value::Varint(value) => writeln!(text, "{indent}{}: {value}", field.number),
+            Value::Fixed64(value) => writeln!(text, "{indent}{}: {value:#018x}", field.number),
+            Value::Fixed32(value) => writeln!(text, "{indent}{}: {value:#010x}", field.number),
+            Value::LengthDelimited(bytes) => match nested_message(bytes) {
+                Some(nested) if nesting < MAX_NESTING => {
+                    let _ = writeln!(text, "{indent}{} {{", field.number);
+                    format_fields(&nested, nesting + 1, text);
+                    writeln!(text, "{indent}}}")
+                }
+                _ => writeln!(text, "{indent}{}: \"{}\"", field.number, escape(bytes)),
+            },
+        };
+    }
+}
+
+fn escape(bytes: &[u8]) -> String {
+    let mut escaped = String::new();
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '"' => escaped.push_str("\\\""),
+                '\\' => escaped.push_str("\\\\"),
+                '\n' => escaped.push_str("\\n"),
+                '\t' => escaped.push_str("\\t"),
+                '\r' => escaped.push_str("\\r"),
+                c if c.is_control() => {
+                    for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+                        let _ = write!(escaped, "\\x{byte:02x}");
+                    }
+                }
+                c => escaped.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            let _ = write!(escaped, "\\x{byte:02x}");
+        }
+    }
+    escaped
+}
+
+/// Parses the text format of a message, see the [module
+/// documentation](self).
+fn parse_text(text: &[u8]) -> Option<Vec<Field>> {
+    let mut parser = TextParser { text, pos: 0 };
+    let fields = parser.fields(0)?;
+    parser.skip_whitespace();
+    (parser.pos == text.len()).then_some(fields)
+}
+
+struct TextParser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl TextParser<'_> {
+    fn fields(&mut self, nesting: usize) -> Option<Vec<Field>> {
+        let mut fields = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b'0'..=b'9') => {}
+                _ => return Some(fields),
+            }
+            let number = u32::try_from(self.number()?).ok().filter(|&n| n > 0)?;
+            self.skip_whitespace();
+            let value = match self.next()? {
+                b'{' if nesting < MAX_NESTING => {
+                    let nested = self.fields(nesting + 1)?;
+                    self.skip_whitespace();
+                    (self.next()? == b'}').then_some(())?;
+                    Value::LengthDelimited(encode(&nested))
+                }
+                b':' => {
+                    self.skip_whitespace();
+                    self.value()?
+                }
+                _ => return None,
+            };
+            fields.push(Field { number, value });
+        }
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        if self.peek() == Some(b'"') {
+            self.pos += 1;
+            return Some(Value::LengthDelimited(self.string()?));
+        }
+        if self.text[self.pos..].starts_with(b"0x") {
+            self.pos += 2;
+            let start = self.pos;
+            while self.peek().is_some_and(|b| b.is_ascii_hexdigit()) {
+                self.pos += 1;
+            }
+            let digits = std::str::from_utf8(&self.text[start..self.pos]).ok()?;
+            return match digits.len() {
+                8 => Some(Value::Fixed32(u32::from_str_radix(digits, 16).ok()?)),
+                16 => Some(Value::Fixed64(u64::from_str_radix(digits, 16).ok()?)),
+                _ => None,
+            };
+        }
+        Some(Value::Varint(self.number()?))
+    }
+
+    fn number(&mut self) -> Option<u64> {
+        let start = self.pos;
+        while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.text[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    fn string(&mut self) -> Option<Vec<u8>> {
+        let mut bytes = Vec::new();
+        loop {
+            match self.next()? {
+                b'"' => return Some(bytes),
+                b'\\' => match self.next()? {
+                    b'n' => bytes.push(b'\n'),
+                    b't' => bytes.push(b'\t'),
+                    b'r' => bytes.push(b'\r'),
+                    b'x' => {
+                        let digits = self.text.get(self.pos..self.pos + 2)?;
+                        self.pos += 2;
+                        let digits = std::str::from_utf8(digits).ok()?;
+                        bytes.push(u8::from_str_radix(digits, 16).ok()?);
+                    }
+                    byte @ (b'"' | b'\\') => bytes.push(byte),
+                    _ => return None,
+                },
+                byte => bytes.push(byte),
+            }
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(byte) = self.peek() {
+            match byte {
+                b'#' => {
+                    while self.peek().is_some_and(|b| b != b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                b if b.is_ascii_whitespace() => self.pos += 1,
+                _ => return,
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.pos += 1;
+        Some(byte)
+    }
+}
+
+/// Applies a random mutation to the fields or the fields of one of the
+/// nested messages.
+fn mutate_fields(fields: &mut Vec<Field>, schema: Option<&Schema>, rng: &mut Rng, nesting: usize) {
+    let known = schema.map_or(&[][..], |s| &s.fields);
+    if fields.is_empty() || rng.in_range(0, 4) == 0 {
+        if known.is_empty() {
+            return;
+        }
+        let field = &known[rng.in_range(0, known.len() - 1)];
+        let value = random_value(field, rng);
+        let at = rng.in_range(0, fields.len());
+        fields.insert(
+            at,
+            Field {
+                number: field.number,
+                value,
+            },
+        );
+        return;
+    }
+    let i = rng.in_range(0, fields.len() - 1);
+    match rng.in_range(0, 9) {
+        0 => {
+            fields.remove(i);
+        }
+        1 => {
+            let field = fields[i].clone();
+            fields.insert(rng.in_range(0, fields.len()), field);
+        }
+        2 => {
+            let j = rng.in_range(0, fields.len() - 1);
+            fields.swap(i, j);
+        }
+        _ => {
+            let nested = schema.and_then(|s| s.nested(fields[i].number));
+            mutate_value(&mut fields[i].value, nested, rng, nesting);
+        }
+    }
+}
+
+/// Returns a random value of the known field.
+fn random_value(field: &KnownField, rng: &mut Rng) -> Value {
+    match &field.value {
+        Value::Varint(_) => Value::Varint(interesting_value(rng)),
+        Value::Fixed64(_) => Value::Fixed64(interesting_value(rng)),
+        Value::Fixed32(_) => Value::Fixed32(interesting_value(rng) as u32),
+        Value::LengthDelimited(_) => {
+            let mut bytes = Vec::new();
+            match &field.nested {
+                Some(nested) => {
+                    let mut fields = Vec::new();
+                    mutate_fields(&mut fields, Some(nested), rng, MAX_NESTING);
+                    bytes = encode(&fields);
+                }
+                None => mutate_bytes(&mut bytes, rng),
+            }
+            Value::LengthDelimited(bytes)
+        }
+    }
+}
+
+fn mutate_value(value: &mut Value, schema: Option<&Schema>, rng: &mut Rng, nesting: usize) {
+    match value {
+        Value::Varint(value) | Value::Fixed64(value) => *value = mutate_number(*value, 64, rng),
+        Value::Fixed32(value) => *value = mutate_number(u64::from(*value), 32, rng) as u32,
+        Value::LengthDelimited(bytes) => {
+            let nested = match schema {
+                Some(_) => parse_message(bytes),
+                None => nested_message(bytes),
+            };
+            match nested {
+                Some(mut nested) if nesting < MAX_NESTING && rng.in_range(0, 3) != 0 => {
+                    mutate_fields(&mut nested, schema, rng, nesting + 1);
+                    *bytes = encode(&nested);
+                }
+                _ => mutate_bytes(bytes, rng),
+            }
+        }
+    }
+}
+
+fn mutate_number(value: u64, bits: u32, rng: &mut Rng) -> u64 {
+    match rng.in_range(0, 4) {
+        0 => interesting_value(rng),
+        1 => value ^ 1 << rng.in_range(0, bits as usize - 1),
+        2 => value.wrapping_add(rng.in_range(1, 16) as u64),
+        3 => value.wrapping_sub(rng.in_range(1, 16) as u64),
+        // libFuzzer's mutations insert the values of the comparisons of
+        // the code under test
+        _ => {
+            let mut bytes = value.to_le_bytes().to_vec();
+            mutator::mutate_bytes(&mut bytes, 8);
+            bytes.resize(8, 0);
+            u64::from_le_bytes(bytes.try_into().unwrap())
+        }
+    }
+}
+
+fn interesting_value(rng: &mut Rng) -> u64 {
+    INTERESTING_VALUES[rng.in_range(0, INTERESTING_VALUES.len() - 1)]
+}
+
+/// Mutates the bytes of a string or bytes field. Strings mostly stay
+/// printable, because string fields must be valid UTF-8.
+fn mutate_bytes(bytes: &mut Vec<u8>, rng: &mut Rng) {
+    let byte = |rng: &mut Rng| {
+        if rng.in_range(0, 3) == 0 {
+            rng.in_range(0, 0xff) as u8
+        } else {
+            rng.in_range(0x20, 0x7e) as u8
+        }
+    };
+    match rng.in_range(0, 4) {
+        0 if !bytes.is_empty() => {
+            bytes.remove(rng.in_range(0, bytes.len() - 1));
+        }
+        1 if !bytes.is_empty() => {
+            let i = rng.in_range(0, bytes.len() - 1);
+            bytes[i] = byte(rng);
+        }
+        2 if !bytes.is_empty() => {
+            let start = rng.in_range(0, bytes.len() - 1);
+            let end = rng.in_range(start + 1, bytes.len());
+            let chunk = bytes[start..end].to_vec();
+            let at = rng.in_range(0, bytes.len());
+            bytes.splice(at..at, chunk);
+        }
+        3 => {
+            let max_size = bytes.len() * 2 + 8;
+            mutator::mutate_bytes(bytes, max_size);
+        }
+        _ => {
+            let at = rng.in_range(0, bytes.len());
+            bytes.insert(at, byte(rng));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, PartialEq, Message)]
+    struct Request {
+        #[prost(string, tag = "1")]
+        path: String,
+        #[prost(uint32, tag = "2")]
+        size: u32,
+        #[prost(message, optional, tag = "3")]
+        range: Option<Range>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct Range {
+        #[prost(fixed64, tag = "1")]
+        start: u64,
+        #[prost(fixed32, tag = "2")]
+        len: u32,
+    }
+
+    fn request() -> Request {
+        Request {
+            path: "/index.html".to_string(),
+            size: 1024,
+            range: Some(Range { start: 1, len: 2 }),
+        }
+    }
+
+    #[test]
+    fn text_format() {
+        let fields = parse_message(&request().encode_to_vec()).unwrap();
+        let text = format_text(&fields);
+        assert_eq!(
+            text,
+            "1: \"/index.html\"\n\
+             2: 1024\n\
+             3 {\n  \
+               1: 0x0000000000000001\n  \
+               2: 0x00000002\n\
+             }\n"
+        );
+        assert_eq!(parse_text(text.as_bytes()), Some(fields));
+        assert_eq!(
+            parse_text(b"# a comment\n1: \"\\x00\\\"\\xff\" 2: 3"),
+            Some(vec![
+                Field {
+                    number: 1,
+                    value: Value::LengthDelimited(b"\x00\"\xff".to_vec()),
+                },
+                Field {
+                    number: 2,
+                    value: Value::Varint(3),
+                },
+            ])
+        );
+        assert_eq!(parse_text(b"1: 0x123"), None);
+        assert_eq!(parse_text(b"1 { 2: 3"), None);
+        assert_eq!(escape(b"a\"\n\x01\xff"), "a\\\"\\n\\x01\\xff");
+    }
+
+    #[test]
+    fn decoded_inputs() {
+        let input = Input::<Request>::new();
+        let text =
+            b"1: \"/index.html\"\n2: 1024\n3 {\n  1: 0x0000000000000001\n  2: 0x00000002\n}\n";
+        assert_eq!((&input).decode(text), Some(request()));
+        assert_eq!((&input).decode(&request().encode_to_vec()), Some(request()));
+        assert_eq!((&input).decode(b"1: 5"), None);
+    }
+
+    #[test]
+    fn probed_schema() {
+        let schema = schema::<Request>();
+        let fields: Vec<(u32, &Value, bool)> = schema
+            .fields
+            .iter()
+            .map(|f| (f.number, &f.value, f.nested.is_some()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                (1, &Value::LengthDelimited(b"a".to_vec()), false),
+                (2, &Value::Varint(1), false),
+                (3, &Value::LengthDelimited(Vec::new()), true),
+            ]
+        );
+        let nested: Vec<(u32, &Value)> = schema
+            .nested(3)
+            .unwrap()
+            .fields
+            .iter()
+            .map(|f| (f.number, &f.value))
+            .collect();
+        assert_eq!(nested, [(1, &Value::Fixed64(1)), (2, &Value::Fixed32(1))]);
+    }
+
+    #[test]
+    fn mutated_inputs_decode() {
+        let mut data = request().encode_to_vec();
+        let mut changed = 0;
+        for seed in 0..200 {
+            let before = data.clone();
+            ProtobufMutator::<Request>::mutate(&mut data, 256, seed);
+            assert!(data.len() <= 256);
+            let decoded = (&Input::<Request>::new()).decode(&data);
+            assert!(decoded.is_some(), "{}", String::from_utf8_lossy(&data));
+            changed += usize::from(data != before);
+        }
+        assert!(changed > 150, "{changed}");
+
+        let mut out = Vec::new();
+        ProtobufMutator::<Request>::crossover(b"2: 1", b"1: \"a\"", &mut out, 256, 0);
+        assert!((&Input::<Request>::new()).decode(&out).is_some());
+    }
+}
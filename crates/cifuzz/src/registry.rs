@@ -0,0 +1,73 @@
+//! The registry of the fuzz tests of a test executable, from which
+//! cargo-cifuzz discovers them.
+//!
+//! libtest lists the harnesses of the fuzz tests among the other tests,
+//! which can't be told apart from a unit test named `fuzz`. To list the
+//! fuzz tests, cargo-cifuzz runs the harnesses with the
+//! `CIFUZZ_REGISTRY_FILE` environment variable set, which makes them
+//! append a line with the information about the fuzz test to that file
+//! instead of fuzzing:
+//!
+//! ```text
+//! <module path>\t<line>\t<source file>
+//! ```
+
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use crate::harness::FuzzTest;
+
+/// The environment variable with the file the harnesses register the
+/// fuzz tests in.
+// Must be kept in sync with crates/cargo-cifuzz/src/build.rs
+const REGISTRY_FILE_ENV: &str = "CIFUZZ_REGISTRY_FILE";
+
+/// Registers the fuzz test if the registry file is set, in which case
+/// the harness returns without running the fuzz test.
+pub fn register(test: &FuzzTest) -> bool {
+    let Some(path) = std::env::var_os(REGISTRY_FILE_ENV) else {
+        return false;
+    };
+    let entry = entry(test);
+    // A single write, as the harnesses may run in parallel
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(entry.as_bytes()));
+    if let Err(err) = result {
+        panic!(
+            "failed to register {} in {}: {err}",
+            test.name,
+            path.to_string_lossy()
+        );
+    }
+    true
+}
+
+fn entry(test: &FuzzTest) -> String {
+    format!("{}\t{}\t{}\n", test.module_path, test.line, test.file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_entry() {
+        let test = FuzzTest {
+            name: "my_fuzz_test",
+            module_path: "foo::parser::my_fuzz_test",
+            file: "src/parser.rs",
+            line: 12,
+            manifest_dir: "/foo",
+            embedded_seed_corpus: &[],
+            dictionary: &[],
+            mutator: None,
+        };
+        assert_eq!(
+            entry(&test),
+            "foo::parser::my_fuzz_test\t12\tsrc/parser.rs\n"
+        );
+    }
+}
@@ -0,0 +1,474 @@
+//! Running a fuzz test as a regression test: Without `--cfg fuzzing`,
+//! the fuzz test is executed with all inputs from its seed corpus, i.e.
+//! the `<fuzz_test>_inputs` directory next to its source file and the
+//! `corpus/<fuzz_test>` directory of its package, and with the crashing
+//! inputs of its findings. A seed corpus embedded into the executable
+//! by the build script replaces the seed corpus directories.
+//!
+//! If the `CIFUZZ_JUNIT_DIR` environment variable is set, the results
+//! of the inputs are also written to `<fuzz_test>.xml` in that directory
+//! as a JUnit XML report, with a test case for every input.
+//!
+//! If the `CIFUZZ_REPLAY_INPUTS` environment variable is set, only the
+//! inputs listed in that file are executed, which is how `cargo cifuzz
+//! run --engine miri` replays the corpus under Miri.
+
+use std::any::Any;
+use std::fmt::Write;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::harness::{FuzzTest, TestOneInput};
+
+/// The file which marks the root directory of a cifuzz project.
+const PROJECT_CONFIG_FILE: &str = "cifuzz.yaml";
+
+/// The environment variable with the directory to write the JUnit XML
+/// reports to.
+const JUNIT_DIR_ENV: &str = "CIFUZZ_JUNIT_DIR";
+
+/// The environment variable with the file listing the inputs to replay,
+/// one path per line.
+// Must be kept in sync with crates/cargo-cifuzz/src/runner/miri.rs
+const REPLAY_INPUTS_ENV: &str = "CIFUZZ_REPLAY_INPUTS";
+
+/// An input the fuzz test is executed with.
+struct Input {
+    /// The name which is printed when the input is executed
+    name: String,
+    data: Vec<u8>,
+}
+
+/// Executes the fuzz test with the empty input and all inputs of its
+/// seed corpus and findings. Panics if the fuzz test panics for any of
+/// them, after all inputs were executed.
+pub fn regression(test: &FuzzTest, test_one_input: TestOneInput) {
+    if let Some(list) = std::env::var_os(REPLAY_INPUTS_ENV) {
+        replay(Path::new(&list), test_one_input);
+        return;
+    }
+    let mut inputs = vec![Input {
+        name: "empty input".to_string(),
+        data: Vec::new(),
+    }];
+    inputs.extend(collect_inputs(test));
+
+    let mut failures = Vec::new();
+    let mut results = Vec::new();
+    for input in &inputs {
+        println!("Running {}", input.name);
+        let start = Instant::now();
+        // The panic hook prints the panic message of failing inputs
+        let result = panic::catch_unwind(AssertUnwindSafe(|| test_one_input(&input.data)));
+        let failure = result.err().map(|payload| panic_message(&*payload));
+        if failure.is_some() {
+            failures.push(input.name.as_str());
+        }
+        results.push(InputResult {
+            name: &input.name,
+            time: start.elapsed(),
+            failure,
+        });
+    }
+
+    if let Some(dir) = std::env::var_os(JUNIT_DIR_ENV) {
+        let path = Path::new(&dir).join(format!("{}.xml", test.name));
+        if let Err(err) = fs::create_dir_all(&dir)
+            .and_then(|()| fs::write(&path, junit_report(test.name, &results)))
+        {
+            eprintln!("Failed to write {}: {err}", path.display());
+        }
+    }
+
+    if !failures.is_empty() {
+        panic!(
+            "The fuzz test {} failed for {} of {} inputs:\n  {}",
+            test.name,
+            failures.len(),
+            inputs.len(),
+            failures.join("\n  ")
+        );
+    }
+}
+
+/// Executes the fuzz test with the inputs listed in the file. Every
+/// input is announced on stderr, where Miri reports undefined behavior
+/// before it aborts, so that cargo-cifuzz knows the input Miri aborted
+/// on. Panics don't fail the replay, they're findings of the fuzzing.
+fn replay(list: &Path, test_one_input: TestOneInput) {
+    let list = match fs::read_to_string(list) {
+        Ok(list) => list,
+        Err(err) => panic!("Failed to read {}: {err}", list.display()),
+    };
+    for path in list.lines().filter(|path| !path.is_empty()) {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(err) => {
+                eprintln!("Failed to read {path}: {err}");
+                continue;
+            }
+        };
+        eprintln!("Running {path}");
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| test_one_input(&data)));
+    }
+}
+
+/// The result of executing the fuzz test with an input.
+struct InputResult<'a> {
+    name: &'a str,
+    time: Duration,
+    /// The panic message, if the fuzz test panicked
+    failure: Option<String>,
+}
+
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "the fuzz test panicked".to_string()
+    }
+}
+
+/// Formats the results as a JUnit XML report with a test suite for the
+/// fuzz test.
+fn junit_report(fuzz_test: &str, results: &[InputResult]) -> String {
+    let time: Duration = results.iter().map(|result| result.time).sum();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+    let _ = writeln!(
+        xml,
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" time=\"{:.3}\">",
+        escape_xml(fuzz_test),
+        results.len(),
+        results
+            .iter()
+            .filter(|result| result.failure.is_some())
+            .count(),
+        time.as_secs_f64()
+    );
+    for result in results {
+        let _ = write!(
+            xml,
+            "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+            escape_xml(result.name),
+            escape_xml(fuzz_test),
+            result.time.as_secs_f64()
+        );
+        match &result.failure {
+            None => xml.push_str("/>\n"),
+            Some(message) => {
+                let _ = writeln!(
+                    xml,
+                    ">\n      <failure type=\"panic\" message=\"{}\"/>\n    </testcase>",
+                    escape_xml(message)
+                );
+            }
+        }
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+/// Escapes text for XML attributes, replacing the control characters
+/// which XML 1.0 doesn't allow.
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\t' => escaped.push_str("&#9;"),
+            c if c.is_control() => escaped.push('\u{fffd}'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn collect_inputs(test: &FuzzTest) -> Vec<Input> {
+    let manifest_dir = Path::new(test.manifest_dir);
+    let mut inputs: Vec<Input> = test
+        .embedded_seed_corpus
+        .iter()
+        .map(|(name, data)| Input {
+            name: name.to_string(),
+            data: data.to_vec(),
+        })
+        .collect();
+    let mut paths = Vec::new();
+    if inputs.is_empty() {
+        for dir in seed_corpus_dirs(test) {
+            list_files(&dir, &mut paths);
+        }
+    }
+    if let Some(dir) = findings_dir(test) {
+        for finding in read_dir_sorted(&dir) {
+            let crashing_input = finding.join("crashing-input");
+            if crashing_input.is_file() && !is_ignored(&finding) {
+                paths.push(crashing_input);
+            }
+        }
+    }
+
+    inputs.extend(paths.into_iter().filter_map(|path| {
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(err) => {
+                eprintln!("Failed to read {}: {err}", path.display());
+                return None;
+            }
+        };
+        let name = path
+            .strip_prefix(manifest_dir)
+            .unwrap_or(&path)
+            .display()
+            .to_string();
+        Some(Input { name, data })
+    }));
+    inputs
+}
+
+/// Returns the seed corpus directories of the fuzz test which exist.
+fn seed_corpus_dirs(test: &FuzzTest) -> Vec<PathBuf> {
+    let manifest_dir = Path::new(test.manifest_dir);
+    // file!() is relative to the directory in which cargo runs rustc,
+    // which is the root of the workspace containing the package
+    let inputs_dir = manifest_dir
+        .ancestors()
+        .map(|dir| dir.join(test.file))
+        .find(|path| path.is_file())
+        .and_then(|source_file| Some(source_file.parent()?.join(format!("{}_inputs", test.name))));
+    let corpus_dir = manifest_dir.join("corpus").join(test.name);
+    inputs_dir
+        .into_iter()
+        .chain([corpus_dir])
+        .filter(|dir| dir.is_dir())
+        .collect()
+}
+
+/// Returns the directory containing the findings of the fuzz test in
+/// the cifuzz project containing the package, if it exists.
+fn findings_dir(test: &FuzzTest) -> Option<PathBuf> {
+    let project_dir = Path::new(test.manifest_dir)
+        .ancestors()
+        .find(|dir| dir.join(PROJECT_CONFIG_FILE).is_file())?;
+    let dir = project_dir.join(".cifuzz").join("findings").join(test.name);
+    dir.is_dir().then_some(dir)
+}
+
+/// Whether the finding was marked as ignored with `cargo cifuzz findings
+/// mark`. The runtime doesn't parse JSON, but the status is written by
+/// serde_json in a fixed format.
+fn is_ignored(finding: &Path) -> bool {
+    fs::read_to_string(finding.join("finding.json"))
+        .is_ok_and(|json| json.contains("\"status\": \"ignored\""))
+}
+
+/// Adds all files in the directory and its subdirectories to `files`.
+fn list_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for path in read_dir_sorted(dir) {
+        if path.is_dir() {
+            list_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+fn read_dir_sorted(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(err) => {
+            eprintln!("Failed to read {}: {err}", dir.display());
+            Vec::new()
+        }
+    };
+    paths.sort();
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fuzz_test(manifest_dir: &Path) -> FuzzTest {
+        FuzzTest {
+            name: "my_fuzz_test",
+            module_path: "foo::my_fuzz_test",
+            file: "src/my_fuzz_test.rs",
+            line: 1,
+            manifest_dir: manifest_dir.to_str().unwrap().to_string().leak(),
+            embedded_seed_corpus: &[],
+            dictionary: &[],
+            mutator: None,
+        }
+    }
+
+    fn write(path: &Path, content: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn collects_seed_corpus_and_findings() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path();
+        let package = project.join("crates").join("foo");
+        write(&project.join("cifuzz.yaml"), b"");
+        write(&package.join("src").join("my_fuzz_test.rs"), b"");
+        let inputs = package.join("src").join("my_fuzz_test_inputs");
+        write(&inputs.join("b"), b"b");
+        write(&inputs.join("a").join("nested"), b"a");
+        let findings = project
+            .join(".cifuzz")
+            .join("findings")
+            .join("my_fuzz_test");
+        write(&findings.join("1234").join("crashing-input"), b"crash");
+        write(&findings.join("1234").join("finding.json"), b"{}");
+        write(&findings.join("5678").join("crashing-input"), b"ignored");
+        write(
+            &findings.join("5678").join("finding.json"),
+            b"{\n  \"status\": \"ignored\"\n}",
+        );
+
+        // file!() is relative to the workspace root in this case
+        let mut test = fuzz_test(&package);
+        test.file = "crates/foo/src/my_fuzz_test.rs";
+        let inputs: Vec<_> = collect_inputs(&test)
+            .into_iter()
+            .map(|input| (input.name, input.data))
+            .collect();
+        let finding = findings.join("1234").join("crashing-input");
+        assert_eq!(
+            inputs,
+            [
+                (
+                    ["src", "my_fuzz_test_inputs", "a", "nested"]
+                        .iter()
+                        .collect::<PathBuf>()
+                        .display()
+                        .to_string(),
+                    b"a".to_vec()
+                ),
+                (
+                    ["src", "my_fuzz_test_inputs", "b"]
+                        .iter()
+                        .collect::<PathBuf>()
+                        .display()
+                        .to_string(),
+                    b"b".to_vec()
+                ),
+                (finding.display().to_string(), b"crash".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn runs_without_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let test = fuzz_test(dir.path());
+        assert!(collect_inputs(&test).is_empty());
+        regression(&test, |data| assert!(data.is_empty()));
+    }
+
+    #[test]
+    #[should_panic(expected = "The fuzz test my_fuzz_test failed for 1 of 3 inputs:\n  src")]
+    fn reports_failing_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let inputs = dir.path().join("src").join("my_fuzz_test_inputs");
+        write(&dir.path().join("src").join("my_fuzz_test.rs"), b"");
+        write(&inputs.join("ok"), b"ok");
+        write(&inputs.join("crash"), b"crash");
+        regression(&fuzz_test(dir.path()), |data| assert_ne!(data, b"crash"));
+    }
+
+    #[test]
+    fn replays_listed_inputs() {
+        static REPLAYED: std::sync::Mutex<Vec<Vec<u8>>> = std::sync::Mutex::new(Vec::new());
+        let dir = tempfile::tempdir().unwrap();
+        write(&dir.path().join("a"), b"a");
+        write(&dir.path().join("crash"), b"crash");
+        let list = dir.path().join("inputs.txt");
+        let paths =
+            ["a", "missing", "crash"].map(|name| dir.path().join(name).display().to_string());
+        write(&list, paths.join("\n").as_bytes());
+        replay(&list, |data| {
+            REPLAYED.lock().unwrap().push(data.to_vec());
+            assert_ne!(data, b"crash");
+        });
+        assert_eq!(
+            *REPLAYED.lock().unwrap(),
+            [b"a".to_vec(), b"crash".to_vec()]
+        );
+    }
+
+    #[test]
+    fn junit_reports() {
+        let results = [
+            InputResult {
+                name: "empty input",
+                time: Duration::from_millis(1),
+                failure: None,
+            },
+            InputResult {
+                name: "src/my_fuzz_test_inputs/crash",
+                time: Duration::from_millis(2),
+                failure: Some("assertion `left != right` failed\n  left: \"crash\"".to_string()),
+            },
+        ];
+        assert_eq!(
+            junit_report("my_fuzz_test", &results),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <testsuites>\n  \
+               <testsuite name=\"my_fuzz_test\" tests=\"2\" failures=\"1\" errors=\"0\" time=\"0.003\">\n    \
+                 <testcase name=\"empty input\" classname=\"my_fuzz_test\" time=\"0.001\"/>\n    \
+                 <testcase name=\"src/my_fuzz_test_inputs/crash\" classname=\"my_fuzz_test\" time=\"0.002\">\n      \
+                   <failure type=\"panic\" message=\"assertion `left != right` failed&#10;  left: &quot;crash&quot;\"/>\n    \
+                 </testcase>\n  \
+               </testsuite>\n\
+             </testsuites>\n"
+        );
+    }
+
+    #[test]
+    fn collects_corpus_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            &dir.path().join("corpus").join("my_fuzz_test").join("seed"),
+            b"seed",
+        );
+        let inputs: Vec<_> = collect_inputs(&fuzz_test(dir.path()))
+            .into_iter()
+            .map(|input| input.data)
+            .collect();
+        assert_eq!(inputs, [b"seed".to_vec()]);
+    }
+
+    #[test]
+    fn embedded_seed_corpus_replaces_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            &dir.path().join("corpus").join("my_fuzz_test").join("seed"),
+            b"seed",
+        );
+        let mut test = fuzz_test(dir.path());
+        test.embedded_seed_corpus = &[("corpus/my_fuzz_test/embedded", b"embedded")];
+        let inputs: Vec<_> = collect_inputs(&test)
+            .into_iter()
+            .map(|input| (input.name, input.data))
+            .collect();
+        assert_eq!(
+            inputs,
+            [(
+                "corpus/my_fuzz_test/embedded".to_string(),
+                b"embedded".to_vec()
+            )]
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! A deterministic random number generator for fuzz tests, see
+//! [`rng`].
+
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::sync::OnceLock;
+
+/// The environment variable via which `cargo cifuzz run --seed` passes
+/// the seed of the generator.
+// Must be kept in sync with crates/cargo-cifuzz/src/runner/libfuzzer.rs
+const SEED_ENV: &str = "CIFUZZ_SEED";
+
+thread_local! {
+    /// The input executed on the thread, from which the state of the
+    /// generator is derived
+    static INPUT: Cell<(*const u8, usize)> = const { Cell::new((std::ptr::null(), 0)) };
+    /// The state of the generator, `None` until it's used for the input
+    static STATE: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Returns the random number generator of the fuzz test, for code under
+/// test which needs randomness, e.g. a randomized data structure.
+///
+/// Random numbers from sources like `rand::thread_rng()` make the
+/// coverage of an input differ between executions, and findings which
+/// depend on them don't reproduce. The numbers of this generator are
+/// derived from the input and the seed of the run, which is 0 unless
+/// it's passed to `cargo cifuzz run --seed` or via `$CIFUZZ_SEED`, so
+/// executing an input again returns the same numbers:
+///
+/// ```
+/// use cifuzz::fuzz_test;
+///
+/// #[fuzz_test]
+/// fn skip_list_fuzz_test(data: &[u8]) {
+///     let mut rng = cifuzz::rng();
+///     for &byte in data {
+///         let level = rng.next_u32().trailing_ones();
+///         // insert byte at the level
+///         # let _ = (byte, level);
+///     }
+/// }
+/// ```
+///
+/// The generator continues for the whole execution of an input and
+/// starts over with the next one. Its state is kept per thread, threads
+/// spawned by the fuzz test start from the seed alone.
+pub fn rng() -> Rng {
+    Rng {
+        _thread: PhantomData,
+    }
+}
+
+/// The random number generator returned by [`rng`], which generates
+/// the numbers with SplitMix64.
+#[derive(Debug)]
+pub struct Rng {
+    // The state is thread-local
+    _thread: PhantomData<*const ()>,
+}
+
+impl Rng {
+    pub fn next_u64(&mut self) -> u64 {
+        let state = STATE
+            .get()
+            .unwrap_or_else(initial_state)
+            .wrapping_add(0x9e37_79b9_7f4a_7c15);
+        STATE.set(Some(state));
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    pub fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+fn seed() -> u64 {
+    static SEED: OnceLock<u64> = OnceLock::new();
+    *SEED.get_or_init(|| {
+        std::env::var(SEED_ENV)
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or(0)
+    })
+}
+
+/// The FNV-1a hash of the input, starting from the seed. It's only
+/// computed if the fuzz test uses the generator.
+fn initial_state() -> u64 {
+    let (data, len) = INPUT.get();
+    let input = if data.is_null() {
+        &[]
+    } else {
+        // The input stays valid until `input_finished`
+        unsafe { std::slice::from_raw_parts(data, len) }
+    };
+    input
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325 ^ seed(), |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Must be called before the fuzz test executes an input, which must
+/// stay valid until [`input_finished`] is called.
+pub(crate) fn input_started(data: &[u8]) {
+    INPUT.set((data.as_ptr(), data.len()));
+    STATE.set(None);
+}
+
+/// Must be called after the fuzz test executed an input.
+pub(crate) fn input_finished() {
+    INPUT.set((std::ptr::null(), 0));
+    STATE.set(None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(input: &[u8]) -> Vec<u64> {
+        input_started(input);
+        let mut rng = rng();
+        let numbers = (0..4).map(|_| rng.next_u64()).collect();
+        input_finished();
+        numbers
+    }
+
+    #[test]
+    fn derives_numbers_from_input() {
+        assert_eq!(numbers(b"abc"), numbers(b"abc"));
+        assert_ne!(numbers(b"abc"), numbers(b"abd"));
+        assert_ne!(numbers(b""), numbers(b"\0"));
+        let numbers = numbers(b"abc");
+        assert!(numbers.windows(2).all(|w| w[0] != w[1]));
+    }
+
+    #[test]
+    fn fills_bytes() {
+        input_started(b"abc");
+        let first = rng().next_u64().to_le_bytes();
+        input_started(b"abc");
+        let mut bytes = [0; 11];
+        rng().fill_bytes(&mut bytes);
+        input_finished();
+        assert_eq!(bytes[..8], first);
+        assert_ne!(bytes[8..], [0; 3]);
+    }
+}
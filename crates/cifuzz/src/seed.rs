@@ -0,0 +1,256 @@
+//! Recording inputs for the seed corpus from unit tests.
+//!
+//! [`seed!`](crate::seed!) runs a fuzz test taking a
+//! [`FuzzedDataProvider`], or several arguments decoded from one, with a
+//! provider which returns the given values and records the input
+//! decoding to them. If the
+//! `CIFUZZ_SEED_RECORD_DIR` environment variable is set, which
+//! `cargo cifuzz corpus record` does, the input is written to the
+//! `<fuzz_test>` directory in that directory.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+use crate::fdp::record::Value;
+use crate::fdp::trace;
+use crate::{ConsumeFromFdp, FuzzedDataProvider};
+
+/// The environment variable with the directory to write the recorded
+/// inputs to.
+// Must be kept in sync with crates/cargo-cifuzz/src/cmd/corpus.rs
+const RECORD_DIR_ENV: &str = "CIFUZZ_SEED_RECORD_DIR";
+
+/// Runs a fuzz test taking a [`FuzzedDataProvider`] with the given
+/// values and records an input for its seed corpus.
+///
+/// The values are returned by the methods of the provider in the order
+/// in which the fuzz test consumes them, see [`SeedValue`] for how they
+/// are given. This turns a unit test of the tested function into a
+/// unit test of the fuzz test, whose input `cargo cifuzz corpus record`
+/// stores in the seed corpus, so that the fuzzer starts with the
+/// coverage reached by the unit test:
+///
+/// ```
+/// use cifuzz::{fuzz_test, FuzzedDataProvider};
+///
+/// # fn explore_me(_: i64, _: i64, _: &str) {}
+/// #[fuzz_test]
+/// fn my_fuzz_test(fdp: &mut FuzzedDataProvider) {
+///     let a: i64 = fdp.consume_int();
+///     let b: i64 = fdp.consume_int();
+///     let c = fdp.consume_remaining_as_string();
+///     explore_me(a, b, &c);
+/// }
+///
+/// // In a unit test
+/// cifuzz::seed!(my_fuzz_test, 397652_i64, 3082562284_i64, "FUZZ");
+/// ```
+///
+/// The arguments of a fuzz test taking several arguments are decoded
+/// from the provider one after the other, so the values are given like
+/// for a fuzz test consuming them itself.
+///
+/// # Panics
+///
+/// Panics if the fuzz test consumes a value of another kind than the
+/// next given value, or a value which the method can't return, e.g. an
+/// integer out of its range. Panics if not all values are consumed or
+/// if the fuzz test consumes values in a way which the recorded input
+/// can't reproduce, e.g. with lengths depending on
+/// [`remaining_bytes`](FuzzedDataProvider::remaining_bytes).
+///
+/// [`SeedValue`]: crate::SeedValue
+#[macro_export]
+macro_rules! seed {
+    ($fuzz_test:path $(, $value:expr)* $(,)?) => {
+        $crate::__private::record_seed(
+            ::core::stringify!($fuzz_test),
+            ::std::vec![$($crate::SeedValue::into_value($value)),*],
+            |fdp| $crate::__private::SeedTarget::run_with(&$fuzz_test, fdp),
+        )
+    };
+}
+
+/// The fuzz tests which [`seed!`](crate::seed!) can run with a provider,
+/// selected by the marker type `P`: those taking the provider and those
+/// whose arguments are decoded from it like the harness does.
+#[doc(hidden)]
+pub trait SeedTarget<P> {
+    fn run_with(&self, fdp: &mut FuzzedDataProvider<'_>);
+}
+
+/// The marker of fuzz tests taking a [`FuzzedDataProvider`].
+#[doc(hidden)]
+pub struct TakesProvider;
+
+impl<F: Fn(&mut FuzzedDataProvider<'_>)> SeedTarget<TakesProvider> for F {
+    fn run_with(&self, fdp: &mut FuzzedDataProvider<'_>) {
+        self(fdp)
+    }
+}
+
+macro_rules! impl_seed_target {
+    ($($ty:ident $arg:ident),+) => {
+        impl<F: Fn($($ty),+), $($ty: ConsumeFromFdp),+> SeedTarget<($($ty,)+)> for F {
+            fn run_with(&self, fdp: &mut FuzzedDataProvider<'_>) {
+                $(let $arg: $ty = fdp.consume();)+
+                self($($arg),+)
+            }
+        }
+    };
+}
+
+impl_seed_target!(A a, B b);
+impl_seed_target!(A a, B b, C c);
+impl_seed_target!(A a, B b, C c, D d);
+impl_seed_target!(A a, B b, C c, D d, E e);
+impl_seed_target!(A a, B b, C c, D d, E e, G g);
+impl_seed_target!(A a, B b, C c, D d, E e, G g, H h);
+impl_seed_target!(A a, B b, C c, D d, E e, G g, H h, I i);
+
+#[doc(hidden)]
+pub fn record_seed<'a>(
+    fuzz_test: &str,
+    values: Vec<Value<'a>>,
+    run: impl Fn(&mut FuzzedDataProvider<'_>),
+) {
+    let fuzz_test = fuzz_test.rsplit("::").next().unwrap_or(fuzz_test).trim();
+    let mut fdp = FuzzedDataProvider::recording(values);
+    run(&mut fdp);
+    let recording = fdp.recorded().expect("the provider records");
+    assert_eq!(
+        recording.remaining_values(),
+        0,
+        "the fuzz test {fuzz_test} didn't consume all values of the seed"
+    );
+
+    // The input is only a seed if the fuzz test consumes it like the
+    // values
+    let input = recording.input();
+    trace::start(false);
+    run(&mut FuzzedDataProvider::new(&input));
+    let regions = trace::finish();
+    assert!(
+        regions == recording.regions(),
+        "the fuzz test {fuzz_test} consumes the recorded input differently than the values \
+         of the seed, which is the case if lengths depend on remaining_bytes"
+    );
+
+    if let Some(dir) = std::env::var_os(RECORD_DIR_ENV) {
+        let mut hasher = DefaultHasher::new();
+        input.hash(&mut hasher);
+        let dir = Path::new(&dir).join(fuzz_test);
+        let path = dir.join(format!("{:016x}", hasher.finish()));
+        if let Err(err) = fs::create_dir_all(&dir).and_then(|()| fs::write(&path, &input)) {
+            eprintln!("Failed to write {}: {err}", path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FuzzEnum, FuzzedDataProvider};
+
+    #[derive(Debug, PartialEq, FuzzEnum)]
+    enum Op {
+        Push,
+        Pop,
+    }
+
+    /// Records the values and checks that the recorded input decodes to
+    /// them.
+    fn consume_all(fdp: &mut FuzzedDataProvider) {
+        assert_eq!(fdp.consume_int::<i64>(), -3);
+        assert!(fdp.consume::<bool>());
+        assert_eq!(fdp.consume_string(10), "a\\b");
+        assert_eq!(fdp.consume_int_in_range(10u16, 1000), 999);
+        assert_eq!(fdp.consume_char(), '\u{e001}');
+        assert_eq!(fdp.consume_bytes(2), b"xy");
+        assert_eq!(fdp.consume_ascii_string(3), "abc");
+        assert_eq!(fdp.consume_string_from_charset("0123456789", 4), "42");
+        assert_eq!(fdp.consume_vec::<u8>(4), [7, 8]);
+        assert_eq!(fdp.consume_enum::<Op>(), Op::Pop);
+        assert_eq!(fdp.consume_float_in_range(-1.0, 1.0), 0.5);
+        assert_eq!(fdp.consume_float::<f64>(), f64::MIN);
+        assert_eq!(fdp.consume_remaining_as_string(), "rest");
+    }
+
+    #[test]
+    fn record_values() {
+        seed!(
+            consume_all,
+            -3_i64,
+            true,
+            "a\\b",
+            999_u16,
+            '\u{e001}',
+            b"xy",
+            "abc",
+            "42",
+            2_usize,
+            7_u8,
+            8_u8,
+            1_u32,
+            0.5_f32,
+            f64::MIN,
+            "rest",
+        );
+    }
+
+    fn decode_args(a: i64, b: bool, c: String) {
+        assert_eq!((a, b, c.as_str()), (-3, true, "abc"));
+    }
+
+    #[test]
+    fn record_args() {
+        seed!(decode_args, -3_i64, true, "abc");
+    }
+
+    fn consume_non_finite(fdp: &mut FuzzedDataProvider) {
+        fdp.set_non_finite_floats(true);
+        assert!(fdp.consume_float::<f32>().is_nan());
+        assert_eq!(fdp.consume_float::<f32>(), f32::NEG_INFINITY);
+        assert_eq!(fdp.consume_float::<f32>(), f32::MAX);
+    }
+
+    #[test]
+    fn record_non_finite_floats() {
+        seed!(consume_non_finite, f32::NAN, f32::NEG_INFINITY, f32::MAX);
+    }
+
+    fn consume_int(fdp: &mut FuzzedDataProvider) {
+        fdp.consume_int_in_range(0, 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "consume_int_in_range can't return 11")]
+    fn record_out_of_range() {
+        seed!(consume_int, 11);
+    }
+
+    #[test]
+    #[should_panic(expected = "consume_int_in_range can't consume bytes or a string")]
+    fn record_other_kind() {
+        seed!(consume_int, "a");
+    }
+
+    #[test]
+    #[should_panic(expected = "didn't consume all values")]
+    fn record_unconsumed() {
+        seed!(consume_int, 1, 2);
+    }
+
+    fn consume_depending_on_remaining(fdp: &mut FuzzedDataProvider) {
+        let max = fdp.remaining_bytes();
+        fdp.consume_int_in_range(0, max);
+        fdp.consume_remaining_bytes();
+    }
+
+    #[test]
+    #[should_panic(expected = "consumes the recorded input differently")]
+    fn record_depending_on_remaining() {
+        seed!(consume_depending_on_remaining, 0_usize, &[b'a'; 300]);
+    }
+}
@@ -0,0 +1,110 @@
+//! Tracks the execution time of the inputs and writes the slowest ones
+//! for `cargo cifuzz run --slow-inputs`.
+//!
+//! The slowest inputs are kept in memory while fuzzing. When libFuzzer
+//! exits the process, or the fuzz test panics, they're written to the
+//! directory passed by cargo-cifuzz, named by their SHA-1 like the
+//! inputs of the corpus, together with an index file per process with
+//! lines `<nanoseconds>\t<sha1>`. Parallel jobs and restarts of the fuzz
+//! test write their own index files.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// The environment variables via which cargo-cifuzz passes the
+/// directory and the number of slow inputs to write.
+// Must be kept in sync with crates/cargo-cifuzz/src/runner/libfuzzer.rs
+const SLOW_INPUTS_DIR_ENV: &str = "CIFUZZ_SLOW_INPUTS_DIR";
+const SLOW_INPUTS_COUNT_ENV: &str = "CIFUZZ_SLOW_INPUTS_COUNT";
+
+struct SlowInputs {
+    dir: PathBuf,
+    count: usize,
+    /// The slowest inputs, the slowest first
+    inputs: Mutex<Vec<(Duration, Vec<u8>)>>,
+}
+
+static SLOW_INPUTS: OnceLock<SlowInputs> = OnceLock::new();
+/// The execution time in nanoseconds which an input must exceed to be
+/// one of the slowest, i.e. the one of the fastest of them once there
+/// are enough
+static THRESHOLD: AtomicU64 = AtomicU64::new(0);
+
+/// Starts tracking the slow inputs, if cargo-cifuzz asked for them.
+pub(crate) fn start() {
+    let Some(dir) = std::env::var_os(SLOW_INPUTS_DIR_ENV) else {
+        return;
+    };
+    let count = std::env::var(SLOW_INPUTS_COUNT_ENV)
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(10);
+    let slow_inputs = SlowInputs {
+        dir: PathBuf::from(dir),
+        count,
+        inputs: Mutex::new(Vec::new()),
+    };
+    if SLOW_INPUTS.set(slow_inputs).is_ok() {
+        // libFuzzer exits the process with exit() when it's done
+        unsafe { libc::atexit(write_at_exit) };
+    }
+}
+
+/// Must be called after the fuzz test executed an input, with the time
+/// it took.
+pub(crate) fn input_finished(data: &[u8], elapsed: Duration) {
+    let nanos = elapsed.as_nanos() as u64;
+    if nanos <= THRESHOLD.load(Ordering::Relaxed) {
+        return;
+    }
+    let Some(slow_inputs) = SLOW_INPUTS.get() else {
+        return;
+    };
+    let mut inputs = slow_inputs.inputs.lock().unwrap();
+    match inputs.iter().position(|(_, input)| input == data) {
+        // Inputs of the corpus are executed again, e.g. after a restart
+        Some(i) if inputs[i].0 >= elapsed => return,
+        Some(i) => inputs[i].0 = elapsed,
+        None => inputs.push((elapsed, data.to_vec())),
+    }
+    inputs.sort_by_key(|(elapsed, _)| std::cmp::Reverse(*elapsed));
+    inputs.truncate(slow_inputs.count);
+    if inputs.len() == slow_inputs.count {
+        let fastest = inputs
+            .last()
+            .map_or(0, |(elapsed, _)| elapsed.as_nanos() as u64);
+        THRESHOLD.store(fastest, Ordering::Relaxed);
+    }
+}
+
+extern "C" fn write_at_exit() {
+    write();
+}
+
+/// Writes the slowest inputs so far to the directory.
+pub(crate) fn write() {
+    let Some(slow_inputs) = SLOW_INPUTS.get() else {
+        return;
+    };
+    // The lock may be held by a panicking thread
+    let Ok(inputs) = slow_inputs.inputs.try_lock() else {
+        return;
+    };
+    let mut index = Vec::new();
+    for (elapsed, input) in inputs.iter() {
+        let sha1 = sha1_smol::Sha1::from(input).digest().to_string();
+        let path = slow_inputs.dir.join(&sha1);
+        if let Err(err) = std::fs::write(&path, input) {
+            eprintln!("failed to write {}: {err}", path.display());
+            return;
+        }
+        let _ = writeln!(index, "{}\t{sha1}", elapsed.as_nanos());
+    }
+    let path = slow_inputs.dir.join(format!("{}.tsv", std::process::id()));
+    if let Err(err) = std::fs::write(&path, index) {
+        eprintln!("failed to write {}: {err}", path.display());
+    }
+}
@@ -0,0 +1,263 @@
+//! A watchdog which reports inputs on which the fuzz test hangs or uses
+//! too much memory.
+//!
+//! libFuzzer detects timeouts with a `SIGALRM` timer, but the signal is
+//! delivered to an arbitrary thread of the process and libFuzzer ignores
+//! it on any thread but the one running the fuzz test. In a libtest
+//! executable that's usually not the main thread, so a hang could stall
+//! the fuzzer forever. libFuzzer also checks the RSS only once per
+//! second, which is enough time for an input to allocate so much memory
+//! that the OS kills the process, without any finding.
+//!
+//! The watchdog thread measures how long the fuzz test has been running
+//! on the current input and checks the RSS of the process. When a limit
+//! is exceeded, it interrupts the fuzzing thread with `SIGUSR2`, whose
+//! handler prints the stack trace of the fuzzing thread:
+//!
+//! * On a timeout, the handler raises `SIGALRM` on the fuzzing thread,
+//!   so that libFuzzer stores the input as a "timeout-<sha1>" artifact
+//!   and exits.
+//! * On exceeding the memory limit, the handler blocks the fuzzing
+//!   thread while the watchdog stores the input as an "oom-<sha1>"
+//!   artifact and exits.
+
+use std::backtrace::Backtrace;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// The exit code of libFuzzer for inputs exceeding the memory limit.
+const OOM_EXIT_CODE: i32 = 71;
+
+/// How long the watchdog waits for the signal handler to print the
+/// stack trace, in case the fuzzing thread was interrupted while holding
+/// a lock the handler needs.
+const STACK_TRACE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the RSS is checked.
+const RSS_CHECK_INTERVAL: Duration = Duration::from_millis(20);
+
+/// The limits of a fuzz test execution, as passed to libFuzzer.
+#[derive(Debug)]
+pub(crate) struct Limits {
+    /// The maximum time the fuzz test may take for a single input
+    pub(crate) timeout: Option<Duration>,
+    /// The maximum RSS of the process in MB
+    pub(crate) rss_limit_mb: Option<u64>,
+    /// The prefix of the paths of the artifacts
+    pub(crate) artifact_prefix: String,
+}
+
+/// Why the watchdog interrupted the fuzzing thread.
+#[derive(Clone, Copy, PartialEq)]
+#[repr(u8)]
+enum Reason {
+    Timeout = 1,
+    OutOfMemory = 2,
+}
+
+/// The time at which the fuzz test started executing the current input,
+/// in milliseconds since `EPOCH`, or 0 if it's not executing an input.
+static INPUT_START: AtomicU64 = AtomicU64::new(0);
+/// The current input, which is valid while `INPUT_START` isn't 0.
+static INPUT_DATA: AtomicPtr<u8> = AtomicPtr::new(std::ptr::null_mut());
+static INPUT_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+static FUZZING_THREAD: OnceLock<libc::pthread_t> = OnceLock::new();
+static LIMITS: OnceLock<Limits> = OnceLock::new();
+static REASON: AtomicU8 = AtomicU8::new(0);
+static STACK_TRACE_PRINTED: AtomicBool = AtomicBool::new(false);
+
+/// Starts the watchdog thread, which reports an input if the fuzz test
+/// exceeds the limits on it. Must be called on the thread which executes
+/// the fuzz test.
+pub(crate) fn start(limits: Limits) {
+    if limits.timeout.is_none() && limits.rss_limit_mb.is_none() {
+        return;
+    }
+    if LIMITS.set(limits).is_err() {
+        return;
+    }
+    EPOCH.get_or_init(Instant::now);
+    FUZZING_THREAD.get_or_init(|| unsafe { libc::pthread_self() });
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = print_stack_trace as extern "C" fn(libc::c_int) as usize;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGUSR2, &action, std::ptr::null_mut());
+    }
+
+    let spawned = std::thread::Builder::new()
+        .name("cifuzz-watchdog".to_string())
+        .spawn(watch);
+    if let Err(err) = spawned {
+        eprintln!("failed to start the cifuzz watchdog: {err}");
+    }
+}
+
+/// Must be called before the fuzz test executes an input.
+pub(crate) fn input_started(data: &[u8]) {
+    if let Some(epoch) = EPOCH.get() {
+        INPUT_DATA.store(data.as_ptr() as *mut u8, Ordering::Relaxed);
+        INPUT_SIZE.store(data.len(), Ordering::Relaxed);
+        // 0 means that no input is executed
+        let now = epoch.elapsed().as_millis().max(1) as u64;
+        INPUT_START.store(now, Ordering::Release);
+    }
+}
+
+/// Must be called after the fuzz test executed an input.
+pub(crate) fn input_finished() {
+    INPUT_START.store(0, Ordering::Release);
+}
+
+fn watch() {
+    let limits = LIMITS.get().unwrap();
+    let epoch = *EPOCH.get().unwrap();
+    let interval = match limits.timeout {
+        Some(timeout) if limits.rss_limit_mb.is_none() => {
+            (timeout / 10).clamp(Duration::from_millis(10), Duration::from_millis(500))
+        }
+        _ => RSS_CHECK_INTERVAL,
+    };
+    // The start of the input which was interrupted because of a timeout.
+    // If the fuzz test finished it right before the signal arrived,
+    // libFuzzer ignores the alarm and the fuzzer continues.
+    let mut timed_out = 0;
+    loop {
+        std::thread::sleep(interval);
+        let start = INPUT_START.load(Ordering::Acquire);
+        if start == 0 {
+            continue;
+        }
+        let running =
+            Duration::from_millis((epoch.elapsed().as_millis() as u64).saturating_sub(start));
+        if limits.timeout.is_some_and(|timeout| running >= timeout) && start != timed_out {
+            timed_out = start;
+            interrupt(Reason::Timeout);
+        }
+        if let Some(limit) = limits.rss_limit_mb {
+            let rss = rss_mb();
+            if rss > limit {
+                interrupt(Reason::OutOfMemory);
+                report_out_of_memory(rss, limit, &limits.artifact_prefix);
+            }
+        }
+    }
+}
+
+fn interrupt(reason: Reason) {
+    REASON.store(reason as u8, Ordering::SeqCst);
+    unsafe { libc::pthread_kill(*FUZZING_THREAD.get().unwrap(), libc::SIGUSR2) };
+}
+
+/// Stores the current input as an "oom-<sha1>" artifact, like libFuzzer
+/// does, and exits.
+fn report_out_of_memory(rss: u64, limit: u64, artifact_prefix: &str) -> ! {
+    let start = Instant::now();
+    while !STACK_TRACE_PRINTED.load(Ordering::SeqCst) && start.elapsed() < STACK_TRACE_TIMEOUT {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    // The fuzzing thread is blocked in the signal handler, so the input
+    // is still valid
+    let data = unsafe {
+        std::slice::from_raw_parts(
+            INPUT_DATA.load(Ordering::Relaxed),
+            INPUT_SIZE.load(Ordering::Relaxed),
+        )
+    };
+    let path = PathBuf::from(format!(
+        "{artifact_prefix}oom-{}",
+        sha1_smol::Sha1::from(data).digest()
+    ));
+    let mut stderr = std::io::stderr().lock();
+    let _ = writeln!(
+        stderr,
+        "=={}== ERROR: libFuzzer: out-of-memory (used: {rss}Mb; exceeds: {limit}Mb)",
+        std::process::id()
+    );
+    match std::fs::write(&path, data) {
+        Ok(()) => {
+            let _ = writeln!(stderr, "Test unit written to {}", path.display());
+        }
+        Err(err) => {
+            let _ = writeln!(stderr, "failed to write {}: {err}", path.display());
+        }
+    }
+    let _ = writeln!(stderr, "SUMMARY: libFuzzer: out-of-memory");
+    unsafe { libc::_exit(OOM_EXIT_CODE) }
+}
+
+/// Returns the resident set size of the process in MB.
+fn rss_mb() -> u64 {
+    // The current RSS instead of the peak, so that memory used by an
+    // earlier input isn't attributed to the current one
+    #[cfg(target_os = "linux")]
+    if let Ok(statm) = std::fs::read_to_string("/proc/self/statm") {
+        if let Some(pages) = statm
+            .split_whitespace()
+            .nth(1)
+            .and_then(|p| p.parse::<u64>().ok())
+        {
+            let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+            return (pages * page_size) >> 20;
+        }
+    }
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) };
+    let max_rss = usage.ru_maxrss as u64;
+    // ru_maxrss is in bytes on macOS and in KB everywhere else
+    if cfg!(target_os = "macos") {
+        max_rss >> 20
+    } else {
+        max_rss >> 10
+    }
+}
+
+/// The handler of `SIGUSR2`, running on the interrupted fuzzing thread.
+///
+/// Capturing a backtrace isn't async-signal-safe, but the process exits
+/// right afterwards. If the fuzzing thread was interrupted while holding
+/// a lock of the allocator, the watchdog reports the input without the
+/// stack trace after [`STACK_TRACE_TIMEOUT`].
+extern "C" fn print_stack_trace(_signal: libc::c_int) {
+    let Some(limits) = LIMITS.get() else {
+        return;
+    };
+    // The output of the input precedes the report, which must not be
+    // captured
+    crate::capture::finish();
+    let thread = std::thread::current();
+    let name = thread.name().unwrap_or("<unnamed>");
+    let reason = REASON.load(Ordering::SeqCst);
+    let mut stderr = std::io::stderr().lock();
+    let _ = if reason == Reason::OutOfMemory as u8 {
+        writeln!(
+            stderr,
+            "thread '{name}' exceeded the memory limit of {} MB",
+            limits.rss_limit_mb.unwrap_or_default()
+        )
+    } else {
+        writeln!(
+            stderr,
+            "thread '{name}' timed out after {} seconds",
+            limits.timeout.unwrap_or_default().as_secs()
+        )
+    };
+    let _ = writeln!(stderr, "stack backtrace:\n{}", Backtrace::force_capture());
+    drop(stderr);
+
+    if reason == Reason::OutOfMemory as u8 {
+        // Keep the input alive and stop allocating until the watchdog
+        // stored it and exited the process
+        STACK_TRACE_PRINTED.store(true, Ordering::SeqCst);
+        loop {
+            std::thread::sleep(Duration::from_secs(1));
+        }
+    }
+    // libFuzzer's alarm handler stores the input and exits
+    unsafe { libc::pthread_kill(libc::pthread_self(), libc::SIGALRM) };
+}
@@ -0,0 +1,29 @@
+// just a function with multiple paths that can be discovered by a fuzzer.
+// The deepest path panics, which cifuzz reports as a finding.
+
+pub fn explore_me(a: i64, b: i64, c: &str) {
+    println!("a: {}; b: {}; c: {}", a, b, c);
+
+    if a >= 20000 {
+        println!("branch 1 has been reached");
+        if b >= 2000000 {
+            println!("branch 2 has been reached");
+            if b - a > 100000 {
+                println!("branch 3 has been reached");
+                if c == "FUZZING" {
+                    panic!("branch 4 has been reached");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explore_me() {
+        explore_me(397652, 3082562284, "FUZZ");
+    }
+}
@@ -0,0 +1,31 @@
+mod explore_me;
+#[cfg(test)]
+mod my_fuzz_test;
+
+use explore_me::explore_me;
+
+/// Calls explore_me with the arguments A B C given on the command line,
+/// or with a few fixed values if there are none.
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [] => {
+            explore_me(1, 1, "A");
+            explore_me(2147483647, 1, "A");
+            explore_me(2147483647, 2147483647, "A");
+            explore_me(2000000000, 2000000123, "A");
+            explore_me(2000000000, 2000000123, "FUZZ");
+        }
+        [a, b, c] => match (a.parse(), b.parse()) {
+            (Ok(a), Ok(b)) => explore_me(a, b, c),
+            _ => {
+                eprintln!("A and B must be integers");
+                std::process::exit(2);
+            }
+        },
+        _ => {
+            eprintln!("Usage: cargo-example [A B C]");
+            std::process::exit(2);
+        }
+    }
+}
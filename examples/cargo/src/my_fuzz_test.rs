@@ -0,0 +1,21 @@
+use cifuzz::{dictionary, fuzz_test};
+
+use crate::explore_me::explore_me;
+
+// The two integers and the string explore_me expects are decoded from
+// the input data in the order of the parameters
+#[fuzz_test]
+fn my_fuzz_test(a: i64, b: i64, c: String) {
+    // The fuzzer inserts the tokens of the dictionary into its inputs,
+    // which makes it a lot faster to find the string explore_me expects
+    dictionary!["FUZZING"];
+
+    explore_me(a, b, &c);
+}
+
+// Runs the fuzz test with the values of the unit test of explore_me.
+// 'cargo cifuzz corpus record' stores its input in the seed corpus.
+#[test]
+fn seed_from_unit_test() {
+    cifuzz::seed!(my_fuzz_test, 397652_i64, 3082562284_i64, "FUZZ");
+}